@@ -0,0 +1,13 @@
+#![no_main]
+use check_for_license::util;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let url = String::from_utf8_lossy(data);
+    if let Some(repo) = util::extract_gh_info(&url) {
+        assert!(!repo.owner.is_empty() && !repo.name.is_empty());
+        assert!(!repo.owner.contains(['/', '?', '#']));
+        assert!(!repo.name.contains(['/', '?', '#']));
+        let _ = repo.canonical_key();
+    }
+});
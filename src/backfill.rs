@@ -0,0 +1,166 @@
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+use std::{fmt, time::Duration};
+
+use crate::subreddit::Subreddit;
+
+const USAGE: &str =
+    "Usage: check_for_license backfill --subreddit <name> --since <duration> [--dry-run]";
+/// Reddit lists at most 1000 posts, at 100 a page.
+pub const MAX_PAGES: usize = 10;
+
+/// What to backfill, from the command line.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Options {
+    pub subreddit: Subreddit,
+    /// How far back to look.
+    pub since: Duration,
+    /// Check posts without queueing replies or saving anything.
+    pub dry_run: bool,
+}
+
+impl Options {
+    /// Parse the arguments of a `backfill` subcommand.
+    pub fn parse(args: &[String]) -> Result<Self> {
+        let mut subreddit = None;
+        let mut since = None;
+        let mut dry_run = false;
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--subreddit" => {
+                    let name = args.next().ok_or_else(|| anyhow!(USAGE))?;
+                    subreddit = Some(
+                        Subreddit::new(name)
+                            .map_err(|e| anyhow!("--subreddit {}. {}", e, e.suggestion()))?,
+                    );
+                }
+                "--since" => {
+                    let value = args.next().ok_or_else(|| anyhow!(USAGE))?;
+                    since = Some(humantime::parse_duration(value).map_err(|e| {
+                        anyhow!("--since takes a duration like `7d`, not '{}': {}", value, e)
+                    })?);
+                }
+                "--dry-run" => dry_run = true,
+                _ => return Err(anyhow!(USAGE)),
+            }
+        }
+        match (subreddit, since) {
+            (Some(subreddit), Some(since)) => Ok(Options {
+                subreddit,
+                since,
+                dry_run,
+            }),
+            _ => Err(anyhow!(USAGE)),
+        }
+    }
+}
+
+/// How far a backfill has got.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Progress {
+    pub pages: usize,
+    /// Posts made since the cutoff.
+    pub posts: usize,
+    /// Posts linking at least one GitHub repository.
+    pub candidates: u64,
+}
+
+impl fmt::Display for Progress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} pages fetched, {} posts checked, {} candidates found",
+            self.pages, self.posts, self.candidates
+        )
+    }
+}
+
+/// The posts on a page, newest first, that were made at or after `cutoff`,
+/// and whether the page reached back past it.
+///
+/// Posts without a `created_utc` are kept.
+pub fn before_cutoff(postings: &[Value], cutoff: u64) -> (Vec<Value>, bool) {
+    let mut kept = vec![];
+    for post in postings {
+        match post["data"]["created_utc"].as_f64() {
+            Some(created) if (created as u64) < cutoff => return (kept, true),
+            _ => kept.push(post.clone()),
+        }
+    }
+    (kept, false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{before_cutoff, Options};
+    use crate::subreddit::Subreddit;
+    use serde_json::{json, Value};
+    use std::time::Duration;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn parses_options() {
+        assert_eq!(
+            Options::parse(&args(&["--subreddit", "/r/rust", "--since", "7d"])).unwrap(),
+            Options {
+                subreddit: Subreddit::new("rust").unwrap(),
+                since: Duration::from_secs(7 * 24 * 60 * 60),
+                dry_run: false,
+            }
+        );
+        assert!(
+            Options::parse(&args(&[
+                "--dry-run",
+                "--since",
+                "12h",
+                "--subreddit",
+                "rust"
+            ]))
+            .unwrap()
+            .dry_run
+        );
+        for bad in &[
+            &["--subreddit", "rust"][..],
+            &["--since", "7d"],
+            &["--subreddit", "rust", "--since", "a week"],
+            &["--subreddit", "rust", "--since"],
+            &["--subreddit", "rust", "--since", "7d", "--nope"],
+            &["--subreddit", "https://reddit.com/r/rust", "--since", "7d"],
+        ] {
+            assert!(Options::parse(&args(bad)).is_err(), "{:?}", bad);
+        }
+    }
+
+    fn page(created: &[u64]) -> Vec<Value> {
+        created
+            .iter()
+            .enumerate()
+            .map(
+                |(i, at)| json!({"data": {"name": format!("t3_{}", i), "created_utc": *at as f64}}),
+            )
+            .collect()
+    }
+
+    #[test]
+    fn stops_at_the_cutoff() {
+        let (kept, reached) = before_cutoff(&page(&[300, 200, 100]), 50);
+        assert_eq!(kept.len(), 3);
+        assert!(!reached);
+
+        let (kept, reached) = before_cutoff(&page(&[300, 200, 100]), 200);
+        assert_eq!(kept.len(), 2);
+        assert!(reached);
+
+        let (kept, reached) = before_cutoff(&page(&[100]), 200);
+        assert!(kept.is_empty());
+        assert!(reached);
+
+        let (kept, reached) = before_cutoff(&[json!({"data": {"name": "t3_x"}})], 200);
+        assert_eq!(kept.len(), 1);
+        assert!(!reached);
+    }
+}
@@ -1,257 +1,8012 @@
-use anyhow::{anyhow, Result};
-use log::{debug, error};
-use reqwest::{header, Client, ClientBuilder};
+use anyhow::{anyhow, Context, Result};
+use futures::future::{BoxFuture, FutureExt, LocalBoxFuture, Shared};
+use log::{debug, error, info, warn};
+use reqwest::Client;
+use serde::de::DeserializeOwned;
 use serde_json::Value;
-use std::{collections::HashMap, fs, time};
-use tokio::time::delay_for;
+use std::{
+    any::Any,
+    collections::{HashMap, HashSet},
+    mem,
+    panic::AssertUnwindSafe,
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex, RwLock,
+    },
+    time,
+};
+use tokio::{
+    runtime::{Handle, RuntimeFlavor},
+    sync::{
+        mpsc::{self, error::TrySendError, Receiver, Sender},
+        watch,
+    },
+    task,
+};
 
-use crate::models::{AccessTokenResponse, Config};
-use crate::util::extract_gh_info;
+use crate::audit::AuditLog;
+use crate::backfill::{self, Progress};
+use crate::claims;
+use crate::clock::{Clock, SystemClock};
+use crate::compaction::{self, map_size, vec_size, worth_shrinking};
+use crate::digest::{self, Row};
+use crate::errors::{self, BotError, DEFAULT_RETRY_AFTER};
+use crate::events::{self, Event, SharedEvents};
+use crate::findings;
+use crate::github::{self, Access, Checked, GitHub};
+use crate::github_app::AppAuth;
+use crate::issues::IssueMode;
+use crate::license::{self, DetectionStep, License, LicenseInfo, LicensePolicy};
+use crate::listing::{Cursor, Listing, Page, Unavailable, PUBLIC_POLL_INTERVAL};
+use crate::models::{Comment, Config, Post, RedditAccess};
+use crate::notify;
+use crate::pending::{PendingReply, PostDetails, RepoLink};
+use crate::pipeline::{self, Batch, Stop};
+use crate::preview::RepoSummary;
+use crate::processed::Processed;
+use crate::queue::ApprovalMode;
+use crate::reddit::{self, Reddit};
+use crate::redis_store::RedisStore;
+use crate::reload;
+use crate::removal::{is_removed, RemovalCheck, Removals};
+use crate::report::{ErrorContext, Level, Reporter};
+use crate::resolved::{self, AckMode};
+use crate::retention::{self, Retention};
+use crate::rules::Verdict;
+use crate::sanitize;
+use crate::scheduler::Scheduler;
+use crate::simulate::{self, Decision, Simulator};
+use crate::skip::SkipReason;
+use crate::state::{DirStore, ForeignState, MemoryStore, Owner, Ownership, StateFile, Store};
+use crate::stats::SubredditStats;
+use crate::subreddit::Subreddit;
+use crate::supervisor::Supervised;
+use crate::templates;
+use crate::transport::Transport;
+use crate::util::{extract_gh_ref, fold_case, GhRepo};
+use crate::visibility::{thread_shows_comment, Visibility};
 
-const BASE_URL: &str = "https://www.reddit.com";
-const OAUTH_URL: &str = "https://oauth.reddit.com";
-const RESPONSE_TEXT: &str = r#"The linked GitHub repository does not contain a license.
+mod compose;
+mod drain;
+mod gating;
 
-Please read over this article for more information: https://help.github.com/en/github/creating-cloning-and-archiving-repositories/licensing-a-repository"#;
-const EMPTY_SUBREDDIT_DELAY: u64 = 15;
+/// How long a subreddit's rules are trusted before being checked again.
+const RULES_CACHE_TTL: time::Duration = time::Duration::from_secs(7 * 24 * 60 * 60);
+/// How often a summary of the bot's activity is logged.
+const SUMMARY_INTERVAL: time::Duration = time::Duration::from_secs(60 * 60);
+/// How often state kept only in memory, such as while Redis can't be
+/// reached, is saved again.
+const FLUSH_INTERVAL: time::Duration = time::Duration::from_secs(60);
+/// How often claims on posts whose replies were never confirmed are
+/// looked at.
+const RECONCILE_INTERVAL: time::Duration = time::Duration::from_secs(60);
+/// How often old findings and replies are aged out of the state directory.
+const RETENTION_INTERVAL: time::Duration = time::Duration::from_secs(24 * 60 * 60);
+/// Seconds to wait before checking a repository once more when GitHub's
+/// answers don't say whether it has a license, such as an empty one.
+const INDETERMINATE_RETRY: u64 = 3 * 60 * 60;
+/// Seconds in a day, which follow-ups on works in progress are set in.
+const DAY: u64 = 24 * 60 * 60;
+
+/// Base URLs of the APIs that the bot talks to.
+#[derive(Clone, Debug)]
+pub struct Endpoints {
+    pub reddit: String,
+    pub reddit_oauth: String,
+    pub github: String,
+}
+
+impl Default for Endpoints {
+    fn default() -> Self {
+        Self {
+            reddit: reddit::BASE_URL.to_owned(),
+            reddit_oauth: reddit::OAUTH_URL.to_owned(),
+            github: github::API_URL.to_owned(),
+        }
+    }
+}
+
+/// A license check that may be awaited by several tasks at once.
+type SharedCheck = Shared<BoxFuture<'static, Result<Checked, Arc<BotError>>>>;
 
 /// Struct that encapsulates all API-interaction logic.
+///
+/// Everything that tasks watching different listings need to agree on is in
+/// its `BotCore`, which `Bot::task` shares with another `Bot`; the rest, like
+/// the posts looked at and replies held back, is the task's own.
 #[derive(Debug)]
 pub struct Bot {
-    config: Config,
-    reddit_client: Client,
-    github_client: Client,
-    access_token: Option<String>,
-    processed: Vec<String>,
+    core: Arc<BotCore>,
+    /// Posts of the watched listing that have been looked at. No other task
+    /// watches the listing, so they're kept here between saves of its state;
+    /// each save merges them with those in the store, which is how the posts
+    /// other instances looked at get here.
+    processed: Processed,
+    /// Store key and state of the listing being watched, as of the last pass
+    /// that was finished.
+    checkpoint: Option<(String, StateFile)>,
+    last_visibility_check: Option<time::Instant>,
+    subreddit_verdicts: HashMap<String, (Verdict, time::Instant)>,
+    /// Replies to look at again, to see whether they were removed.
+    removal_checks: Vec<RemovalCheck>,
+    removals: Removals,
+    config_updates: Option<watch::Receiver<Config>>,
+    /// Replies held back from the watched listing, saved with its state. An
+    /// instance sharing the store holds back its own copy of a reply, as it
+    /// looks at the same posts; whichever makes it first claims the post,
+    /// and the others drop theirs on finding it claimed.
+    pending: Vec<PendingReply>,
+    /// The post being handled, to give context to a panic.
+    current_post: Option<String>,
+    reporter: Option<Reporter>,
+    /// Check posts without queueing replies or suppressing anything.
+    dry_run: bool,
+    /// Queue every reply for approval and open no issues, whatever the
+    /// config says, as a backfill does.
+    queue_everything: bool,
+    /// Take over the state of listings stamped as another account's, or
+    /// another listing's.
+    adopt_state: bool,
+    /// Listings that Reddit won't show, by store key, so they're only
+    /// warned about once.
+    unavailable: HashMap<String, Unavailable>,
+    /// Listings that the bot has tried opting in to as quarantined.
+    quarantine_optins: HashSet<String>,
+    compaction: compaction::Schedule,
+    /// Panic when handling this post, to test recovering from panics.
+    #[cfg(test)]
+    panic_on: Option<String>,
+}
+
+/// What the bot's tasks share: clients, config, login, state store, events
+/// and the hourly reply count.
+///
+/// Locks are only held while what they guard is read or changed, never
+/// across a request, so tasks can't deadlock on each other.
+#[derive(Debug)]
+pub struct BotCore {
+    config: RwLock<Arc<Config>>,
+    reddit: Reddit,
+    github: GitHub,
+    store: Mutex<Box<dyn Store>>,
+    clock: Box<dyn Clock>,
+    events: SharedEvents,
+    in_flight: Mutex<HashMap<String, SharedCheck>>,
+    /// When each reply in the last hour was made.
+    replies: Mutex<Vec<time::Instant>>,
+    /// When each note of praise in the last day was left.
+    praised: Mutex<Vec<time::Instant>>,
+    /// Whether replies are paused because the bot isn't publicly visible.
+    paused: AtomicBool,
+    /// Whether Reddit has been found down, and not seen back up since.
+    maintenance: AtomicBool,
+    /// Unix timestamp of the last check for repositories that gained a
+    /// license after being replied about.
+    resolved_checked: AtomicU64,
+    stop: Stop,
+}
+
+impl BotCore {
+    /// The config as it is now; it isn't changed by later reloads.
+    pub fn config(&self) -> Arc<Config> {
+        self.config.read().unwrap().clone()
+    }
+
+    /// Switch every task to a new config.
+    pub fn set_config(&self, config: Config) {
+        *self.config.write().unwrap() = Arc::new(config);
+    }
+
+    pub fn events(&self) -> &SharedEvents {
+        &self.events
+    }
+
+    /// Run `f` on the state store.
+    ///
+    /// A store may wait on the network or the disk, Redis for up to its
+    /// timeout, so on a multi-threaded runtime the worker thread hands its
+    /// other tasks over to another before taking the lock around the store,
+    /// rather than holding them up until it's done.
+    fn with_store<T>(&self, f: impl FnOnce(&mut dyn Store) -> T) -> T {
+        let run = || f(&mut **self.store.lock().unwrap_or_else(|e| e.into_inner()));
+        match Handle::try_current().map(|h| h.runtime_flavor()) {
+            Ok(RuntimeFlavor::MultiThread) => task::block_in_place(run),
+            _ => run(),
+        }
+    }
+
+    /// The saved state of a listing.
+    pub fn load_state(&self, key: &str) -> Result<StateFile> {
+        self.with_store(|s| s.load(key))
+    }
+
+    /// Change the saved state of a listing, without losing changes other
+    /// tasks make to it at the same time.
+    pub fn update_state(
+        &self,
+        key: &str,
+        mut change: impl FnMut(&mut StateFile),
+    ) -> Result<StateFile> {
+        self.with_store(|s| s.update(key, &mut change))
+    }
+
+    /// Ask the watch loop to stop: no more posts are fetched, and those
+    /// already fetched are processed before it returns.
+    pub fn stop(&self) {
+        self.stop.request();
+    }
+
+    /// Whether replies are paused, because the bot isn't publicly visible
+    /// or its state store can't claim posts.
+    fn paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst) || self.with_store(|s| s.is_degraded())
+    }
+}
+
+/// Link posts in a listing that point to the same repository.
+#[derive(Debug)]
+struct RepoGroup {
+    repo: GhRepo,
+    url: String,
+    fullnames: Vec<String>,
+    /// SPDX id of the license the repository had when it was last checked,
+    /// if this check found none.
+    removed_license: Option<String>,
+    summary: RepoSummary,
+    /// How its license was looked for when it was checked.
+    detection_path: Vec<DetectionStep>,
+    /// Unix timestamp of when the newest post linking it was made, or 0 if
+    /// that isn't known.
+    posted_at: u64,
 }
 
-/// Build a `reqwest::Client`.
-fn build_client(config: &Config, access_token: Option<String>) -> Result<Client> {
-    let mut builder = ClientBuilder::new()
-        .user_agent(&config.user_agent)
-        .timeout(time::Duration::from_secs(60));
-    if let Some(t) = access_token {
-        let mut headers = header::HeaderMap::new();
-        headers.insert(
-            header::AUTHORIZATION,
-            header::HeaderValue::from_str(&format!("bearer {}", t))?,
+impl RepoGroup {
+    fn link(&self) -> RepoLink {
+        RepoLink {
+            repo: self.repo.clone(),
+            url: sanitize::url(&self.url).unwrap_or_else(|| self.repo.html_url()),
+            removed_license: self.removed_license.clone(),
+            summary: self.summary.clone(),
+        }
+    }
+}
+
+/// Builds a `Bot`, with any of its clients, endpoints, state store or clock
+/// replaced. Whatever isn't given is set up the way `Bot::new` does it.
+#[derive(Debug, Default)]
+pub struct BotBuilder {
+    config: Option<Config>,
+    reddit_client: Option<Client>,
+    github_client: Option<Client>,
+    endpoints: Option<Endpoints>,
+    store: Option<Box<dyn Store>>,
+    clock: Option<Box<dyn Clock>>,
+}
+
+impl BotBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn config(mut self, config: Config) -> Self {
+        self.config = Some(config);
+        self
+    }
+
+    /// Client for Reddit's API; the access token is added to each request.
+    pub fn reddit_client(mut self, client: Client) -> Self {
+        self.reddit_client = Some(client);
+        self
+    }
+
+    pub fn github_client(mut self, client: Client) -> Self {
+        self.github_client = Some(client);
+        self
+    }
+
+    pub fn endpoints(mut self, endpoints: Endpoints) -> Self {
+        self.endpoints = Some(endpoints);
+        self
+    }
+
+    /// Where the state of watched listings is kept, instead of the state directory.
+    pub fn store(mut self, store: Box<dyn Store>) -> Self {
+        self.store = Some(store);
+        self
+    }
+
+    pub fn clock(mut self, clock: Box<dyn Clock>) -> Self {
+        self.clock = Some(clock);
+        self
+    }
+
+    pub fn build(self) -> Result<Bot> {
+        let config = self
+            .config
+            .ok_or_else(|| anyhow!("A bot needs a config to be built"))?;
+        let reddit_client = match self.reddit_client {
+            Some(client) => client,
+            None => reddit::build_client(&config)?,
+        };
+        let github_client = match self.github_client {
+            Some(client) => client,
+            None => github::build_client(&config)?,
+        };
+        let github_app = config.github_app.as_ref().map(AppAuth::load).transpose()?;
+        let mut transport = Transport::default();
+        if let Some(path) = &config.http_audit_path {
+            info!("Writing HTTP requests to {}", path.display());
+            let secrets = vec![config.password.clone(), config.client_secret.clone()]
+                .into_iter()
+                .chain(config.github_token.clone())
+                .collect();
+            transport = transport.audited(Arc::new(AuditLog::new(path.clone(), secrets)));
+        }
+        let endpoints = self.endpoints.unwrap_or_default();
+        let events = SharedEvents::default();
+        let mut reddit = Reddit::new(
+            reddit_client,
+            endpoints.reddit,
+            endpoints.reddit_oauth,
+            transport.clone(),
         );
-        builder = builder.default_headers(headers);
+        if config.reddit_access() == RedditAccess::Public {
+            reddit = reddit.public_only();
+        }
+        let core = BotCore {
+            reddit,
+            github: GitHub::new(
+                github_client,
+                endpoints.github,
+                github_app,
+                transport,
+                events.clone(),
+            ),
+            store: Mutex::new(match (self.store, &config.redis_url) {
+                (Some(store), _) => store,
+                (None, Some(url)) => {
+                    info!("Keeping state in Redis");
+                    Box::new(RedisStore::open(url, config.processed_retention)?)
+                }
+                (None, None) => Box::new(DirStore::new(config.state_dir.clone())),
+            }),
+            clock: self.clock.unwrap_or_else(|| Box::new(SystemClock)),
+            events,
+            in_flight: Mutex::default(),
+            replies: Mutex::default(),
+            praised: Mutex::default(),
+            paused: AtomicBool::new(false),
+            maintenance: AtomicBool::new(false),
+            resolved_checked: AtomicU64::new(0),
+            stop: Stop::default(),
+            config: RwLock::new(Arc::new(config)),
+        };
+        Ok(Bot::from_core(Arc::new(core)))
+    }
+}
+
+impl Bot {
+    /// A task using a shared core, with nothing looked at or held back yet.
+    pub fn from_core(core: Arc<BotCore>) -> Bot {
+        Bot {
+            core,
+            processed: Processed::default(),
+            checkpoint: None,
+            last_visibility_check: None,
+            subreddit_verdicts: HashMap::new(),
+            removal_checks: vec![],
+            removals: Removals::default(),
+            config_updates: None,
+            pending: vec![],
+            current_post: None,
+            reporter: None,
+            dry_run: false,
+            queue_everything: false,
+            adopt_state: false,
+            unavailable: HashMap::new(),
+            quarantine_optins: HashSet::new(),
+            compaction: compaction::Schedule::default(),
+            #[cfg(test)]
+            panic_on: None,
+        }
+    }
+}
+
+/// The message a panic was raised with, if it had one.
+pub(crate) fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message
+    } else {
+        "no message"
+    }
+}
+
+/// An error for a panic while handling a post, if it's known which.
+fn panicked(post: Option<&str>, payload: &(dyn Any + Send)) -> anyhow::Error {
+    match post {
+        Some(post) => anyhow!(
+            "panicked while handling post {}: {}",
+            post,
+            panic_message(payload)
+        ),
+        None => anyhow!("panicked: {}", panic_message(payload)),
     }
-    Ok(builder.build()?)
 }
 
 impl Bot {
     /// Create a new bot from a `Config`.
     pub fn new(config: Config) -> Result<Self> {
-        Ok(Self {
-            config: config.clone(),
-            reddit_client: build_client(&config, None)?,
-            github_client: ClientBuilder::new()
-                .timeout(time::Duration::from_secs(15))
-                .user_agent(format!("User {}", config.github_username))
-                .build()?,
-            access_token: None,
-            processed: vec![],
-        })
+        BotBuilder::new().config(config).build()
+    }
+
+    /// Create a new bot from a `Config`, talking to the given API endpoints.
+    pub fn with_endpoints(config: Config, endpoints: Endpoints) -> Result<Self> {
+        BotBuilder::new()
+            .config(config)
+            .endpoints(endpoints)
+            .build()
+    }
+
+    /// Report errors in the watch loop to Sentry.
+    pub fn with_reporter(mut self, reporter: Reporter) -> Self {
+        self.reporter = Some(reporter);
+        self
+    }
+
+    /// Take over the state of listings stamped as belonging to another
+    /// account or listing, rather than refusing to use it.
+    pub fn adopting_state(mut self) -> Self {
+        self.adopt_state = true;
+        self
+    }
+
+    /// Carry out the bot's HTTP requests with the given transport, still
+    /// writing them to the audit log if there is one.
+    #[cfg(any(test, feature = "cassettes"))]
+    pub fn with_transport(mut self, transport: Transport) -> Self {
+        let core = self.core_mut();
+        let transport = match core.reddit.transport.audit() {
+            Some(audit) => transport.audited(audit.clone()),
+            None => transport,
+        };
+        core.github.transport = transport.clone();
+        core.reddit.transport = transport;
+        self
+    }
+
+    /// Record findings and replies to the given event log.
+    pub fn with_events(mut self, events: SharedEvents) -> Self {
+        let core = self.core_mut();
+        core.github.events = events.clone();
+        core.events = events;
+        self
+    }
+
+    /// The core, for setting up a bot before it's shared with other tasks.
+    fn core_mut(&mut self) -> &mut BotCore {
+        Arc::get_mut(&mut self.core).expect("a bot is set up before its core is shared")
+    }
+
+    /// What this bot shares with its other tasks.
+    pub fn core(&self) -> &Arc<BotCore> {
+        &self.core
+    }
+
+    /// Another task sharing this bot's core, to watch something else at the
+    /// same time. It reports errors and applies config updates the same way.
+    pub fn task(&self) -> Bot {
+        Bot {
+            reporter: self.reporter.clone(),
+            config_updates: self.config_updates.clone(),
+            ..Bot::from_core(self.core.clone())
+        }
+    }
+
+    /// The config as it is now.
+    fn config(&self) -> Arc<Config> {
+        self.core.config()
+    }
+
+    fn approval_mode(&self) -> ApprovalMode {
+        if self.queue_everything {
+            ApprovalMode::Manual
+        } else {
+            self.config().approval_mode
+        }
+    }
+
+    fn issue_mode(&self) -> IssueMode {
+        if self.queue_everything {
+            IssueMode::Off
+        } else {
+            self.config().github_issue_mode
+        }
+    }
+
+    /// Apply configs sent on the channel, such as on SIGHUP, as they arrive.
+    pub fn with_config_updates(mut self, updates: watch::Receiver<Config>) -> Self {
+        self.config_updates = Some(updates);
+        self
+    }
+
+    /// Switch to the newest config sent to the bot, if there's been one.
+    ///
+    /// Settings that need a restart are logged and left as they were.
+    fn apply_config_updates(&mut self) {
+        let new = match &mut self.config_updates {
+            Some(updates) if updates.has_changed().unwrap_or(false) => {
+                updates.borrow_and_update().clone()
+            }
+            _ => return,
+        };
+        let (merged, live, restart) = reload::apply(&self.config(), &new);
+        if !restart.is_empty() {
+            warn!(
+                "Changes to {} need a restart to take effect",
+                restart.join(", ")
+            );
+        }
+        if !live.is_empty() {
+            info!("Applied new configuration for {}", live.join(", "));
+            self.core.set_config(merged);
+        }
     }
 
     /// Logs the bot in.
     ///
     /// Must be called before making any authenticated calls.
-    pub async fn login(&mut self) -> Result<()> {
+    pub async fn login(&self) -> Result<(), BotError> {
+        if self.reads_publicly() {
+            warn!(
+                "No Reddit credentials are set: reading /r/{} from Reddit's public listings \
+                 every {}, with replies, messages and digests disabled; findings are only \
+                 reported",
+                self.config().subreddit,
+                humantime::format_duration(PUBLIC_POLL_INTERVAL)
+            );
+        }
         debug!("Performing bot login");
-        let form = {
-            let mut form = HashMap::new();
-            form.insert("grant_type", "password");
-            form.insert("username", &self.config.username);
-            form.insert("password", &self.config.password);
-            form
-        };
-        let resp = self
-            .reddit_client
-            .post(&format!("{}/api/v1/access_token", BASE_URL))
-            .basic_auth(&self.config.client_id, Some(&self.config.client_secret))
-            .form(&form)
-            .send()
-            .await?;
-        if !resp.status().is_success() {
-            return Err(anyhow!("Got status {} from login attempt", resp.status()));
+        self.core.reddit.login(&self.config()).await
+    }
+
+    /// Whether there's no account to log in as, so Reddit is only read
+    /// publicly and nothing is posted there.
+    fn reads_publicly(&self) -> bool {
+        self.core.reddit.is_public_only()
+    }
+
+    /// Log in, waiting for as long as Reddit is down rather than trying its
+    /// token endpoint over and over.
+    pub async fn login_when_available(&mut self) -> Result<(), BotError> {
+        loop {
+            match self.login().await {
+                Err(e @ BotError::ServiceUnavailable { .. }) => {
+                    self.wait_out_maintenance(&e.into()).await
+                }
+                result => return result,
+            }
         }
-        let data = resp.json::<AccessTokenResponse>().await?;
-        debug!("ATR from API: {:?}", data);
-        self.reddit_client = build_client(&self.config, Some(data.token))?;
+    }
 
-        Ok(())
+    /// Wait before trying Reddit again while it's down, warning about it and
+    /// reporting it the first time.
+    async fn wait_out_maintenance(&mut self, error: &anyhow::Error) {
+        let asked = match error.downcast_ref::<BotError>().map(BotError::kind) {
+            Some(BotError::ServiceUnavailable { retry_after, .. }) => *retry_after,
+            _ => None,
+        };
+        let backoff = time::Duration::from_secs(self.config().maintenance_backoff);
+        let (wait, why) = errors::retry_wait(asked, backoff);
+        if self.core.maintenance.swap(true, Ordering::SeqCst) {
+            debug!("Reddit is still unavailable, waiting {:?} {}", wait, why);
+        } else {
+            warn!(
+                "{:#}; trying again in {}, {}",
+                error,
+                humantime::format_duration(wait),
+                why
+            );
+            if let Some(reporter) = &mut self.reporter {
+                let context = ErrorContext::default();
+                if let Err(e) = reporter.capture(error, Level::Warning, &context).await {
+                    warn!("Could not report Reddit outage to Sentry: {:#}", e);
+                }
+            }
+        }
+        notify::sleep(wait).await;
+    }
+
+    /// What requests to GitHub go by, as of now.
+    fn github_access(&self) -> Access {
+        Access {
+            token: self.config().github_token.clone(),
+            floor: self.config().github_quota_floor,
+            now: self.core.clock.unix_now(),
+        }
+    }
+
+    /// How long it's been since a moment measured by the bot's clock.
+    fn since(&self, moment: time::Instant) -> time::Duration {
+        self.core.clock.now().saturating_duration_since(moment)
     }
 
-    /// Checks to see if a url matches a GH project without a license.
-    async fn check_post(&self, url: &str) -> Result<bool> {
-        let (org, repo) = match extract_gh_info(url) {
-            Some(pair) => pair,
-            None => return Err(anyhow!("Could not parse GitHub url at {}", url)),
+    /// OAuth scopes granted at the last login; `*` means all of them.
+    pub fn scopes(&self) -> Vec<String> {
+        self.core.reddit.scopes()
+    }
+
+    /// How many GitHub API requests are left, out of how many, until the limit resets.
+    pub async fn github_rate_limit(&self) -> Result<(u64, u64), BotError> {
+        let floor = self.config().github_quota_floor;
+        self.core.github.rate_limit(floor).await
+    }
+
+    /// Whether so few GitHub requests are left that checks should wait until
+    /// the rate limit resets.
+    fn github_quota_low(&self) -> bool {
+        let quota = self.core.events.lock().unwrap().github_quota();
+        quota
+            .is_some_and(|q| q.is_low(self.config().github_quota_floor, self.core.clock.unix_now()))
+    }
+
+    /// A line summing up the bot's recent activity and GitHub quota, followed
+    /// by one for each subreddit it has seen posts from.
+    fn summary(&self) -> String {
+        let events = self.core.events.lock().unwrap();
+        let replies = events.replies_since(self.core.clock.unix_now().saturating_sub(60 * 60));
+        let quota = match events.github_quota() {
+            Some(quota) => quota.to_string(),
+            None => "GitHub quota not seen yet".to_owned(),
         };
-        {
-            // check for valid project
-            debug!("Checking for valid GH project");
-            let url = format!("https://api.github.com/repos/{}/{}", org, repo);
-            debug!("Checking {}", url);
-            let resp = self.github_client.get(&url).send().await?;
-            if !resp.status().is_success() {
-                return Err(anyhow!(
-                    "Invalid GH project '{}/{}' (got status {})",
-                    org,
-                    repo,
-                    resp.status()
-                ));
-            } else {
-                debug!("Project has a license");
+        let mut summary = format!(
+            "{} replies in the last hour, {}, {} restarts; {}",
+            replies,
+            events.queues(),
+            events.restarts(),
+            quota
+        );
+        if events.compactions().runs > 0 {
+            summary.push_str("; ");
+            summary.push_str(&events.compactions().to_string());
+        }
+        if !events.latencies().is_empty() {
+            summary.push_str("; ");
+            summary.push_str(&events.latencies().to_string());
+        }
+        for line in events.stats().lines() {
+            summary.push_str("\n  ");
+            summary.push_str(&line);
+        }
+        for (name, status) in events.task_statuses() {
+            summary.push_str(&format!("\n  task {}: {}", name, status));
+        }
+        summary
+    }
+
+    /// The tasks run every so often while a listing is watched.
+    fn scheduler(&self) -> Scheduler<BotCore> {
+        let statuses = self.core.events.lock().unwrap().tasks();
+        Scheduler::new(self.core.clone(), statuses)
+            .every(
+                "flush",
+                FLUSH_INTERVAL,
+                time::Duration::from_secs(5),
+                |core| async move { core.with_store(|s| s.flush()) }.boxed(),
+            )
+            .every("summary", SUMMARY_INTERVAL, time::Duration::ZERO, |core| {
+                async move {
+                    info!("Summary: {}", Bot::from_core(core).summary());
+                    Ok(())
+                }
+                .boxed()
+            })
+            .every(
+                "reconcile claims",
+                RECONCILE_INTERVAL,
+                time::Duration::from_secs(10),
+                |core| {
+                    async move {
+                        Bot::from_core(core)
+                            .reconcile_claims(claims::CLAIM_TIMEOUT)
+                            .await;
+                        Ok(())
+                    }
+                    .boxed()
+                },
+            )
+            .every(
+                "age out records",
+                RETENTION_INTERVAL,
+                time::Duration::from_secs(10 * 60),
+                |core| async move { Bot::from_core(core).age_out_records() }.boxed(),
+            )
+    }
+
+    /// Checks whether a GH project has a license.
+    async fn check_post(&self, repo: &GhRepo) -> Result<License, BotError> {
+        self.check_at(repo, vec![])
+            .await
+            .map(|(license, ..)| license)
+    }
+
+    /// Checks whether a GH project linked with a URL has a license, at the
+    /// ref the URL points to as well as on its default branch, along with
+    /// what GitHub says about it otherwise.
+    async fn check_link(&self, repo: &GhRepo, url: &str) -> Result<Checked, BotError> {
+        let refs = extract_gh_ref(url)
+            .map(|link| link.candidates())
+            .unwrap_or_default();
+        self.check_at(repo, refs).await
+    }
+
+    /// Checks whether a GH project has a license, on its default branch or at
+    /// the first of `refs` that it has.
+    ///
+    /// Concurrent checks of the same repository share a single set of requests.
+    async fn check_at(&self, repo: &GhRepo, refs: Vec<String>) -> Result<Checked, BotError> {
+        if let Transport::Simulated(simulator) = &self.core.github.transport {
+            return Ok(match simulator.fixture(repo) {
+                Some(f) if f.licensed => (
+                    license::verdict(
+                        &LicenseInfo::from_spdx(f.license.clone()),
+                        &LicensePolicy::from_config(&self.config()),
+                    ),
+                    f.summary.clone(),
+                    vec![],
+                ),
+                Some(f) => (License::Missing, f.summary.clone(), vec![]),
+                None => (
+                    License::Indeterminate("no fixture".to_owned()),
+                    RepoSummary::default(),
+                    vec![],
+                ),
+            });
+        }
+        let key = match refs.first() {
+            Some(git_ref) => format!("{}@{}", repo.canonical_key(), git_ref),
+            None => repo.canonical_key(),
+        };
+        let check = {
+            let mut in_flight = self.core.in_flight.lock().unwrap();
+            in_flight
+                .entry(key.clone())
+                .or_insert_with(|| {
+                    let core = self.core.clone();
+                    let repo = repo.clone();
+                    let floor = self.config().github_quota_floor;
+                    let policy = LicensePolicy::from_config(&self.config());
+                    async move { core.github.check(&repo, &refs, floor, &policy).await }
+                        .map(|r| r.map_err(Arc::new))
+                        .boxed()
+                        .shared()
+                })
+                .clone()
+        };
+        let result = check.await;
+        self.core.in_flight.lock().unwrap().remove(&key);
+        result.map_err(BotError::from_shared)
+    }
+
+    /// Look at a comment again once the removal check delay is up, to see
+    /// whether it was removed.
+    fn check_for_removal(&mut self, comment: Option<&str>, post: &str, subreddit: &str) {
+        if let (Some(comment), delay) = (comment, self.config().removal_check_delay) {
+            if delay > 0 {
+                self.removal_checks.push(RemovalCheck {
+                    comment: comment.to_owned(),
+                    post: post.to_owned(),
+                    subreddit: subreddit.to_owned(),
+                    due: self.core.clock.unix_now() + delay,
+                });
             }
         }
-        {
-            // check for license
-            let resp = self
-                .github_client
-                .get(&format!(
-                    "https://api.github.com/repos/{}/{}/license",
-                    org, repo
-                ))
-                .send()
-                .await?;
-            if !resp.status().is_success() {
-                debug!(
-                    "Got status {} from GitHub API for testing {}/{}",
-                    resp.status(),
-                    org,
-                    repo
-                );
-                return Ok(true);
+    }
+
+    /// Archive or drop the findings older than the retention period, if
+    /// there is one, and forget the replies too old to hold back another.
+    ///
+    /// Replies are kept for the longer of the reply and the cross-subreddit
+    /// dedup windows, as both look back through them.
+    fn age_out_records(&self) -> Result<()> {
+        let config = self.config();
+        let now = self.core.clock.unix_now();
+        match &config.findings_path {
+            Some(log) if config.findings_retention_days > 0 => {
+                let cutoff = now.saturating_sub(config.findings_retention_days * 24 * 60 * 60);
+                for path in &[findings::rotated_path(log), log.clone()] {
+                    self.age_out_findings(path, log, cutoff, config.findings_retention)?;
+                }
             }
+            _ => {}
         }
-        Ok(false)
+        let horizon = config
+            .reply_dedup_ttl
+            .max(config.cross_sub_window_minutes * 60);
+        let pruned = self.core.with_store(|s| s.prune_replies(now, horizon))?;
+        if pruned > 0 {
+            info!(
+                "Forgot {} replies made over {} ago",
+                pruned,
+                humantime::format_duration(time::Duration::from_secs(horizon))
+            );
+        }
+        Ok(())
     }
 
-    /// Responds
-    async fn respond_to(&mut self, fullname: &str) -> Result<()> {
-        debug!("Responding to post {}", fullname);
-        let data = {
-            let mut map = HashMap::new();
-            map.insert("api_type", "json");
-            map.insert("thing_id", fullname);
-            map.insert("text", RESPONSE_TEXT);
-            map
-        };
-        let resp = self
-            .reddit_client
-            .post(&format!("{}/api/comment", OAUTH_URL))
-            .form(&data)
-            .send()
-            .await?;
-        if !resp.status().is_success() {
-            Err(anyhow!(
-                "Got status {} from responding to post",
-                resp.status()
-            ))
-        } else {
-            Ok(())
+    /// Compact one file of the findings log at `log`.
+    fn age_out_findings(
+        &self,
+        path: &Path,
+        log: &Path,
+        cutoff: u64,
+        retention: Retention,
+    ) -> Result<()> {
+        let compaction = match retention::Compaction::start(path, log, cutoff, retention)? {
+            Some(compaction) => compaction,
+            None => return Ok(()),
+        };
+        let (aged_out, archives) = (compaction.aged_out, compaction.archives.len());
+        // nothing's added to the findings log while it's replaced
+        let _events = self.core.events.lock().unwrap();
+        if compaction.finish()? && aged_out > 0 {
+            match archives {
+                0 => info!("Dropped {} findings from {}", aged_out, path.display()),
+                _ => info!(
+                    "Archived {} findings from {} into {} monthly archives",
+                    aged_out,
+                    path.display(),
+                    archives
+                ),
+            }
         }
+        Ok(())
     }
 
-    async fn delay(&self, subreddit: &str) {
-        debug!(
-            "No new posts in /r/{}, waiting {} seconds for checking again",
-            subreddit, EMPTY_SUBREDDIT_DELAY
-        );
-        delay_for(time::Duration::from_secs(EMPTY_SUBREDDIT_DELAY)).await;
+    /// Settle the claims on posts made at least `age` seconds ago that were
+    /// neither confirmed nor released, such as when the bot stopped between
+    /// claiming a post and replying to it.
+    ///
+    /// A claim is confirmed if one of the bot's recent comments is on the
+    /// post, and given up otherwise. Failures are logged, and the claims
+    /// settled on a later pass.
+    async fn reconcile_claims(&self, age: u64) {
+        let claims = match self.core.with_store(|s| s.reply_claims()) {
+            Ok(claims) => claims,
+            Err(e) => {
+                warn!("Could not load reply claims: {:#}", e);
+                return;
+            }
+        };
+        let unresolved = claims::unresolved(&claims, self.core.clock.unix_now(), age);
+        if unresolved.is_empty() {
+            return;
+        }
+        let comments = match self.fetch_own_comments(100).await {
+            Ok(comments) => comments,
+            Err(e) => {
+                warn!(
+                    "Could not load own comments to settle reply claims: {:#}",
+                    e
+                );
+                return;
+            }
+        };
+        for claim in unresolved {
+            let result = match comments.iter().find(|c| c.link_id == claim.fullname) {
+                Some(comment) => {
+                    info!("Found reply {} to {}", comment.name, claim.fullname);
+                    self.core
+                        .with_store(|s| s.confirm_reply(&claim.fullname, Some(&comment.name)))
+                }
+                None => {
+                    info!(
+                        "No reply to {} was made, it may be replied to again",
+                        claim.fullname
+                    );
+                    self.core.with_store(|s| s.release_reply(&claim.fullname))
+                }
+            };
+            if let Err(e) = result {
+                warn!("Could not settle the claim on {}: {:#}", claim.fullname, e);
+            }
+        }
     }
 
-    /// Single call to /r/{subreddit}/new and processing everything found.
-    async fn watch_subreddit_once(
-        &mut self,
-        subreddit: &str,
-        after: &Option<String>,
-    ) -> Result<Option<String>> {
-        debug!("Making request to see new from /r/{}", subreddit);
-        let query = match after {
-            Some(ref q) => vec![("raw_json", "1"), ("after", q)],
-            None => vec![("raw_json", "1")],
-        };
-        let resp = self
-            .reddit_client
-            .get(&format!("{}/r/{}/new", OAUTH_URL, subreddit))
-            .query(&query)
-            .send()
-            .await?;
-        if !resp.status().is_success() {
-            return Err(anyhow!(
-                "Got status {} from listing endpoint",
-                resp.status()
-            ));
+    /// Look at the replies that are due to be looked at again, and deal with
+    /// those that were removed.
+    ///
+    /// Failures are logged, and the replies looked at again on the next pass.
+    async fn check_removals_if_due(&mut self) {
+        let now = self.core.clock.unix_now();
+        let (due, waiting): (Vec<_>, Vec<_>) =
+            self.removal_checks.drain(..).partition(|c| c.due <= now);
+        self.removal_checks = waiting;
+        if due.is_empty() {
+            return;
+        }
+        let fullnames: Vec<String> = due.iter().map(|c| c.comment.clone()).collect();
+        let comments: Vec<Value> = match self.fetch_info(&fullnames).await {
+            Ok(comments) => comments,
+            Err(e) => {
+                warn!("Could not check whether replies were removed: {:#}", e);
+                self.removal_checks.extend(due);
+                return;
+            }
+        };
+        for check in due {
+            let removed = comments
+                .iter()
+                .any(|c| c["name"].as_str() == Some(&check.comment) && is_removed(c));
+            if removed {
+                self.reply_removed(&check).await;
+            } else {
+                debug!("Reply {} is still up", check.comment);
+            }
+        }
+    }
+
+    /// Check again the repositories replied about recently, at most once every
+    /// `resolved::CHECK_INTERVAL` across all tasks, and acknowledge those that
+    /// have gained a license since.
+    ///
+    /// Failures are logged, and the repositories checked again next time.
+    async fn check_resolved_if_due(&self) {
+        let mode = self.config().ack_resolved;
+        if mode == AckMode::Off || self.dry_run || self.github_quota_low() {
+            return;
         }
-        let data: Value = resp.json().await?;
-        let postings = data["data"]["children"].as_array().unwrap();
-        if postings.is_empty() {
-            self.delay(subreddit).await;
-            return Ok(after.to_owned());
+        let now = self.core.clock.unix_now();
+        let last = self.core.resolved_checked.load(Ordering::SeqCst);
+        if now.saturating_sub(last) < resolved::CHECK_INTERVAL
+            || self
+                .core
+                .resolved_checked
+                .compare_exchange(last, now, Ordering::SeqCst, Ordering::SeqCst)
+                .is_err()
+        {
+            return;
         }
-        for post_wrapper in postings {
-            let post = &post_wrapper["data"];
-            let fullname = post["name"].as_str().unwrap().to_owned();
-            if self.processed.contains(&fullname) {
+        let records = match self.core.with_store(|s| s.replies()) {
+            Ok(records) => records,
+            Err(e) => {
+                warn!("Could not load replies to check again: {:#}", e);
+                return;
+            }
+        };
+        for (comment, repos) in resolved::due(&records, now) {
+            let mut licensed = vec![];
+            for repo in repos {
+                match self.check_post(&repo).await {
+                    Ok(License::Found(spdx_id)) => licensed.push((repo, spdx_id)),
+                    Ok(_) => {}
+                    Err(e) => debug!("Could not check {} again: {}", repo, e),
+                }
+            }
+            if licensed.is_empty() {
                 continue;
             }
-            self.processed.push(fullname.to_owned());
-            if post["domain"].as_str().unwrap().starts_with("self.") {
+            if let Err(e) = self.acknowledge(mode, &comment, &licensed).await {
+                warn!("Could not acknowledge new licenses in {}: {}", comment, e);
                 continue;
             }
-            let url = post["url"].as_str().unwrap();
-            debug!("Found link post to: {}", url);
-            if url.contains("github.com") && self.check_post(url).await? {
-                self.respond_to(&fullname).await?;
+            info!(
+                "Acknowledged {} new licenses in reply {}",
+                licensed.len(),
+                comment
+            );
+            for (repo, _) in &licensed {
+                if let Err(e) = self
+                    .core
+                    .with_store(|s| s.resolve_reply(&comment, &repo.to_string(), now))
+                {
+                    warn!("Could not record {} as resolved: {:#}", repo, e);
+                }
             }
         }
-        if let Some(new_after) = data["data"]["after"].as_str() {
-            debug!("After is now {}", new_after);
-            Ok(Some(new_after.to_owned()))
-        } else {
-            self.delay(subreddit).await;
-            Ok(after.to_owned())
+    }
+
+    /// Say that repositories replied about in one of the bot's comments now
+    /// have licenses, in a reply to it or in an edit of it.
+    async fn acknowledge(
+        &self,
+        mode: AckMode,
+        comment: &str,
+        licensed: &[(GhRepo, Option<String>)],
+    ) -> Result<(), BotError> {
+        let note = resolved::text(licensed);
+        let action = "acknowledging a new license";
+        match mode {
+            AckMode::Off => Ok(()),
+            AckMode::Comment => self.core.reddit.comment(comment, &note, action).await,
+            AckMode::Edit => {
+                let original = self
+                    .fetch_info::<Comment>(&[comment.to_owned()])
+                    .await
+                    .map_err(|e| BotError::Permanent(format!("{:#}", e)))?
+                    .into_iter()
+                    .next()
+                    .ok_or_else(|| BotError::Permanent(format!("{} is gone", comment)))?;
+                let text = format!("{}\n\n---\n\n{}", original.body, note);
+                self.core.reddit.edit(comment, &text, action).await
+            }
         }
     }
 
-    /// Watch a subreddit for all new posts.
+    /// The license a repository that was just found unlicensed had when it
+    /// was last checked, if it had one.
     ///
-    /// This function loops and does not return unless there's an error.
-    pub async fn watch_subreddit(&mut self, subreddit: &str) -> Result<()> {
-        let processed = {
-            match fs::read_to_string(format!("processed-{}.json", subreddit)) {
-                Ok(data) => match serde_json::from_str::<Vec<String>>(&data) {
-                    Ok(data) => {
-                        debug!("Loaded processed list with {} items", data.len());
-                        data
-                    }
-                    Err(_) => vec![],
-                },
-                Err(_) => vec![],
+    /// That's warned about and reported to Sentry, since it may mean the
+    /// license was deleted by accident, or that the repository was taken over.
+    async fn removed_license(
+        &mut self,
+        repo: &GhRepo,
+        post: &str,
+        subreddit: &str,
+    ) -> Option<String> {
+        let license = self
+            .core
+            .events
+            .lock()
+            .unwrap()
+            .removed_license(&repo.to_string())?;
+        warn!(
+            "{} had the {} license when it was last checked, and has no license now",
+            repo, license
+        );
+        if let Some(reporter) = &mut self.reporter {
+            let error = anyhow!("The {} license was removed from {}", license, repo);
+            let context = ErrorContext {
+                subreddit: Some(subreddit.to_owned()),
+                post: Some(post.to_owned()),
+                repo: Some(repo.to_string()),
+            };
+            if let Err(e) = reporter.capture(&error, Level::Warning, &context).await {
+                warn!("Could not report removed license to Sentry: {:#}", e);
             }
-        };
-        self.processed = processed;
-        let mut after: Option<String> = None;
-        loop {
-            after = match self.watch_subreddit_once(subreddit, &after).await {
-                Ok(a) => a,
-                Err(e) => {
-                    error!(
-                        "Encountered error in processing loop for /r/{}: {}",
-                        subreddit, e
-                    );
-                    after
-                }
+        }
+        Some(license)
+    }
+
+    /// Warn that a reply was removed, counting it against its subreddit.
+    async fn reply_removed(&mut self, check: &RemovalCheck) {
+        warn!(
+            "Reply {} to post {} was removed from /r/{}",
+            check.comment, check.post, check.subreddit
+        );
+        let count = self.removals.record(&check.subreddit);
+        if self.config().auto_disable_after_removals == Some(count) {
+            warn!(
+                "/r/{} has removed {} replies, only reporting findings there from now on",
+                check.subreddit, count
+            );
+        }
+        if let Some(reporter) = &mut self.reporter {
+            let error = anyhow!("Reply {} to post {} was removed", check.comment, check.post);
+            let context = ErrorContext {
+                subreddit: Some(check.subreddit.clone()),
+                post: Some(check.post.clone()),
+                repo: None,
             };
-            fs::write(
-                format!("processed-{}.json", subreddit),
-                serde_json::to_string(&self.processed)?,
-            )?;
+            if let Err(e) = reporter.capture(&error, Level::Warning, &context).await {
+                warn!("Could not report removed reply to Sentry: {:#}", e);
+            }
+        }
+    }
+
+    /// Fetch the current state of posts by their fullnames.
+    ///
+    /// Deleted posts aren't returned by Reddit, so the result may be shorter
+    /// than the input.
+    pub async fn fetch_posts_by_fullname(&self, fullnames: &[String]) -> Result<Vec<Post>> {
+        self.fetch_info(fullnames).await
+    }
+
+    /// Fetch the current state of things of any kind by their fullnames,
+    /// leaving out those Reddit doesn't return.
+    async fn fetch_info<T: DeserializeOwned>(&self, fullnames: &[String]) -> Result<Vec<T>> {
+        self.core.reddit.info(fullnames).await
+    }
+
+    /// Fetch the other submissions of a post's link.
+    async fn fetch_duplicates(&self, fullname: &str) -> Result<Vec<Post>> {
+        self.core.reddit.duplicates(fullname).await
+    }
+
+    /// Fetch the bot's most recent comments, newest first.
+    async fn fetch_own_comments(&self, limit: usize) -> Result<Vec<Comment>> {
+        let username = &self.config().username;
+        self.core.reddit.user_comments(username, limit).await
+    }
+
+    /// Mark every post the bot has recently commented in as processed.
+    ///
+    /// Protects against re-commenting on recent posts if the state files were
+    /// lost. Failures are logged rather than returned.
+    async fn seed_from_comment_history(&mut self) {
+        match self.fetch_own_comments(100).await {
+            Ok(comments) => {
+                let now = self.core.clock.unix_now();
+                let mut seeded = 0;
+                for comment in comments {
+                    if self.processed.insert(&comment.link_id, now) {
+                        seeded += 1;
+                    }
+                }
+                debug!("Seeded {} posts from own comment history", seeded);
+            }
+            Err(e) => error!("Could not load own comment history: {}", e),
         }
     }
+
+    /// Check whether the bot's account and latest comment are publicly visible.
+    ///
+    /// Shadowbanned accounts and comments caught by AutoModerator look normal
+    /// to the bot itself, so both are fetched without authentication.
+    async fn check_visibility(&self) -> Result<Visibility> {
+        let profile = format!("/user/{}/about.json", self.config().username);
+        let reddit = &self.core.reddit;
+        if reddit
+            .public(&profile, &[], "anonymous profile lookup")
+            .await?
+            .is_none()
+        {
+            return Ok(Visibility::AccountHidden);
+        }
+        let comment = match self.fetch_own_comments(1).await?.into_iter().next() {
+            Some(c) => c,
+            None => return Ok(Visibility::Visible),
+        };
+        let permalink = match &comment.permalink {
+            Some(p) => p.trim_end_matches('/'),
+            None => return Ok(Visibility::Visible),
+        };
+        let thread = format!("{}.json", permalink);
+        let query = [("raw_json", "1")];
+        let thread: Value = match reddit
+            .public(&thread, &query, "anonymous comment lookup")
+            .await?
+        {
+            Some(resp) => resp.json().await?,
+            None => return Ok(Visibility::CommentHidden(comment.name)),
+        };
+        if thread_shows_comment(&thread, &comment.name) {
+            Ok(Visibility::Visible)
+        } else {
+            Ok(Visibility::CommentHidden(comment.name))
+        }
+    }
+
+    /// The week's findings, each re-checked to see if it's still unlicensed.
+    async fn digest_rows(&self, from: u64, to: u64) -> Result<Vec<Row>> {
+        let events = events::load(&self.config().state_dir)?;
+        let mut checked: HashMap<String, Option<bool>> = HashMap::new();
+        let mut rows = vec![];
+        for finding in digest::aggregate(&events, from, to) {
+            let key = fold_case(&finding.repo);
+            if !checked.contains_key(&key) {
+                let still_unlicensed = match finding.repo.parse::<GhRepo>() {
+                    Ok(repo) => match self.check_post(&repo).await {
+                        Ok(License::Missing) => Some(true),
+                        Ok(License::Found(_)) => Some(false),
+                        Ok(License::Indeterminate(_)) => None,
+                        Err(e) => {
+                            warn!("Could not re-check {} for the digest: {}", finding.repo, e);
+                            None
+                        }
+                    },
+                    Err(_) => None,
+                };
+                checked.insert(key.clone(), still_unlicensed);
+            }
+            rows.push(Row {
+                still_unlicensed: checked[&key],
+                finding,
+            });
+        }
+        Ok(rows)
+    }
+
+    /// Post a digest to the configured thread and send it to the configured modmail.
+    async fn send_digest(&self, text: &str) -> Result<(), BotError> {
+        if let Some(thread) = &self.config().digest_thread {
+            let thing_id = if thread.starts_with("t3_") {
+                thread.clone()
+            } else {
+                format!("t3_{}", thread)
+            };
+            self.core
+                .reddit
+                .comment(&thing_id, text, "posting digest")
+                .await?;
+        }
+        if let Some(subreddit) = &self.config().digest_modmail {
+            let to = format!("/r/{}", subreddit);
+            self.core
+                .reddit
+                .compose(&to, "Weekly license digest", text, "sending digest")
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Post the weekly digest if one is configured and it's time.
+    ///
+    /// The first time, the digest is only scheduled, so that starting the bot
+    /// doesn't post one straight away.
+    async fn post_digest_if_due(&mut self) {
+        let at = match self.config().digest_at {
+            Some(at) => at,
+            None => return,
+        };
+        let dir = self.config().state_dir.clone();
+        let due = at.last_before(&self.config().timezone, self.core.clock.unix_now() as i64) as u64;
+        let last = match digest::last_posted(&dir) {
+            Ok(last) => last,
+            Err(e) => {
+                warn!("Could not tell when the digest was last posted: {:#}", e);
+                return;
+            }
+        };
+        if last >= due {
+            return;
+        }
+        if last > 0 {
+            let from = due.saturating_sub(digest::WEEK);
+            if let Err(e) = self.post_digest(from, due).await {
+                warn!("Could not post the weekly digest: {:#}", e);
+                return;
+            }
+        } else {
+            info!("Scheduled the weekly digest for {} every week", at);
+        }
+        if let Err(e) = digest::mark_posted(&dir, due) {
+            warn!("Could not record posting the digest: {:#}", e);
+        }
+    }
+
+    /// Post the digest of the findings between `from` and `to`, if there were any.
+    async fn post_digest(&self, from: u64, to: u64) -> Result<()> {
+        let rows = self.digest_rows(from, to).await?;
+        if rows.is_empty() {
+            info!("Nothing was found this week, not posting a digest");
+            return Ok(());
+        }
+        info!("Posting the weekly digest of {} findings", rows.len());
+        self.send_digest(&digest::render_comment(&rows, from, to))
+            .await?;
+        Ok(())
+    }
+
+    /// Run the visibility self-check if it's due, pausing replies if configured.
+    async fn check_visibility_if_due(&mut self) {
+        let interval = time::Duration::from_secs(self.config().visibility_check_interval);
+        if let Some(last) = self.last_visibility_check {
+            if self.since(last) < interval {
+                return;
+            }
+        }
+        self.last_visibility_check = Some(self.core.clock.now());
+        match self.check_visibility().await {
+            Ok(Visibility::Visible) => {
+                debug!("Visibility self-check passed");
+                if self.core.paused.swap(false, Ordering::SeqCst) {
+                    info!("Bot is visible again, resuming replies");
+                    notify::status("Visible again, replies resumed");
+                }
+            }
+            Ok(hidden) => {
+                error!(
+                    "Bot activity is not publicly visible ({:?}); the account may be shadowbanned or filtered",
+                    hidden
+                );
+                let pause = self.config().pause_on_shadowban;
+                if pause && !self.core.paused.swap(true, Ordering::SeqCst) {
+                    error!("Pausing replies until the bot is visible again");
+                }
+                let status = format!("Not publicly visible ({:?})", hidden);
+                match pause {
+                    true => notify::status(&format!("{}, replies paused", status)),
+                    false => notify::status(&status),
+                }
+            }
+            Err(e) => warn!("Could not run visibility self-check: {}", e),
+        }
+    }
+
+    /// How long to wait before polling a listing that's caught up on again.
+    fn poll_interval(&self, listing: &Listing) -> time::Duration {
+        if self.reads_publicly() {
+            PUBLIC_POLL_INTERVAL
+        } else {
+            listing.poll_interval()
+        }
+    }
+
+    async fn delay(&self, listing: &Listing) {
+        let interval = self.poll_interval(listing);
+        debug!(
+            "Caught up on {}, waiting {} seconds for checking again",
+            listing,
+            interval.as_secs()
+        );
+        tokio::select! {
+            _ = notify::sleep(interval) => {}
+            _ = self.core.stop.requested() => {}
+        }
+    }
+
+    /// Fetch a single page of a listing.
+    async fn fetch_page(&self, listing: &Listing, before: &Option<String>) -> Result<Page> {
+        debug!("Making request to see new from {}", listing);
+        self.request_page(listing, listing.query(before)).await
+    }
+
+    /// Request a page of a listing, with the given query.
+    ///
+    /// A banned, private or quarantined listing is a
+    /// [`BotError::ListingUnavailable`] rather than a page.
+    async fn request_page(
+        &self,
+        listing: &Listing,
+        query: Vec<(&'static str, String)>,
+    ) -> Result<Page> {
+        self.core.reddit.page(listing, &query).await
+    }
+
+    /// Opt the bot's account in to viewing a quarantined subreddit.
+    async fn opt_in_to_quarantine(&self, listing: &Listing) -> Result<(), BotError> {
+        let names = match listing {
+            Listing::Subreddit(names) => names,
+            _ => {
+                return Err(BotError::Permanent(format!(
+                    "{} isn't a subreddit",
+                    listing
+                )))
+            }
+        };
+        for name in names {
+            self.core.reddit.opt_in_to_quarantine(name.name()).await?;
+        }
+        Ok(())
+    }
+
+    /// Wait before looking at a listing that Reddit won't show again, warning
+    /// about it the first time.
+    ///
+    /// With `quarantine_optin` set, a quarantined subreddit is opted in to
+    /// and looked at again straight away instead, once.
+    async fn back_off_unavailable(&mut self, listing: &Listing, reason: &Unavailable) {
+        let key = listing.store_key();
+        if *reason == Unavailable::Quarantined
+            && self.config().quarantine_optin
+            && self.quarantine_optins.insert(key.clone())
+        {
+            match self.opt_in_to_quarantine(listing).await {
+                Ok(()) => {
+                    info!("Opted in to quarantined {}", listing);
+                    return;
+                }
+                Err(e) => warn!("Could not opt in to quarantined {}: {}", listing, e),
+            }
+        }
+        let wait = time::Duration::from_secs(self.config().unavailable_retry_interval);
+        if self.unavailable.insert(key, reason.clone()).as_ref() != Some(reason) {
+            warn!(
+                "{} is {}, looking again every {}",
+                listing,
+                reason,
+                humantime::format_duration(wait)
+            );
+            if let Some(reporter) = &mut self.reporter {
+                let error = anyhow!("{} is {}", listing, reason);
+                let context = ErrorContext::of(listing, &error, None);
+                if let Err(e) = reporter.capture(&error, Level::Warning, &context).await {
+                    warn!("Could not report unavailable listing to Sentry: {:#}", e);
+                }
+            }
+        }
+        notify::sleep(wait).await;
+    }
+
+    /// Fetch a page of a listing older than the post `after`, or the newest
+    /// page if there's none.
+    async fn fetch_older_page(&self, listing: &Listing, after: &Option<String>) -> Result<Page> {
+        let mut query = listing.query(&None);
+        if let Some(a) = after {
+            query.push(("after", a.to_owned()));
+        }
+        self.request_page(listing, query).await
+    }
+
+    /// Check the posts made in a subreddit in the last `since` seconds once,
+    /// newest first, as far back as Reddit lists them.
+    ///
+    /// Replies always wait in the approval queue, and no issues are opened.
+    /// The posts are marked as processed in the subreddit's state, so the
+    /// watch loop doesn't look at them again. With `dry_run`, nothing is
+    /// queued or saved.
+    pub async fn backfill(
+        &mut self,
+        subreddit: &Subreddit,
+        since: u64,
+        dry_run: bool,
+        mut progress: impl FnMut(&Progress),
+    ) -> Result<Progress> {
+        let listing = Listing::Subreddit(vec![subreddit.clone()]);
+        let key = listing.store_key();
+        self.adopt_legacy_state(&listing)?;
+        let cursor = self.load_state(&key)?;
+        self.queue_everything = true;
+        self.dry_run = dry_run;
+        let cutoff = self.core.clock.unix_now().saturating_sub(since);
+        let events = self.core.events.clone();
+        let candidates = || {
+            events
+                .lock()
+                .unwrap()
+                .stats_mut()
+                .subreddit(subreddit.name())
+                .candidates
+        };
+        let start = candidates();
+        let mut done = Progress::default();
+        let mut after = None;
+        while done.pages < backfill::MAX_PAGES {
+            let page = self.fetch_older_page(&listing, &after).await?;
+            let (recent, reached) = backfill::before_cutoff(&page.data.children, cutoff);
+            if !recent.is_empty() {
+                self.process_page(&listing, &recent).await?;
+            }
+            done.pages += 1;
+            done.posts += recent.len();
+            done.candidates = candidates() - start;
+            progress(&done);
+            if reached {
+                break;
+            }
+            after = match page.data.after {
+                Some(a) => Some(a),
+                None => break,
+            };
+        }
+        if !dry_run {
+            self.save_state(&key, &cursor)?;
+        }
+        Ok(done)
+    }
+
+    /// What the bot would do about the posts in a saved listing, going
+    /// through the same steps as when watching one, without the network.
+    ///
+    /// Checks are answered from the fixtures, and can't be told either way
+    /// for repositories without one. Every reply that would be made is made
+    /// to the simulation, whatever the active hours and approval mode say,
+    /// so that it can be read; no issues are opened and nothing is saved.
+    pub async fn simulate(
+        listing_json: &str,
+        options: &simulate::Options,
+    ) -> Result<Vec<Decision>> {
+        let postings = simulate::postings(listing_json)?;
+        let mut config = options.config.clone();
+        config.active_hours = None;
+        config.approval_mode = ApprovalMode::Auto;
+        config.github_issue_mode = IssueMode::Off;
+        config.sandbox_subreddit = None;
+        config.redis_url = None;
+        config.http_audit_path = None;
+        config.github_app = None;
+        let simulator = Arc::new(Simulator::new(&postings, options.fixtures.clone()));
+        let mut bot = BotBuilder::new()
+            .config(config)
+            .store(Box::new(MemoryStore::default()))
+            .build()?;
+        let core = bot.core_mut();
+        core.reddit.transport = Transport::Simulated(simulator.clone());
+        core.github.transport = Transport::Simulated(simulator.clone());
+        bot.dry_run = true;
+        let subreddit = postings
+            .first()
+            .and_then(|p| p["data"]["subreddit"].as_str())
+            .unwrap_or_default();
+        // posts that say nothing of their subreddit are still replayed
+        let listing = Listing::Subreddit(Subreddit::new(subreddit).into_iter().collect());
+        let mut events = vec![];
+        for page in postings.chunks(simulate::PAGE_LEN) {
+            bot.process_page(&listing, page).await?;
+            let log = mem::take(&mut *bot.core.events.lock().unwrap());
+            events.extend(log.recent(usize::MAX).into_iter().rev());
+        }
+        let replies = simulator.replies();
+        Ok(postings
+            .iter()
+            .map(|posting| {
+                let fullname = posting["data"]["name"].as_str().unwrap_or_default();
+                let skipped = bot.processed.skipped(fullname);
+                Decision::new(posting, skipped, &events, &replies)
+            })
+            .collect())
+    }
+
+    /// Process every post on a page of a listing.
+    ///
+    /// Each repository is checked once, however many posts link it, and each
+    /// post gets at most one reply, covering every unlicensed repository it
+    /// links that hasn't been replied about on this page yet.
+    ///
+    /// If it fails, the posts it wasn't done with are forgotten, to be looked
+    /// at again when the page is.
+    ///
+    /// Returns the fullnames of every post on the page, newest first.
+    async fn process_page(&mut self, listing: &Listing, postings: &[Value]) -> Result<Vec<String>> {
+        let queued = self.pending.len();
+        let mut scan = self.scan_page(listing, postings);
+        if !scan.post_repos.is_empty() && self.github_quota_low() {
+            info!(
+                "GitHub quota is low, deferring checks of {} posts",
+                scan.post_repos.len()
+            );
+            for (fullname, indices) in &scan.post_repos {
+                let repos = indices.iter().map(|&i| scan.groups[i].link()).collect();
+                let held = scan.held(fullname, repos, self.core.clock.unix_now());
+                self.pending.push(PendingReply {
+                    deferred: true,
+                    ..held
+                });
+            }
+            return Ok(scan.fullnames);
+        }
+        let unlicensed = match self.check_page(&mut scan).await {
+            Ok(unlicensed) => unlicensed,
+            Err(e) => {
+                self.roll_back(scan.post_repos.iter().map(|(f, _)| f), queued);
+                return Err(e);
+            }
+        };
+        if unlicensed.contains(&true) {
+            self.reply_to_page(&scan, &unlicensed, queued).await?;
+        }
+        Ok(scan.fullnames)
+    }
+
+    /// Forget that the given posts were seen, and what was held back about
+    /// them since the queue of held back work had `queued` entries, so that
+    /// they're looked at afresh when their page is tried again.
+    fn roll_back<'a>(&mut self, fullnames: impl Iterator<Item = &'a String>, queued: usize) {
+        let fullnames: HashSet<&String> = fullnames.collect();
+        for fullname in &fullnames {
+            self.processed.remove(fullname);
+        }
+        let mut i = 0;
+        self.pending.retain(|p| {
+            i += 1;
+            i <= queued || !fullnames.contains(&p.fullname)
+        });
+    }
+
+    /// Update the stats of a subreddit.
+    fn count(&self, subreddit: &str, update: impl FnOnce(&mut SubredditStats)) {
+        update(
+            self.core
+                .events
+                .lock()
+                .unwrap()
+                .stats_mut()
+                .subreddit(subreddit),
+        );
+    }
+
+    /// Count the outcome of checking a repository linked in a subreddit.
+    /// Whether a post made at a time is young enough that a repository it
+    /// links may not be public yet.
+    fn is_young(&self, posted_at: u64) -> bool {
+        let age = self.config().young_post_age;
+        posted_at > 0 && self.core.clock.unix_now().saturating_sub(posted_at) < age
+    }
+
+    fn count_check(
+        &self,
+        subreddit: &str,
+        result: Result<(&License, &[DetectionStep]), &BotError>,
+    ) {
+        match result {
+            Ok((License::Missing, _)) => self.count(subreddit, |s| s.unlicensed += 1),
+            Ok((License::Found(_), _)) | Ok((License::Indeterminate(_), _)) => {}
+            Err(_) => self.count(subreddit, |s| s.errors += 1),
+        }
+        if let Some(probe) = result
+            .ok()
+            .and_then(|(_, path)| license::overturned_by(path))
+        {
+            self.count(subreddit, |s| {
+                *s.overturns.entry(probe.label().to_owned()).or_default() += 1
+            });
+        }
+    }
+
+    /// Add what was done about the unlicensed repositories in a post to the event log.
+    fn record_events(
+        &self,
+        repos: &[&RepoGroup],
+        fullname: &str,
+        details: &HashMap<String, PostDetails>,
+        action: &str,
+    ) {
+        for group in repos {
+            self.record_event(group, fullname, details, &License::Missing, action);
+        }
+    }
+
+    /// Add what was done about a repository to the event log.
+    fn record_event(
+        &self,
+        group: &RepoGroup,
+        fullname: &str,
+        details: &HashMap<String, PostDetails>,
+        license: &License,
+        action: &str,
+    ) {
+        let post = details.get(fullname).cloned().unwrap_or_default();
+        let repo = group.repo.to_string();
+        let path = group.detection_path.clone();
+        self.record_checked(fullname, repo, post, license, action, path);
+    }
+
+    /// Add what was done about a held back reply to the event log.
+    fn record_pending(&self, pending: &PendingReply, action: &str) {
+        for link in &pending.repos {
+            let repo = link.repo.to_string();
+            let post = pending.details.clone();
+            self.record(&pending.fullname, repo, post, &License::Missing, action);
+        }
+    }
+
+    fn record(
+        &self,
+        fullname: &str,
+        repo: String,
+        post: PostDetails,
+        license: &License,
+        action: &str,
+    ) {
+        self.record_checked(fullname, repo, post, license, action, vec![]);
+    }
+
+    /// Add what was done about a repository to the event log, with how its
+    /// license was looked for.
+    fn record_checked(
+        &self,
+        fullname: &str,
+        repo: String,
+        post: PostDetails,
+        license: &License,
+        action: &str,
+        detection_path: Vec<DetectionStep>,
+    ) {
+        let (verdict, license) = match license {
+            License::Missing => ("unlicensed", None),
+            License::Found(spdx_id) => ("licensed", spdx_id.clone()),
+            License::Indeterminate(_) => ("indeterminate", None),
+        };
+        let mut events = self.core.events.lock().unwrap();
+        if let Some(reason) = SkipReason::from_action(verdict, action) {
+            events.stats_mut().skip(&post.subreddit, reason);
+        }
+        events.record(Event {
+            at: self.core.clock.unix_now(),
+            fullname: fullname.to_owned(),
+            title: post.title,
+            subreddit: post.subreddit,
+            permalink: post.permalink,
+            author: post.author,
+            repo,
+            verdict: verdict.to_owned(),
+            license,
+            action: action.to_owned(),
+            removed_license: None,
+            detection_path,
+        });
+    }
+
+    /// Hand a page fetched from a listing over to be processed, with where
+    /// the listing is up to once it has been, waiting while too many pages
+    /// are waiting already.
+    ///
+    /// Returns false if nothing is processing pages any more.
+    async fn hand_over(
+        &mut self,
+        postings: Vec<Value>,
+        cursor: &Cursor,
+        pages: &Sender<Batch>,
+    ) -> bool {
+        let now = self.core.clock.unix_now();
+        for fullname in pipeline::fullnames(&postings) {
+            self.processed.insert(&fullname, now);
+        }
+        let batch = Batch {
+            postings,
+            cursor: cursor.clone(),
+        };
+        match pages.try_send(batch) {
+            Ok(()) => true,
+            Err(TrySendError::Full(batch)) => {
+                debug!("Waiting for the pages already fetched to be processed");
+                pages.send(batch).await.is_ok()
+            }
+            Err(TrySendError::Closed(_)) => false,
+        }
+    }
+
+    /// Fetch everything in a listing newer than the cursor's anchor, handing
+    /// each page over to be processed as it's fetched.
+    ///
+    /// Pages are followed towards newer posts until the newest post is reached
+    /// or the configured page cap is hit. Returns whether the listing has been
+    /// caught up with; if not, the next call continues from where this one
+    /// stopped.
+    ///
+    /// What's been handed over is taken to have been seen, so that a listing
+    /// answering with nothing new can be told apart.
+    async fn fetch_new(
+        &mut self,
+        listing: &Listing,
+        cursor: &mut Cursor,
+        pages: &Sender<Batch>,
+    ) -> Result<bool> {
+        let anchor = match cursor.anchor() {
+            Some(a) => a.to_owned(),
+            None => {
+                debug!("No anchor for {}, fetching newest posts", listing);
+                let postings = self.fetch_page(listing, &None).await?.data.children;
+                cursor.advance(&pipeline::fullnames(&postings));
+                self.hand_over(postings, cursor, pages).await;
+                return Ok(true);
+            }
+        };
+        let mut before = Some(anchor.clone());
+        for page in 1..=self.config().max_pages.max(1) {
+            let fetched = self.fetch_page(listing, &before).await?;
+            let postings = fetched.data.children;
+            if postings.is_empty() {
+                if page == 1 {
+                    self.verify_anchor(listing, cursor, &anchor, pages).await?;
+                }
+                return Ok(true);
+            }
+            let all_seen = postings.iter().all(|p| {
+                p["data"]["name"]
+                    .as_str()
+                    .is_some_and(|f| self.processed.contains(f))
+            });
+            let fullnames = pipeline::fullnames(&postings);
+            debug!("Page {} of {} had {} posts", page, listing, fullnames.len());
+            cursor.advance(&fullnames);
+            let stale = cursor.note_page(all_seen);
+            if stale {
+                warn!(
+                    "{} keeps answering with posts already seen, starting again from its newest posts",
+                    listing
+                );
+                cursor.reset();
+            }
+            if !self.hand_over(postings, cursor, pages).await || stale {
+                return Ok(true);
+            }
+            before = match fetched.data.before {
+                Some(b) => Some(b),
+                None => return Ok(true),
+            };
+        }
+        debug!("Hit page cap for {}, resuming from {:?}", listing, before);
+        Ok(false)
+    }
+
+    /// Check that an anchor which returned no newer posts still exists.
+    ///
+    /// Reddit returns an empty page for posts newer than a deleted post, so
+    /// the newest posts are fetched without the anchor, and handed over to
+    /// be processed. If the anchor isn't among them, it's aged back to the
+    /// next-newest known post.
+    async fn verify_anchor(
+        &mut self,
+        listing: &Listing,
+        cursor: &mut Cursor,
+        anchor: &str,
+        pages: &Sender<Batch>,
+    ) -> Result<()> {
+        let postings = self.fetch_page(listing, &None).await?.data.children;
+        if !pipeline::fullnames(&postings).iter().any(|f| f == anchor) {
+            let aged = cursor.age();
+            warn!(
+                "Anchor {:?} for {} appears to have been deleted, now using {:?}",
+                aged,
+                listing,
+                cursor.anchor()
+            );
+        }
+        self.hand_over(postings, cursor, pages).await;
+        Ok(())
+    }
+
+    /// Process a page handed over to be processed, moving the cursor past
+    /// it once it has been.
+    async fn process_batch(
+        &mut self,
+        listing: &Listing,
+        batch: &Batch,
+        cursor: &mut Cursor,
+    ) -> Result<()> {
+        self.process_page(listing, &batch.postings).await?;
+        *cursor = batch.cursor.clone();
+        Ok(())
+    }
+
+    /// A bot to fetch a listing's pages for this one to process, knowing
+    /// which posts this one has seen.
+    fn producer(&self) -> Bot {
+        Bot {
+            processed: self.processed.clone(),
+            ..self.task()
+        }
+    }
+
+    /// Process everything in a listing newer than the cursor's anchor,
+    /// fetching each page while the one before it is processed.
+    ///
+    /// Returns whether the listing has been caught up with, as `fetch_new`
+    /// does. The cursor is only moved past pages that have been processed,
+    /// so that when processing fails, the pages after it are fetched again.
+    async fn watch_listing_once(&mut self, listing: &Listing, cursor: &mut Cursor) -> Result<bool> {
+        let (pages, mut batches) = mpsc::channel(self.config().pipeline_depth.max(1));
+        let mut producer = self.producer();
+        let mut ahead = cursor.clone();
+        let fetch = async move { producer.fetch_new(listing, &mut ahead, &pages).await };
+        let process = async move {
+            while let Some(batch) = batches.recv().await {
+                self.process_batch(listing, &batch, cursor).await?;
+            }
+            Ok::<_, anyhow::Error>(())
+        };
+        let (fetched, processed) = futures::join!(fetch, process);
+        processed?;
+        fetched
+    }
+
+    /// Watch what the config asks for: a search, a user, or subreddits.
+    ///
+    /// This function loops and does not return unless there's an error.
+    pub async fn watch(&mut self) -> Result<()> {
+        if let Some(query) = self.config().search_query.clone() {
+            self.watch_search(&query).await
+        } else if let Some(user) = self.config().watch_user.clone() {
+            self.watch_user(&user).await
+        } else {
+            let subreddit = self.config().subreddit.clone();
+            self.watch_subreddit(&subreddit).await
+        }
+    }
+
+    /// Watch a subreddit for all new posts.
+    ///
+    /// Several subreddits can be watched together with multireddit syntax,
+    /// e.g. `rust+programming`, or as a comma-separated list.
+    ///
+    /// This function loops and does not return unless there's an error.
+    pub async fn watch_subreddit(&mut self, subreddit: &str) -> Result<()> {
+        self.watch_listing(&Listing::subreddits(subreddit)?).await
+    }
+
+    /// Watch a user's submissions for all new posts.
+    ///
+    /// This function loops and does not return unless there's an error.
+    pub async fn watch_user(&mut self, username: &str) -> Result<()> {
+        self.watch_listing(&Listing::User(username.to_owned()))
+            .await
+    }
+
+    /// Watch Reddit-wide search results for new posts linking to GitHub.
+    ///
+    /// This function loops and does not return unless there's an error.
+    pub async fn watch_search(&mut self, query: &str) -> Result<()> {
+        let listing = Listing::Search {
+            query: query.to_owned(),
+            interval: self.config().search_interval,
+        };
+        self.watch_listing(&listing).await
+    }
+
+    /// Move the state of a listing saved under the key it had before to the
+    /// one it has now.
+    fn adopt_legacy_state(&self, listing: &Listing) -> Result<()> {
+        if let Some(old) = listing.legacy_store_key() {
+            let key = listing.store_key();
+            if self.core.with_store(|s| s.move_state(&old, &key))? {
+                info!("Moved the state of {} from {} to {}", listing, old, key);
+            }
+        }
+        Ok(())
+    }
+
+    /// Pick up where the bot left off with a listing, returning its cursor.
+    ///
+    /// Processed posts saved without when they were seen are taken to have
+    /// been seen now, and those past the retention are forgotten.
+    fn load_state(&mut self, key: &str) -> Result<Cursor> {
+        let mut state = self.core.load_state(key)?;
+        self.claim_state(key, &mut state)?;
+        let now = self.core.clock.unix_now();
+        state.processed.date_undated(now);
+        self.prune_processed(key, &mut state.processed, now);
+        self.processed = state.processed.clone();
+        self.pending = state.pending.clone();
+        self.core
+            .events
+            .lock()
+            .unwrap()
+            .stats_mut()
+            .restore(&state.stats);
+        let cursor = state.cursor.clone();
+        self.checkpoint = Some((key.to_owned(), state));
+        Ok(cursor)
+    }
+
+    /// Stamp a listing's state as this bot's, making sure it's not another's.
+    ///
+    /// State stamped as another account's or listing's is refused, unless
+    /// adopting state was asked for; state that only differs in case, as
+    /// after a subreddit's name was configured differently, is taken over.
+    fn claim_state(&self, key: &str, state: &mut StateFile) -> Result<()> {
+        let owner = Owner::new(&self.config().username, key);
+        match (owner.ownership(state.owner.as_ref()), &state.owner) {
+            (Ownership::Same, _) => return Ok(()),
+            (Ownership::Other, Some(stamp)) if self.adopt_state => {
+                warn!("Adopting the state of {} from {}", key, stamp)
+            }
+            (Ownership::Other, Some(stamp)) => {
+                return Err(ForeignState {
+                    key: key.to_owned(),
+                    stamp: stamp.clone(),
+                    owner,
+                }
+                .into())
+            }
+            (Ownership::Recased, Some(stamp)) => warn!(
+                "The state of {} was stamped as {}, taking it as {}",
+                key, stamp, owner
+            ),
+            _ => debug!("Stamping the state of {} as {}", key, owner),
+        }
+        state.owner = Some(owner);
+        Ok(())
+    }
+
+    /// Drop what's no longer needed from memory every `compact_every` passes:
+    /// processed posts past the retention, subreddit rules past their TTL and
+    /// replies that no longer count towards the hourly cap. Collections left
+    /// with a lot of spare room are shrunk.
+    fn compact_if_due(&mut self) {
+        let config = self.config();
+        if !self.compaction.tick(config.compact_every) {
+            return;
+        }
+        let before = self.heap_size();
+        let now = self.core.clock.unix_now();
+        let mut evicted = 0;
+        let mut processed: Vec<&mut Processed> = vec![&mut self.processed];
+        if let Some((_, state)) = &mut self.checkpoint {
+            processed.push(&mut state.processed);
+        }
+        for processed in processed {
+            evicted += processed.prune(now, config.processed_retention, config.processed_max);
+            processed.shrink();
+        }
+
+        let instant = self.core.clock.now();
+        let verdicts = &mut self.subreddit_verdicts;
+        let cached = verdicts.len();
+        verdicts.retain(|_, (_, checked)| {
+            instant.saturating_duration_since(*checked) < RULES_CACHE_TTL
+        });
+        evicted += cached - verdicts.len();
+        if worth_shrinking(verdicts.len(), verdicts.capacity()) {
+            verdicts.shrink_to_fit();
+        }
+
+        let hour = time::Duration::from_secs(60 * 60);
+        let mut replies = self.core.replies.lock().unwrap();
+        let counted = replies.len();
+        replies.retain(|&sent| instant.saturating_duration_since(sent) < hour);
+        evicted += counted - replies.len();
+        if worth_shrinking(replies.len(), replies.capacity()) {
+            replies.shrink_to_fit();
+        }
+        drop(replies);
+
+        let reclaimed = before.saturating_sub(self.heap_size());
+        debug!(
+            "Compacted memory, evicting {} entries and reclaiming about {} bytes",
+            evicted, reclaimed
+        );
+        self.core
+            .events
+            .lock()
+            .unwrap()
+            .record_compaction(evicted, reclaimed);
+    }
+
+    /// Estimated bytes taken up on the heap by what compaction looks at.
+    fn heap_size(&self) -> usize {
+        let checkpoint = self.checkpoint.as_ref().map_or(0, |(key, state)| {
+            key.capacity() + state.processed.heap_size()
+        });
+        let verdicts = map_size(&self.subreddit_verdicts, |key, (verdict, _)| {
+            key.capacity()
+                + match verdict {
+                    Verdict::ReportOnly(reason) => reason.capacity(),
+                    Verdict::Allowed => 0,
+                }
+        });
+        let replies = self.core.replies.lock().unwrap();
+        self.processed.heap_size()
+            + checkpoint
+            + verdicts
+            + vec_size(&replies, replies.capacity(), |_| 0)
+    }
+
+    /// Forget processed posts past the retention, or beyond the most kept.
+    fn prune_processed(&self, key: &str, processed: &mut Processed, now: u64) {
+        let config = self.config();
+        let pruned = processed.prune(now, config.processed_retention, config.processed_max);
+        if pruned > 0 {
+            debug!("Forgot {} old processed posts in {}", pruned, key);
+        }
+    }
+
+    /// Save the state of a listing after a pass over it has been finished.
+    ///
+    /// Posts another task has marked as processed in the meantime, such as a
+    /// backfill of the same subreddit, stay marked, while those that have
+    /// been kept too long are forgotten.
+    fn save_state(&mut self, key: &str, cursor: &Cursor) -> Result<()> {
+        let mut processed = self.processed.clone();
+        let now = self.core.clock.unix_now();
+        let state = self.core.update_state(key, |state| {
+            // counted under the store's lock, so a task saving after
+            // another can't write older counts over its newer ones
+            let stats = self.core.events.lock().unwrap().stats().clone();
+            processed.merge(&state.processed);
+            processed.date_undated(now);
+            self.prune_processed(key, &mut processed, now);
+            *state = StateFile {
+                processed: processed.clone(),
+                cursor: cursor.clone(),
+                pending: self.pending.clone(),
+                stats,
+                owner: Some(Owner::new(&self.config().username, key)),
+                ..StateFile::default()
+            };
+        })?;
+        self.processed = processed;
+        self.checkpoint = Some((key.to_owned(), state));
+        Ok(())
+    }
+
+    /// Save the state of the watched listing as of the last finished pass.
+    ///
+    /// Meant for shutting down, after the future watching the listing has
+    /// been dropped. A pass that was interrupted is thrown away rather than
+    /// saved half done, so its posts are looked at again after a restart;
+    /// any replies it made are in the reply history, so they aren't repeated.
+    pub fn save_checkpoint(&mut self) -> Result<()> {
+        let (key, state) = match &self.checkpoint {
+            Some(checkpoint) => checkpoint.clone(),
+            None => return Ok(()),
+        };
+        self.pending = state.pending.clone();
+        let mut processed = state.processed.clone();
+        self.core.update_state(&key, |saved| {
+            processed.merge(&saved.processed);
+            *saved = StateFile {
+                processed: processed.clone(),
+                ..state.clone()
+            };
+        })?;
+        self.processed = processed;
+        Ok(())
+    }
+
+    /// Watch a listing for all new posts.
+    ///
+    /// The listing's pages are fetched and handed over to be checked and
+    /// replied to, up to `pipeline_depth` of them waiting at a time, so that
+    /// slow checks don't hold up polling the listing. With a depth of 0,
+    /// fetching and processing take turns instead.
+    ///
+    /// The bot's scheduled tasks run alongside, until watching stops.
+    ///
+    /// This function loops and does not return unless there's an error, or
+    /// stopping was asked for and the pages already fetched are processed.
+    async fn watch_listing(&mut self, listing: &Listing) -> Result<()> {
+        let key = listing.store_key();
+        self.adopt_legacy_state(listing)?;
+        let cursor = self.load_state(&key)?;
+        self.reconcile_claims(0).await;
+        if !self.reads_publicly() {
+            self.seed_from_comment_history().await;
+        }
+        let scheduler = self.scheduler();
+        let core = self.core.clone();
+        let (done, watched) = tokio::sync::oneshot::channel::<()>();
+        let stopped = async {
+            tokio::select! {
+                _ = core.stop.requested() => {}
+                _ = watched => {}
+            }
+        };
+        let watching = async {
+            let result = self.watch_from(listing, cursor).await;
+            drop(done);
+            result
+        };
+        let (result, ()) = futures::join!(watching, scheduler.run(stopped));
+        result
+    }
+
+    /// Watch a listing from `cursor`, fetching its pages while they're
+    /// processed or taking turns, as `pipeline_depth` says.
+    ///
+    /// The one producer and the one consumer are futures joined on the
+    /// calling task, not tasks of their own: they run concurrently, so one
+    /// waiting on Reddit or GitHub lets the other go on, but not in parallel,
+    /// and the pages are processed one at a time, in order.
+    async fn watch_from(&mut self, listing: &Listing, cursor: Cursor) -> Result<()> {
+        let depth = self.config().pipeline_depth;
+        if depth == 0 {
+            return self.watch_in_turns(listing, cursor).await;
+        }
+        let (pages, batches) = mpsc::channel(depth);
+        let producer = self.producer();
+        let (fetched, processed) = futures::join!(
+            producer.produce(listing, cursor.clone(), pages),
+            self.consume(listing, cursor, batches)
+        );
+        processed.and(fetched)
+    }
+
+    /// Fetch a listing's new pages for `consume` to process, polling
+    /// again once caught up, until stopping is asked for or nothing is
+    /// processing them any more.
+    async fn produce(
+        mut self,
+        listing: &Listing,
+        mut cursor: Cursor,
+        pages: Sender<Batch>,
+    ) -> Result<()> {
+        let core = self.core.clone();
+        loop {
+            if core.stop.is_requested() {
+                debug!("Stopped fetching {}", listing);
+                return Ok(());
+            }
+            let result = self
+                .fetch_new(listing, &mut cursor, &pages)
+                .await
+                .with_context(|| format!("watching {}", listing));
+            if pages.is_closed() {
+                return Ok(());
+            }
+            if result.is_ok() {
+                self.note_success(listing);
+            }
+            let wait = async {
+                match result {
+                    Ok(true) => self.delay(listing).await,
+                    Ok(false) => {}
+                    Err(e) => self.handle_watch_error(listing, &e).await?,
+                }
+                Ok::<_, anyhow::Error>(())
+            };
+            tokio::select! {
+                waited = wait => waited?,
+                _ = core.stop.requested() => {}
+                _ = pages.closed() => {}
+            }
+        }
+    }
+
+    /// Process the pages of a listing `produce` fetches, and work through
+    /// what's held back, at least once per poll interval.
+    ///
+    /// A page that fails to be processed is tried again after the failure
+    /// has been waited out, looking again at the posts on it that weren't
+    /// done with, and the listing's state is saved with the cursor
+    /// past the last page that has been processed. Returns once every page
+    /// has been processed and no more will be fetched.
+    async fn consume(
+        &mut self,
+        listing: &Listing,
+        mut cursor: Cursor,
+        mut batches: Receiver<Batch>,
+    ) -> Result<()> {
+        let key = listing.store_key();
+        let mut ready = false;
+        // a page that failed to be processed, to try again
+        let mut failed = None;
+        loop {
+            self.housekeeping().await;
+            let batch = match failed.take() {
+                Some(batch) => Some(batch),
+                None => {
+                    match tokio::time::timeout(self.poll_interval(listing), batches.recv()).await {
+                        Ok(Some(batch)) => Some(batch),
+                        Ok(None) => {
+                            info!("Processed every page fetched from {}", listing);
+                            return Ok(());
+                        }
+                        Err(_) => None,
+                    }
+                }
+            };
+            let result = self
+                .consume_pass(listing, batch.as_ref(), &mut cursor)
+                .await
+                .with_context(|| format!("watching {}", listing));
+            self.save_state(&key, &cursor)?;
+            self.compact_if_due();
+            match result {
+                Ok(()) => {
+                    if !ready {
+                        notify::ready();
+                        ready = true;
+                    }
+                    self.note_success(listing);
+                }
+                Err(e) => {
+                    failed = batch;
+                    self.handle_watch_error(listing, &e).await?;
+                }
+            }
+        }
+    }
+
+    /// Watch a listing, fetching and processing its pages in turn.
+    async fn watch_in_turns(&mut self, listing: &Listing, mut cursor: Cursor) -> Result<()> {
+        let key = listing.store_key();
+        let mut ready = false;
+        while !self.core.stop.is_requested() {
+            self.housekeeping().await;
+            let result = self
+                .watch_pass(listing, &mut cursor)
+                .await
+                .with_context(|| format!("watching {}", listing));
+            self.save_state(&key, &cursor)?;
+            self.compact_if_due();
+            if result.is_ok() {
+                if !ready {
+                    notify::ready();
+                    ready = true;
+                }
+                self.note_success(listing);
+            }
+            match result {
+                Ok(true) => self.delay(listing).await,
+                Ok(false) => {}
+                Err(e) => self.handle_watch_error(listing, &e).await?,
+            }
+        }
+        Ok(())
+    }
+
+    /// What's done before every pass over a listing: keeping the watchdog
+    /// fed, taking up config changes and making whatever checks are due.
+    async fn housekeeping(&mut self) {
+        notify::watchdog();
+        self.apply_config_updates();
+        templates::refresh(&self.config().template_dir);
+        if !self.reads_publicly() {
+            self.check_visibility_if_due().await;
+        }
+        self.post_digest_if_due().await;
+        self.check_removals_if_due().await;
+        self.check_resolved_if_due().await;
+    }
+
+    /// Note that a pass over a listing went well, after any that didn't.
+    fn note_success(&mut self, listing: &Listing) {
+        if let Some(reporter) = &mut self.reporter {
+            reporter.clear();
+        }
+        if self.unavailable.remove(&listing.store_key()).is_some() {
+            info!("{} is available again", listing);
+        }
+        if self.core.maintenance.swap(false, Ordering::SeqCst) {
+            info!("Reddit is available again");
+        }
+    }
+
+    /// Wait out what made a pass over a listing fail, reporting it if it's
+    /// unexpected. Returns an error if it can't be recovered from.
+    async fn handle_watch_error(&mut self, listing: &Listing, e: &anyhow::Error) -> Result<()> {
+        if let Some(reason) = unavailable(e) {
+            let reason = reason.clone();
+            self.back_off_unavailable(listing, &reason).await;
+        } else if in_maintenance(e) {
+            self.wait_out_maintenance(e).await;
+        } else {
+            error!("Encountered error in processing loop: {:#}", e);
+            if let Some(reporter) = &mut self.reporter {
+                let context = ErrorContext::of(listing, e, self.current_post.as_deref());
+                if let Err(e) = reporter.capture(e, Level::Error, &context).await {
+                    warn!("Could not report error to Sentry: {:#}", e);
+                }
+            }
+            self.recover(listing, e).await?;
+        }
+        Ok(())
+    }
+
+    /// Make one pass over a listing, after any replies waiting to be made.
+    ///
+    /// A panic during the pass is turned into an error, so that a bug in
+    /// handling one post costs a pass rather than the whole bot.
+    async fn watch_pass(&mut self, listing: &Listing, cursor: &mut Cursor) -> Result<bool> {
+        self.current_post = None;
+        let pass = async {
+            self.drain_queues().await?;
+            self.watch_listing_once(listing, cursor).await
+        };
+        match AssertUnwindSafe(pass).catch_unwind().await {
+            Ok(result) => result,
+            Err(payload) => Err(panicked(self.current_post.as_deref(), &*payload)),
+        }
+    }
+
+    /// Work through what's held back, then process a page `produce` fetched
+    /// if there is one, as `watch_pass` does.
+    async fn consume_pass(
+        &mut self,
+        listing: &Listing,
+        batch: Option<&Batch>,
+        cursor: &mut Cursor,
+    ) -> Result<()> {
+        self.current_post = None;
+        let pass = async {
+            self.drain_queues().await?;
+            match batch {
+                Some(batch) => self.process_batch(listing, batch, cursor).await,
+                None => Ok(()),
+            }
+        };
+        match AssertUnwindSafe(pass).catch_unwind().await {
+            Ok(result) => result,
+            Err(payload) => Err(panicked(self.current_post.as_deref(), &*payload)),
+        }
+    }
+
+    /// Wait out or repair the cause of a failed pass over a listing.
+    ///
+    /// A rejected token means logging in again; rate limits are waited out,
+    /// and anything else is retried after the usual poll interval.
+    async fn recover(&mut self, listing: &Listing, e: &anyhow::Error) -> Result<()> {
+        match e.downcast_ref::<BotError>().map(BotError::kind) {
+            Some(BotError::RedditAuth(_)) => {
+                warn!("Reddit rejected the access token, logging in again");
+                self.login_when_available().await?;
+            }
+            Some(BotError::RedditRateLimited { retry_after }) => {
+                let (wait, why) = errors::retry_wait(*retry_after, DEFAULT_RETRY_AFTER);
+                warn!("Rate limited by Reddit, waiting {:?} {}", wait, why);
+                notify::sleep(wait).await;
+            }
+            Some(BotError::GitHubRateLimited { reset, retry_after }) => {
+                let until_reset = reset
+                    .duration_since(self.core.clock.system_time())
+                    .unwrap_or_default();
+                let (wait, why) = errors::retry_wait(*retry_after, until_reset);
+                warn!("Rate limited by GitHub, waiting {:?} {}", wait, why);
+                notify::sleep(wait).await;
+            }
+            _ => self.delay(listing).await,
+        }
+        Ok(())
+    }
+}
+
+/// Whether an error means Reddit is down, as it is for maintenance.
+fn in_maintenance(error: &anyhow::Error) -> bool {
+    matches!(
+        error.downcast_ref::<BotError>().map(BotError::kind),
+        Some(BotError::ServiceUnavailable { .. })
+    )
+}
+
+/// Why Reddit won't show a listing, if that's what an error is.
+fn unavailable(error: &anyhow::Error) -> Option<&Unavailable> {
+    match error.downcast_ref::<BotError>().map(BotError::kind) {
+        Some(BotError::ListingUnavailable { reason, .. }) => Some(reason),
+        _ => None,
+    }
+}
+
+impl Supervised for Bot {
+    fn run(&mut self) -> LocalBoxFuture<'_, Result<()>> {
+        self.watch().boxed_local()
+    }
+
+    fn restart(&mut self) -> LocalBoxFuture<'_, Result<()>> {
+        async move { Ok(self.login_when_available().await?) }.boxed_local()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{panic_message, resolved, Bot, BotBuilder, Endpoints, License};
+    use crate::backfill::Progress;
+    use crate::check;
+    use crate::clock::ManualClock;
+    use crate::digest;
+    use crate::duplicates::DuplicateMode;
+    use crate::errors::BotError;
+    use crate::events::{self, Event, EventLog};
+    use crate::history::ReplyRecord;
+    use crate::issues::IssueMode;
+    use crate::latency::Stages;
+    use crate::license::{DetectionStep, Probe};
+    use crate::listing::{Cursor, Listing, Unavailable, PUBLIC_POLL_INTERVAL, STALE_AFTER};
+    use crate::models::{Config, Post};
+    use crate::pending::{Budget, PendingReply, PostDetails, QueueKind, RepoLink};
+    use crate::pipeline::Batch;
+    use crate::preview::RepoSummary;
+    use crate::queue::{ApprovalMode, ApprovalQueue};
+    use crate::reply;
+    use crate::report::Reporter;
+    use crate::resolved::AckMode;
+    use crate::restricted;
+    use crate::rules::Verdict;
+    use crate::schedule::ActiveHours;
+    use crate::simulate;
+    use crate::skip::SkipReason;
+    use crate::state::{DirStore, ForeignState, MemoryStore, Owner, StateFile, Store};
+    use crate::subreddit::Subreddit;
+    use crate::suppress::{self, Suppression};
+    use crate::transport::Transport;
+    use crate::util::GhRepo;
+    use crate::visibility::Visibility;
+    use futures::future::join_all;
+    use mockito::{mock, server_url, Matcher};
+    use std::{
+        env, fs,
+        path::Path,
+        rc::Rc,
+        sync::atomic::Ordering,
+        time::{Duration, UNIX_EPOCH},
+    };
+    use tokio::task::LocalSet;
+
+    fn test_config() -> Config {
+        Config {
+            check_concurrency: 4,
+            pause_on_shadowban: true,
+            invited_subreddits: vec!["invited".to_owned()],
+            // held back replies are made without mocking their checks again
+            recheck_after: 0,
+            maintenance_backoff: 0,
+            ..Config::for_tests()
+        }
+    }
+
+    fn test_endpoints() -> Endpoints {
+        Endpoints {
+            reddit: server_url(),
+            reddit_oauth: server_url(),
+            github: server_url(),
+        }
+    }
+
+    fn test_bot() -> Bot {
+        BotBuilder::new()
+            .config(test_config())
+            .endpoints(test_endpoints())
+            .store(Box::new(MemoryStore::default()))
+            .build()
+            .unwrap()
+    }
+
+    fn link_post(name: &str, url: &str) -> String {
+        post(name, "github.com", url)
+    }
+
+    fn post(name: &str, domain: &str, url: &str) -> String {
+        format!(
+            r#"{{"data":{{"name":"{}","domain":"{}","url":"{}"}}}}"#,
+            name, domain, url
+        )
+    }
+
+    fn info_mock(ids: &str, live: &[&str]) -> mockito::Mock {
+        let children: Vec<String> = live
+            .iter()
+            .map(|n| format!(r#"{{"kind":"t3","data":{{"name":"{}","author":"op"}}}}"#, n))
+            .collect();
+        mock("GET", "/api/info")
+            .match_query(Matcher::UrlEncoded("id".to_owned(), ids.to_owned()))
+            .with_body(format!(
+                r#"{{"kind":"Listing","data":{{"children":[{}]}}}}"#,
+                children.join(",")
+            ))
+            .create()
+    }
+
+    /// Mocks for a repository under `/gitref` whose default branch is `main`,
+    /// with or without a license there.
+    fn ref_repo(name: &str, licensed: bool) -> Vec<mockito::Mock> {
+        let repo = format!("/gitref/repos/gitref/{}", name);
+        let license = mock("GET", &*format!("{}/license", repo));
+        vec![
+            mock("GET", &*repo)
+                .with_body(r#"{"default_branch":"main"}"#)
+                .create(),
+            if licensed {
+                license.with_body(r#"{"license":{"spdx_id":"MIT"}}"#)
+            } else {
+                license.with_status(404)
+            }
+            .create(),
+        ]
+    }
+
+    /// A mock listing the files of a repository under `/gitref` at a ref, or
+    /// saying there's no such ref.
+    fn ref_contents(name: &str, git_ref: &str, files: Option<&[&str]>) -> mockito::Mock {
+        let m = mock("GET", &*format!("/gitref/repos/gitref/{}/contents/", name))
+            .match_query(Matcher::UrlEncoded("ref".to_owned(), git_ref.to_owned()));
+        match files {
+            Some(files) => {
+                let entries: Vec<String> = files
+                    .iter()
+                    .map(|f| format!(r#"{{"type":"file","name":"{}"}}"#, f))
+                    .collect();
+                m.with_body(format!("[{}]", entries.join(",")))
+            }
+            None => m.with_status(404),
+        }
+    }
+
+    #[tokio::test]
+    async fn checks_the_ref_a_repository_is_linked_at() {
+        let bot = BotBuilder::new()
+            .config(test_config())
+            .endpoints(Endpoints {
+                reddit: server_url(),
+                reddit_oauth: server_url(),
+                github: format!("{}/gitref", server_url()),
+            })
+            .store(Box::new(MemoryStore::default()))
+            .build()
+            .unwrap();
+        let check = |name: &str, path: &str| {
+            let url = format!("https://github.com/gitref/{}{}", name, path);
+            let repo = GhRepo::new("gitref", name);
+            let bot = &bot;
+            async move { bot.check_link(&repo, &url).await.unwrap().0 }
+        };
+
+        // a branch with a slash in its name adds a license
+        let _repo = ref_repo("added", false);
+        let at_branch = ref_contents("added", "feature/foo", Some(&["README.md", "LICENSE-MIT"]))
+            .expect(2)
+            .create();
+        let longer = ref_contents("added", "feature/foo/src", None).create();
+        let shorter = ref_contents("added", "feature", Some(&[]))
+            .expect(0)
+            .create();
+        assert_eq!(
+            check("added", "/tree/feature/foo").await,
+            License::Found(None)
+        );
+        assert_eq!(
+            check("added", "/blob/feature/foo/src/main.rs?plain=1").await,
+            License::Found(None)
+        );
+        at_branch.assert();
+        longer.assert();
+        shorter.assert();
+
+        // or doesn't
+        let _repo = ref_repo("unlicensed", false);
+        let _contents = ref_contents("unlicensed", "dev", Some(&["README.md"])).create();
+        assert_eq!(check("unlicensed", "/tree/dev").await, License::Missing);
+
+        // a branch that removes the license still counts as licensed
+        let _repo = ref_repo("removed", true);
+        let contents = ref_contents("removed", "dev", Some(&["README.md"]))
+            .expect(0)
+            .create();
+        assert_eq!(
+            check("removed", "/tree/dev").await,
+            License::Found(Some("MIT".to_owned()))
+        );
+        contents.assert();
+
+        // refs that no longer exist fall back to the default branch
+        let _repo = ref_repo("gone", false);
+        let gone = ref_contents("gone", "old/branch", None).create();
+        let shorter = ref_contents("gone", "old", None).create();
+        assert_eq!(check("gone", "/tree/old/branch").await, License::Missing);
+        gone.assert();
+        shorter.assert();
+
+        // which isn't asked about twice
+        let _repo = ref_repo("default", false);
+        let in_dir = ref_contents("default", "main/src", None).create();
+        let default = ref_contents("default", "main", Some(&["LICENSE"]))
+            .expect(0)
+            .create();
+        assert_eq!(check("default", "/tree/main").await, License::Missing);
+        assert_eq!(check("default", "/tree/main/src").await, License::Missing);
+        in_dir.assert();
+        default.assert();
+    }
+
+    #[tokio::test]
+    async fn one_github_check_for_posts_sharing_a_repo() {
+        let listing = format!(
+            r#"{{"data":{{"after":null,"children":[{},{},{}]}}}}"#,
+            link_post("t3_a", "https://github.com/dedup/viral"),
+            link_post(
+                "t3_b",
+                "https://github.com/Dedup/Viral/blob/master/README.md"
+            ),
+            link_post("t3_c", "https://github.com/dedup/viral/"),
+        );
+        let _listing = mock("GET", "/r/dedup/new")
+            .match_query(Matcher::Any)
+            .with_body(listing)
+            .create();
+        let repo = mock("GET", "/repos/dedup/viral")
+            .with_body("{}")
+            .expect(1)
+            .create();
+        let license = mock("GET", "/repos/dedup/viral/license")
+            .with_status(404)
+            .expect(1)
+            .create();
+        let _info = info_mock("t3_a,t3_b,t3_c", &["t3_a", "t3_b", "t3_c"]);
+        let comment = mock("POST", "/api/comment")
+            .match_body(Matcher::UrlEncoded(
+                "thing_id".to_owned(),
+                "t3_a".to_owned(),
+            ))
+            .expect(1)
+            .create();
+
+        let mut bot = test_bot();
+        let caught_up = bot
+            .watch_listing_once(
+                &Listing::subreddits("dedup").unwrap(),
+                &mut Cursor::default(),
+            )
+            .await
+            .unwrap();
+
+        assert!(caught_up);
+        repo.assert();
+        license.assert();
+        comment.assert();
+    }
+
+    #[tokio::test]
+    async fn concurrent_checks_of_a_repo_are_coalesced() {
+        let repo = mock("GET", "/repos/coalesce/repo")
+            .with_body("{}")
+            .expect(1)
+            .create();
+        let license = mock("GET", "/repos/coalesce/repo/license")
+            .with_body("{}")
+            .expect(1)
+            .create();
+
+        let bot = test_bot();
+        let (lower, upper) = (
+            GhRepo::new("coalesce", "repo"),
+            GhRepo::new("Coalesce", "Repo"),
+        );
+        let (a, b) = futures::join!(bot.check_post(&lower), bot.check_post(&upper));
+
+        assert_eq!(a.unwrap(), License::Found(None));
+        assert_eq!(b.unwrap(), License::Found(None));
+        repo.assert();
+        license.assert();
+    }
+
+    #[tokio::test]
+    async fn search_only_checks_github_domain_posts() {
+        let listing = format!(
+            r#"{{"kind":"Listing","data":{{"after":null,"children":[{},{},{}]}}}}"#,
+            link_post("t3_s1", "https://github.com/search/found"),
+            post(
+                "t3_s2",
+                "example.com",
+                "https://example.com/mirror?from=github.com/search/mirrored"
+            ),
+            post(
+                "t3_s3",
+                "self.rust",
+                "https://reddit.com/r/rust/comments/s3"
+            ),
+        );
+        let search = mock("GET", "/search")
+            .match_query(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("q".to_owned(), "site:github.com".to_owned()),
+                Matcher::UrlEncoded("sort".to_owned(), "new".to_owned()),
+                Matcher::UrlEncoded("restrict_sr".to_owned(), "false".to_owned()),
+            ]))
+            .with_body(listing)
+            .create();
+        let found = mock("GET", "/repos/search/found")
+            .with_body("{}")
+            .expect(1)
+            .create();
+        let found_license = mock("GET", "/repos/search/found/license")
+            .with_body("{}")
+            .expect(1)
+            .create();
+        let mirrored = mock("GET", Matcher::Regex("^/repos/search/mirrored".to_owned()))
+            .expect(0)
+            .create();
+
+        let mut bot = test_bot();
+        let listing = Listing::Search {
+            query: "site:github.com".to_owned(),
+            interval: 60,
+        };
+        let mut cursor = Cursor::default();
+        bot.watch_listing_once(&listing, &mut cursor).await.unwrap();
+
+        assert_eq!(cursor.anchor(), Some("t3_s1"));
+        search.assert();
+        found.assert();
+        found_license.assert();
+        mirrored.assert();
+        assert_eq!(bot.processed.fullnames(), vec!["t3_s1", "t3_s2", "t3_s3"]);
+    }
+
+    #[tokio::test]
+    async fn reply_cap_limits_responses() {
+        let listing = format!(
+            r#"{{"data":{{"after":null,"children":[{},{}]}}}}"#,
+            link_post("t3_cap1", "https://github.com/capped/one"),
+            link_post("t3_cap2", "https://github.com/capped/two"),
+        );
+        let _listing = mock("GET", "/r/capped/new")
+            .match_query(Matcher::Any)
+            .with_body(listing)
+            .create();
+        let _repos = mock(
+            "GET",
+            Matcher::Regex("^/repos/capped/(one|two)$".to_owned()),
+        )
+        .with_body("{}")
+        .create();
+        let _licenses = mock(
+            "GET",
+            Matcher::Regex("^/repos/capped/(one|two)/license$".to_owned()),
+        )
+        .with_status(404)
+        .create();
+        let _info = info_mock("t3_cap1,t3_cap2", &["t3_cap1", "t3_cap2"]);
+        let comment = mock("POST", "/api/comment")
+            .match_body(Matcher::Regex("thing_id=t3_cap[12]".to_owned()))
+            .expect(1)
+            .create();
+
+        let mut bot = test_bot();
+        configure(&bot, |c| c.max_replies_per_hour = Some(1));
+        bot.watch_listing_once(
+            &Listing::subreddits("capped").unwrap(),
+            &mut Cursor::default(),
+        )
+        .await
+        .unwrap();
+
+        comment.assert();
+        // the other reply is held back until the cap lets up
+        let held: Vec<&str> = bot.pending.iter().map(|p| p.fullname.as_str()).collect();
+        assert_eq!(held, vec!["t3_cap2"]);
+        assert_eq!(bot.pending[0].kind(), QueueKind::Delayed);
+        assert!(bot.pending[0].stages.capped.is_some());
+    }
+
+    #[tokio::test]
+    async fn posts_of_a_repo_in_other_subreddits_are_coordinated() {
+        for (mode, prefix) in [
+            (DuplicateMode::Skip, "crossskip"),
+            (DuplicateMode::Reply, "crossreply"),
+        ] {
+            let (rust, programming) = (format!("{}a", prefix), format!("{}b", prefix));
+            let post = |name: &str, author: &str, subreddit: &str| {
+                format!(
+                    r#"{{"kind":"t3","data":{{"name":"t3_{}","author":"{}","subreddit":"{}",
+                    "permalink":"/r/{}/comments/{}/x/","domain":"github.com",
+                    "url":"https://github.com/{}/project"}}}}"#,
+                    name, author, subreddit, subreddit, name, prefix
+                )
+            };
+            let listing = |posts: &[String]| {
+                format!(
+                    r#"{{"data":{{"after":null,"children":[{}]}}}}"#,
+                    posts.join(",")
+                )
+            };
+            let first = post(&format!("{}1", prefix), "op", &rust);
+            let second = post(&format!("{}2", prefix), "OP", &programming);
+            let path = format!("/r/{}+{}/new", rust, programming);
+            let _repo = mock("GET", &*format!("/repos/{}/project", prefix))
+                .with_body("{}")
+                .create();
+            let _license = mock("GET", &*format!("/repos/{}/project/license", prefix))
+                .with_status(404)
+                .create();
+            let full = mock("POST", "/api/comment")
+                .match_body(Matcher::Regex(format!(
+                    "thing_id=t3_{}1.*text=The\\+linked",
+                    prefix
+                )))
+                .with_body(r#"{"json":{"data":{"things":[{"data":{"name":"t1_first"}}]}}}"#)
+                .expect(1)
+                .create();
+            let pointer = mock("POST", "/api/comment")
+                .match_body(Matcher::Regex(format!(
+                    "thing_id=t3_{}2.*another\\+subreddit.*{}%2Fcomments%2F{}1%2Fx%2Ffirst%2F",
+                    prefix, rust, prefix
+                )))
+                .expect(usize::from(mode == DuplicateMode::Reply))
+                .create();
+
+            let mut bot = test_bot();
+            configure(&bot, |c| {
+                c.invited_subreddits = vec![rust.clone(), programming.clone()];
+                c.cross_sub_mode = mode;
+            });
+            let watched = Listing::subreddits(&format!("{}+{}", rust, programming)).unwrap();
+            let mut cursor = Cursor::default();
+            // the author posts the repository in one subreddit, then another
+            for posts in [vec![first.clone()], vec![second.clone(), first.clone()]] {
+                let _listing = mock("GET", &*path)
+                    .match_query(Matcher::Any)
+                    .with_body(listing(&posts))
+                    .create();
+                let _info = mock("GET", "/api/info")
+                    .match_query(Matcher::Regex(format!("id=t3_{}", prefix)))
+                    .with_body(listing(&posts))
+                    .create();
+                bot.watch_listing_once(&watched, &mut cursor).await.unwrap();
+            }
+
+            full.assert();
+            pointer.assert();
+            let actions: Vec<String> = bot
+                .core
+                .events
+                .lock()
+                .unwrap()
+                .recent(10)
+                .into_iter()
+                .map(|e| e.action)
+                .collect();
+            let skipped = actions.iter().any(|a| a == "replied in another subreddit");
+            assert_eq!(skipped, mode == DuplicateMode::Skip, "{:?}", actions);
+        }
+    }
+
+    #[tokio::test]
+    async fn reads_listings_publicly_without_credentials() {
+        let listing = r#"{"data":{"after":null,"children":[{"data":{
+            "name":"t3_pub","author":"op","domain":"github.com",
+            "url":"https://github.com/public/scan"
+        }}]}}"#;
+        let token = mock("POST", "/api/v1/access_token").expect(0).create();
+        let page = mock("GET", "/r/publicscan/new.json")
+            .match_query(Matcher::Any)
+            .match_header("authorization", Matcher::Missing)
+            .with_body(listing)
+            .create();
+        let _repo = mock("GET", "/repos/public/scan").with_body("{}").create();
+        let _license = mock("GET", "/repos/public/scan/license")
+            .with_status(404)
+            .create();
+        let info = mock("GET", "/api/info.json")
+            .match_query(Matcher::UrlEncoded("id".to_owned(), "t3_pub".to_owned()))
+            .match_header("authorization", Matcher::Missing)
+            .with_body(
+                r#"{"kind":"Listing","data":{"children":[
+                    {"kind":"t3","data":{"name":"t3_pub","author":"op","subreddit":"publicscan"}}
+                ]}}"#,
+            )
+            .create();
+        let comment = mock("POST", Matcher::Regex("^/api/comment".to_owned()))
+            .expect(0)
+            .create();
+
+        let config = Config {
+            username: String::new(),
+            password: String::new(),
+            client_id: String::new(),
+            client_secret: String::new(),
+            http_listen: Some("127.0.0.1:8080".parse().unwrap()),
+            ..test_config()
+        };
+        let mut bot = BotBuilder::new()
+            .config(config)
+            .endpoints(test_endpoints())
+            .store(Box::new(MemoryStore::default()))
+            .build()
+            .unwrap();
+        bot.login().await.unwrap();
+        let listing = Listing::subreddits("publicscan").unwrap();
+        assert_eq!(bot.poll_interval(&listing), PUBLIC_POLL_INTERVAL);
+        bot.watch_listing_once(&listing, &mut Cursor::default())
+            .await
+            .unwrap();
+
+        token.assert();
+        page.assert();
+        info.assert();
+        comment.assert();
+        assert_eq!(
+            bot.core.events.lock().unwrap().recent(1)[0].action,
+            "report only: no Reddit credentials"
+        );
+    }
+
+    #[tokio::test]
+    async fn aborted_pass_is_not_saved() {
+        let listing_mock = mock("GET", "/aborted/r/aborted/new")
+            .match_query(Matcher::Any)
+            .with_body(format!(
+                r#"{{"data":{{"after":null,"children":[{}]}}}}"#,
+                link_post("t3_aborted", "https://github.com/aborted/repo")
+            ))
+            .create();
+        // GitHub accepts the connection but never answers, so the pass hangs
+        // in the middle of checking the post
+        let hung = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let store = MemoryStore::default();
+        let base = format!("{}/aborted", server_url());
+        let bot = BotBuilder::new()
+            .config(test_config())
+            .endpoints(Endpoints {
+                reddit: base.clone(),
+                reddit_oauth: base,
+                github: format!("http://{}", hung.local_addr().unwrap()),
+            })
+            .store(Box::new(store.clone()))
+            .build()
+            .unwrap();
+        let bot = Rc::new(tokio::sync::Mutex::new(bot));
+        let listing = Listing::subreddits("aborted").unwrap();
+
+        LocalSet::new()
+            .run_until(async {
+                let task = tokio::task::spawn_local({
+                    let bot = bot.clone();
+                    let listing = listing.clone();
+                    async move { bot.lock().await.watch_listing(&listing).await }
+                });
+                while !listing_mock.matched() {
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                }
+                tokio::time::sleep(Duration::from_millis(200)).await;
+                task.abort();
+                assert!(task.await.unwrap_err().is_cancelled());
+            })
+            .await;
+
+        let mut bot = bot.try_lock().unwrap();
+        assert_eq!(bot.processed.fullnames(), vec!["t3_aborted"]);
+        bot.save_checkpoint().unwrap();
+        // the half done pass is thrown away, so the post is looked at again
+        assert!(bot.processed.is_empty());
+        let saved = store.load(&listing.store_key()).unwrap();
+        assert!(saved.processed.is_empty());
+        assert_eq!(saved.cursor, Cursor::default());
+    }
+
+    #[tokio::test]
+    async fn fully_injected_bot_replies() {
+        let listing = format!(
+            r#"{{"data":{{"after":null,"children":[{},{}]}}}}"#,
+            link_post("t3_inj1", "https://github.com/injected/one"),
+            link_post("t3_inj2", "https://github.com/injected/two"),
+        );
+        let _login = mock("POST", "/injected/api/v1/access_token")
+            .with_body(
+                r#"{"access_token":"t","token_type":"bearer","expires_in":3600,"scope":"*"}"#,
+            )
+            .create();
+        let _listing = mock("GET", "/injected/r/injected/new")
+            .match_query(Matcher::Any)
+            .match_header("authorization", "Bearer t")
+            .with_body(listing)
+            .create();
+        let _licenses = mock(
+            "GET",
+            Matcher::Regex("^/injected/repos/injected/(one|two)/license$".to_owned()),
+        )
+        .match_header("user-agent", "injected-github")
+        .with_status(404)
+        .create();
+        let _repos = mock(
+            "GET",
+            Matcher::Regex("^/injected/repos/injected/(one|two)$".to_owned()),
+        )
+        .match_header("user-agent", "injected-github")
+        .with_body("{}")
+        .create();
+        let _info = mock("GET", "/injected/api/info")
+            .match_query(Matcher::Any)
+            .with_body(
+                r#"{"kind":"Listing","data":{"children":[
+                    {"kind":"t3","data":{"name":"t3_inj1","author":"op"}},
+                    {"kind":"t3","data":{"name":"t3_inj2","author":"op"}}]}}"#,
+            )
+            .create();
+        let comment = mock("POST", "/injected/api/comment")
+            .match_header("user-agent", "injected-reddit")
+            .match_header("authorization", "Bearer t")
+            .match_body(Matcher::Regex("thing_id=t3_inj[12]".to_owned()))
+            .expect(1)
+            .create();
+
+        let base = format!("{}/injected", server_url());
+        let store = MemoryStore::default();
+        let clock = ManualClock::new(UNIX_EPOCH + Duration::from_secs(1_600_000_000));
+        let mut config = test_config();
+        config.max_replies_per_hour = Some(1);
+        let build = |config: Config| {
+            BotBuilder::new()
+                .config(config)
+                .reddit_client(
+                    reqwest::Client::builder()
+                        .user_agent("injected-reddit")
+                        .build()
+                        .unwrap(),
+                )
+                .github_client(
+                    reqwest::Client::builder()
+                        .user_agent("injected-github")
+                        .build()
+                        .unwrap(),
+                )
+                .endpoints(Endpoints {
+                    reddit: base.clone(),
+                    reddit_oauth: base.clone(),
+                    github: base.clone(),
+                })
+                .store(Box::new(store.clone()))
+                .clock(Box::new(clock.clone()))
+                .build()
+                .unwrap()
+        };
+        let mut bot = build(config.clone());
+        bot.login().await.unwrap();
+        let listing = Listing::subreddits("injected").unwrap();
+        let key = listing.store_key();
+        let mut cursor = bot.load_state(&key).unwrap();
+        bot.watch_listing_once(&listing, &mut cursor).await.unwrap();
+        bot.save_state(&key, &cursor).unwrap();
+        comment.assert();
+
+        // the reply cap is measured by the injected clock, not by sleeping
+        assert!(bot.reply_cap_reached());
+        clock.advance(Duration::from_secs(61 * 60));
+        assert!(!bot.reply_cap_reached());
+
+        // another bot picks up from the state the first one stored
+        let mut restarted = build(config);
+        restarted.load_state(&key).unwrap();
+        assert_eq!(restarted.processed.fullnames(), vec!["t3_inj1", "t3_inj2"]);
+    }
+
+    #[test]
+    fn refuses_state_stamped_as_another_bots_unless_adopting_it() {
+        let store = MemoryStore::default();
+        let build = || {
+            BotBuilder::new()
+                .config(test_config())
+                .store(Box::new(store.clone()))
+                .build()
+                .unwrap()
+        };
+        let stamped = |account: &str, listing: &str| {
+            let mut state = StateFile {
+                owner: Some(Owner::new(account, listing)),
+                ..StateFile::default()
+            };
+            // undated, so it's taken to have been seen when loaded
+            state.processed.insert("t3_seen", 0);
+            store.clone().save("rust", &state).unwrap();
+        };
+        let owner = || store.load("rust").unwrap().owner;
+
+        // state saved before stamping is taken as this bot's
+        store.clone().save("rust", &StateFile::default()).unwrap();
+        let mut bot = build();
+        bot.load_state("rust").unwrap();
+        bot.save_state("rust", &Cursor::default()).unwrap();
+        assert_eq!(owner(), Some(Owner::new("bot", "rust")));
+
+        // as is state stamped in another case
+        stamped("Bot", "Rust");
+        let mut bot = build();
+        bot.load_state("rust").unwrap();
+        assert_eq!(bot.processed.fullnames(), vec!["t3_seen"]);
+        bot.save_state("rust", &Cursor::default()).unwrap();
+        assert_eq!(owner(), Some(Owner::new("bot", "rust")));
+
+        // but not another account's, or another listing's
+        for (account, listing) in &[("other_bot", "rust"), ("bot", "programming")] {
+            stamped(account, listing);
+            let mut bot = build();
+            let err = bot.load_state("rust").unwrap_err();
+            let foreign = err.downcast_ref::<ForeignState>().unwrap();
+            assert_eq!(foreign.stamp, Owner::new(account, listing));
+            assert!(bot.processed.is_empty());
+            assert_eq!(owner(), Some(Owner::new(account, listing)));
+        }
+
+        // unless it's adopted
+        stamped("other_bot", "rust");
+        let mut bot = build().adopting_state();
+        bot.load_state("rust").unwrap();
+        assert_eq!(bot.processed.fullnames(), vec!["t3_seen"]);
+        bot.save_state("rust", &Cursor::default()).unwrap();
+        assert_eq!(owner(), Some(Owner::new("bot", "rust")));
+        build().load_state("rust").unwrap();
+    }
+
+    #[test]
+    fn forgets_processed_posts_after_the_retention() {
+        const DAY: u64 = 24 * 60 * 60;
+        let now = 1_600_000_000;
+        let store = MemoryStore::default();
+        let clock = ManualClock::new(UNIX_EPOCH + Duration::from_secs(now));
+        let mut config = test_config();
+        config.processed_retention = 90 * DAY;
+        config.processed_max = Some(3);
+        let mut bot = BotBuilder::new()
+            .config(config)
+            .store(Box::new(store.clone()))
+            .clock(Box::new(clock.clone()))
+            .build()
+            .unwrap();
+        let saved = StateFile {
+            processed: serde_json::from_str(
+                r#"["t3_migrated", {"fullname": "t3_stale", "seen_at": 1500000000},
+                    {"fullname": "t3_recent", "seen_at": 1599990000}]"#,
+            )
+            .unwrap(),
+            ..StateFile::default()
+        };
+        store.clone().save("retained", &saved).unwrap();
+
+        // on load, undated posts count as seen now and stale ones are dropped
+        bot.load_state("retained").unwrap();
+        assert_eq!(bot.processed.fullnames(), vec!["t3_migrated", "t3_recent"]);
+        assert_eq!(bot.processed.seen_at("t3_migrated"), Some(now));
+
+        // while running, the cap is reached before the retention is
+        clock.advance(Duration::from_secs(DAY));
+        bot.processed.insert("t3_new1", now + DAY);
+        bot.processed.insert("t3_new2", now + DAY);
+        bot.save_state("retained", &Cursor::default()).unwrap();
+        assert_eq!(
+            store.load("retained").unwrap().processed.fullnames(),
+            vec!["t3_migrated", "t3_new1", "t3_new2"]
+        );
+
+        // and then the retention is reached before the cap
+        clock.advance(Duration::from_secs(90 * DAY));
+        bot.save_state("retained", &Cursor::default()).unwrap();
+        assert_eq!(
+            store.load("retained").unwrap().processed.fullnames(),
+            vec!["t3_new1", "t3_new2"]
+        );
+        assert_eq!(bot.processed.len(), 2);
+    }
+
+    #[test]
+    fn compacts_memory_every_few_passes() {
+        const DAY: u64 = 24 * 60 * 60;
+        let now = 1_600_000_000;
+        let clock = ManualClock::new(UNIX_EPOCH + Duration::from_secs(now));
+        let mut config = test_config();
+        config.compact_every = 3;
+        config.processed_retention = 30 * DAY;
+        let mut bot = BotBuilder::new()
+            .config(config)
+            .store(Box::new(MemoryStore::default()))
+            .clock(Box::new(clock.clone()))
+            .build()
+            .unwrap();
+
+        // months of posts, rules cached long ago and replies made in the last hour
+        let seen: Vec<serde_json::Value> = (0..100_000)
+            .map(|i| serde_json::json!({"fullname": format!("t3_{:06}", i), "seen_at": now + i / 1000 * DAY}))
+            .collect();
+        bot.processed = serde_json::from_value(serde_json::Value::Array(seen)).unwrap();
+        for i in 0..2_000 {
+            bot.subreddit_verdicts.insert(
+                format!("old{}", i),
+                (
+                    Verdict::ReportOnly("bots banned".to_owned()),
+                    bot.core.clock.now(),
+                ),
+            );
+        }
+        bot.core.replies.lock().unwrap().push(bot.core.clock.now());
+        clock.advance(Duration::from_secs(100 * DAY));
+        bot.subreddit_verdicts
+            .insert("new".to_owned(), (Verdict::Allowed, bot.core.clock.now()));
+        bot.core.replies.lock().unwrap().push(bot.core.clock.now());
+        let before = bot.heap_size();
+
+        bot.compact_if_due();
+        bot.compact_if_due();
+        assert_eq!(bot.processed.len(), 100_000);
+        assert_eq!(bot.core.events.lock().unwrap().compactions().runs, 0);
+
+        bot.compact_if_due();
+        // posts seen in the last 30 days of the 100 are kept
+        assert_eq!(bot.processed.len(), 30_000);
+        assert_eq!(bot.subreddit_verdicts.len(), 1);
+        assert_eq!(bot.core.replies.lock().unwrap().len(), 1);
+        let compactions = bot.core.events.lock().unwrap().compactions();
+        assert_eq!(compactions.runs, 1);
+        assert_eq!(compactions.evicted, 70_000 + 2_000 + 1);
+        assert_eq!(
+            compactions.reclaimed_bytes,
+            (before - bot.heap_size()) as u64
+        );
+        assert!(
+            compactions.reclaimed_bytes > 70_000 * 32,
+            "{:?}",
+            compactions
+        );
+        assert!(bot
+            .summary()
+            .contains("1 compactions, 72001 entries evicted"));
+
+        // nothing left to evict the next time
+        for _ in 0..3 {
+            bot.compact_if_due();
+        }
+        let compactions = bot.core.events.lock().unwrap().compactions();
+        assert_eq!((compactions.runs, compactions.evicted), (2, 72_001));
+    }
+
+    /// Watch a listing of link posts with the given issue mode, against
+    /// mocks under `/{prefix}`.
+    async fn watch_with_issues(
+        prefix: &str,
+        mode: IssueMode,
+        store: &MemoryStore,
+        posts: &[(&str, &str)],
+    ) -> Bot {
+        let children: Vec<String> = posts
+            .iter()
+            .map(|(name, repo)| link_post(name, &format!("https://github.com/{}", repo)))
+            .collect();
+        let live: Vec<String> = posts
+            .iter()
+            .map(|(name, _)| {
+                format!(
+                    r#"{{"kind":"t3","data":{{"name":"{}","author":"op"}}}}"#,
+                    name
+                )
+            })
+            .collect();
+        // subreddits can't have dashes in their names, unlike the prefixes
+        let subreddit = prefix.replace('-', "_");
+        let _listing = mock("GET", format!("/{}/r/{}/new", prefix, subreddit).as_str())
+            .match_query(Matcher::Any)
+            .with_body(format!(
+                r#"{{"data":{{"after":null,"children":[{}]}}}}"#,
+                children.join(",")
+            ))
+            .create();
+        let _info = mock("GET", format!("/{}/api/info", prefix).as_str())
+            .match_query(Matcher::Any)
+            .with_body(format!(
+                r#"{{"kind":"Listing","data":{{"children":[{}]}}}}"#,
+                live.join(",")
+            ))
+            .create();
+        let _licenses = mock(
+            "GET",
+            Matcher::Regex(format!("^/{}/repos/[^/]+/[^/]+/license$", prefix)),
+        )
+        .with_status(404)
+        .create();
+
+        let base = format!("{}/{}", server_url(), prefix);
+        let mut config = test_config();
+        config.github_issue_mode = mode;
+        config.github_token = Some("ghtoken".to_owned());
+        config.github_issue_max_stars = 1000;
+        // so it's the ceiling on issues that's reached, not on replies
+        config.max_stars = 10_000;
+        let mut bot = BotBuilder::new()
+            .config(config)
+            .endpoints(Endpoints {
+                reddit: base.clone(),
+                reddit_oauth: base.clone(),
+                github: base,
+            })
+            .store(Box::new(store.clone()))
+            .build()
+            .unwrap();
+        bot.watch_listing_once(
+            &Listing::subreddits(&subreddit).unwrap(),
+            &mut Cursor::default(),
+        )
+        .await
+        .unwrap();
+        bot
+    }
+
+    #[tokio::test]
+    async fn opens_issues_instead_of_replying() {
+        let _repos = [
+            ("open", r#"{"has_issues":true,"stargazers_count":3}"#),
+            ("closed", r#"{"has_issues":false,"stargazers_count":3}"#),
+            ("popular", r#"{"has_issues":true,"stargazers_count":5000}"#),
+        ]
+        .iter()
+        .map(|(repo, body)| {
+            mock("GET", format!("/issues/repos/someone/{}", repo).as_str())
+                .with_body(*body)
+                .create()
+        })
+        .collect::<Vec<_>>();
+        let issue = mock("POST", "/issues/repos/someone/open/issues")
+            .match_header("authorization", "token ghtoken")
+            .match_body(Matcher::PartialJsonString(
+                r#"{"title":"Consider adding a LICENSE file"}"#.to_owned(),
+            ))
+            .with_status(201)
+            .with_body(r#"{"number":7,"html_url":"https://github.com/someone/open/issues/7"}"#)
+            .expect(1)
+            .create();
+        let skipped = mock(
+            "POST",
+            Matcher::Regex("^/issues/repos/someone/(closed|popular)/issues$".to_owned()),
+        )
+        .expect(0)
+        .create();
+        let comment = mock("POST", "/issues/api/comment").expect(0).create();
+
+        let store = MemoryStore::default();
+        let mut bot = watch_with_issues(
+            "issues",
+            IssueMode::Instead,
+            &store,
+            &[
+                ("t3_iss1", "someone/open"),
+                ("t3_iss2", "someone/closed"),
+                ("t3_iss3", "someone/popular"),
+            ],
+        )
+        .await;
+        let mut actions: Vec<(String, String)> = bot
+            .core
+            .events
+            .lock()
+            .unwrap()
+            .recent(10)
+            .into_iter()
+            .map(|e| (e.repo, e.action))
+            .collect();
+        actions.sort();
+        assert_eq!(
+            actions,
+            vec![
+                ("someone/closed".to_owned(), "issues disabled".to_owned()),
+                ("someone/open".to_owned(), "opened issue".to_owned()),
+                (
+                    "someone/popular".to_owned(),
+                    "too many stars for an issue".to_owned()
+                ),
+            ]
+        );
+        let filed = store.issues().unwrap();
+        assert_eq!(filed.len(), 1);
+        assert_eq!(filed[0].repo, "someone/open");
+        assert_eq!(filed[0].number, 7);
+        assert_eq!(filed[0].post, "t3_iss1");
+
+        // a later post linking the same repository doesn't get a second issue
+        let again = PendingReply {
+            fullname: "t3_iss4".to_owned(),
+            repos: vec![RepoLink {
+                repo: GhRepo {
+                    owner: "Someone".to_owned(),
+                    name: "Open".to_owned(),
+                },
+                url: "https://github.com/Someone/Open".to_owned(),
+                removed_license: None,
+                summary: RepoSummary::default(),
+            }],
+            details: PostDetails::default(),
+            queued_at: 0,
+            deferred: false,
+            earlier: None,
+            cross_post: None,
+            check_after: None,
+            not_before: None,
+            stages: Stages::default(),
+        };
+        bot.deliver(&again).await.unwrap();
+        assert_eq!(
+            bot.core.events.lock().unwrap().recent(1)[0].action,
+            "issue already opened"
+        );
+        issue.assert();
+        skipped.assert();
+        comment.assert();
+        assert!(store.replies().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn opens_issues_as_well_as_replying() {
+        let _repo = mock("GET", "/issues-also/repos/someone/both")
+            .with_body(r#"{"has_issues":true,"stargazers_count":0}"#)
+            .create();
+        let issue = mock("POST", "/issues-also/repos/someone/both/issues")
+            .match_header("authorization", "token ghtoken")
+            .with_status(201)
+            .with_body(r#"{"number":1,"html_url":"https://github.com/someone/both/issues/1"}"#)
+            .expect(1)
+            .create();
+        let comment = mock("POST", "/issues-also/api/comment")
+            .match_body(Matcher::UrlEncoded(
+                "thing_id".to_owned(),
+                "t3_also1".to_owned(),
+            ))
+            .expect(1)
+            .create();
+
+        let store = MemoryStore::default();
+        watch_with_issues(
+            "issues-also",
+            IssueMode::Also,
+            &store,
+            &[("t3_also1", "someone/both")],
+        )
+        .await;
+        issue.assert();
+        comment.assert();
+        assert_eq!(store.issues().unwrap().len(), 1);
+        assert_eq!(store.replies().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn issue_failures_are_recorded() {
+        let _repo = mock("GET", "/issues-fail/repos/someone/locked")
+            .with_body(r#"{"has_issues":true,"stargazers_count":0}"#)
+            .create();
+        let _issue = mock("POST", "/issues-fail/repos/someone/locked/issues")
+            .with_status(410)
+            .create();
+
+        let store = MemoryStore::default();
+        let bot = watch_with_issues(
+            "issues-fail",
+            IssueMode::Instead,
+            &store,
+            &[("t3_fail1", "someone/locked")],
+        )
+        .await;
+        let action = bot.core.events.lock().unwrap().recent(1)[0].action.clone();
+        assert!(action.starts_with("issue failed: "), "{}", action);
+        assert!(store.issues().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn checks_wait_for_the_github_quota() {
+        let now = 1_600_000_000;
+        let reset = (now + 60 * 60).to_string();
+        let github = |path: &str, status: usize, remaining: &str| {
+            mock("GET", path)
+                .with_status(status)
+                .with_header("x-ratelimit-remaining", remaining)
+                .with_header("x-ratelimit-limit", "60")
+                .with_header("x-ratelimit-reset", &reset)
+                .with_body("{}")
+        };
+        let _first = github("/quota/repos/someone/first", 200, "4").create();
+        let _first_license = github("/quota/repos/someone/first/license", 404, "3").create();
+        let second = github("/quota/repos/someone/second", 200, "59").create();
+        let _second_license = github("/quota/repos/someone/second/license", 404, "58").create();
+        let _info = mock("GET", "/quota/api/info")
+            .match_query(Matcher::Any)
+            .with_body(
+                r#"{"kind":"Listing","data":{"children":[
+                    {"kind":"t3","data":{"name":"t3_quota1","author":"op"}},
+                    {"kind":"t3","data":{"name":"t3_quota2","author":"op"}}]}}"#,
+            )
+            .create();
+        let comment = mock("POST", "/quota/api/comment")
+            .match_body(Matcher::Regex("thing_id=t3_quota[12]".to_owned()))
+            .expect(2)
+            .create();
+
+        let clock = ManualClock::new(UNIX_EPOCH + Duration::from_secs(now));
+        let base = format!("{}/quota", server_url());
+        let mut bot = BotBuilder::new()
+            .config(test_config())
+            .endpoints(Endpoints {
+                reddit: base.clone(),
+                reddit_oauth: base.clone(),
+                github: base,
+            })
+            .store(Box::new(MemoryStore::default()))
+            .clock(Box::new(clock.clone()))
+            .build()
+            .unwrap();
+        let listing = Listing::subreddits("quota").unwrap();
+        let page = |name: &str, repo: &str| -> Vec<serde_json::Value> {
+            vec![serde_json::from_str(&link_post(
+                name,
+                &format!("https://github.com/someone/{}", repo),
+            ))
+            .unwrap()]
+        };
+
+        // this check uses up the quota down to the floor
+        bot.process_page(&listing, &page("t3_quota1", "first"))
+            .await
+            .unwrap();
+        assert!(bot.github_quota_low());
+        let summary = bot.summary();
+        assert!(
+            summary.contains("1 replies in the last hour, nothing queued, 0 dropped"),
+            "{}",
+            summary
+        );
+        assert!(
+            summary.contains("3 of 60 GitHub requests left"),
+            "{}",
+            summary
+        );
+
+        // so the next post's check is deferred
+        bot.process_page(&listing, &page("t3_quota2", "second"))
+            .await
+            .unwrap();
+        assert!(!second.matched());
+        assert_eq!(bot.pending.len(), 1);
+        assert!(bot.pending[0].deferred);
+        bot.drain_pending(&mut Budget::new(0)).await.unwrap();
+        assert!(!second.matched());
+        assert_eq!(bot.pending.len(), 1);
+
+        // and made once the limit resets, with the reply held back until the
+        // next pass, as re-checks come after the delayed replies
+        clock.advance(Duration::from_secs(60 * 60));
+        assert!(!bot.github_quota_low());
+        bot.drain_pending(&mut Budget::new(0)).await.unwrap();
+        assert!(second.matched());
+        assert_eq!(bot.pending[0].kind(), QueueKind::Delayed);
+        bot.drain_pending(&mut Budget::new(0)).await.unwrap();
+        assert!(bot.pending.is_empty());
+        comment.assert();
+        assert!(!bot.github_quota_low());
+    }
+
+    #[tokio::test]
+    async fn empty_repositories_are_checked_again_later() {
+        for (prefix, still_empty) in [("emptyrepo", true), ("filledrepo", false)] {
+            let now = 1_600_000_000;
+            let repo_path = format!("/{}/repos/someone/project", prefix);
+            let mut empty = Some(
+                mock("GET", repo_path.as_str())
+                    .with_body(r#"{"size":0}"#)
+                    .expect(if still_empty { 2 } else { 1 })
+                    .create(),
+            );
+            let contents = mock("GET", format!("{}/contents/", repo_path).as_str())
+                .with_status(409)
+                .with_body(r#"{"message":"Git Repository is empty."}"#)
+                .expect(if still_empty { 2 } else { 1 })
+                .create();
+            let _info = mock("GET", format!("/{}/api/info", prefix).as_str())
+                .match_query(Matcher::Any)
+                .with_body(format!(
+                    r#"{{"data":{{"children":[{{"data":{{"name":"t3_{}","author":"op"}}}}]}}}}"#,
+                    prefix
+                ))
+                .create();
+            let comment = mock("POST", format!("/{}/api/comment", prefix).as_str())
+                .expect(if still_empty { 0 } else { 1 })
+                .create();
+
+            let clock = ManualClock::new(UNIX_EPOCH + Duration::from_secs(now));
+            let base = format!("{}/{}", server_url(), prefix);
+            let mut bot = BotBuilder::new()
+                .config(Config {
+                    invited_subreddits: vec![prefix.to_owned()],
+                    ..test_config()
+                })
+                .endpoints(Endpoints {
+                    reddit: base.clone(),
+                    reddit_oauth: base.clone(),
+                    github: base,
+                })
+                .store(Box::new(MemoryStore::default()))
+                .clock(Box::new(clock.clone()))
+                .build()
+                .unwrap();
+            let listing = Listing::subreddits(prefix).unwrap();
+            let post = link_post(
+                &format!("t3_{}", prefix),
+                "https://github.com/someone/project",
+            );
+            bot.process_page(&listing, &[serde_json::from_str(&post).unwrap()])
+                .await
+                .unwrap();
+            assert_eq!(bot.pending.len(), 1);
+            assert!(bot.pending[0].deferred);
+            assert_eq!(bot.pending[0].check_after, Some(now + 3 * 60 * 60));
+            {
+                let events = bot.core.events.lock().unwrap();
+                let event = &events.recent(1)[0];
+                assert_eq!(
+                    (event.verdict.as_str(), event.action.as_str()),
+                    ("indeterminate", "deferred")
+                );
+                assert_eq!(events.stats().clone().subreddit(prefix).unlicensed, 0);
+            }
+
+            // not checked again until a few hours have passed
+            bot.drain_pending(&mut Budget::new(0)).await.unwrap();
+            assert_eq!(bot.pending.len(), 1);
+
+            let _filled = if still_empty {
+                None
+            } else {
+                empty.take().unwrap().assert();
+                Some((
+                    mock("GET", repo_path.as_str())
+                        .with_body(r#"{"size":1}"#)
+                        .expect(1)
+                        .create(),
+                    mock("GET", format!("{}/license", repo_path).as_str())
+                        .with_status(404)
+                        .create(),
+                ))
+            };
+            clock.advance(Duration::from_secs(3 * 60 * 60));
+            bot.drain_pending(&mut Budget::new(0)).await.unwrap();
+            assert!(bot.pending.is_empty());
+            contents.assert();
+            comment.assert();
+            let action = bot.core.events.lock().unwrap().recent(1)[0].action.clone();
+            if still_empty {
+                // only tried once more
+                empty.unwrap().assert();
+                assert_eq!(action, "empty repository");
+            } else {
+                assert_eq!(action, "replied");
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn repositories_missing_from_young_posts_are_looked_for_once_more() {
+        // the post's age when checked, and whether the repository is public
+        // ten minutes later
+        for (prefix, age, made_public) in [
+            ("publicnow", 60, true),
+            ("nevermade", 60, false),
+            ("oldmissing", 60 * 60, false),
+        ] {
+            let now = 1_600_000_000;
+            let repo_path = format!("/{}/repos/someone/project", prefix);
+            let young = age < 15 * 60;
+            let mut missing = Some(
+                mock("GET", repo_path.as_str())
+                    .with_status(404)
+                    .with_body(r#"{"message":"Not Found"}"#)
+                    .expect(if young && !made_public { 2 } else { 1 })
+                    .create(),
+            );
+            let _info = mock("GET", format!("/{}/api/info", prefix).as_str())
+                .match_query(Matcher::Any)
+                .with_body(format!(
+                    r#"{{"data":{{"children":[{{"data":{{"name":"t3_{}","author":"op"}}}}]}}}}"#,
+                    prefix
+                ))
+                .create();
+            let comment = mock("POST", format!("/{}/api/comment", prefix).as_str())
+                .expect(if made_public { 1 } else { 0 })
+                .create();
+
+            let clock = ManualClock::new(UNIX_EPOCH + Duration::from_secs(now));
+            let base = format!("{}/{}", server_url(), prefix);
+            let mut bot = BotBuilder::new()
+                .config(Config {
+                    invited_subreddits: vec![prefix.to_owned()],
+                    ..test_config()
+                })
+                .endpoints(Endpoints {
+                    reddit: base.clone(),
+                    reddit_oauth: base.clone(),
+                    github: base,
+                })
+                .store(Box::new(MemoryStore::default()))
+                .clock(Box::new(clock.clone()))
+                .build()
+                .unwrap();
+            let listing = Listing::subreddits(prefix).unwrap();
+            let post = format!(
+                r#"{{"data":{{"name":"t3_{0}","subreddit":"{0}","domain":"github.com",
+                   "created_utc":{1}.0,"url":"https://github.com/someone/project"}}}}"#,
+                prefix,
+                now - age
+            );
+            bot.process_page(&listing, &[serde_json::from_str(&post).unwrap()])
+                .await
+                .unwrap();
+            if !young {
+                // an old post's link is given up on straight away, as before
+                missing.unwrap().assert();
+                assert!(bot.pending.is_empty());
+                assert!(bot.core.events.lock().unwrap().recent(1).is_empty());
+                continue;
+            }
+            assert_eq!(bot.pending.len(), 1);
+            assert_eq!(bot.pending[0].check_after, Some(now + 10 * 60));
+            {
+                let events = bot.core.events.lock().unwrap();
+                let event = &events.recent(1)[0];
+                assert_eq!(
+                    (event.verdict.as_str(), event.action.as_str()),
+                    ("indeterminate", "deferred")
+                );
+                assert_eq!(events.stats().clone().subreddit(prefix).errors, 0);
+            }
+
+            // not looked for again until ten minutes have passed
+            bot.drain_pending(&mut Budget::new(0)).await.unwrap();
+            assert_eq!(bot.pending.len(), 1);
+
+            let _public = if made_public {
+                missing.take().unwrap().assert();
+                Some((
+                    mock("GET", repo_path.as_str())
+                        .with_body(r#"{"size":1}"#)
+                        .expect(1)
+                        .create(),
+                    mock("GET", format!("{}/license", repo_path).as_str())
+                        .with_status(404)
+                        .create(),
+                ))
+            } else {
+                None
+            };
+            clock.advance(Duration::from_secs(10 * 60));
+            bot.drain_pending(&mut Budget::new(0)).await.unwrap();
+            // with a reply held back to the next pass, like any other
+            bot.drain_pending(&mut Budget::new(0)).await.unwrap();
+            assert!(bot.pending.is_empty());
+            comment.assert();
+            let events = bot.core.events.lock().unwrap();
+            let action = events.recent(1)[0].action.clone();
+            if made_public {
+                assert_eq!(action, "replied");
+                // timed from when the post was submitted
+                assert_eq!(events.latencies().summary().max, Some(age + 10 * 60));
+            } else {
+                // only looked for once more
+                missing.unwrap().assert();
+                assert_eq!(action, "not a repository");
+                let skips = events.stats().clone().subreddit(prefix).skips.clone();
+                assert_eq!(skips.get("not a repository"), Some(&1), "{:?}", skips);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn weekly_digest() {
+        let tmp = tempfile::tempdir().unwrap();
+        // 2021-06-07 was a Monday
+        let monday = 1_623_056_400;
+        let mut log = EventLog::open(tmp.path()).unwrap();
+        let found = |at: u64, fullname: &str, repo: &str| Event {
+            at,
+            fullname: fullname.to_owned(),
+            title: format!("Post {}", fullname),
+            subreddit: "rust".to_owned(),
+            permalink: String::new(),
+            author: "op".to_owned(),
+            repo: repo.to_owned(),
+            verdict: "unlicensed".to_owned(),
+            license: None,
+            action: "replied".to_owned(),
+            removed_license: None,
+            detection_path: vec![],
+        };
+        log.record(found(monday + 60, "t3_dg1", "digest/still"));
+        log.record(found(monday + 120, "t3_dg2", "digest/fixed"));
+        log.record(found(monday + 180, "t3_dg3", "digest/still"));
+        let _repos = mock(
+            "GET",
+            Matcher::Regex("^/digest/repos/digest/(still|fixed)$".to_owned()),
+        )
+        .with_body("{}")
+        .create();
+        let still = mock("GET", "/digest/repos/digest/still/license")
+            .with_status(404)
+            .expect(1)
+            .create();
+        let _fixed = mock("GET", "/digest/repos/digest/fixed/license")
+            .with_body(r#"{"license":{"spdx_id":"MIT"}}"#)
+            .create();
+        let comment = mock("POST", "/digest/api/comment")
+            .match_body(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("thing_id".to_owned(), "t3_thread".to_owned()),
+                Matcher::Regex("3\\+posts\\+linked".to_owned()),
+                Matcher::Regex("digest%2Fstill%29\\+%7C\\+yes".to_owned()),
+                Matcher::Regex("digest%2Ffixed%29\\+%7C\\+no".to_owned()),
+            ]))
+            .expect(1)
+            .create();
+        let modmail = mock("POST", "/digest/api/compose")
+            .match_body(Matcher::UrlEncoded("to".to_owned(), "/r/rust".to_owned()))
+            .expect(1)
+            .create();
+
+        let clock = ManualClock::new(UNIX_EPOCH + Duration::from_secs(monday + 30 * 60));
+        let mut config = test_config();
+        config.state_dir = tmp.path().to_owned();
+        config.digest_at = Some("Mon 09:00".parse().unwrap());
+        config.digest_thread = Some("thread".to_owned());
+        config.digest_modmail = Some("rust".to_owned());
+        let base = format!("{}/digest", server_url());
+        let mut bot = BotBuilder::new()
+            .config(config)
+            .endpoints(Endpoints {
+                reddit: base.clone(),
+                reddit_oauth: base.clone(),
+                github: base,
+            })
+            .store(Box::new(MemoryStore::default()))
+            .clock(Box::new(clock.clone()))
+            .build()
+            .unwrap();
+
+        // the first digest is only scheduled
+        bot.post_digest_if_due().await;
+        assert_eq!(digest::last_posted(tmp.path()).unwrap(), monday);
+
+        clock.advance(Duration::from_secs(digest::WEEK));
+        bot.post_digest_if_due().await;
+        // and it's only posted once
+        bot.post_digest_if_due().await;
+        comment.assert();
+        modmail.assert();
+        still.assert();
+        assert_eq!(
+            digest::last_posted(tmp.path()).unwrap(),
+            monday + digest::WEEK
+        );
+    }
+
+    #[tokio::test]
+    async fn builder_needs_a_config() {
+        assert!(BotBuilder::new().build().is_err());
+    }
+
+    /// A window of active hours around, or away from, the current time.
+    fn active_hours(open_now: bool) -> ActiveHours {
+        let minute = (events::now() / 60 % (24 * 60)) as i64;
+        let (start, end) = if open_now {
+            (minute - 10, minute + 10)
+        } else {
+            (minute + 10, minute + 20)
+        };
+        let clock = |m: i64| format!("{:02}:{:02}", m.rem_euclid(1440) / 60, m.rem_euclid(60));
+        format!("{}-{}", clock(start), clock(end)).parse().unwrap()
+    }
+
+    #[tokio::test]
+    async fn replies_outside_active_hours_are_held_back() {
+        let listing = format!(
+            r#"{{"data":{{"after":null,"children":[{},{}]}}}}"#,
+            link_post("t3_quiet1", "https://github.com/quiet/one"),
+            link_post("t3_quiet2", "https://github.com/quiet/two"),
+        );
+        let _listing = mock("GET", "/r/quiet/new")
+            .match_query(Matcher::Any)
+            .with_body(listing)
+            .create();
+        let _repos = mock("GET", Matcher::Regex("^/repos/quiet/(one|two)$".to_owned()))
+            .with_body("{}")
+            .create();
+        let _licenses = mock(
+            "GET",
+            Matcher::Regex("^/repos/quiet/(one|two)/license$".to_owned()),
+        )
+        .with_status(404)
+        .create();
+        let _info = info_mock("t3_quiet1,t3_quiet2", &["t3_quiet1"]);
+        let _held_info = info_mock("t3_quiet1", &["t3_quiet1"]);
+        let comment = mock("POST", "/api/comment")
+            .match_body(Matcher::Regex("thing_id=t3_quiet".to_owned()))
+            .expect(0)
+            .create();
+
+        let mut bot = test_bot();
+        configure(&bot, |c| c.active_hours = Some(active_hours(false)));
+        bot.watch_listing_once(
+            &Listing::subreddits("quiet").unwrap(),
+            &mut Cursor::default(),
+        )
+        .await
+        .unwrap();
+        comment.assert();
+        let held: Vec<&str> = bot.pending.iter().map(|p| p.fullname.as_str()).collect();
+        assert_eq!(held, vec!["t3_quiet1"]);
+        let actions: Vec<(String, String)> = bot
+            .core
+            .events
+            .lock()
+            .unwrap()
+            .recent(2)
+            .into_iter()
+            .map(|e| (e.fullname, e.action))
+            .collect();
+        assert!(actions.contains(&("t3_quiet1".to_owned(), "held for active hours".to_owned())));
+        assert!(actions.contains(&("t3_quiet2".to_owned(), "post removed".to_owned())));
+
+        // nothing is made while the window is still closed
+        bot.drain_pending(&mut Budget::new(0)).await.unwrap();
+        assert_eq!(bot.pending.len(), 1);
+
+        let comment = mock("POST", "/api/comment")
+            .match_body(Matcher::UrlEncoded(
+                "thing_id".to_owned(),
+                "t3_quiet1".to_owned(),
+            ))
+            .expect(1)
+            .create();
+        configure(&bot, |c| c.active_hours = Some(active_hours(true)));
+        bot.drain_pending(&mut Budget::new(0)).await.unwrap();
+        comment.assert();
+        assert!(bot.pending.is_empty());
+        let event = &bot.core.events.lock().unwrap().recent(1)[0];
+        assert_eq!(
+            (event.fullname.as_str(), event.action.as_str()),
+            ("t3_quiet1", "replied")
+        );
+    }
+
+    #[tokio::test]
+    async fn replies_while_paused_are_held_back() {
+        let listing = format!(
+            r#"{{"data":{{"after":null,"children":[{}]}}}}"#,
+            link_post("t3_paused1", "https://github.com/paused/one"),
+        );
+        let _listing = mock("GET", "/r/paused/new")
+            .match_query(Matcher::Any)
+            .with_body(listing)
+            .create();
+        let _repo = mock("GET", "/repos/paused/one").with_body("{}").create();
+        let _license = mock("GET", "/repos/paused/one/license")
+            .with_status(404)
+            .create();
+        let _info = info_mock("t3_paused1", &["t3_paused1"]);
+        let comment = mock("POST", "/api/comment")
+            .match_body(Matcher::Regex("thing_id=t3_paused".to_owned()))
+            .expect(0)
+            .create();
+
+        let mut bot = test_bot();
+        bot.core.paused.store(true, Ordering::SeqCst);
+        bot.watch_listing_once(
+            &Listing::subreddits("paused").unwrap(),
+            &mut Cursor::default(),
+        )
+        .await
+        .unwrap();
+        comment.assert();
+        let held: Vec<&str> = bot.pending.iter().map(|p| p.fullname.as_str()).collect();
+        assert_eq!(held, vec!["t3_paused1"]);
+        let event = &bot.core.events.lock().unwrap().recent(1)[0];
+        assert_eq!(event.action, "held while paused");
+
+        // nothing is made until replies resume
+        bot.drain_pending(&mut Budget::new(0)).await.unwrap();
+        assert_eq!(bot.pending.len(), 1);
+
+        let comment = mock("POST", "/api/comment")
+            .match_body(Matcher::UrlEncoded(
+                "thing_id".to_owned(),
+                "t3_paused1".to_owned(),
+            ))
+            .expect(1)
+            .create();
+        bot.core.paused.store(false, Ordering::SeqCst);
+        bot.drain_pending(&mut Budget::new(0)).await.unwrap();
+        comment.assert();
+        assert!(bot.pending.is_empty());
+    }
+
+    #[tokio::test]
+    async fn works_in_progress_are_followed_up_on() {
+        let wip_post = |name: &str, repo: &str, title: &str, flair: &str| {
+            format!(
+                r#"{{"data":{{"name":"{}","domain":"github.com","url":"https://github.com/wipfollow/{}","title":"{}","link_flair_text":"{}"}}}}"#,
+                name, repo, title, flair
+            )
+        };
+        let listing = format!(
+            r#"{{"data":{{"after":null,"children":[{},{}]}}}}"#,
+            wip_post("t3_wipone", "one", "[WIP] one", ""),
+            wip_post("t3_wiptwo", "two", "Two", "Prototype"),
+        );
+        let _listing = mock("GET", "/r/wipfollow/new")
+            .match_query(Matcher::Any)
+            .with_body(listing)
+            .create();
+        let _repos = mock(
+            "GET",
+            Matcher::Regex("^/repos/wipfollow/(one|two)$".to_owned()),
+        )
+        .with_body("{}")
+        .create();
+        let unlicensed = mock(
+            "GET",
+            Matcher::Regex("^/repos/wipfollow/(one|two)/license$".to_owned()),
+        )
+        .with_status(404)
+        .create();
+        let _info = info_mock("t3_wipone,t3_wiptwo", &["t3_wipone", "t3_wiptwo"]);
+        let comment = mock("POST", "/api/comment")
+            .match_body(Matcher::Regex("thing_id=t3_wip".to_owned()))
+            .expect(0)
+            .create();
+
+        let start = 1_600_000_000;
+        let clock = ManualClock::new(UNIX_EPOCH + Duration::from_secs(start));
+        let mut config = test_config();
+        config.wip_phrases = vec!["wip".to_owned(), "prototype".to_owned()];
+        config.wip_followup_days = 7;
+        let mut bot = BotBuilder::new()
+            .config(config)
+            .endpoints(test_endpoints())
+            .store(Box::new(MemoryStore::default()))
+            .clock(Box::new(clock.clone()))
+            .build()
+            .unwrap();
+        bot.watch_listing_once(
+            &Listing::subreddits("wipfollow").unwrap(),
+            &mut Cursor::default(),
+        )
+        .await
+        .unwrap();
+        comment.assert();
+        let held: Vec<(&str, Option<u64>)> = bot
+            .pending
+            .iter()
+            .map(|p| (p.fullname.as_str(), p.not_before))
+            .collect();
+        let due = Some(start + 7 * 24 * 60 * 60);
+        assert_eq!(held, [("t3_wipone", due), ("t3_wiptwo", due)]);
+        assert!(bot.pending.iter().all(|p| p.kind() == QueueKind::Delayed));
+        let actions: Vec<String> = bot
+            .core
+            .events
+            .lock()
+            .unwrap()
+            .recent(2)
+            .into_iter()
+            .map(|e| e.action)
+            .collect();
+        assert_eq!(actions, ["held as work in progress"; 2]);
+
+        // nothing is made before the follow-ups are due
+        clock.advance(Duration::from_secs(6 * 24 * 60 * 60));
+        bot.drain_pending(&mut Budget::new(0)).await.unwrap();
+        assert_eq!(bot.pending.len(), 2);
+
+        // by when the second has a license
+        drop(unlicensed);
+        let _one = mock("GET", "/repos/wipfollow/one/license")
+            .with_status(404)
+            .create();
+        let _two = mock("GET", "/repos/wipfollow/two/license")
+            .with_body(r#"{"license":{"key":"mit","spdx_id":"MIT"}}"#)
+            .create();
+        let comment = mock("POST", "/api/comment")
+            .match_body(Matcher::UrlEncoded(
+                "thing_id".to_owned(),
+                "t3_wipone".to_owned(),
+            ))
+            .expect(1)
+            .create();
+        clock.advance(Duration::from_secs(24 * 60 * 60));
+        bot.drain_pending(&mut Budget::new(0)).await.unwrap();
+        comment.assert();
+        assert!(bot.pending.is_empty());
+        let events = bot.core.events.lock().unwrap().recent(2);
+        let actions: Vec<(&str, &str)> = events
+            .iter()
+            .map(|e| (e.fullname.as_str(), e.action.as_str()))
+            .collect();
+        assert!(actions.contains(&("t3_wipone", "replied")));
+        assert!(actions.contains(&("t3_wiptwo", "resolved_before_reply")));
+    }
+
+    #[tokio::test]
+    async fn works_in_progress_are_skipped_without_a_follow_up() {
+        let listing = r#"{"data":{"after":null,"children":[{"data":{"name":"t3_wipskip","domain":"github.com","url":"https://github.com/wipskip/repo","title":"Toy project: a shell"}}]}}"#;
+        let _listing = mock("GET", "/r/wipskip/new")
+            .match_query(Matcher::Any)
+            .with_body(listing)
+            .create();
+        let repo = mock("GET", "/repos/wipskip/repo").expect(0).create();
+
+        let mut bot = test_bot();
+        configure(&bot, |c| c.wip_phrases = vec!["toy project".to_owned()]);
+        bot.watch_listing_once(
+            &Listing::subreddits("wipskip").unwrap(),
+            &mut Cursor::default(),
+        )
+        .await
+        .unwrap();
+        repo.assert();
+        assert!(bot.pending.is_empty());
+        let skips = bot
+            .core
+            .events
+            .lock()
+            .unwrap()
+            .stats()
+            .clone()
+            .subreddit("")
+            .skips
+            .clone();
+        assert_eq!(skips["skipped_wip"], 1);
+    }
+
+    #[tokio::test]
+    async fn held_back_replies_respect_the_reply_cap() {
+        let _info = info_mock("t3_held1,t3_held2", &["t3_held1", "t3_held2"]);
+        let comment = mock("POST", "/api/comment")
+            .match_body(Matcher::Regex("thing_id=t3_held[12]".to_owned()))
+            .expect(1)
+            .create();
+
+        let mut bot = test_bot();
+        configure(&bot, |c| c.max_replies_per_hour = Some(1));
+        for name in &["t3_held1", "t3_held2"] {
+            bot.pending.push(PendingReply {
+                fullname: name.to_string(),
+                repos: vec![RepoLink {
+                    repo: GhRepo {
+                        owner: "held".to_owned(),
+                        name: name.to_string(),
+                    },
+                    url: format!("https://github.com/held/{}", name),
+                    removed_license: None,
+                    summary: RepoSummary::default(),
+                }],
+                details: PostDetails::default(),
+                queued_at: 0,
+                deferred: false,
+                earlier: None,
+                cross_post: None,
+                check_after: None,
+                not_before: None,
+                stages: Stages::default(),
+            });
+        }
+        bot.drain_pending(&mut Budget::new(0)).await.unwrap();
+
+        comment.assert();
+        assert_eq!(bot.pending.len(), 1);
+        assert_eq!(bot.pending[0].fullname, "t3_held2");
+    }
+
+    /// A reply about `prefix/{name}` in post `t3_{name}`, held back in a queue
+    /// since `queued_at`.
+    fn queued(prefix: &str, name: &str, kind: QueueKind, queued_at: u64) -> PendingReply {
+        PendingReply {
+            fullname: format!("t3_{}", name),
+            repos: vec![RepoLink {
+                repo: GhRepo::new(prefix, name),
+                url: format!("https://github.com/{}/{}", prefix, name),
+                removed_license: None,
+                summary: RepoSummary::default(),
+            }],
+            details: PostDetails {
+                subreddit: prefix.to_owned(),
+                ..PostDetails::default()
+            },
+            queued_at,
+            deferred: kind != QueueKind::Delayed,
+            earlier: None,
+            cross_post: None,
+            check_after: if kind == QueueKind::Retry {
+                Some(queued_at)
+            } else {
+                None
+            },
+            not_before: None,
+            stages: Stages::default(),
+        }
+    }
+
+    #[test]
+    fn ages_out_old_findings_and_replies() {
+        let day = 24 * 60 * 60;
+        let now = 1_600_000_000 + 100 * day;
+        let tmp = tempfile::tempdir().unwrap();
+        let findings = tmp.path().join("findings.jsonl");
+        let mut lines = String::new();
+        for (i, days_ago) in [95, 80, 40, 20, 1].iter().enumerate() {
+            let event = Event {
+                at: now - days_ago * day,
+                fullname: format!("t3_{}", i),
+                title: "A project".to_owned(),
+                subreddit: "rust".to_owned(),
+                permalink: String::new(),
+                author: "op".to_owned(),
+                repo: "some/repo".to_owned(),
+                verdict: "unlicensed".to_owned(),
+                license: None,
+                action: "replied".to_owned(),
+                removed_license: None,
+                detection_path: vec![],
+            };
+            lines.push_str(&serde_json::to_string(&event).unwrap());
+            lines.push('\n');
+        }
+        fs::write(&findings, lines).unwrap();
+        let mut store = MemoryStore::default();
+        let reply = |post: &str, days_ago: u64| ReplyRecord {
+            author: "op".to_owned(),
+            repo: "some/repo".to_owned(),
+            post: post.to_owned(),
+            comment: None,
+            at: now - days_ago * day,
+            resolved: None,
+        };
+        store
+            .add_replies(&[reply("t3_a", 60), reply("t3_b", 31), reply("t3_c", 2)])
+            .unwrap();
+        let bot = BotBuilder::new()
+            .config(Config {
+                findings_path: Some(findings.clone()),
+                findings_retention_days: 30,
+                reply_dedup_ttl: 30 * day,
+                ..test_config()
+            })
+            .store(Box::new(store.clone()))
+            .clock(Box::new(ManualClock::new(
+                UNIX_EPOCH + Duration::from_secs(now),
+            )))
+            .build()
+            .unwrap();
+
+        bot.age_out_records().unwrap();
+        let kept: Vec<Event> = fs::read_to_string(&findings)
+            .unwrap()
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(
+            kept.iter().map(|e| e.fullname.as_str()).collect::<Vec<_>>(),
+            vec!["t3_3", "t3_4"]
+        );
+        for month in &["2020-09", "2020-10", "2020-11"] {
+            assert!(tmp
+                .path()
+                .join(format!("findings-{}.jsonl.gz", month))
+                .exists());
+        }
+        assert_eq!(
+            store
+                .replies()
+                .unwrap()
+                .iter()
+                .map(|r| r.post.as_str())
+                .collect::<Vec<_>>(),
+            vec!["t3_c"]
+        );
+    }
+
+    fn queue_bot(prefix: &str, state_dir: &Path, clock: &ManualClock) -> Bot {
+        let base = format!("{}/{}", server_url(), prefix);
+        BotBuilder::new()
+            .config(Config {
+                state_dir: state_dir.to_owned(),
+                queue_budget: 2,
+                ..test_config()
+            })
+            .endpoints(Endpoints {
+                reddit: base.clone(),
+                reddit_oauth: base.clone(),
+                github: base,
+            })
+            .store(Box::new(MemoryStore::default()))
+            .clock(Box::new(clock.clone()))
+            .build()
+            .unwrap()
+    }
+
+    fn queued_kinds(bot: &Bot) -> Vec<(&str, QueueKind)> {
+        bot.pending
+            .iter()
+            .map(|p| (p.fullname.as_str(), p.kind()))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn deferred_checks_of_removed_posts_are_dropped_unchecked() {
+        let now = 1_600_000_000;
+        let _info = mock("GET", "/gone/api/info")
+            .match_query(Matcher::UrlEncoded(
+                "id".to_owned(),
+                "t3_gonekept,t3_goneremoved".to_owned(),
+            ))
+            .with_body(r#"{"data":{"children":[{"data":{"name":"t3_gonekept","author":"op"}}]}}"#)
+            .create();
+        let kept = mock("GET", "/gone/repos/gone/gonekept")
+            .with_body("{}")
+            .expect(1)
+            .create();
+        let _kept_license = mock("GET", "/gone/repos/gone/gonekept/license")
+            .with_body("{}")
+            .create();
+        let removed = mock(
+            "GET",
+            Matcher::Regex("^/gone/repos/gone/goneremoved".to_owned()),
+        )
+        .expect(0)
+        .create();
+        let tmp = tempfile::tempdir().unwrap();
+        let clock = ManualClock::new(UNIX_EPOCH + Duration::from_secs(now));
+        let mut bot = queue_bot("gone", tmp.path(), &clock);
+        bot.pending = vec![
+            queued("gone", "gonekept", QueueKind::Recheck, now),
+            queued("gone", "goneremoved", QueueKind::Recheck, now),
+        ];
+
+        bot.check_deferred(QueueKind::Recheck, &mut Budget::new(0))
+            .await
+            .unwrap();
+        kept.assert();
+        removed.assert();
+        assert!(bot.pending.is_empty());
+        let event = &bot.core.events.lock().unwrap().recent(1)[0];
+        assert_eq!(
+            (event.fullname.as_str(), event.action.as_str()),
+            ("t3_goneremoved", "post removed")
+        );
+    }
+
+    #[tokio::test]
+    async fn reply_latency_counts_waiting_for_the_reply_cap() {
+        let now = 1_600_000_000;
+        let _info = mock("GET", "/latency/api/info")
+            .match_query(Matcher::Any)
+            .with_body(r#"{"data":{"children":[{"data":{"name":"t3_lcapped","author":"op"}}]}}"#)
+            .create();
+        let tmp = tempfile::tempdir().unwrap();
+        let clock = ManualClock::new(UNIX_EPOCH + Duration::from_secs(now));
+        let mut bot = queue_bot("latency", tmp.path(), &clock);
+        configure(&bot, |c| c.max_replies_per_hour = Some(1));
+        bot.core.replies.lock().unwrap().push(bot.core.clock.now());
+        bot.pending = vec![PendingReply {
+            stages: Stages::new(now - 600, now - 300),
+            ..queued("latency", "lcapped", QueueKind::Delayed, now - 300)
+        }];
+
+        // held back by the cap, which is noted once
+        bot.drain_queues().await.unwrap();
+        clock.advance(Duration::from_secs(30 * 60));
+        bot.drain_queues().await.unwrap();
+        assert_eq!(bot.pending[0].stages.capped, Some(now));
+        assert!(bot.core.events.lock().unwrap().latencies().is_empty());
+
+        let comment = comment_mock("latency", "t1_lcapped").expect(1).create();
+        clock.advance(Duration::from_secs(30 * 60));
+        bot.drain_queues().await.unwrap();
+        comment.assert();
+        assert!(bot.pending.is_empty());
+        let latency = bot.core.events.lock().unwrap().latencies().summary();
+        assert_eq!((latency.replies, latency.max), (1, Some(600 + 60 * 60)));
+        assert!(bot.summary().contains("reply latency p50 1h 10m"));
+    }
+
+    #[tokio::test]
+    async fn queues_are_drained_in_order_within_a_budget() {
+        let now = 1_600_000_000;
+        let _info = mock("GET", "/priority/api/info")
+            .match_query(Matcher::Any)
+            .with_body(
+                r#"{"data":{"children":[
+                    {"data":{"name":"t3_papproved","author":"op"}},
+                    {"data":{"name":"t3_pretry","author":"op"}},
+                    {"data":{"name":"t3_pdelayed","author":"op"}},
+                    {"data":{"name":"t3_precheck","author":"op"}}]}}"#,
+            )
+            .create();
+        let checks: Vec<mockito::Mock> = ["pretry", "precheck"]
+            .iter()
+            .flat_map(|name| {
+                vec![
+                    mock("GET", &*format!("/priority/repos/priority/{}", name))
+                        .with_body("{}")
+                        .expect(1)
+                        .create(),
+                    mock(
+                        "GET",
+                        &*format!("/priority/repos/priority/{}/license", name),
+                    )
+                    .with_status(404)
+                    .expect(1)
+                    .create(),
+                ]
+            })
+            .collect();
+        let comments: Vec<mockito::Mock> = ["papproved", "pretry", "pdelayed"]
+            .iter()
+            .map(|name| {
+                comment_mock("priority", &format!("t1_{}", name))
+                    .match_body(Matcher::Regex(format!("thing_id=t3_{}(&|$)", name)))
+                    .expect(1)
+                    .create()
+            })
+            .collect();
+
+        let tmp = tempfile::tempdir().unwrap();
+        let clock = ManualClock::new(UNIX_EPOCH + Duration::from_secs(now));
+        let mut bot = queue_bot("priority", tmp.path(), &clock);
+        bot.pending = vec![
+            queued("priority", "precheck", QueueKind::Recheck, now - 30),
+            queued("priority", "pdelayed", QueueKind::Delayed, now - 20),
+            queued("priority", "pretry", QueueKind::Retry, now - 10),
+        ];
+        ApprovalQueue::update(tmp.path(), |q| {
+            let id = q.push(queued("priority", "papproved", QueueKind::Delayed, now));
+            Ok(q.approve(id)?)
+        })
+        .unwrap();
+        // the approved reply, then the retry, which turns out to be unlicensed
+        bot.drain_queues().await.unwrap();
+        assert!(ApprovalQueue::load(tmp.path()).unwrap().items().is_empty());
+        assert_eq!(
+            queued_kinds(&bot),
+            vec![
+                ("t3_precheck", QueueKind::Recheck),
+                ("t3_pdelayed", QueueKind::Delayed),
+                ("t3_pretry", QueueKind::Delayed),
+            ]
+        );
+        // the delayed replies, leaving the re-check
+        bot.drain_queues().await.unwrap();
+        assert_eq!(
+            queued_kinds(&bot),
+            vec![("t3_precheck", QueueKind::Recheck)]
+        );
+        bot.drain_queues().await.unwrap();
+        assert_eq!(
+            queued_kinds(&bot),
+            vec![("t3_precheck", QueueKind::Delayed)]
+        );
+        for mock in checks.iter().chain(&comments) {
+            mock.assert();
+        }
+    }
+
+    #[tokio::test]
+    async fn full_queues_drop_their_oldest_items() {
+        let now = 1_600_000_000;
+        let tmp = tempfile::tempdir().unwrap();
+        let clock = ManualClock::new(UNIX_EPOCH + Duration::from_secs(now));
+        let mut bot = queue_bot("dropping", tmp.path(), &clock);
+        configure(&bot, |c| {
+            c.max_rechecks = 1;
+            c.retry_max_age = 60 * 60;
+        });
+        bot.pending = vec![
+            queued("dropping", "old", QueueKind::Recheck, now - 20),
+            queued("dropping", "new", QueueKind::Recheck, now - 10),
+            queued("dropping", "retry", QueueKind::Retry, now - 10),
+            queued("dropping", "held", QueueKind::Delayed, now - 10 * 60 * 60),
+        ];
+        ApprovalQueue::update(tmp.path(), |q| {
+            let id = q.push(queued("dropping", "approved", QueueKind::Delayed, 0));
+            Ok(q.approve(id)?)
+        })
+        .unwrap();
+
+        bot.bound_queues().unwrap();
+        assert_eq!(
+            queued_kinds(&bot),
+            vec![
+                ("t3_new", QueueKind::Recheck),
+                ("t3_retry", QueueKind::Retry),
+                ("t3_held", QueueKind::Delayed),
+            ]
+        );
+        // the retry ages out an hour after it was queued
+        clock.advance(Duration::from_secs(60 * 60 - 11));
+        bot.bound_queues().unwrap();
+        assert_eq!(bot.pending.len(), 3);
+        clock.advance(Duration::from_secs(1));
+        bot.bound_queues().unwrap();
+        assert_eq!(bot.pending.len(), 2);
+
+        let mut events = bot.core.events.lock().unwrap();
+        let actions: Vec<(String, String)> = events
+            .recent(10)
+            .into_iter()
+            .map(|e| (e.fullname, e.action))
+            .collect();
+        assert_eq!(
+            actions,
+            vec![
+                ("t3_retry".to_owned(), "retry expired".to_owned()),
+                ("t3_old".to_owned(), "queue full".to_owned()),
+            ]
+        );
+        let queues = events.queues().clone();
+        assert_eq!(queues.dropped[&QueueKind::Recheck], 1);
+        assert_eq!(queues.dropped[&QueueKind::Retry], 1);
+        assert_eq!(queues.waiting[&QueueKind::Approved], 1);
+        assert_eq!(queues.waiting[&QueueKind::Delayed], 1);
+        let stats = events.stats_mut().subreddit("dropping");
+        assert_eq!(stats.skips.get("queue full"), Some(&1));
+    }
+
+    /// Make a reply about /`prefix`/repo, held back for `age` seconds, after
+    /// the repository has or hasn't been given a license in the meantime,
+    /// and was or wasn't checked for another post `checked_ago` seconds ago.
+    async fn drain_held(
+        prefix: &str,
+        age: u64,
+        checked_ago: Option<u64>,
+        licensed: bool,
+        approved: bool,
+    ) -> Bot {
+        let now = 1_600_000_000;
+        let checked = if checked_ago.unwrap_or(age) >= 10 * 60 {
+            1
+        } else {
+            0
+        };
+        let fullname = format!("t3_{}", prefix);
+        let _info = mock("GET", format!("/{}/api/info", prefix).as_str())
+            .match_query(Matcher::Any)
+            .with_body(format!(
+                r#"{{"data":{{"children":[{{"data":{{"name":"{}","author":"op"}}}}]}}}}"#,
+                fullname
+            ))
+            .create();
+        let repo = mock("GET", format!("/{0}/repos/{0}/repo", prefix).as_str())
+            .with_body("{}")
+            .expect(checked)
+            .create();
+        let license = mock(
+            "GET",
+            format!("/{0}/repos/{0}/repo/license", prefix).as_str(),
+        );
+        let license = if licensed {
+            license.with_body(r#"{"license":{"spdx_id":"MIT"}}"#)
+        } else {
+            license.with_status(404)
+        }
+        .expect(checked)
+        .create();
+        let comment = mock("POST", format!("/{}/api/comment", prefix).as_str())
+            .expect(if licensed && checked == 1 { 0 } else { 1 })
+            .create();
+
+        let tmp = tempfile::tempdir().unwrap();
+        let base = format!("{}/{}", server_url(), prefix);
+        let mut bot = BotBuilder::new()
+            .config(Config {
+                state_dir: tmp.path().to_owned(),
+                recheck_after: 10 * 60,
+                ..test_config()
+            })
+            .endpoints(Endpoints {
+                reddit: base.clone(),
+                reddit_oauth: base.clone(),
+                github: base,
+            })
+            .store(Box::new(MemoryStore::default()))
+            .clock(Box::new(ManualClock::new(
+                UNIX_EPOCH + Duration::from_secs(now),
+            )))
+            .build()
+            .unwrap();
+        let reply = PendingReply {
+            fullname,
+            repos: vec![RepoLink {
+                repo: GhRepo::new(prefix, "repo"),
+                url: format!("https://github.com/{}/repo", prefix),
+                removed_license: None,
+                summary: RepoSummary::default(),
+            }],
+            details: PostDetails {
+                subreddit: "invited".to_owned(),
+                ..PostDetails::default()
+            },
+            queued_at: now - age,
+            deferred: false,
+            earlier: None,
+            cross_post: None,
+            check_after: None,
+            not_before: None,
+            stages: Stages::default(),
+        };
+        if let Some(ago) = checked_ago {
+            bot.core.events.lock().unwrap().record(Event {
+                at: now - ago,
+                fullname: "t3_other".to_owned(),
+                title: String::new(),
+                subreddit: "invited".to_owned(),
+                permalink: String::new(),
+                author: "op".to_owned(),
+                repo: format!("{}/repo", prefix),
+                verdict: "unlicensed".to_owned(),
+                license: None,
+                action: "replied".to_owned(),
+                removed_license: None,
+                detection_path: vec![],
+            });
+        }
+        if approved {
+            ApprovalQueue::update(tmp.path(), |q| {
+                let id = q.push(reply);
+                Ok(q.approve(id)?)
+            })
+            .unwrap();
+            bot.drain_approved(&mut Budget::new(0)).await.unwrap();
+            assert!(ApprovalQueue::load(tmp.path()).unwrap().items().is_empty());
+        } else {
+            bot.pending.push(reply);
+            bot.drain_pending(&mut Budget::new(0)).await.unwrap();
+            assert!(bot.pending.is_empty());
+        }
+        repo.assert();
+        license.assert();
+        comment.assert();
+        bot
+    }
+
+    #[tokio::test]
+    async fn no_reply_once_a_license_has_been_added() {
+        for (prefix, approved) in [("resolvedheld", false), ("resolvedapproved", true)] {
+            let bot = drain_held(prefix, 11 * 60, None, true, approved).await;
+            let mut events = bot.core.events.lock().unwrap();
+            let event = &events.recent(1)[0];
+            assert_eq!(event.action, "resolved_before_reply");
+            assert_eq!(event.license.as_deref(), Some("MIT"));
+            let stats = events.stats_mut().subreddit("invited").clone();
+            assert_eq!(stats.skips["resolved_before_reply"], 1);
+            assert_eq!((stats.unlicensed, stats.errors), (0, 0));
+        }
+    }
+
+    #[tokio::test]
+    async fn stale_replies_are_made_if_still_unlicensed() {
+        drain_held("stillunlicensed", 11 * 60, None, false, false).await;
+        drain_held("stillunlicensedapproved", 11 * 60, None, false, true).await;
+    }
+
+    #[tokio::test]
+    async fn fresh_replies_are_not_checked_again() {
+        // the license would be found, but isn't looked for
+        drain_held("freshheld", 5 * 60, None, true, false).await;
+        drain_held("freshapproved", 5 * 60, None, true, true).await;
+    }
+
+    #[tokio::test]
+    async fn replies_are_checked_again_by_when_their_repositories_were_last_checked() {
+        // held for long, but checked for another post since
+        drain_held("checkedsince", 60 * 60, Some(5 * 60), true, false).await;
+        // held briefly, but last checked long before
+        drain_held("checkedbefore", 5 * 60, Some(60 * 60), true, false).await;
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn tasks_share_the_core_without_losing_changes() {
+        let _login = mock("POST", "/sharedcore/api/v1/access_token")
+            .with_body(
+                r#"{"access_token":"t","token_type":"bearer","expires_in":3600,"scope":"*"}"#,
+            )
+            .expect_at_least(8)
+            .create();
+        let _info = mock("GET", "/sharedcore/api/info")
+            .match_query(Matcher::Any)
+            .match_header("authorization", "Bearer t")
+            .with_body(r#"{"data":{"children":[]}}"#)
+            .create();
+        let store = MemoryStore::default();
+        let base = format!("{}/sharedcore", server_url());
+        let bot = BotBuilder::new()
+            .config(test_config())
+            .endpoints(Endpoints {
+                reddit: base.clone(),
+                reddit_oauth: base.clone(),
+                github: base,
+            })
+            .store(Box::new(store.clone()))
+            .build()
+            .unwrap();
+        bot.login().await.unwrap();
+
+        let tasks: Vec<_> = (0..8)
+            .map(|n| {
+                let mut task = bot.task();
+                tokio::spawn(async move {
+                    for i in 0..25 {
+                        // logging in again swaps the token out from under
+                        // the other tasks' requests
+                        if i % 5 == 0 {
+                            task.login().await.unwrap();
+                        }
+                        let fullname = format!("t3_{}x{}", n, i);
+                        task.fetch_posts_by_fullname(std::slice::from_ref(&fullname))
+                            .await
+                            .unwrap();
+                        let now = task.core.clock.unix_now();
+                        task.processed.insert(&fullname, now);
+                        task.count("shared", |s| s.scanned += 1);
+                        task.save_state("shared", &Cursor::default()).unwrap();
+                        task.core()
+                            .update_state("counted", |s| {
+                                s.processed.insert(&format!("{}x{}", n, i), now);
+                            })
+                            .unwrap();
+                    }
+                })
+            })
+            .collect();
+        let finished = tokio::time::timeout(Duration::from_secs(60), join_all(tasks))
+            .await
+            .expect("tasks sharing a core deadlocked");
+        for result in finished {
+            result.unwrap();
+        }
+
+        let state = store.load("shared").unwrap();
+        assert_eq!(state.processed.len(), 8 * 25);
+        assert_eq!(state.stats.clone().subreddit("shared").scanned, 8 * 25);
+        assert_eq!(store.load("counted").unwrap().processed.len(), 8 * 25);
+        assert_eq!(bot.scopes(), vec!["*"]);
+    }
+
+    fn maintenance_bot(prefix: &str) -> Bot {
+        let base = format!("{}/{}", server_url(), prefix);
+        BotBuilder::new()
+            .config(test_config())
+            .endpoints(Endpoints {
+                reddit: base.clone(),
+                reddit_oauth: base.clone(),
+                github: base,
+            })
+            .store(Box::new(MemoryStore::default()))
+            .build()
+            .unwrap()
+            .with_reporter(Reporter::new(
+                format!("{}/{}/1", server_url().replace("://", "://key@"), prefix)
+                    .parse()
+                    .unwrap(),
+            ))
+    }
+
+    const MAINTENANCE_PAGE: &str =
+        "<html><body><h1>reddit is down for maintenance</h1></body></html>";
+
+    #[tokio::test]
+    async fn login_failures_say_which_credentials_are_wrong() {
+        let cases: [(&str, usize, &str); 7] = [
+            ("badpassword", 200, r#"{"error": "invalid_grant"}"#),
+            (
+                "badclient",
+                401,
+                r#"{"message": "Unauthorized", "error": 401}"#,
+            ),
+            ("badclientbody", 200, r#"{"error": "invalid_client"}"#),
+            ("notscript", 200, r#"{"error": "unauthorized_client"}"#),
+            ("badgrant", 200, r#"{"error": "unsupported_grant_type"}"#),
+            ("badrequest", 200, r#"{"error": "invalid_request"}"#),
+            ("badscope", 200, r#"{"error": "invalid_scope"}"#),
+        ];
+        for (prefix, status, body) in cases {
+            // tried once, not waited on and tried again
+            let login = mock("POST", format!("/{}/api/v1/access_token", prefix).as_str())
+                .with_status(status)
+                .with_header("content-type", "application/json; charset=UTF-8")
+                .with_body(body)
+                .expect(1)
+                .create();
+            let e = maintenance_bot(prefix)
+                .login_when_available()
+                .await
+                .unwrap_err();
+            login.assert();
+            let expected = match prefix {
+                "badpassword" => matches!(e, BotError::BadCredentials(_)),
+                "badclient" | "badclientbody" => matches!(e, BotError::BadClient(_)),
+                "notscript" => matches!(e, BotError::UnauthorizedClient(_)),
+                "badgrant" => matches!(e, BotError::UnsupportedGrant(_)),
+                "badrequest" => matches!(e, BotError::BadLoginRequest(_)),
+                _ => matches!(e, BotError::Permanent(_)),
+            };
+            assert!(expected, "{}: {:?}", prefix, e);
+        }
+    }
+
+    #[tokio::test]
+    async fn html_from_reddit_is_maintenance_not_a_decode_error() {
+        for (prefix, status) in [("maintenancelogin", 503), ("maintenancelogin200", 200)] {
+            let _login = mock("POST", format!("/{}/api/v1/access_token", prefix).as_str())
+                .with_status(status)
+                .with_header("content-type", "text/html; charset=UTF-8")
+                .with_body(MAINTENANCE_PAGE)
+                .create();
+            let e = maintenance_bot(prefix).login().await.unwrap_err();
+            assert!(
+                matches!(e, BotError::ServiceUnavailable { .. }),
+                "{}: {:?}",
+                status,
+                e
+            );
+            assert!(e.is_retryable());
+        }
+
+        let _info = mock("GET", "/maintenanceinfo/api/info")
+            .match_query(Matcher::Any)
+            .with_header("content-type", "text/plain")
+            .with_body("not json")
+            .create();
+        let e = maintenance_bot("maintenanceinfo")
+            .fetch_posts_by_fullname(&["t3_a".to_owned()])
+            .await
+            .unwrap_err();
+        let e = e.downcast_ref::<BotError>().unwrap();
+        assert!(
+            matches!(e, BotError::Parse(m) if m.contains("text/plain")),
+            "{:?}",
+            e
+        );
+    }
+
+    #[tokio::test]
+    async fn backs_off_while_reddit_is_down() {
+        let prefix = "maintenance";
+        let listing = Listing::subreddits(prefix).unwrap();
+        let sentry = mock("POST", format!("/{}/api/1/store/", prefix).as_str())
+            .match_body(Matcher::PartialJsonString(
+                r#"{"level":"warning"}"#.to_owned(),
+            ))
+            .expect(1)
+            .create();
+        let down = mock("GET", format!("/{0}/r/{0}/new", prefix).as_str())
+            .match_query(Matcher::Any)
+            .with_status(503)
+            .with_header("content-type", "text/html")
+            .with_body(MAINTENANCE_PAGE)
+            .create();
+        let mut bot = maintenance_bot(prefix);
+        let e = bot
+            .watch_pass(&listing, &mut Cursor::default())
+            .await
+            .unwrap_err();
+        assert!(super::in_maintenance(&e), "{:#}", e);
+        // only reported the first time
+        bot.wait_out_maintenance(&e).await;
+        bot.wait_out_maintenance(&e).await;
+        sentry.assert();
+        assert!(bot.core.maintenance.load(Ordering::SeqCst));
+
+        drop(down);
+        let _up = mock("GET", format!("/{0}/r/{0}/new", prefix).as_str())
+            .match_query(Matcher::Any)
+            .with_body(r#"{"data":{"children":[],"before":null,"after":null}}"#)
+            .create();
+        // the loop carries on until it's stopped
+        let _ = tokio::time::timeout(Duration::from_secs(2), bot.watch_listing(&listing)).await;
+        assert!(!bot.core.maintenance.load(Ordering::SeqCst));
+    }
+
+    /// Change the config of a bot that's already been built.
+    fn configure(bot: &Bot, change: impl FnOnce(&mut Config)) {
+        let mut config = (*bot.config()).clone();
+        change(&mut config);
+        bot.core.set_config(config);
+    }
+
+    fn self_post(name: &str, selftext: &str) -> String {
+        format!(
+            r#"{{"data":{{"name":"{}","domain":"self.multi","url":"https://www.reddit.com/r/multi/{}","selftext":"{}"}}}}"#,
+            name, name, selftext
+        )
+    }
+
+    async fn watch_multi(children: &[String]) -> Bot {
+        let listing = format!(
+            r#"{{"data":{{"after":null,"children":[{}]}}}}"#,
+            children.join(",")
+        );
+        let _listing = mock("GET", "/r/multi/new")
+            .match_query(Matcher::Any)
+            .with_body(listing)
+            .create();
+        let mut bot = test_bot();
+        bot.watch_listing_once(
+            &Listing::subreddits("multi").unwrap(),
+            &mut Cursor::default(),
+        )
+        .await
+        .unwrap();
+        bot
+    }
+
+    fn multi_repo_mocks(repo: &str, licensed: bool) -> (mockito::Mock, mockito::Mock) {
+        let path = format!("/repos/multi/{}", repo);
+        let license = mock("GET", format!("{}/license", path).as_str());
+        (
+            mock("GET", path.as_str())
+                .with_body("{}")
+                .expect(1)
+                .create(),
+            if licensed {
+                license.with_body(r#"{"license":{"spdx_id":"MIT"}}"#)
+            } else {
+                license.with_status(404)
+            }
+            .expect(1)
+            .create(),
+        )
+    }
+
+    #[tokio::test]
+    async fn single_unlicensed_repo_gets_the_singular_reply() {
+        let _repo = multi_repo_mocks("single", false);
+        let _info = info_mock("t3_multi1", &["t3_multi1"]);
+        let comment = mock("POST", "/api/comment")
+            .match_body(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("thing_id".to_owned(), "t3_multi1".to_owned()),
+                Matcher::Regex("repository\\+does\\+not".to_owned()),
+            ]))
+            .expect(1)
+            .create();
+
+        watch_multi(&[self_post(
+            "t3_multi1",
+            "Check out https://github.com/multi/single!",
+        )])
+        .await;
+        comment.assert();
+    }
+
+    #[tokio::test]
+    async fn single_reply_summarizes_the_repo() {
+        let _repo = mock("GET", "/repos/multi/described")
+            .with_body(
+                r#"{"language":"Rust","created_at":"2024-01-02T03:04:05Z",
+                    "default_branch":"main","stargazers_count":234}"#,
+            )
+            .create();
+        let _license = mock("GET", "/repos/multi/described/license")
+            .with_status(404)
+            .create();
+        let _info = info_mock("t3_multi4", &["t3_multi4"]);
+        let comment = mock("POST", "/api/comment")
+            .match_body(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("thing_id".to_owned(), "t3_multi4".to_owned()),
+                Matcher::Regex(
+                    "text=Repository%3A\\+multi%2Fdescribed\\+%E2%80%94\\+Rust%2C\\+\
+                     created\\+2024-01-02%2C\\+default\\+branch\\+main%2C\\+234\\+stars\
+                     %0A%0AThe\\+linked"
+                        .to_owned(),
+                ),
+            ]))
+            .expect(1)
+            .create();
+
+        watch_multi(&[self_post(
+            "t3_multi4",
+            "Check out https://github.com/multi/described!",
+        )])
+        .await;
+        comment.assert();
+    }
+
+    #[tokio::test]
+    async fn one_reply_lists_every_unlicensed_repo_in_a_post() {
+        let _one = multi_repo_mocks("one", false);
+        let _two = multi_repo_mocks("two", false);
+        let _info = info_mock("t3_multi2", &["t3_multi2"]);
+        let comment = mock("POST", "/api/comment")
+            .match_body(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("thing_id".to_owned(), "t3_multi2".to_owned()),
+                Matcher::Regex("repositories\\+do\\+not".to_owned()),
+                Matcher::Regex("multi%2Fone".to_owned()),
+                Matcher::Regex("multi%2Ftwo".to_owned()),
+            ]))
+            .expect(1)
+            .create();
+
+        let bot = watch_multi(&[self_post(
+            "t3_multi2",
+            "Server: https://github.com/multi/one and client: https://github.com/multi/two              (see also https://github.com/Multi/One/issues)",
+        )])
+        .await;
+        comment.assert();
+        let mut replied: Vec<String> = bot
+            .core
+            .events
+            .lock()
+            .unwrap()
+            .recent(10)
+            .into_iter()
+            .filter(|e| e.action == "replied" && e.fullname == "t3_multi2")
+            .map(|e| e.repo)
+            .collect();
+        replied.sort();
+        assert_eq!(replied, vec!["multi/one", "multi/two"]);
+    }
+
+    #[tokio::test]
+    async fn licensed_repos_are_left_out_of_the_reply() {
+        let _licensed = multi_repo_mocks("licensed", true);
+        let _unlicensed = multi_repo_mocks("unlicensed", false);
+        let _info = info_mock("t3_multi3", &["t3_multi3"]);
+        let comment = mock("POST", "/api/comment")
+            .match_body(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("thing_id".to_owned(), "t3_multi3".to_owned()),
+                Matcher::Regex("repository\\+does\\+not".to_owned()),
+            ]))
+            .expect(1)
+            .create();
+
+        let bot = watch_multi(&[self_post(
+            "t3_multi3",
+            "https://github.com/multi/licensed https://github.com/multi/unlicensed",
+        )])
+        .await;
+        comment.assert();
+        let events: Vec<(String, String, Option<String>)> = bot
+            .core
+            .events
+            .lock()
+            .unwrap()
+            .recent(10)
+            .into_iter()
+            .map(|e| (e.repo, e.action, e.license))
+            .collect();
+        assert!(events.contains(&(
+            "multi/licensed".to_owned(),
+            "none".to_owned(),
+            Some("MIT".to_owned())
+        )));
+        assert!(events.contains(&("multi/unlicensed".to_owned(), "replied".to_owned(), None)));
+    }
+
+    #[tokio::test]
+    async fn manual_approval_queues_replies_until_approved() {
+        let tmp = tempfile::tempdir().unwrap();
+        let listing = format!(
+            r#"{{"data":{{"after":null,"children":[{},{},{}]}}}}"#,
+            link_post("t3_appr1", "https://github.com/approve/one"),
+            link_post("t3_appr2", "https://github.com/approve/two"),
+            link_post("t3_appr3", "https://github.com/approve/three"),
+        );
+        let _listing = mock("GET", "/r/approve/new")
+            .match_query(Matcher::Any)
+            .with_body(listing)
+            .create();
+        let _repos = mock(
+            "GET",
+            Matcher::Regex("^/repos/approve/(one|two|three)$".to_owned()),
+        )
+        .with_body("{}")
+        .create();
+        let _licenses = mock(
+            "GET",
+            Matcher::Regex("^/repos/approve/(one|two|three)/license$".to_owned()),
+        )
+        .with_status(404)
+        .create();
+        let _info = info_mock(
+            "t3_appr1,t3_appr2,t3_appr3",
+            &["t3_appr1", "t3_appr2", "t3_appr3"],
+        );
+        let no_comment = mock("POST", "/api/comment")
+            .match_body(Matcher::Regex("thing_id=t3_appr".to_owned()))
+            .expect(0)
+            .create();
+
+        let mut bot = test_bot();
+        configure(&bot, |c| c.state_dir = tmp.path().to_owned());
+        configure(&bot, |c| c.approval_mode = ApprovalMode::Manual);
+        configure(&bot, |c| c.max_replies_per_hour = Some(1));
+        bot.watch_listing_once(
+            &Listing::subreddits("approve").unwrap(),
+            &mut Cursor::default(),
+        )
+        .await
+        .unwrap();
+        // nothing is made before it's approved
+        bot.drain_approved(&mut Budget::new(0)).await.unwrap();
+        no_comment.assert();
+        let queue = ApprovalQueue::load(tmp.path()).unwrap();
+        let queued: Vec<(u64, &str)> = queue
+            .items()
+            .iter()
+            .map(|i| (i.id, i.reply.fullname.as_str()))
+            .collect();
+        assert_eq!(
+            queued,
+            vec![(1, "t3_appr1"), (2, "t3_appr2"), (3, "t3_appr3")]
+        );
+
+        ApprovalQueue::update(tmp.path(), |q| {
+            q.approve(2)?;
+            q.approve(3)?;
+            Ok(())
+        })
+        .unwrap();
+        let _approved_info = info_mock("t3_appr2,t3_appr3", &["t3_appr2", "t3_appr3"]);
+        let comment = mock("POST", "/api/comment")
+            .match_body(Matcher::UrlEncoded(
+                "thing_id".to_owned(),
+                "t3_appr2".to_owned(),
+            ))
+            .expect(1)
+            .create();
+        bot.drain_approved(&mut Budget::new(0)).await.unwrap();
+
+        // the reply cap leaves the second approved reply for later
+        comment.assert();
+        let queue = ApprovalQueue::load(tmp.path()).unwrap();
+        let left: Vec<u64> = queue.items().iter().map(|i| i.id).collect();
+        assert_eq!(left, vec![1, 3]);
+        assert_eq!(queue.approved()[0].id, 3);
+    }
+
+    #[tokio::test]
+    async fn suppressed_repos_are_not_replied_about() {
+        let tmp = tempfile::tempdir().unwrap();
+        suppress::add(
+            tmp.path(),
+            Suppression::repo(
+                &GhRepo::new("Suppressed", "Repo"),
+                "approval queue",
+                "rejected",
+            ),
+        )
+        .unwrap();
+        let _repo = mock("GET", "/repos/suppressed/repo")
+            .with_body("{}")
+            .create();
+        let _license = mock("GET", "/repos/suppressed/repo/license")
+            .with_status(404)
+            .create();
+        let _listing = mock("GET", "/r/suppressed/new")
+            .match_query(Matcher::Any)
+            .with_body(format!(
+                r#"{{"data":{{"after":null,"children":[{}]}}}}"#,
+                link_post("t3_supp", "https://github.com/suppressed/repo")
+            ))
+            .create();
+        let _info = info_mock("t3_supp", &["t3_supp"]);
+        let comment = mock("POST", "/api/comment")
+            .match_body(Matcher::Regex("thing_id=t3_supp".to_owned()))
+            .expect(0)
+            .create();
+
+        let mut bot = test_bot();
+        configure(&bot, |c| c.state_dir = tmp.path().to_owned());
+        bot.watch_listing_once(
+            &Listing::subreddits("suppressed").unwrap(),
+            &mut Cursor::default(),
+        )
+        .await
+        .unwrap();
+        comment.assert();
+        assert_eq!(
+            bot.core.events.lock().unwrap().recent(1)[0].action,
+            "suppressed"
+        );
+    }
+
+    #[tokio::test]
+    async fn suppressed_users_are_not_replied_to() {
+        let tmp = tempfile::tempdir().unwrap();
+        let _repo = mock("GET", "/repos/suppressed-user/repo")
+            .with_body("{}")
+            .create();
+        let _license = mock("GET", "/repos/suppressed-user/repo/license")
+            .with_status(404)
+            .create();
+        let _listing = mock("GET", "/r/suppresseduser/new")
+            .match_query(Matcher::Any)
+            .with_body(
+                r#"{"data":{"after":null,"children":[{"data":{"name":"t3_suppuser","author":"Spammer",
+                    "domain":"github.com","url":"https://github.com/suppressed-user/repo"}}]}}"#,
+            )
+            .create();
+        let _info = info_mock("t3_suppuser", &["t3_suppuser"]);
+        let comment = mock("POST", "/api/comment")
+            .match_body(Matcher::Regex("thing_id=t3_suppuser".to_owned()))
+            .expect(0)
+            .create();
+
+        let mut bot = test_bot();
+        configure(&bot, |c| c.state_dir = tmp.path().to_owned());
+        // added while the bot is running, as the CLI would
+        suppress::run(tmp.path(), &["add".to_owned(), "user:spammer".to_owned()]).unwrap();
+        bot.watch_listing_once(
+            &Listing::subreddits("suppresseduser").unwrap(),
+            &mut Cursor::default(),
+        )
+        .await
+        .unwrap();
+        comment.assert();
+        assert_eq!(
+            bot.core.events.lock().unwrap().recent(1)[0].action,
+            "suppressed"
+        );
+    }
+
+    #[tokio::test]
+    async fn respects_authors_opting_out() {
+        let tmp = tempfile::tempdir().unwrap();
+        let _repo = mock("GET", "/repos/optout/repo").with_body("{}").create();
+        let _license = mock("GET", "/repos/optout/repo/license")
+            .with_status(404)
+            .create();
+        let post = r#"{"data":{"name":"t3_optout","author":"op","domain":"self.test","title":"My game",
+            "selftext":"Source at https://github.com/optout/repo, but it's NOT\n open source."}}"#;
+        let _listing = mock("GET", "/r/optout/new")
+            .match_query(Matcher::Any)
+            .with_body(format!(
+                r#"{{"data":{{"after":null,"children":[{}]}}}}"#,
+                post
+            ))
+            .create();
+        let _info = info_mock("t3_optout", &["t3_optout"]);
+        let comment = mock("POST", "/api/comment")
+            .match_body(Matcher::Regex("thing_id=t3_optout".to_owned()))
+            .expect(0)
+            .create();
+
+        let mut bot = test_bot();
+        configure(&bot, |c| c.state_dir = tmp.path().to_owned());
+        configure(&bot, |c| {
+            c.author_opt_out_phrases = vec!["not open source".to_owned()]
+        });
+        bot.watch_listing_once(
+            &Listing::subreddits("optout").unwrap(),
+            &mut Cursor::default(),
+        )
+        .await
+        .unwrap();
+        comment.assert();
+        assert_eq!(
+            bot.core.events.lock().unwrap().recent(1)[0].action,
+            "author opted out"
+        );
+        let list = suppress::load(tmp.path()).unwrap();
+        assert_eq!(list.len(), 1);
+        assert_eq!(list[0].entry, "optout/repo");
+        assert_eq!(list[0].reason, "author_opted_out");
+    }
+
+    #[tokio::test]
+    async fn never_replies_about_high_profile_repositories() {
+        let _popular = mock("GET", "/repos/someone/popular")
+            .with_body(r#"{"stargazers_count":5000}"#)
+            .create();
+        let _famous = mock("GET", "/repos/famous/tool")
+            .with_body(r#"{"stargazers_count":3}"#)
+            .create();
+        let _licenses = mock(
+            "GET",
+            Matcher::Regex("^/repos/(someone/popular|famous/tool)/license$".to_owned()),
+        )
+        .with_status(404)
+        .create();
+        let post = r#"{"data":{"name":"t3_famous","author":"op","domain":"self.test","title":"News",
+            "selftext":"https://github.com/someone/popular and https://github.com/famous/tool"}}"#;
+        let _listing = mock("GET", "/r/highprofile/new")
+            .match_query(Matcher::Any)
+            .with_body(format!(
+                r#"{{"data":{{"after":null,"children":[{}]}}}}"#,
+                post
+            ))
+            .create();
+        let _info = info_mock("t3_famous", &["t3_famous"]);
+        let comment = mock("POST", "/api/comment")
+            .match_body(Matcher::Regex("thing_id=t3_famous".to_owned()))
+            .expect(0)
+            .create();
+
+        let mut bot = test_bot();
+        configure(&bot, |c| c.high_profile_owners = vec!["famous".to_owned()]);
+        bot.watch_listing_once(
+            &Listing::subreddits("highprofile").unwrap(),
+            &mut Cursor::default(),
+        )
+        .await
+        .unwrap();
+        comment.assert();
+        let events = bot.core.events.lock().unwrap().recent(2);
+        assert_eq!(events.len(), 2);
+        for event in &events {
+            assert_eq!(event.verdict, "unlicensed");
+            assert_eq!(event.action, "skipped_high_profile");
+            assert_eq!(event.skip_reason(), Some(SkipReason::HighProfile));
+        }
+    }
+
+    #[tokio::test]
+    async fn never_replies_to_its_own_posts() {
+        let repo = mock("GET", "/repos/selfpost/repo").expect(0).create();
+        let license = mock("GET", "/repos/selfpost/repo/license")
+            .expect(0)
+            .create();
+        let post = r#"{"data":{"name":"t3_selfpost","author":"Bot","domain":"github.com",
+            "subreddit":"selfpost","url":"https://github.com/selfpost/repo"}}"#;
+        let _listing = mock("GET", "/r/selfpost/new")
+            .match_query(Matcher::Any)
+            .with_body(format!(
+                r#"{{"data":{{"after":null,"children":[{}]}}}}"#,
+                post
+            ))
+            .create();
+        let comment = mock("POST", "/api/comment")
+            .match_body(Matcher::Regex("thing_id=t3_selfpost".to_owned()))
+            .expect(0)
+            .create();
+
+        let mut bot = test_bot();
+        bot.watch_listing_once(
+            &Listing::subreddits("selfpost").unwrap(),
+            &mut Cursor::default(),
+        )
+        .await
+        .unwrap();
+        repo.assert();
+        license.assert();
+        comment.assert();
+        assert!(bot.processed.contains("t3_selfpost"));
+        assert_eq!(
+            bot.processed.skipped("t3_selfpost"),
+            Some(SkipReason::OwnPost)
+        );
+        let mut events = bot.core.events.lock().unwrap();
+        let stats = events.stats_mut().subreddit("selfpost");
+        assert_eq!(stats.skips.get("own post"), Some(&1));
+        assert_eq!(stats.unlicensed, 0);
+    }
+
+    #[tokio::test]
+    async fn records_why_posts_without_repositories_were_skipped() {
+        let long = format!(
+            r#"{{"data":{{"name":"t3_long","author":"op","domain":"github.com",
+                "subreddit":"nolinks","url":"https://github.com/some/repo?utm={}"}}}}"#,
+            "x".repeat(5000)
+        );
+        let posts = [
+            r#"{"data":{"name":"t3_nolinks","author":"op","domain":"self.nolinks",
+                "subreddit":"nolinks","selftext":"no code here"}}"#,
+            r#"{"data":{"name":"t3_elsewhere","author":"op","domain":"gitlab.com",
+                "subreddit":"nolinks","url":"https://gitlab.com/some/repo"}}"#,
+            &long,
+            r#"{"data":{"name":"t3_nourl","author":"op","domain":"github.com",
+                "subreddit":"nolinks"}}"#,
+            r#"{"data":{"author":"op","domain":"github.com","subreddit":"nolinks",
+                "url":"https://github.com/some/repo"}}"#,
+        ];
+        let _listing = mock("GET", "/r/nolinks/new")
+            .match_query(Matcher::Any)
+            .with_body(format!(
+                r#"{{"data":{{"after":null,"children":[{}]}}}}"#,
+                posts.join(",")
+            ))
+            .create();
+
+        let mut bot = test_bot();
+        bot.watch_listing_once(
+            &Listing::subreddits("nolinks").unwrap(),
+            &mut Cursor::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            bot.processed.skipped("t3_nolinks"),
+            Some(SkipReason::NoLinks)
+        );
+        assert_eq!(
+            bot.processed.skipped("t3_elsewhere"),
+            Some(SkipReason::NotGithub)
+        );
+        assert_eq!(
+            bot.processed.skipped("t3_long"),
+            Some(SkipReason::UrlTooLong)
+        );
+        assert_eq!(
+            bot.processed.skipped("t3_nourl"),
+            Some(SkipReason::Unreadable)
+        );
+        // a post without a fullname can't even be remembered
+        assert_eq!(bot.processed.len(), 4);
+        let mut events = bot.core.events.lock().unwrap();
+        let stats = events.stats_mut().subreddit("nolinks");
+        assert_eq!(stats.skips.get("no links"), Some(&1));
+        assert_eq!(stats.skips.get("not GitHub"), Some(&1));
+        assert_eq!(stats.skips.get("URL too long"), Some(&1));
+        assert_eq!(stats.skips.get("unreadable post"), Some(&1));
+        assert_eq!(stats.scanned, 4);
+        assert_eq!(stats.candidates, 0);
+    }
+
+    #[tokio::test]
+    async fn reads_links_wrapped_in_reddit_redirects() {
+        let posts = [
+            r#"{"data":{"name":"t3_media","author":"op","domain":"reddit.com",
+                "subreddit":"wrapped","url":"https://www.reddit.com/media?url=https%3A%2F%2Fgithub.com%2Fwrapped%2Fmedia"}}"#,
+            r#"{"data":{"name":"t3_out","author":"op","domain":"out.reddit.com",
+                "subreddit":"wrapped","url":"https://out.reddit.com/t3_out?url=https%3A%2F%2Fwww.reddit.com%2Fmedia%3Furl%3Dhttps%253A%252F%252Fgithub.com%252Fwrapped%252Fout&token=x"}}"#,
+            r#"{"data":{"name":"t3_broken","author":"op","domain":"reddit.com",
+                "subreddit":"wrapped","url":"https://www.reddit.com/media?link=elsewhere"}}"#,
+        ];
+        let _listing = mock("GET", "/r/wrapped/new")
+            .match_query(Matcher::Any)
+            .with_body(format!(
+                r#"{{"data":{{"after":null,"children":[{}]}}}}"#,
+                posts.join(",")
+            ))
+            .create();
+        let _repos: Vec<mockito::Mock> = ["media", "out"]
+            .iter()
+            .map(|name| {
+                mock("GET", format!("/repos/wrapped/{}", name).as_str())
+                    .with_body("{}")
+                    .create()
+            })
+            .collect();
+        let licenses: Vec<mockito::Mock> = ["media", "out"]
+            .iter()
+            .map(|name| {
+                mock("GET", format!("/repos/wrapped/{}/license", name).as_str())
+                    .with_body(r#"{"license":{"key":"mit","spdx_id":"MIT"}}"#)
+                    .expect(1)
+                    .create()
+            })
+            .collect();
+
+        let mut bot = test_bot();
+        bot.watch_listing_once(
+            &Listing::subreddits("wrapped").unwrap(),
+            &mut Cursor::default(),
+        )
+        .await
+        .unwrap();
+        for license in &licenses {
+            license.assert();
+        }
+        assert_eq!(bot.processed.skipped("t3_media"), None);
+        assert_eq!(bot.processed.skipped("t3_out"), None);
+        assert_eq!(
+            bot.processed.skipped("t3_broken"),
+            Some(SkipReason::NotGithub)
+        );
+    }
+
+    #[tokio::test]
+    async fn posts_by_deleted_accounts_are_replied_to_only_if_configured() {
+        let watch = |subreddit: &'static str| {
+            let post = format!(
+                r#"{{"data":{{"name":"t3_{0}","author":"[deleted]","domain":"github.com",
+                "subreddit":"{0}","url":"https://github.com/{0}/repo"}}}}"#,
+                subreddit
+            );
+            mock("GET", format!("/r/{}/new", subreddit).as_str())
+                .match_query(Matcher::Any)
+                .with_body(format!(
+                    r#"{{"data":{{"after":null,"children":[{}]}}}}"#,
+                    post
+                ))
+                .create()
+        };
+
+        // left alone by default, without asking GitHub
+        let _listing = watch("deletedoff");
+        let repo = mock("GET", "/repos/deletedoff/repo").expect(0).create();
+        let mut bot = test_bot();
+        bot.watch_listing_once(
+            &Listing::subreddits("deletedoff").unwrap(),
+            &mut Cursor::default(),
+        )
+        .await
+        .unwrap();
+        repo.assert();
+        {
+            let mut events = bot.core.events.lock().unwrap();
+            let stats = events.stats_mut().subreddit("deletedoff");
+            assert_eq!(stats.skips.get("deleted author"), Some(&1));
+        }
+
+        // replied to when asked for, with the author matching no one
+        let _listing = watch("deletedon");
+        let _repo = mock("GET", "/repos/deletedon/repo")
+            .with_body("{}")
+            .create();
+        let _license = mock("GET", "/repos/deletedon/repo/license")
+            .with_status(404)
+            .create();
+        let _info = mock("GET", "/api/info")
+            .match_query(Matcher::UrlEncoded(
+                "id".to_owned(),
+                "t3_deletedon".to_owned(),
+            ))
+            .with_body(
+                r#"{"data":{"children":[{"data":{"name":"t3_deletedon","author":"[deleted]"}}]}}"#,
+            )
+            .create();
+        let comment = mock("POST", "/api/comment")
+            .match_body(Matcher::Regex("thing_id=t3_deletedon".to_owned()))
+            .expect(1)
+            .create();
+        let mut config = test_config();
+        config.reply_to_deleted = true;
+        config.exempt_authors = vec!["[deleted]".to_owned()];
+        bot.core.set_config(config);
+        bot.watch_listing_once(
+            &Listing::subreddits("deletedon").unwrap(),
+            &mut Cursor::default(),
+        )
+        .await
+        .unwrap();
+        comment.assert();
+    }
+
+    #[test]
+    fn replies_wait_on_posts_by_deleted_accounts_only_if_configured() {
+        let post: Post = serde_json::from_str(r#"{"name":"t3_a","author":"[deleted]"}"#).unwrap();
+        let removed: Post = serde_json::from_str(
+            r#"{"name":"t3_b","author":"[deleted]","removed_by_category":"deleted"}"#,
+        )
+        .unwrap();
+        let bot = test_bot();
+        assert!(!bot.can_reply_to(&post));
+        let mut config = test_config();
+        config.reply_to_deleted = true;
+        bot.core.set_config(config);
+        assert!(bot.can_reply_to(&post));
+        assert!(!bot.can_reply_to(&removed));
+    }
+
+    #[tokio::test]
+    async fn panic_costs_one_pass() {
+        let _listing = mock("GET", "/r/panicky/new")
+            .match_query(Matcher::Any)
+            .with_body(format!(
+                r#"{{"data":{{"after":null,"children":[{}]}}}}"#,
+                link_post("t3_boom", "https://github.com/panicky/repo")
+            ))
+            .create();
+        let listing = Listing::subreddits("panicky").unwrap();
+        let mut cursor = Cursor::default();
+        let mut bot = test_bot();
+        bot.panic_on = Some("t3_boom".to_owned());
+
+        let err = bot.watch_pass(&listing, &mut cursor).await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "panicked while handling post t3_boom: deliberate panic"
+        );
+        // the bot carries on, without tripping over the same post again
+        assert!(bot.processed.contains("t3_boom"));
+        assert!(bot.watch_pass(&listing, &mut cursor).await.is_ok());
+    }
+
+    fn listing_page(before: Option<&str>, names: &[&str]) -> String {
+        let children: Vec<String> = names
+            .iter()
+            .map(|n| link_post(n, &format!("https://github.com/pages/{}", n)))
+            .collect();
+        let before = match before {
+            Some(b) => format!(r#""{}""#, b),
+            None => "null".to_owned(),
+        };
+        format!(
+            r#"{{"data":{{"before":{},"after":null,"children":[{}]}}}}"#,
+            before,
+            children.join(",")
+        )
+    }
+
+    fn page_mock(subreddit: &str, before: Option<&str>, body: String) -> mockito::Mock {
+        let query = match before {
+            Some(b) => format!("^raw_json=1&limit=100&before={}$", b),
+            None => "^raw_json=1&limit=100$".to_owned(),
+        };
+        mock("GET", format!("/r/{}/new", subreddit).as_str())
+            .match_query(Matcher::Regex(query))
+            .with_body(body)
+            .expect(1)
+            .create()
+    }
+
+    fn licensed_mocks() -> (mockito::Mock, mockito::Mock) {
+        (
+            mock(
+                "GET",
+                Matcher::Regex("^/repos/pages/t3_[a-z0-9]+$".to_owned()),
+            )
+            .with_body("{}")
+            .create(),
+            mock(
+                "GET",
+                Matcher::Regex("^/repos/pages/t3_[a-z0-9]+/license$".to_owned()),
+            )
+            .with_body("{}")
+            .create(),
+        )
+    }
+
+    fn cursor_at(known: &[&str]) -> Cursor {
+        let mut cursor = Cursor::default();
+        let known: Vec<String> = known.iter().map(|k| k.to_string()).collect();
+        cursor.advance(&known);
+        cursor
+    }
+
+    #[tokio::test]
+    async fn first_poll_anchors_on_newest_post() {
+        let _github = licensed_mocks();
+        let newest = page_mock(
+            "anchorless",
+            None,
+            listing_page(None, &["t3_an2", "t3_an1"]),
+        );
+
+        let mut bot = test_bot();
+        let mut cursor = Cursor::default();
+        let listing = Listing::subreddits("anchorless").unwrap();
+        let caught_up = bot.watch_listing_once(&listing, &mut cursor).await.unwrap();
+
+        assert!(caught_up);
+        newest.assert();
+        assert_eq!(cursor.anchor(), Some("t3_an2"));
+        assert_eq!(bot.processed.fullnames(), vec!["t3_an2", "t3_an1"]);
+    }
+
+    #[tokio::test]
+    async fn follows_before_until_newest() {
+        let _github = licensed_mocks();
+        let first = page_mock(
+            "paged",
+            Some("t3_p0"),
+            listing_page(Some("t3_p2"), &["t3_p2", "t3_p1"]),
+        );
+        let second = page_mock("paged", Some("t3_p2"), listing_page(None, &["t3_p3"]));
+
+        let mut bot = test_bot();
+        let mut cursor = cursor_at(&["t3_p0"]);
+        let listing = Listing::subreddits("paged").unwrap();
+        let caught_up = bot.watch_listing_once(&listing, &mut cursor).await.unwrap();
+
+        assert!(caught_up);
+        first.assert();
+        second.assert();
+        assert_eq!(cursor.anchor(), Some("t3_p3"));
+        assert_eq!(bot.processed.fullnames(), vec!["t3_p2", "t3_p1", "t3_p3"]);
+    }
+
+    #[tokio::test]
+    async fn follows_before_until_page_cap() {
+        let _github = licensed_mocks();
+        let first = page_mock(
+            "capped_pages",
+            Some("t3_cp0"),
+            listing_page(Some("t3_cp1"), &["t3_cp1"]),
+        );
+        let second = page_mock(
+            "capped_pages",
+            Some("t3_cp1"),
+            listing_page(Some("t3_cp2"), &["t3_cp2"]),
+        );
+        let third = page_mock(
+            "capped_pages",
+            Some("t3_cp2"),
+            listing_page(None, &["t3_cp3"]),
+        );
+
+        let mut bot = test_bot();
+        configure(&bot, |c| c.max_pages = 2);
+        let mut cursor = cursor_at(&["t3_cp0"]);
+        let listing = Listing::subreddits("capped_pages").unwrap();
+        let caught_up = bot.watch_listing_once(&listing, &mut cursor).await.unwrap();
+        assert!(!caught_up);
+        assert_eq!(cursor.anchor(), Some("t3_cp2"));
+        first.assert();
+        second.assert();
+
+        let caught_up = bot.watch_listing_once(&listing, &mut cursor).await.unwrap();
+        assert!(caught_up);
+        assert_eq!(cursor.anchor(), Some("t3_cp3"));
+        third.assert();
+    }
+
+    /// Mocks of the pages of a listing, each with one post, from the one
+    /// after `{prefix}0` to the newest, `{prefix}{pages}`.
+    fn chained_pages(subreddit: &str, prefix: &str, pages: usize) -> Vec<mockito::Mock> {
+        (1..=pages)
+            .map(|n| {
+                let post = format!("t3_{}{}", prefix, n);
+                let next = if n < pages { Some(post.as_str()) } else { None };
+                mock("GET", format!("/r/{}/new", subreddit).as_str())
+                    .match_query(Matcher::Regex(format!(
+                        "^raw_json=1&limit=100&before=t3_{}{}$",
+                        prefix,
+                        n - 1
+                    )))
+                    .with_body(format!(
+                        r#"{{"data":{{"before":{},"after":null,"children":[{}]}}}}"#,
+                        next.map_or("null".to_owned(), |b| format!(r#""{}""#, b)),
+                        link_post(&post, &format!("https://github.com/{}/{}", prefix, post))
+                    ))
+                    .create()
+            })
+            .collect()
+    }
+
+    /// A bot checking repositories with a GitHub found at `github`.
+    fn bot_with_github(github: String, store: MemoryStore, depth: usize) -> Bot {
+        BotBuilder::new()
+            .config(Config {
+                pipeline_depth: depth,
+                max_pages: 10,
+                ..test_config()
+            })
+            .endpoints(Endpoints {
+                reddit: server_url(),
+                reddit_oauth: server_url(),
+                github,
+            })
+            .store(Box::new(store))
+            .build()
+            .unwrap()
+    }
+
+    /// A GitHub that takes a while to answer every request with an empty
+    /// object, so that every repository checked is licensed.
+    fn slow_github(wait: Duration) -> String {
+        use std::io::{Read, Write};
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for mut stream in listener.incoming().flatten() {
+                std::thread::spawn(move || {
+                    let _ = stream.read(&mut [0; 4096]);
+                    std::thread::sleep(wait);
+                    let _ = stream.write_all(
+                        b"HTTP/1.1 200 OK\r\ncontent-type: application/json\r\n\
+                          content-length: 2\r\nconnection: close\r\n\r\n{}",
+                    );
+                });
+            }
+        });
+        url
+    }
+
+    #[tokio::test]
+    async fn fetching_waits_while_pages_wait_to_be_processed() {
+        let pages = chained_pages("backpressure", "bp", 4);
+        // GitHub accepts the connection but never answers, so the first
+        // page is never done with
+        let hung = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let github = format!("http://{}", hung.local_addr().unwrap());
+        let mut bot = bot_with_github(github, MemoryStore::default(), 1);
+        let listing = Listing::subreddits("backpressure").unwrap();
+        let mut cursor = cursor_at(&["t3_bp0"]);
+
+        let pass = bot.watch_listing_once(&listing, &mut cursor);
+        assert!(tokio::time::timeout(Duration::from_secs(1), pass)
+            .await
+            .is_err());
+        // one page being processed, one waiting to be, and one waiting to
+        // be handed over, with nothing more fetched
+        assert!(pages[..3].iter().all(|p| p.matched()));
+        assert!(!pages[3].matched());
+        assert_eq!(cursor.anchor(), Some("t3_bp0"));
+    }
+
+    #[tokio::test]
+    async fn pages_after_one_that_failed_are_fetched_again() {
+        let _pages = chained_pages("lostpages", "lp", 3);
+        let failing = mock("GET", "/repos/lp/t3_lp1")
+            .with_status(500)
+            .expect(1)
+            .create();
+        // the failed repository is checked again, along with the later ones
+        let later = (
+            mock("GET", Matcher::Regex("^/repos/lp/t3_lp[123]$".to_owned()))
+                .with_body("{}")
+                .expect(3)
+                .create(),
+            mock(
+                "GET",
+                Matcher::Regex("^/repos/lp/t3_lp[123]/license$".to_owned()),
+            )
+            .with_body("{}")
+            .expect(3)
+            .create(),
+        );
+        let mut bot = test_bot();
+        let listing = Listing::subreddits("lostpages").unwrap();
+        let mut cursor = cursor_at(&["t3_lp0"]);
+
+        // the pages after the first were fetched while it was processed,
+        // but not processed after it failed, and neither was its post
+        assert!(bot.watch_listing_once(&listing, &mut cursor).await.is_err());
+        failing.assert();
+        assert_eq!(cursor.anchor(), Some("t3_lp0"));
+        assert!(bot.processed.is_empty());
+
+        // so the cursor still comes before them, and they're fetched again
+        assert!(bot.watch_listing_once(&listing, &mut cursor).await.unwrap());
+        assert_eq!(cursor.anchor(), Some("t3_lp3"));
+        assert_eq!(
+            bot.processed.fullnames(),
+            vec!["t3_lp1", "t3_lp2", "t3_lp3"]
+        );
+        later.0.assert();
+        later.1.assert();
+    }
+
+    #[tokio::test]
+    async fn posts_after_a_failed_reply_are_looked_at_again() {
+        let listing = format!(
+            r#"{{"data":{{"after":null,"children":[{},{}]}}}}"#,
+            link_post("t3_rb1", "https://github.com/rollback/one"),
+            link_post("t3_rb2", "https://github.com/rollback/two"),
+        );
+        let _listing = mock("GET", "/r/rollback/new")
+            .match_query(Matcher::Any)
+            .with_body(listing)
+            .create();
+        let _repos = mock(
+            "GET",
+            Matcher::Regex("^/repos/rollback/(one|two)$".to_owned()),
+        )
+        .with_body("{}")
+        .create();
+        let _licenses = mock(
+            "GET",
+            Matcher::Regex("^/repos/rollback/(one|two)/license$".to_owned()),
+        )
+        .with_status(404)
+        .create();
+        let _info = info_mock("t3_rb1,t3_rb2", &["t3_rb1", "t3_rb2"]);
+        let replied = mock("POST", "/api/comment")
+            .match_body(Matcher::UrlEncoded(
+                "thing_id".to_owned(),
+                "t3_rb1".to_owned(),
+            ))
+            .expect(1)
+            .create();
+        let _failed = mock("POST", "/api/comment")
+            .match_body(Matcher::UrlEncoded(
+                "thing_id".to_owned(),
+                "t3_rb2".to_owned(),
+            ))
+            .with_status(503)
+            .create();
+
+        let mut bot = test_bot();
+        let result = bot
+            .watch_listing_once(
+                &Listing::subreddits("rollback").unwrap(),
+                &mut Cursor::default(),
+            )
+            .await;
+        assert!(result.is_err());
+        replied.assert();
+        // the post replied to is done with, the other is looked at again
+        assert_eq!(bot.processed.fullnames(), vec!["t3_rb1"]);
+    }
+
+    #[tokio::test]
+    async fn a_failed_page_is_processed_when_tried_again() {
+        let failing = mock("GET", "/repos/retrybatch/one")
+            .with_status(500)
+            .expect(1)
+            .create();
+        let checked = (
+            mock("GET", "/repos/retrybatch/one")
+                .with_body("{}")
+                .expect(1)
+                .create(),
+            mock("GET", "/repos/retrybatch/one/license")
+                .with_body("{}")
+                .expect(1)
+                .create(),
+        );
+        let listing = Listing::subreddits("retrybatch").unwrap();
+        let post = link_post("t3_rt1", "https://github.com/retrybatch/one");
+        let batch = Batch {
+            postings: vec![serde_json::from_str(&post).unwrap()],
+            cursor: cursor_at(&["t3_rt1"]),
+        };
+        let mut bot = test_bot();
+        let mut cursor = Cursor::default();
+
+        // as consume does, the page is held on to when it fails
+        let pass = bot.consume_pass(&listing, Some(&batch), &mut cursor).await;
+        assert!(pass.is_err());
+        failing.assert();
+        assert_eq!(cursor.anchor(), None);
+        assert!(!bot.processed.contains("t3_rt1"));
+
+        bot.consume_pass(&listing, Some(&batch), &mut cursor)
+            .await
+            .unwrap();
+        checked.0.assert();
+        checked.1.assert();
+        assert_eq!(cursor.anchor(), Some("t3_rt1"));
+        assert!(bot.processed.contains("t3_rt1"));
+    }
+
+    #[tokio::test]
+    async fn stopping_processes_the_pages_already_fetched() {
+        let pages = chained_pages("stopdrain", "sd", 3);
+        let store = MemoryStore::default();
+        let github = slow_github(Duration::from_millis(150));
+        let mut bot = bot_with_github(github, store.clone(), 4);
+        let listing = Listing::subreddits("stopdrain").unwrap();
+        let key = listing.store_key();
+        bot.core()
+            .update_state(&key, |s| s.cursor = cursor_at(&["t3_sd0"]))
+            .unwrap();
+
+        let core = bot.core().clone();
+        let stop_once_fetched = async {
+            while !pages[2].matched() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            // every page has been fetched, but not yet processed
+            let saved = core.load_state(&key).unwrap();
+            assert!(!saved.processed.contains("t3_sd3"));
+            core.stop();
+        };
+        let watched = tokio::time::timeout(Duration::from_secs(20), async {
+            tokio::join!(bot.watch_listing(&listing), stop_once_fetched).0
+        })
+        .await
+        .expect("the watch loop didn't stop");
+        watched.unwrap();
+
+        let saved = store.load(&key).unwrap();
+        for post in &["t3_sd1", "t3_sd2", "t3_sd3"] {
+            assert!(saved.processed.contains(post), "{}", post);
+        }
+        assert_eq!(saved.cursor.anchor(), Some("t3_sd3"));
+    }
+
+    #[tokio::test]
+    async fn stale_cursor_is_reset() {
+        let _github = licensed_mocks();
+        // Reddit serving the top of the listing, whatever the anchor
+        let top = mock("GET", "/r/stale/new")
+            .match_query(Matcher::Regex(
+                "^raw_json=1&limit=100&before=t3_st[0-9]$".to_owned(),
+            ))
+            .with_body(listing_page(Some("t3_st2"), &["t3_st2", "t3_st1"]))
+            .expect(STALE_AFTER as usize + 1)
+            .create();
+
+        let mut bot = test_bot();
+        configure(&bot, |c| c.max_pages = 1);
+        bot.processed = vec!["t3_st2", "t3_st1"].into_iter().collect();
+        let mut cursor = cursor_at(&["t3_st0"]);
+        let listing = Listing::subreddits("stale").unwrap();
+        for _ in 0..STALE_AFTER {
+            bot.watch_listing_once(&listing, &mut cursor).await.unwrap();
+            assert!(cursor.anchor().is_some());
+        }
+        let caught_up = bot.watch_listing_once(&listing, &mut cursor).await.unwrap();
+        assert!(caught_up);
+        assert_eq!(cursor.anchor(), None);
+        top.assert();
+
+        // the next pass starts from the newest posts, and only the new one is handled
+        let newest = page_mock(
+            "stale",
+            None,
+            listing_page(None, &["t3_st3", "t3_st2", "t3_st1"]),
+        );
+        bot.watch_listing_once(&listing, &mut cursor).await.unwrap();
+        newest.assert();
+        assert_eq!(cursor.anchor(), Some("t3_st3"));
+        assert_eq!(
+            bot.processed.fullnames(),
+            vec!["t3_st2", "t3_st1", "t3_st3"]
+        );
+    }
+
+    #[tokio::test]
+    async fn empty_page_with_live_anchor_keeps_anchor() {
+        let _github = licensed_mocks();
+        let before = page_mock("quiet", Some("t3_q2"), listing_page(None, &[]));
+        let newest = page_mock("quiet", None, listing_page(None, &["t3_q2", "t3_q1"]));
+
+        let mut bot = test_bot();
+        bot.processed = vec!["t3_q2", "t3_q1"].into_iter().collect();
+        let mut cursor = cursor_at(&["t3_q2", "t3_q1"]);
+        let listing = Listing::subreddits("quiet").unwrap();
+        let caught_up = bot.watch_listing_once(&listing, &mut cursor).await.unwrap();
+
+        assert!(caught_up);
+        before.assert();
+        newest.assert();
+        assert_eq!(cursor.anchor(), Some("t3_q2"));
+    }
+
+    #[tokio::test]
+    async fn deleted_anchor_is_aged_back() {
+        let _github = licensed_mocks();
+        // t3_da3 was deleted, so Reddit returns nothing newer than it
+        let before_deleted = page_mock("deleted", Some("t3_da3"), listing_page(None, &[]));
+        let newest = page_mock(
+            "deleted",
+            None,
+            listing_page(None, &["t3_da4", "t3_da2", "t3_da1"]),
+        );
+        let before_aged = page_mock("deleted", Some("t3_da2"), listing_page(None, &["t3_da4"]));
+
+        let mut bot = test_bot();
+        bot.processed = vec!["t3_da3", "t3_da2", "t3_da1"].into_iter().collect();
+        let mut cursor = cursor_at(&["t3_da3", "t3_da2", "t3_da1"]);
+        let listing = Listing::subreddits("deleted").unwrap();
+
+        bot.watch_listing_once(&listing, &mut cursor).await.unwrap();
+        before_deleted.assert();
+        newest.assert();
+        assert_eq!(cursor.anchor(), Some("t3_da2"));
+        // the new post was still picked up by the fallback fetch
+        assert!(bot.processed.contains("t3_da4"));
+
+        bot.watch_listing_once(&listing, &mut cursor).await.unwrap();
+        before_aged.assert();
+        assert_eq!(cursor.anchor(), Some("t3_da4"));
+    }
+
+    #[test]
+    fn panic_messages() {
+        let payload = std::panic::catch_unwind(|| panic!("{}", 42)).unwrap_err();
+        assert_eq!(panic_message(&*payload), "42");
+        let payload = std::panic::catch_unwind(|| std::panic::panic_any(42)).unwrap_err();
+        assert_eq!(panic_message(&*payload), "no message");
+    }
+
+    #[tokio::test]
+    async fn fetch_posts_skips_missing_ids() {
+        let info = info_mock("t3_i1,t3_i2,t3_i3", &["t3_i1", "t3_i3"]);
+
+        let bot = test_bot();
+        let fullnames = vec!["t3_i1".to_owned(), "t3_i2".to_owned(), "t3_i3".to_owned()];
+        let posts = bot.fetch_posts_by_fullname(&fullnames).await.unwrap();
+
+        info.assert();
+        let names: Vec<&str> = posts.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, vec!["t3_i1", "t3_i3"]);
+    }
+
+    #[tokio::test]
+    async fn responds_to_first_live_post_for_a_repo() {
+        let listing = format!(
+            r#"{{"data":{{"after":null,"children":[{},{}]}}}}"#,
+            link_post("t3_rm1", "https://github.com/removed/repo"),
+            link_post("t3_rm2", "https://github.com/removed/repo"),
+        );
+        let _listing = mock("GET", "/r/removed/new")
+            .match_query(Matcher::Any)
+            .with_body(listing)
+            .create();
+        let _repo = mock("GET", "/repos/removed/repo").with_body("{}").create();
+        let _license = mock("GET", "/repos/removed/repo/license")
+            .with_status(404)
+            .create();
+        let _info = info_mock("t3_rm1,t3_rm2", &["t3_rm2"]);
+        let comment = mock("POST", "/api/comment")
+            .match_body(Matcher::UrlEncoded(
+                "thing_id".to_owned(),
+                "t3_rm2".to_owned(),
+            ))
+            .expect(1)
+            .create();
+
+        let mut bot = test_bot();
+        bot.watch_listing_once(
+            &Listing::subreddits("removed").unwrap(),
+            &mut Cursor::default(),
+        )
+        .await
+        .unwrap();
+
+        comment.assert();
+    }
+
+    fn history_mock(body: &str) -> mockito::Mock {
+        mock("GET", "/user/bot/comments")
+            .match_query(Matcher::Any)
+            .with_body(body)
+            .create()
+    }
+
+    #[tokio::test]
+    async fn comment_history_suppresses_replies() {
+        let _history = history_mock(
+            r#"{"kind":"Listing","data":{"children":[
+                {"kind":"t1","data":{"name":"t1_x","link_id":"t3_hist1"}},
+                {"kind":"t1","data":{"name":"t1_y","link_id":"t3_hist2"}}
+            ]}}"#,
+        );
+        let listing = format!(
+            r#"{{"data":{{"after":null,"children":[{}]}}}}"#,
+            link_post("t3_hist1", "https://github.com/history/repo"),
+        );
+        let _listing = mock("GET", "/r/history/new")
+            .match_query(Matcher::Any)
+            .with_body(listing)
+            .create();
+        let repo = mock("GET", "/repos/history/repo").expect(0).create();
+        let comment = mock("POST", "/api/comment")
+            .match_body(Matcher::UrlEncoded(
+                "thing_id".to_owned(),
+                "t3_hist1".to_owned(),
+            ))
+            .expect(0)
+            .create();
+
+        let mut bot = test_bot();
+        bot.processed = vec!["t3_older", "t3_hist2"].into_iter().collect();
+        bot.seed_from_comment_history().await;
+        assert_eq!(
+            bot.processed.fullnames(),
+            vec!["t3_older", "t3_hist2", "t3_hist1"]
+        );
+
+        bot.watch_listing_once(
+            &Listing::subreddits("history").unwrap(),
+            &mut Cursor::default(),
+        )
+        .await
+        .unwrap();
+        repo.assert();
+        comment.assert();
+    }
+
+    #[tokio::test]
+    async fn comment_history_failure_is_tolerated() {
+        let history = mock("GET", "/user/unlucky/comments")
+            .match_query(Matcher::Any)
+            .with_status(500)
+            .create();
+
+        let mut bot = test_bot();
+        configure(&bot, |c| c.username = "unlucky".to_owned());
+        bot.processed = vec!["t3_kept"].into_iter().collect();
+        bot.seed_from_comment_history().await;
+
+        history.assert();
+        assert_eq!(bot.processed.fullnames(), vec!["t3_kept"]);
+    }
+
+    /// A bot whose Reddit requests carry a token, to tell them apart from
+    /// anonymous ones.
+    fn authenticated_bot(username: &str) -> Bot {
+        let bot = test_bot();
+        configure(&bot, |c| c.username = username.to_owned());
+        bot.core.reddit.token.write().unwrap().access = Some("token".to_owned());
+        bot
+    }
+
+    fn own_comments_mock(username: &str, permalink: &str) -> mockito::Mock {
+        mock("GET", format!("/user/{}/comments", username).as_str())
+            .match_query(Matcher::Any)
+            .match_header("authorization", "Bearer token")
+            .with_body(format!(
+                r#"{{"data":{{"children":[{{"data":{{"name":"t1_mine","link_id":"t3_p","permalink":"{}"}}}}]}}}}"#,
+                permalink
+            ))
+            .create()
+    }
+
+    fn anonymous_mock(path: &str, status: usize, body: &str) -> mockito::Mock {
+        mock("GET", path)
+            .match_query(Matcher::Any)
+            .match_header("authorization", Matcher::Missing)
+            .with_status(status)
+            .with_body(body)
+            .create()
+    }
+
+    fn thread_with(comment: &str, body: &str) -> String {
+        format!(
+            r#"[{{"data":{{"children":[]}}}},{{"data":{{"children":[{{"kind":"t1","data":{{"name":"{}","body":"{}","replies":""}}}}]}}}}]"#,
+            comment, body
+        )
+    }
+
+    #[tokio::test]
+    async fn visible_comment_passes_self_check() {
+        let _about = anonymous_mock("/user/seen/about.json", 200, "{}");
+        let _comments = own_comments_mock("seen", "/r/x/comments/p/t/mine/");
+        let thread = anonymous_mock(
+            "/r/x/comments/p/t/mine.json",
+            200,
+            &thread_with("t1_mine", "hello"),
+        );
+
+        let mut bot = authenticated_bot("seen");
+        assert_eq!(bot.check_visibility().await.unwrap(), Visibility::Visible);
+        thread.assert();
+
+        bot.core.paused.store(true, Ordering::SeqCst);
+        bot.check_visibility_if_due().await;
+        assert!(!bot.core.paused());
+    }
+
+    #[tokio::test]
+    async fn hidden_comment_pauses_replies() {
+        let _about = anonymous_mock("/user/filtered/about.json", 200, "{}");
+        let _comments = own_comments_mock("filtered", "/r/x/comments/q/t/mine/");
+        let _thread = anonymous_mock(
+            "/r/x/comments/q/t/mine.json",
+            200,
+            &thread_with("t1_mine", "[removed]"),
+        );
+
+        let mut bot = authenticated_bot("filtered");
+        assert_eq!(
+            bot.check_visibility().await.unwrap(),
+            Visibility::CommentHidden("t1_mine".to_owned())
+        );
+        bot.check_visibility_if_due().await;
+        assert!(bot.core.paused());
+    }
+
+    #[tokio::test]
+    async fn anonymous_profile_404_is_shadowban() {
+        let _about = anonymous_mock("/user/shadowed/about.json", 404, "{}");
+        let comments = own_comments_mock("shadowed", "/r/x/comments/r/t/mine/").expect(0);
+
+        let mut bot = authenticated_bot("shadowed");
+        configure(&bot, |c| c.pause_on_shadowban = false);
+        assert_eq!(
+            bot.check_visibility().await.unwrap(),
+            Visibility::AccountHidden
+        );
+        bot.check_visibility_if_due().await;
+        assert!(!bot.core.paused());
+        comments.assert();
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn a_slow_store_doesnt_hold_up_other_tasks() {
+        let core = test_bot().core;
+        let start = std::time::Instant::now();
+        // as a store waiting on an unreachable Redis does, on the one worker
+        let slow = tokio::spawn(async move {
+            core.with_store(|_| std::thread::sleep(Duration::from_millis(500)))
+        });
+        let other = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            start.elapsed()
+        });
+        assert!(other.await.unwrap() < Duration::from_millis(400));
+        slow.await.unwrap();
+    }
+
+    #[test]
+    fn replies_wait_while_redis_is_unreachable() {
+        let mut config = test_config();
+        // nothing listens on port 1
+        config.redis_url = Some("redis://127.0.0.1:1/".to_owned());
+        let bot = BotBuilder::new().config(config).build().unwrap();
+        assert!(bot.core.paused());
+
+        // listings are still scanned, from what's kept in memory
+        let state = bot
+            .core
+            .update_state("r-rust", |s| {
+                s.processed.insert("t3_a", 1);
+            })
+            .unwrap();
+        assert!(state.processed.contains("t3_a"));
+        assert!(bot
+            .core
+            .load_state("r-rust")
+            .unwrap()
+            .processed
+            .contains("t3_a"));
+    }
+
+    fn subreddit_mocks(name: &str, rules: &str, hits: usize) -> (mockito::Mock, mockito::Mock) {
+        (
+            mock("GET", format!("/r/{}/about", name).as_str())
+                .match_query(Matcher::Any)
+                .with_body(r#"{"data":{"subreddit_type":"public","quarantine":false}}"#)
+                .expect(hits)
+                .create(),
+            mock("GET", format!("/r/{}/about/rules", name).as_str())
+                .match_query(Matcher::Any)
+                .with_body(rules)
+                .expect(hits)
+                .create(),
+        )
+    }
+
+    #[tokio::test]
+    async fn subreddit_verdict_is_cached() {
+        let (about, rules) = subreddit_mocks(
+            "nobots",
+            r#"{"rules":[{"short_name":"No bots","description":""}]}"#,
+            1,
+        );
+
+        let mut bot = test_bot();
+        for name in &["nobots", "NoBots"] {
+            assert_eq!(
+                bot.subreddit_verdict(name).await,
+                Verdict::ReportOnly("rules mention 'no bots'".to_owned())
+            );
+        }
+        about.assert();
+        rules.assert();
+    }
+
+    #[tokio::test]
+    async fn invited_subreddit_overrides_rules() {
+        let (about, rules) = subreddit_mocks(
+            "invited",
+            r#"{"rules":[{"short_name":"No bots","description":""}]}"#,
+            0,
+        );
+
+        let mut bot = test_bot();
+        assert_eq!(bot.subreddit_verdict("Invited").await, Verdict::Allowed);
+        about.assert();
+        rules.assert();
+    }
+
+    /// A bot that replies to one post in /r/invited, then finds out after
+    /// `removal_check_delay` whether its reply was removed, against mocks
+    /// under `/{prefix}`.
+    async fn reply_then_check_removal(prefix: &str, removed: bool, clock: &ManualClock) -> Bot {
+        let listing = format!(
+            r#"{{"data":{{"after":null,"children":[{{"data":{{"name":"t3_rm","domain":"github.com","subreddit":"invited","url":"https://github.com/{}/repo"}}}}]}}}}"#,
+            prefix
+        );
+        let _listing = mock("GET", &*format!("/{}/r/invited/new", prefix))
+            .match_query(Matcher::Any)
+            .with_body(listing)
+            .create();
+        let _repo = mock("GET", &*format!("/{}/repos/{}/repo", prefix, prefix))
+            .with_body("{}")
+            .create();
+        let _license = mock(
+            "GET",
+            &*format!("/{}/repos/{}/repo/license", prefix, prefix),
+        )
+        .with_status(404)
+        .create();
+        let _post = mock("GET", &*format!("/{}/api/info", prefix))
+            .match_query(Matcher::UrlEncoded("id".to_owned(), "t3_rm".to_owned()))
+            .with_body(r#"{"data":{"children":[{"data":{"name":"t3_rm","subreddit":"invited"}}]}}"#)
+            .create();
+        let comment = mock("POST", &*format!("/{}/api/comment", prefix))
+            .with_body(r#"{"json":{"data":{"things":[{"data":{"name":"t1_rm"}}]}}}"#)
+            .expect(1)
+            .create();
+        let reply = mock("GET", &*format!("/{}/api/info", prefix))
+            .match_query(Matcher::UrlEncoded("id".to_owned(), "t1_rm".to_owned()))
+            .with_body(format!(
+                r#"{{"data":{{"children":[{{"data":{{"name":"t1_rm","removed":{}}}}}]}}}}"#,
+                removed
+            ))
+            .expect(1)
+            .create();
+
+        let base = format!("{}/{}", server_url(), prefix);
+        let mut config = test_config();
+        config.auto_disable_after_removals = Some(1);
+        let mut bot = BotBuilder::new()
+            .config(config)
+            .endpoints(Endpoints {
+                reddit: base.clone(),
+                reddit_oauth: base.clone(),
+                github: base.clone(),
+            })
+            .store(Box::new(MemoryStore::default()))
+            .clock(Box::new(clock.clone()))
+            .build()
+            .unwrap()
+            .with_reporter(Reporter::new(
+                format!("{}/{}/1", server_url().replace("://", "://key@"), prefix)
+                    .parse()
+                    .unwrap(),
+            ));
+        bot.watch_listing_once(
+            &Listing::subreddits("invited").unwrap(),
+            &mut Cursor::default(),
+        )
+        .await
+        .unwrap();
+        comment.assert();
+        let stats = bot
+            .core
+            .events
+            .lock()
+            .unwrap()
+            .stats_mut()
+            .subreddit("Invited")
+            .clone();
+        assert_eq!(
+            (
+                stats.scanned,
+                stats.candidates,
+                stats.unlicensed,
+                stats.replies
+            ),
+            (1, 1, 1, 1)
+        );
+
+        // the reply isn't looked at again until the delay is up
+        bot.check_removals_if_due().await;
+        assert_eq!(bot.removal_checks.len(), 1);
+        clock.advance(Duration::from_secs(120));
+        bot.check_removals_if_due().await;
+        assert!(bot.removal_checks.is_empty());
+        reply.assert();
+        bot
+    }
+
+    #[tokio::test]
+    async fn removed_replies_turn_off_replies_in_a_subreddit() {
+        let sentry = mock("POST", "/removed/api/1/store/")
+            .match_body(Matcher::PartialJsonString(
+                r#"{"level":"warning","tags":{"subreddit":"invited","post":"t3_rm"}}"#.to_owned(),
+            ))
+            .expect(1)
+            .create();
+        let clock = ManualClock::new(UNIX_EPOCH + Duration::from_secs(1_600_000_000));
+        let mut bot = reply_then_check_removal("removed", true, &clock).await;
+        sentry.assert();
+        // even though the subreddit invited the bot
+        assert_eq!(
+            bot.subreddit_verdict("invited").await,
+            Verdict::ReportOnly("replies were removed".to_owned())
+        );
+    }
+
+    #[tokio::test]
+    async fn subreddits_needing_approval_are_only_reported_in_until_approved() {
+        let sentry = mock("POST", "/restricted/api/1/store/")
+            .match_body(Matcher::PartialJsonString(
+                r#"{"level":"warning","tags":{"subreddit":"Invited","post":"t3_first"}}"#
+                    .to_owned(),
+            ))
+            .expect(1)
+            .create();
+        let refused = mock("POST", "/restricted/api/comment")
+            .with_status(403)
+            .with_body(
+                r#"{"json":{"errors":[["SUBREDDIT_NOTALLOWED","you aren't allowed to post there.","parent"]]}}"#,
+            )
+            .expect(2)
+            .create();
+        let store = MemoryStore::default();
+        let clock = ManualClock::new(UNIX_EPOCH + Duration::from_secs(1_600_000_000));
+        let mut bot =
+            ack_bot("restricted", AckMode::Off, &store, &clock).with_reporter(Reporter::new(
+                format!("{}/restricted/1", server_url().replace("://", "://key@"))
+                    .parse()
+                    .unwrap(),
+            ));
+        let reply = |fullname: &str| PendingReply {
+            details: PostDetails {
+                subreddit: "Invited".to_owned(),
+                ..PostDetails::default()
+            },
+            ..claimed_reply(fullname)
+        };
+
+        // the refusal is recorded, rather than failing the pass
+        bot.deliver(&reply("t3_first")).await.unwrap();
+        sentry.assert();
+        assert!(store.reply_claims().unwrap().is_empty());
+        let events = bot.core.events.lock().unwrap().recent(10);
+        assert_eq!(events[0].action, "report only: commenting needs approval");
+        let restrictions = store.restrictions().unwrap();
+        assert_eq!(restrictions.len(), 1);
+        assert_eq!(restrictions[0].subreddit, "invited");
+        let report_only = Verdict::ReportOnly("commenting needs approval".to_owned());
+        assert_eq!(bot.subreddit_verdict("invited").await, report_only);
+
+        // a reply is tried again a day later, and only once
+        clock.advance(Duration::from_secs(restricted::PROBE_INTERVAL - 1));
+        assert_eq!(bot.subreddit_verdict("invited").await, report_only);
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(bot.subreddit_verdict("invited").await, Verdict::Allowed);
+        assert_eq!(bot.subreddit_verdict("invited").await, report_only);
+        bot.deliver(&reply("t3_second")).await.unwrap();
+        refused.assert();
+        assert_eq!(bot.subreddit_verdict("invited").await, report_only);
+
+        // until one goes through, once the bot has been approved
+        drop(refused);
+        let comment = comment_mock("restricted", "t1_third").expect(1).create();
+        clock.advance(Duration::from_secs(restricted::PROBE_INTERVAL));
+        assert_eq!(bot.subreddit_verdict("invited").await, Verdict::Allowed);
+        bot.deliver(&reply("t3_third")).await.unwrap();
+        comment.assert();
+        assert!(store.restrictions().unwrap().is_empty());
+        assert_eq!(bot.subreddit_verdict("invited").await, Verdict::Allowed);
+        assert_eq!(bot.subreddit_verdict("invited").await, Verdict::Allowed);
+    }
+
+    #[tokio::test]
+    async fn refusals_for_needing_approval_are_told_apart() {
+        let _forbidden = mock("POST", "/forbidden/api/comment")
+            .with_status(403)
+            .with_body(r#"{"message":"Forbidden","error":403}"#)
+            .create();
+        let _in_body = mock("POST", "/notallowed/api/comment")
+            .with_body(
+                r#"{"json":{"errors":[["SUBREDDIT_NOTALLOWED","you aren't allowed to post there.","parent"]]}}"#,
+            )
+            .create();
+        let store = MemoryStore::default();
+        let clock = ManualClock::new(UNIX_EPOCH + Duration::from_secs(1_600_000_000));
+        let mut post = claimed_reply("t3_refused");
+        post.details.subreddit = "locked".to_owned();
+
+        let mut bot = ack_bot("forbidden", AckMode::Off, &store, &clock);
+        let e = bot.respond_to(&post).await.unwrap_err();
+        assert!(matches!(e, BotError::Permanent(_)), "{:?}", e);
+
+        // Reddit can refuse with a 200, and errors in the body
+        let mut bot = ack_bot("notallowed", AckMode::Off, &store, &clock);
+        let e = bot.respond_to(&post).await.unwrap_err();
+        assert!(
+            matches!(&e, BotError::CommentRestricted(subreddit) if subreddit == "locked"),
+            "{:?}",
+            e
+        );
+        assert!(store.reply_claims().unwrap().is_empty());
+        assert!(store.replies().unwrap().is_empty());
+    }
+
+    /// A bot that acknowledges new licenses in the given way, with
+    /// endpoints under `prefix`.
+    fn ack_bot(prefix: &str, mode: AckMode, store: &MemoryStore, clock: &ManualClock) -> Bot {
+        let base = format!("{}/{}", server_url(), prefix);
+        let mut config = test_config();
+        config.ack_resolved = mode;
+        BotBuilder::new()
+            .config(config)
+            .endpoints(Endpoints {
+                reddit: base.clone(),
+                reddit_oauth: base.clone(),
+                github: base,
+            })
+            .store(Box::new(store.clone()))
+            .clock(Box::new(clock.clone()))
+            .build()
+            .unwrap()
+    }
+
+    /// A store with a reply about `{prefix}/repo` in comment `t1_{prefix}`
+    /// made a day before `now`, and the GitHub mocks saying it has an MIT
+    /// license now.
+    fn licensed_since(prefix: &str, now: u64) -> (MemoryStore, Vec<mockito::Mock>) {
+        let mut store = MemoryStore::default();
+        store
+            .add_replies(&[ReplyRecord {
+                author: "op".to_owned(),
+                repo: format!("{}/repo", prefix),
+                post: format!("t3_{}", prefix),
+                comment: Some(format!("t1_{}", prefix)),
+                at: now - 24 * 60 * 60,
+                resolved: None,
+            }])
+            .unwrap();
+        let repo = format!("/{}/repos/{}/repo", prefix, prefix);
+        let mocks = vec![
+            mock("GET", &*repo).with_body("{}").create(),
+            mock("GET", &*format!("{}/license", repo))
+                .with_body(r#"{"license":{"spdx_id":"MIT"}}"#)
+                .create(),
+        ];
+        (store, mocks)
+    }
+
+    #[tokio::test]
+    async fn acknowledges_new_licenses_in_a_reply_to_its_comment() {
+        let now = 1_600_000_000;
+        let clock = ManualClock::new(UNIX_EPOCH + Duration::from_secs(now));
+        let (store, _github) = licensed_since("ackreply", now);
+        let comment = mock("POST", "/ackreply/api/comment")
+            .match_body(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("thing_id".to_owned(), "t1_ackreply".to_owned()),
+                Matcher::Regex("MIT\\+license".to_owned()),
+            ]))
+            .expect(1)
+            .create();
+
+        let bot = ack_bot("ackreply", AckMode::Comment, &store, &clock);
+        bot.check_resolved_if_due().await;
+        assert_eq!(store.replies().unwrap()[0].resolved, Some(now));
+        // not checked again until the interval is up, and then not acknowledged twice
+        bot.check_resolved_if_due().await;
+        clock.advance(Duration::from_secs(resolved::CHECK_INTERVAL));
+        bot.check_resolved_if_due().await;
+
+        // nor after a restart
+        let restarted = ack_bot("ackreply", AckMode::Comment, &store, &clock);
+        restarted.check_resolved_if_due().await;
+        comment.assert();
+    }
+
+    #[tokio::test]
+    async fn acknowledges_new_licenses_as_configured() {
+        let now = 1_600_000_000;
+        let clock = ManualClock::new(UNIX_EPOCH + Duration::from_secs(now));
+        let (store, _github) = licensed_since("ackedit", now);
+        let _info = mock("GET", "/ackedit/api/info")
+            .match_query(Matcher::UrlEncoded(
+                "id".to_owned(),
+                "t1_ackedit".to_owned(),
+            ))
+            .with_body(
+                r#"{"data":{"children":[{"data":{"name":"t1_ackedit","link_id":"t3_ackedit","body":"Original text"}}]}}"#,
+            )
+            .create();
+        let edit = mock("POST", "/ackedit/api/editusertext")
+            .match_body(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("thing_id".to_owned(), "t1_ackedit".to_owned()),
+                Matcher::Regex("^.*text=Original\\+text.*MIT\\+license".to_owned()),
+            ]))
+            .expect(1)
+            .create();
+        let comment = mock("POST", "/ackedit/api/comment").expect(0).create();
+        ack_bot("ackedit", AckMode::Edit, &store, &clock)
+            .check_resolved_if_due()
+            .await;
+        edit.assert();
+        comment.assert();
+        assert_eq!(store.replies().unwrap()[0].resolved, Some(now));
+
+        let (store, _github) = licensed_since("ackoff", now);
+        let license = mock("GET", "/ackoff/repos/ackoff/repo/license")
+            .expect(0)
+            .create();
+        let comment = mock("POST", "/ackoff/api/comment").expect(0).create();
+        ack_bot("ackoff", AckMode::Off, &store, &clock)
+            .check_resolved_if_due()
+            .await;
+        license.assert();
+        comment.assert();
+        assert_eq!(store.replies().unwrap()[0].resolved, None);
+    }
+
+    #[tokio::test]
+    async fn replies_left_up_are_not_counted() {
+        let sentry = mock("POST", "/kept/api/1/store/").expect(0).create();
+        let clock = ManualClock::new(UNIX_EPOCH + Duration::from_secs(1_600_000_000));
+        let mut bot = reply_then_check_removal("kept", false, &clock).await;
+        sentry.assert();
+        assert_eq!(bot.subreddit_verdict("invited").await, Verdict::Allowed);
+    }
+
+    /// A bot watching /r/`prefix`, which Reddit answers with `status` and
+    /// `body`, after a pass over it.
+    async fn watch_unavailable(
+        prefix: &str,
+        status: usize,
+        body: &str,
+        quarantine_optin: bool,
+    ) -> (Bot, Unavailable) {
+        let _listing = mock("GET", format!("/{0}/r/{0}/new", prefix).as_str())
+            .match_query(Matcher::Any)
+            .with_status(status)
+            .with_body(body)
+            .create();
+        let base = format!("{}/{}", server_url(), prefix);
+        let mut bot = BotBuilder::new()
+            .config(Config {
+                unavailable_retry_interval: 0,
+                quarantine_optin,
+                ..test_config()
+            })
+            .endpoints(Endpoints {
+                reddit: base.clone(),
+                reddit_oauth: base.clone(),
+                github: base,
+            })
+            .store(Box::new(MemoryStore::default()))
+            .build()
+            .unwrap()
+            .with_reporter(Reporter::new(
+                format!("{}/{}/1", server_url().replace("://", "://key@"), prefix)
+                    .parse()
+                    .unwrap(),
+            ));
+        let listing = Listing::subreddits(prefix).unwrap();
+        let e = bot
+            .watch_pass(&listing, &mut Cursor::default())
+            .await
+            .unwrap_err();
+        let reason = super::unavailable(&e).unwrap().clone();
+        (bot, reason)
+    }
+
+    #[tokio::test]
+    async fn banned_and_private_subreddits_are_backed_off() {
+        for (prefix, status, body, expected) in [
+            (
+                "banned",
+                404,
+                r#"{"reason": "banned", "message": "Not Found", "error": 404}"#,
+                Unavailable::Banned,
+            ),
+            (
+                "private",
+                403,
+                r#"{"reason": "private", "message": "Forbidden", "error": 403}"#,
+                Unavailable::Private,
+            ),
+        ] {
+            let sentry = mock("POST", format!("/{}/api/1/store/", prefix).as_str())
+                .match_body(Matcher::PartialJsonString(format!(
+                    r#"{{"level":"warning","tags":{{"subreddit":"{}"}}}}"#,
+                    prefix
+                )))
+                .expect(1)
+                .create();
+            let (mut bot, reason) = watch_unavailable(prefix, status, body, true).await;
+            assert_eq!(reason, expected);
+            let listing = Listing::subreddits(prefix).unwrap();
+            // only reported the first time
+            bot.back_off_unavailable(&listing, &reason).await;
+            bot.back_off_unavailable(&listing, &reason).await;
+            sentry.assert();
+            assert_eq!(bot.unavailable.get(prefix), Some(&expected));
+            assert!(bot.quarantine_optins.is_empty());
+        }
+    }
+
+    #[tokio::test]
+    async fn opts_in_to_quarantined_subreddits_once() {
+        let optin = mock("POST", "/quarantined/api/quarantine_optin")
+            .match_body(Matcher::UrlEncoded(
+                "sr_name".to_owned(),
+                "quarantined".to_owned(),
+            ))
+            .expect(1)
+            .create();
+        let sentry = mock("POST", "/quarantined/api/1/store/").expect(1).create();
+        let (mut bot, reason) = watch_unavailable(
+            "quarantined",
+            403,
+            r#"{"reason": "quarantined", "quarantine_message": "This community is quarantined.", "message": "Forbidden", "error": 403}"#,
+            true,
+        )
+        .await;
+        assert_eq!(reason, Unavailable::Quarantined);
+        let listing = Listing::subreddits("quarantined").unwrap();
+        bot.back_off_unavailable(&listing, &reason).await;
+        optin.assert();
+        assert!(bot.unavailable.is_empty());
+        // if opting in didn't help, it's backed off from like any other
+        bot.back_off_unavailable(&listing, &reason).await;
+        optin.assert();
+        sentry.assert();
+        assert_eq!(
+            bot.unavailable.get("quarantined"),
+            Some(&Unavailable::Quarantined)
+        );
+    }
+
+    #[tokio::test]
+    async fn quarantined_subreddits_are_not_opted_in_to_unless_configured() {
+        let optin = mock("POST", "/interstitial/api/quarantine_optin")
+            .expect(0)
+            .create();
+        let _sentry = mock("POST", "/interstitial/api/1/store/").create();
+        let (mut bot, reason) = watch_unavailable(
+            "interstitial",
+            200,
+            "<html><body><p>This community has been quarantined.</p></body></html>",
+            false,
+        )
+        .await;
+        assert_eq!(reason, Unavailable::Quarantined);
+        bot.back_off_unavailable(&Listing::subreddits("interstitial").unwrap(), &reason)
+            .await;
+        optin.assert();
+        assert!(bot.unavailable.contains_key("interstitial"));
+    }
+
+    #[tokio::test]
+    async fn no_reply_where_bots_are_banned() {
+        let listing = format!(
+            r#"{{"data":{{"after":null,"children":[{}]}}}}"#,
+            link_post("t3_banned", "https://github.com/banned/repo"),
+        );
+        let _listing = mock("GET", "/r/botfree/new")
+            .match_query(Matcher::Any)
+            .with_body(listing)
+            .create();
+        let _repo = mock("GET", "/repos/banned/repo").with_body("{}").create();
+        let _license = mock("GET", "/repos/banned/repo/license")
+            .with_status(404)
+            .create();
+        let _info = mock("GET", "/api/info")
+            .match_query(Matcher::UrlEncoded("id".to_owned(), "t3_banned".to_owned()))
+            .with_body(
+                r#"{"data":{"children":[{"data":{"name":"t3_banned","subreddit":"botfree"}}]}}"#,
+            )
+            .create();
+        let _subreddit = subreddit_mocks(
+            "botfree",
+            r#"{"rules":[{"short_name":"Spam","description":"Bot accounts are banned"}]}"#,
+            1,
+        );
+        let comment = mock("POST", "/api/comment")
+            .match_body(Matcher::UrlEncoded(
+                "thing_id".to_owned(),
+                "t3_banned".to_owned(),
+            ))
+            .expect(0)
+            .create();
+
+        let mut bot = test_bot();
+        bot.watch_listing_once(
+            &Listing::subreddits("botfree").unwrap(),
+            &mut Cursor::default(),
+        )
+        .await
+        .unwrap();
+
+        comment.assert();
+    }
+
+    #[tokio::test]
+    async fn error_chain_names_post_and_repo() {
+        let listing = format!(
+            r#"{{"data":{{"after":null,"children":[{}]}}}}"#,
+            link_post("t3_ctx", "https://github.com/context/repo"),
+        );
+        let _listing = mock("GET", "/r/context/new")
+            .match_query(Matcher::Any)
+            .with_body(listing)
+            .create();
+        let _repo = mock("GET", "/repos/context/repo").with_status(502).create();
+
+        let mut bot = test_bot();
+        let err = bot
+            .watch_listing_once(
+                &Listing::subreddits("context").unwrap(),
+                &mut Cursor::default(),
+            )
+            .await
+            .unwrap_err();
+        let rendered = format!("{:#}", err);
+
+        assert!(rendered.contains("post t3_ctx"), "{}", rendered);
+        assert!(
+            rendered.contains("https://github.com/context/repo"),
+            "{}",
+            rendered
+        );
+        assert!(rendered.contains("'context/repo'"), "{}", rendered);
+        assert!(rendered.contains("502"), "{}", rendered);
+    }
+
+    #[tokio::test]
+    async fn links_to_github_profiles_are_skipped() {
+        let listing = format!(
+            r#"{{"data":{{"after":null,"children":[{},{},{}]}}}}"#,
+            link_post("t3_badurl3", "https://github.com/badurl/project"),
+            link_post("t3_badurl2", "https://github.com/just-an-org"),
+            link_post("t3_badurl1", "https://github.com/someone?tab=repositories"),
+        );
+        let _listing = mock("GET", "/r/badurl/new")
+            .match_query(Matcher::Any)
+            .with_body(listing)
+            .create();
+        let repo = mock("GET", "/repos/badurl/project")
+            .with_body("{}")
+            .expect(1)
+            .create();
+        let license = mock("GET", "/repos/badurl/project/license")
+            .with_body(r#"{"license": {"spdx_id": "MIT"}}"#)
+            .expect(1)
+            .create();
+
+        let mut bot = test_bot();
+        bot.watch_listing_once(
+            &Listing::subreddits("badurl").unwrap(),
+            &mut Cursor::default(),
+        )
+        .await
+        .unwrap();
+
+        // the profile links cost nothing, and the post beside them is checked
+        repo.assert();
+        license.assert();
+        for fullname in &["t3_badurl1", "t3_badurl2"] {
+            assert_eq!(
+                bot.processed.skipped(fullname),
+                Some(SkipReason::NotARepository)
+            );
+        }
+        assert_eq!(bot.processed.skipped("t3_badurl3"), None);
+    }
+
+    #[tokio::test]
+    async fn invalid_repo_is_skipped_and_others_checked() {
+        let listing = format!(
+            r#"{{"data":{{"after":null,"children":[{},{}]}}}}"#,
+            link_post("t3_inv1", "https://github.com/skipped/missing"),
+            link_post("t3_inv2", "https://github.com/skipped/present"),
+        );
+        let _listing = mock("GET", "/r/skipped/new")
+            .match_query(Matcher::Any)
+            .with_body(listing)
+            .create();
+        let _missing = mock("GET", "/repos/skipped/missing")
+            .with_status(404)
+            .create();
+        let _present = mock("GET", "/repos/skipped/present")
+            .with_body("{}")
+            .create();
+        let _license = mock("GET", "/repos/skipped/present/license")
+            .with_status(404)
+            .create();
+        let _info = info_mock("t3_inv2", &["t3_inv2"]);
+        let comment = mock("POST", "/api/comment")
+            .match_body(Matcher::UrlEncoded(
+                "thing_id".to_owned(),
+                "t3_inv2".to_owned(),
+            ))
+            .expect(1)
+            .create();
+
+        let mut bot = test_bot();
+        bot.watch_listing_once(
+            &Listing::subreddits("skipped").unwrap(),
+            &mut Cursor::default(),
+        )
+        .await
+        .unwrap();
+        comment.assert();
+    }
+
+    #[tokio::test]
+    async fn rate_limited_license_lookup_is_not_unlicensed() {
+        let listing = format!(
+            r#"{{"data":{{"after":null,"children":[{}]}}}}"#,
+            link_post("t3_ghrl", "https://github.com/ghlimit/repo"),
+        );
+        let _listing = mock("GET", "/r/ghlimit/new")
+            .match_query(Matcher::Any)
+            .with_body(listing)
+            .create();
+        let _repo = mock("GET", "/repos/ghlimit/repo").with_body("{}").create();
+        let _license = mock("GET", "/repos/ghlimit/repo/license")
+            .with_status(403)
+            .with_header("x-ratelimit-remaining", "0")
+            .with_header("x-ratelimit-reset", "1000")
+            .create();
+        let comment = mock("POST", "/api/comment")
+            .match_body(Matcher::UrlEncoded(
+                "thing_id".to_owned(),
+                "t3_ghrl".to_owned(),
+            ))
+            .expect(0)
+            .create();
+
+        let mut bot = test_bot();
+        let err = bot
+            .watch_listing_once(
+                &Listing::subreddits("ghlimit").unwrap(),
+                &mut Cursor::default(),
+            )
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<BotError>().map(BotError::kind),
+            Some(BotError::GitHubRateLimited { .. })
+        ));
+        comment.assert();
+    }
+
+    #[tokio::test]
+    async fn license_policy_decides_what_counts_as_a_license() {
+        let mut mocks = vec![];
+        for (name, license) in &[
+            ("mit", r#"{"key":"mit","spdx_id":"MIT"}"#),
+            ("wtfpl", r#"{"key":"wtfpl","spdx_id":"WTFPL"}"#),
+            ("other", r#"{"key":"other","spdx_id":"NOASSERTION"}"#),
+        ] {
+            let repo = format!("/repos/policy/{}", name);
+            mocks.push(mock("GET", &*repo).with_body("{}").create());
+            mocks.push(
+                mock("GET", &*format!("{}/license", repo))
+                    .with_body(format!(r#"{{"license":{}}}"#, license))
+                    .create(),
+            );
+        }
+        let bot = test_bot();
+        let check = |name: &'static str| {
+            let bot = &bot;
+            async move {
+                let url = format!("https://github.com/policy/{}", name);
+                let repo = GhRepo::new("policy", name);
+                bot.check_link(&repo, &url).await.unwrap().0
+            }
+        };
+        let found = |id: &str| License::Found(Some(id.to_owned()));
+
+        assert_eq!(check("mit").await, found("MIT"));
+        assert_eq!(check("wtfpl").await, found("WTFPL"));
+        assert_eq!(check("other").await, found("NOASSERTION"));
+
+        configure(&bot, |c| {
+            c.treat_other_as_unlicensed = true;
+            c.non_licenses = vec!["WTFPL".to_owned()];
+        });
+        assert_eq!(check("mit").await, found("MIT"));
+        assert_eq!(check("wtfpl").await, License::Missing);
+        assert_eq!(check("other").await, License::Missing);
+    }
+
+    #[tokio::test]
+    async fn trails_and_counts_checks_a_fallback_overturned() {
+        let listing = format!(
+            r#"{{"data":{{"after":null,"children":[{}]}}}}"#,
+            link_post("t3_trailed", "https://github.com/trailed/repo/tree/dev"),
+        );
+        let _listing = mock("GET", "/r/trailed/new")
+            .match_query(Matcher::Any)
+            .with_body(listing)
+            .create();
+        let _repo = mock("GET", "/repos/trailed/repo")
+            .with_body(r#"{"default_branch":"main"}"#)
+            .create();
+        let _license = mock("GET", "/repos/trailed/repo/license")
+            .with_status(404)
+            .create();
+        let _contents = mock("GET", "/repos/trailed/repo/contents/")
+            .match_query(Matcher::UrlEncoded("ref".to_owned(), "dev".to_owned()))
+            .with_body(r#"[{"type":"file","name":"LICENSE"}]"#)
+            .create();
+
+        let mut bot = test_bot();
+        bot.watch_listing_once(
+            &Listing::subreddits("trailed").unwrap(),
+            &mut Cursor::default(),
+        )
+        .await
+        .unwrap();
+
+        let events = bot.core.events.lock().unwrap();
+        let event = events
+            .recent(10)
+            .into_iter()
+            .find(|e| e.fullname == "t3_trailed")
+            .unwrap();
+        assert_eq!(event.verdict, "licensed");
+        assert_eq!(
+            event.detection_path,
+            [
+                DetectionStep::new(Probe::LicenseApi, check::Verdict::Unlicensed),
+                DetectionStep::new(Probe::LinkedRef, check::Verdict::Licensed).with_detail("dev"),
+            ]
+        );
+        // the post doesn't say which subreddit it's in
+        let stats = events.stats().clone().subreddit("").clone();
+        assert_eq!(stats.overturns["linked ref"], 1);
+        assert_eq!(stats.overturns.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn github_asking_for_a_pause_is_a_rate_limit() {
+        let listing = format!(
+            r#"{{"data":{{"after":null,"children":[{}]}}}}"#,
+            link_post("t3_ghpause", "https://github.com/ghpause/repo"),
+        );
+        let _listing = mock("GET", "/r/ghpause/new")
+            .match_query(Matcher::Any)
+            .with_body(listing)
+            .create();
+        let _repo = mock("GET", "/repos/ghpause/repo")
+            .with_status(429)
+            .with_header("retry-after", "120")
+            .create();
+
+        let mut bot = test_bot();
+        let err = bot
+            .watch_listing_once(
+                &Listing::subreddits("ghpause").unwrap(),
+                &mut Cursor::default(),
+            )
+            .await
+            .unwrap_err();
+        match err.downcast_ref::<BotError>().map(BotError::kind) {
+            Some(BotError::GitHubRateLimited { retry_after, .. }) => {
+                assert_eq!(*retry_after, Some(Duration::from_secs(120)))
+            }
+            other => panic!("expected a GitHub rate limit, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn rejected_token_logs_in_again() {
+        let listing = mock("GET", "/r/expired/new")
+            .match_query(Matcher::Any)
+            .with_status(401)
+            .create();
+        let login = mock("POST", "/api/v1/access_token")
+            .with_body(
+                r#"{"access_token":"fresh","token_type":"bearer","expires_in":3600,"scope":"*"}"#,
+            )
+            .expect(1)
+            .create();
+
+        let mut bot = test_bot();
+        let listing_name = Listing::subreddits("expired").unwrap();
+        let err = bot
+            .watch_listing_once(&listing_name, &mut Cursor::default())
+            .await
+            .unwrap_err();
+        bot.recover(&listing_name, &err).await.unwrap();
+
+        listing.assert();
+        login.assert();
+        assert_eq!(
+            bot.core.reddit.token.read().unwrap().access,
+            Some("fresh".to_owned())
+        );
+    }
+
+    #[tokio::test]
+    async fn simulates_the_sample_listing() {
+        let tmp = tempfile::tempdir().unwrap();
+        let mut config = test_config();
+        config.state_dir = tmp.path().into();
+        config.author_opt_out_phrases = vec!["all rights reserved".to_owned()];
+        config.approval_mode = ApprovalMode::Manual;
+        let options = simulate::Options {
+            config,
+            fixtures: simulate::parse_fixtures(include_str!("../samples/fixtures.json")).unwrap(),
+        };
+        let decisions = Bot::simulate(include_str!("../samples/listing.json"), &options)
+            .await
+            .unwrap();
+        let report = simulate::report(&decisions);
+        if env::var("CFL_UPDATE_SAMPLES").is_ok() {
+            fs::write("samples/decisions.txt", &report).unwrap();
+        }
+        assert_eq!(report, include_str!("../samples/decisions.txt"));
+        assert_eq!(decisions.len(), 8);
+        // nothing was left behind in the state directory
+        assert_eq!(fs::read_dir(tmp.path()).unwrap().count(), 0);
+    }
+
+    #[tokio::test]
+    async fn replays_recorded_reply_flow() {
+        let transport = Transport::replay(include_str!("../cassettes/reply_flow.json"));
+        let endpoints = Endpoints {
+            reddit: "http://reddit.invalid".to_owned(),
+            reddit_oauth: "http://oauth.reddit.invalid".to_owned(),
+            github: "http://github.invalid".to_owned(),
+        };
+        let store = MemoryStore::default();
+        let mut bot = BotBuilder::new()
+            .config(test_config())
+            .endpoints(endpoints)
+            .store(Box::new(store.clone()))
+            .build()
+            .unwrap()
+            .with_transport(transport.clone());
+
+        bot.login().await.unwrap();
+        let mut cursor = Cursor::default();
+        let caught_up = bot
+            .watch_listing_once(&Listing::subreddits("licensefree").unwrap(), &mut cursor)
+            .await
+            .unwrap();
+
+        assert!(caught_up);
+        assert_eq!(cursor.anchor(), Some("t3_1f8zq2k"));
+        assert_eq!(bot.core.replies.lock().unwrap().len(), 1);
+        assert_eq!(transport.remaining(), 0);
+        let events = bot.core.events.lock().unwrap().recent(10);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].title, "I made a terminal pomodoro timer in Rust");
+        assert_eq!(events[0].subreddit, "licensefree");
+        assert_eq!(events[0].repo, "someone-new/tomato-term");
+        assert_eq!(events[0].action, "replied");
+        let replies = store.replies().unwrap();
+        assert_eq!(replies.len(), 1);
+        assert_eq!(replies[0].author, events[0].author);
+        assert_eq!(replies[0].repo, "someone-new/tomato-term");
+        assert_eq!(replies[0].post, "t3_1f8zq2k");
+        assert_eq!(replies[0].comment.as_deref(), Some("t1_lm3x9qa"));
+    }
+
+    #[tokio::test]
+    async fn one_reply_per_author_and_repo() {
+        let by = |name: &str, author: &str| {
+            format!(
+                r#"{{"data":{{"name":"{}","author":"{}","domain":"github.com","url":"https://github.com/dedup-author/repo"}}}}"#,
+                name, author
+            )
+        };
+        let listing = format!(
+            r#"{{"data":{{"after":null,"children":[{},{}]}}}}"#,
+            by("t3_da1", "alice"),
+            by("t3_da2", "bob"),
+        );
+        let _listing = mock("GET", "/r/dedupauthor/new")
+            .match_query(Matcher::Any)
+            .with_body(listing)
+            .create();
+        let _repo = mock("GET", "/repos/dedup-author/repo")
+            .with_body("{}")
+            .create();
+        let _license = mock("GET", "/repos/dedup-author/repo/license")
+            .with_status(404)
+            .create();
+        let _info = info_mock("t3_da1,t3_da2", &["t3_da1", "t3_da2"]);
+        // alice was replied to about the repository last week, bob never was
+        let comment = mock("POST", "/api/comment")
+            .match_body(Matcher::UrlEncoded(
+                "thing_id".to_owned(),
+                "t3_da2".to_owned(),
+            ))
+            .expect(1)
+            .create();
+
+        let mut store = MemoryStore::default();
+        let clock = ManualClock::new(UNIX_EPOCH + Duration::from_secs(1_600_000_000));
+        store
+            .add_replies(&[ReplyRecord {
+                author: "alice".to_owned(),
+                repo: "dedup-author/repo".to_owned(),
+                post: "t3_old".to_owned(),
+                comment: None,
+                at: 1_600_000_000 - 7 * 24 * 60 * 60,
+                resolved: None,
+            }])
+            .unwrap();
+        let mut bot = BotBuilder::new()
+            .config(test_config())
+            .endpoints(test_endpoints())
+            .store(Box::new(store.clone()))
+            .clock(Box::new(clock))
+            .build()
+            .unwrap();
+        bot.watch_listing_once(
+            &Listing::subreddits("dedupauthor").unwrap(),
+            &mut Cursor::default(),
+        )
+        .await
+        .unwrap();
+        comment.assert();
+        let actions: Vec<(String, String)> = bot
+            .core
+            .events
+            .lock()
+            .unwrap()
+            .recent(10)
+            .into_iter()
+            .map(|e| (e.fullname, e.action))
+            .collect();
+        assert!(actions.contains(&("t3_da1".to_owned(), "already replied to author".to_owned())));
+        let replies = store.replies().unwrap();
+        assert_eq!(replies.len(), 2);
+        assert_eq!(replies[1].author, "bob");
+    }
+
+    /// Watch a subreddit with a post linking a repository that was replied
+    /// about in an earlier submission of the same link.
+    /// A backfill of the last week over three pages of posts, the second of
+    /// which reaches back past the cutoff.
+    async fn backfill_pages(prefix: &str, state_dir: &Path, dry_run: bool) -> Progress {
+        let now = 1_600_000_000;
+        let post = |n: u64, age: u64| {
+            format!(
+                r#"{{"kind":"t3","data":{{"name":"t3_{0}{1}","author":"op","subreddit":"{0}","created_utc":{2}.0,"domain":"github.com","url":"https://github.com/{0}/repo{1}"}}}}"#,
+                prefix,
+                n,
+                now - age
+            )
+        };
+        let page = |posts: &[String], after: &str| {
+            format!(
+                r#"{{"data":{{"after":{},"children":[{}]}}}}"#,
+                after,
+                posts.join(",")
+            )
+        };
+        let day = 24 * 60 * 60;
+        let path = format!("/{0}/r/{0}/new", prefix);
+        let _first = mock("GET", path.as_str())
+            .match_query(Matcher::Exact("raw_json=1&limit=100".to_owned()))
+            .with_body(page(
+                &[post(1, 60), post(2, day)],
+                &format!(r#""t3_{}2""#, prefix),
+            ))
+            .create();
+        let _second = mock("GET", path.as_str())
+            .match_query(Matcher::UrlEncoded(
+                "after".to_owned(),
+                format!("t3_{}2", prefix),
+            ))
+            .with_body(page(
+                &[post(3, 6 * day), post(4, 8 * day)],
+                &format!(r#""t3_{}4""#, prefix),
+            ))
+            .create();
+        let third = mock("GET", path.as_str())
+            .match_query(Matcher::UrlEncoded(
+                "after".to_owned(),
+                format!("t3_{}4", prefix),
+            ))
+            .with_body(page(&[post(5, 9 * day)], "null"))
+            .expect(0)
+            .create();
+        let _repos = mock(
+            "GET",
+            Matcher::Regex(format!("^/{0}/repos/{0}/repo[0-9]$", prefix)),
+        )
+        .with_body("{}")
+        .create();
+        let _licenses = mock(
+            "GET",
+            Matcher::Regex(format!("^/{0}/repos/{0}/repo[0-9]/license$", prefix)),
+        )
+        .with_status(404)
+        .create();
+        let _info = mock("GET", format!("/{}/api/info", prefix).as_str())
+            .match_query(Matcher::Any)
+            .with_body(page(&[post(1, 60), post(2, day), post(3, 6 * day)], "null"))
+            .create();
+        let no_comment = mock("POST", format!("/{}/api/comment", prefix).as_str())
+            .expect(0)
+            .create();
+
+        let base = format!("{}/{}", server_url(), prefix);
+        let mut bot = BotBuilder::new()
+            .config(Config {
+                state_dir: state_dir.to_owned(),
+                invited_subreddits: vec![prefix.to_owned()],
+                github_issue_mode: IssueMode::Also,
+                ..test_config()
+            })
+            .endpoints(Endpoints {
+                reddit: base.clone(),
+                reddit_oauth: base.clone(),
+                github: base,
+            })
+            .store(Box::new(MemoryStore::default()))
+            .clock(Box::new(ManualClock::new(
+                UNIX_EPOCH + Duration::from_secs(now),
+            )))
+            .build()
+            .unwrap();
+        let mut reported = vec![];
+        let done = bot
+            .backfill(&Subreddit::new(prefix).unwrap(), 7 * day, dry_run, |p| {
+                reported.push(p.clone())
+            })
+            .await
+            .unwrap();
+        third.assert();
+        no_comment.assert();
+        assert_eq!(reported.len(), 2);
+        assert_eq!(reported.last(), Some(&done));
+        done
+    }
+
+    #[tokio::test]
+    async fn backfill_queues_replies_for_posts_since_the_cutoff() {
+        let tmp = tempfile::tempdir().unwrap();
+        let done = backfill_pages("backfill", tmp.path(), false).await;
+        assert_eq!(
+            done,
+            Progress {
+                pages: 2,
+                posts: 3,
+                candidates: 3,
+            }
+        );
+        let queue = ApprovalQueue::load(tmp.path()).unwrap();
+        let queued: Vec<&str> = queue
+            .items()
+            .iter()
+            .map(|i| i.reply.fullname.as_str())
+            .collect();
+        assert_eq!(queued, vec!["t3_backfill1", "t3_backfill2", "t3_backfill3"]);
+    }
+
+    #[tokio::test]
+    async fn backfill_dry_run_queues_nothing() {
+        let tmp = tempfile::tempdir().unwrap();
+        let done = backfill_pages("backfilldry", tmp.path(), true).await;
+        assert_eq!(done.candidates, 3);
+        assert!(ApprovalQueue::load(tmp.path()).unwrap().items().is_empty());
+    }
+
+    async fn watch_duplicate(prefix: &str, mode: DuplicateMode) -> Bot {
+        let post = format!(
+            r#"{{"kind":"t3","data":{{"name":"t3_{0}new","author":"op","subreddit":"invited","domain":"github.com","url":"https://github.com/{0}/repo"}}}}"#,
+            prefix
+        );
+        let _listing = mock("GET", format!("/{}/r/{}/new", prefix, prefix).as_str())
+            .match_query(Matcher::Any)
+            .with_body(format!(
+                r#"{{"data":{{"after":null,"children":[{}]}}}}"#,
+                post
+            ))
+            .create();
+        let _repo = mock("GET", format!("/{0}/repos/{0}/repo", prefix).as_str())
+            .with_body("{}")
+            .create();
+        let _license = mock(
+            "GET",
+            format!("/{0}/repos/{0}/repo/license", prefix).as_str(),
+        )
+        .with_status(404)
+        .create();
+        let _info = mock("GET", format!("/{}/api/info", prefix).as_str())
+            .match_query(Matcher::Any)
+            .with_body(format!(r#"{{"data":{{"children":[{}]}}}}"#, post))
+            .create();
+        let _duplicates = mock("GET", format!("/{0}/duplicates/{0}new", prefix).as_str())
+            .match_query(Matcher::Any)
+            .with_body(format!(
+                r#"[{{"data":{{"children":[{post}]}}}},
+                    {{"data":{{"children":[{{"kind":"t3","data":{{"name":"t3_{prefix}old","author":"op",
+                        "permalink":"/r/programming/comments/{prefix}old/repo/"}}}}]}}}}]"#,
+                post = post,
+                prefix = prefix
+            ))
+            .create();
+
+        let mut store = MemoryStore::default();
+        store
+            .add_replies(&[ReplyRecord {
+                author: "someone_else".to_owned(),
+                repo: format!("{}/repo", prefix),
+                post: format!("t3_{}old", prefix),
+                comment: None,
+                at: 1_600_000_000 - 24 * 60 * 60,
+                resolved: None,
+            }])
+            .unwrap();
+        let base = format!("{}/{}", server_url(), prefix);
+        let mut bot = BotBuilder::new()
+            .config(Config {
+                duplicate_mode: mode,
+                ..test_config()
+            })
+            .endpoints(Endpoints {
+                reddit: base.clone(),
+                reddit_oauth: base.clone(),
+                github: base,
+            })
+            .store(Box::new(store))
+            .clock(Box::new(ManualClock::new(
+                UNIX_EPOCH + Duration::from_secs(1_600_000_000),
+            )))
+            .build()
+            .unwrap();
+        bot.watch_listing_once(
+            &Listing::subreddits(prefix).unwrap(),
+            &mut Cursor::default(),
+        )
+        .await
+        .unwrap();
+        bot
+    }
+
+    #[tokio::test]
+    async fn skips_links_replied_to_in_earlier_submissions() {
+        let comment = mock("POST", "/dupeskip/api/comment").expect(0).create();
+        let bot = watch_duplicate("dupeskip", DuplicateMode::Skip).await;
+        comment.assert();
+        let action = bot.core.events.lock().unwrap().recent(1)[0].action.clone();
+        assert_eq!(action, "replied in earlier post");
+    }
+
+    #[tokio::test]
+    async fn points_to_replies_in_earlier_submissions() {
+        let texts = reply::texts(
+            &test_config(),
+            "invited",
+            Some("https://www.reddit.com/r/programming/comments/dupereplyold/repo/"),
+        )
+        .unwrap();
+        let text = reply::compose_earlier(&[], &texts);
+        let comment = mock("POST", "/dupereply/api/comment")
+            .match_body(Matcher::UrlEncoded("text".to_owned(), text))
+            .expect(1)
+            .create();
+        watch_duplicate("dupereply", DuplicateMode::Reply).await;
+        comment.assert();
+    }
+
+    #[tokio::test]
+    async fn reloaded_config_applies_on_next_pass() {
+        let (updates, receiver) = tokio::sync::watch::channel(test_config());
+        let mut bot = test_bot().with_config_updates(receiver);
+        bot.apply_config_updates();
+        assert!(!bot.reply_cap_reached());
+
+        let mut reloaded = test_config();
+        reloaded.max_replies_per_hour = Some(0);
+        reloaded.username = "renamed".to_owned();
+        updates.send(reloaded).unwrap();
+        bot.apply_config_updates();
+
+        assert!(bot.reply_cap_reached());
+        assert_eq!(bot.config().username, "bot");
+    }
+
+    fn claimed_reply(fullname: &str) -> PendingReply {
+        PendingReply {
+            fullname: fullname.to_owned(),
+            repos: vec![RepoLink {
+                repo: GhRepo::new("someone", "project"),
+                url: "https://github.com/someone/project".to_owned(),
+                removed_license: None,
+                summary: RepoSummary::default(),
+            }],
+            details: PostDetails::default(),
+            queued_at: 0,
+            deferred: false,
+            earlier: None,
+            cross_post: None,
+            check_after: None,
+            not_before: None,
+            stages: Stages::default(),
+        }
+    }
+
+    fn comment_mock(prefix: &str, name: &str) -> mockito::Mock {
+        mock("POST", format!("/{}/api/comment", prefix).as_str()).with_body(format!(
+            r#"{{"json":{{"data":{{"things":[{{"data":{{"name":"{}"}}}}]}}}}}}"#,
+            name
+        ))
+    }
+
+    /// Mocks under `/praise` for licensed repositories `praised/{name}`, each
+    /// with the given top-level files, linked by posts `t3_{name}`, which are
+    /// all up.
+    fn praised_repos(repos: &[(&str, &[&str])]) -> Vec<mockito::Mock> {
+        let mut mocks = vec![
+            mock(
+                "GET",
+                Matcher::Regex("^/praise/repos/praised/[a-z0-9]+$".to_owned()),
+            )
+            .with_body("{}")
+            .create(),
+            mock(
+                "GET",
+                Matcher::Regex("^/praise/repos/praised/[a-z0-9]+/license$".to_owned()),
+            )
+            .with_body(r#"{"license":{"spdx_id":"MIT"}}"#)
+            .create(),
+        ];
+        let mut live = vec![];
+        for (name, files) in repos {
+            let entries: Vec<String> = files
+                .iter()
+                .map(|f| format!(r#"{{"type":"file","name":"{}"}}"#, f))
+                .collect();
+            mocks.push(
+                mock("GET", &*format!("/praise/repos/praised/{}/contents/", name))
+                    .match_query(Matcher::Any)
+                    .with_body(format!("[{}]", entries.join(",")))
+                    .create(),
+            );
+            live.push(format!(
+                r#"{{"kind":"t3","data":{{"name":"t3_{}","author":"op"}}}}"#,
+                name
+            ));
+        }
+        mocks.push(
+            mock("GET", "/praise/api/info")
+                .match_query(Matcher::Any)
+                .with_body(format!(
+                    r#"{{"kind":"Listing","data":{{"children":[{}]}}}}"#,
+                    live.join(",")
+                ))
+                .create(),
+        );
+        mocks
+    }
+
+    /// Run a pass over /r/{subreddit}, whose posts link `praised/{name}`.
+    async fn praise_pass(bot: &mut Bot, subreddit: &str, names: &[&str]) {
+        let children: Vec<String> = names
+            .iter()
+            .map(|n| {
+                link_post(
+                    &format!("t3_{}", n),
+                    &format!("https://github.com/praised/{}", n),
+                )
+            })
+            .collect();
+        let _listing = mock("GET", &*format!("/praise/r/{}/new", subreddit))
+            .match_query(Matcher::Any)
+            .with_body(format!(
+                r#"{{"data":{{"after":null,"children":[{}]}}}}"#,
+                children.join(",")
+            ))
+            .create();
+        bot.watch_listing_once(
+            &Listing::subreddits(subreddit).unwrap(),
+            &mut Cursor::default(),
+        )
+        .await
+        .unwrap();
+    }
+
+    fn praise_mock(name: &str, times: usize) -> mockito::Mock {
+        comment_mock("praise", &format!("t1_{}", name))
+            .match_body(Matcher::Regex(format!("thing_id=t3_{}(&|$)", name)))
+            .expect(times)
+            .create()
+    }
+
+    #[tokio::test]
+    async fn praises_repositories_with_community_files() {
+        let _github = praised_repos(&[
+            ("exemplary", &["readme.md", "CONTRIBUTING", "LICENSE"]),
+            ("plain", &["README.md", "LICENSE"]),
+            ("unasked", &["README.md", "CONTRIBUTING.md"]),
+        ]);
+        let exemplary = praise_mock("exemplary", 1);
+        let plain = praise_mock("plain", 0);
+        let unasked = praise_mock("unasked", 0);
+
+        let mut store = MemoryStore::default();
+        let clock = ManualClock::new(UNIX_EPOCH + Duration::from_secs(1_600_000_000));
+        let mut bot = ack_bot("praise", AckMode::Off, &store, &clock);
+        praise_pass(&mut bot, "praiseoff", &["unasked"]).await;
+        configure(&bot, |c| c.praise_mode = true);
+        praise_pass(&mut bot, "praiseon", &["exemplary", "plain"]).await;
+
+        exemplary.assert();
+        plain.assert();
+        unasked.assert();
+        assert!(!store.try_claim_reply("t3_exemplary", 0).unwrap());
+    }
+
+    #[tokio::test]
+    async fn praise_is_capped_per_day() {
+        let _github = praised_repos(&[
+            ("capone", &["README", "CONTRIBUTING.md"]),
+            ("captwo", &["README", "CONTRIBUTING.md"]),
+            ("capthree", &["README", "CONTRIBUTING.md"]),
+        ]);
+        let one = praise_mock("capone", 1);
+        let two = praise_mock("captwo", 0);
+        let three = praise_mock("capthree", 1);
+
+        let store = MemoryStore::default();
+        let clock = ManualClock::new(UNIX_EPOCH + Duration::from_secs(1_600_000_000));
+        let mut bot = ack_bot("praise", AckMode::Off, &store, &clock);
+        configure(&bot, |c| {
+            c.praise_mode = true;
+            c.max_praise_per_day = 1;
+        });
+        praise_pass(&mut bot, "capfirst", &["capone"]).await;
+        praise_pass(&mut bot, "capsecond", &["captwo"]).await;
+        clock.advance(Duration::from_secs(25 * 60 * 60));
+        praise_pass(&mut bot, "capthird", &["capthree"]).await;
+
+        one.assert();
+        two.assert();
+        three.assert();
+    }
+
+    #[tokio::test]
+    async fn claimed_posts_are_not_praised() {
+        let _github = praised_repos(&[("claimed", &["README.md", "CONTRIBUTING.md"])]);
+        let claimed = praise_mock("claimed", 0);
+
+        let mut store = MemoryStore::default();
+        let clock = ManualClock::new(UNIX_EPOCH + Duration::from_secs(1_600_000_000));
+        let mut bot = ack_bot("praise", AckMode::Off, &store, &clock);
+        configure(&bot, |c| c.praise_mode = true);
+        assert!(store.try_claim_reply("t3_claimed", 1).unwrap());
+        praise_pass(&mut bot, "praiseclaimed", &["claimed"]).await;
+
+        claimed.assert();
+    }
+
+    #[tokio::test]
+    async fn racing_tasks_reply_to_a_post_once() {
+        let comment = comment_mock("claimrace", "t1_race").expect(1).create();
+        let store = MemoryStore::default();
+        let clock = ManualClock::new(UNIX_EPOCH + Duration::from_secs(1_600_000_000));
+        let bot = ack_bot("claimrace", AckMode::Off, &store, &clock);
+        let reply = claimed_reply("t3_race");
+        let tasks: Vec<_> = (0..4)
+            .map(|_| {
+                let mut task = bot.task();
+                let reply = reply.clone();
+                tokio::spawn(async move { task.respond_to(&reply).await.unwrap() })
+            })
+            .collect();
+        let replied = join_all(tasks)
+            .await
+            .into_iter()
+            .filter(|r| *r.as_ref().unwrap())
+            .count();
+        assert_eq!(replied, 1);
+        comment.assert();
+        let claims = store.reply_claims().unwrap();
+        assert_eq!(claims.len(), 1);
+        assert!(claims[0].replied);
+        assert_eq!(claims[0].comment.as_deref(), Some("t1_race"));
+        assert_eq!(store.replies().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn refused_replies_give_up_their_claim() {
+        let _comment = mock("POST", "/claimrefused/api/comment")
+            .with_status(403)
+            .create();
+        let mut store = MemoryStore::default();
+        let clock = ManualClock::new(UNIX_EPOCH + Duration::from_secs(1_600_000_000));
+        let mut bot = ack_bot("claimrefused", AckMode::Off, &store, &clock);
+        assert!(bot.respond_to(&claimed_reply("t3_locked")).await.is_err());
+        assert!(store.reply_claims().unwrap().is_empty());
+        assert!(store.try_claim_reply("t3_locked", 0).unwrap());
+    }
+
+    #[tokio::test]
+    async fn replies_that_timed_out_but_were_made_are_found() {
+        let comment = mock("POST", "/verifymade/api/comment")
+            .with_status(504)
+            .expect(1)
+            .create();
+        // the bot's own comments haven't caught up, but the post's have
+        let own = mock("GET", "/verifymade/user/bot/comments")
+            .match_query(Matcher::Any)
+            .with_body(r#"{"kind":"Listing","data":{"children":[]}}"#)
+            .expect(1)
+            .create();
+        let post = mock("GET", "/verifymade/comments/made")
+            .match_query(Matcher::Any)
+            .with_body(
+                r#"[{"kind":"Listing","data":{"children":[{"kind":"t3","data":{"name":"t3_made"}}]}},
+                    {"kind":"Listing","data":{"children":[
+                        {"kind":"t1","data":{"name":"t1_other","author":"someone"}},
+                        {"kind":"t1","data":{"name":"t1_made","author":"Bot"}},
+                        {"kind":"more","data":{"count":3,"children":["a","b","c"]}}
+                    ]}}]"#,
+            )
+            .expect(1)
+            .create();
+        let store = MemoryStore::default();
+        let clock = ManualClock::new(UNIX_EPOCH + Duration::from_secs(1_600_000_000));
+        let mut bot = ack_bot("verifymade", AckMode::Off, &store, &clock);
+        assert!(bot.respond_to(&claimed_reply("t3_made")).await.unwrap());
+        comment.assert();
+        own.assert();
+        post.assert();
+        let claims = store.reply_claims().unwrap();
+        assert_eq!(claims.len(), 1);
+        assert!(claims[0].replied);
+        assert_eq!(claims[0].comment.as_deref(), Some("t1_made"));
+        assert_eq!(store.replies().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn replies_that_timed_out_and_were_not_made_are_tried_again_once() {
+        let comment = mock("POST", "/verifylost/api/comment")
+            .with_status(504)
+            .expect(2)
+            .create();
+        let own = mock("GET", "/verifylost/user/bot/comments")
+            .match_query(Matcher::Any)
+            .with_body(
+                r#"{"kind":"Listing","data":{"children":[
+                    {"kind":"t1","data":{"name":"t1_elsewhere","link_id":"t3_elsewhere"}}
+                ]}}"#,
+            )
+            .expect(3)
+            .create();
+        let post = mock("GET", "/verifylost/comments/lost")
+            .match_query(Matcher::Any)
+            .with_body(
+                r#"[{"kind":"Listing","data":{"children":[{"kind":"t3","data":{"name":"t3_lost"}}]}},
+                    {"kind":"Listing","data":{"children":[
+                        {"kind":"t1","data":{"name":"t1_other","author":"someone"}}
+                    ]}}]"#,
+            )
+            .expect(2)
+            .create();
+        let mut store = MemoryStore::default();
+        let clock = ManualClock::new(UNIX_EPOCH + Duration::from_secs(1_600_000_000));
+        let mut bot = ack_bot("verifylost", AckMode::Off, &store, &clock);
+        let e = bot.respond_to(&claimed_reply("t3_lost")).await.unwrap_err();
+        assert!(e.may_have_been_sent(), "{:?}", e);
+        comment.assert();
+        post.assert();
+        assert!(store.replies().unwrap().is_empty());
+
+        // the claim stays until it's old enough to be sure about
+        let claims = store.reply_claims().unwrap();
+        assert_eq!(claims.len(), 1);
+        assert!(!claims[0].replied);
+        assert!(!store.try_claim_reply("t3_lost", 0).unwrap());
+        bot.reconcile_claims(0).await;
+        own.assert();
+        assert!(store.reply_claims().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn replies_that_fail_before_being_sent_give_up_their_claim() {
+        let closed = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let base = format!("http://{}", closed.local_addr().unwrap());
+        drop(closed);
+        let store = MemoryStore::default();
+        let mut bot = BotBuilder::new()
+            .config(test_config())
+            .endpoints(Endpoints {
+                reddit: base.clone(),
+                reddit_oauth: base.clone(),
+                github: base,
+            })
+            .store(Box::new(store.clone()))
+            .build()
+            .unwrap();
+        let e = bot
+            .respond_to(&claimed_reply("t3_unsent"))
+            .await
+            .unwrap_err();
+        assert!(!e.may_have_been_sent(), "{:?}", e);
+        assert!(store.reply_claims().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn claims_left_by_a_crash_are_settled() {
+        let tmp = tempfile::tempdir().unwrap();
+        let now = 1_600_000_000;
+        // the bot stopped after claiming both posts, having replied to one
+        let mut crashed = DirStore::new(tmp.path().to_owned());
+        assert!(crashed.try_claim_reply("t3_made", now - 60).unwrap());
+        assert!(crashed.try_claim_reply("t3_lost", now - 60).unwrap());
+        drop(crashed);
+
+        let history = mock("GET", "/claimcrash/user/bot/comments")
+            .match_query(Matcher::Any)
+            .with_body(
+                r#"{"kind":"Listing","data":{"children":[
+                    {"kind":"t1","data":{"name":"t1_made","link_id":"t3_made"}}
+                ]}}"#,
+            )
+            .expect(1)
+            .create();
+        let comment = comment_mock("claimcrash", "t1_lost").expect(1).create();
+        let base = format!("{}/claimcrash", server_url());
+        let mut bot = BotBuilder::new()
+            .config(test_config())
+            .endpoints(Endpoints {
+                reddit: base.clone(),
+                reddit_oauth: base.clone(),
+                github: base,
+            })
+            .store(Box::new(DirStore::new(tmp.path().to_owned())))
+            .clock(Box::new(ManualClock::new(
+                UNIX_EPOCH + Duration::from_secs(now),
+            )))
+            .build()
+            .unwrap();
+
+        // too recent to look into while running, but not after a restart
+        bot.reconcile_claims(crate::claims::CLAIM_TIMEOUT).await;
+        bot.reconcile_claims(0).await;
+        history.assert();
+        let claims = crate::claims::load(tmp.path()).unwrap();
+        assert_eq!(claims.len(), 1);
+        assert_eq!(claims[0].comment.as_deref(), Some("t1_made"));
+
+        assert!(!bot.respond_to(&claimed_reply("t3_made")).await.unwrap());
+        assert!(bot.respond_to(&claimed_reply("t3_lost")).await.unwrap());
+        comment.assert();
+        let claims = crate::claims::load(tmp.path()).unwrap();
+        assert!(claims.iter().all(|c| c.replied));
+    }
+
+    #[tokio::test]
+    async fn sandboxed_replies_are_posted_to_the_sandbox() {
+        let comment = comment_mock("sandbox", "t1_real").expect(0).create();
+        let submit = mock("POST", "/sandbox/api/submit")
+            .match_body(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("sr".to_owned(), "cfl_sandbox".to_owned()),
+                Matcher::UrlEncoded("kind".to_owned(), "self".to_owned()),
+                Matcher::Regex(
+                    "text=Would\\+have\\+commented\\+on\\+\
+                     https%3A%2F%2Fwww.reddit.com%2Fr%2Frust%2Fcomments%2Fsandboxed%2F%3A"
+                        .to_owned(),
+                ),
+                Matcher::Regex("%3E\\+The\\+linked\\+GitHub\\+repository".to_owned()),
+            ]))
+            .with_body(r#"{"json":{"errors":[],"data":{"id":"sbx","name":"t3_sbx"}}}"#)
+            .expect(1)
+            .create();
+        let store = MemoryStore::default();
+        let clock = ManualClock::new(UNIX_EPOCH + Duration::from_secs(1_600_000_000));
+        let mut bot = ack_bot("sandbox", AckMode::Off, &store, &clock);
+        configure(&bot, |c| {
+            c.sandbox_subreddit = Some("cfl_sandbox".to_owned())
+        });
+        let reply = PendingReply {
+            details: PostDetails {
+                subreddit: "rust".to_owned(),
+                permalink: "/r/rust/comments/sandboxed/".to_owned(),
+                ..PostDetails::default()
+            },
+            ..claimed_reply("t3_sandboxed")
+        };
+
+        assert!(bot.respond_to(&reply).await.unwrap());
+        submit.assert();
+        comment.assert();
+        // the post in the sandbox is recorded as the reply
+        let claims = store.reply_claims().unwrap();
+        assert_eq!(claims.len(), 1);
+        assert_eq!(claims[0].fullname, "t3_sandboxed");
+        assert!(claims[0].replied);
+        assert_eq!(claims[0].comment.as_deref(), Some("t3_sbx"));
+        let replies = store.replies().unwrap();
+        assert_eq!(replies.len(), 1);
+        assert_eq!(replies[0].post, "t3_sandboxed");
+        assert_eq!(replies[0].comment.as_deref(), Some("t3_sbx"));
+
+        // and a post is only posted about once, like any reply
+        assert!(!bot.respond_to(&reply).await.unwrap());
+    }
 }
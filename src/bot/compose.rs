@@ -0,0 +1,798 @@
+use anyhow::{Context, Result};
+use log::{debug, info, warn};
+use serde_json::Value;
+use std::collections::HashMap;
+
+use super::gating::PageScan;
+use super::{Bot, RepoGroup, DAY};
+use crate::claims::RepoClaim;
+use crate::duplicates::{self, DuplicateMode, EarlierReply};
+use crate::errors::BotError;
+use crate::history::{self, ReplyRecord};
+use crate::issues::{self, IssueRecord, ISSUE_TITLE};
+use crate::license::License;
+use crate::models::{Author, Post};
+use crate::pending::{PendingReply, PostDetails, RepoLink};
+use crate::praise;
+use crate::queue::{ApprovalMode, ApprovalQueue};
+use crate::reddit;
+use crate::reply;
+use crate::restricted;
+use crate::rules::Verdict;
+use crate::sanitize;
+use crate::suppress::{self, Suppression};
+use crate::util::GhRepo;
+
+/// Times a post is looked at for the bot's reply after posting it failed in
+/// a way that doesn't say whether it was made.
+const REPLY_VERIFICATIONS: u32 = 2;
+
+impl Bot {
+    /// Reply to the live posts on a page about the unlicensed repositories
+    /// they link, at most once about each repository.
+    ///
+    /// If it fails, the posts it wasn't done with are forgotten, along with
+    /// what was held back about them since the queue had `queued` entries.
+    pub(super) async fn reply_to_page(
+        &mut self,
+        scan: &PageScan,
+        unlicensed: &[bool],
+        queued: usize,
+    ) -> Result<()> {
+        // posts may have been removed while their repos were being checked
+        let candidates: Vec<String> = scan
+            .post_repos
+            .iter()
+            .filter(|(_, indices)| indices.iter().any(|&i| unlicensed[i]))
+            .map(|(fullname, _)| fullname.clone())
+            .collect();
+        let live = match self.fetch_posts_by_fullname(&candidates).await {
+            Ok(live) => live,
+            Err(e) => {
+                self.roll_back(scan.post_repos.iter().map(|(f, _)| f), queued);
+                return Err(e);
+            }
+        };
+        let live: HashMap<String, Post> = live
+            .into_iter()
+            .filter(|p| self.can_reply_to(p))
+            .map(|p| (p.name.clone(), p))
+            .collect();
+        let suppressions = suppress::load(&self.config().state_dir).unwrap_or_else(|e| {
+            warn!("Could not load the suppression list: {:#}", e);
+            vec![]
+        });
+        let history = self.core.with_store(|s| s.replies()).unwrap_or_else(|e| {
+            warn!("Could not load the reply history: {:#}", e);
+            vec![]
+        });
+        let groups = &scan.groups;
+        // only one reply per repository, to the first live post found linking it
+        let mut covered = vec![false; groups.len()];
+        // repositories skipped because the post's author was already replied to about them
+        let mut repeated = vec![false; groups.len()];
+        let now = self.core.clock.unix_now();
+        let window = match self.dry_run {
+            true => 0,
+            false => self.config().cross_sub_window_minutes * 60,
+        };
+        for (k, (target, indices)) in scan.post_repos.iter().enumerate() {
+            self.current_post = Some(target.clone());
+            let candidates: Vec<usize> = indices
+                .iter()
+                .copied()
+                .filter(|&i| unlicensed[i] && !covered[i])
+                .collect();
+            let post = match live.get(target) {
+                Some(post) if !candidates.is_empty() => post,
+                _ => continue,
+            };
+            let author = scan
+                .details
+                .get(target)
+                .map(|d| d.author.as_str())
+                .unwrap_or_default();
+            let (again, chosen): (Vec<usize>, Vec<usize>) =
+                candidates.into_iter().partition(|&i| {
+                    let g = &groups[i];
+                    let ttl = self.config().reply_dedup_ttl;
+                    history::replied_recently(&history, author, &g.repo, now, ttl)
+                        // replies within the window are left to the repository claims
+                        && !history::replied_recently(&history, author, &g.repo, now, window)
+                });
+            if !again.is_empty() {
+                debug!(
+                    "Already replied to {} about some repositories in {}",
+                    author, target
+                );
+                let again: Vec<&RepoGroup> = again.iter().map(|&i| &groups[i]).collect();
+                self.record_events(&again, target, &scan.details, "already replied to author");
+            }
+            for &i in &again {
+                repeated[i] = true;
+            }
+            for &i in &chosen {
+                covered[i] = true;
+            }
+            let chosen: Vec<&RepoGroup> = chosen.iter().map(|&i| &groups[i]).collect();
+            let replied = self
+                .reply_to_post(scan, post, chosen, &suppressions, &history, now)
+                .await;
+            if let Err(e) = replied {
+                // the posts replied to so far stay done with
+                self.roll_back(scan.post_repos[k..].iter().map(|(f, _)| f), queued);
+                return Err(e);
+            }
+        }
+        for (i, group) in groups.iter().enumerate() {
+            if unlicensed[i] && !covered[i] && !repeated[i] {
+                debug!(
+                    "Every post linking {} has been removed, not responding",
+                    group.repo
+                );
+                let fullname = &group.fullnames[0];
+                self.record_event(
+                    group,
+                    fullname,
+                    &scan.details,
+                    &License::Missing,
+                    "post removed",
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Reply to a live post about the unlicensed repositories chosen from
+    /// it, unless they're suppressed, its author opted out, it's a work in
+    /// progress, or they were already replied about elsewhere.
+    async fn reply_to_post(
+        &mut self,
+        scan: &PageScan,
+        post: &Post,
+        chosen: Vec<&RepoGroup>,
+        suppressions: &[Suppression],
+        history: &[ReplyRecord],
+        now: u64,
+    ) -> Result<()> {
+        let target = &post.name;
+        let details = &scan.details;
+        let author = details
+            .get(target)
+            .map(|d| d.author.as_str())
+            .unwrap_or_default();
+        let (suppressed, repos): (Vec<&RepoGroup>, Vec<&RepoGroup>) =
+            chosen.into_iter().partition(|g| {
+                suppress::is_user_suppressed(suppressions, author, now)
+                    || suppress::is_suppressed(suppressions, &g.repo, now)
+            });
+        if !suppressed.is_empty() {
+            debug!("Not replying about suppressed repositories in {}", target);
+            self.record_events(&suppressed, target, details, "suppressed");
+        }
+        if repos.is_empty() {
+            return Ok(());
+        }
+        if let Some(phrase) = scan.opted_out.get(target) {
+            info!(
+                "The author of {} says its code is unlicensed on purpose ('{}'), not responding",
+                target, phrase
+            );
+            for g in repos.iter().filter(|_| !self.dry_run) {
+                let opted_out = Suppression::repo(&g.repo, "post", "author_opted_out");
+                if let Err(e) = suppress::add(&self.config().state_dir, opted_out) {
+                    warn!("Could not suppress {}: {:#}", g.repo, e);
+                }
+            }
+            self.record_events(&repos, target, details, "author opted out");
+            return Ok(());
+        }
+        let links: Vec<RepoLink> = repos.iter().map(|g| g.link()).collect();
+        if scan.wip.contains(target) {
+            let days = self.config().wip_followup_days;
+            info!(
+                "{} is a work in progress, replying about it in {} days if it's still up and unlicensed",
+                target, days
+            );
+            let now = self.core.clock.unix_now();
+            self.pending.push(PendingReply {
+                not_before: Some(now + days * DAY),
+                ..scan.held(target, links, now)
+            });
+            self.record_events(&repos, target, details, "held as work in progress");
+            return Ok(());
+        }
+        for group in repos.iter().filter(|g| g.fullnames.len() > 1) {
+            debug!(
+                "Only responding to {} out of {:?} about {}",
+                target, group.fullnames, group.repo
+            );
+        }
+        let names: Vec<String> = repos.iter().map(|g| g.repo.to_string()).collect();
+        let earlier = self.earlier_discussion(post, &repos, history, now).await;
+        if let Some(e) = &earlier {
+            if self.config().duplicate_mode == DuplicateMode::Skip {
+                info!(
+                    "Already replied about {} in {}, an earlier submission of {}: {}",
+                    e.repo, e.post, target, e.permalink
+                );
+                self.record_events(&repos, target, details, "replied in earlier post");
+                return Ok(());
+            }
+        }
+        let subreddit = &post.subreddit;
+        if !subreddit.is_empty() {
+            if let Verdict::ReportOnly(reason) = self.subreddit_verdict(subreddit).await {
+                info!(
+                    "Found {} without a license in {} on /r/{}, not responding ({})",
+                    names.join(", "),
+                    target,
+                    subreddit,
+                    reason
+                );
+                let action = format!("report only: {}", reason);
+                self.record_events(&repos, target, details, &action);
+                return Ok(());
+            }
+        }
+        let held = scan.held(target, links, self.core.clock.unix_now());
+        let cross_post = self.cross_posted(&held.details, target, &repos);
+        if let Some(c) = &cross_post {
+            if !c.is_elsewhere(&held.details.subreddit) {
+                debug!(
+                    "Already replying to {} about {} in {}",
+                    author,
+                    names.join(", "),
+                    c.fullname
+                );
+                self.record_events(&repos, target, details, "already replied to author");
+                return Ok(());
+            }
+            if self.config().cross_sub_mode == DuplicateMode::Skip {
+                info!(
+                    "Already replying about {} on {} in /r/{}, the author's post of it before {}",
+                    names.join(", "),
+                    c.fullname,
+                    c.subreddit,
+                    target
+                );
+                self.record_events(&repos, target, details, "replied in another subreddit");
+                return Ok(());
+            }
+        }
+        let held = PendingReply {
+            earlier: earlier.map(|e| e.permalink),
+            cross_post: cross_post.map(|c| self.claimed_reply_link(&c)),
+            ..held
+        };
+        self.reply_or_hold(held, &repos, details).await
+    }
+
+    /// Reply about the unlicensed repositories in a post, or hold the reply
+    /// back: for approval, until replies resume, until the active hours, or
+    /// until the reply cap lets up.
+    async fn reply_or_hold(
+        &mut self,
+        mut held: PendingReply,
+        repos: &[&RepoGroup],
+        details: &HashMap<String, PostDetails>,
+    ) -> Result<()> {
+        let target = held.fullname.clone();
+        let names: Vec<String> = repos.iter().map(|g| g.repo.to_string()).collect();
+        if self.approval_mode() == ApprovalMode::Manual && self.dry_run {
+            info!("Would queue reply to {} about {}", target, names.join(", "));
+            self.record_events(repos, &target, details, "would queue for approval");
+            return Ok(());
+        }
+        if self.approval_mode() == ApprovalMode::Manual {
+            let id = ApprovalQueue::update(&self.config().state_dir, |q| Ok(q.push(held)))
+                .with_context(|| format!("queueing reply to {} for approval", target))?;
+            info!("Queued reply to {} for approval as {}", target, id);
+            self.record_events(repos, &target, details, "awaiting approval");
+            return Ok(());
+        }
+        if self.core.paused() {
+            warn!(
+                "Replies are paused, holding back reply to {} until they resume",
+                target
+            );
+            self.pending.push(held);
+            self.record_events(repos, &target, details, "held while paused");
+            return Ok(());
+        }
+        if !self.in_active_hours() {
+            info!(
+                "Outside of active hours, holding back reply to {} until they start",
+                target
+            );
+            self.pending.push(held);
+            self.record_events(repos, &target, details, "held for active hours");
+            return Ok(());
+        }
+        if self.reply_cap_reached() {
+            info!(
+                "Reply cap reached, holding back reply to {} until it lets up",
+                target
+            );
+            held.stages.cap(self.core.clock.unix_now());
+            self.pending.push(held);
+            self.record_events(repos, &target, details, "held for reply cap");
+            return Ok(());
+        }
+        self.deliver(&held)
+            .await
+            .with_context(|| format!("commenting on post {} about {}", target, names.join(", ")))?;
+        self.core
+            .replies
+            .lock()
+            .unwrap()
+            .push(self.core.clock.now());
+        Ok(())
+    }
+
+    /// Responds to a post about the unlicensed repositories it links,
+    /// returning whether it did.
+    ///
+    /// The post is claimed first, so it's never replied to twice. If Reddit
+    /// refuses the reply the claim is given up; if it can't be told whether
+    /// the reply was made, the claim is settled later by `reconcile_claims`.
+    pub(super) async fn respond_to(&mut self, reply: &PendingReply) -> Result<bool, BotError> {
+        debug!("Responding to post {}", reply.fullname);
+        let text =
+            reply::text(reply, &self.config()).map_err(|e| BotError::Permanent(e.to_string()))?;
+        let comment = match self
+            .comment_once(&reply.fullname, &reply.details, &text)
+            .await?
+        {
+            Some(comment) => comment,
+            None => return Ok(false),
+        };
+        self.check_for_removal(
+            comment.as_deref(),
+            &reply.fullname,
+            &reply.details.subreddit,
+        );
+        self.record_reply(reply, comment);
+        self.count(&reply.details.subreddit, |s| s.replies += 1);
+        self.comment_allowed(&reply.details.subreddit);
+        Ok(true)
+    }
+
+    /// Comment on a post, claiming it first so that it's only ever commented
+    /// on once.
+    ///
+    /// With a sandbox subreddit configured, what would have been commented is
+    /// posted there instead, and stands in for the comment.
+    ///
+    /// If posting fails in a way that doesn't say whether the comment was
+    /// made, such as a timeout, the post is looked at for it before posting
+    /// again, up to `REPLY_VERIFICATIONS` times. A claim that still can't be
+    /// settled is left for `reconcile_claims`.
+    ///
+    /// Returns `None` if it was already claimed, or else the fullname of the
+    /// comment, if Reddit said what it was.
+    async fn comment_once(
+        &self,
+        fullname: &str,
+        post: &PostDetails,
+        text: &str,
+    ) -> Result<Option<Option<String>>, BotError> {
+        let claimed = self
+            .core
+            .with_store(|s| s.try_claim_reply(fullname, self.core.clock.unix_now()))
+            .map_err(|e| BotError::Permanent(format!("Could not claim {}: {:#}", fullname, e)))?;
+        if !claimed {
+            warn!(
+                "Post {} has already been replied to, or is being, not replying again",
+                fullname
+            );
+            return Ok(None);
+        }
+        let sandbox = self.config().sandbox_subreddit.clone();
+        if let Some(sandbox) = &sandbox {
+            info!(
+                "Posting the reply to {} in /r/{} instead",
+                fullname, sandbox
+            );
+        }
+        let request = self
+            .core
+            .reddit
+            .reply_request(sandbox.as_deref(), fullname, post, text);
+        let posted_in = sandbox.unwrap_or_else(|| post.subreddit.clone());
+        let posted_in = posted_in.as_str();
+        let mut verifications = 0;
+        let resp = loop {
+            let attempt = request
+                .try_clone()
+                .expect("replies are sent as forms, which can be cloned");
+            let e = match self.core.reddit.send_reply(attempt, posted_in).await {
+                Ok(resp) => break resp,
+                Err(e) => e,
+            };
+            if !e.may_have_been_sent() {
+                // nothing was posted, so the post can be replied to again
+                if let Err(e) = self.core.with_store(|s| s.release_reply(fullname)) {
+                    warn!("Could not release the claim on {}: {:#}", fullname, e);
+                }
+                return Err(e);
+            }
+            if self.config().sandbox_subreddit.is_some() {
+                return Err(e);
+            }
+            verifications += 1;
+            warn!(
+                "Replying to {} failed ({}), looking for the reply before trying again",
+                fullname, e
+            );
+            match self.find_reply(fullname).await {
+                Ok(Some(comment)) => {
+                    info!("Found reply {} to {}", comment, fullname);
+                    if let Err(e) = self
+                        .core
+                        .with_store(|s| s.confirm_reply(fullname, Some(&comment)))
+                    {
+                        warn!("Could not confirm the reply to {}: {:#}", fullname, e);
+                    }
+                    return Ok(Some(Some(comment)));
+                }
+                Ok(None) if verifications < REPLY_VERIFICATIONS => {
+                    info!("No reply to {} was made, posting it again", fullname)
+                }
+                // it may yet show up, as listings can lag behind
+                Ok(None) => return Err(e),
+                Err(find_error) => {
+                    warn!(
+                        "Could not look for the reply to {}: {:#}",
+                        fullname, find_error
+                    );
+                    return Err(e);
+                }
+            }
+        };
+        let body = resp.json::<Value>().await.ok();
+        if body.as_ref().is_some_and(restricted::is_not_allowed) {
+            if let Err(e) = self.core.with_store(|s| s.release_reply(fullname)) {
+                warn!("Could not release the claim on {}: {:#}", fullname, e);
+            }
+            return Err(BotError::CommentRestricted(posted_in.to_owned()));
+        }
+        let comment = body.as_ref().and_then(reddit::reply_name);
+        if let Err(e) = self
+            .core
+            .with_store(|s| s.confirm_reply(fullname, comment.as_deref()))
+        {
+            warn!("Could not confirm the reply to {}: {:#}", fullname, e);
+        }
+        Ok(Some(comment))
+    }
+
+    /// Look for the bot's reply to a post among its own recent comments, and
+    /// then among the post's top-level comments, in case the first hasn't
+    /// caught up. Returns the reply's fullname.
+    async fn find_reply(&self, fullname: &str) -> Result<Option<String>> {
+        let own = self.fetch_own_comments(25).await?;
+        if let Some(comment) = own.into_iter().find(|c| c.link_id == fullname) {
+            return Ok(Some(comment.name));
+        }
+        // the post, and then its comments
+        let listings = self.core.reddit.post_comments(fullname).await?;
+        let username = &self.config().username;
+        Ok(listings
+            .get(1)
+            .into_iter()
+            .flat_map(|listing| &listing.data.children)
+            .find(|c| {
+                c.data["author"]
+                    .as_str()
+                    .is_some_and(|a| a.eq_ignore_ascii_case(username))
+            })
+            .and_then(|c| c.data["name"].as_str().map(str::to_owned)))
+    }
+
+    /// Let a post's author know about the unlicensed repositories it links, by
+    /// replying, opening issues on the repositories, or both.
+    pub(super) async fn deliver(&mut self, reply: &PendingReply) -> Result<(), BotError> {
+        let mode = self.issue_mode();
+        if mode.replies() && self.reads_publicly() {
+            self.record_pending(reply, "report only: no Reddit credentials");
+        } else if mode.replies() {
+            match self.respond_to(reply).await {
+                Ok(true) => {
+                    self.record_latency(reply);
+                    self.record_pending(reply, "replied")
+                }
+                Ok(false) => self.record_pending(reply, "already replied"),
+                Err(BotError::CommentRestricted(subreddit)) => {
+                    self.comment_restricted(&subreddit, &reply.fullname).await;
+                    self.record_pending(reply, "report only: commenting needs approval");
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        if !mode.files_issues() {
+            return Ok(());
+        }
+        for link in &reply.repos {
+            let action = match self.file_issue(link, reply).await {
+                Ok(action) => action.to_owned(),
+                Err(e) => {
+                    warn!("Could not open an issue on {}: {}", link.repo, e);
+                    format!("issue failed: {}", e)
+                }
+            };
+            let repo = link.repo.to_string();
+            let post = reply.details.clone();
+            self.record(&reply.fullname, repo, post, &License::Missing, &action);
+        }
+        Ok(())
+    }
+
+    /// Count how long after its post a reply was made, logging where the
+    /// time went.
+    fn record_latency(&self, reply: &PendingReply) {
+        if let Some(breakdown) = reply.stages.breakdown(self.core.clock.unix_now()) {
+            debug!("Replied to {} {}", reply.fullname, breakdown);
+            self.core
+                .events
+                .lock()
+                .unwrap()
+                .record_latency(breakdown.total);
+        }
+    }
+
+    /// Open an issue about the missing license on a repository, unless one has
+    /// been opened before, the repository has issues turned off, or it has
+    /// more stars than the configured limit.
+    ///
+    /// Returns what was done, for the event log.
+    async fn file_issue(
+        &mut self,
+        link: &RepoLink,
+        reply: &PendingReply,
+    ) -> Result<&'static str, BotError> {
+        let name = link.repo.to_string();
+        let filed = self
+            .core
+            .with_store(|s| s.issues())
+            .map_err(|e| BotError::Permanent(format!("Could not load opened issues: {:#}", e)))?;
+        if issues::already_filed(&filed, &link.repo) {
+            debug!("Already opened an issue on {}", name);
+            return Ok("issue already opened");
+        }
+        let access = self.github_access();
+        let repo = self.core.github.repository(&name, &access).await?;
+        if !repo.has_issues {
+            debug!("{} has issues turned off", name);
+            return Ok("issues disabled");
+        }
+        let stars = repo.stargazers_count;
+        if stars > self.config().github_issue_max_stars {
+            debug!(
+                "Not opening an issue on {}, which has {} stars",
+                name, stars
+            );
+            return Ok("too many stars for an issue");
+        }
+        let body = issues::body(&reply.details.permalink)
+            .map_err(|e| BotError::Permanent(e.to_string()))?;
+        let issue = self
+            .core
+            .github
+            .open_issue(&name, ISSUE_TITLE, &body, &access)
+            .await?;
+        let record = IssueRecord {
+            repo: name,
+            number: issue.number,
+            url: sanitize::url(&issue.html_url).unwrap_or_default(),
+            post: reply.fullname.clone(),
+            at: self.core.clock.unix_now(),
+        };
+        info!("Opened issue {} on {}", record.url, record.repo);
+        if let Err(e) = self.core.with_store(|s| s.add_issue(&record)) {
+            warn!("Could not record issue on {}: {:#}", record.repo, e);
+        }
+        Ok("opened issue")
+    }
+
+    /// Add a reply to the history of replies, one record per repository.
+    fn record_reply(&mut self, reply: &PendingReply, comment: Option<String>) {
+        let records: Vec<ReplyRecord> = reply
+            .repos
+            .iter()
+            .map(|link| ReplyRecord {
+                author: reply.details.author.clone(),
+                repo: link.repo.to_string(),
+                post: reply.fullname.clone(),
+                comment: comment.clone(),
+                at: self.core.clock.unix_now(),
+                resolved: None,
+            })
+            .collect();
+        if let Err(e) = self.core.with_store(|s| s.add_replies(&records)) {
+            warn!("Could not record reply to {}: {:#}", reply.fullname, e);
+        }
+    }
+
+    /// Find an earlier submission of a link post's link where the bot
+    /// recently replied about any of the repositories.
+    ///
+    /// Reddit is only asked about other submissions if there's a recent reply
+    /// about one of the repositories at all.
+    async fn earlier_discussion(
+        &self,
+        post: &Post,
+        repos: &[&RepoGroup],
+        history: &[ReplyRecord],
+        now: u64,
+    ) -> Option<EarlierReply> {
+        let window = self.config().duplicate_window;
+        if window == 0 || post.domain.starts_with("self.") {
+            return None;
+        }
+        let repos: Vec<GhRepo> = repos.iter().map(|g| g.repo.clone()).collect();
+        if !duplicates::worth_checking(history, &repos, now, window) {
+            return None;
+        }
+        match self.fetch_duplicates(&post.name).await {
+            Ok(others) => duplicates::earlier_reply(history, &others, &repos, now, window),
+            Err(e) => {
+                warn!(
+                    "Could not fetch other submissions of {}: {:#}",
+                    post.name, e
+                );
+                None
+            }
+        }
+    }
+
+    /// Claim the repositories a post is about for its author, returning the
+    /// claim of another of their posts if every one of them was claimed by
+    /// one within the cross-subreddit window.
+    fn cross_posted(
+        &self,
+        details: &PostDetails,
+        fullname: &str,
+        repos: &[&RepoGroup],
+    ) -> Option<RepoClaim> {
+        let window = self.config().cross_sub_window_minutes * 60;
+        if window == 0 || self.dry_run || matches!(details.author(), Author::Deleted) {
+            return None;
+        }
+        let now = self.core.clock.unix_now();
+        let mut held = vec![];
+        for group in repos {
+            let claim = RepoClaim {
+                author: details.author.clone(),
+                repo: group.repo.to_string(),
+                fullname: fullname.to_owned(),
+                subreddit: details.subreddit.clone(),
+                permalink: details.permalink.clone(),
+                at: now,
+            };
+            match self.core.with_store(|s| s.try_claim_repo(&claim, window)) {
+                Ok(Some(claim)) => held.push(claim),
+                Ok(None) => {}
+                Err(e) => warn!("Could not claim {} for {}: {:#}", group.repo, fullname, e),
+            }
+        }
+        if held.len() < repos.len() {
+            return None;
+        }
+        held.into_iter().next()
+    }
+
+    /// Where the reply on the post that claimed a repository is.
+    fn claimed_reply_link(&self, claim: &RepoClaim) -> String {
+        let comment = match self.core.with_store(|s| s.reply_claims()) {
+            Ok(claims) => claims
+                .into_iter()
+                .find(|c| c.fullname == claim.fullname)
+                .and_then(|c| c.comment),
+            Err(e) => {
+                warn!("Could not load reply claims: {:#}", e);
+                None
+            }
+        };
+        claim.link(comment.as_deref())
+    }
+
+    /// Leave a note of praise on the post linking a licensed repository, if
+    /// the repository has a README and contributing guidelines too and
+    /// nothing stands in the way of replying.
+    ///
+    /// Returns what was done, for the event log. Failures are only logged,
+    /// as praise is never worth failing a pass over.
+    pub(super) async fn praise(
+        &mut self,
+        group: &RepoGroup,
+        details: &HashMap<String, PostDetails>,
+    ) -> &'static str {
+        let fullname = &group.fullnames[0];
+        let post = details.get(fullname).cloned().unwrap_or_default();
+        if self.dry_run
+            || self.core.paused()
+            || !self.issue_mode().replies()
+            || self.approval_mode() == ApprovalMode::Manual
+            || !self.in_active_hours()
+            || self.reply_cap_reached()
+        {
+            return "none";
+        }
+        let per_day = self.config().max_praise_per_day;
+        let now = self.core.clock.now();
+        if !praise::within_cap(&mut self.core.praised.lock().unwrap(), now, per_day) {
+            debug!("Praise cap reached, not praising {}", group.repo);
+            return "praise cap reached";
+        }
+        let suppressions = suppress::load(&self.config().state_dir).unwrap_or_else(|e| {
+            warn!("Could not load the suppression list: {:#}", e);
+            vec![]
+        });
+        let unix_now = self.core.clock.unix_now();
+        if suppress::is_user_suppressed(&suppressions, &post.author, unix_now)
+            || suppress::is_suppressed(&suppressions, &group.repo, unix_now)
+        {
+            return "suppressed";
+        }
+        if !post.subreddit.is_empty() {
+            if let Verdict::ReportOnly(_) = self.subreddit_verdict(&post.subreddit).await {
+                return "none";
+            }
+        }
+        match self.has_community_files(&group.repo).await {
+            Ok(true) => {}
+            Ok(false) => return "none",
+            Err(e) => {
+                warn!("Could not look for {}'s community files: {}", group.repo, e);
+                return "none";
+            }
+        }
+        match self
+            .fetch_posts_by_fullname(std::slice::from_ref(fullname))
+            .await
+        {
+            Ok(posts) if posts.iter().any(|p| self.can_reply_to(p)) => {}
+            Ok(_) => return "post removed",
+            Err(e) => {
+                warn!("Could not check that {} is still up: {:#}", fullname, e);
+                return "none";
+            }
+        }
+        let text = match reply::praise(&post.subreddit, &self.config()) {
+            Ok(text) => text,
+            Err(e) => {
+                warn!("Could not render the praise template: {:#}", e);
+                return "none";
+            }
+        };
+        match self.comment_once(fullname, &post, &text).await {
+            Ok(Some(comment)) => {
+                info!("Praised {} in {}", group.repo, fullname);
+                self.check_for_removal(comment.as_deref(), fullname, &post.subreddit);
+                self.core.praised.lock().unwrap().push(now);
+                self.core.replies.lock().unwrap().push(now);
+                self.comment_allowed(&post.subreddit);
+                "praised"
+            }
+            Ok(None) => "already replied",
+            Err(BotError::CommentRestricted(subreddit)) => {
+                self.comment_restricted(&subreddit, fullname).await;
+                "none"
+            }
+            Err(e) => {
+                warn!("Could not praise {} in {}: {}", group.repo, fullname, e);
+                "none"
+            }
+        }
+    }
+
+    /// Whether a repository has a README and contributing guidelines among
+    /// its top-level files.
+    async fn has_community_files(&self, repo: &GhRepo) -> Result<bool, BotError> {
+        let files = self.core.github.files(repo, &self.github_access()).await?;
+        Ok(praise::has_community_files(
+            files.iter().map(String::as_str),
+        ))
+    }
+}
@@ -0,0 +1,474 @@
+use anyhow::{Context, Result};
+use log::{debug, info, warn};
+use std::collections::HashSet;
+
+use super::gating::should_engage;
+use super::{Bot, INDETERMINATE_RETRY};
+use crate::errors::{BotError, PostContext};
+use crate::history;
+use crate::license::License;
+use crate::pending::{self, Budget, Dropped, Limits, PendingReply, QueueKind, RepoLink};
+use crate::queue::{ApprovalMode, ApprovalQueue};
+use crate::suppress;
+
+impl Bot {
+    /// Work through the held back replies and checks, as far as the reply
+    /// cap and the pass's budget allow: retries first, then the delayed
+    /// replies, then the checks deferred while the GitHub quota was low.
+    ///
+    /// Posts that have been removed in the meantime are dropped.
+    pub(super) async fn drain_pending(&mut self, budget: &mut Budget) -> Result<()> {
+        if self.pending.is_empty() || self.core.paused() || !self.in_active_hours() {
+            return Ok(());
+        }
+        self.check_deferred(QueueKind::Retry, budget).await?;
+        self.make_delayed_replies(budget).await?;
+        self.check_deferred(QueueKind::Recheck, budget).await
+    }
+
+    /// Make the replies held back until the active hours, and the follow-ups
+    /// on works in progress that are due.
+    async fn make_delayed_replies(&mut self, budget: &mut Budget) -> Result<()> {
+        let now = self.core.clock.unix_now();
+        let due = |p: &PendingReply| {
+            p.kind() == QueueKind::Delayed && p.not_before.is_none_or(|at| at <= now)
+        };
+        let fullnames: Vec<String> = self
+            .pending
+            .iter()
+            .filter(|p| due(p))
+            .map(|p| p.fullname.clone())
+            .collect();
+        if fullnames.is_empty() || budget.is_spent() {
+            return Ok(());
+        }
+        let live = self.live_fullnames(&fullnames).await?;
+        info!("Making {} held back replies", fullnames.len());
+        let mut i = 0;
+        while let Some(pending) = self.pending.get(i).cloned() {
+            if !due(&pending) {
+                i += 1;
+                continue;
+            }
+            if !live.contains(&pending.fullname) {
+                debug!("Post {} has been removed, not responding", pending.fullname);
+                self.pending.remove(i);
+                self.record_pending(&pending, "post removed");
+                continue;
+            }
+            if self.reply_cap_reached() {
+                info!(
+                    "Reply cap reached, {} held back replies left",
+                    self.pending.len()
+                );
+                let now = self.core.clock.unix_now();
+                for reply in &mut self.pending[i..] {
+                    if reply.kind() == QueueKind::Delayed {
+                        reply.stages.cap(now);
+                    }
+                }
+                break;
+            }
+            if !budget.take() {
+                info!("Leaving the other held back replies for the next pass");
+                break;
+            }
+            let pending =
+                match self.recheck_if_stale(&pending).await.with_context(|| {
+                    format!("checking held back post {} again", pending.fullname)
+                })? {
+                    Some(pending) => pending,
+                    None => {
+                        self.pending.remove(i);
+                        continue;
+                    }
+                };
+            self.deliver(&pending)
+                .await
+                .with_context(|| format!("commenting on held back post {}", pending.fullname))?;
+            self.pending.remove(i);
+            self.core
+                .replies
+                .lock()
+                .unwrap()
+                .push(self.core.clock.now());
+        }
+        Ok(())
+    }
+
+    /// Check the repositories in posts in a queue of deferred checks: those
+    /// deferred while the GitHub quota was low, once it has recovered, or
+    /// those to be tried again, once they're due.
+    ///
+    /// Posts that have been removed in the meantime are dropped unchecked.
+    /// Licensed, suppressed and already replied about repositories are left
+    /// out, and what's left is held back to be replied about like any other.
+    pub(super) async fn check_deferred(
+        &mut self,
+        kind: QueueKind,
+        budget: &mut Budget,
+    ) -> Result<()> {
+        if !self.pending.iter().any(|p| p.kind() == kind) || self.github_quota_low() {
+            return Ok(());
+        }
+        let suppressions = suppress::load(&self.config().state_dir).unwrap_or_else(|e| {
+            warn!("Could not load the suppression list: {:#}", e);
+            vec![]
+        });
+        let history = self.core.with_store(|s| s.replies()).unwrap_or_else(|e| {
+            warn!("Could not load the reply history: {:#}", e);
+            vec![]
+        });
+        let now = self.core.clock.unix_now();
+        let due =
+            |p: &PendingReply| p.kind() == kind && p.check_after.is_none_or(|after| after <= now);
+        let fullnames: Vec<String> = self
+            .pending
+            .iter()
+            .filter(|p| due(p))
+            .map(|p| p.fullname.clone())
+            .collect();
+        if fullnames.is_empty() || budget.is_spent() {
+            return Ok(());
+        }
+        // GitHub isn't asked about posts removed in the meantime
+        let live = self.live_fullnames(&fullnames).await?;
+        let mut i = 0;
+        while let Some(mut reply) = self.pending.get(i).cloned() {
+            if !due(&reply) {
+                i += 1;
+                continue;
+            }
+            if !live.contains(&reply.fullname) {
+                debug!("Post {} has been removed, not checking it", reply.fullname);
+                self.pending.remove(i);
+                let unchecked = License::Indeterminate("not checked".to_owned());
+                for link in &reply.repos {
+                    let (repo, post) = (link.repo.to_string(), reply.details.clone());
+                    self.record(&reply.fullname, repo, post, &unchecked, "post removed");
+                }
+                continue;
+            }
+            if !budget.take() {
+                debug!("Leaving the other deferred checks for the next pass");
+                break;
+            }
+            debug!("Making the deferred checks of {}", reply.fullname);
+            let mut unlicensed = vec![];
+            let mut indeterminate = vec![];
+            for link in &reply.repos {
+                let repo = link.repo.to_string();
+                let post = reply.details.clone();
+                let result = self.check_link(&link.repo, &link.url).await;
+                let counted = result.as_ref().map(|(l, _, p)| (l, &p[..]));
+                self.count_check(&reply.details.subreddit, counted);
+                let path = result
+                    .as_ref()
+                    .map(|(.., path)| path.clone())
+                    .unwrap_or_default();
+                let action = match result {
+                    Ok((License::Missing, summary, _)) => {
+                        let subreddit = &reply.details.subreddit;
+                        let removed = self
+                            .removed_license(&link.repo, &reply.fullname, subreddit)
+                            .await;
+                        let now = self.core.clock.unix_now();
+                        let ttl = self.config().reply_dedup_ttl;
+                        let author = &reply.details.author;
+                        let engage =
+                            should_engage(&post, Some((&link.repo, &summary)), &self.config());
+                        if suppress::is_suppressed(&suppressions, &link.repo, now)
+                            || suppress::is_user_suppressed(&suppressions, author, now)
+                        {
+                            "suppressed"
+                        } else if let Err(reason) = engage {
+                            reason.label()
+                        } else if history::replied_recently(&history, author, &link.repo, now, ttl)
+                        {
+                            "already replied to author"
+                        } else {
+                            unlicensed.push(RepoLink {
+                                removed_license: removed,
+                                summary,
+                                ..link.clone()
+                            });
+                            continue;
+                        }
+                    }
+                    Ok((License::Indeterminate(reason), ..)) if reply.check_after.is_none() => {
+                        let license = License::Indeterminate(reason);
+                        self.record_checked(
+                            &reply.fullname,
+                            repo,
+                            post,
+                            &license,
+                            "deferred",
+                            path,
+                        );
+                        indeterminate.push(link.clone());
+                        continue;
+                    }
+                    Ok((License::Indeterminate(reason), ..)) => {
+                        info!(
+                            "Still can't tell if {} has a license ({}), not replying to {} about it",
+                            link.repo, reason, reply.fullname
+                        );
+                        let license = License::Indeterminate(reason.clone());
+                        self.record_checked(&reply.fullname, repo, post, &license, &reason, path);
+                        continue;
+                    }
+                    Ok((found, ..)) => {
+                        self.record_checked(&reply.fullname, repo, post, &found, "none", path);
+                        continue;
+                    }
+                    Err(e) if e.is_not_found() && reply.check_after.is_some() => {
+                        info!(
+                            "Still can't find {} ({}), not replying to {} about it",
+                            link.repo, e, reply.fullname
+                        );
+                        let license = License::Indeterminate(e.to_string());
+                        self.record(&reply.fullname, repo, post, &license, "not a repository");
+                        continue;
+                    }
+                    Err(e) if !e.is_retryable() => {
+                        warn!("Skipping post {} ({}): {}", reply.fullname, link.url, e);
+                        continue;
+                    }
+                    Err(e) => {
+                        return Err(e).with_context(|| PostContext {
+                            post: reply.fullname.clone(),
+                            url: link.url.clone(),
+                            repo: Some(repo),
+                        })
+                    }
+                };
+                let license = License::Missing;
+                self.record_checked(&reply.fullname, repo, post, &license, action, path);
+            }
+            if !indeterminate.is_empty() {
+                self.pending.push(PendingReply {
+                    repos: indeterminate,
+                    check_after: Some(now + INDETERMINATE_RETRY),
+                    ..reply.clone()
+                });
+            }
+            reply.repos = unlicensed;
+            reply.deferred = false;
+            reply.check_after = None;
+            // held from now on, as far as checking again goes
+            reply.queued_at = self.core.clock.unix_now();
+            if reply.repos.is_empty() {
+                self.pending.remove(i);
+            } else if self.approval_mode() == ApprovalMode::Manual {
+                let fullname = reply.fullname.clone();
+                let id =
+                    ApprovalQueue::update(&self.config().state_dir, |q| Ok(q.push(reply.clone())))
+                        .with_context(|| format!("queueing reply to {} for approval", fullname))?;
+                info!("Queued reply to {} for approval as {}", fullname, id);
+                self.record_pending(&reply, "awaiting approval");
+                self.pending.remove(i);
+            } else {
+                self.pending[i] = reply;
+                i += 1;
+            }
+        }
+        Ok(())
+    }
+
+    /// Check the repositories in a held back reply again if one of them was
+    /// last checked longer than `recheck_after` ago, or the reply follows up
+    /// on a work in progress, since their authors may have added a license
+    /// in the meantime.
+    ///
+    /// Returns the reply about those that are still unlicensed, or `None` if
+    /// none are. The checks aren't counted in the statistics again, but a
+    /// repository that now has a license is counted as skipped.
+    async fn recheck_if_stale(
+        &self,
+        reply: &PendingReply,
+    ) -> Result<Option<PendingReply>, BotError> {
+        let after = self.config().recheck_after;
+        let checked_at = {
+            let events = self.core.events.lock().unwrap();
+            reply
+                .repos
+                .iter()
+                .map(|link| {
+                    events
+                        .history()
+                        .last_checked(&link.repo.to_string())
+                        .unwrap_or(reply.queued_at)
+                })
+                .min()
+                .unwrap_or(reply.queued_at)
+        };
+        let since = self.core.clock.unix_now().saturating_sub(checked_at);
+        if reply.not_before.is_none() && (after == 0 || since < after) {
+            return Ok(Some(reply.clone()));
+        }
+        debug!("Checking the repositories in {} again", reply.fullname);
+        let mut unlicensed = vec![];
+        for link in &reply.repos {
+            match self.check_link(&link.repo, &link.url).await {
+                Ok((License::Missing, summary, _)) => unlicensed.push(RepoLink {
+                    summary,
+                    ..link.clone()
+                }),
+                Ok((License::Indeterminate(reason), ..)) => {
+                    warn!(
+                        "Could not tell if {} has a license now: {}",
+                        link.repo, reason
+                    );
+                    unlicensed.push(link.clone());
+                }
+                Ok((found, _, path)) => {
+                    info!(
+                        "{} has a license now, not replying to {} about it",
+                        link.repo, reply.fullname
+                    );
+                    let post = reply.details.clone();
+                    let repo = link.repo.to_string();
+                    let action = "resolved_before_reply";
+                    self.record_checked(&reply.fullname, repo, post, &found, action, path);
+                }
+                Err(e) if e.is_retryable() => return Err(e),
+                Err(e) => {
+                    warn!("Could not check {} again: {}", link.repo, e);
+                    unlicensed.push(link.clone());
+                }
+            }
+        }
+        if unlicensed.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(PendingReply {
+            repos: unlicensed,
+            ..reply.clone()
+        }))
+    }
+
+    /// Work through the queues of held back work in order of priority, the
+    /// approved replies first, up to the budget of a pass, so that there's
+    /// always time left to poll the listing.
+    pub(super) async fn drain_queues(&mut self) -> Result<()> {
+        let mut budget = Budget::new(self.config().queue_budget);
+        self.bound_queues()?;
+        self.drain_approved(&mut budget).await?;
+        self.drain_pending(&mut budget).await
+    }
+
+    /// Drop what doesn't fit in the queues of held back replies and checks,
+    /// and count what's left in them.
+    ///
+    /// Approved replies are never dropped.
+    pub(super) fn bound_queues(&mut self) -> Result<()> {
+        let config = self.config();
+        let limits = Limits {
+            retries: config.max_retries,
+            delayed: config.max_delayed_replies,
+            rechecks: config.max_rechecks,
+            retry_max_age: config.retry_max_age,
+        };
+        let now = self.core.clock.unix_now();
+        for (reply, why) in pending::enforce(&mut self.pending, &limits, now) {
+            let kind = reply.kind();
+            let action = match why {
+                Dropped::Full => "queue full",
+                Dropped::Expired => "retry expired",
+            };
+            warn!(
+                "Dropping {} from the {} queue ({})",
+                reply.fullname,
+                kind.label(),
+                action
+            );
+            let license = match kind {
+                QueueKind::Delayed => License::Missing,
+                _ => License::Indeterminate(action.to_owned()),
+            };
+            for link in &reply.repos {
+                let post = reply.details.clone();
+                self.record(
+                    &reply.fullname,
+                    link.repo.to_string(),
+                    post,
+                    &license,
+                    action,
+                );
+            }
+            self.core.events.lock().unwrap().queues_mut().drop_one(kind);
+        }
+        let approved = ApprovalQueue::load(&config.state_dir)?.approved().len();
+        let mut events = self.core.events.lock().unwrap();
+        events.queues_mut().count(approved, &self.pending);
+        Ok(())
+    }
+
+    /// Make the replies that have been approved, as far as the reply cap and
+    /// the pass's budget allow.
+    pub(super) async fn drain_approved(&mut self, budget: &mut Budget) -> Result<()> {
+        if self.core.paused() || !self.in_active_hours() {
+            return Ok(());
+        }
+        let dir = self.config().state_dir.clone();
+        let approved = ApprovalQueue::load(&dir)?.approved();
+        if approved.is_empty() {
+            return Ok(());
+        }
+        let fullnames: Vec<String> = approved.iter().map(|i| i.reply.fullname.clone()).collect();
+        let live = self.live_fullnames(&fullnames).await?;
+        info!("Making {} approved replies", approved.len());
+        // the item may have been rejected in the meantime, which is fine
+        let dequeue = |id| ApprovalQueue::update(&dir, |q| Ok(q.remove(id).ok()));
+        for item in approved {
+            if !live.contains(&item.reply.fullname) {
+                debug!(
+                    "Post {} has been removed, not responding",
+                    item.reply.fullname
+                );
+                dequeue(item.id)?;
+                self.record_pending(&item.reply, "post removed");
+                continue;
+            }
+            if self.reply_cap_reached() {
+                info!("Reply cap reached, leaving the other approved replies for later");
+                break;
+            }
+            if !budget.take() {
+                info!("Leaving the other approved replies for the next pass");
+                break;
+            }
+            let reply =
+                match self.recheck_if_stale(&item.reply).await.with_context(|| {
+                    format!("checking approved post {} again", item.reply.fullname)
+                })? {
+                    Some(reply) => reply,
+                    None => {
+                        dequeue(item.id)?;
+                        continue;
+                    }
+                };
+            self.deliver(&reply)
+                .await
+                .with_context(|| format!("commenting on approved post {}", reply.fullname))?;
+            dequeue(item.id)?;
+            self.core
+                .replies
+                .lock()
+                .unwrap()
+                .push(self.core.clock.now());
+        }
+        Ok(())
+    }
+
+    /// Fullnames of those posts that can still be replied to.
+    async fn live_fullnames(&self, fullnames: &[String]) -> Result<HashSet<String>> {
+        Ok(self
+            .fetch_posts_by_fullname(fullnames)
+            .await?
+            .into_iter()
+            .filter(|p| self.can_reply_to(p))
+            .map(|p| p.name)
+            .collect())
+    }
+}
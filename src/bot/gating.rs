@@ -0,0 +1,715 @@
+use anyhow::{anyhow, Context, Result};
+use futures::stream::{self, StreamExt};
+use log::{debug, info, warn};
+use serde_json::Value;
+use std::{
+    collections::{HashMap, HashSet},
+    time,
+};
+
+use super::{Bot, RepoGroup, INDETERMINATE_RETRY, RULES_CACHE_TTL};
+use crate::errors::{BotError, PostContext};
+use crate::github::Checked;
+use crate::history;
+use crate::latency::Stages;
+use crate::license::License;
+use crate::listing::Listing;
+use crate::models::{Author, Config, Post};
+use crate::pending::{PendingReply, PostDetails, RepoLink};
+use crate::preview::RepoSummary;
+use crate::reddit;
+use crate::report::{ErrorContext, Level};
+use crate::restricted;
+use crate::rules::{assess, is_invited, Verdict};
+use crate::sanitize::{self, NAME_LEN, TITLE_LEN};
+use crate::skip::SkipReason;
+use crate::util::{
+    extract_gh_info, extract_gh_links, find_phrase, opt_out_phrase, truncate_url, unwrap_redirects,
+    GhRepo, LinkLimits,
+};
+
+/// Longest part of a URL too long to be read that's logged.
+const TRUNCATED_URL_LEN: usize = 200;
+/// Seconds to wait before looking for a repository linked in a young post
+/// once more, when GitHub couldn't find it, as it may have been made public
+/// only after the post.
+const NOT_FOUND_RETRY: u64 = 10 * 60;
+/// Why a repository GitHub couldn't find is checked again.
+const MAYBE_NOT_PUBLIC_YET: &str = "not found, may not be public yet";
+
+/// The new posts on a page of a listing, and the repositories they link.
+#[derive(Debug, Default)]
+pub(super) struct PageScan {
+    /// Fullnames of every post on the page, newest first.
+    pub(super) fullnames: Vec<String>,
+    /// Each repository linked, with every new post linking it.
+    pub(super) groups: Vec<RepoGroup>,
+    pub(super) details: HashMap<String, PostDetails>,
+    /// When each new post was submitted and got to, for the reply latency.
+    pub(super) stages: HashMap<String, Stages>,
+    /// The groups linked in each new post, in page order.
+    pub(super) post_repos: Vec<(String, Vec<usize>)>,
+    /// Posts whose authors say their code is unlicensed on purpose, with how.
+    pub(super) opted_out: HashMap<String, String>,
+    /// Works in progress to follow up on later rather than skip.
+    pub(super) wip: HashSet<String>,
+}
+
+impl PageScan {
+    /// A reply to a post on the page about some of its repositories, to be
+    /// held back.
+    pub(super) fn held(
+        &self,
+        fullname: &str,
+        repos: Vec<RepoLink>,
+        queued_at: u64,
+    ) -> PendingReply {
+        PendingReply {
+            fullname: fullname.to_owned(),
+            repos,
+            details: self.details.get(fullname).cloned().unwrap_or_default(),
+            queued_at,
+            deferred: false,
+            earlier: None,
+            cross_post: None,
+            check_after: None,
+            not_before: None,
+            stages: self.stages.get(fullname).copied().unwrap_or_default(),
+        }
+    }
+}
+
+impl Bot {
+    /// The repositories a new post links, with the URLs they're linked by,
+    /// or why it's skipped without any of them being checked.
+    fn post_links(
+        &self,
+        listing: &Listing,
+        fullname: &str,
+        post: &Value,
+    ) -> Result<Vec<(GhRepo, String)>, SkipReason> {
+        let domain = post["domain"].as_str().ok_or(SkipReason::Unreadable)?;
+        let limits = LinkLimits {
+            max_url_len: self.config().max_url_len,
+            max_links: self.config().max_links_per_post,
+        };
+        if domain.starts_with("self.") {
+            if listing.github_domain_only() {
+                return Err(SkipReason::NoLinks);
+            }
+            let selftext = post["selftext"].as_str().unwrap_or_default();
+            let links: Vec<(GhRepo, String)> = extract_gh_links(selftext, &limits)
+                .into_iter()
+                .map(|repo| {
+                    let url = repo.html_url();
+                    (repo, url)
+                })
+                .collect();
+            if links.is_empty() {
+                return Err(SkipReason::NoLinks);
+            }
+            return Ok(links);
+        }
+        if listing.github_domain_only() && domain != "github.com" {
+            return Err(SkipReason::NotGithub);
+        }
+        let wrapped = post["url"].as_str().ok_or(SkipReason::Unreadable)?;
+        if limits.too_long(wrapped) {
+            warn!(
+                "Not reading the {} byte URL of {}: {}",
+                wrapped.len(),
+                fullname,
+                truncate_url(wrapped, TRUNCATED_URL_LEN)
+            );
+            return Err(SkipReason::UrlTooLong);
+        }
+        // some clients link through a Reddit redirect, whose domain is Reddit's
+        let url = unwrap_redirects(wrapped);
+        debug!("Found link post to: {}", url);
+        if !url.contains("github.com") {
+            return Err(SkipReason::NotGithub);
+        }
+        match extract_gh_info(&url) {
+            Some(repo) => Ok(vec![(repo, url)]),
+            // such as a profile or an organization
+            None => {
+                debug!(
+                    "{} links to GitHub, but not a repository: {}",
+                    fullname, url
+                );
+                Err(SkipReason::NotARepository)
+            }
+        }
+    }
+
+    /// Look over the posts on a page that haven't been seen yet, skipping
+    /// those the bot shouldn't engage with and grouping the rest by the
+    /// repositories they link.
+    pub(super) fn scan_page(&mut self, listing: &Listing, postings: &[Value]) -> PageScan {
+        let mut scan = PageScan::default();
+        let mut group_index: HashMap<String, usize> = HashMap::new();
+        for post_wrapper in postings {
+            let post = &post_wrapper["data"];
+            let fullname = match post["name"].as_str() {
+                Some(fullname) => fullname.to_owned(),
+                None => {
+                    warn!("Skipping a post without a fullname");
+                    continue;
+                }
+            };
+            scan.fullnames.push(fullname.clone());
+            if !self.processed.insert(&fullname, self.core.clock.unix_now()) {
+                continue;
+            }
+            self.current_post = Some(fullname.clone());
+            #[cfg(test)]
+            {
+                if self.panic_on.as_ref() == Some(&fullname) {
+                    panic!("deliberate panic");
+                }
+            }
+            let raw = |name: &str| post[name].as_str().unwrap_or_default();
+            // what's kept of the post, as it's written by whoever posted it
+            let field = |name: &str, max_chars| sanitize::text(raw(name), max_chars);
+            let subreddit = field("subreddit", NAME_LEN);
+            self.count(&subreddit, |s| s.scanned += 1);
+            scan.details.insert(
+                fullname.clone(),
+                PostDetails {
+                    title: field("title", TITLE_LEN),
+                    subreddit: subreddit.clone(),
+                    permalink: post["permalink"]
+                        .as_str()
+                        .and_then(|p| sanitize::url(&format!("{}{}", reddit::BASE_URL, p)))
+                        .unwrap_or_default(),
+                    author: field("author", NAME_LEN),
+                    flair: field("link_flair_text", NAME_LEN),
+                },
+            );
+            let posted_at = post["created_utc"].as_f64().unwrap_or_default() as u64;
+            let seen_at = self.core.clock.unix_now();
+            scan.stages
+                .insert(fullname.clone(), Stages::new(posted_at, seen_at));
+            let links = match self.post_links(listing, &fullname, post) {
+                Ok(links) => links,
+                Err(reason) => {
+                    self.processed.skip(&fullname, reason);
+                    self.core
+                        .events
+                        .lock()
+                        .unwrap()
+                        .stats_mut()
+                        .skip(&subreddit, reason);
+                    continue;
+                }
+            };
+            self.count(&subreddit, |s| s.candidates += 1);
+            match should_engage(&scan.details[&fullname], None, &self.config()) {
+                Err(SkipReason::WorkInProgress) if self.config().wip_followup_days > 0 => {
+                    debug!("{} is a work in progress, to be followed up on", fullname);
+                    scan.wip.insert(fullname.clone());
+                }
+                Err(reason) => {
+                    debug!("Not looking at {} ({})", fullname, reason);
+                    self.processed.skip(&fullname, reason);
+                    // counted as skips without being checked, so GitHub isn't asked
+                    let mut events = self.core.events.lock().unwrap();
+                    for _ in &links {
+                        events.stats_mut().skip(&subreddit, reason);
+                    }
+                    continue;
+                }
+                Ok(()) => {}
+            }
+            let text = format!("{}\n{}", raw("title"), raw("selftext"));
+            if let Some(phrase) = opt_out_phrase(&text, &self.config().author_opt_out_phrases) {
+                scan.opted_out.insert(fullname.clone(), phrase.to_owned());
+            }
+            let groups = &mut scan.groups;
+            let mut indices = vec![];
+            for (repo, url) in links {
+                let key = repo.canonical_key();
+                let i = match group_index.get(&key) {
+                    Some(&i) => {
+                        groups[i].fullnames.push(fullname.clone());
+                        groups[i].posted_at = groups[i].posted_at.max(posted_at);
+                        i
+                    }
+                    None => {
+                        group_index.insert(key, groups.len());
+                        groups.push(RepoGroup {
+                            repo,
+                            url,
+                            fullnames: vec![fullname.clone()],
+                            removed_license: None,
+                            summary: RepoSummary::default(),
+                            detection_path: vec![],
+                            posted_at,
+                        });
+                        groups.len() - 1
+                    }
+                };
+                indices.push(i);
+            }
+            scan.post_repos.push((fullname, indices));
+        }
+        scan
+    }
+
+    /// Check the license of every repository linked on a page, noting the
+    /// findings on their groups. Licensed repositories may be praised, and
+    /// those it can't yet tell about are held back to be checked again.
+    ///
+    /// Returns whether each group's repository is unlicensed and should be
+    /// replied about.
+    pub(super) async fn check_page(&mut self, scan: &mut PageScan) -> Result<Vec<bool>> {
+        let concurrency = self.config().check_concurrency.max(1);
+        let results: Vec<Result<Checked, BotError>> = {
+            let bot = &*self;
+            stream::iter(scan.groups.iter())
+                .map(|group| bot.check_link(&group.repo, &group.url))
+                .buffered(concurrency)
+                .collect()
+                .await
+        };
+        let mut unlicensed = vec![false; scan.groups.len()];
+        // licensed repositories that are all a post links, which may be praised
+        let mut to_praise = vec![];
+        // repositories it can't tell about yet, with how long to wait to check again
+        let mut to_defer = vec![];
+        let post_repos = &scan.post_repos;
+        let details = &scan.details;
+        let sole_repo = |fullname: &str, i: usize| {
+            post_repos
+                .iter()
+                .any(|(f, indices)| f == fullname && indices[..] == [i])
+        };
+        for (i, (group, result)) in scan.groups.iter_mut().zip(results).enumerate() {
+            let subreddit = details
+                .get(&group.fullnames[0])
+                .map(|d| d.subreddit.as_str())
+                .unwrap_or_default();
+            let result = match result {
+                // the link may have been posted just before the repository was made public
+                Err(e) if e.is_not_found() && self.is_young(group.posted_at) => Ok((
+                    License::Indeterminate(MAYBE_NOT_PUBLIC_YET.to_owned()),
+                    RepoSummary::default(),
+                    vec![],
+                )),
+                result => result,
+            };
+            self.count_check(subreddit, result.as_ref().map(|(l, _, p)| (l, &p[..])));
+            let license = match result {
+                Ok((l, summary, path)) => {
+                    group.summary = summary;
+                    group.detection_path = path;
+                    l
+                }
+                Err(e) if !e.is_retryable() => {
+                    warn!(
+                        "Skipping post {} ({}): {}",
+                        group.fullnames.join(", "),
+                        group.url,
+                        e
+                    );
+                    continue;
+                }
+                Err(e) => {
+                    return Err(e).with_context(|| PostContext {
+                        post: group.fullnames.join(", "),
+                        url: group.url.clone(),
+                        repo: Some(group.repo.to_string()),
+                    });
+                }
+            };
+            let fullname = &group.fullnames[0];
+            let post = details.get(fullname).cloned().unwrap_or_default();
+            if let Err(reason) =
+                should_engage(&post, Some((&group.repo, &group.summary)), &self.config())
+            {
+                info!(
+                    "Not replying about {}, a high-profile repository, in {}",
+                    group.repo, fullname
+                );
+                self.record_event(group, fullname, details, &license, reason.label());
+                continue;
+            }
+            match license {
+                License::Missing => {
+                    unlicensed[i] = true;
+                    let fullname = &group.fullnames[0];
+                    group.removed_license =
+                        self.removed_license(&group.repo, fullname, subreddit).await;
+                }
+                License::Indeterminate(reason) => {
+                    info!(
+                        "Can't tell if {} has a license ({}), checking again later",
+                        group.repo, reason
+                    );
+                    let retry = if reason == MAYBE_NOT_PUBLIC_YET {
+                        NOT_FOUND_RETRY
+                    } else {
+                        INDETERMINATE_RETRY
+                    };
+                    let license = License::Indeterminate(reason);
+                    self.record_event(group, &group.fullnames[0], details, &license, "deferred");
+                    to_defer.push((i, retry));
+                }
+                found @ License::Found(_)
+                    if self.config().praise_mode && sole_repo(&group.fullnames[0], i) =>
+                {
+                    to_praise.push((i, found))
+                }
+                found => self.record_event(group, &group.fullnames[0], details, &found, "none"),
+            }
+        }
+        for (i, retry) in to_defer {
+            let group = &scan.groups[i];
+            let now = self.core.clock.unix_now();
+            let held = scan.held(&group.fullnames[0], vec![group.link()], now);
+            self.pending.push(PendingReply {
+                deferred: true,
+                check_after: Some(now + retry),
+                ..held
+            });
+        }
+        for (i, license) in to_praise {
+            let group = &scan.groups[i];
+            let action = self.praise(group, &scan.details).await;
+            self.record_event(group, &group.fullnames[0], &scan.details, &license, action);
+        }
+        Ok(unlicensed)
+    }
+
+    /// Fetch a subreddit's description and rules and decide whether to comment there.
+    async fn fetch_subreddit_verdict(&self, subreddit: &str) -> Result<Verdict> {
+        let mut responses = vec![];
+        for path in &["about", "about/rules"] {
+            responses.push(self.core.reddit.subreddit(subreddit, path).await?);
+        }
+        Ok(assess(&responses[0], &responses[1]))
+    }
+
+    /// Whether the bot may comment in a subreddit.
+    ///
+    /// Subreddits are checked before the first reply in them and the verdict
+    /// is cached; subreddits that invited the bot are never checked. Either
+    /// way, the bot only reports findings in a subreddit once it has removed
+    /// the configured number of replies, or while only approved users may
+    /// comment there, apart from one reply a day to see whether that's
+    /// changed.
+    pub(super) async fn subreddit_verdict(&mut self, subreddit: &str) -> Verdict {
+        if self.reads_publicly() {
+            return Verdict::ReportOnly("no Reddit credentials".to_owned());
+        }
+        if self
+            .removals
+            .reached(subreddit, self.config().auto_disable_after_removals)
+        {
+            return Verdict::ReportOnly("replies were removed".to_owned());
+        }
+        if !self.may_try_commenting(subreddit) {
+            return Verdict::ReportOnly("commenting needs approval".to_owned());
+        }
+        if is_invited(subreddit, &self.config().invited_subreddits) {
+            return Verdict::Allowed;
+        }
+        let key = subreddit.to_lowercase();
+        if let Some((verdict, checked)) = self.subreddit_verdicts.get(&key) {
+            if self.since(*checked) < RULES_CACHE_TTL {
+                return verdict.clone();
+            }
+        }
+        match self.fetch_subreddit_verdict(subreddit).await {
+            Ok(verdict) => {
+                if let Verdict::ReportOnly(reason) = &verdict {
+                    warn!(
+                        "Not commenting in /r/{} ({}), only reporting findings there",
+                        subreddit, reason
+                    );
+                }
+                self.subreddit_verdicts
+                    .insert(key, (verdict.clone(), self.core.clock.now()));
+                verdict
+            }
+            Err(e) => {
+                warn!("Could not check the rules of /r/{}: {}", subreddit, e);
+                Verdict::ReportOnly("rules could not be checked".to_owned())
+            }
+        }
+    }
+
+    /// Whether a subreddit hasn't refused a reply for needing approval, or
+    /// it's been long enough since to try again, which counts as the try.
+    fn may_try_commenting(&self, subreddit: &str) -> bool {
+        let now = self.core.clock.unix_now();
+        self.core.with_store(|store| {
+            let mut restrictions = match store.restrictions() {
+                Ok(restrictions) => restrictions,
+                Err(e) => {
+                    warn!("Could not load restricted subreddits: {:#}", e);
+                    return true;
+                }
+            };
+            match restricted::find(&restrictions, subreddit) {
+                None => return true,
+                Some(restriction) if !restriction.probe_due(now) => return false,
+                Some(_) => {}
+            }
+            info!(
+                "Trying a reply in /r/{} again, to see whether the bot has been approved",
+                subreddit
+            );
+            restricted::restrict(&mut restrictions, subreddit, now);
+            if let Err(e) = store.save_restrictions(&restrictions) {
+                warn!("Could not save restricted subreddits: {:#}", e);
+            }
+            true
+        })
+    }
+
+    /// Only report findings in a subreddit that refused a reply because only
+    /// approved users may comment there, telling the operator the first time.
+    pub(super) async fn comment_restricted(&mut self, subreddit: &str, post: &str) {
+        let now = self.core.clock.unix_now();
+        let new = self.core.with_store(|store| {
+            let mut restrictions = store.restrictions().unwrap_or_else(|e| {
+                warn!("Could not load restricted subreddits: {:#}", e);
+                vec![]
+            });
+            let new = restricted::restrict(&mut restrictions, subreddit, now);
+            if let Err(e) = store.save_restrictions(&restrictions) {
+                warn!("Could not save restricted subreddits: {:#}", e);
+            }
+            new
+        });
+        if !new {
+            info!(
+                "Only approved users may still comment in /r/{}, trying again in a day",
+                subreddit
+            );
+            return;
+        }
+        warn!(
+            "Only approved users may comment in /r/{}, only reporting findings there until \
+             the moderators approve /u/{}",
+            subreddit,
+            self.config().username
+        );
+        if let Some(reporter) = &mut self.reporter {
+            let error = anyhow!(
+                "Only approved users may comment in /r/{}; ask its moderators to approve the bot",
+                subreddit
+            );
+            let context = ErrorContext {
+                subreddit: Some(subreddit.to_owned()),
+                post: Some(post.to_owned()),
+                repo: None,
+            };
+            if let Err(e) = reporter.capture(&error, Level::Warning, &context).await {
+                warn!("Could not report restricted subreddit to Sentry: {:#}", e);
+            }
+        }
+    }
+
+    /// Reply in a subreddit as usual again, if a reply there was refused
+    /// before, as the bot has since been approved.
+    pub(super) fn comment_allowed(&self, subreddit: &str) {
+        let result: Result<bool> = self.core.with_store(|store| {
+            let mut restrictions = store.restrictions()?;
+            if !restricted::lift(&mut restrictions, subreddit) {
+                return Ok(false);
+            }
+            store.save_restrictions(&restrictions)?;
+            Ok(true)
+        });
+        match result {
+            Ok(true) => info!(
+                "The bot may comment in /r/{} now, replying there again",
+                subreddit
+            ),
+            Ok(false) => {}
+            Err(e) => warn!(
+                "Could not update restricted subreddits for /r/{}: {:#}",
+                subreddit, e
+            ),
+        }
+    }
+
+    /// Whether replies may be made now, given the configured active hours.
+    pub(super) fn in_active_hours(&self) -> bool {
+        match &self.config().active_hours {
+            Some(hours) => {
+                hours.contains(&self.config().timezone, self.core.clock.unix_now() as i64)
+            }
+            None => true,
+        }
+    }
+
+    /// Whether a post is still there to reply to, and isn't by an account
+    /// that's since been deleted, unless those are replied to.
+    pub(super) fn can_reply_to(&self, post: &Post) -> bool {
+        !post.is_removed() && (!post.author.is_deleted() || self.config().reply_to_deleted)
+    }
+
+    /// Whether the configured hourly reply cap has been reached.
+    pub(super) fn reply_cap_reached(&mut self) -> bool {
+        let hour = time::Duration::from_secs(60 * 60);
+        let now = self.core.clock.now();
+        let mut replies = self.core.replies.lock().unwrap();
+        replies.retain(|&sent| now.saturating_duration_since(sent) < hour);
+        match self.config().max_replies_per_hour {
+            Some(cap) => replies.len() >= cap,
+            None => false,
+        }
+    }
+}
+
+/// Whether a post is one the bot should have anything to do with; if not,
+/// why not.
+///
+/// It's checked before any of the repositories the post links, and again
+/// with each repository, as GitHub described it, once it's been checked.
+/// Whether it's a work in progress is only looked at the first time, so one
+/// that's followed up on isn't skipped when its repositories are checked.
+pub(super) fn should_engage(
+    post: &PostDetails,
+    repo: Option<(&GhRepo, &RepoSummary)>,
+    config: &Config,
+) -> Result<(), SkipReason> {
+    match repo {
+        Some((repo, summary)) => {
+            let owner = repo.owner.to_lowercase();
+            if config.high_profile_owners.contains(&owner)
+                || summary.stars.is_some_and(|stars| stars > config.max_stars)
+            {
+                return Err(SkipReason::HighProfile);
+            }
+        }
+        None => {
+            if let Some(phrase) = wip_phrase(post, &config.wip_phrases) {
+                debug!("'{}' says a post is a work in progress", phrase);
+                return Err(SkipReason::WorkInProgress);
+            }
+        }
+    }
+    let author = match post.author() {
+        // nobody to act on a reply, and nobody to compare with the bot's
+        // own account or the exempt ones
+        Author::Deleted if config.reply_to_deleted => return Ok(()),
+        Author::Deleted => return Err(SkipReason::DeletedAuthor),
+        Author::User(name) => name,
+    };
+    if history::same_author(&author, &config.username) {
+        return Err(SkipReason::OwnPost);
+    }
+    if config
+        .exempt_authors
+        .iter()
+        .any(|name| history::same_author(&author, name))
+    {
+        return Err(SkipReason::ExemptAuthor);
+    }
+    Ok(())
+}
+
+/// The first of the phrases in a post's title or flair, which say its code
+/// is a work in progress.
+fn wip_phrase<'a>(post: &PostDetails, phrases: &'a [String]) -> Option<&'a str> {
+    find_phrase(&format!("{}\n{}", post.title, post.flair), phrases)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::should_engage;
+    use crate::models::Config;
+    use crate::pending::PostDetails;
+    use crate::preview::RepoSummary;
+    use crate::skip::SkipReason;
+    use crate::util::GhRepo;
+
+    #[test]
+    fn does_not_engage_with_own_or_exempt_posts() {
+        let mut config = Config::for_tests();
+        config.exempt_authors = vec!["u/Maintainer".to_owned()];
+        let post = |author: &str| PostDetails {
+            author: author.to_owned(),
+            ..PostDetails::default()
+        };
+        assert_eq!(should_engage(&post("someone"), None, &config), Ok(()));
+        assert_eq!(
+            should_engage(&post("BOT"), None, &config),
+            Err(SkipReason::OwnPost)
+        );
+        assert_eq!(
+            should_engage(&post("maintainer"), None, &config),
+            Err(SkipReason::ExemptAuthor)
+        );
+        assert_eq!(should_engage(&post(""), None, &config), Ok(()));
+    }
+
+    #[test]
+    fn does_not_engage_with_works_in_progress() {
+        let mut config = Config::for_tests();
+        config.wip_phrases = ["wip", "work in progress", "prototype", "toy project"]
+            .iter()
+            .map(|p| p.to_string())
+            .collect();
+        let post = |title: &str, flair: &str| PostDetails {
+            title: title.to_owned(),
+            flair: flair.to_owned(),
+            author: "someone".to_owned(),
+            ..PostDetails::default()
+        };
+        let wip = Err(SkipReason::WorkInProgress);
+        let cases = [
+            ("[WIP] A parser for TOML", "", wip),
+            ("wip: a parser", "", wip),
+            ("A parser, very much a Work In Progress", "", wip),
+            ("My toy-project in Rust", "", wip),
+            ("A parser for TOML", "Prototype", wip),
+            ("A parser for TOML", "Work in progress", wip),
+            ("A parser for TOML", "", Ok(())),
+            // only whole words, so these aren't works in progress
+            ("Wipe your disks safely", "", Ok(())),
+            ("A prototyping tool", "Showcase", Ok(())),
+            ("A toy for the project", "", Ok(())),
+        ];
+        for (title, flair, expected) in &cases {
+            assert_eq!(
+                should_engage(&post(title, flair), None, &config),
+                *expected,
+                "{:?} flaired {:?}",
+                title,
+                flair
+            );
+        }
+        // not looked at again with each repository
+        let repo = GhRepo::new("someone", "project");
+        let checked = Some((&repo, &RepoSummary::default()));
+        assert_eq!(should_engage(&post("[WIP]", ""), checked, &config), Ok(()));
+        // and never without any phrases
+        config.wip_phrases = vec![];
+        assert_eq!(should_engage(&post("[WIP]", ""), None, &config), Ok(()));
+    }
+
+    #[test]
+    fn does_not_engage_with_high_profile_repositories() {
+        let mut config = Config::for_tests();
+        config.high_profile_owners = vec!["torvalds".to_owned()];
+        config.max_stars = 500;
+        let post = PostDetails::default();
+        let summary = |stars| RepoSummary {
+            stars,
+            ..RepoSummary::default()
+        };
+        let engage = |owner: &str, stars| {
+            let repo = GhRepo::new(owner, "project");
+            should_engage(&post, Some((&repo, &summary(stars))), &config)
+        };
+        assert_eq!(engage("someone", Some(500)), Ok(()));
+        assert_eq!(engage("someone", None), Ok(()));
+        assert_eq!(engage("Torvalds", Some(3)), Err(SkipReason::HighProfile));
+        assert_eq!(engage("someone", Some(501)), Err(SkipReason::HighProfile));
+    }
+}
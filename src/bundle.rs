@@ -0,0 +1,483 @@
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{collections::BTreeMap, fs, path::Path};
+
+use crate::claims::{self, ReplyClaim};
+use crate::digest;
+use crate::events::{self, Event};
+use crate::history::{self, ReplyRecord};
+use crate::issues::{self, IssueRecord};
+use crate::paths::{digest_file, history_file, issue_file};
+use crate::queue::ApprovalQueue;
+use crate::state::{self, StateFile, STATE_VERSION};
+use crate::suppress::{self, Suppression};
+use crate::util::fold_case;
+
+const USAGE: &str = "Usage: check_for_license state export [--output <file>]\n\
+                     \x20      check_for_license state import <file> [--merge|--replace]";
+/// What a bundle says it is, so that any other JSON file is refused.
+const FORMAT: &str = "check_for_license state bundle";
+/// Version of the bundle format written by this build.
+pub const BUNDLE_VERSION: u64 = 1;
+
+/// Everything in a state directory, in one file that can be moved to
+/// another host.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Bundle {
+    pub format: String,
+    pub version: u64,
+    /// Version of the listing state files the bundle was made from.
+    pub state_version: u64,
+    /// Unix timestamp of the export.
+    pub exported_at: u64,
+    /// The state of each watched listing, by store key.
+    #[serde(default)]
+    pub listings: BTreeMap<String, StateFile>,
+    #[serde(default)]
+    pub replies: Vec<ReplyRecord>,
+    #[serde(default)]
+    pub issues: Vec<IssueRecord>,
+    #[serde(default)]
+    pub suppressions: Vec<Suppression>,
+    #[serde(default)]
+    pub approvals: ApprovalQueue,
+    #[serde(default)]
+    pub events: Vec<Event>,
+    /// Posts the bot is replying or has replied to.
+    #[serde(default)]
+    pub claims: Vec<ReplyClaim>,
+    /// Scheduled time of the last digest that was posted, if one has been.
+    #[serde(default)]
+    pub digest_posted: Option<u64>,
+}
+
+/// How an imported bundle is combined with what's already in the state
+/// directory.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mode {
+    /// Keep both, preferring the newer of two records about the same thing.
+    Merge,
+    /// Throw away what's there first.
+    Replace,
+}
+
+/// Store keys of the listings with state in the directory, in either format.
+pub(crate) fn listing_keys(dir: &Path) -> Result<Vec<String>> {
+    let mut keys = vec![];
+    for entry in fs::read_dir(dir).with_context(|| format!("reading {}", dir.display()))? {
+        let name = entry?.file_name().to_string_lossy().into_owned();
+        let key = name.strip_suffix(".json").and_then(|n| {
+            n.strip_prefix("state-")
+                .or_else(|| n.strip_prefix("processed-"))
+        });
+        if let Some(key) = key {
+            if !keys.iter().any(|k| k == key) {
+                keys.push(key.to_owned());
+            }
+        }
+    }
+    keys.sort();
+    Ok(keys)
+}
+
+/// Collect everything in the state directory into a bundle.
+pub fn export(dir: &Path) -> Result<Bundle> {
+    let mut listings = BTreeMap::new();
+    for key in listing_keys(dir)? {
+        let state = state::load(dir, &key)?;
+        listings.insert(key, state);
+    }
+    let last = digest::last_posted(dir)?;
+    Ok(Bundle {
+        format: FORMAT.to_owned(),
+        version: BUNDLE_VERSION,
+        state_version: STATE_VERSION,
+        exported_at: events::now(),
+        listings,
+        replies: history::load(dir)?,
+        issues: issues::load(dir)?,
+        suppressions: suppress::load(dir)?,
+        approvals: ApprovalQueue::load(dir)?,
+        events: events::load(dir)?,
+        claims: claims::load(dir)?,
+        digest_posted: (last > 0).then_some(last),
+    })
+}
+
+/// Read a bundle, refusing files that aren't bundles or are too new.
+pub fn parse(data: &str) -> Result<Bundle> {
+    let value: Value = serde_json::from_str(data)?;
+    if value["format"] != FORMAT {
+        return Err(anyhow!("Not a check_for_license state bundle"));
+    }
+    let version = value["version"].as_u64().unwrap_or_default();
+    let state_version = value["state_version"].as_u64().unwrap_or_default();
+    if version > BUNDLE_VERSION || state_version > STATE_VERSION {
+        return Err(anyhow!(
+            "Bundle is version {} with state version {}, but this build only understands up \
+             to version {} with state version {}; upgrade check_for_license to import it",
+            version,
+            state_version,
+            BUNDLE_VERSION,
+            STATE_VERSION
+        ));
+    }
+    Ok(serde_json::from_value(value)?)
+}
+
+/// Add records to a list, where a record about the same thing as one
+/// already there replaces it only if it's newer. The list is left oldest first.
+fn merge_newest<T: Clone, K: PartialEq>(
+    list: &mut Vec<T>,
+    incoming: &[T],
+    key: impl Fn(&T) -> K,
+    at: impl Fn(&T) -> u64,
+) {
+    for record in incoming {
+        match list.iter_mut().find(|r| key(r) == key(record)) {
+            Some(existing) if at(record) > at(existing) => *existing = record.clone(),
+            Some(_) => {}
+            None => list.push(record.clone()),
+        }
+    }
+    list.sort_by_key(|r| at(r));
+}
+
+/// Combine the state of a listing from a bundle with the state already kept.
+fn merge_listing(state: &mut StateFile, incoming: &StateFile) {
+    state.processed.merge(&incoming.processed);
+    if state.cursor.anchor().is_none() {
+        state.cursor = incoming.cursor.clone();
+    }
+    merge_newest(
+        &mut state.pending,
+        &incoming.pending,
+        |p| p.fullname.clone(),
+        |p| p.queued_at,
+    );
+    state.stats.restore(&incoming.stats);
+}
+
+/// Write records as JSON lines, replacing the file atomically.
+fn write_lines<T: Serialize>(path: &Path, records: &[T]) -> Result<()> {
+    let mut lines = String::new();
+    for record in records {
+        lines.push_str(&serde_json::to_string(record)?);
+        lines.push('\n');
+    }
+    let tmp = path.with_extension("jsonl.tmp");
+    fs::write(&tmp, lines)
+        .and_then(|_| fs::rename(&tmp, path))
+        .with_context(|| format!("writing {}", path.display()))
+}
+
+/// Load a bundle into the state directory.
+///
+/// The bot shouldn't be running against the directory while this happens.
+pub fn import(dir: &Path, bundle: &Bundle, mode: Mode) -> Result<()> {
+    let mut current = match mode {
+        Mode::Merge => export(dir)?,
+        Mode::Replace => {
+            for key in listing_keys(dir)? {
+                for name in ["state", "processed", "cursor"] {
+                    let path = dir.join(format!("{}-{}.json", name, key));
+                    if path.exists() {
+                        fs::remove_file(&path)
+                            .with_context(|| format!("removing {}", path.display()))?;
+                    }
+                }
+            }
+            let path = digest_file(dir);
+            if path.exists() {
+                fs::remove_file(&path).with_context(|| format!("removing {}", path.display()))?;
+            }
+            bundle.clone()
+        }
+    };
+    if mode == Mode::Merge {
+        for (key, incoming) in &bundle.listings {
+            let state = current.listings.entry(key.clone()).or_default();
+            merge_listing(state, incoming);
+        }
+        merge_newest(
+            &mut current.replies,
+            &bundle.replies,
+            |r| (r.post.clone(), fold_case(&r.repo)),
+            |r| r.at,
+        );
+        merge_newest(
+            &mut current.issues,
+            &bundle.issues,
+            |r| fold_case(&r.repo),
+            |r| r.at,
+        );
+        merge_newest(
+            &mut current.suppressions,
+            &bundle.suppressions,
+            |s| s.entry.clone(),
+            |s| s.at,
+        );
+        current.approvals.merge(&bundle.approvals);
+        for event in &bundle.events {
+            if !current.events.contains(event) {
+                current.events.push(event.clone());
+            }
+        }
+        current.events.sort_by_key(|e| e.at);
+        // a reply made on either host counts
+        for claim in &bundle.claims {
+            match current
+                .claims
+                .iter_mut()
+                .find(|c| c.fullname == claim.fullname)
+            {
+                Some(existing) if claim.replied && !existing.replied => *existing = claim.clone(),
+                Some(_) => {}
+                None => current.claims.push(claim.clone()),
+            }
+        }
+        current.claims.sort_by_key(|c| c.at);
+        current.digest_posted = current.digest_posted.max(bundle.digest_posted);
+    }
+
+    for (key, state) in &current.listings {
+        state::save(dir, key, state)?;
+    }
+    write_lines(&history_file(dir), &current.replies)?;
+    write_lines(&issue_file(dir), &current.issues)?;
+    write_lines(&events::log_file(dir), &current.events)?;
+    claims::save(dir, &current.claims)?;
+    suppress::save(dir, &current.suppressions)?;
+    current.approvals.save(dir)?;
+    if let Some(at) = current.digest_posted {
+        digest::mark_posted(dir, at)?;
+    }
+    Ok(())
+}
+
+/// Run a `state` subcommand against the state directory, returning its output.
+pub fn run(dir: &Path, args: &[String]) -> Result<String> {
+    let (command, args) = args.split_first().ok_or_else(|| anyhow!(USAGE))?;
+    match command.as_str() {
+        "export" => {
+            let bundle = serde_json::to_string_pretty(&export(dir)?)?;
+            match args {
+                [] => Ok(format!("{}\n", bundle)),
+                [flag, path] if flag == "--output" => {
+                    fs::write(path, bundle).with_context(|| format!("writing {}", path))?;
+                    Ok(format!("Exported {} to {}\n", dir.display(), path))
+                }
+                _ => Err(anyhow!(USAGE)),
+            }
+        }
+        "import" => {
+            let (path, mode) = match args {
+                [path] => (path, Mode::Merge),
+                [path, flag] if flag == "--merge" => (path, Mode::Merge),
+                [path, flag] if flag == "--replace" => (path, Mode::Replace),
+                _ => return Err(anyhow!(USAGE)),
+            };
+            let data = fs::read_to_string(path).with_context(|| format!("reading {}", path))?;
+            let bundle = parse(&data).with_context(|| format!("loading {}", path))?;
+            import(dir, &bundle, mode)?;
+            Ok(format!(
+                "Imported {} listings and {} replies from {} into {}\n",
+                bundle.listings.len(),
+                bundle.replies.len(),
+                path,
+                dir.display()
+            ))
+        }
+        _ => Err(anyhow!(USAGE)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{export, import, parse, run, Mode};
+    use crate::history::{self, ReplyRecord};
+    use crate::issues::{self, IssueRecord};
+    use crate::latency::Stages;
+    use crate::pending::{PendingReply, PostDetails, RepoLink};
+    use crate::preview::RepoSummary;
+    use crate::queue::ApprovalQueue;
+    use crate::state::{self, StateFile};
+    use crate::suppress::{self, Suppression};
+    use crate::util::GhRepo;
+
+    fn reply(post: &str, comment: &str, at: u64) -> ReplyRecord {
+        ReplyRecord {
+            author: "someone".to_owned(),
+            repo: "someone/project".to_owned(),
+            post: post.to_owned(),
+            comment: Some(comment.to_owned()),
+            at,
+            resolved: None,
+        }
+    }
+
+    fn pending(fullname: &str, queued_at: u64) -> PendingReply {
+        PendingReply {
+            fullname: fullname.to_owned(),
+            repos: vec![RepoLink {
+                repo: GhRepo::new("someone", "project"),
+                url: "https://github.com/someone/project".to_owned(),
+                removed_license: None,
+                summary: RepoSummary::default(),
+            }],
+            details: PostDetails::default(),
+            queued_at,
+            deferred: false,
+            earlier: None,
+            cross_post: None,
+            check_after: None,
+            not_before: None,
+            stages: Stages::default(),
+        }
+    }
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    /// Fill a state directory with a bit of everything.
+    fn populate(dir: &std::path::Path) {
+        state::save(
+            dir,
+            "new-rust",
+            &StateFile {
+                processed: vec!["t3_a", "t3_b"].into_iter().collect(),
+                pending: vec![pending("t3_b", 100)],
+                ..StateFile::default()
+            },
+        )
+        .unwrap();
+        history::append(dir, &[reply("t3_a", "t1_a", 100)]).unwrap();
+        issues::append(
+            dir,
+            &IssueRecord {
+                repo: "someone/project".to_owned(),
+                number: 1,
+                url: "https://github.com/someone/project/issues/1".to_owned(),
+                post: "t3_a".to_owned(),
+                at: 100,
+            },
+        )
+        .unwrap();
+        suppress::add(
+            dir,
+            Suppression {
+                entry: "other/project".to_owned(),
+                source: "test".to_owned(),
+                reason: "asked".to_owned(),
+                at: 100,
+                expires: None,
+            },
+        )
+        .unwrap();
+        ApprovalQueue::update(dir, |q| Ok(q.push(pending("t3_c", 100)))).unwrap();
+    }
+
+    #[test]
+    fn round_trips_through_a_bundle() {
+        let from = tempfile::tempdir().unwrap();
+        let to = tempfile::tempdir().unwrap();
+        populate(from.path());
+        let path = from.path().join("bundle.json");
+        let path = path.to_str().unwrap();
+
+        run(from.path(), &args(&["export", "--output", path])).unwrap();
+        run(to.path(), &args(&["import", path, "--replace"])).unwrap();
+
+        let before = export(from.path()).unwrap();
+        let mut after = export(to.path()).unwrap();
+        after.exported_at = before.exported_at;
+        assert_eq!(before, after);
+        assert_eq!(
+            state::load(to.path(), "new-rust")
+                .unwrap()
+                .processed
+                .fullnames(),
+            vec!["t3_a", "t3_b"]
+        );
+        assert_eq!(ApprovalQueue::load(to.path()).unwrap().items().len(), 1);
+    }
+
+    #[test]
+    fn replace_throws_away_what_was_there() {
+        let from = tempfile::tempdir().unwrap();
+        let to = tempfile::tempdir().unwrap();
+        populate(from.path());
+        state::save(to.path(), "new-other", &StateFile::default()).unwrap();
+        history::append(to.path(), &[reply("t3_z", "t1_z", 50)]).unwrap();
+
+        import(to.path(), &export(from.path()).unwrap(), Mode::Replace).unwrap();
+        assert!(!to.path().join("state-new-other.json").exists());
+        assert_eq!(
+            history::load(to.path()).unwrap(),
+            vec![reply("t3_a", "t1_a", 100)]
+        );
+    }
+
+    #[test]
+    fn merge_prefers_the_newer_reply_to_the_same_post() {
+        let from = tempfile::tempdir().unwrap();
+        let to = tempfile::tempdir().unwrap();
+        populate(from.path());
+        populate(to.path());
+        history::append(to.path(), &[reply("t3_d", "t1_d", 150)]).unwrap();
+        state::save(
+            to.path(),
+            "new-rust",
+            &StateFile {
+                processed: vec!["t3_d"].into_iter().collect(),
+                pending: vec![pending("t3_b", 300)],
+                ..StateFile::default()
+            },
+        )
+        .unwrap();
+
+        // The same post was replied to again, later, on the old host, and
+        // each host has a reply the other doesn't.
+        let mut bundle = export(from.path()).unwrap();
+        bundle.replies = vec![reply("t3_e", "t1_e", 50), reply("t3_a", "t1_newer", 200)];
+        import(to.path(), &bundle, Mode::Merge).unwrap();
+
+        assert_eq!(
+            history::load(to.path()).unwrap(),
+            vec![
+                reply("t3_e", "t1_e", 50),
+                reply("t3_d", "t1_d", 150),
+                reply("t3_a", "t1_newer", 200),
+            ]
+        );
+        let state = state::load(to.path(), "new-rust").unwrap();
+        assert_eq!(state.processed.fullnames(), vec!["t3_d", "t3_a", "t3_b"]);
+        assert_eq!(state.pending, vec![pending("t3_b", 300)]);
+        assert_eq!(suppress::load(to.path()).unwrap().len(), 1);
+        assert_eq!(issues::load(to.path()).unwrap().len(), 1);
+        assert_eq!(ApprovalQueue::load(to.path()).unwrap().items().len(), 1);
+
+        // Importing an older record about the same post keeps the newer one.
+        bundle.replies = vec![reply("t3_a", "t1_older", 10)];
+        import(to.path(), &bundle, Mode::Merge).unwrap();
+        assert!(history::load(to.path())
+            .unwrap()
+            .contains(&reply("t3_a", "t1_newer", 200)));
+    }
+
+    #[test]
+    fn refuses_other_files_and_newer_bundles() {
+        assert!(parse("{\"processed\": []}").is_err());
+        let newer = format!(
+            "{{\"format\": \"{}\", \"version\": 99, \"state_version\": 1, \"exported_at\": 0}}",
+            super::FORMAT
+        );
+        let error = parse(&newer).unwrap_err().to_string();
+        assert!(error.contains("upgrade"), "{}", error);
+        let tmp = tempfile::tempdir().unwrap();
+        let bundle = run(tmp.path(), &args(&["export"])).unwrap();
+        assert!(parse(&bundle).unwrap().listings.is_empty());
+    }
+}
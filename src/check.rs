@@ -0,0 +1,1355 @@
+use anyhow::{anyhow, Context, Result};
+use futures::{stream, StreamExt};
+use log::debug;
+use reqwest::{header, Client, ClientBuilder, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    env, fmt,
+    io::{self, BufRead},
+    path::Path,
+    time::Duration,
+};
+
+use crate::bot::Endpoints;
+use crate::errors::BotError;
+use crate::license::{DetectionStep, Probe};
+use crate::repo_history::{CheckRecord, RepoHistory};
+use crate::util::{extract_gh_info, format_table};
+
+/// Files whose presence at the top of a repository means it has a license.
+const LICENSE_FILES: &[&str] = &["LICENSE", "LICENCE", "COPYING", "UNLICENSE"];
+
+/// Settings for a one-off license check.
+///
+/// ```
+/// use check_for_license::check::CheckOptions;
+///
+/// let options = CheckOptions {
+///     github_token: Some("ghp_example".to_owned()),
+///     ..CheckOptions::default()
+/// };
+/// assert_eq!(options.github_url, "https://api.github.com");
+/// ```
+#[derive(Clone, Debug)]
+pub struct CheckOptions {
+    /// Token to send to GitHub, for a higher rate limit and private repositories.
+    pub github_token: Option<String>,
+    /// Base URL of GitHub's API.
+    pub github_url: String,
+    pub user_agent: String,
+    pub timeout: Duration,
+}
+
+impl Default for CheckOptions {
+    fn default() -> Self {
+        Self {
+            github_token: None,
+            github_url: Endpoints::default().github,
+            user_agent: concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION")).to_owned(),
+            timeout: Duration::from_secs(60),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Verdict {
+    Licensed,
+    Unlicensed,
+    /// GitHub's answers don't say either way; the report says why.
+    Indeterminate,
+}
+
+/// How a repository's license was found.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Method {
+    /// GitHub recognized a license file.
+    LicenseApi,
+    /// A file named like a license is at the top of the repository.
+    ContentsScan,
+    /// A package manifest (`Cargo.toml` or `package.json`) declares a license.
+    Manifest,
+}
+
+/// A license that was found, as far as it could be identified.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct DetectedLicense {
+    /// SPDX id, or for a manifest the SPDX expression it declares.
+    pub spdx_id: Option<String>,
+    pub name: Option<String>,
+}
+
+/// The result of checking a repository for a license.
+///
+/// ```
+/// use check_for_license::check::{CheckReport, Verdict};
+///
+/// let report: CheckReport = serde_json::from_str(
+///     r#"{"repo":"someone/project","url":"https://github.com/someone/project",
+///         "verdict":"unlicensed","license":null,"method":null}"#,
+/// )
+/// .unwrap();
+/// assert_eq!(report.verdict, Verdict::Unlicensed);
+/// ```
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct CheckReport {
+    /// The repository as GitHub names it, as `owner/name`, after any renames.
+    pub repo: String,
+    pub url: String,
+    pub verdict: Verdict,
+    /// The license, when the repository has one.
+    pub license: Option<DetectedLicense>,
+    /// How the license was found, when the repository has one.
+    pub method: Option<Method>,
+    /// Why there's no telling, when the verdict is indeterminate.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// Each way the repository was looked through for a license, in order,
+    /// with what it made of it.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub detection_path: Vec<DetectionStep>,
+    /// The bot's latest checks of the repository, oldest first, when it has
+    /// checked it before.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub history: Vec<CheckRecord>,
+}
+
+impl fmt::Display for CheckReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "repository: {} ({})", self.repo, self.url)?;
+        match self.verdict {
+            Verdict::Licensed => writeln!(f, "verdict:    licensed")?,
+            Verdict::Unlicensed => writeln!(f, "verdict:    unlicensed")?,
+            Verdict::Indeterminate => writeln!(
+                f,
+                "verdict:    indeterminate ({})",
+                self.reason.as_deref().unwrap_or("unknown reason")
+            )?,
+        }
+        if let Some(license) = &self.license {
+            let described = match (&license.spdx_id, &license.name) {
+                (Some(id), Some(name)) => format!("{} ({})", id, name),
+                (Some(id), None) => id.clone(),
+                (None, Some(name)) => name.clone(),
+                (None, None) => "not identified".to_owned(),
+            };
+            writeln!(f, "license:    {}", described)?;
+        }
+        if let Some(method) = self.method {
+            let method = match method {
+                Method::LicenseApi => "GitHub's license API",
+                Method::ContentsScan => "a license file in the repository",
+                Method::Manifest => "the package manifest",
+            };
+            writeln!(f, "found by:   {}", method)?;
+        }
+        for (i, step) in self.detection_path.iter().enumerate() {
+            let label = if i == 0 { "checked:" } else { "" };
+            writeln!(f, "{:<12}{}", label, step)?;
+        }
+        Ok(())
+    }
+}
+
+impl CheckReport {
+    fn licensed(
+        repo: &Repo,
+        license: DetectedLicense,
+        method: Method,
+        detection_path: Vec<DetectionStep>,
+    ) -> Self {
+        Self {
+            repo: repo.full_name.clone(),
+            url: repo.html_url.clone(),
+            verdict: Verdict::Licensed,
+            license: Some(license),
+            method: Some(method),
+            reason: None,
+            detection_path,
+            history: vec![],
+        }
+    }
+
+    fn indeterminate(repo: &Repo, reason: &str, detection_path: Vec<DetectionStep>) -> Self {
+        Self {
+            repo: repo.full_name.clone(),
+            url: repo.html_url.clone(),
+            verdict: Verdict::Indeterminate,
+            license: None,
+            method: None,
+            reason: Some(reason.to_owned()),
+            detection_path,
+            history: vec![],
+        }
+    }
+}
+
+/// A repository as GitHub describes it.
+struct Repo {
+    full_name: String,
+    html_url: String,
+}
+
+fn build_client(options: &CheckOptions) -> Result<Client> {
+    let mut headers = header::HeaderMap::new();
+    if let Some(token) = &options.github_token {
+        headers.insert(
+            header::AUTHORIZATION,
+            header::HeaderValue::from_str(&format!("token {}", token))?,
+        );
+    }
+    Ok(ClientBuilder::new()
+        .user_agent(&options.user_agent)
+        .timeout(options.timeout)
+        .default_headers(headers)
+        .build()?)
+}
+
+/// Check whether the GitHub repository at a URL has a license.
+///
+/// GitHub's license API is asked first. If it doesn't know of a license, the
+/// files at the top of the repository are looked through for one, and then
+/// `Cargo.toml` and `package.json` for a declared license. No Reddit settings
+/// are needed.
+///
+/// ```no_run
+/// use check_for_license::check::{check_repository_url, CheckOptions, Verdict};
+///
+/// # async fn example() -> anyhow::Result<()> {
+/// let report = check_repository_url(
+///     "https://github.com/Celeo/check_for_license",
+///     &CheckOptions::default(),
+/// )
+/// .await?;
+/// if report.verdict == Verdict::Unlicensed {
+///     println!("{} has no license", report.repo);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub async fn check_repository_url(url: &str, options: &CheckOptions) -> Result<CheckReport> {
+    let mut gh_repo = extract_gh_info(url)
+        .ok_or_else(|| anyhow!("'{}' is not a link to a GitHub repository", url))?;
+    gh_repo.name = gh_repo.name.trim_end_matches(".git").to_owned();
+    let client = build_client(options)?;
+    let base = options.github_url.trim_end_matches('/');
+
+    let resp = client.get(gh_repo.api_url(base)).send().await?;
+    if !resp.status().is_success() {
+        let action = format!("Invalid GH project '{}'", gh_repo);
+        return Err(BotError::from_github(resp, &action).into());
+    }
+    let data: Value = resp.json().await?;
+    let repo = Repo {
+        full_name: data["full_name"]
+            .as_str()
+            .map(str::to_owned)
+            .unwrap_or_else(|| gh_repo.to_string()),
+        html_url: data["html_url"]
+            .as_str()
+            .map(str::to_owned)
+            .unwrap_or_else(|| gh_repo.html_url()),
+    };
+    let base = format!("{}/repos/{}", base, repo.full_name);
+    let mut path = vec![];
+
+    if let Some(license) = license_api(&client, &base).await? {
+        let step = DetectionStep::new(Probe::LicenseApi, Verdict::Licensed);
+        path.push(
+            match license.spdx_id.clone().or_else(|| license.name.clone()) {
+                Some(id) => step.with_detail(id),
+                None => step,
+            },
+        );
+        return Ok(CheckReport::licensed(
+            &repo,
+            license,
+            Method::LicenseApi,
+            path,
+        ));
+    }
+    path.push(DetectionStep::new(Probe::LicenseApi, Verdict::Unlicensed));
+    let files = match top_level_files(&client, &base).await? {
+        Some(files) => files,
+        // GitHub knows of no license in a repository without commits either
+        None if data["size"] == 0 => {
+            path.push(
+                DetectionStep::new(Probe::ContentsScan, Verdict::Indeterminate)
+                    .with_detail("empty repository"),
+            );
+            return Ok(CheckReport::indeterminate(&repo, "empty repository", path));
+        }
+        None => vec![],
+    };
+    if let Some(file) = files.iter().find(|file| is_license_file(file)) {
+        path.push(DetectionStep::new(Probe::ContentsScan, Verdict::Licensed).with_detail(file));
+        let license = DetectedLicense::default();
+        return Ok(CheckReport::licensed(
+            &repo,
+            license,
+            Method::ContentsScan,
+            path,
+        ));
+    }
+    path.push(DetectionStep::new(Probe::ContentsScan, Verdict::Unlicensed));
+    for (manifest, probe) in &[
+        ("Cargo.toml", Probe::CargoToml),
+        ("package.json", Probe::PackageJson),
+    ] {
+        if !files.iter().any(|file| file == manifest) {
+            continue;
+        }
+        if let Some(spdx_id) = manifest_license(&client, &base, manifest).await? {
+            path.push(DetectionStep::new(*probe, Verdict::Licensed).with_detail(&spdx_id));
+            let license = DetectedLicense {
+                spdx_id: Some(spdx_id),
+                name: None,
+            };
+            return Ok(CheckReport::licensed(
+                &repo,
+                license,
+                Method::Manifest,
+                path,
+            ));
+        }
+        path.push(DetectionStep::new(*probe, Verdict::Unlicensed));
+    }
+    Ok(CheckReport {
+        repo: repo.full_name,
+        url: repo.html_url,
+        verdict: Verdict::Unlicensed,
+        license: None,
+        method: None,
+        reason: None,
+        detection_path: path,
+        history: vec![],
+    })
+}
+
+/// The license GitHub recognized for a repository, if any.
+async fn license_api(client: &Client, base: &str) -> Result<Option<DetectedLicense>> {
+    let resp = client.get(format!("{}/license", base)).send().await?;
+    if resp.status() == StatusCode::NOT_FOUND {
+        debug!("GitHub knows of no license for {}", base);
+        return Ok(None);
+    }
+    if !resp.status().is_success() {
+        return Err(BotError::from_github(resp, "Could not fetch license").into());
+    }
+    let data: Value = resp.json().await?;
+    let field = |key: &str| {
+        data["license"][key]
+            .as_str()
+            // GitHub's placeholder for a license it couldn't identify
+            .filter(|v| *v != "NOASSERTION")
+            .map(str::to_owned)
+    };
+    Ok(Some(DetectedLicense {
+        spdx_id: field("spdx_id"),
+        name: field("name"),
+    }))
+}
+
+/// Names of the files at the top of a repository, or `None` if GitHub
+/// says the repository is empty.
+async fn top_level_files(client: &Client, base: &str) -> Result<Option<Vec<String>>> {
+    let resp = client.get(format!("{}/contents/", base)).send().await?;
+    if resp.status() == StatusCode::NOT_FOUND {
+        return Ok(Some(vec![]));
+    }
+    // "Git Repository is empty."
+    if resp.status() == StatusCode::CONFLICT {
+        return Ok(None);
+    }
+    if !resp.status().is_success() {
+        return Err(BotError::from_github(resp, "Could not list repository contents").into());
+    }
+    let entries: Value = resp.json().await?;
+    Ok(Some(
+        entries
+            .as_array()
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+            .iter()
+            .filter(|e| e["type"] == "file")
+            .filter_map(|e| e["name"].as_str().map(str::to_owned))
+            .collect(),
+    ))
+}
+
+pub(crate) fn is_license_file(name: &str) -> bool {
+    let name = name.to_uppercase();
+    LICENSE_FILES.iter().any(|prefix| name.starts_with(prefix))
+}
+
+/// The license declared by a package manifest, if it declares one.
+async fn manifest_license(client: &Client, base: &str, manifest: &str) -> Result<Option<String>> {
+    let resp = client
+        .get(format!("{}/contents/{}", base, manifest))
+        .header(header::ACCEPT, "application/vnd.github.v3.raw")
+        .send()
+        .await?;
+    if !resp.status().is_success() {
+        return Err(BotError::from_github(resp, &format!("Could not fetch {}", manifest)).into());
+    }
+    let text = resp.text().await?;
+    Ok(match manifest {
+        "package.json" => package_json_license(&text),
+        _ => cargo_toml_license(&text),
+    })
+}
+
+/// The `license` of the `[package]` table in a `Cargo.toml`.
+fn cargo_toml_license(text: &str) -> Option<String> {
+    let mut in_package = false;
+    for line in text.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_package = line == "[package]";
+            continue;
+        }
+        if !in_package {
+            continue;
+        }
+        if let Some((key, value)) = line.split_once('=') {
+            if key.trim() == "license" {
+                let value = value.trim().trim_matches(|c| c == '"' || c == '\'');
+                return Some(value.to_owned()).filter(|v| !v.is_empty());
+            }
+        }
+    }
+    None
+}
+
+/// The `license` of a `package.json`.
+fn package_json_license(text: &str) -> Option<String> {
+    let data: Value = serde_json::from_str(text).ok()?;
+    data["license"]
+        .as_str()
+        .filter(|v| !v.is_empty() && *v != "UNLICENSED")
+        .map(str::to_owned)
+}
+
+/// Repositories checked at once by default when several are given.
+const DEFAULT_CONCURRENCY: usize = 4;
+
+/// What checking a repository came to, which decides the `check`
+/// subcommand's exit code.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    Licensed,
+    /// No license was found, including when there's no telling, as with an
+    /// empty repository.
+    Unlicensed,
+    /// GitHub has no such repository, or won't say that it does.
+    NotFound,
+    /// The repository couldn't be checked, such as when the URL isn't a
+    /// GitHub link or GitHub couldn't be reached.
+    Error,
+}
+
+impl Outcome {
+    /// The exit code for this outcome: 0 when licensed, 2 when unlicensed,
+    /// 3 when not found and 1 on errors.
+    pub fn exit_code(self) -> i32 {
+        match self {
+            Outcome::Licensed => 0,
+            Outcome::Error => 1,
+            Outcome::Unlicensed => 2,
+            Outcome::NotFound => 3,
+        }
+    }
+
+    /// The worst of several outcomes, licensed if there are none.
+    pub fn worst(outcomes: impl IntoIterator<Item = Outcome>) -> Outcome {
+        outcomes
+            .into_iter()
+            .max_by_key(|o| match o {
+                Outcome::Licensed => 0,
+                Outcome::Unlicensed => 1,
+                Outcome::NotFound => 2,
+                Outcome::Error => 3,
+            })
+            .unwrap_or(Outcome::Licensed)
+    }
+}
+
+/// The result of checking one of several URLs.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct CheckResult {
+    /// The URL as it was given.
+    pub url: String,
+    pub outcome: Outcome,
+    /// The report, when the repository could be checked.
+    pub report: Option<CheckReport>,
+    /// Why it couldn't be, when it couldn't.
+    pub error: Option<String>,
+}
+
+impl CheckResult {
+    fn new(url: &str, result: Result<CheckReport>) -> Self {
+        match result {
+            Ok(report) => Self {
+                url: url.to_owned(),
+                outcome: match report.verdict {
+                    Verdict::Licensed => Outcome::Licensed,
+                    Verdict::Unlicensed | Verdict::Indeterminate => Outcome::Unlicensed,
+                },
+                report: Some(report),
+                error: None,
+            },
+            Err(e) => {
+                let not_found = e
+                    .downcast_ref::<BotError>()
+                    .is_some_and(BotError::is_not_found);
+                Self {
+                    url: url.to_owned(),
+                    outcome: if not_found {
+                        Outcome::NotFound
+                    } else {
+                        Outcome::Error
+                    },
+                    report: None,
+                    error: Some(format!("{:#}", e)),
+                }
+            }
+        }
+    }
+}
+
+/// Check several URLs, at most `concurrency` at a time, returning their
+/// results in the order they were given.
+pub async fn check_urls(
+    urls: &[String],
+    options: &CheckOptions,
+    concurrency: usize,
+) -> Vec<CheckResult> {
+    stream::iter(urls)
+        .map(|url| async move { CheckResult::new(url, check_repository_url(url, options).await) })
+        .buffered(concurrency.max(1))
+        .collect()
+        .await
+}
+
+/// The URLs in lines read from stdin, skipping blank lines and `#` comments.
+fn read_urls(input: impl BufRead) -> Result<Vec<String>> {
+    let mut urls = vec![];
+    for line in input.lines() {
+        let line = line.context("Could not read URLs from stdin")?;
+        let line = line.trim();
+        if !line.is_empty() && !line.starts_with('#') {
+            urls.push(line.to_owned());
+        }
+    }
+    Ok(urls)
+}
+
+/// Several results laid out as a table, one repository to a row.
+fn table(results: &[CheckResult]) -> String {
+    let rows: Vec<Vec<String>> = results
+        .iter()
+        .map(|r| {
+            let outcome = match (r.outcome, &r.report) {
+                (_, Some(report)) if report.verdict == Verdict::Indeterminate => {
+                    "unlicensed (indeterminate)"
+                }
+                (Outcome::Licensed, _) => "licensed",
+                (Outcome::Unlicensed, _) => "unlicensed",
+                (Outcome::NotFound, _) => "not found",
+                (Outcome::Error, _) => "error",
+            };
+            let detail = match (&r.report, &r.error) {
+                (Some(report), _) => report
+                    .license
+                    .as_ref()
+                    .and_then(|l| l.spdx_id.clone().or_else(|| l.name.clone()))
+                    .or_else(|| report.reason.clone())
+                    .unwrap_or_default(),
+                (None, Some(error)) => error.clone(),
+                (None, None) => String::new(),
+            };
+            vec![r.url.clone(), outcome.to_owned(), detail]
+        })
+        .collect();
+    format_table(&["URL", "RESULT", "DETAIL"], &rows)
+}
+
+/// Add the bot's latest checks of each repository to its report, from the
+/// history in the state directory, when the bot has checked it before.
+fn add_history(results: &mut [CheckResult], dir: &Path) {
+    let history = match RepoHistory::load(dir) {
+        Ok(history) => history,
+        Err(e) => {
+            debug!("Not adding the repositories' history: {:#}", e);
+            return;
+        }
+    };
+    for result in results {
+        // looked for as it was linked too, in case it has since been renamed
+        let linked = extract_gh_info(&result.url).map(|repo| repo.to_string());
+        if let Some(report) = &mut result.report {
+            let checks = match history.checks(&report.repo) {
+                [] => linked.map_or(&[][..], |repo| history.checks(&repo)),
+                checks => checks,
+            };
+            report.history = checks.to_vec();
+        }
+    }
+}
+
+/// Check repositories from the command line:
+/// `check [<url>...] [--stdin] [--json] [--concurrency <n>]`.
+///
+/// A single URL is reported on in full, as text or with `--json` as its
+/// [`CheckReport`]. Several, given as arguments or one to a line on stdin
+/// with `--stdin`, are reported on in a table, or with `--json` as an array
+/// of [`CheckResult`]s. Returns the exit code for the worst outcome; only
+/// bad arguments are errors.
+///
+/// A GitHub token is read from `CFL_GITHUB_TOKEN`, if it's set.
+pub async fn run(args: &[String]) -> Result<i32> {
+    let mut urls = vec![];
+    let mut json = false;
+    let mut stdin = false;
+    let mut concurrency = DEFAULT_CONCURRENCY;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--json" => json = true,
+            "--stdin" => stdin = true,
+            "--concurrency" => {
+                concurrency = args
+                    .next()
+                    .and_then(|n| n.parse().ok())
+                    .filter(|n| *n > 0)
+                    .ok_or_else(|| anyhow!("--concurrency takes a number above 0"))?
+            }
+            a if a.starts_with("--") => return Err(anyhow!("Unknown option '{}'", a)),
+            a => urls.push(a.to_owned()),
+        }
+    }
+    if stdin {
+        urls.extend(read_urls(io::stdin().lock())?);
+    }
+    if urls.is_empty() {
+        return Err(anyhow!(
+            "Usage: check_for_license check [<url>...] [--stdin] [--json] [--concurrency <n>]"
+        ));
+    }
+    let options = CheckOptions {
+        github_token: env::var("CFL_GITHUB_TOKEN").ok(),
+        ..CheckOptions::default()
+    };
+    let mut results = check_urls(&urls, &options, concurrency).await;
+    let state_dir = env::var("CFL_STATE_DIR").unwrap_or_else(|_| ".".to_owned());
+    add_history(&mut results, Path::new(&state_dir));
+    let outcome = Outcome::worst(results.iter().map(|r| r.outcome));
+    match &results[..] {
+        [single] if !stdin => match (&single.report, &single.error) {
+            (Some(report), _) if json => println!("{}", serde_json::to_string_pretty(report)?),
+            (Some(report), _) => print!("{}", report),
+            (None, error) => eprintln!(
+                "Could not check {}: {}",
+                single.url,
+                error.as_deref().unwrap_or_default()
+            ),
+        },
+        _ if json => println!("{}", serde_json::to_string_pretty(&results)?),
+        _ => print!("{}", table(&results)),
+    }
+    Ok(outcome.exit_code())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        add_history, cargo_toml_license, check_repository_url, check_urls, is_license_file,
+        package_json_license, read_urls, table, CheckOptions, CheckReport, CheckResult,
+        DetectedLicense, Method, Outcome, Verdict,
+    };
+    use crate::license::{overturned_by, DetectionStep, Probe};
+    use crate::repo_history::{CheckRecord, RepoHistory};
+    use mockito::{mock, server_url};
+    use serde_json::json;
+
+    fn options(prefix: &str) -> CheckOptions {
+        CheckOptions {
+            github_token: Some("secret".to_owned()),
+            github_url: format!("{}/{}", server_url(), prefix),
+            ..CheckOptions::default()
+        }
+    }
+
+    fn repo_mock(prefix: &str, full_name: &str) -> mockito::Mock {
+        mock("GET", format!("/{}/repos/{}", prefix, full_name).as_str())
+            .match_header("authorization", "token secret")
+            .with_body(
+                json!({
+                    "full_name": full_name,
+                    "html_url": format!("https://github.com/{}", full_name),
+                })
+                .to_string(),
+            )
+            .create()
+    }
+
+    fn contents_mock(prefix: &str, full_name: &str, files: &[&str]) -> mockito::Mock {
+        let entries: Vec<_> = files
+            .iter()
+            .map(|name| json!({ "name": name, "type": "file" }))
+            .collect();
+        mock(
+            "GET",
+            format!("/{}/repos/{}/contents/", prefix, full_name).as_str(),
+        )
+        .with_body(json!(entries).to_string())
+        .create()
+    }
+
+    fn no_license_mock(prefix: &str, full_name: &str) -> mockito::Mock {
+        mock(
+            "GET",
+            format!("/{}/repos/{}/license", prefix, full_name).as_str(),
+        )
+        .with_status(404)
+        .create()
+    }
+
+    #[tokio::test]
+    async fn license_api() {
+        let _repo = repo_mock("check-api", "someone/project");
+        let _license = mock("GET", "/check-api/repos/someone/project/license")
+            .with_body(r#"{"license":{"spdx_id":"MIT","name":"MIT License"}}"#)
+            .create();
+        let report = check_repository_url(
+            "https://github.com/someone/project/tree/main",
+            &options("check-api"),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            report,
+            CheckReport {
+                repo: "someone/project".to_owned(),
+                url: "https://github.com/someone/project".to_owned(),
+                verdict: Verdict::Licensed,
+                license: Some(DetectedLicense {
+                    spdx_id: Some("MIT".to_owned()),
+                    name: Some("MIT License".to_owned()),
+                }),
+                method: Some(Method::LicenseApi),
+                reason: None,
+                detection_path: vec![
+                    DetectionStep::new(Probe::LicenseApi, Verdict::Licensed).with_detail("MIT")
+                ],
+                history: vec![],
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn renamed_repos_are_reported_by_their_new_name() {
+        let _old = mock("GET", "/check-renamed/repos/someone/Old")
+            .with_body(r#"{"full_name":"someone/new","html_url":"https://github.com/someone/new"}"#)
+            .create();
+        let _license = mock("GET", "/check-renamed/repos/someone/new/license")
+            .with_body(r#"{"license":{"spdx_id":"NOASSERTION","name":"Other"}}"#)
+            .create();
+        let report = check_repository_url(
+            "https://github.com/someone/Old.git",
+            &options("check-renamed"),
+        )
+        .await
+        .unwrap();
+        assert_eq!(report.repo, "someone/new");
+        assert_eq!(
+            report.license,
+            Some(DetectedLicense {
+                spdx_id: None,
+                name: Some("Other".to_owned()),
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn contents_scan() {
+        let _repo = repo_mock("check-contents", "someone/project");
+        let _license = no_license_mock("check-contents", "someone/project");
+        let _contents = contents_mock(
+            "check-contents",
+            "someone/project",
+            &["README.md", "Licence.txt"],
+        );
+        let report = check_repository_url(
+            "https://github.com/someone/project",
+            &options("check-contents"),
+        )
+        .await
+        .unwrap();
+        assert_eq!(report.verdict, Verdict::Licensed);
+        assert_eq!(report.method, Some(Method::ContentsScan));
+        assert_eq!(report.license, Some(DetectedLicense::default()));
+    }
+
+    #[tokio::test]
+    async fn manifest() {
+        let _repo = repo_mock("check-manifest", "someone/project");
+        let _license = no_license_mock("check-manifest", "someone/project");
+        let _contents = contents_mock("check-manifest", "someone/project", &["Cargo.toml", "src"]);
+        let _cargo = mock(
+            "GET",
+            "/check-manifest/repos/someone/project/contents/Cargo.toml",
+        )
+        .match_header("accept", "application/vnd.github.v3.raw")
+        .with_body("[package]\nname = \"project\"\nlicense = \"MIT OR Apache-2.0\"\n")
+        .create();
+        let report = check_repository_url(
+            "https://github.com/someone/project",
+            &options("check-manifest"),
+        )
+        .await
+        .unwrap();
+        assert_eq!(report.method, Some(Method::Manifest));
+        assert_eq!(
+            report.license.unwrap().spdx_id.as_deref(),
+            Some("MIT OR Apache-2.0")
+        );
+    }
+
+    #[tokio::test]
+    async fn unlicensed() {
+        let _repo = repo_mock("check-none", "someone/project");
+        let _license = no_license_mock("check-none", "someone/project");
+        let _contents = contents_mock("check-none", "someone/project", &["package.json"]);
+        let _package = mock(
+            "GET",
+            "/check-none/repos/someone/project/contents/package.json",
+        )
+        .with_body(r#"{"name":"project","license":"UNLICENSED"}"#)
+        .create();
+        let report =
+            check_repository_url("https://github.com/someone/project", &options("check-none"))
+                .await
+                .unwrap();
+        assert_eq!(report.verdict, Verdict::Unlicensed);
+        assert_eq!(report.license, None);
+        assert_eq!(report.method, None);
+    }
+
+    #[tokio::test]
+    async fn trails_every_fallback() {
+        use Verdict::{Indeterminate as I, Licensed as L, Unlicensed as U};
+        let step = |probe, verdict, detail: Option<&str>| {
+            let step = DetectionStep::new(probe, verdict);
+            match detail {
+                Some(detail) => step.with_detail(detail),
+                None => step,
+            }
+        };
+        // (license API's SPDX id, top-level files, Cargo.toml, package.json,
+        // trail, the fallback that overturned the license API)
+        type Case = (
+            Option<&'static str>,
+            Option<&'static [&'static str]>,
+            &'static str,
+            &'static str,
+            Vec<DetectionStep>,
+            Option<Probe>,
+        );
+        let cases: Vec<Case> = vec![
+            (
+                Some("MIT"),
+                Some(&[]),
+                "",
+                "",
+                vec![step(Probe::LicenseApi, L, Some("MIT"))],
+                None,
+            ),
+            (
+                None,
+                Some(&["README.md", "LICENSE-MIT"]),
+                "",
+                "",
+                vec![
+                    step(Probe::LicenseApi, U, None),
+                    step(Probe::ContentsScan, L, Some("LICENSE-MIT")),
+                ],
+                Some(Probe::ContentsScan),
+            ),
+            (
+                None,
+                Some(&["Cargo.toml", "package.json"]),
+                "[package]\nlicense = \"MIT\"\n",
+                "",
+                vec![
+                    step(Probe::LicenseApi, U, None),
+                    step(Probe::ContentsScan, U, None),
+                    step(Probe::CargoToml, L, Some("MIT")),
+                ],
+                Some(Probe::CargoToml),
+            ),
+            (
+                None,
+                Some(&["Cargo.toml", "package.json"]),
+                "[package]\nname = \"a\"\n",
+                r#"{"license":"ISC"}"#,
+                vec![
+                    step(Probe::LicenseApi, U, None),
+                    step(Probe::ContentsScan, U, None),
+                    step(Probe::CargoToml, U, None),
+                    step(Probe::PackageJson, L, Some("ISC")),
+                ],
+                Some(Probe::PackageJson),
+            ),
+            (
+                None,
+                Some(&["package.json"]),
+                "",
+                r#"{"license":"UNLICENSED"}"#,
+                vec![
+                    step(Probe::LicenseApi, U, None),
+                    step(Probe::ContentsScan, U, None),
+                    step(Probe::PackageJson, U, None),
+                ],
+                None,
+            ),
+            (
+                None,
+                Some(&["README.md"]),
+                "",
+                "",
+                vec![
+                    step(Probe::LicenseApi, U, None),
+                    step(Probe::ContentsScan, U, None),
+                ],
+                None,
+            ),
+            (
+                None,
+                None,
+                "",
+                "",
+                vec![
+                    step(Probe::LicenseApi, U, None),
+                    step(Probe::ContentsScan, I, Some("empty repository")),
+                ],
+                Some(Probe::ContentsScan),
+            ),
+        ];
+        for (i, (spdx_id, files, cargo, package, trail, overturned)) in cases.iter().enumerate() {
+            let prefix = format!("check-trail-{}", i);
+            let path = |rest: &str| format!("/{}/repos/someone/project{}", prefix, rest);
+            let _repo = mock("GET", path("").as_str())
+                .with_body(
+                    json!({
+                        "full_name": "someone/project",
+                        "html_url": "https://github.com/someone/project",
+                        "size": if files.is_some() { 1 } else { 0 },
+                    })
+                    .to_string(),
+                )
+                .create();
+            let _license = match spdx_id {
+                Some(id) => mock("GET", path("/license").as_str())
+                    .with_body(json!({ "license": { "spdx_id": id } }).to_string())
+                    .create(),
+                None => no_license_mock(&prefix, "someone/project"),
+            };
+            let _contents = match files {
+                Some(files) => contents_mock(&prefix, "someone/project", files),
+                None => mock("GET", path("/contents/").as_str())
+                    .with_status(409)
+                    .create(),
+            };
+            let _cargo = mock("GET", path("/contents/Cargo.toml").as_str())
+                .with_body(*cargo)
+                .create();
+            let _package = mock("GET", path("/contents/package.json").as_str())
+                .with_body(*package)
+                .create();
+            let report =
+                check_repository_url("https://github.com/someone/project", &options(&prefix))
+                    .await
+                    .unwrap();
+            assert_eq!(&report.detection_path, trail, "case {}", i);
+            assert_eq!(
+                overturned_by(&report.detection_path),
+                *overturned,
+                "case {}",
+                i
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn empty_repos_are_indeterminate() {
+        let _repo = mock("GET", "/check-empty/repos/someone/project")
+            .with_body(
+                r#"{"full_name":"someone/project","html_url":"https://github.com/someone/project","size":0}"#,
+            )
+            .create();
+        let _license = no_license_mock("check-empty", "someone/project");
+        let _contents = mock("GET", "/check-empty/repos/someone/project/contents/")
+            .with_status(409)
+            .with_body(r#"{"message":"This repository is empty.","status":"409"}"#)
+            .create();
+        let report = check_repository_url(
+            "https://github.com/someone/project",
+            &options("check-empty"),
+        )
+        .await
+        .unwrap();
+        assert_eq!(report.verdict, Verdict::Indeterminate);
+        assert_eq!(report.reason.as_deref(), Some("empty repository"));
+        assert!(report
+            .to_string()
+            .contains("verdict:    indeterminate (empty repository)"));
+        assert_eq!(
+            serde_json::to_value(&report).unwrap()["reason"],
+            json!("empty repository")
+        );
+    }
+
+    #[tokio::test]
+    async fn missing_repos_and_bad_urls_are_errors() {
+        let _repo = mock("GET", "/check-missing/repos/someone/gone")
+            .with_status(404)
+            .create();
+        let options = options("check-missing");
+        let e = check_repository_url("https://github.com/someone/gone", &options)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "Invalid GH project 'someone/gone' (got status 404 Not Found)"
+        );
+        assert!(
+            check_repository_url("https://gitlab.com/someone/project", &options)
+                .await
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn report_serialization() {
+        let report = CheckReport {
+            repo: "someone/project".to_owned(),
+            url: "https://github.com/someone/project".to_owned(),
+            verdict: Verdict::Licensed,
+            license: Some(DetectedLicense {
+                spdx_id: Some("MIT OR Apache-2.0".to_owned()),
+                name: None,
+            }),
+            method: Some(Method::Manifest),
+            reason: None,
+            detection_path: vec![],
+            history: vec![],
+        };
+        let value = serde_json::to_value(&report).unwrap();
+        assert_eq!(
+            value,
+            json!({
+                "repo": "someone/project",
+                "url": "https://github.com/someone/project",
+                "verdict": "licensed",
+                "license": { "spdx_id": "MIT OR Apache-2.0", "name": null },
+                "method": "manifest",
+            })
+        );
+        assert_eq!(
+            serde_json::from_value::<CheckReport>(value).unwrap(),
+            report
+        );
+        let unlicensed = CheckReport {
+            verdict: Verdict::Unlicensed,
+            license: None,
+            method: None,
+            ..report
+        };
+        assert_eq!(
+            serde_json::to_value(&unlicensed).unwrap()["verdict"],
+            json!("unlicensed")
+        );
+    }
+
+    #[test]
+    fn text_output() {
+        let report = CheckReport {
+            repo: "someone/project".to_owned(),
+            url: "https://github.com/someone/project".to_owned(),
+            verdict: Verdict::Licensed,
+            license: Some(DetectedLicense {
+                spdx_id: Some("MIT".to_owned()),
+                name: Some("MIT License".to_owned()),
+            }),
+            method: Some(Method::LicenseApi),
+            reason: None,
+            detection_path: vec![],
+            history: vec![],
+        };
+        assert_eq!(
+            report.to_string(),
+            "repository: someone/project (https://github.com/someone/project)\n\
+             verdict:    licensed\n\
+             license:    MIT (MIT License)\n\
+             found by:   GitHub's license API\n"
+        );
+        let overturned = CheckReport {
+            license: Some(DetectedLicense::default()),
+            method: Some(Method::ContentsScan),
+            detection_path: vec![
+                DetectionStep::new(Probe::LicenseApi, Verdict::Unlicensed),
+                DetectionStep::new(Probe::ContentsScan, Verdict::Licensed).with_detail("COPYING"),
+            ],
+            ..report
+        };
+        assert_eq!(
+            overturned.to_string(),
+            "repository: someone/project (https://github.com/someone/project)\n\
+             verdict:    licensed\n\
+             license:    not identified\n\
+             found by:   a license file in the repository\n\
+             checked:    license API: unlicensed\n\
+             \x20           contents scan: licensed (COPYING)\n"
+        );
+    }
+
+    #[test]
+    fn outcomes_map_to_exit_codes() {
+        let codes: Vec<i32> = [
+            Outcome::Licensed,
+            Outcome::Error,
+            Outcome::Unlicensed,
+            Outcome::NotFound,
+        ]
+        .iter()
+        .map(|o| o.exit_code())
+        .collect();
+        assert_eq!(codes, [0, 1, 2, 3]);
+
+        assert_eq!(Outcome::worst([]), Outcome::Licensed);
+        assert_eq!(
+            Outcome::worst([Outcome::Licensed, Outcome::Unlicensed]),
+            Outcome::Unlicensed
+        );
+        assert_eq!(
+            Outcome::worst([Outcome::NotFound, Outcome::Unlicensed, Outcome::Licensed]),
+            Outcome::NotFound
+        );
+        assert_eq!(
+            Outcome::worst([Outcome::NotFound, Outcome::Error, Outcome::Unlicensed]),
+            Outcome::Error
+        );
+    }
+
+    #[test]
+    fn reads_urls_one_to_a_line() {
+        let input = "https://github.com/a/b\n\n  # mine\n  https://github.com/c/d  \n";
+        assert_eq!(
+            read_urls(input.as_bytes()).unwrap(),
+            ["https://github.com/a/b", "https://github.com/c/d"]
+        );
+        assert!(read_urls(&b""[..]).unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn checks_many_urls_in_order() {
+        let _licensed = repo_mock("check-batch", "someone/licensed");
+        let _license = mock("GET", "/check-batch/repos/someone/licensed/license")
+            .with_body(r#"{"license":{"spdx_id":"MIT","name":"MIT License"}}"#)
+            .create();
+        let _unlicensed = repo_mock("check-batch", "someone/unlicensed");
+        let _no_license = no_license_mock("check-batch", "someone/unlicensed");
+        let _contents = contents_mock("check-batch", "someone/unlicensed", &["README.md"]);
+        let _gone = mock("GET", "/check-batch/repos/someone/gone")
+            .with_status(404)
+            .create();
+        let _broken = mock("GET", "/check-batch/repos/someone/broken")
+            .with_status(500)
+            .create();
+        let input = "https://github.com/someone/licensed\n\
+                     https://github.com/someone/unlicensed\n\
+                     https://github.com/someone/gone\n\
+                     https://github.com/someone/broken\n\
+                     https://gitlab.com/someone/elsewhere\n";
+        let urls = read_urls(input.as_bytes()).unwrap();
+        let results = check_urls(&urls, &options("check-batch"), 2).await;
+
+        let outcomes: Vec<Outcome> = results.iter().map(|r| r.outcome).collect();
+        assert_eq!(
+            outcomes,
+            [
+                Outcome::Licensed,
+                Outcome::Unlicensed,
+                Outcome::NotFound,
+                Outcome::Error,
+                Outcome::Error
+            ]
+        );
+        let given: Vec<&str> = results.iter().map(|r| r.url.as_str()).collect();
+        assert_eq!(given, urls);
+        assert_eq!(Outcome::worst(outcomes).exit_code(), 1);
+        assert_eq!(
+            results[2].error.as_deref(),
+            Some("Invalid GH project 'someone/gone' (got status 404 Not Found)")
+        );
+
+        assert_eq!(
+            table(&results[..3]),
+            "URL                                    RESULT      DETAIL\n\
+             https://github.com/someone/licensed    licensed    MIT\n\
+             https://github.com/someone/unlicensed  unlicensed\n\
+             https://github.com/someone/gone        not found   \
+             Invalid GH project 'someone/gone' (got status 404 Not Found)\n"
+        );
+    }
+
+    #[test]
+    fn result_serialization() {
+        let report = CheckReport {
+            repo: "someone/project".to_owned(),
+            url: "https://github.com/someone/project".to_owned(),
+            verdict: Verdict::Unlicensed,
+            license: None,
+            method: None,
+            reason: None,
+            detection_path: vec![],
+            history: vec![],
+        };
+        let results = [
+            CheckResult {
+                url: "https://github.com/someone/project".to_owned(),
+                outcome: Outcome::Unlicensed,
+                report: Some(report),
+                error: None,
+            },
+            CheckResult {
+                url: "https://github.com/someone/gone".to_owned(),
+                outcome: Outcome::NotFound,
+                report: None,
+                error: Some("not found".to_owned()),
+            },
+        ];
+        assert_eq!(
+            serde_json::to_value(results).unwrap(),
+            json!([
+                {
+                    "url": "https://github.com/someone/project",
+                    "outcome": "unlicensed",
+                    "report": {
+                        "repo": "someone/project",
+                        "url": "https://github.com/someone/project",
+                        "verdict": "unlicensed",
+                        "license": null,
+                        "method": null,
+                    },
+                    "error": null,
+                },
+                {
+                    "url": "https://github.com/someone/gone",
+                    "outcome": "not_found",
+                    "report": null,
+                    "error": "not found",
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn reports_include_the_bots_history_of_known_repositories() {
+        let tmp = tempfile::tempdir().unwrap();
+        let mut history = RepoHistory::default();
+        let check = |at, verdict| CheckRecord {
+            at,
+            verdict,
+            license: None,
+            detection_path: vec![],
+        };
+        history.append("Someone/Project", check(1, Verdict::Unlicensed));
+        history.append("Someone/Project", check(2, Verdict::Licensed));
+        history.append("someone/old-name", check(3, Verdict::Unlicensed));
+        history.save(tmp.path()).unwrap();
+
+        let report = |repo: &str| CheckReport {
+            repo: repo.to_owned(),
+            url: format!("https://github.com/{}", repo),
+            verdict: Verdict::Licensed,
+            license: None,
+            method: None,
+            reason: None,
+            detection_path: vec![],
+            history: vec![],
+        };
+        let result = |url: &str, report| CheckResult {
+            url: url.to_owned(),
+            outcome: Outcome::Licensed,
+            report,
+            error: None,
+        };
+        let mut results = vec![
+            result(
+                "https://github.com/someone/project",
+                Some(report("someone/project")),
+            ),
+            // renamed since the bot checked it
+            result(
+                "https://github.com/someone/old-name",
+                Some(report("someone/new-name")),
+            ),
+            result(
+                "https://github.com/someone/unknown",
+                Some(report("someone/unknown")),
+            ),
+            result("https://github.com/someone/gone", None),
+        ];
+        add_history(&mut results, tmp.path());
+        let history: Vec<Vec<u64>> = results
+            .iter()
+            .filter_map(|r| r.report.as_ref())
+            .map(|r| r.history.iter().map(|c| c.at).collect())
+            .collect();
+        assert_eq!(history, vec![vec![1, 2], vec![3], vec![]]);
+        let json = serde_json::to_value(&results[0]).unwrap();
+        assert_eq!(json["report"]["history"][1]["verdict"], "licensed");
+        assert!(serde_json::to_value(&results[2]).unwrap()["report"]
+            .get("history")
+            .is_none());
+    }
+
+    #[test]
+    fn manifests() {
+        assert_eq!(
+            cargo_toml_license("[package]\nname = \"a\"\nlicense = \"MIT\"\n").as_deref(),
+            Some("MIT")
+        );
+        // only the package's own license counts
+        assert_eq!(
+            cargo_toml_license("[package]\nname = \"a\"\n[dependencies]\nlicense = \"1\"\n"),
+            None
+        );
+        assert_eq!(
+            package_json_license(r#"{"license":"ISC"}"#).as_deref(),
+            Some("ISC")
+        );
+        assert_eq!(package_json_license(r#"{"name":"a"}"#), None);
+        assert_eq!(package_json_license("not json"), None);
+    }
+
+    #[test]
+    fn license_files() {
+        for name in &[
+            "LICENSE",
+            "license.md",
+            "LICENCE-MIT",
+            "COPYING",
+            "UNLICENSE",
+        ] {
+            assert!(is_license_file(name), "{}", name);
+        }
+        for name in &["README.md", "Cargo.toml", "licensing-notes.txt"] {
+            assert!(!is_license_file(name), "{}", name);
+        }
+    }
+}
@@ -0,0 +1,311 @@
+use anyhow::{Context, Result};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{fs, path::Path, sync::Mutex};
+
+use crate::paths::{claim_file, repo_claim_file};
+use crate::reddit::BASE_URL;
+use crate::util::{author_key, fold_case};
+
+/// Seconds after which a claim that was neither confirmed nor released is
+/// looked into, as the bot may have lost track of whether its reply was made.
+pub const CLAIM_TIMEOUT: u64 = 10 * 60;
+
+/// Seconds after which Reddit archives a post, so it can't be replied to
+/// and its claim can be forgotten.
+pub const ARCHIVED_AFTER: u64 = 180 * 24 * 60 * 60;
+
+/// Serializes changes to claim files, so that two stores on the same state
+/// directory can't both claim a post.
+static LOCK: Mutex<()> = Mutex::new(());
+
+/// A post the bot is replying to, or has replied to.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct ReplyClaim {
+    pub fullname: String,
+    /// Unix timestamp of the claim.
+    pub at: u64,
+    /// Whether the reply is known to have been made.
+    #[serde(default)]
+    pub replied: bool,
+    /// Fullname of the bot's comment, if Reddit said what it was.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+}
+
+/// A repository claimed for its author by the first of their posts of it to
+/// be replied about, so that their posts of it in other subreddits aren't
+/// replied about again.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct RepoClaim {
+    pub author: String,
+    /// The repository, as `org/repo`.
+    pub repo: String,
+    /// Fullname of the post that claimed the repository.
+    pub fullname: String,
+    pub subreddit: String,
+    /// Permalink of the post, if it's known.
+    pub permalink: String,
+    /// Unix timestamp of the claim.
+    pub at: u64,
+}
+
+impl RepoClaim {
+    /// What claims are made under: the author and repository, whatever
+    /// their case.
+    pub fn key(&self) -> String {
+        format!("{}/{}", author_key(&self.author), fold_case(&self.repo))
+    }
+
+    /// Where the reply about the repository is: the bot's `comment` on the
+    /// post, if it's known, or else the post.
+    pub fn link(&self, comment: Option<&str>) -> String {
+        let post = match self.permalink.trim_end_matches('/') {
+            "" => format!(
+                "{}/comments/{}/_",
+                BASE_URL,
+                self.fullname.trim_start_matches("t3_")
+            ),
+            permalink => permalink.to_owned(),
+        };
+        match comment {
+            Some(comment) => format!("{}/{}/", post, comment.trim_start_matches("t1_")),
+            None => format!("{}/", post),
+        }
+    }
+
+    /// Whether the claim was made by a post in another subreddit than `subreddit`.
+    pub fn is_elsewhere(&self, subreddit: &str) -> bool {
+        !self.subreddit.eq_ignore_ascii_case(subreddit)
+    }
+}
+
+/// Claim a repository for a post, unless another post of the same author
+/// claimed it less than `window` seconds before, returning that post's claim.
+///
+/// Claims older than the window are forgotten.
+pub fn claim_repo(
+    claims: &mut Vec<RepoClaim>,
+    claim: &RepoClaim,
+    window: u64,
+) -> Option<RepoClaim> {
+    claims.retain(|c| claim.at.saturating_sub(c.at) < window);
+    let key = claim.key();
+    if let Some(held) = claims.iter().find(|c| c.key() == key) {
+        return (held.fullname != claim.fullname).then(|| held.clone());
+    }
+    claims.push(claim.clone());
+    None
+}
+
+/// Claim a post at `at`, returning whether it was unclaimed.
+///
+/// Claims on posts that must have been archived by then are forgotten.
+pub fn claim(claims: &mut Vec<ReplyClaim>, fullname: &str, at: u64) -> bool {
+    claims.retain(|c| !c.replied || at.saturating_sub(c.at) < ARCHIVED_AFTER);
+    if claims.iter().any(|c| c.fullname == fullname) {
+        return false;
+    }
+    claims.push(ReplyClaim {
+        fullname: fullname.to_owned(),
+        at,
+        replied: false,
+        comment: None,
+    });
+    true
+}
+
+/// Mark the reply to a claimed post as made.
+pub fn confirm(claims: &mut [ReplyClaim], fullname: &str, comment: Option<&str>) {
+    for claim in claims.iter_mut().filter(|c| c.fullname == fullname) {
+        claim.replied = true;
+        claim.comment = comment.map(str::to_owned).or_else(|| claim.comment.take());
+    }
+}
+
+/// Give up the claim on a post whose reply wasn't made, unless it was.
+pub fn release(claims: &mut Vec<ReplyClaim>, fullname: &str) {
+    claims.retain(|c| c.fullname != fullname || c.replied);
+}
+
+/// Claims made at least `age` seconds before `now` that were neither
+/// confirmed nor released.
+pub fn unresolved(claims: &[ReplyClaim], now: u64, age: u64) -> Vec<&ReplyClaim> {
+    claims
+        .iter()
+        .filter(|c| !c.replied && now.saturating_sub(c.at) >= age)
+        .collect()
+}
+
+fn read<T: DeserializeOwned>(path: &Path) -> Result<Vec<T>> {
+    if !path.exists() {
+        return Ok(vec![]);
+    }
+    let data = fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+    serde_json::from_str(&data).with_context(|| format!("parsing {}", path.display()))
+}
+
+fn write<T: Serialize>(path: &Path, claims: &[T]) -> Result<()> {
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, serde_json::to_string(claims)?)
+        .and_then(|_| fs::rename(&tmp, path))
+        .with_context(|| format!("writing {}", path.display()))
+}
+
+/// Load the claims from the state directory.
+pub fn load(dir: &Path) -> Result<Vec<ReplyClaim>> {
+    read(&claim_file(dir))
+}
+
+/// Save the claims to the state directory, replacing the file atomically.
+pub fn save(dir: &Path, claims: &[ReplyClaim]) -> Result<()> {
+    write(&claim_file(dir), claims)
+}
+
+/// Load the claims in the state directory, change them and save them again,
+/// with no other change in between.
+pub fn update<T>(dir: &Path, change: impl FnOnce(&mut Vec<ReplyClaim>) -> T) -> Result<T> {
+    let _guard = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut claims = load(dir)?;
+    let result = change(&mut claims);
+    save(dir, &claims)?;
+    Ok(result)
+}
+
+/// Load the repository claims in the state directory, change them and save
+/// them again, with no other change in between.
+pub fn update_repos<T>(dir: &Path, change: impl FnOnce(&mut Vec<RepoClaim>) -> T) -> Result<T> {
+    let _guard = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let path = repo_claim_file(dir);
+    let mut claims = read(&path)?;
+    let result = change(&mut claims);
+    write(&path, &claims)?;
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        claim, claim_repo, confirm, load, release, unresolved, update, update_repos, RepoClaim,
+        ARCHIVED_AFTER,
+    };
+    use std::{path::PathBuf, sync::Arc, thread};
+
+    #[test]
+    fn claims_each_post_once() {
+        let mut claims = vec![];
+        assert!(claim(&mut claims, "t3_a", 1));
+        assert!(!claim(&mut claims, "t3_a", 2));
+        assert!(claim(&mut claims, "t3_b", 2));
+
+        // a failed reply frees the post up again, but a made one never does
+        release(&mut claims, "t3_a");
+        assert!(claim(&mut claims, "t3_a", 3));
+        confirm(&mut claims, "t3_b", Some("t1_b"));
+        release(&mut claims, "t3_b");
+        assert!(!claim(&mut claims, "t3_b", 4));
+        assert_eq!(claims[0].comment.as_deref(), Some("t1_b"));
+
+        // until the post is archived
+        assert!(claim(&mut claims, "t3_c", 4 + ARCHIVED_AFTER));
+        assert!(claim(&mut claims, "t3_b", 4 + ARCHIVED_AFTER));
+    }
+
+    #[test]
+    fn claims_repos_per_author_across_subreddits() {
+        let post = |fullname: &str, author: &str, repo: &str, subreddit: &str, at| RepoClaim {
+            author: author.to_owned(),
+            repo: repo.to_owned(),
+            fullname: fullname.to_owned(),
+            subreddit: subreddit.to_owned(),
+            permalink: format!(
+                "https://www.reddit.com/r/{}/comments/{}/x/",
+                subreddit, fullname
+            ),
+            at,
+        };
+        let mut claims = vec![];
+        let first = post("t3_a", "op", "someone/project", "rust", 100);
+        assert_eq!(claim_repo(&mut claims, &first, 3600), None);
+        // claiming again from the same post changes nothing
+        assert_eq!(claim_repo(&mut claims, &first, 3600), None);
+
+        let cross = post("t3_b", "OP", "Someone/Project", "programming", 200);
+        let held = claim_repo(&mut claims, &cross, 3600).unwrap();
+        assert_eq!(held, first);
+        assert!(held.is_elsewhere("programming"));
+        assert!(!held.is_elsewhere("Rust"));
+
+        // other authors and other repositories are claimed separately
+        let other = post(
+            "t3_c",
+            "someone_else",
+            "someone/project",
+            "programming",
+            200,
+        );
+        assert_eq!(claim_repo(&mut claims, &other, 3600), None);
+        let another = post("t3_d", "op", "someone/other", "programming", 200);
+        assert_eq!(claim_repo(&mut claims, &another, 3600), None);
+
+        // until the window is over
+        let later = post("t3_e", "op", "someone/project", "coolgithubprojects", 3800);
+        assert_eq!(claim_repo(&mut claims, &later, 3600), None);
+        assert_eq!(claims.len(), 1);
+        assert_eq!(claims[0].fullname, "t3_e");
+
+        assert_eq!(
+            first.link(Some("t1_reply")),
+            "https://www.reddit.com/r/rust/comments/t3_a/x/reply/"
+        );
+        let unknown = RepoClaim {
+            permalink: String::new(),
+            ..first.clone()
+        };
+        assert_eq!(unknown.link(None), "https://www.reddit.com/comments/a/_/");
+        assert_eq!(
+            unknown.link(Some("t1_reply")),
+            "https://www.reddit.com/comments/a/_/reply/"
+        );
+
+        let tmp = tempfile::tempdir().unwrap();
+        let in_dir = |c: &RepoClaim| update_repos(tmp.path(), |cs| claim_repo(cs, c, 3600));
+        assert_eq!(in_dir(&first).unwrap(), None);
+        assert_eq!(in_dir(&cross).unwrap(), Some(first));
+    }
+
+    #[test]
+    fn finds_claims_left_unresolved() {
+        let mut claims = vec![];
+        claim(&mut claims, "t3_old", 100);
+        claim(&mut claims, "t3_new", 190);
+        claim(&mut claims, "t3_done", 100);
+        confirm(&mut claims, "t3_done", None);
+        let names = |found: Vec<&super::ReplyClaim>| -> Vec<String> {
+            found.iter().map(|c| c.fullname.clone()).collect()
+        };
+        assert_eq!(names(unresolved(&claims, 200, 50)), vec!["t3_old"]);
+        assert_eq!(names(unresolved(&claims, 200, 0)), vec!["t3_old", "t3_new"]);
+    }
+
+    #[test]
+    fn racing_claims_on_one_directory_have_one_winner() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir: Arc<PathBuf> = Arc::new(tmp.path().to_owned());
+        let racers: Vec<_> = (0..4)
+            .map(|_| {
+                let dir = dir.clone();
+                thread::spawn(move || {
+                    (0..20)
+                        .filter(|i| {
+                            let fullname = format!("t3_{}", i);
+                            update(&dir, |claims| claim(claims, &fullname, 1)).unwrap()
+                        })
+                        .count()
+                })
+            })
+            .collect();
+        let won: usize = racers.into_iter().map(|r| r.join().unwrap()).sum();
+        assert_eq!(won, 20);
+        assert_eq!(load(&dir).unwrap().len(), 20);
+    }
+}
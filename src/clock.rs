@@ -0,0 +1,95 @@
+use std::{
+    fmt::Debug,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+/// Where the bot gets the time from, so that tests can control it.
+pub trait Clock: Debug + Send + Sync {
+    /// The time, for measuring how long it's been since something happened.
+    fn now(&self) -> Instant;
+
+    /// The time of day, for schedules and timestamps.
+    fn system_time(&self) -> SystemTime;
+
+    /// The time of day in seconds since the Unix epoch.
+    fn unix_now(&self) -> u64 {
+        self.system_time()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default()
+    }
+}
+
+/// The real time.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn system_time(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// A clock that only moves when it's told to.
+///
+/// Clones share the same time, so a test can keep one to move the time of
+/// the clock it gave to a `Bot`.
+#[derive(Clone, Debug)]
+pub struct ManualClock {
+    start: Instant,
+    start_time: SystemTime,
+    elapsed: Arc<Mutex<Duration>>,
+}
+
+impl ManualClock {
+    /// A clock stopped at the given time of day.
+    pub fn new(at: SystemTime) -> Self {
+        Self {
+            start: Instant::now(),
+            start_time: at,
+            elapsed: Arc::new(Mutex::new(Duration::default())),
+        }
+    }
+
+    /// Move the time forward.
+    pub fn advance(&self, by: Duration) {
+        *self.elapsed.lock().unwrap() += by;
+    }
+
+    fn elapsed(&self) -> Duration {
+        *self.elapsed.lock().unwrap()
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        self.start + self.elapsed()
+    }
+
+    fn system_time(&self) -> SystemTime {
+        self.start_time + self.elapsed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Clock, ManualClock};
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn manual_clocks_move_together() {
+        let clock = ManualClock::new(UNIX_EPOCH + Duration::from_secs(1_600_000_000));
+        let copy = clock.clone();
+        let before = copy.now();
+        assert_eq!(copy.unix_now(), 1_600_000_000);
+
+        clock.advance(Duration::from_secs(90));
+        assert_eq!(copy.now() - before, Duration::from_secs(90));
+        assert_eq!(copy.unix_now(), 1_600_000_090);
+    }
+}
@@ -0,0 +1,133 @@
+use serde::Serialize;
+use std::{collections::HashMap, fmt, mem::size_of};
+
+/// Spare room a collection must have, in entries, before it's shrunk.
+const MIN_SLACK: usize = 1024;
+
+/// What compacting the bot's in-memory state has done since it started.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+pub struct Compactions {
+    pub runs: u64,
+    /// Entries dropped from memory.
+    pub evicted: u64,
+    /// Estimated bytes given back.
+    pub reclaimed_bytes: u64,
+}
+
+impl Compactions {
+    pub fn record(&mut self, evicted: usize, reclaimed_bytes: usize) {
+        self.runs += 1;
+        self.evicted += evicted as u64;
+        self.reclaimed_bytes += reclaimed_bytes as u64;
+    }
+}
+
+impl fmt::Display for Compactions {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} compactions, {} entries evicted, about {} KiB reclaimed",
+            self.runs,
+            self.evicted,
+            self.reclaimed_bytes / 1024
+        )
+    }
+}
+
+/// Counts passes of the watch loop to tell when to compact.
+#[derive(Debug, Default)]
+pub struct Schedule {
+    passes: u64,
+}
+
+impl Schedule {
+    /// Count a pass, returning whether it's the `every`th since the last
+    /// compaction. An `every` of 0 turns compaction off.
+    pub fn tick(&mut self, every: u64) -> bool {
+        if every == 0 {
+            return false;
+        }
+        self.passes += 1;
+        if self.passes < every {
+            return false;
+        }
+        self.passes = 0;
+        true
+    }
+}
+
+/// Whether a collection has so much more room than it uses, after entries
+/// were evicted from it, that it's worth shrinking.
+pub fn worth_shrinking(len: usize, capacity: usize) -> bool {
+    capacity - len >= len.max(MIN_SLACK)
+}
+
+/// Estimated bytes a map takes up on the heap, given what its keys and
+/// values hold beyond their own size.
+pub fn map_size<K, V>(map: &HashMap<K, V>, held: impl Fn(&K, &V) -> usize) -> usize {
+    map.capacity() * size_of::<(K, V)>() + map.iter().map(|(k, v)| held(k, v)).sum::<usize>()
+}
+
+/// Estimated bytes a vector takes up on the heap, given what its entries
+/// hold beyond their own size.
+pub fn vec_size<T>(vec: &[T], capacity: usize, held: impl Fn(&T) -> usize) -> usize {
+    capacity * size_of::<T>() + vec.iter().map(held).sum::<usize>()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{map_size, vec_size, worth_shrinking, Compactions, Schedule, MIN_SLACK};
+    use std::{collections::HashMap, mem::size_of};
+
+    #[test]
+    fn compacts_every_nth_pass() {
+        let mut schedule = Schedule::default();
+        let due: Vec<bool> = (0..7).map(|_| schedule.tick(3)).collect();
+        assert_eq!(due, vec![false, false, true, false, false, true, false]);
+        assert!((0..10).all(|_| !schedule.tick(0)));
+        assert!(schedule.tick(1));
+    }
+
+    #[test]
+    fn only_shrinks_after_large_evictions() {
+        assert!(!worth_shrinking(0, 0));
+        assert!(!worth_shrinking(10, MIN_SLACK));
+        assert!(worth_shrinking(10, MIN_SLACK + 10));
+        assert!(!worth_shrinking(100_000, 150_000));
+        assert!(worth_shrinking(100_000, 200_000));
+    }
+
+    #[test]
+    fn estimates_sizes() {
+        let names: Vec<String> = (0..1000).map(|i| format!("t3_{:06}", i)).collect();
+        let held: usize = names.iter().map(|n| n.capacity()).sum();
+        assert_eq!(
+            vec_size(&names, names.capacity(), |n| n.capacity()),
+            names.capacity() * size_of::<String>() + held
+        );
+        assert!(held >= 9000);
+
+        let map: HashMap<String, u64> = names.into_iter().map(|n| (n, 0)).collect();
+        let size = map_size(&map, |k, _| k.capacity());
+        assert!(size >= 1000 * size_of::<(String, u64)>() + held);
+    }
+
+    #[test]
+    fn adds_up_compactions() {
+        let mut compactions = Compactions::default();
+        compactions.record(10, 2048);
+        compactions.record(0, 1024);
+        assert_eq!(
+            compactions,
+            Compactions {
+                runs: 2,
+                evicted: 10,
+                reclaimed_bytes: 3072
+            }
+        );
+        assert_eq!(
+            compactions.to_string(),
+            "2 compactions, 10 entries evicted, about 3 KiB reclaimed"
+        );
+    }
+}
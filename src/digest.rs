@@ -0,0 +1,323 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashSet,
+    fs,
+    path::Path,
+    time::{Duration, UNIX_EPOCH},
+};
+
+use crate::events::Event;
+use crate::models::Author;
+use crate::paths::digest_file;
+use crate::reply::MAX_COMMENT_LEN;
+use crate::util::fold_case;
+
+pub const WEEK: u64 = 7 * 24 * 60 * 60;
+
+/// A post that linked an unlicensed repository during the week.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Finding {
+    pub fullname: String,
+    pub title: String,
+    pub permalink: String,
+    pub author: String,
+    /// The repository, as `org/repo`.
+    pub repo: String,
+}
+
+/// A line of the digest: a finding, and whether the repository still has no
+/// license, if that could be checked.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Row {
+    pub finding: Finding,
+    pub still_unlicensed: Option<bool>,
+}
+
+/// When the last digest was posted, kept in the state directory so that a
+/// restart doesn't post it again.
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct DigestState {
+    /// Unix timestamp of the scheduled time of the last digest.
+    last: u64,
+}
+
+/// Scheduled time of the last digest that was posted, or 0 if none has been.
+pub fn last_posted(dir: &Path) -> Result<u64> {
+    let path = digest_file(dir);
+    if !path.exists() {
+        return Ok(0);
+    }
+    let data = fs::read_to_string(&path).with_context(|| format!("reading {}", path.display()))?;
+    let state: DigestState =
+        serde_json::from_str(&data).with_context(|| format!("loading {}", path.display()))?;
+    Ok(state.last)
+}
+
+/// Remember that the digest scheduled at `at` has been posted.
+pub fn mark_posted(dir: &Path, at: u64) -> Result<()> {
+    let path = digest_file(dir);
+    fs::write(&path, serde_json::to_string(&DigestState { last: at })?)
+        .with_context(|| format!("writing {}", path.display()))
+}
+
+/// The posts found linking unlicensed repositories between `from` and `to`,
+/// each post and repository once, oldest first.
+pub fn aggregate(events: &[Event], from: u64, to: u64) -> Vec<Finding> {
+    let mut seen = HashSet::new();
+    events
+        .iter()
+        .filter(|e| e.verdict == "unlicensed" && from <= e.at && e.at < to)
+        .filter(|e| seen.insert((e.fullname.clone(), fold_case(&e.repo))))
+        .map(|e| Finding {
+            fullname: e.fullname.clone(),
+            title: e.title.clone(),
+            permalink: e.permalink.clone(),
+            author: e.author.clone(),
+            repo: e.repo.clone(),
+        })
+        .collect()
+}
+
+/// Make text safe to put in a cell of a markdown table.
+fn cell(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('|', "\\|")
+        .replace('[', "\\[")
+        .replace(']', "\\]")
+        .replace('\n', " ")
+}
+
+fn row(row: &Row) -> String {
+    let finding = &row.finding;
+    let post = if finding.permalink.is_empty() {
+        cell(&finding.title)
+    } else {
+        format!("[{}]({})", cell(&finding.title), finding.permalink)
+    };
+    let author = match Author::parse(&finding.author) {
+        Author::User(name) if name.is_empty() => "-".to_owned(),
+        Author::User(name) => format!("/u/{}", name),
+        // not a link, as there's no user to link to
+        Author::Deleted => "[deleted]".to_owned(),
+    };
+    let still = match row.still_unlicensed {
+        Some(true) => "yes",
+        Some(false) => "no",
+        None => "unknown",
+    };
+    format!(
+        "| {} | {} | [{}](https://github.com/{}) | {} |\n",
+        post, author, finding.repo, finding.repo, still
+    )
+}
+
+fn date(at: u64) -> String {
+    let stamp = humantime::format_rfc3339_seconds(UNIX_EPOCH + Duration::from_secs(at));
+    stamp.to_string()[..10].to_owned()
+}
+
+/// Render the digest as a markdown table, leaving out rows that would make it
+/// longer than `max_len` characters and saying how many were left out.
+pub fn render(rows: &[Row], from: u64, to: u64, max_len: usize) -> String {
+    let posts: HashSet<&str> = rows.iter().map(|r| r.finding.fullname.as_str()).collect();
+    let mut text = format!(
+        "**License digest for {} to {}**\n\n\
+         {} {} linked repositories without a license.\n\n\
+         | Post | Author | Repository | Still unlicensed |\n\
+         |---|---|---|---|\n",
+        date(from),
+        date(to),
+        posts.len(),
+        if posts.len() == 1 { "post" } else { "posts" },
+    );
+    let mut len = text.chars().count();
+    for (i, r) in rows.iter().enumerate() {
+        let line = row(r);
+        let omitted = rows.len() - i - 1;
+        // leave room to say how many rows were left out, should any have to be
+        let note = if omitted > 0 {
+            omitted_note(omitted).chars().count()
+        } else {
+            0
+        };
+        let line_len = line.chars().count();
+        if len + line_len + note > max_len {
+            text.push_str(&omitted_note(rows.len() - i));
+            return text;
+        }
+        text.push_str(&line);
+        len += line_len;
+    }
+    text
+}
+
+fn omitted_note(count: usize) -> String {
+    format!(
+        "\n_...and {} more {} left out to fit._\n",
+        count,
+        if count == 1 { "row" } else { "rows" }
+    )
+}
+
+/// Render the digest to fit in a comment.
+pub fn render_comment(rows: &[Row], from: u64, to: u64) -> String {
+    render(rows, from, to, MAX_COMMENT_LEN)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{aggregate, last_posted, mark_posted, render, Finding, Row, WEEK};
+    use crate::events::Event;
+
+    const START: u64 = 1_600_000_000;
+
+    fn event(at: u64, fullname: &str, repo: &str, verdict: &str) -> Event {
+        Event {
+            at,
+            fullname: fullname.to_owned(),
+            title: format!("Post {}", fullname),
+            subreddit: "rust".to_owned(),
+            permalink: format!("https://www.reddit.com/r/rust/comments/{}/", fullname),
+            author: "someone".to_owned(),
+            repo: repo.to_owned(),
+            verdict: verdict.to_owned(),
+            license: None,
+            action: "replied".to_owned(),
+            removed_license: None,
+            detection_path: vec![],
+        }
+    }
+
+    fn finding(n: usize) -> Finding {
+        Finding {
+            fullname: format!("t3_{}", n),
+            title: format!("Post {}", n),
+            permalink: format!("https://www.reddit.com/r/rust/comments/{}/", n),
+            author: "someone".to_owned(),
+            repo: format!("someone/project{}", n),
+        }
+    }
+
+    #[test]
+    fn aggregates_the_weeks_unlicensed_findings() {
+        let events = vec![
+            event(START - 1, "t3_before", "a/one", "unlicensed"),
+            event(START, "t3_a", "a/one", "unlicensed"),
+            event(START + 10, "t3_a", "A/One", "unlicensed"),
+            event(START + 20, "t3_a", "a/two", "unlicensed"),
+            event(START + 30, "t3_b", "b/licensed", "licensed"),
+            event(START + 40, "t3_c", "a/one", "unlicensed"),
+            event(START + WEEK, "t3_after", "a/one", "unlicensed"),
+        ];
+        let found: Vec<(String, String)> = aggregate(&events, START, START + WEEK)
+            .into_iter()
+            .map(|f| (f.fullname, f.repo))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("t3_a".to_owned(), "a/one".to_owned()),
+                ("t3_a".to_owned(), "a/two".to_owned()),
+                ("t3_c".to_owned(), "a/one".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn renders_a_table() {
+        let mut tricky = finding(2);
+        tricky.title = "A [weird] | title".to_owned();
+        tricky.author = String::new();
+        let rows = vec![
+            Row {
+                finding: finding(1),
+                still_unlicensed: Some(true),
+            },
+            Row {
+                finding: tricky,
+                still_unlicensed: None,
+            },
+        ];
+        assert_eq!(
+            render(&rows, START, START + WEEK, 10_000),
+            "**License digest for 2020-09-13 to 2020-09-20**\n\n\
+             2 posts linked repositories without a license.\n\n\
+             | Post | Author | Repository | Still unlicensed |\n\
+             |---|---|---|---|\n\
+             | [Post 1](https://www.reddit.com/r/rust/comments/1/) | /u/someone | \
+             [someone/project1](https://github.com/someone/project1) | yes |\n\
+             | [A \\[weird\\] \\| title](https://www.reddit.com/r/rust/comments/2/) | - | \
+             [someone/project2](https://github.com/someone/project2) | unknown |\n"
+        );
+    }
+
+    #[test]
+    fn renders_non_ascii_titles_and_authors() {
+        let mut post = finding(1);
+        post.title = "Мой проект 🎉 [v2]".to_owned();
+        post.author = "Вася".to_owned();
+        let rows = vec![Row {
+            finding: post,
+            still_unlicensed: Some(true),
+        }];
+        let text = render(&rows, START, START + WEEK, 10_000);
+        assert!(
+            text.contains(
+                "| [Мой проект 🎉 \\[v2\\]](https://www.reddit.com/r/rust/comments/1/) | /u/Вася |"
+            ),
+            "{}",
+            text
+        );
+    }
+
+    #[test]
+    fn deleted_authors_are_not_linked() {
+        let mut gone = finding(1);
+        gone.author = "[deleted]".to_owned();
+        let rows = vec![Row {
+            finding: gone,
+            still_unlicensed: Some(true),
+        }];
+        let text = render(&rows, START, START + WEEK, 10_000);
+        assert!(text.contains("| [deleted] |"), "{}", text);
+        assert!(!text.contains("/u/"), "{}", text);
+    }
+
+    #[test]
+    fn truncates_to_fit() {
+        let rows: Vec<Row> = (0..200)
+            .map(|n| Row {
+                finding: finding(n),
+                still_unlicensed: Some(n % 2 == 0),
+            })
+            .collect();
+        let text = render(&rows, START, START + WEEK, 2_000);
+        assert!(text.chars().count() <= 2_000, "{}", text.len());
+        let shown = text.matches("| /u/someone |").count();
+        assert!(shown > 0);
+        assert!(
+            text.ends_with(&format!(
+                "_...and {} more rows left out to fit._\n",
+                200 - shown
+            )),
+            "{}",
+            text
+        );
+        assert!(text.contains("200 posts linked"));
+
+        // everything fits exactly, with no note
+        let all = render(&rows[..3], START, START + WEEK, 10_000);
+        let exact = render(&rows[..3], START, START + WEEK, all.chars().count());
+        assert_eq!(exact, all);
+        assert!(!exact.contains("left out"));
+    }
+
+    #[test]
+    fn remembers_the_last_digest() {
+        let tmp = tempfile::tempdir().unwrap();
+        assert_eq!(last_posted(tmp.path()).unwrap(), 0);
+        mark_posted(tmp.path(), START).unwrap();
+        assert_eq!(last_posted(tmp.path()).unwrap(), START);
+    }
+}
@@ -0,0 +1,633 @@
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use serde_json::json;
+use std::{
+    convert::TryFrom,
+    fmt::Write,
+    fs,
+    future::Future,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+use tokio::time::timeout;
+
+use crate::bot::{Bot, Endpoints};
+use crate::models::{Config, RedditAccess, Severity};
+use crate::pending::RepoLink;
+use crate::preview::RepoSummary;
+use crate::reply;
+use crate::report::Reporter;
+use crate::templates;
+use crate::util::GhRepo;
+
+/// Longest any one check may take.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(15);
+/// OAuth scopes that the bot's requests need.
+const REQUIRED_SCOPES: &[&str] = &["history", "read", "submit"];
+/// Checks that can't be made without a config.
+const NEED_CONFIG: &[(&str, bool)] = &[
+    ("state_dir", true),
+    ("templates", true),
+    ("reddit_login", true),
+    ("reddit_scopes", true),
+    ("github", true),
+    ("sentry", false),
+];
+const USAGE: &str = "Usage: check_for_license doctor [--format text|json]";
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Ok,
+    Warn,
+    Fail,
+    /// Not checked, because something it depends on failed or isn't set up.
+    Skip,
+}
+
+/// The outcome of one of the doctor's checks.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct CheckResult {
+    pub name: &'static str,
+    pub status: Status,
+    pub detail: String,
+    pub duration_ms: u64,
+    /// Whether the bot can't run properly if this check fails.
+    pub required: bool,
+}
+
+/// The outcomes of all the doctor's checks, in the order they were made.
+#[derive(Debug, Default)]
+pub struct Report {
+    pub checks: Vec<CheckResult>,
+}
+
+impl Report {
+    /// Required checks that failed.
+    pub fn failures(&self) -> Vec<&CheckResult> {
+        self.checks
+            .iter()
+            .filter(|c| c.required && c.status == Status::Fail)
+            .collect()
+    }
+
+    pub fn passed(&self) -> bool {
+        self.failures().is_empty()
+    }
+
+    fn skip_rest(&mut self, names: &[(&'static str, bool)], why: &str) {
+        for &(name, required) in names {
+            self.checks.push(CheckResult {
+                name,
+                status: Status::Skip,
+                detail: why.to_owned(),
+                duration_ms: 0,
+                required,
+            });
+        }
+    }
+
+    pub fn render(&self, format: Format) -> String {
+        match format {
+            Format::Json => format!(
+                "{}\n",
+                json!({ "ok": self.passed(), "checks": self.checks })
+            ),
+            Format::Text => {
+                let mut out = String::new();
+                for check in &self.checks {
+                    let _ = writeln!(
+                        out,
+                        "{:<4}  {:<13}  {} ({} ms)",
+                        format!("{:?}", check.status).to_lowercase(),
+                        check.name,
+                        check.detail,
+                        check.duration_ms
+                    );
+                }
+                let failures: Vec<&str> = self.failures().iter().map(|c| c.name).collect();
+                if failures.is_empty() {
+                    out.push_str("All required checks passed\n");
+                } else {
+                    let _ = writeln!(out, "Required checks failed: {}", failures.join(", "));
+                }
+                out
+            }
+        }
+    }
+}
+
+/// How the doctor prints its report.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Text,
+    Json,
+}
+
+impl Format {
+    /// Read `--format text|json` from the doctor's arguments.
+    fn from_args(args: &[String]) -> Result<Self> {
+        let value = match args {
+            [] => return Ok(Format::Text),
+            [flag, value] if flag == "--format" => value.as_str(),
+            [flag] => flag
+                .strip_prefix("--format=")
+                .ok_or_else(|| anyhow!(USAGE))?,
+            _ => return Err(anyhow!(USAGE)),
+        };
+        match value {
+            "text" => Ok(Format::Text),
+            "json" => Ok(Format::Json),
+            _ => Err(anyhow!(USAGE)),
+        }
+    }
+}
+
+/// Checks that the bot is set up to run: its config, its state directory,
+/// and the services it talks to.
+#[derive(Debug)]
+pub struct Doctor {
+    env_file: PathBuf,
+    endpoints: Endpoints,
+    timeout: Duration,
+}
+
+impl Doctor {
+    pub fn new(env_file: PathBuf) -> Self {
+        Self {
+            env_file,
+            endpoints: Endpoints::default(),
+            timeout: CHECK_TIMEOUT,
+        }
+    }
+
+    #[cfg(test)]
+    fn with_endpoints(mut self, endpoints: Endpoints, timeout: Duration) -> Self {
+        self.endpoints = endpoints;
+        self.timeout = timeout;
+        self
+    }
+
+    /// Make one check, failing it if it takes too long.
+    async fn check(
+        &self,
+        name: &'static str,
+        required: bool,
+        check: impl Future<Output = (Status, String)>,
+    ) -> CheckResult {
+        let start = Instant::now();
+        let (status, detail) = match timeout(self.timeout, check).await {
+            Ok(outcome) => outcome,
+            Err(_) => (
+                Status::Fail,
+                format!(
+                    "timed out after {}",
+                    humantime::format_duration(self.timeout)
+                ),
+            ),
+        };
+        CheckResult {
+            name,
+            status,
+            detail,
+            duration_ms: u64::try_from(start.elapsed().as_millis()).unwrap_or(u64::MAX),
+            required,
+        }
+    }
+
+    /// Make every check, loading the env file and then the config first.
+    pub async fn run(&self, load_config: impl FnOnce() -> Result<Config>) -> Report {
+        let mut report = Report::default();
+        let env_file = async {
+            match kankyo::load_from_path(&self.env_file, true) {
+                Ok(()) => (Status::Ok, format!("loaded {}", self.env_file.display())),
+                Err(e) => (
+                    Status::Fail,
+                    format!(
+                        "could not load {} ({}); the bot won't start without it",
+                        self.env_file.display(),
+                        e
+                    ),
+                ),
+            }
+        };
+        report
+            .checks
+            .push(self.check("env_file", true, env_file).await);
+
+        let mut config = None;
+        let loaded = async {
+            match load_config() {
+                Ok(c) => {
+                    let problems = c.validate();
+                    let result = if problems.is_empty() {
+                        (Status::Ok, "all settings are present and valid".to_owned())
+                    } else {
+                        let status = if problems.iter().any(|p| p.severity == Severity::Error) {
+                            Status::Fail
+                        } else {
+                            Status::Warn
+                        };
+                        let details: Vec<String> = problems.iter().map(|p| p.to_string()).collect();
+                        (status, details.join("; "))
+                    };
+                    config = Some(c);
+                    result
+                }
+                Err(e) => (Status::Fail, format!("{:#}", e)),
+            }
+        };
+        report.checks.push(self.check("config", true, loaded).await);
+        let config = match config {
+            Some(c) => c,
+            None => {
+                report.skip_rest(NEED_CONFIG, "needs a valid config");
+                return report;
+            }
+        };
+
+        let state_dir = async {
+            let probe = config.state_dir.join(".doctor");
+            match fs::write(&probe, "").and_then(|_| fs::remove_file(&probe)) {
+                Ok(()) => (
+                    Status::Ok,
+                    format!("{} is writable", config.state_dir.display()),
+                ),
+                Err(e) => (
+                    Status::Fail,
+                    format!("can't write to {}: {}", config.state_dir.display(), e),
+                ),
+            }
+        };
+        report
+            .checks
+            .push(self.check("state_dir", true, state_dir).await);
+
+        let templates = async {
+            let repo = |name: &str| RepoLink {
+                repo: GhRepo {
+                    owner: "someone".to_owned(),
+                    name: name.to_owned(),
+                },
+                url: format!("https://github.com/someone/{}", name),
+                removed_license: None,
+                summary: RepoSummary::default(),
+            };
+            let repos = [repo("one"), repo("two")];
+            // every locale replies are made in, English included
+            let subreddits = Some(String::new())
+                .into_iter()
+                .chain(config.subreddit_locales.keys().cloned());
+            let mut too_long = vec![];
+            for subreddit in subreddits {
+                let locale = config.locale(&subreddit);
+                match reply::texts(&config, &subreddit, Some("https://www.reddit.com/")) {
+                    Ok(texts) if reply::compose(&repos, &texts).ends_with(&texts.footer) => {}
+                    Ok(_) => too_long.push(locale.to_owned()),
+                    Err(e) => return (Status::Fail, format!("{}: {:#}", locale, e)),
+                }
+            }
+            if let Some((path, e)) = templates::rejected(&config.template_dir).first() {
+                return (
+                    Status::Fail,
+                    format!("{} is invalid and isn't used: {}", path.display(), e),
+                );
+            }
+            if too_long.is_empty() {
+                (
+                    Status::Ok,
+                    "replies render within Reddit's limit".to_owned(),
+                )
+            } else {
+                (
+                    Status::Warn,
+                    format!(
+                        "the footer is too long to fit in a reply in {}, and will be left out",
+                        too_long.join(", ")
+                    ),
+                )
+            }
+        };
+        report
+            .checks
+            .push(self.check("templates", true, templates).await);
+
+        let bot = match Bot::with_endpoints(config.clone(), self.endpoints.clone()) {
+            Ok(bot) => bot,
+            Err(e) => {
+                report.skip_rest(
+                    &NEED_CONFIG[2..],
+                    &format!("could not set up HTTP clients: {:#}", e),
+                );
+                return report;
+            }
+        };
+        let public = config.reddit_access() == RedditAccess::Public;
+        let login = async {
+            match bot.login().await {
+                Ok(()) if public => (
+                    Status::Ok,
+                    "no credentials, reading public listings".to_owned(),
+                ),
+                Ok(()) => (Status::Ok, format!("logged in as /u/{}", config.username)),
+                Err(e) => (Status::Fail, e.to_string()),
+            }
+        };
+        let login = self.check("reddit_login", true, login).await;
+        let logged_in = login.status == Status::Ok;
+        report.checks.push(login);
+        if public {
+            report.skip_rest(&NEED_CONFIG[3..4], "there's no Reddit account");
+        } else if logged_in {
+            let scopes = async {
+                let granted = bot.scopes();
+                let missing: Vec<&str> = REQUIRED_SCOPES
+                    .iter()
+                    .filter(|&&s| !granted.iter().any(|g| g == s || g == "*"))
+                    .copied()
+                    .collect();
+                if missing.is_empty() {
+                    (Status::Ok, format!("granted {}", granted.join(" ")))
+                } else {
+                    (Status::Fail, format!("missing {}", missing.join(", ")))
+                }
+            };
+            report
+                .checks
+                .push(self.check("reddit_scopes", true, scopes).await);
+        } else {
+            report.skip_rest(&NEED_CONFIG[3..4], "needs a Reddit login");
+        }
+
+        let github = async {
+            match bot.github_rate_limit().await {
+                Ok((0, limit)) => (
+                    Status::Fail,
+                    format!("all {} requests of the rate limit are used up", limit),
+                ),
+                Ok((remaining, limit)) if remaining * 10 < limit => (
+                    Status::Warn,
+                    format!("only {} of {} requests left", remaining, limit),
+                ),
+                Ok((remaining, limit)) => (
+                    Status::Ok,
+                    format!("{} of {} requests left", remaining, limit),
+                ),
+                Err(e) => (Status::Fail, e.to_string()),
+            }
+        };
+        report.checks.push(self.check("github", true, github).await);
+
+        let sentry = async {
+            match &config.sentry_dsn {
+                None => (Status::Skip, "CFL_SENTRY_DSN isn't set".to_owned()),
+                Some(dsn) => match Reporter::new(dsn.clone()).check_reachable().await {
+                    Ok(()) => (Status::Ok, "Sentry is reachable".to_owned()),
+                    Err(e) => (Status::Fail, format!("{:#}", e)),
+                },
+            }
+        };
+        report
+            .checks
+            .push(self.check("sentry", false, sentry).await);
+        report
+    }
+}
+
+/// Run the `doctor` subcommand, printing its report.
+///
+/// Fails if any required check did, so that it can gate a deploy.
+pub async fn run(args: &[String]) -> Result<()> {
+    let format = Format::from_args(args)?;
+    let report = Doctor::new(PathBuf::from(".env"))
+        .run(Config::from_env)
+        .await;
+    print!("{}", report.render(format));
+    if report.passed() {
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "{} required checks failed",
+            report.failures().len()
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Doctor, Format, Report, Status};
+    use crate::bot::Endpoints;
+    use crate::models::Config;
+    use anyhow::anyhow;
+    use mockito::{mock, server_url};
+    use std::{fs, net::TcpListener, path::Path, time::Duration};
+
+    fn config(state_dir: &Path) -> Config {
+        Config {
+            state_dir: state_dir.to_owned(),
+            ..Config::for_tests()
+        }
+    }
+
+    /// Endpoints under a path of their own, so tests' mocks don't overlap.
+    fn endpoints(prefix: &str) -> Endpoints {
+        let base = format!("{}/{}", server_url(), prefix);
+        Endpoints {
+            reddit: base.clone(),
+            reddit_oauth: base.clone(),
+            github: base,
+        }
+    }
+
+    fn login_mock(prefix: &str, status: usize, scope: &str) -> mockito::Mock {
+        mock("POST", format!("/{}/api/v1/access_token", prefix).as_str())
+            .with_status(status)
+            .with_body(format!(
+                r#"{{"access_token":"t","token_type":"bearer","expires_in":3600,"scope":"{}"}}"#,
+                scope
+            ))
+            .create()
+    }
+
+    fn rate_limit_mock(prefix: &str, remaining: u64) -> mockito::Mock {
+        mock("GET", format!("/{}/rate_limit", prefix).as_str())
+            .with_body(format!(
+                r#"{{"rate":{{"limit":60,"remaining":{},"reset":0}}}}"#,
+                remaining
+            ))
+            .create()
+    }
+
+    fn statuses(report: &Report) -> Vec<(&str, Status)> {
+        report.checks.iter().map(|c| (c.name, c.status)).collect()
+    }
+
+    fn doctor(dir: &Path, prefix: &str) -> Doctor {
+        let env_file = dir.join(".env");
+        fs::write(&env_file, "# nothing to see here\n").unwrap();
+        Doctor::new(env_file).with_endpoints(endpoints(prefix), Duration::from_secs(5))
+    }
+
+    #[tokio::test]
+    async fn everything_passes() {
+        let tmp = tempfile::tempdir().unwrap();
+        let _login = login_mock("doctor-ok", 200, "*");
+        let _rate = rate_limit_mock("doctor-ok", 59);
+        let report = doctor(tmp.path(), "doctor-ok")
+            .run(|| Ok(config(tmp.path())))
+            .await;
+        assert_eq!(
+            statuses(&report),
+            vec![
+                ("env_file", Status::Ok),
+                ("config", Status::Ok),
+                ("state_dir", Status::Ok),
+                ("templates", Status::Ok),
+                ("reddit_login", Status::Ok),
+                ("reddit_scopes", Status::Ok),
+                ("github", Status::Ok),
+                ("sentry", Status::Skip),
+            ]
+        );
+        assert!(report.passed());
+        assert_eq!(report.checks[6].detail, "59 of 60 requests left");
+    }
+
+    #[tokio::test]
+    async fn failures_are_reported_and_fail_the_run() {
+        let tmp = tempfile::tempdir().unwrap();
+        let _login = login_mock("doctor-bad", 401, "");
+        let _rate = rate_limit_mock("doctor-bad", 0);
+        let mut config = config(&tmp.path().join("missing"));
+        config.sentry_dsn = Some("http://key@127.0.0.1:1/1".parse().unwrap());
+        let report = Doctor::new(tmp.path().join("no.env"))
+            .with_endpoints(endpoints("doctor-bad"), Duration::from_secs(5))
+            .run(|| Ok(config))
+            .await;
+        assert_eq!(
+            statuses(&report),
+            vec![
+                ("env_file", Status::Fail),
+                ("config", Status::Ok),
+                ("state_dir", Status::Fail),
+                ("templates", Status::Ok),
+                ("reddit_login", Status::Fail),
+                ("reddit_scopes", Status::Skip),
+                ("github", Status::Fail),
+                ("sentry", Status::Fail),
+            ]
+        );
+        let failed: Vec<&str> = report.failures().iter().map(|c| c.name).collect();
+        // Sentry isn't needed to run the bot
+        assert_eq!(
+            failed,
+            vec!["env_file", "state_dir", "reddit_login", "github"]
+        );
+        assert!(!report.passed());
+    }
+
+    #[tokio::test]
+    async fn config_problems() {
+        let tmp = tempfile::tempdir().unwrap();
+        let mut warned = config(tmp.path());
+        warned.user_agent = "my bot".to_owned();
+        let report = doctor(tmp.path(), "doctor-invalid")
+            .run(|| Ok(warned))
+            .await;
+        assert_eq!(report.checks[1].status, Status::Warn);
+        assert!(report.checks[1].detail.starts_with("CFL_USER_AGENT "));
+
+        let mut invalid = config(tmp.path());
+        invalid.user_agent = "my bot".to_owned();
+        invalid.subreddit = "rust-lang".to_owned();
+        let report = doctor(tmp.path(), "doctor-invalid")
+            .run(|| Ok(invalid))
+            .await;
+        assert_eq!(report.checks[1].status, Status::Fail);
+        assert!(report.checks[1].detail.contains("; CFL_SUBREDDIT "));
+    }
+
+    #[tokio::test]
+    async fn missing_scopes_and_low_rate_limit() {
+        let tmp = tempfile::tempdir().unwrap();
+        let _login = login_mock("doctor-scopes", 200, "identity read");
+        let _rate = rate_limit_mock("doctor-scopes", 5);
+        let report = doctor(tmp.path(), "doctor-scopes")
+            .run(|| Ok(config(tmp.path())))
+            .await;
+        let scopes = &report.checks[5];
+        assert_eq!(scopes.status, Status::Fail);
+        assert_eq!(scopes.detail, "missing history, submit");
+        let github = &report.checks[6];
+        assert_eq!(github.status, Status::Warn);
+        assert_eq!(github.detail, "only 5 of 60 requests left");
+    }
+
+    #[tokio::test]
+    async fn hung_service_times_out_alone() {
+        let tmp = tempfile::tempdir().unwrap();
+        // accepts connections but never answers
+        let hung = TcpListener::bind("127.0.0.1:0").unwrap();
+        let _login = login_mock("doctor-hung", 200, "*");
+        let mut endpoints = endpoints("doctor-hung");
+        endpoints.github = format!("http://{}", hung.local_addr().unwrap());
+        let report = doctor(tmp.path(), "doctor-hung")
+            .with_endpoints(endpoints, Duration::from_millis(200))
+            .run(|| Ok(config(tmp.path())))
+            .await;
+        let github = &report.checks[6];
+        assert_eq!(github.status, Status::Fail);
+        assert_eq!(github.detail, "timed out after 200ms");
+        assert!(github.duration_ms < 5000);
+        assert_eq!(report.checks[4].status, Status::Ok);
+        assert_eq!(report.checks[7].status, Status::Skip);
+    }
+
+    #[tokio::test]
+    async fn bad_config_skips_the_rest() {
+        let tmp = tempfile::tempdir().unwrap();
+        let report = doctor(tmp.path(), "doctor-config")
+            .run(|| Err(anyhow!("CFL_USERNAME isn't set")))
+            .await;
+        assert_eq!(report.checks[1].status, Status::Fail);
+        assert_eq!(report.checks[1].detail, "CFL_USERNAME isn't set");
+        assert_eq!(report.checks.len(), 8);
+        assert!(report.checks[2..].iter().all(|c| c.status == Status::Skip));
+
+        let json: serde_json::Value = serde_json::from_str(&report.render(Format::Json)).unwrap();
+        assert_eq!(json["ok"], false);
+        let config = &json["checks"][1];
+        assert_eq!(config["name"], "config");
+        assert_eq!(config["status"], "fail");
+        assert_eq!(config["required"], true);
+        assert!(config["duration_ms"].is_u64());
+
+        let text = report.render(Format::Text);
+        assert!(
+            text.contains("fail  config         CFL_USERNAME isn't set"),
+            "{}",
+            text
+        );
+        assert!(
+            text.ends_with("Required checks failed: config\n"),
+            "{}",
+            text
+        );
+    }
+
+    #[test]
+    fn format_arguments() {
+        let args = |a: &[&str]| a.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert_eq!(Format::from_args(&args(&[])).unwrap(), Format::Text);
+        assert_eq!(
+            Format::from_args(&args(&["--format", "json"])).unwrap(),
+            Format::Json
+        );
+        assert_eq!(
+            Format::from_args(&args(&["--format=text"])).unwrap(),
+            Format::Text
+        );
+        assert!(Format::from_args(&args(&["--format", "yaml"])).is_err());
+        assert!(Format::from_args(&args(&["--verbose"])).is_err());
+    }
+}
@@ -0,0 +1,245 @@
+use anyhow::{anyhow, Result};
+use serde::{de::IgnoredAny, Deserialize};
+use std::str::FromStr;
+
+use crate::history::ReplyRecord;
+use crate::models::{ListingResponse, Post};
+use crate::util::{fold_case, GhRepo};
+
+/// What to do about a post linking repositories that the bot already replied
+/// about in another submission of the same link.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DuplicateMode {
+    /// Don't reply, only log where the earlier reply is.
+    Skip,
+    /// Reply briefly, pointing to the earlier thread.
+    Reply,
+}
+
+impl FromStr for DuplicateMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "skip" => Ok(DuplicateMode::Skip),
+            "reply" => Ok(DuplicateMode::Reply),
+            _ => Err(anyhow!("Duplicate mode must be 'skip' or 'reply'")),
+        }
+    }
+}
+
+/// Response from `/duplicates/{id}`: a listing with just the post itself,
+/// then a listing of the other submissions of its link.
+#[derive(Debug, Deserialize)]
+pub struct DuplicatesResponse(IgnoredAny, ListingResponse<Post>);
+
+impl DuplicatesResponse {
+    /// The other submissions of the post's link.
+    pub fn others(self) -> Vec<Post> {
+        self.1.data.children.into_iter().map(|t| t.data).collect()
+    }
+}
+
+/// An earlier submission of the same link that the bot replied to.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EarlierReply {
+    /// Fullname of the earlier post.
+    pub post: String,
+    /// Full URL of the earlier post on Reddit.
+    pub permalink: String,
+    /// The repository the bot replied about there, as `org/repo`.
+    pub repo: String,
+}
+
+/// The replies about any of the repositories made less than `window` seconds
+/// before `now`.
+fn recent<'a>(
+    history: &'a [ReplyRecord],
+    repos: &'a [GhRepo],
+    now: u64,
+    window: u64,
+) -> impl Iterator<Item = &'a ReplyRecord> {
+    history.iter().filter(move |r| {
+        now.saturating_sub(r.at) < window
+            && repos
+                .iter()
+                .any(|repo| fold_case(&r.repo) == repo.canonical_key())
+    })
+}
+
+/// Whether the bot replied about any of the repositories within the window,
+/// so that it's worth looking for other submissions of a post's link.
+pub fn worth_checking(history: &[ReplyRecord], repos: &[GhRepo], now: u64, window: u64) -> bool {
+    recent(history, repos, now, window).next().is_some()
+}
+
+/// Find an earlier submission of a post's link, among its `duplicates`,
+/// where the bot replied about one of the repositories within the window.
+pub fn earlier_reply(
+    history: &[ReplyRecord],
+    duplicates: &[Post],
+    repos: &[GhRepo],
+    now: u64,
+    window: u64,
+) -> Option<EarlierReply> {
+    recent(history, repos, now, window).find_map(|r| {
+        let post = duplicates.iter().find(|p| p.name == r.post)?;
+        Some(EarlierReply {
+            post: post.name.clone(),
+            permalink: match post.permalink.as_str() {
+                "" => format!(
+                    "https://www.reddit.com/comments/{}",
+                    post.name.trim_start_matches("t3_")
+                ),
+                path => format!("https://www.reddit.com{}", path),
+            },
+            repo: r.repo.clone(),
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{earlier_reply, worth_checking, DuplicateMode, DuplicatesResponse, EarlierReply};
+    use crate::history::ReplyRecord;
+    use crate::models::Post;
+    use crate::util::GhRepo;
+
+    const DAY: u64 = 24 * 60 * 60;
+    const NOW: u64 = 1_600_000_000;
+
+    /// Trimmed from a real `/duplicates/{id}` response.
+    const FIXTURE: &str = r#"[
+        {"kind": "Listing", "data": {"after": null, "dist": 1, "children": [
+            {"kind": "t3", "data": {"name": "t3_new", "subreddit": "rust",
+                "domain": "github.com", "url": "https://github.com/someone/project",
+                "author": "op", "permalink": "/r/rust/comments/new/my_project/",
+                "num_crossposts": 0}}
+        ], "before": null}},
+        {"kind": "Listing", "data": {"after": null, "dist": 2, "children": [
+            {"kind": "t3", "data": {"name": "t3_old", "subreddit": "programming",
+                "domain": "github.com", "url": "https://github.com/someone/project",
+                "author": "op", "permalink": "/r/programming/comments/old/my_project/",
+                "removed_by_category": null}},
+            {"kind": "t3", "data": {"name": "t3_other", "subreddit": "coolgithubprojects",
+                "domain": "github.com", "url": "https://github.com/someone/project",
+                "author": "someone_else", "permalink": "/r/coolgithubprojects/comments/other/x/"}}
+        ], "before": null}}
+    ]"#;
+
+    fn duplicates() -> Vec<Post> {
+        serde_json::from_str::<DuplicatesResponse>(FIXTURE)
+            .unwrap()
+            .others()
+    }
+
+    fn reply(repo: &str, post: &str, at: u64) -> ReplyRecord {
+        ReplyRecord {
+            author: "op".to_owned(),
+            repo: repo.to_owned(),
+            post: post.to_owned(),
+            comment: None,
+            at,
+            resolved: None,
+        }
+    }
+
+    #[test]
+    fn parses_duplicates() {
+        let names: Vec<String> = duplicates().into_iter().map(|p| p.name).collect();
+        assert_eq!(names, vec!["t3_old", "t3_other"]);
+        assert_eq!(
+            duplicates()[0].permalink,
+            "/r/programming/comments/old/my_project/"
+        );
+        // a post with no duplicates has an empty second listing
+        let alone =
+            r#"[{"data":{"children":[{"data":{"name":"t3_new"}}]}},{"data":{"children":[]}}]"#;
+        assert!(serde_json::from_str::<DuplicatesResponse>(alone)
+            .unwrap()
+            .others()
+            .is_empty());
+    }
+
+    #[test]
+    fn finds_reply_in_earlier_submission() {
+        let history = vec![
+            reply("someone/other", "t3_old", NOW - DAY),
+            reply("Someone/Project", "t3_old", NOW - DAY),
+        ];
+        let repos = [GhRepo::new("someone", "project")];
+        assert!(worth_checking(&history, &repos, NOW, 7 * DAY));
+        assert_eq!(
+            earlier_reply(&history, &duplicates(), &repos, NOW, 7 * DAY),
+            Some(EarlierReply {
+                post: "t3_old".to_owned(),
+                permalink: "https://www.reddit.com/r/programming/comments/old/my_project/"
+                    .to_owned(),
+                repo: "Someone/Project".to_owned(),
+            })
+        );
+    }
+
+    #[test]
+    fn skip_decision() {
+        let repos = [GhRepo::new("someone", "project")];
+        let cases: &[(&str, ReplyRecord, bool, bool)] = &[
+            // (case, reply in history, worth checking, earlier reply found)
+            (
+                "in window",
+                reply("someone/project", "t3_other", NOW - DAY),
+                true,
+                true,
+            ),
+            (
+                "too old",
+                reply("someone/project", "t3_old", NOW - 8 * DAY),
+                false,
+                false,
+            ),
+            (
+                "other repo",
+                reply("someone/else", "t3_old", NOW - DAY),
+                false,
+                false,
+            ),
+            (
+                "not a duplicate",
+                reply("someone/project", "t3_unrelated", NOW - DAY),
+                true,
+                false,
+            ),
+        ];
+        for (case, record, worth, found) in cases {
+            let history = vec![record.clone()];
+            assert_eq!(
+                worth_checking(&history, &repos, NOW, 7 * DAY),
+                *worth,
+                "{}",
+                case
+            );
+            assert_eq!(
+                earlier_reply(&history, &duplicates(), &repos, NOW, 7 * DAY).is_some(),
+                *found,
+                "{}",
+                case
+            );
+        }
+        // a window of 0 turns this off
+        let history = vec![reply("someone/project", "t3_old", NOW)];
+        assert!(!worth_checking(&history, &repos, NOW, 0));
+    }
+
+    #[test]
+    fn parses_modes() {
+        assert_eq!(
+            "skip".parse::<DuplicateMode>().unwrap(),
+            DuplicateMode::Skip
+        );
+        assert_eq!(
+            "reply".parse::<DuplicateMode>().unwrap(),
+            DuplicateMode::Reply
+        );
+        assert!("off".parse::<DuplicateMode>().is_err());
+    }
+}
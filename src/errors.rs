@@ -0,0 +1,623 @@
+use crate::listing::Unavailable;
+use reqwest::{header::HeaderMap, Response, StatusCode};
+use std::{
+    fmt,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use thiserror::Error;
+
+/// Wait used when Reddit rate-limits without saying for how long.
+pub const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(60);
+/// Longest wait that a `Retry-After` header is taken at its word for.
+pub const MAX_RETRY_AFTER: Duration = Duration::from_secs(15 * 60);
+
+/// Errors from talking to Reddit and GitHub, classified by how to recover.
+#[derive(Debug, Error)]
+pub enum BotError {
+    /// The access token or credentials were rejected; log in again.
+    #[error("Reddit rejected the bot's authentication: {0}")]
+    RedditAuth(String),
+    /// Reddit refused the account's username or password at login.
+    #[error(
+        "Reddit rejected the username or password ({0}). Check CFL_USERNAME and CFL_PASSWORD; \
+         the bot can't log in to an account with two-factor authentication turned on"
+    )]
+    BadCredentials(String),
+    /// Reddit refused the app's client id or secret at login.
+    #[error(
+        "Reddit rejected the client id or secret ({0}). Check CFL_CLIENT_ID and \
+         CFL_CLIENT_SECRET, and that the app is a script app"
+    )]
+    BadClient(String),
+    /// Reddit won't let the app log in with a password, as only script apps may.
+    #[error(
+        "Reddit won't let the app log in with a password ({0}). Create a script app at \
+         https://www.reddit.com/prefs/apps and set CFL_CLIENT_ID and CFL_CLIENT_SECRET to its \
+         id and secret"
+    )]
+    UnauthorizedClient(String),
+    /// Reddit doesn't take the kind of login the bot asked for.
+    #[error(
+        "Reddit doesn't take the bot's kind of login ({0}). Check that no proxy between the bot \
+         and Reddit changes the login request, or update the bot if Reddit has changed how \
+         script apps log in"
+    )]
+    UnsupportedGrant(String),
+    /// Reddit found something missing from the login request.
+    #[error(
+        "Reddit found the login request incomplete ({0}). Check that CFL_USERNAME, \
+         CFL_PASSWORD, CFL_CLIENT_ID and CFL_CLIENT_SECRET are all set, without quotes or \
+         spaces around them"
+    )]
+    BadLoginRequest(String),
+    /// `retry_after` is what the response's `Retry-After` header asked for.
+    #[error("Rate limited by Reddit{}", asked(retry_after))]
+    RedditRateLimited { retry_after: Option<Duration> },
+    #[error("Rate limited by GitHub until {reset:?}{}", asked(retry_after))]
+    GitHubRateLimited {
+        reset: SystemTime,
+        retry_after: Option<Duration>,
+    },
+    #[error("Could not parse response: {0}")]
+    Parse(String),
+    #[error(transparent)]
+    Transient(reqwest::Error),
+    #[error("{0}")]
+    Permanent(String),
+    /// GitHub has nothing at the path asked for, or won't say that it does.
+    #[error("{0}")]
+    NotFound(String),
+    /// Reddit won't show a listing, such as a banned or private subreddit.
+    #[error("{listing} is {reason}")]
+    ListingUnavailable {
+        listing: String,
+        reason: Unavailable,
+    },
+    /// Reddit is down, usually for maintenance; wait a while before trying again.
+    #[error(
+        "Reddit is unavailable (got status {status}), probably for maintenance{}",
+        asked(retry_after)
+    )]
+    ServiceUnavailable {
+        status: StatusCode,
+        retry_after: Option<Duration>,
+    },
+    /// Only approved users may comment in the subreddit, and the bot isn't one.
+    #[error("Only approved users may comment in /r/{0}")]
+    CommentRestricted(String),
+    /// The same error, seen by several tasks sharing one request.
+    #[error(transparent)]
+    Shared(Arc<BotError>),
+}
+
+impl BotError {
+    /// Whether the operation that failed is worth trying again later.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            BotError::RedditRateLimited { .. }
+            | BotError::GitHubRateLimited { .. }
+            | BotError::Transient(_)
+            | BotError::ServiceUnavailable { .. } => true,
+            BotError::RedditAuth(_)
+            | BotError::BadCredentials(_)
+            | BotError::BadClient(_)
+            | BotError::UnauthorizedClient(_)
+            | BotError::UnsupportedGrant(_)
+            | BotError::BadLoginRequest(_)
+            | BotError::Parse(_)
+            | BotError::Permanent(_)
+            | BotError::NotFound(_)
+            | BotError::ListingUnavailable { .. }
+            | BotError::CommentRestricted(_) => false,
+            BotError::Shared(e) => e.is_retryable(),
+        }
+    }
+
+    /// Whether a request may have been carried out even though it failed, as
+    /// when the connection was lost or timed out after it was sent, or a
+    /// gateway stopped waiting for Reddit's answer.
+    pub fn may_have_been_sent(&self) -> bool {
+        match self.kind() {
+            BotError::Transient(e) => !e.is_connect() && (e.is_timeout() || e.is_request()),
+            BotError::ServiceUnavailable { status, .. } => *status == StatusCode::GATEWAY_TIMEOUT,
+            _ => false,
+        }
+    }
+
+    /// Whether GitHub said that what was asked for isn't there, which for a
+    /// repository may only be because it's still private.
+    pub fn is_not_found(&self) -> bool {
+        matches!(self.kind(), BotError::NotFound(_))
+    }
+
+    /// Unwrap an error that may have been shared between tasks.
+    pub fn from_shared(e: Arc<BotError>) -> Self {
+        Arc::try_unwrap(e).unwrap_or_else(BotError::Shared)
+    }
+
+    /// The underlying error, looking through any sharing.
+    pub fn kind(&self) -> &BotError {
+        match self {
+            BotError::Shared(e) => e.kind(),
+            e => e,
+        }
+    }
+
+    /// Classify an unsuccessful response from Reddit.
+    pub fn from_reddit(resp: Response, action: &str) -> Self {
+        let status = resp.status();
+        let retry_after = retry_after(resp.headers(), SystemTime::now());
+        match status {
+            StatusCode::UNAUTHORIZED => {
+                BotError::RedditAuth(format!("Got status {} from {}", status, action))
+            }
+            StatusCode::TOO_MANY_REQUESTS => BotError::RedditRateLimited { retry_after },
+            StatusCode::BAD_GATEWAY
+            | StatusCode::SERVICE_UNAVAILABLE
+            | StatusCode::GATEWAY_TIMEOUT => BotError::ServiceUnavailable {
+                status,
+                retry_after,
+            },
+            s if s.is_server_error() => BotError::from_status(resp),
+            s => BotError::Permanent(format!("Got status {} from {}", s, action)),
+        }
+    }
+
+    /// Whether an answer from Reddit's token endpoint with this status has a
+    /// JSON body saying how the login went.
+    pub fn login_body_expected(status: StatusCode) -> bool {
+        status.is_success()
+            || matches!(
+                status,
+                StatusCode::BAD_REQUEST | StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN
+            )
+    }
+
+    /// Classify the answer from Reddit's token endpoint, if it's a failure.
+    ///
+    /// Reddit says a client id or secret is wrong with a 401 status, but a
+    /// username or password with an `invalid_grant` error in a body that
+    /// comes with a 200, as do its other OAuth errors. Each of those has its
+    /// own error saying what to fix; ones Reddit isn't known to send are
+    /// permanent errors naming the code.
+    pub fn from_login(status: StatusCode, body: &str) -> Option<Self> {
+        let body: serde_json::Value = serde_json::from_str(body).unwrap_or_default();
+        let error = body["error"].as_str();
+        let message = body["message"].as_str();
+        Some(match (status, error) {
+            (_, Some(code @ "invalid_grant")) => BotError::BadCredentials(code.to_owned()),
+            (_, Some(code @ "invalid_client")) => BotError::BadClient(code.to_owned()),
+            (_, Some(code @ "unauthorized_client")) => {
+                BotError::UnauthorizedClient(code.to_owned())
+            }
+            (_, Some(code @ "unsupported_grant_type")) => {
+                BotError::UnsupportedGrant(code.to_owned())
+            }
+            (_, Some(code @ "invalid_request")) => BotError::BadLoginRequest(code.to_owned()),
+            (StatusCode::UNAUTHORIZED, _) => BotError::BadClient(match message {
+                Some(message) => format!("got status {}: {}", status, message),
+                None => format!("got status {}", status),
+            }),
+            (_, Some(error)) => BotError::Permanent(format!("Reddit refused to log in: {}", error)),
+            (s, None) if !s.is_success() => {
+                BotError::Permanent(format!("Got status {} from login", s))
+            }
+            (_, None) => return None,
+        })
+    }
+
+    /// Classify an unsuccessful response from GitHub.
+    ///
+    /// Besides running out of its rate limit, GitHub can ask for a pause
+    /// with just a `Retry-After` header, which is treated the same way.
+    pub fn from_github(resp: Response, action: &str) -> Self {
+        let status = resp.status();
+        let now = SystemTime::now();
+        let retry_after = retry_after(resp.headers(), now);
+        if let Some(reset) = github_rate_limit_reset(status, resp.headers()) {
+            return BotError::GitHubRateLimited { reset, retry_after };
+        }
+        let pausing = matches!(
+            status,
+            StatusCode::FORBIDDEN | StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE
+        );
+        if pausing && retry_after.is_some() {
+            return BotError::GitHubRateLimited {
+                reset: now,
+                retry_after,
+            };
+        }
+        if status.is_server_error() {
+            return BotError::from_status(resp);
+        }
+        let message = format!("{} (got status {})", action, status);
+        if status == StatusCode::NOT_FOUND {
+            return BotError::NotFound(message);
+        }
+        BotError::Permanent(message)
+    }
+
+    /// Classify a response from Reddit that says it isn't JSON, going by its
+    /// content type. An HTML page is what Reddit serves while it's down.
+    pub fn not_json(status: StatusCode, content_type: &str, action: &str) -> Option<Self> {
+        let essence = content_type.split(';').next().unwrap_or("").trim();
+        if essence.is_empty() || essence.ends_with("json") {
+            return None;
+        }
+        Some(if essence == "text/html" {
+            BotError::ServiceUnavailable {
+                status,
+                retry_after: None,
+            }
+        } else {
+            BotError::Parse(format!(
+                "Expected JSON from {}, got {}",
+                action, content_type
+            ))
+        })
+    }
+
+    fn from_status(resp: Response) -> Self {
+        match resp.error_for_status() {
+            Err(e) => BotError::Transient(e),
+            Ok(resp) => BotError::Permanent(format!("Unexpected status {}", resp.status())),
+        }
+    }
+}
+
+/// Context naming the post, and repository if known, that an error happened with.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PostContext {
+    /// Fullnames of the posts, comma-separated.
+    pub post: String,
+    pub url: String,
+    /// The repository, as `org/repo`.
+    pub repo: Option<String>,
+}
+
+impl fmt::Display for PostContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.repo {
+            Some(repo) => write!(
+                f,
+                "checking '{}' for post {} ({})",
+                repo, self.post, self.url
+            ),
+            None => write!(f, "post {} ({})", self.post, self.url),
+        }
+    }
+}
+
+impl From<reqwest::Error> for BotError {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_decode() {
+            BotError::Parse(e.to_string())
+        } else {
+            BotError::Transient(e)
+        }
+    }
+}
+
+impl From<serde_json::Error> for BotError {
+    fn from(e: serde_json::Error) -> Self {
+        BotError::Parse(e.to_string())
+    }
+}
+
+/// How long a `Retry-After` header asks to wait from `now`, whether it's
+/// given in seconds or as an HTTP date.
+fn retry_after(headers: &HeaderMap, now: SystemTime) -> Option<Duration> {
+    let value = headers.get("retry-after")?.to_str().ok()?.trim();
+    if let Ok(seconds) = value.parse() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let at = httpdate::parse_http_date(value).ok()?;
+    Some(at.duration_since(now).unwrap_or_default())
+}
+
+fn asked(retry_after: &Option<Duration>) -> String {
+    match retry_after {
+        Some(wait) => format!(", asked to wait {}", humantime::format_duration(*wait)),
+        None => String::new(),
+    }
+}
+
+/// How long to wait before trying again, and why: the longer of what a
+/// `Retry-After` header asked for, up to `MAX_RETRY_AFTER`, and `backoff`.
+pub fn retry_wait(asked: Option<Duration>, backoff: Duration) -> (Duration, &'static str) {
+    match asked.map(|wait| wait.min(MAX_RETRY_AFTER)) {
+        Some(wait) if wait > backoff => (wait, "as the Retry-After header asked"),
+        _ => (backoff, "backing off"),
+    }
+}
+
+/// When GitHub's rate limit resets, if this response says it's been exhausted.
+fn github_rate_limit_reset(status: StatusCode, headers: &HeaderMap) -> Option<SystemTime> {
+    if status != StatusCode::FORBIDDEN && status != StatusCode::TOO_MANY_REQUESTS {
+        return None;
+    }
+    let remaining = headers.get("x-ratelimit-remaining")?.to_str().ok()?;
+    if remaining.trim() != "0" {
+        return None;
+    }
+    let reset: u64 = headers
+        .get("x-ratelimit-reset")?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()?;
+    Some(UNIX_EPOCH + Duration::from_secs(reset))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{github_rate_limit_reset, retry_after, retry_wait, BotError, MAX_RETRY_AFTER};
+    use reqwest::{
+        header::{HeaderMap, HeaderValue},
+        StatusCode,
+    };
+    use std::{
+        sync::Arc,
+        time::{Duration, UNIX_EPOCH},
+    };
+
+    #[test]
+    fn retryability() {
+        assert!(BotError::RedditRateLimited {
+            retry_after: Some(Duration::from_secs(1))
+        }
+        .is_retryable());
+        assert!(BotError::GitHubRateLimited {
+            reset: UNIX_EPOCH,
+            retry_after: None
+        }
+        .is_retryable());
+        assert!(!BotError::RedditAuth("x".to_owned()).is_retryable());
+        assert!(!BotError::Parse("x".to_owned()).is_retryable());
+        assert!(!BotError::Permanent("x".to_owned()).is_retryable());
+        assert!(!BotError::NotFound("x".to_owned()).is_retryable());
+        assert!(BotError::ServiceUnavailable {
+            status: StatusCode::SERVICE_UNAVAILABLE,
+            retry_after: None
+        }
+        .is_retryable());
+    }
+
+    #[tokio::test]
+    async fn tells_whether_a_failed_request_may_have_been_sent() {
+        // nothing is listening, so the request never left
+        let closed = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let refused = format!("http://{}/", closed.local_addr().unwrap());
+        drop(closed);
+        let e = BotError::from(reqwest::get(&refused).await.unwrap_err());
+        assert!(e.is_retryable());
+        assert!(!e.may_have_been_sent(), "{:?}", e);
+
+        // the connection is made, but the request is never answered
+        let silent = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_millis(200))
+            .build()
+            .unwrap();
+        let e = client
+            .post(format!(
+                "http://{}/api/comment",
+                silent.local_addr().unwrap()
+            ))
+            .send()
+            .await
+            .unwrap_err();
+        let e = BotError::from(e);
+        assert!(e.may_have_been_sent(), "{:?}", e);
+        assert!(BotError::Shared(Arc::new(e)).may_have_been_sent());
+
+        let unavailable = |status| BotError::ServiceUnavailable {
+            status,
+            retry_after: None,
+        };
+        assert!(unavailable(StatusCode::GATEWAY_TIMEOUT).may_have_been_sent());
+        assert!(!unavailable(StatusCode::SERVICE_UNAVAILABLE).may_have_been_sent());
+        assert!(!BotError::RedditRateLimited { retry_after: None }.may_have_been_sent());
+        assert!(!BotError::Permanent("x".to_owned()).may_have_been_sent());
+    }
+
+    #[test]
+    fn login_failures() {
+        let ok = StatusCode::OK;
+        let unauthorized = StatusCode::UNAUTHORIZED;
+        let token = r#"{"access_token":"t","token_type":"bearer","expires_in":3600,"scope":"*"}"#;
+        assert!(BotError::from_login(ok, token).is_none());
+
+        // a wrong username or password, or a missing two-factor code
+        let e = BotError::from_login(ok, r#"{"error": "invalid_grant"}"#).unwrap();
+        assert!(matches!(e, BotError::BadCredentials(_)), "{:?}", e);
+        assert!(e.to_string().contains("CFL_PASSWORD"), "{}", e);
+        assert!(!e.is_retryable());
+
+        // a wrong client id or secret
+        let e = BotError::from_login(unauthorized, r#"{"message": "Unauthorized", "error": 401}"#)
+            .unwrap();
+        assert!(matches!(e, BotError::BadClient(_)), "{:?}", e);
+        assert!(e
+            .to_string()
+            .contains("got status 401 Unauthorized: Unauthorized"));
+        assert!(e.to_string().contains("CFL_CLIENT_SECRET"), "{}", e);
+        assert!(!e.is_retryable());
+        let e = BotError::from_login(unauthorized, "").unwrap();
+        assert!(matches!(e, BotError::BadClient(_)), "{:?}", e);
+
+        // the other OAuth errors each say what to fix, and aren't worth retrying
+        type Kind = fn(&BotError) -> bool;
+        let cases: [(&str, Kind, &str); 4] = [
+            (
+                r#"{"error": "invalid_client"}"#,
+                |e| matches!(e, BotError::BadClient(_)),
+                "CFL_CLIENT_SECRET",
+            ),
+            (
+                r#"{"error": "unauthorized_client"}"#,
+                |e| matches!(e, BotError::UnauthorizedClient(_)),
+                "script app",
+            ),
+            (
+                r#"{"error": "unsupported_grant_type"}"#,
+                |e| matches!(e, BotError::UnsupportedGrant(_)),
+                "proxy",
+            ),
+            (
+                r#"{"error": "invalid_request"}"#,
+                |e| matches!(e, BotError::BadLoginRequest(_)),
+                "CFL_USERNAME",
+            ),
+        ];
+        for (body, expected, hint) in cases {
+            let e = BotError::from_login(ok, body).unwrap();
+            assert!(expected(&e), "{}: {:?}", body, e);
+            assert!(e.to_string().contains(hint), "{}", e);
+            assert!(!e.is_retryable());
+        }
+        let e = BotError::from_login(ok, r#"{"error": "invalid_scope"}"#).unwrap();
+        assert!(
+            matches!(&e, BotError::Permanent(m) if m == "Reddit refused to log in: invalid_scope"),
+            "{:?}",
+            e
+        );
+        let e = BotError::from_login(StatusCode::FORBIDDEN, "{}").unwrap();
+        assert!(
+            matches!(&e, BotError::Permanent(m) if m.contains("403")),
+            "{:?}",
+            e
+        );
+
+        assert!(BotError::login_body_expected(StatusCode::BAD_REQUEST));
+        assert!(!BotError::login_body_expected(
+            StatusCode::TOO_MANY_REQUESTS
+        ));
+        assert!(!BotError::login_body_expected(
+            StatusCode::SERVICE_UNAVAILABLE
+        ));
+    }
+
+    #[test]
+    fn tells_html_from_json() {
+        let ok = StatusCode::OK;
+        assert!(BotError::not_json(ok, "application/json; charset=UTF-8", "x").is_none());
+        assert!(BotError::not_json(ok, "", "x").is_none());
+        assert!(matches!(
+            BotError::not_json(
+                StatusCode::SERVICE_UNAVAILABLE,
+                "text/html; charset=UTF-8",
+                "x"
+            ),
+            Some(BotError::ServiceUnavailable {
+                status: StatusCode::SERVICE_UNAVAILABLE,
+                retry_after: None
+            })
+        ));
+        assert!(matches!(
+            BotError::not_json(ok, "text/plain", "login"),
+            Some(BotError::Parse(message)) if message.contains("login")
+        ));
+    }
+
+    #[test]
+    fn shared_errors_keep_their_kind() {
+        let single = BotError::from_shared(Arc::new(BotError::Parse("x".to_owned())));
+        assert!(matches!(single, BotError::Parse(_)));
+
+        let limited = || BotError::GitHubRateLimited {
+            reset: UNIX_EPOCH,
+            retry_after: None,
+        };
+        let arc = Arc::new(limited());
+        let _other_holder = arc.clone();
+        let shared = BotError::from_shared(arc);
+        assert!(matches!(shared, BotError::Shared(_)));
+        assert!(shared.is_retryable());
+        assert!(matches!(shared.kind(), BotError::GitHubRateLimited { .. }));
+        assert_eq!(shared.to_string(), limited().to_string());
+    }
+
+    #[test]
+    fn from_serde_error() {
+        let e = serde_json::from_str::<u32>("nope").unwrap_err();
+        let e: BotError = e.into();
+        assert!(matches!(e, BotError::Parse(_)));
+    }
+
+    #[tokio::test]
+    async fn from_reqwest_errors() {
+        // nothing listens on port 1, so this fails to connect
+        let e = reqwest::get("http://127.0.0.1:1/").await.unwrap_err();
+        let e: BotError = e.into();
+        assert!(matches!(e, BotError::Transient(_)));
+        assert!(e.is_retryable());
+    }
+
+    #[test]
+    fn retry_after_in_seconds() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+        let mut headers = HeaderMap::new();
+        assert_eq!(retry_after(&headers, now), None);
+        headers.insert("retry-after", HeaderValue::from_static(" 30 "));
+        assert_eq!(retry_after(&headers, now), Some(Duration::from_secs(30)));
+        headers.insert("retry-after", HeaderValue::from_static("soon"));
+        assert_eq!(retry_after(&headers, now), None);
+    }
+
+    #[test]
+    fn retry_after_as_a_date() {
+        // Sun, 13 Sep 2020 12:26:40 GMT
+        let now = UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "retry-after",
+            HeaderValue::from_static("Sun, 13 Sep 2020 12:28:10 GMT"),
+        );
+        assert_eq!(retry_after(&headers, now), Some(Duration::from_secs(90)));
+        // a date that's already passed means trying again straight away
+        headers.insert(
+            "retry-after",
+            HeaderValue::from_static("Sun, 13 Sep 2020 12:00:00 GMT"),
+        );
+        assert_eq!(retry_after(&headers, now), Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn waits_for_the_longer_of_the_header_and_the_backoff() {
+        let minute = Duration::from_secs(60);
+        assert_eq!(retry_wait(None, minute), (minute, "backing off"));
+        assert_eq!(retry_wait(Some(minute / 2), minute).0, minute);
+        assert_eq!(
+            retry_wait(Some(minute * 2), minute),
+            (minute * 2, "as the Retry-After header asked")
+        );
+        // a day is taken as the cap, but the backoff can be longer still
+        let day = Duration::from_secs(24 * 60 * 60);
+        assert_eq!(retry_wait(Some(day), minute).0, MAX_RETRY_AFTER);
+        assert_eq!(retry_wait(Some(day), day / 2).0, day / 2);
+    }
+
+    #[test]
+    fn github_rate_limit_headers() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-ratelimit-remaining", HeaderValue::from_static("0"));
+        headers.insert("x-ratelimit-reset", HeaderValue::from_static("1000"));
+        assert_eq!(
+            github_rate_limit_reset(StatusCode::FORBIDDEN, &headers),
+            Some(UNIX_EPOCH + Duration::from_secs(1000))
+        );
+        assert_eq!(
+            github_rate_limit_reset(StatusCode::NOT_FOUND, &headers),
+            None
+        );
+
+        headers.insert("x-ratelimit-remaining", HeaderValue::from_static("12"));
+        assert_eq!(
+            github_rate_limit_reset(StatusCode::FORBIDDEN, &headers),
+            None
+        );
+    }
+}
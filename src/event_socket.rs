@@ -0,0 +1,249 @@
+use anyhow::{anyhow, Context, Result};
+use log::{debug, info, warn};
+use std::{
+    fs,
+    os::unix::fs::FileTypeExt,
+    path::Path,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::{
+    io::AsyncWriteExt,
+    net::{UnixListener, UnixStream},
+    sync::mpsc::{self, error::TrySendError},
+    task::AbortHandle,
+};
+
+use crate::events::Event;
+
+/// Events a client may fall behind by before it's dropped.
+const CLIENT_BACKLOG: usize = 256;
+
+/// A client connected to the event socket.
+#[derive(Debug)]
+struct Client {
+    lines: mpsc::Sender<Arc<str>>,
+    /// The task writing lines to the client's connection, which closes it
+    /// when aborted.
+    writer: AbortHandle,
+}
+
+/// A Unix socket that every recorded event is written to, as one line of
+/// JSON, for other tools to follow as it happens.
+///
+/// Sending never waits on a client: one that falls [`CLIENT_BACKLOG`]
+/// events behind is disconnected.
+#[derive(Clone, Debug, Default)]
+pub struct EventSocket {
+    clients: Arc<Mutex<Vec<Client>>>,
+}
+
+impl EventSocket {
+    /// Listen for clients at `path`, replacing a socket left there by an
+    /// earlier run.
+    pub fn bind(path: &Path) -> Result<Self> {
+        if let Ok(meta) = fs::symlink_metadata(path) {
+            if !meta.file_type().is_socket() {
+                return Err(anyhow!("{} exists and isn't a socket", path.display()));
+            }
+            fs::remove_file(path).with_context(|| format!("removing {}", path.display()))?;
+        }
+        let listener =
+            UnixListener::bind(path).with_context(|| format!("binding {}", path.display()))?;
+        let socket = Self::default();
+        tokio::spawn(socket.clone().accept(listener));
+        Ok(socket)
+    }
+
+    async fn accept(self, listener: UnixListener) {
+        loop {
+            match listener.accept().await {
+                Ok((conn, _)) => self.add(conn),
+                Err(e) => {
+                    warn!("Could not accept a client of the event socket: {}", e);
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                }
+            }
+        }
+    }
+
+    fn add(&self, mut conn: UnixStream) {
+        let (lines, mut rx) = mpsc::channel::<Arc<str>>(CLIENT_BACKLOG);
+        let writer = tokio::spawn(async move {
+            while let Some(line) = rx.recv().await {
+                if conn.write_all(line.as_bytes()).await.is_err() {
+                    break;
+                }
+            }
+        });
+        debug!("Client connected to the event socket");
+        self.clients.lock().unwrap().push(Client {
+            lines,
+            writer: writer.abort_handle(),
+        });
+    }
+
+    /// Number of clients connected.
+    pub fn clients(&self) -> usize {
+        self.clients.lock().unwrap().len()
+    }
+
+    /// Write an event to every client, dropping those that have gone away
+    /// or fallen too far behind.
+    pub fn send(&self, event: &Event) {
+        let line: Arc<str> = match serde_json::to_string(event) {
+            Ok(json) => format!("{}\n", json).into(),
+            Err(e) => {
+                warn!("Could not write event to the event socket: {}", e);
+                return;
+            }
+        };
+        self.clients
+            .lock()
+            .unwrap()
+            .retain(|client| match client.lines.try_send(line.clone()) {
+                Ok(()) => true,
+                Err(TrySendError::Full(_)) => {
+                    info!("Dropping a client of the event socket that fell behind");
+                    client.writer.abort();
+                    false
+                }
+                Err(TrySendError::Closed(_)) => false,
+            });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EventSocket, CLIENT_BACKLOG};
+    use crate::events::Event;
+    use std::{path::Path, time::Duration};
+    use tokio::{
+        io::{AsyncBufReadExt, AsyncReadExt, BufReader},
+        net::UnixStream,
+        time::{sleep, timeout},
+    };
+
+    fn event(title: &str) -> Event {
+        Event {
+            at: 1_600_000_000,
+            fullname: "t3_abc".to_owned(),
+            title: title.to_owned(),
+            subreddit: "rust".to_owned(),
+            permalink: String::new(),
+            author: "op".to_owned(),
+            repo: "some/repo".to_owned(),
+            verdict: "unlicensed".to_owned(),
+            license: None,
+            action: "replied".to_owned(),
+            removed_license: None,
+            detection_path: vec![],
+        }
+    }
+
+    async fn connect(socket: &EventSocket, path: &Path) -> BufReader<UnixStream> {
+        let before = socket.clients();
+        let conn = UnixStream::connect(path).await.unwrap();
+        timeout(Duration::from_secs(5), async {
+            while socket.clients() == before {
+                sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await
+        .unwrap();
+        BufReader::new(conn)
+    }
+
+    async fn next_event(client: &mut BufReader<UnixStream>) -> Event {
+        let mut line = String::new();
+        timeout(Duration::from_secs(5), client.read_line(&mut line))
+            .await
+            .unwrap()
+            .unwrap();
+        serde_json::from_str(&line).unwrap()
+    }
+
+    #[tokio::test]
+    async fn writes_events_to_every_client() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("events.sock");
+        let socket = EventSocket::bind(&path).unwrap();
+        let mut first = connect(&socket, &path).await;
+        let mut second = connect(&socket, &path).await;
+
+        socket.send(&event("one"));
+        socket.send(&event("two"));
+        for client in [&mut first, &mut second] {
+            assert_eq!(next_event(client).await, event("one"));
+            assert_eq!(next_event(client).await, event("two"));
+        }
+    }
+
+    #[tokio::test]
+    async fn forgets_clients_that_disconnect() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("events.sock");
+        let socket = EventSocket::bind(&path).unwrap();
+        let mut staying = connect(&socket, &path).await;
+        drop(connect(&socket, &path).await);
+        assert_eq!(socket.clients(), 2);
+
+        // the writer finds out when it next writes, and the socket after that
+        for title in ["one", "two", "three"] {
+            socket.send(&event(title));
+            assert_eq!(next_event(&mut staying).await, event(title));
+            sleep(Duration::from_millis(20)).await;
+        }
+        assert_eq!(socket.clients(), 1);
+    }
+
+    #[tokio::test]
+    async fn replaces_a_stale_socket_but_not_other_files() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("events.sock");
+        drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+        let socket = EventSocket::bind(&path).unwrap();
+        let mut client = connect(&socket, &path).await;
+        socket.send(&event("one"));
+        assert_eq!(next_event(&mut client).await, event("one"));
+
+        let file = tmp.path().join("events.jsonl");
+        std::fs::write(&file, "").unwrap();
+        assert!(EventSocket::bind(&file).is_err());
+        assert!(file.exists());
+    }
+
+    #[tokio::test]
+    async fn drops_clients_that_fall_behind_without_waiting() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("events.sock");
+        let socket = EventSocket::bind(&path).unwrap();
+        let mut slow = connect(&socket, &path).await;
+        let mut fast = connect(&socket, &path).await;
+
+        // big enough for the slow client's connection to fill up, and then
+        // its backlog
+        let title = "x".repeat(8 * 1024);
+        let mut sent = 0;
+        while socket.clients() == 2 {
+            assert!(sent < 100 * CLIENT_BACKLOG, "slow client was never dropped");
+            socket.send(&event(&title));
+            assert_eq!(next_event(&mut fast).await.title, title);
+            sent += 1;
+        }
+        assert!(sent > CLIENT_BACKLOG);
+
+        // the fast client still gets everything
+        socket.send(&event("after"));
+        assert_eq!(next_event(&mut fast).await, event("after"));
+
+        // and the slow one only what made it through before it was closed
+        let mut rest = vec![];
+        timeout(Duration::from_secs(5), slow.read_to_end(&mut rest))
+            .await
+            .unwrap()
+            .unwrap();
+        let received = rest.iter().filter(|b| **b == b'\n').count();
+        assert!(received < sent, "{} of {}", received, sent);
+    }
+}
@@ -0,0 +1,496 @@
+use anyhow::{Context, Result};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fs::{self, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::compaction::Compactions;
+#[cfg(unix)]
+use crate::event_socket::EventSocket;
+use crate::feed;
+use crate::findings::FindingsLog;
+use crate::latency::Latencies;
+use crate::license::DetectionStep;
+use crate::pending::QueueStats;
+use crate::quota::{Quota, QuotaTracker};
+use crate::repo_history::{CheckRecord, RepoHistory};
+use crate::sanitize::{self, NAME_LEN, TEXT_LEN, TITLE_LEN};
+use crate::scheduler::{SharedStatuses, TaskStatus};
+use crate::skip::SkipReason;
+use crate::stats::Stats;
+
+/// Number of recent events kept in memory for the dashboard.
+const RECENT_EVENTS: usize = 500;
+
+/// What the bot did about one repository linked in a post.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Event {
+    /// Unix timestamp, in seconds, of when the repository was checked.
+    pub at: u64,
+    pub fullname: String,
+    pub title: String,
+    pub subreddit: String,
+    /// Full URL of the post on Reddit.
+    #[serde(default)]
+    pub permalink: String,
+    #[serde(default)]
+    pub author: String,
+    /// The repository, as `org/repo`.
+    pub repo: String,
+    /// Whether the repository was found to be `licensed` or `unlicensed`.
+    pub verdict: String,
+    /// SPDX id of the repository's license, if GitHub recognized it.
+    #[serde(default)]
+    pub license: Option<String>,
+    /// What was done about it: `replied`, or why nothing was.
+    pub action: String,
+    /// SPDX id of the license an unlicensed repository had when it was
+    /// checked before, which may mean it was deleted by accident or the
+    /// repository was taken over.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub removed_license: Option<String>,
+    /// How the repository's license was looked for, and what each way came
+    /// to, when it was checked just before.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub detection_path: Vec<DetectionStep>,
+}
+
+impl Event {
+    /// Why nothing was said about the repository, if nothing was.
+    pub fn skip_reason(&self) -> Option<SkipReason> {
+        SkipReason::from_action(&self.verdict, &self.action)
+    }
+
+    /// The event with what came from Reddit and GitHub cleaned of control
+    /// characters and cut to length, and its permalink dropped unless it's a
+    /// web URL, fit to be kept and shown.
+    pub fn sanitized(self) -> Self {
+        let name = |s: String| sanitize::text(&s, NAME_LEN);
+        Self {
+            fullname: name(self.fullname),
+            title: sanitize::text(&self.title, TITLE_LEN),
+            subreddit: name(self.subreddit),
+            permalink: sanitize::url(&self.permalink).unwrap_or_default(),
+            author: name(self.author),
+            repo: name(self.repo),
+            verdict: name(self.verdict),
+            license: self.license.map(name),
+            action: sanitize::text(&self.action, TEXT_LEN),
+            removed_license: self.removed_license.map(name),
+            detection_path: self
+                .detection_path
+                .into_iter()
+                .map(DetectionStep::sanitized)
+                .collect(),
+            ..self
+        }
+    }
+}
+
+/// Current Unix time in seconds.
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+pub(crate) fn log_file(dir: &Path) -> PathBuf {
+    dir.join("events.jsonl")
+}
+
+/// Load every event in the event log in the state directory, oldest first.
+pub fn load(dir: &Path) -> Result<Vec<Event>> {
+    let path = log_file(dir);
+    let mut events = vec![];
+    if path.exists() {
+        let data =
+            fs::read_to_string(&path).with_context(|| format!("reading {}", path.display()))?;
+        for line in data.lines().filter(|l| !l.trim().is_empty()) {
+            match serde_json::from_str::<Event>(line) {
+                Ok(event) => events.push(event.sanitized()),
+                Err(e) => warn!("Skipping unreadable event in {}: {}", path.display(), e),
+            }
+        }
+    }
+    Ok(events)
+}
+
+/// Record of the bot's recent findings and replies.
+///
+/// Every event is appended to a JSON-lines file, and the newest are kept in
+/// memory so they can be listed without reading the file back.
+#[derive(Debug, Default)]
+pub struct EventLog {
+    path: Option<PathBuf>,
+    recent: VecDeque<Event>,
+    findings: Option<FindingsLog>,
+    feed: Option<PathBuf>,
+    #[cfg(unix)]
+    socket: Option<EventSocket>,
+    /// Times the watch loop has been restarted since the process started.
+    restarts: u64,
+    compactions: Compactions,
+    /// Seconds from posts to the bot's replies to them.
+    latencies: Latencies,
+    /// How the bot's periodic tasks have been running, by name.
+    tasks: SharedStatuses,
+    queues: QueueStats,
+    github_quota: QuotaTracker,
+    /// What the bot has done in each subreddit, kept with the listing's state.
+    stats: Stats,
+    /// The latest checks of each repository.
+    history: RepoHistory,
+    /// State directory the history is saved in, if it's saved.
+    history_dir: Option<PathBuf>,
+}
+
+/// An event log shared between the bot and the HTTP listener.
+pub type SharedEvents = Arc<Mutex<EventLog>>;
+
+impl EventLog {
+    /// Open the event log in the state directory, loading its newest events.
+    pub fn open(dir: &Path) -> Result<Self> {
+        let events = load(dir)?;
+        let history = if RepoHistory::exists(dir) {
+            RepoHistory::load(dir)?
+        } else {
+            RepoHistory::from_events(&events)
+        };
+        let mut recent = VecDeque::from(events);
+        while recent.len() > RECENT_EVENTS {
+            recent.pop_front();
+        }
+        Ok(Self {
+            path: Some(log_file(dir)),
+            recent,
+            findings: None,
+            feed: None,
+            #[cfg(unix)]
+            socket: None,
+            restarts: 0,
+            compactions: Compactions::default(),
+            latencies: Latencies::default(),
+            tasks: SharedStatuses::default(),
+            queues: QueueStats::default(),
+            github_quota: QuotaTracker::default(),
+            stats: Stats::default(),
+            history,
+            history_dir: Some(dir.to_owned()),
+        })
+    }
+
+    /// Regenerate an Atom feed at `path` whenever an unlicensed repository is found.
+    pub fn with_feed(mut self, path: PathBuf) -> Self {
+        self.feed = Some(path);
+        self
+    }
+
+    /// Also append every event to a findings log.
+    pub fn with_findings(mut self, findings: FindingsLog) -> Self {
+        self.findings = Some(findings);
+        self
+    }
+
+    /// Also write every event to the clients of an event socket.
+    #[cfg(unix)]
+    pub fn with_socket(mut self, socket: EventSocket) -> Self {
+        self.socket = Some(socket);
+        self
+    }
+
+    /// Record an event, appending it to the log file if there is one.
+    ///
+    /// An unlicensed repository that had a license when it was last checked
+    /// is recorded with the license it had.
+    pub fn record(&mut self, event: Event) {
+        let mut event = event.sanitized();
+        if event.verdict == "unlicensed" && event.removed_license.is_none() {
+            event.removed_license = self.removed_license(&event.repo);
+        }
+        self.remember_check(&event);
+        if let Some(path) = &self.path {
+            let written = serde_json::to_string(&event)
+                .map_err(anyhow::Error::from)
+                .and_then(|line| {
+                    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+                    writeln!(file, "{}", line)?;
+                    Ok(())
+                });
+            if let Err(e) = written {
+                warn!("Could not write event to {}: {}", path.display(), e);
+            }
+        }
+        if let Some(findings) = &self.findings {
+            if let Err(e) = findings.append(&event) {
+                warn!("Could not write to findings log: {}", e);
+            }
+        }
+        #[cfg(unix)]
+        if let Some(socket) = &self.socket {
+            socket.send(&event);
+        }
+        if self.recent.len() == RECENT_EVENTS {
+            self.recent.pop_front();
+        }
+        let unlicensed = event.verdict == "unlicensed";
+        self.recent.push_back(event);
+        if let (Some(path), true) = (&self.feed, unlicensed) {
+            if let Err(e) = feed::write(path, &self.recent(RECENT_EVENTS)) {
+                warn!("Could not write feed: {}", e);
+            }
+        }
+    }
+
+    /// Add the check an event records to its repository's history, saving
+    /// the history if it changed.
+    fn remember_check(&mut self, event: &Event) {
+        let check = match CheckRecord::from_event(event) {
+            Some(check) => check,
+            None => return,
+        };
+        if !self.history.append(&event.repo, check) {
+            return;
+        }
+        if let Some(dir) = &self.history_dir {
+            if let Err(e) = self.history.save(dir) {
+                warn!("Could not save the repositories' history: {:#}", e);
+            }
+        }
+    }
+
+    /// The SPDX id of the license a repository, as `org/repo`, had when it
+    /// was last checked, if it had one GitHub recognized.
+    pub fn removed_license(&self, repo: &str) -> Option<String> {
+        self.history.last_license(repo)
+    }
+
+    /// The latest checks of each repository.
+    pub fn history(&self) -> &RepoHistory {
+        &self.history
+    }
+
+    /// Up to `limit` of the most recent events, newest first.
+    pub fn recent(&self, limit: usize) -> Vec<Event> {
+        self.recent.iter().rev().take(limit).cloned().collect()
+    }
+
+    /// Count a restart of the watch loop.
+    pub fn record_restart(&mut self) {
+        self.restarts += 1;
+    }
+
+    pub fn restarts(&self) -> u64 {
+        self.restarts
+    }
+
+    /// Count a compaction of the bot's in-memory state.
+    pub fn record_compaction(&mut self, evicted: usize, reclaimed_bytes: usize) {
+        self.compactions.record(evicted, reclaimed_bytes);
+    }
+
+    pub fn compactions(&self) -> Compactions {
+        self.compactions
+    }
+
+    /// Count a reply made `secs` after its post.
+    pub fn record_latency(&mut self, secs: u64) {
+        self.latencies.record(secs);
+    }
+
+    pub fn latencies(&self) -> &Latencies {
+        &self.latencies
+    }
+
+    /// Where the scheduler keeps the statuses of the bot's periodic tasks.
+    pub fn tasks(&self) -> SharedStatuses {
+        self.tasks.clone()
+    }
+
+    /// How each of the bot's periodic tasks has been running, by name.
+    pub fn task_statuses(&self) -> BTreeMap<String, TaskStatus> {
+        self.tasks.lock().unwrap().clone()
+    }
+
+    /// What waits in the queues of held back work, and what was dropped.
+    pub fn queues(&self) -> &QueueStats {
+        &self.queues
+    }
+
+    pub fn queues_mut(&mut self) -> &mut QueueStats {
+        &mut self.queues
+    }
+
+    /// Keep GitHub's latest rate limit, returning whether to warn that it's
+    /// below `floor`.
+    pub fn record_github_quota(&mut self, quota: Quota, floor: u64) -> bool {
+        self.github_quota.update(quota, floor)
+    }
+
+    /// GitHub's rate limit as of the latest response, if one has been seen.
+    pub fn github_quota(&self) -> Option<Quota> {
+        self.github_quota.latest()
+    }
+
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    pub fn stats_mut(&mut self) -> &mut Stats {
+        &mut self.stats
+    }
+
+    /// Number of replies made since the given Unix timestamp.
+    pub fn replies_since(&self, since: u64) -> usize {
+        self.recent
+            .iter()
+            .filter(|e| e.at >= since && e.action == "replied")
+            .count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Event, EventLog, RECENT_EVENTS};
+    use crate::findings::FindingsLog;
+    use std::fs;
+
+    fn event(at: u64, action: &str) -> Event {
+        Event {
+            at,
+            fullname: format!("t3_{}", at),
+            title: "A project".to_owned(),
+            subreddit: "rust".to_owned(),
+            permalink: String::new(),
+            author: "someone".to_owned(),
+            repo: "someone/project".to_owned(),
+            verdict: "unlicensed".to_owned(),
+            license: None,
+            action: action.to_owned(),
+            removed_license: None,
+            detection_path: vec![],
+        }
+    }
+
+    #[test]
+    fn persists_and_reloads_events() {
+        let tmp = tempfile::tempdir().unwrap();
+        {
+            let mut log = EventLog::open(tmp.path()).unwrap();
+            log.record(event(1, "replied"));
+            log.record(event(2, "reply cap reached"));
+        }
+        let log = EventLog::open(tmp.path()).unwrap();
+        let recent = log.recent(10);
+        assert_eq!(recent.len(), 2);
+        assert_eq!(recent[0].at, 2);
+        assert_eq!(recent[1], event(1, "replied"));
+    }
+
+    #[test]
+    fn sanitizes_events_kept_and_reloaded() {
+        let tmp = tempfile::tempdir().unwrap();
+        let mut hostile = event(1, "replied");
+        hostile.title = format!("Title\u{1b}[2J\n{}", "x".repeat(10_000));
+        hostile.permalink = "javascript:alert(1)".to_owned();
+        hostile.author = "some\u{202e}one".to_owned();
+        {
+            let mut log = EventLog::open(tmp.path()).unwrap();
+            log.record(hostile.clone());
+            let kept = &log.recent(1)[0];
+            assert!(kept.title.starts_with("Title[2J xxx"));
+            assert_eq!(kept.title.chars().count(), crate::sanitize::TITLE_LEN);
+            assert_eq!(kept.permalink, "");
+            assert_eq!(kept.author, "someone");
+        }
+
+        // written by an older version, or by hand
+        let line = serde_json::to_string(&hostile).unwrap();
+        fs::write(super::log_file(tmp.path()), line + "\n").unwrap();
+        let log = EventLog::open(tmp.path()).unwrap();
+        let reloaded = &log.recent(1)[0];
+        assert_eq!(reloaded.permalink, "");
+        assert_eq!(reloaded.author, "someone");
+        assert!(reloaded.title.ends_with('…'));
+    }
+
+    #[test]
+    fn copies_events_to_findings_log() {
+        let tmp = tempfile::tempdir().unwrap();
+        let findings = tmp.path().join("findings.jsonl");
+        let mut log = EventLog::open(tmp.path())
+            .unwrap()
+            .with_findings(FindingsLog::new(findings.clone(), 1024 * 1024));
+        log.record(event(1, "replied"));
+        assert_eq!(fs::read_to_string(findings).unwrap().lines().count(), 1);
+    }
+
+    #[test]
+    fn regenerates_feed_on_new_findings() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("feed.atom");
+        let mut log = EventLog::default().with_feed(path.clone());
+        let mut licensed = event(1, "none");
+        licensed.verdict = "licensed".to_owned();
+        log.record(licensed);
+        assert!(!path.exists());
+        log.record(event(2, "replied"));
+        let feed = fs::read_to_string(&path).unwrap();
+        assert!(feed.contains("<id>urn:reddit:t3_2</id>"), "{}", feed);
+    }
+
+    #[test]
+    fn notices_repositories_losing_their_license() {
+        let tmp = tempfile::tempdir().unwrap();
+        let licensed = |at: u64, repo: &str, license: Option<&str>| Event {
+            verdict: "licensed".to_owned(),
+            license: license.map(str::to_owned),
+            repo: repo.to_owned(),
+            ..event(at, "none")
+        };
+        {
+            let mut log = EventLog::open(tmp.path()).unwrap();
+            log.record(licensed(1, "someone/project", Some("MIT")));
+            log.record(licensed(2, "someone/unknown", Some("NOASSERTION")));
+            log.record(licensed(3, "someone/relicensed", Some("MIT")));
+            log.record(licensed(4, "someone/relicensed", Some("Apache-2.0")));
+        }
+
+        // the history is read back from the log file
+        let mut log = EventLog::open(tmp.path()).unwrap();
+        assert_eq!(
+            log.removed_license("Someone/Project"),
+            Some("MIT".to_owned())
+        );
+        assert_eq!(log.removed_license("someone/unknown"), None);
+        assert_eq!(
+            log.removed_license("someone/relicensed"),
+            Some("Apache-2.0".to_owned())
+        );
+        assert_eq!(log.removed_license("someone/never"), None);
+
+        log.record(event(5, "replied"));
+        assert_eq!(log.recent(1)[0].removed_license, Some("MIT".to_owned()));
+        // only the check that found the license gone is flagged
+        log.record(event(6, "replied"));
+        assert_eq!(log.recent(1)[0].removed_license, None);
+        assert_eq!(log.removed_license("someone/project"), None);
+    }
+
+    #[test]
+    fn keeps_newest_events_in_memory() {
+        let mut log = EventLog::default();
+        for at in 0..(RECENT_EVENTS as u64 + 10) {
+            log.record(event(at, "replied"));
+        }
+        assert_eq!(log.recent(usize::MAX).len(), RECENT_EVENTS);
+        assert_eq!(log.recent(1)[0].at, RECENT_EVENTS as u64 + 9);
+        assert_eq!(log.replies_since(RECENT_EVENTS as u64), 10);
+    }
+}
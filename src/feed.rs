@@ -0,0 +1,188 @@
+use anyhow::{Context, Result};
+use std::{
+    fs,
+    path::Path,
+    time::{Duration, UNIX_EPOCH},
+};
+
+use crate::events::Event;
+use crate::http::escape;
+
+/// Most posts listed in the feed.
+pub const FEED_ENTRIES: usize = 50;
+
+fn timestamp(at: u64) -> String {
+    humantime::format_rfc3339_seconds(UNIX_EPOCH + Duration::from_secs(at)).to_string()
+}
+
+fn link(event: &Event) -> String {
+    if event.permalink.is_empty() {
+        format!(
+            "https://redd.it/{}",
+            event.fullname.trim_start_matches("t3_")
+        )
+    } else {
+        event.permalink.clone()
+    }
+}
+
+/// Render an Atom feed of the unlicensed repositories among `events`.
+///
+/// `events` are expected newest first, as returned by `EventLog::recent`.
+/// There's one entry per post, identified by its fullname so that readers
+/// don't show a post twice when the feed is regenerated.
+pub fn render(events: &[Event]) -> String {
+    let mut posts: Vec<(&Event, Vec<&str>)> = vec![];
+    for event in events.iter().filter(|e| e.verdict == "unlicensed") {
+        if let Some((_, repos)) = posts.iter_mut().find(|(e, _)| e.fullname == event.fullname) {
+            if !repos.contains(&event.repo.as_str()) {
+                repos.push(&event.repo);
+            }
+        } else if posts.len() < FEED_ENTRIES {
+            posts.push((event, vec![&event.repo]));
+        }
+    }
+    let updated = posts.first().map(|(e, _)| e.at).unwrap_or_default();
+    let entries: String = posts
+        .iter()
+        .map(|(event, repos)| {
+            let repos = repos.join(", ");
+            format!(
+                r#"  <entry>
+    <id>urn:reddit:{}</id>
+    <title>{}</title>
+    <link href="{}"/>
+    <updated>{}</updated>
+    <summary>{} linked by u/{} in /r/{} is unlicensed; action: {}</summary>
+  </entry>
+"#,
+                escape(&event.fullname),
+                escape(&repos),
+                escape(&link(event)),
+                timestamp(event.at),
+                escape(&repos),
+                escape(&event.author),
+                escape(&event.subreddit),
+                escape(&event.action)
+            )
+        })
+        .collect();
+    format!(
+        r#"<?xml version="1.0" encoding="utf-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom">
+  <id>urn:check_for_license:findings</id>
+  <title>check_for_license: unlicensed repositories</title>
+  <author><name>check_for_license</name></author>
+  <updated>{}</updated>
+{}</feed>
+"#,
+        timestamp(updated),
+        entries
+    )
+}
+
+/// Write the feed to a file, replacing it atomically.
+pub fn write(path: &Path, events: &[Event]) -> Result<()> {
+    let mut tmp = path.to_owned().into_os_string();
+    tmp.push(".tmp");
+    fs::write(&tmp, render(events))
+        .and_then(|_| fs::rename(&tmp, path))
+        .with_context(|| format!("writing feed {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{render, write, FEED_ENTRIES};
+    use crate::events::Event;
+    use std::fs;
+
+    fn event(at: u64, fullname: &str, repo: &str, verdict: &str) -> Event {
+        Event {
+            at,
+            fullname: fullname.to_owned(),
+            title: "A project".to_owned(),
+            subreddit: "rust".to_owned(),
+            permalink: format!("https://www.reddit.com/r/rust/comments/{}/", fullname),
+            author: "someone".to_owned(),
+            repo: repo.to_owned(),
+            verdict: verdict.to_owned(),
+            license: None,
+            action: "replied".to_owned(),
+            removed_license: None,
+            detection_path: vec![],
+        }
+    }
+
+    #[test]
+    fn empty_feed() {
+        assert_eq!(
+            render(&[]),
+            r#"<?xml version="1.0" encoding="utf-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom">
+  <id>urn:check_for_license:findings</id>
+  <title>check_for_license: unlicensed repositories</title>
+  <author><name>check_for_license</name></author>
+  <updated>1970-01-01T00:00:00Z</updated>
+</feed>
+"#
+        );
+    }
+
+    #[test]
+    fn one_entry_per_unlicensed_post() {
+        let mut no_link = event(1_600_000_000, "t3_c", "<b>/tags", "unlicensed");
+        no_link.permalink = String::new();
+        let events = vec![
+            event(1_600_000_300, "t3_a", "someone/one", "unlicensed"),
+            event(1_600_000_200, "t3_b", "someone/licensed", "licensed"),
+            event(1_600_000_100, "t3_a", "someone/two", "unlicensed"),
+            no_link,
+        ];
+        assert_eq!(
+            render(&events),
+            r#"<?xml version="1.0" encoding="utf-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom">
+  <id>urn:check_for_license:findings</id>
+  <title>check_for_license: unlicensed repositories</title>
+  <author><name>check_for_license</name></author>
+  <updated>2020-09-13T12:31:40Z</updated>
+  <entry>
+    <id>urn:reddit:t3_a</id>
+    <title>someone/one, someone/two</title>
+    <link href="https://www.reddit.com/r/rust/comments/t3_a/"/>
+    <updated>2020-09-13T12:31:40Z</updated>
+    <summary>someone/one, someone/two linked by u/someone in /r/rust is unlicensed; action: replied</summary>
+  </entry>
+  <entry>
+    <id>urn:reddit:t3_c</id>
+    <title>&lt;b&gt;/tags</title>
+    <link href="https://redd.it/c"/>
+    <updated>2020-09-13T12:26:40Z</updated>
+    <summary>&lt;b&gt;/tags linked by u/someone in /r/rust is unlicensed; action: replied</summary>
+  </entry>
+</feed>
+"#
+        );
+    }
+
+    #[test]
+    fn limits_entries() {
+        let events: Vec<Event> = (0..FEED_ENTRIES as u64 + 5)
+            .rev()
+            .map(|at| event(at, &format!("t3_{}", at), "someone/project", "unlicensed"))
+            .collect();
+        let feed = render(&events);
+        assert_eq!(feed.matches("<entry>").count(), FEED_ENTRIES);
+        assert!(feed.contains(&format!("urn:reddit:t3_{}<", FEED_ENTRIES + 4)));
+        assert!(!feed.contains("urn:reddit:t3_4<"));
+    }
+
+    #[test]
+    fn writes_feed_file() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("feed.atom");
+        let events = vec![event(1, "t3_a", "someone/one", "unlicensed")];
+        write(&path, &events).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), render(&events));
+    }
+}
@@ -0,0 +1,139 @@
+use anyhow::{anyhow, Result};
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use crate::events::Event;
+
+/// Where a findings log at `path` is moved to when it's full.
+pub fn rotated_path(path: &Path) -> PathBuf {
+    let mut name = path.to_owned().into_os_string();
+    name.push(".1");
+    name.into()
+}
+
+/// Append-only JSON-lines record of every finding, for downstream analysis.
+///
+/// Each finding is written with a single `write` call so that readers never
+/// see half a line. Once the file would grow past `max_bytes` it's moved to
+/// `{path}.1`, replacing any previous one, and a new file is started.
+#[derive(Debug)]
+pub struct FindingsLog {
+    path: PathBuf,
+    max_bytes: u64,
+}
+
+impl FindingsLog {
+    pub fn new(path: PathBuf, max_bytes: u64) -> Self {
+        Self { path, max_bytes }
+    }
+
+    /// Append one finding to the log, sanitized, rotating it first if it's
+    /// full.
+    pub fn append(&self, event: &Event) -> Result<()> {
+        let mut line = serde_json::to_vec(&event.clone().sanitized())?;
+        line.push(b'\n');
+        if let Ok(meta) = fs::metadata(&self.path) {
+            if meta.len() > 0 && meta.len() + line.len() as u64 > self.max_bytes {
+                fs::rename(&self.path, rotated_path(&self.path))?;
+            }
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        let written = file.write(&line)?;
+        if written != line.len() {
+            return Err(anyhow!(
+                "Only wrote {} of {} bytes to {}",
+                written,
+                line.len(),
+                self.path.display()
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FindingsLog;
+    use crate::events::Event;
+    use std::fs;
+
+    fn finding(fullname: &str) -> Event {
+        Event {
+            at: 1_600_000_000,
+            fullname: fullname.to_owned(),
+            title: "A project".to_owned(),
+            subreddit: "rust".to_owned(),
+            permalink: "https://www.reddit.com/r/rust/comments/abc/a_project/".to_owned(),
+            author: "someone".to_owned(),
+            repo: "someone/project".to_owned(),
+            verdict: "licensed".to_owned(),
+            license: Some("MIT".to_owned()),
+            action: "none".to_owned(),
+            removed_license: None,
+            detection_path: vec![],
+        }
+    }
+
+    #[test]
+    fn writes_one_json_object_per_line() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("findings.jsonl");
+        let log = FindingsLog::new(path.clone(), 1024 * 1024);
+        log.append(&finding("t3_a")).unwrap();
+        log.append(&finding("t3_b")).unwrap();
+
+        let data = fs::read_to_string(&path).unwrap();
+        let lines: Vec<serde_json::Value> = data
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(
+            lines[0],
+            serde_json::json!({
+                "at": 1_600_000_000,
+                "fullname": "t3_a",
+                "title": "A project",
+                "subreddit": "rust",
+                "permalink": "https://www.reddit.com/r/rust/comments/abc/a_project/",
+                "author": "someone",
+                "repo": "someone/project",
+                "verdict": "licensed",
+                "license": "MIT",
+                "action": "none",
+            })
+        );
+        assert_eq!(lines[1]["fullname"], "t3_b");
+    }
+
+    #[test]
+    fn rotates_when_full() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("findings.jsonl");
+        let line_len = serde_json::to_vec(&finding("t3_a")).unwrap().len() as u64 + 1;
+        let log = FindingsLog::new(path.clone(), line_len * 2);
+        for fullname in &["t3_a", "t3_b", "t3_c"] {
+            log.append(&finding(fullname)).unwrap();
+        }
+
+        let rotated = fs::read_to_string(tmp.path().join("findings.jsonl.1")).unwrap();
+        let current = fs::read_to_string(&path).unwrap();
+        assert_eq!(rotated.lines().count(), 2);
+        assert!(rotated.contains("t3_a") && rotated.contains("t3_b"));
+        assert_eq!(current.lines().count(), 1);
+        assert!(current.contains("t3_c"));
+    }
+
+    #[test]
+    fn unwritable_path_is_an_error() {
+        let tmp = tempfile::tempdir().unwrap();
+        let log = FindingsLog::new(tmp.path().join("missing").join("findings.jsonl"), 1024);
+        assert!(log.append(&finding("t3_a")).is_err());
+    }
+}
@@ -0,0 +1,466 @@
+use anyhow::Result;
+use log::{debug, warn};
+use reqwest::{
+    header::HeaderMap, Client, ClientBuilder, Method, RequestBuilder, Response, StatusCode,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::time::Duration;
+
+use crate::check::{is_license_file, Verdict};
+use crate::errors::BotError;
+use crate::events::SharedEvents;
+use crate::github_app::AppAuth;
+use crate::license::{self, DetectionStep, License, LicenseInfo, LicensePolicy, Probe};
+use crate::models::Config;
+use crate::preview::RepoSummary;
+use crate::quota::Quota;
+use crate::transport::Transport;
+use crate::util::GhRepo;
+
+pub const API_URL: &str = "https://api.github.com";
+
+/// What checking a repository came to: whether it has a license, what else
+/// GitHub says about it, and each way the license was looked for.
+pub type Checked = (License, RepoSummary, Vec<DetectionStep>);
+
+/// Build a `reqwest::Client` for talking to GitHub.
+pub fn build_client(config: &Config) -> Result<Client> {
+    Ok(ClientBuilder::new()
+        .timeout(Duration::from_secs(15))
+        .user_agent(config.github_user_agent())
+        .build()?)
+}
+
+/// What requests to GitHub go by that can change while the bot runs.
+#[derive(Clone, Debug, Default)]
+pub struct Access {
+    /// The configured token, used unless there's a GitHub App.
+    pub token: Option<String>,
+    /// Requests left below which a warning is logged.
+    pub floor: u64,
+    /// Unix time now, to sign the GitHub App's requests with.
+    pub now: u64,
+}
+
+/// A repository, as much of it as the bot needs to open an issue on it.
+#[derive(Debug, Default, Deserialize)]
+pub struct Repository {
+    #[serde(default)]
+    pub has_issues: bool,
+    #[serde(default)]
+    pub stargazers_count: u64,
+}
+
+/// An issue the bot opened.
+#[derive(Debug, Default, Deserialize)]
+pub struct Issue {
+    #[serde(default)]
+    pub number: u64,
+    #[serde(default)]
+    pub html_url: String,
+}
+
+/// One of the entries of a repository's directory.
+#[derive(Debug, Deserialize)]
+struct Entry {
+    #[serde(default, rename = "type")]
+    kind: String,
+    #[serde(default)]
+    name: String,
+}
+
+/// The names of the files among a directory's entries.
+fn file_names(entries: Vec<Entry>) -> Vec<String> {
+    entries
+        .into_iter()
+        .filter(|e| e.kind == "file")
+        .map(|e| e.name)
+        .collect()
+}
+
+/// GitHub's API, as the configured GitHub App or token if there is one.
+#[derive(Debug)]
+pub struct GitHub {
+    client: Client,
+    base_url: String,
+    /// The GitHub App installation to authenticate as, if any.
+    app: Option<AppAuth>,
+    pub transport: Transport,
+    /// Where the rate limit from each response is kept.
+    pub events: SharedEvents,
+}
+
+impl GitHub {
+    pub fn new(
+        client: Client,
+        base_url: String,
+        app: Option<AppAuth>,
+        transport: Transport,
+        events: SharedEvents,
+    ) -> Self {
+        Self {
+            client,
+            base_url,
+            app,
+            transport,
+            events,
+        }
+    }
+
+    /// A request to GitHub's API, carrying a token for the GitHub App if
+    /// there is one, or else the configured token if there is one.
+    async fn authorized(
+        &self,
+        method: Method,
+        url: &str,
+        access: &Access,
+    ) -> Result<RequestBuilder, BotError> {
+        let request = self.client.request(method, url);
+        let token = match &self.app {
+            Some(app) => Some(
+                app.token(&self.client, &self.transport, &self.base_url, access.now)
+                    .await?,
+            ),
+            None => access.token.clone(),
+        };
+        Ok(match token {
+            Some(token) => request.header("authorization", format!("token {}", token)),
+            None => request,
+        })
+    }
+
+    async fn send(&self, request: RequestBuilder) -> Result<Response, BotError> {
+        Ok(self.transport.send(&self.client, request).await?)
+    }
+
+    /// Keep the rate limit from a response's headers, warning the first
+    /// time in each rate limit window that it drops below `floor`.
+    fn note_quota(&self, headers: &HeaderMap, floor: u64) {
+        let quota = match Quota::from_headers(headers) {
+            Some(q) => q,
+            None => return,
+        };
+        if self
+            .events
+            .lock()
+            .unwrap()
+            .record_github_quota(quota, floor)
+        {
+            warn!("Only {}, deferring new checks until then", quota);
+        }
+    }
+
+    /// How many requests are left, out of how many, until the limit resets.
+    pub async fn rate_limit(&self, floor: u64) -> Result<(u64, u64), BotError> {
+        let request = self.client.get(format!("{}/rate_limit", self.base_url));
+        let resp = self.send(request).await?;
+        self.note_quota(resp.headers(), floor);
+        if !resp.status().is_success() {
+            return Err(BotError::from_github(resp, "Could not fetch rate limit"));
+        }
+        let data = resp.json::<Value>().await?;
+        match (
+            data["rate"]["remaining"].as_u64(),
+            data["rate"]["limit"].as_u64(),
+        ) {
+            (Some(remaining), Some(limit)) => Ok((remaining, limit)),
+            _ => Err(BotError::Parse(format!("Unexpected rate limit: {}", data))),
+        }
+    }
+
+    /// The names of the files at the top of a repository, at `git_ref` or
+    /// on its default branch, or `None` if it has no such ref.
+    async fn top_level_files(
+        &self,
+        request: RequestBuilder,
+        repo: &GhRepo,
+        git_ref: Option<&str>,
+        floor: u64,
+    ) -> Result<Option<Vec<String>>, BotError> {
+        let request = match git_ref {
+            Some(git_ref) => request.query(&[("ref", git_ref)]),
+            None => request,
+        };
+        let resp = self.send(request).await?;
+        self.note_quota(resp.headers(), floor);
+        if git_ref.is_some() && resp.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !resp.status().is_success() {
+            let action = match git_ref {
+                Some(git_ref) => format!("Could not list the files of '{}' at {}", repo, git_ref),
+                None => format!("Could not list the files of '{}'", repo),
+            };
+            return Err(BotError::from_github(resp, &action));
+        }
+        let entries = resp.json::<Vec<Entry>>().await.unwrap_or_default();
+        Ok(Some(file_names(entries)))
+    }
+
+    /// The names of the files at the top of a repository's default branch.
+    pub async fn files(&self, repo: &GhRepo, access: &Access) -> Result<Vec<String>, BotError> {
+        let url = format!("{}/contents/", repo.api_url(&self.base_url));
+        let request = self.authorized(Method::GET, &url, access).await?;
+        let files = self
+            .top_level_files(request, repo, None, access.floor)
+            .await?;
+        Ok(files.unwrap_or_default())
+    }
+
+    /// Checks to see if a GH project exists and is without a license.
+    ///
+    /// If it was linked at a ref, the files there are looked through for a
+    /// license too, and it counts as licensed if either has one.
+    pub async fn check(
+        &self,
+        repo: &GhRepo,
+        refs: &[String],
+        floor: u64,
+        policy: &LicensePolicy,
+    ) -> Result<Checked, BotError> {
+        let url = repo.api_url(&self.base_url);
+        let mut path = vec![];
+        let (default_branch, summary) = {
+            // check for valid project
+            debug!("Checking for valid GH project");
+            debug!("Checking {}", url);
+            let resp = self.send(self.client.get(&url)).await?;
+            self.note_quota(resp.headers(), floor);
+            if !resp.status().is_success() {
+                return Err(BotError::from_github(
+                    resp,
+                    &format!("Invalid GH project '{}'", repo),
+                ));
+            }
+            let data = resp.json::<Value>().await.unwrap_or_default();
+            let summary = RepoSummary::from_repo(&data);
+            if data["size"] == json!(0) {
+                // GitHub knows of no license in a repository without commits
+                // either, so ask for its contents, which it refuses for those
+                let request = self.client.get(format!("{}/contents/", url));
+                let resp = self.send(request).await?;
+                self.note_quota(resp.headers(), floor);
+                if resp.status() == StatusCode::CONFLICT {
+                    debug!("{} is an empty repository", repo);
+                    path.push(
+                        DetectionStep::new(Probe::ContentsScan, Verdict::Indeterminate)
+                            .with_detail("empty repository"),
+                    );
+                    return Ok((
+                        License::Indeterminate("empty repository".to_owned()),
+                        summary,
+                        path,
+                    ));
+                }
+            }
+            (data["default_branch"].as_str().map(str::to_owned), summary)
+        };
+        {
+            // check for license
+            let resp = self
+                .send(self.client.get(format!("{}/license", url)))
+                .await?;
+            self.note_quota(resp.headers(), floor);
+            if resp.status() == StatusCode::NOT_FOUND {
+                debug!("Got status 404 from GitHub API for testing {}", repo);
+                path.push(DetectionStep::new(Probe::LicenseApi, Verdict::Unlicensed));
+            } else if !resp.status().is_success() {
+                // a rate-limited lookup says nothing about the license
+                return Err(BotError::from_github(
+                    resp,
+                    &format!("Could not fetch license of '{}'", repo),
+                ));
+            } else {
+                let info = LicenseInfo::parse(&resp.json::<Value>().await.unwrap_or_default());
+                debug!(
+                    "{} has license {} at {}",
+                    repo,
+                    info.spdx_id.as_deref().unwrap_or("unknown"),
+                    info.links.html.as_deref().unwrap_or("an unknown path")
+                );
+                let license = license::verdict(&info, policy);
+                let verdict = match license {
+                    License::Found(_) => Verdict::Licensed,
+                    _ => Verdict::Unlicensed,
+                };
+                let step = DetectionStep::new(Probe::LicenseApi, verdict);
+                path.push(match info.spdx_id {
+                    Some(id) => step.with_detail(id),
+                    None => step,
+                });
+                return Ok((license, summary, path));
+            }
+        }
+        // the ref linked, if any, may have a license that the default branch doesn't
+        for git_ref in refs {
+            if Some(git_ref) == default_branch.as_ref() {
+                // already checked
+                return Ok((License::Missing, summary, path));
+            }
+            let request = self.client.get(format!("{}/contents/", url));
+            let files = match self
+                .top_level_files(request, repo, Some(git_ref), floor)
+                .await?
+            {
+                Some(files) => files,
+                None => {
+                    debug!("{} has no ref {}", repo, git_ref);
+                    continue;
+                }
+            };
+            let step = |verdict| DetectionStep::new(Probe::LinkedRef, verdict).with_detail(git_ref);
+            if files.iter().any(|f| is_license_file(f)) {
+                debug!("{} has a license at {}", repo, git_ref);
+                path.push(step(Verdict::Licensed));
+                return Ok((License::Found(None), summary, path));
+            }
+            path.push(step(Verdict::Unlicensed));
+            return Ok((License::Missing, summary, path));
+        }
+        if !refs.is_empty() {
+            debug!(
+                "{} has none of the refs it was linked at, going by its default branch",
+                repo
+            );
+        }
+        Ok((License::Missing, summary, path))
+    }
+
+    /// A repository, by its `owner/name`.
+    pub async fn repository(&self, name: &str, access: &Access) -> Result<Repository, BotError> {
+        let url = format!("{}/repos/{}", self.base_url, name);
+        let request = self.authorized(Method::GET, &url, access).await?;
+        let resp = self.send(request).await?;
+        if !resp.status().is_success() {
+            return Err(BotError::from_github(
+                resp,
+                &format!("Could not fetch '{}'", name),
+            ));
+        }
+        resp.json()
+            .await
+            .map_err(|e| BotError::Parse(e.to_string()))
+    }
+
+    /// Open an issue on a repository, by its `owner/name`.
+    pub async fn open_issue(
+        &self,
+        name: &str,
+        title: &str,
+        body: &str,
+        access: &Access,
+    ) -> Result<Issue, BotError> {
+        let url = format!("{}/repos/{}/issues", self.base_url, name);
+        let request = self
+            .authorized(Method::POST, &url, access)
+            .await?
+            .json(&json!({ "title": title, "body": body }));
+        let resp = self.send(request).await?;
+        if !resp.status().is_success() {
+            return Err(BotError::from_github(
+                resp,
+                &format!("Could not open an issue on '{}'", name),
+            ));
+        }
+        resp.json()
+            .await
+            .map_err(|e| BotError::Parse(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Access, GitHub};
+    use crate::events::SharedEvents;
+    use crate::transport::Transport;
+    use crate::util::GhRepo;
+    use mockito::{mock, server_url, Matcher};
+    use reqwest::Client;
+
+    fn client(prefix: &str) -> GitHub {
+        GitHub::new(
+            Client::new(),
+            format!("{}/{}", server_url(), prefix),
+            None,
+            Transport::Live,
+            SharedEvents::default(),
+        )
+    }
+
+    fn access() -> Access {
+        Access {
+            token: Some("ghp_secret".to_owned()),
+            floor: 5,
+            now: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn reads_the_rate_limit_and_keeps_the_quota() {
+        let _m = mock("GET", "/ghclient_rate/rate_limit")
+            .with_header("x-ratelimit-remaining", "3")
+            .with_header("x-ratelimit-limit", "60")
+            .with_header("x-ratelimit-reset", "1000")
+            .with_body(r#"{"rate":{"remaining":3,"limit":60}}"#)
+            .create();
+        let github = client("ghclient_rate");
+        assert_eq!(github.rate_limit(5).await.unwrap(), (3, 60));
+        let quota = github.events.lock().unwrap().github_quota().unwrap();
+        assert_eq!(quota.remaining, 3);
+
+        let _bad = mock("GET", "/ghclient_badrate/rate_limit")
+            .with_body(r#"{"rate":{}}"#)
+            .create();
+        assert!(client("ghclient_badrate").rate_limit(5).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn lists_only_the_files_of_a_repository() {
+        let _m = mock("GET", "/ghclient_files/repos/someone/project/contents/")
+            .match_header("authorization", "token ghp_secret")
+            .with_body(
+                r#"[{"type":"file","name":"README.md"},{"type":"dir","name":"src"},
+                    {"type":"file","name":"CONTRIBUTING.md"}]"#,
+            )
+            .create();
+        let files = client("ghclient_files")
+            .files(&GhRepo::new("someone", "project"), &access())
+            .await
+            .unwrap();
+        assert_eq!(files, vec!["README.md", "CONTRIBUTING.md"]);
+    }
+
+    #[tokio::test]
+    async fn opens_issues_as_the_configured_token() {
+        let _repo = mock("GET", "/ghclient_issue/repos/someone/project")
+            .match_header("authorization", "token ghp_secret")
+            .with_body(r#"{"has_issues":true,"stargazers_count":12,"size":3}"#)
+            .create();
+        let issue = mock("POST", "/ghclient_issue/repos/someone/project/issues")
+            .match_header("authorization", "token ghp_secret")
+            .match_body(Matcher::PartialJsonString(
+                r#"{"title":"A title","body":"A body"}"#.to_owned(),
+            ))
+            .with_status(201)
+            .with_body(r#"{"number":7,"html_url":"https://github.com/someone/project/issues/7"}"#)
+            .create();
+        let github = client("ghclient_issue");
+        let repo = github
+            .repository("someone/project", &access())
+            .await
+            .unwrap();
+        assert!(repo.has_issues);
+        assert_eq!(repo.stargazers_count, 12);
+        let opened = github
+            .open_issue("someone/project", "A title", "A body", &access())
+            .await
+            .unwrap();
+        issue.assert();
+        assert_eq!(opened.number, 7);
+        assert_eq!(
+            opened.html_url,
+            "https://github.com/someone/project/issues/7"
+        );
+    }
+}
@@ -0,0 +1,450 @@
+use anyhow::{anyhow, Context, Result};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+    path::Path,
+    time::{Duration, UNIX_EPOCH},
+};
+
+use crate::paths::history_file;
+use crate::resolved;
+use crate::util::{author_key, fold_case, format_table, GhRepo};
+
+const USAGE: &str =
+    "Usage: check_for_license history [--author <username>] [--repo <org/name>] [--json]";
+
+/// A reply the bot made to a post's author about one repository.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct ReplyRecord {
+    pub author: String,
+    /// The repository, as `org/repo`.
+    pub repo: String,
+    /// Fullname of the post replied to.
+    pub post: String,
+    /// Fullname of the bot's comment, if Reddit said what it was.
+    pub comment: Option<String>,
+    /// Unix timestamp of the reply.
+    pub at: u64,
+    /// Unix timestamp of when the repository was found to have a license
+    /// after all, and that was acknowledged.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resolved: Option<u64>,
+}
+
+/// Load every reply the bot has made from the state directory.
+///
+/// Lines that can't be read are skipped, so one bad write doesn't lose the rest.
+pub fn load(dir: &Path) -> Result<Vec<ReplyRecord>> {
+    let path = history_file(dir);
+    if !path.exists() {
+        return Ok(vec![]);
+    }
+    let data = fs::read_to_string(&path).with_context(|| format!("reading {}", path.display()))?;
+    let mut records = vec![];
+    for line in data.lines().filter(|l| !l.trim().is_empty()) {
+        match serde_json::from_str(line) {
+            Ok(record) => records.push(record),
+            Err(e) => warn!("Skipping unreadable reply in {}: {}", path.display(), e),
+        }
+    }
+    Ok(records)
+}
+
+/// Add replies to the history in the state directory.
+pub fn append(dir: &Path, records: &[ReplyRecord]) -> Result<()> {
+    let path = history_file(dir);
+    let mut lines = String::new();
+    for record in records {
+        lines.push_str(&serde_json::to_string(record)?);
+        lines.push('\n');
+    }
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .and_then(|mut file| file.write_all(lines.as_bytes()))
+        .with_context(|| format!("writing {}", path.display()))
+}
+
+/// Mark the replies in a comment about a repository as resolved at `at`,
+/// rewriting the history in the state directory.
+pub fn resolve(dir: &Path, comment: &str, repo: &str, at: u64) -> Result<()> {
+    let mut records = load(dir)?;
+    mark_resolved(&mut records, comment, repo, at);
+    rewrite(dir, &records)
+}
+
+/// Forget the replies in the state directory that `expired` says can go,
+/// returning how many there were.
+pub fn prune(dir: &Path, now: u64, horizon: u64) -> Result<usize> {
+    let records = load(dir)?;
+    let expired = expired(&records, now, horizon);
+    if expired > 0 {
+        rewrite(dir, &records[expired..])?;
+    }
+    Ok(expired)
+}
+
+/// Replace the history in the state directory with `records`.
+fn rewrite(dir: &Path, records: &[ReplyRecord]) -> Result<()> {
+    let path = history_file(dir);
+    let mut lines = String::new();
+    for record in records {
+        lines.push_str(&serde_json::to_string(record)?);
+        lines.push('\n');
+    }
+    let tmp = path.with_extension("jsonl.tmp");
+    fs::write(&tmp, lines)
+        .and_then(|_| fs::rename(&tmp, &path))
+        .with_context(|| format!("writing {}", path.display()))
+}
+
+/// Mark the records of a comment about a repository as resolved at `at`.
+pub fn mark_resolved(records: &mut [ReplyRecord], comment: &str, repo: &str, at: u64) {
+    for record in records
+        .iter_mut()
+        .filter(|r| r.comment.as_deref() == Some(comment) && fold_case(&r.repo) == fold_case(repo))
+    {
+        record.resolved = Some(at);
+    }
+}
+
+/// How many of the oldest replies can be forgotten at `now`: those made at
+/// least `horizon` seconds before, up to the first that's still needed.
+///
+/// A reply is needed for as long as its repositories are checked for having
+/// gained a license, unless they were found to. Only the oldest are counted,
+/// so replies added meanwhile don't change which these are.
+pub fn expired(records: &[ReplyRecord], now: u64, horizon: u64) -> usize {
+    records
+        .iter()
+        .take_while(|r| {
+            let age = now.saturating_sub(r.at);
+            let checked = r.comment.is_some() && r.resolved.is_none();
+            age >= horizon && (!checked || age >= resolved::CHECK_WINDOW)
+        })
+        .count()
+}
+
+/// Usernames are compared without case, and with or without a `u/` prefix.
+pub(crate) fn same_author(a: &str, b: &str) -> bool {
+    author_key(a) == author_key(b)
+}
+
+/// Which replies to look for; an empty query matches all of them.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Query {
+    pub author: Option<String>,
+    /// The repository, as `org/repo`.
+    pub repo: Option<String>,
+}
+
+impl Query {
+    pub fn matches(&self, record: &ReplyRecord) -> bool {
+        let author = match &self.author {
+            Some(author) => same_author(author, &record.author),
+            None => true,
+        };
+        let repo = match &self.repo {
+            Some(repo) => fold_case(repo) == fold_case(&record.repo),
+            None => true,
+        };
+        author && repo
+    }
+}
+
+/// The replies that match a query, oldest first.
+pub fn matching<'a>(records: &'a [ReplyRecord], query: &Query) -> Vec<&'a ReplyRecord> {
+    records.iter().filter(|r| query.matches(r)).collect()
+}
+
+/// Whether the bot has replied to an author about a repository in the last
+/// `ttl` seconds. A `ttl` of 0 turns this off, as does an unknown author.
+pub fn replied_recently(
+    records: &[ReplyRecord],
+    author: &str,
+    repo: &GhRepo,
+    now: u64,
+    ttl: u64,
+) -> bool {
+    if ttl == 0 || author.is_empty() || author == "[deleted]" {
+        return false;
+    }
+    let key = repo.canonical_key();
+    records.iter().any(|r| {
+        same_author(&r.author, author)
+            && fold_case(&r.repo) == key
+            && now.saturating_sub(r.at) < ttl
+    })
+}
+
+fn table(records: &[&ReplyRecord]) -> String {
+    let rows: Vec<Vec<String>> = records
+        .iter()
+        .map(|r| {
+            vec![
+                humantime::format_rfc3339_seconds(UNIX_EPOCH + Duration::from_secs(r.at))
+                    .to_string(),
+                r.author.clone(),
+                r.repo.clone(),
+                r.post.clone(),
+                r.comment.clone().unwrap_or_else(|| "-".to_owned()),
+            ]
+        })
+        .collect();
+    format_table(&["WHEN", "AUTHOR", "REPO", "POST", "COMMENT"], &rows)
+}
+
+/// Run a `history` subcommand against the reply history, returning its output.
+pub fn run(dir: &Path, args: &[String]) -> Result<String> {
+    let mut query = Query::default();
+    let mut json = false;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--author" => query.author = Some(args.next().ok_or_else(|| anyhow!(USAGE))?.clone()),
+            "--repo" => query.repo = Some(args.next().ok_or_else(|| anyhow!(USAGE))?.clone()),
+            "--json" => json = true,
+            _ => return Err(anyhow!(USAGE)),
+        }
+    }
+    let records = load(dir)?;
+    let found = matching(&records, &query);
+    if json {
+        return Ok(format!("{}\n", serde_json::to_string_pretty(&found)?));
+    }
+    if found.is_empty() {
+        return Ok("No matching replies\n".to_owned());
+    }
+    Ok(table(&found))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        append, expired, load, matching, prune, replied_recently, resolve, run, Query, ReplyRecord,
+    };
+    use crate::util::GhRepo;
+
+    const DAY: u64 = 24 * 60 * 60;
+
+    fn record(author: &str, repo: &str, post: &str, at: u64) -> ReplyRecord {
+        ReplyRecord {
+            author: author.to_owned(),
+            repo: repo.to_owned(),
+            post: post.to_owned(),
+            comment: Some(format!("t1_{}", post.trim_start_matches("t3_"))),
+            at,
+            resolved: None,
+        }
+    }
+
+    fn records() -> Vec<ReplyRecord> {
+        vec![
+            record("alice", "someone/project", "t3_a", 1_600_000_000),
+            record("bob", "someone/project", "t3_b", 1_600_000_000 + DAY),
+            record("Alice", "other/thing", "t3_c", 1_600_000_000 + 2 * DAY),
+        ]
+    }
+
+    #[test]
+    fn queries() {
+        let records = records();
+        let posts = |query: Query| -> Vec<String> {
+            matching(&records, &query)
+                .iter()
+                .map(|r| r.post.clone())
+                .collect()
+        };
+        assert_eq!(posts(Query::default()).len(), 3);
+        let by_alice = Query {
+            author: Some("u/ALICE".to_owned()),
+            repo: None,
+        };
+        assert_eq!(posts(by_alice), vec!["t3_a", "t3_c"]);
+        let about_project = Query {
+            author: None,
+            repo: Some("Someone/Project".to_owned()),
+        };
+        assert_eq!(posts(about_project), vec!["t3_a", "t3_b"]);
+        let both = Query {
+            author: Some("bob".to_owned()),
+            repo: Some("other/thing".to_owned()),
+        };
+        assert!(posts(both).is_empty());
+    }
+
+    #[test]
+    fn dedup_is_per_author_and_repo_within_the_ttl() {
+        let records = records();
+        let now = 1_600_000_000 + 3 * DAY;
+        let ttl = 7 * DAY;
+        assert!(replied_recently(
+            &records,
+            "alice",
+            &GhRepo::new("someone", "project"),
+            now,
+            ttl
+        ));
+        assert!(replied_recently(
+            &records,
+            "ALICE",
+            &GhRepo::new("Someone", "PROJECT"),
+            now,
+            ttl
+        ));
+        // names outside ASCII compare without case too
+        let cyrillic = [record("Вася", "Org/Проект", "t3_v", 1_600_000_000)];
+        assert!(replied_recently(
+            &cyrillic,
+            "/u/ВАСЯ",
+            &GhRepo::new("org", "проект"),
+            now,
+            ttl
+        ));
+        // someone else posting the same repository still gets a reply
+        assert!(!replied_recently(
+            &records,
+            "carol",
+            &GhRepo::new("someone", "project"),
+            now,
+            ttl
+        ));
+        assert!(!replied_recently(
+            &records,
+            "bob",
+            &GhRepo::new("other", "thing"),
+            now,
+            ttl
+        ));
+        // expired
+        assert!(!replied_recently(
+            &records,
+            "alice",
+            &GhRepo::new("someone", "project"),
+            now,
+            3 * DAY
+        ));
+        assert!(replied_recently(
+            &records,
+            "bob",
+            &GhRepo::new("someone", "project"),
+            now,
+            3 * DAY
+        ));
+        // turned off, or nobody to dedupe by
+        assert!(!replied_recently(
+            &records,
+            "alice",
+            &GhRepo::new("someone", "project"),
+            now,
+            0
+        ));
+        assert!(!replied_recently(
+            &records,
+            "",
+            &GhRepo::new("someone", "project"),
+            now,
+            ttl
+        ));
+        assert!(!replied_recently(
+            &records,
+            "[deleted]",
+            &GhRepo::new("someone", "project"),
+            now,
+            ttl
+        ));
+    }
+
+    #[test]
+    fn stores_records() {
+        let tmp = tempfile::tempdir().unwrap();
+        assert!(load(tmp.path()).unwrap().is_empty());
+        let records = records();
+        append(tmp.path(), &records[..2]).unwrap();
+        append(tmp.path(), &records[2..]).unwrap();
+        assert_eq!(load(tmp.path()).unwrap(), records);
+    }
+
+    #[test]
+    fn resolves_records() {
+        let tmp = tempfile::tempdir().unwrap();
+        let records = records();
+        append(tmp.path(), &records).unwrap();
+        resolve(tmp.path(), "t1_a", "Someone/Project", 5).unwrap();
+        let loaded = load(tmp.path()).unwrap();
+        assert_eq!(loaded[0].resolved, Some(5));
+        assert_eq!(&loaded[1..], &records[1..]);
+    }
+
+    #[test]
+    fn prunes_replies_past_the_horizon() {
+        let now = 1_600_000_000 + 100 * DAY;
+        let at = |days_ago: u64| now - days_ago * DAY;
+        let old =
+            |post: &str, days_ago: u64| record("alice", "someone/project", post, at(days_ago));
+        let uncommented = ReplyRecord {
+            comment: None,
+            ..old("t3_b", 50)
+        };
+        let resolved = ReplyRecord {
+            resolved: Some(at(40)),
+            ..old("t3_c", 45)
+        };
+        let records = vec![
+            old("t3_a", 90),
+            uncommented,
+            resolved,
+            old("t3_d", 20),
+            old("t3_e", 1),
+        ];
+        // a reply still checked for a license is kept past the horizon
+        assert_eq!(expired(&records, now, 10 * DAY), 3);
+        assert_eq!(expired(&records, now, 46 * DAY), 2);
+        assert_eq!(expired(&records, now, 60 * DAY), 1);
+        assert_eq!(expired(&records, now, 100 * DAY), 0);
+        assert_eq!(expired(&[], now, DAY), 0);
+        // an unresolved reply whose repository is still checked holds back
+        // the newer ones too
+        let checked = vec![old("t3_a", 90), old("t3_f", 25), old("t3_b", 50)];
+        assert_eq!(expired(&checked, now, 10 * DAY), 1);
+
+        let tmp = tempfile::tempdir().unwrap();
+        let many: Vec<ReplyRecord> = (0..10_000)
+            .map(|i| old(&format!("t3_{}", i), 100 - i / 100))
+            .collect();
+        append(tmp.path(), &many).unwrap();
+        assert_eq!(prune(tmp.path(), now, 60 * DAY).unwrap(), 4_100);
+        assert_eq!(load(tmp.path()).unwrap(), &many[4_100..]);
+        assert_eq!(prune(tmp.path(), now, 60 * DAY).unwrap(), 0);
+        assert_eq!(load(tmp.path()).unwrap().len(), 5_900);
+    }
+
+    #[test]
+    fn history_command() {
+        let tmp = tempfile::tempdir().unwrap();
+        let args = |args: &[&str]| -> Vec<String> { args.iter().map(|a| a.to_string()).collect() };
+        assert_eq!(
+            run(tmp.path(), &args(&["--author", "alice"])).unwrap(),
+            "No matching replies\n"
+        );
+        let mut records = records();
+        records[1].comment = None;
+        append(tmp.path(), &records).unwrap();
+
+        assert_eq!(
+            run(tmp.path(), &args(&["--repo", "someone/project"])).unwrap(),
+            "WHEN                  AUTHOR  REPO             POST  COMMENT\n\
+             2020-09-13T12:26:40Z  alice   someone/project  t3_a  t1_a\n\
+             2020-09-14T12:26:40Z  bob     someone/project  t3_b  -\n"
+        );
+        let json = run(tmp.path(), &args(&["--author", "alice", "--json"])).unwrap();
+        let found: Vec<ReplyRecord> = serde_json::from_str(&json).unwrap();
+        assert_eq!(found, vec![records[0].clone(), records[2].clone()]);
+
+        assert!(run(tmp.path(), &args(&["--author"])).is_err());
+        assert!(run(tmp.path(), &args(&["--everything"])).is_err());
+    }
+}
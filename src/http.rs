@@ -0,0 +1,681 @@
+use anyhow::Result;
+use hyper::{
+    header,
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server, StatusCode,
+};
+use log::error;
+use serde_json::json;
+use std::{convert::Infallible, net::SocketAddr};
+use tokio::sync::watch;
+
+use crate::events::{self, Event, SharedEvents};
+use crate::feed;
+use crate::models::Config;
+use crate::policy;
+use crate::queue::{ApprovalQueue, QueueError};
+use crate::reply;
+use crate::skip::SkipReason;
+
+/// Events listed when a request doesn't ask for a number.
+const DEFAULT_LIMIT: usize = 50;
+/// Most events listed in one response.
+const MAX_LIMIT: usize = 500;
+const DAY: u64 = 24 * 60 * 60;
+
+/// The bot's optional HTTP listener, serving a dashboard of recent activity.
+#[derive(Clone, Debug)]
+pub struct Dashboard {
+    events: SharedEvents,
+    config: watch::Receiver<Config>,
+}
+
+impl Dashboard {
+    pub fn new(events: SharedEvents, config: watch::Receiver<Config>) -> Self {
+        Self { events, config }
+    }
+
+    /// Whether a request carries the configured token, if one is required.
+    ///
+    /// The token can also be given as a `token` query parameter, so the HTML
+    /// page can be opened in a browser.
+    fn authorized(&self, req: &Request<Body>) -> bool {
+        let token = match &self.config.borrow().http_token {
+            Some(t) => t.clone(),
+            None => return true,
+        };
+        let bearer = req
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "))
+            .map(|v| same_token(v, &token));
+        bearer.unwrap_or_else(|| query_param(req, "token").is_some_and(|v| same_token(&v, &token)))
+    }
+
+    /// Answer a single request.
+    pub fn handle(&self, req: &Request<Body>) -> Response<Body> {
+        if !self.authorized(req) {
+            return Response::builder()
+                .status(StatusCode::UNAUTHORIZED)
+                .header(header::WWW_AUTHENTICATE, "Bearer")
+                .body(Body::from("Unauthorized"))
+                .unwrap();
+        }
+        let limit = query_param(req, "limit")
+            .and_then(|l| l.parse().ok())
+            .unwrap_or(DEFAULT_LIMIT)
+            .min(MAX_LIMIT);
+        match (req.method(), req.uri().path()) {
+            (&Method::GET, "/") => Response::builder()
+                .header(header::CONTENT_TYPE, "text/html; charset=utf-8")
+                .body(Body::from(self.render_html(limit)))
+                .unwrap(),
+            (&Method::GET, "/api/recent") => Response::builder()
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(self.recent_json(limit).to_string()))
+                .unwrap(),
+            (&Method::GET, "/feed.atom") => Response::builder()
+                .header(header::CONTENT_TYPE, "application/atom+xml")
+                .body(Body::from(feed::render(
+                    &self.events.lock().unwrap().recent(MAX_LIMIT),
+                )))
+                .unwrap(),
+            (&Method::GET, "/policy") => self.policy(query_param(req, "subreddit").as_deref()),
+            (&Method::GET, "/api/queue") => match self.queue_json() {
+                Ok(json) => Response::builder()
+                    .header(header::CONTENT_TYPE, "application/json")
+                    .body(Body::from(json.to_string()))
+                    .unwrap(),
+                Err(e) => error_response(&e),
+            },
+            (&Method::POST, path) if path.starts_with("/api/queue/") => {
+                // an open dashboard can show the queue, but not act on it
+                if self.config.borrow().http_token.is_none() {
+                    return Response::builder()
+                        .status(StatusCode::FORBIDDEN)
+                        .body(Body::from("Set CFL_HTTP_TOKEN to act on the queue"))
+                        .unwrap();
+                }
+                self.act_on_queue(&path["/api/queue/".len()..])
+            }
+            _ => not_found(),
+        }
+    }
+
+    /// The policy in effect in a subreddit, or the first one watched, as
+    /// markdown.
+    fn policy(&self, subreddit: Option<&str>) -> Response<Body> {
+        let config = self.config.borrow().clone();
+        match policy::subreddit(&config, subreddit) {
+            Ok(subreddit) => Response::builder()
+                .header(header::CONTENT_TYPE, "text/markdown; charset=utf-8")
+                .body(Body::from(policy::render(&config, &subreddit)))
+                .unwrap(),
+            Err(e) => Response::builder()
+                .status(StatusCode::BAD_REQUEST)
+                .body(Body::from(e.to_string()))
+                .unwrap(),
+        }
+    }
+
+    /// Replies in the approval queue, with the comment each would make.
+    fn queue_json(&self) -> Result<serde_json::Value> {
+        let config = self.config.borrow().clone();
+        let items = ApprovalQueue::load(&config.state_dir)?
+            .items()
+            .iter()
+            .map(|item| {
+                Ok(json!({
+                    "id": item.id,
+                    "status": item.status,
+                    "fullname": item.reply.fullname,
+                    "title": item.reply.details.title,
+                    "subreddit": item.reply.details.subreddit,
+                    "permalink": item.reply.details.permalink,
+                    "repos": item.reply.repos,
+                    "queued_at": item.reply.queued_at,
+                    "preview": reply::text(&item.reply, &config)?,
+                }))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(json!({ "items": items }))
+    }
+
+    /// Approve or reject a queued reply, given the `{id}/{action}` part of the path.
+    fn act_on_queue(&self, rest: &str) -> Response<Body> {
+        let (id, action) = match rest.split_once('/') {
+            Some((id, action)) => (id, action),
+            None => return not_found(),
+        };
+        let id: u64 = match id.parse() {
+            Ok(id) => id,
+            Err(_) => return not_found(),
+        };
+        let dir = self.config.borrow().state_dir.clone();
+        let result = match action {
+            "approve" => ApprovalQueue::update(&dir, |q| Ok(q.approve(id)?)),
+            "reject" => ApprovalQueue::update(&dir, |q| q.reject(&dir, id).map(|_| ())),
+            _ => return not_found(),
+        };
+        match result {
+            Ok(()) => Response::builder()
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(
+                    json!({ "id": id, "action": action }).to_string(),
+                ))
+                .unwrap(),
+            Err(e) => error_response(&e),
+        }
+    }
+
+    /// Reply counts, the state of the hourly reply cap and GitHub's rate limit.
+    fn totals(&self) -> serde_json::Value {
+        let now = events::now();
+        let events = self.events.lock().unwrap();
+        let last_hour = events.replies_since(now.saturating_sub(60 * 60));
+        json!({
+            "replies_today": events.replies_since(now - now % DAY),
+            "replies_this_week": events.replies_since(now.saturating_sub(7 * DAY)),
+            "replies_last_hour": last_hour,
+            "reply_cap_per_hour": self.config.borrow().max_replies_per_hour,
+            "restarts": events.restarts(),
+            "compactions": events.compactions(),
+            "reply_latency": events.latencies().summary(),
+            "tasks": events.task_statuses(),
+            "queues": events.queues(),
+            "github_quota": events.github_quota(),
+            "subreddits": events.stats(),
+        })
+    }
+
+    fn recent_json(&self, limit: usize) -> serde_json::Value {
+        json!({
+            "totals": self.totals(),
+            "events": self.events.lock().unwrap().recent(limit),
+        })
+    }
+
+    fn render_html(&self, limit: usize) -> String {
+        let totals = self.totals();
+        let cap = match totals["reply_cap_per_hour"].as_u64() {
+            Some(cap) => format!("{} of {} this hour", totals["replies_last_hour"], cap),
+            None => format!("{} this hour, no cap", totals["replies_last_hour"]),
+        };
+        let rows: String = self
+            .events
+            .lock()
+            .unwrap()
+            .recent(limit)
+            .iter()
+            .map(render_row)
+            .collect();
+        format!(
+            r#"<!DOCTYPE html>
+<html>
+<head><meta charset="utf-8"><title>check_for_license</title></head>
+<body>
+<h1>check_for_license</h1>
+<p>Replies today: {} &middot; last 7 days: {} &middot; {}</p>
+<table>
+<tr><th>Time (UTC)</th><th>Post</th><th>Subreddit</th><th>Repository</th><th>Verdict</th><th>Action</th><th>Skipped because</th></tr>
+{}</table>
+</body>
+</html>
+"#,
+            totals["replies_today"],
+            totals["replies_this_week"],
+            escape(&cap),
+            rows
+        )
+    }
+}
+
+fn not_found() -> Response<Body> {
+    Response::builder()
+        .status(StatusCode::NOT_FOUND)
+        .body(Body::from("Not found"))
+        .unwrap()
+}
+
+/// Answer with the status that fits an error, keeping unexpected ones in the log.
+fn error_response(error: &anyhow::Error) -> Response<Body> {
+    let status = match error.downcast_ref::<QueueError>() {
+        Some(QueueError::UnknownId(_)) => StatusCode::NOT_FOUND,
+        Some(QueueError::AlreadyApproved(_)) => StatusCode::CONFLICT,
+        None => {
+            error!("HTTP request failed: {:#}", error);
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    };
+    Response::builder()
+        .status(status)
+        .body(Body::from(error.to_string()))
+        .unwrap()
+}
+
+/// A row of the events table, with the event sanitized and then escaped, as
+/// titles and repository names come from whoever posted them.
+fn render_row(event: &Event) -> String {
+    let event = event.clone().sanitized();
+    format!(
+        "<tr><td>{}</td><td>{}</td><td>/r/{}</td><td><a href=\"https://github.com/{}\">{}</a></td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+        event.at,
+        escape(if event.title.is_empty() {
+            &event.fullname
+        } else {
+            &event.title
+        }),
+        escape(&event.subreddit),
+        escape(&event.repo),
+        escape(&event.repo),
+        escape(&event.verdict),
+        escape(&event.action),
+        event
+            .skip_reason()
+            .map(SkipReason::label)
+            .unwrap_or_default()
+    )
+}
+
+/// Escape text for inclusion in HTML.
+pub(crate) fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+/// Compare a given token to the configured one, taking the same time
+/// wherever they first differ.
+fn same_token(given: &str, expected: &str) -> bool {
+    let (given, expected) = (given.as_bytes(), expected.as_bytes());
+    given.len() == expected.len()
+        && given
+            .iter()
+            .zip(expected)
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+fn query_param(req: &Request<Body>, name: &str) -> Option<String> {
+    req.uri().query()?.split('&').find_map(|pair| {
+        let (key, value) = pair.split_once('=')?;
+        if key == name {
+            Some(value.to_owned())
+        } else {
+            None
+        }
+    })
+}
+
+/// Serve the dashboard until the process exits.
+pub async fn serve(addr: SocketAddr, dashboard: Dashboard) -> Result<()> {
+    let make_service = make_service_fn(move |_| {
+        let dashboard = dashboard.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |req| {
+                let resp = dashboard.handle(&req);
+                async move { Ok::<_, Infallible>(resp) }
+            }))
+        }
+    });
+    Server::try_bind(&addr)?.serve(make_service).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{escape, same_token, Dashboard};
+    use crate::events::{self, Event, SharedEvents};
+    use crate::latency::Stages;
+    use crate::models::Config;
+    use crate::pending::{PendingReply, PostDetails, RepoLink};
+    use crate::preview::RepoSummary;
+    use crate::queue::ApprovalQueue;
+    use crate::quota::Quota;
+    use crate::suppress;
+    use crate::util::GhRepo;
+    use hyper::{body, Body, Request, StatusCode};
+    use std::path::Path;
+    use tokio::sync::watch;
+
+    fn config(token: Option<&str>) -> Config {
+        Config {
+            max_replies_per_hour: Some(5),
+            http_token: token.map(str::to_owned),
+            ..Config::for_tests()
+        }
+    }
+
+    fn dashboard(token: Option<&str>) -> Dashboard {
+        let events = SharedEvents::default();
+        for (title, action) in &[("<b>Old</b>", "reply cap reached"), ("New", "replied")] {
+            events.lock().unwrap().record(Event {
+                at: events::now(),
+                fullname: "t3_x".to_owned(),
+                title: title.to_string(),
+                subreddit: "rust".to_owned(),
+                permalink: String::new(),
+                author: "someone".to_owned(),
+                repo: "someone/project".to_owned(),
+                verdict: "unlicensed".to_owned(),
+                license: None,
+                action: action.to_string(),
+                removed_license: None,
+                detection_path: vec![],
+            });
+        }
+        let (_, config) = watch::channel(config(token));
+        Dashboard::new(events, config)
+    }
+
+    fn get(uri: &str, auth: Option<&str>) -> Request<Body> {
+        let mut req = Request::get(uri);
+        if let Some(a) = auth {
+            req = req.header("authorization", a);
+        }
+        req.body(Body::empty()).unwrap()
+    }
+
+    async fn body_of(resp: hyper::Response<Body>) -> String {
+        let bytes = body::to_bytes(resp.into_body()).await.unwrap();
+        String::from_utf8(bytes.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn recent_json_shape() {
+        let resp = dashboard(None).handle(&get("/api/recent?limit=1", None));
+        assert_eq!(resp.status(), StatusCode::OK);
+        let json: serde_json::Value = serde_json::from_str(&body_of(resp).await).unwrap();
+
+        assert_eq!(json["totals"]["replies_today"], 1);
+        assert_eq!(json["totals"]["replies_this_week"], 1);
+        assert_eq!(json["totals"]["replies_last_hour"], 1);
+        assert_eq!(json["totals"]["reply_cap_per_hour"], 5);
+        assert_eq!(json["totals"]["restarts"], 0);
+        assert_eq!(json["totals"]["compactions"]["runs"], 0);
+        assert!(json["totals"]["tasks"].is_object());
+        assert_eq!(json["totals"]["reply_latency"]["replies"], 0);
+        assert!(json["totals"]["reply_latency"]["p90"].is_null());
+        assert!(json["totals"]["queues"]["waiting"].is_object());
+        assert!(json["totals"]["github_quota"].is_null());
+        let events = json["events"].as_array().unwrap();
+        assert_eq!(events.len(), 1);
+        let event = &events[0];
+        assert_eq!(event["title"], "New");
+        assert_eq!(event["subreddit"], "rust");
+        assert_eq!(event["repo"], "someone/project");
+        assert_eq!(event["verdict"], "unlicensed");
+        assert_eq!(event["action"], "replied");
+        assert!(event["at"].as_u64().unwrap() > 0);
+    }
+
+    #[tokio::test]
+    async fn recent_json_has_github_quota() {
+        let dashboard = dashboard(None);
+        let quota = Quota {
+            remaining: 42,
+            limit: 60,
+            reset: 1_600_000_000,
+        };
+        dashboard
+            .events
+            .lock()
+            .unwrap()
+            .record_github_quota(quota, 5);
+        let resp = dashboard.handle(&get("/api/recent", None));
+        let json: serde_json::Value = serde_json::from_str(&body_of(resp).await).unwrap();
+        assert_eq!(
+            json["totals"]["github_quota"],
+            serde_json::json!({ "remaining": 42, "limit": 60, "reset": 1_600_000_000 })
+        );
+    }
+
+    #[tokio::test]
+    async fn html_page_escapes_titles() {
+        let resp = dashboard(None).handle(&get("/", None));
+        assert_eq!(resp.status(), StatusCode::OK);
+        let html = body_of(resp).await;
+        assert!(html.contains("&lt;b&gt;Old&lt;/b&gt;"), "{}", html);
+        assert!(html.contains("1 of 5 this hour"), "{}", html);
+        assert!(
+            html.contains("<td>reply cap reached</td><td>reply cap reached</td>"),
+            "{}",
+            html
+        );
+    }
+
+    #[tokio::test]
+    async fn html_page_defuses_xss_shaped_posts() {
+        let hostile = Event {
+            at: 1,
+            fullname: "t3_x".to_owned(),
+            title: "<script>alert(1)</script>\u{202e}".to_owned(),
+            subreddit: "rust\"><svg onload=alert(1)>".to_owned(),
+            permalink: "javascript:alert(1)".to_owned(),
+            author: "someone".to_owned(),
+            repo: "a/b\"><img src=x onerror=alert(1)>".to_owned(),
+            verdict: "unlicensed".to_owned(),
+            license: None,
+            action: "replied\u{0}<iframe>".to_owned(),
+            removed_license: None,
+            detection_path: vec![],
+        };
+        // rendered even if it got past being sanitized when it was recorded
+        let row = super::render_row(&hostile);
+        let dashboard = dashboard(None);
+        dashboard.events.lock().unwrap().record(hostile);
+        let html = body_of(dashboard.handle(&get("/", None))).await;
+        for html in &[row, html] {
+            for tag in &["<script", "<img", "<svg", "<iframe", "\u{202e}", "\u{0}"] {
+                assert!(!html.contains(tag), "{} in {}", tag, html);
+            }
+            assert!(
+                html.contains("&lt;script&gt;alert(1)&lt;/script&gt;"),
+                "{}",
+                html
+            );
+            assert!(
+                html.contains(
+                    "href=\"https://github.com/a/b&quot;&gt;&lt;img src=x onerror=alert(1)&gt;\""
+                ),
+                "{}",
+                html
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn token_is_required_when_configured() {
+        let dashboard = dashboard(Some("sekrit"));
+        let cases = vec![
+            (get("/api/recent", None), StatusCode::UNAUTHORIZED),
+            (
+                get("/api/recent", Some("Bearer wrong")),
+                StatusCode::UNAUTHORIZED,
+            ),
+            (get("/api/recent", Some("Bearer sekrit")), StatusCode::OK),
+            (get("/?token=sekrit", None), StatusCode::OK),
+            (get("/nope", Some("Bearer sekrit")), StatusCode::NOT_FOUND),
+        ];
+        for (req, status) in cases {
+            assert_eq!(dashboard.handle(&req).status(), status, "{}", req.uri());
+        }
+    }
+
+    #[tokio::test]
+    async fn serves_policy() {
+        let dashboard = dashboard(None);
+        let resp = dashboard.handle(&get("/policy", None));
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            resp.headers()["content-type"],
+            "text/markdown; charset=utf-8"
+        );
+        assert!(body_of(resp).await.starts_with("# Policy in /r/test\n"));
+        let resp = dashboard.handle(&get("/policy?subreddit=r/rust", None));
+        let policy = body_of(resp).await;
+        assert!(policy.starts_with("# Policy in /r/rust\n"), "{}", policy);
+        assert!(policy.contains("| Reply cap | 5 per hour |"), "{}", policy);
+        let resp = dashboard.handle(&get("/policy?subreddit=not%20one", None));
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn serves_atom_feed() {
+        let resp = dashboard(None).handle(&get("/feed.atom", None));
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers()["content-type"], "application/atom+xml");
+        let feed = body_of(resp).await;
+        assert!(feed.contains("<id>urn:reddit:t3_x</id>"), "{}", feed);
+    }
+
+    fn queued_dashboard(dir: &Path, token: Option<&str>) -> Dashboard {
+        ApprovalQueue::update(dir, |q| {
+            for (name, repo) in &[("t3_q1", "one"), ("t3_q2", "two")] {
+                q.push(PendingReply {
+                    fullname: name.to_string(),
+                    repos: vec![RepoLink {
+                        repo: GhRepo {
+                            owner: "queued".to_owned(),
+                            name: repo.to_string(),
+                        },
+                        url: format!("https://github.com/queued/{}", repo),
+                        removed_license: None,
+                        summary: RepoSummary::default(),
+                    }],
+                    details: PostDetails {
+                        title: format!("Post about {}", repo),
+                        ..PostDetails::default()
+                    },
+                    queued_at: 1,
+                    deferred: false,
+                    earlier: None,
+                    cross_post: None,
+                    check_after: None,
+                    not_before: None,
+                    stages: Stages::default(),
+                });
+            }
+            Ok(())
+        })
+        .unwrap();
+        let mut config = config(token);
+        config.state_dir = dir.to_owned();
+        let (_, config) = watch::channel(config);
+        Dashboard::new(SharedEvents::default(), config)
+    }
+
+    fn post(uri: &str, auth: Option<&str>) -> Request<Body> {
+        let mut req = Request::post(uri);
+        if let Some(a) = auth {
+            req = req.header("authorization", a);
+        }
+        req.body(Body::empty()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn lists_queue_with_previews() {
+        let tmp = tempfile::tempdir().unwrap();
+        let resp = queued_dashboard(tmp.path(), None).handle(&get("/api/queue", None));
+        assert_eq!(resp.status(), StatusCode::OK);
+        let json: serde_json::Value = serde_json::from_str(&body_of(resp).await).unwrap();
+        let items = json["items"].as_array().unwrap();
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0]["id"], 1);
+        assert_eq!(items[0]["status"], "pending");
+        assert_eq!(items[0]["fullname"], "t3_q1");
+        assert_eq!(items[0]["title"], "Post about one");
+        assert_eq!(items[0]["repos"][0]["repo"], "one");
+        let preview = items[0]["preview"].as_str().unwrap();
+        assert!(
+            preview.starts_with("The linked GitHub repository does not contain a license."),
+            "{}",
+            preview
+        );
+        assert!(preview.contains(r#"Reply "!ignore""#), "{}", preview);
+    }
+
+    #[tokio::test]
+    async fn approves_and_rejects_over_http() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dashboard = queued_dashboard(tmp.path(), Some("sekrit"));
+        let auth = Some("Bearer sekrit");
+
+        let resp = dashboard.handle(&post("/api/queue/1/approve", auth));
+        assert_eq!(resp.status(), StatusCode::OK);
+        let resp = dashboard.handle(&post("/api/queue/2/reject", auth));
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let queue = ApprovalQueue::load(tmp.path()).unwrap();
+        assert_eq!(queue.items().len(), 1);
+        assert_eq!(queue.approved()[0].id, 1);
+        let suppressed = suppress::load(tmp.path()).unwrap();
+        assert!(suppress::is_suppressed(
+            &suppressed,
+            &GhRepo::new("queued", "two"),
+            0
+        ));
+    }
+
+    #[tokio::test]
+    async fn queue_actions_fail_cleanly() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dashboard = queued_dashboard(tmp.path(), Some("sekrit"));
+        let auth = Some("Bearer sekrit");
+        let cases = vec![
+            (post("/api/queue/1/approve", None), StatusCode::UNAUTHORIZED),
+            (
+                post("/api/queue/1/approve", Some("Bearer wrong")),
+                StatusCode::UNAUTHORIZED,
+            ),
+            (get("/api/queue", None), StatusCode::UNAUTHORIZED),
+            (post("/api/queue/9/approve", auth), StatusCode::NOT_FOUND),
+            (post("/api/queue/9/reject", auth), StatusCode::NOT_FOUND),
+            (post("/api/queue/x/approve", auth), StatusCode::NOT_FOUND),
+            (post("/api/queue/1/nope", auth), StatusCode::NOT_FOUND),
+            (post("/api/queue/1/approve", auth), StatusCode::OK),
+            (post("/api/queue/1/approve", auth), StatusCode::CONFLICT),
+        ];
+        for (req, status) in cases {
+            assert_eq!(dashboard.handle(&req).status(), status, "{}", req.uri());
+        }
+        // nothing was changed by the failed requests
+        let queue = ApprovalQueue::load(tmp.path()).unwrap();
+        assert_eq!(queue.items().len(), 2);
+        assert!(suppress::load(tmp.path()).unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn queue_actions_need_a_token() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dashboard = queued_dashboard(tmp.path(), None);
+        for uri in &["/api/queue/1/approve", "/api/queue/2/reject"] {
+            let resp = dashboard.handle(&post(uri, None));
+            assert_eq!(resp.status(), StatusCode::FORBIDDEN, "{}", uri);
+        }
+        let resp = dashboard.handle(&get("/api/queue", None));
+        assert_eq!(resp.status(), StatusCode::OK);
+        let queue = ApprovalQueue::load(tmp.path()).unwrap();
+        assert!(queue.approved().is_empty());
+        assert!(suppress::load(tmp.path()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn compares_tokens() {
+        assert!(same_token("sekrit", "sekrit"));
+        assert!(!same_token("sekrix", "sekrit"));
+        assert!(!same_token("sekri", "sekrit"));
+        assert!(!same_token("", "sekrit"));
+    }
+
+    #[test]
+    fn escapes_html() {
+        assert_eq!(
+            escape(r#"<a href="x">&'"#),
+            "&lt;a href=&quot;x&quot;&gt;&amp;&#39;"
+        );
+    }
+}
@@ -0,0 +1,182 @@
+use anyhow::{anyhow, Context, Result};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+    path::Path,
+    str::FromStr,
+};
+
+use crate::paths::issue_file;
+use crate::reply::{render, SOURCE_URL};
+use crate::util::GhRepo;
+
+/// Title of the issues the bot opens.
+pub const ISSUE_TITLE: &str = "Consider adding a LICENSE file";
+const ISSUE_BODY: &str = r#"Hi! This repository was linked on Reddit ({post}), but it doesn't have a license. Without one, nobody else can legally use, change or share the code, even though it's public.
+
+If you'd like others to be able to use it, https://choosealicense.com can help you pick a license, and GitHub explains how to add one here: https://help.github.com/en/github/creating-cloning-and-archiving-repositories/licensing-a-repository
+
+If the code isn't meant to be used by others, feel free to close this issue.
+
+---
+
+I'm a bot, and I won't open another issue here. [source]({source})"#;
+
+/// Whether the bot opens issues on the unlicensed repositories it finds.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum IssueMode {
+    /// Only reply on Reddit.
+    Off,
+    /// Open an issue rather than replying on Reddit.
+    Instead,
+    /// Reply on Reddit and open an issue.
+    Also,
+}
+
+impl FromStr for IssueMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "off" => Ok(IssueMode::Off),
+            "instead" => Ok(IssueMode::Instead),
+            "also" => Ok(IssueMode::Also),
+            _ => Err(anyhow!("Issue mode must be 'off', 'instead' or 'also'")),
+        }
+    }
+}
+
+impl IssueMode {
+    /// Whether replies are made on Reddit in this mode.
+    pub fn replies(self) -> bool {
+        self != IssueMode::Instead
+    }
+
+    /// Whether issues are opened in this mode.
+    pub fn files_issues(self) -> bool {
+        self != IssueMode::Off
+    }
+}
+
+/// An issue the bot opened on a repository.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct IssueRecord {
+    /// The repository, as `org/repo`.
+    pub repo: String,
+    pub number: u64,
+    pub url: String,
+    /// Fullname of the post that linked the repository.
+    pub post: String,
+    /// Unix timestamp of when the issue was opened.
+    pub at: u64,
+}
+
+/// The body of an issue about a repository linked from a post.
+pub fn body(permalink: &str) -> Result<String> {
+    let post = if permalink.is_empty() {
+        "a post"
+    } else {
+        permalink
+    };
+    render(
+        ISSUE_BODY,
+        &[("post", Some(post)), ("source", Some(SOURCE_URL))],
+    )
+}
+
+/// Whether the bot has already opened an issue on a repository.
+pub fn already_filed(records: &[IssueRecord], repo: &GhRepo) -> bool {
+    let key = repo.canonical_key();
+    records.iter().any(|r| {
+        r.repo
+            .parse::<GhRepo>()
+            .is_ok_and(|r| r.canonical_key() == key)
+    })
+}
+
+/// Load every issue the bot has opened from the state directory.
+///
+/// Lines that can't be read are skipped, so one bad write doesn't lose the rest.
+pub fn load(dir: &Path) -> Result<Vec<IssueRecord>> {
+    let path = issue_file(dir);
+    if !path.exists() {
+        return Ok(vec![]);
+    }
+    let data = fs::read_to_string(&path).with_context(|| format!("reading {}", path.display()))?;
+    let mut records = vec![];
+    for line in data.lines().filter(|l| !l.trim().is_empty()) {
+        match serde_json::from_str(line) {
+            Ok(record) => records.push(record),
+            Err(e) => warn!("Skipping unreadable issue in {}: {}", path.display(), e),
+        }
+    }
+    Ok(records)
+}
+
+/// Add an issue to those recorded in the state directory.
+pub fn append(dir: &Path, record: &IssueRecord) -> Result<()> {
+    let path = issue_file(dir);
+    let line = format!("{}\n", serde_json::to_string(record)?);
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .and_then(|mut file| file.write_all(line.as_bytes()))
+        .with_context(|| format!("writing {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{already_filed, append, body, load, IssueMode, IssueRecord};
+    use crate::util::GhRepo;
+
+    fn record(repo: &str) -> IssueRecord {
+        IssueRecord {
+            repo: repo.to_owned(),
+            number: 1,
+            url: format!("https://github.com/{}/issues/1", repo),
+            post: "t3_a".to_owned(),
+            at: 1_600_000_000,
+        }
+    }
+
+    #[test]
+    fn parses_modes() {
+        assert_eq!("off".parse::<IssueMode>().unwrap(), IssueMode::Off);
+        assert_eq!("instead".parse::<IssueMode>().unwrap(), IssueMode::Instead);
+        assert_eq!("also".parse::<IssueMode>().unwrap(), IssueMode::Also);
+        assert!("on".parse::<IssueMode>().is_err());
+        assert!(IssueMode::Off.replies() && !IssueMode::Off.files_issues());
+        assert!(!IssueMode::Instead.replies() && IssueMode::Instead.files_issues());
+        assert!(IssueMode::Also.replies() && IssueMode::Also.files_issues());
+    }
+
+    #[test]
+    fn knows_where_issues_were_filed() {
+        let records = vec![record("Someone/Project")];
+        assert!(already_filed(&records, &GhRepo::new("someone", "project")));
+        assert!(!already_filed(&records, &GhRepo::new("someone", "other")));
+        assert!(!already_filed(&[], &GhRepo::new("someone", "project")));
+    }
+
+    #[test]
+    fn body_links_the_post() {
+        let text = body("https://www.reddit.com/r/rust/comments/abc/").unwrap();
+        assert!(text.contains("(https://www.reddit.com/r/rust/comments/abc/)"));
+        assert!(body("").unwrap().contains("(a post)"));
+    }
+
+    #[test]
+    fn records_round_trip() {
+        let tmp = tempfile::tempdir().unwrap();
+        assert!(load(tmp.path()).unwrap().is_empty());
+        append(tmp.path(), &record("a/one")).unwrap();
+        append(tmp.path(), &record("b/two")).unwrap();
+        assert_eq!(
+            load(tmp.path()).unwrap(),
+            vec![record("a/one"), record("b/two")]
+        );
+    }
+}
@@ -0,0 +1,255 @@
+use serde::{Deserialize, Serialize};
+use std::{collections::VecDeque, fmt, time::Duration};
+
+/// Most recent replies whose latencies are kept to work out percentiles.
+const SAMPLES: usize = 1000;
+
+/// When a reply went through each stage on its way to being made, as Unix
+/// timestamps, to tell where the time between a post and the reply went.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Stages {
+    /// When the post was submitted, or 0 if Reddit didn't say.
+    #[serde(default)]
+    pub posted: u64,
+    /// When the bot got to the post in its listing.
+    #[serde(default)]
+    pub seen: u64,
+    /// When the reply was first held back because the reply cap was reached.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub capped: Option<u64>,
+}
+
+/// Where the time between a post and the bot's reply to it went, in seconds.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Breakdown {
+    pub total: u64,
+    /// From the post being submitted until the bot got to it.
+    pub detection: u64,
+    /// From then until the reply could be sent, checking the repositories
+    /// and waiting in queues, such as for the active hours.
+    pub queued: u64,
+    /// Held back by the reply cap.
+    pub capped: u64,
+}
+
+impl Stages {
+    pub fn new(posted: u64, seen: u64) -> Self {
+        Self {
+            posted,
+            seen,
+            capped: None,
+        }
+    }
+
+    /// Whether nothing is known about the stages, as for replies held back
+    /// before they were timestamped.
+    pub fn is_empty(&self) -> bool {
+        *self == Stages::default()
+    }
+
+    /// Note that the reply cap held the reply back at `now`, unless it had
+    /// already.
+    pub fn cap(&mut self, now: u64) {
+        self.capped.get_or_insert(now);
+    }
+
+    /// Where the time went, for a reply made at `replied`, or `None` if it's
+    /// not known when the post was submitted.
+    ///
+    /// Stages out of order, as when Reddit's clock is ahead of the bot's, are
+    /// taken to have been passed through at once.
+    pub fn breakdown(&self, replied: u64) -> Option<Breakdown> {
+        if self.posted == 0 {
+            return None;
+        }
+        let posted = self.posted.min(replied);
+        let seen = self.seen.clamp(posted, replied);
+        let capped = self.capped.map_or(replied, |at| at.clamp(seen, replied));
+        Some(Breakdown {
+            total: replied - posted,
+            detection: seen - posted,
+            queued: capped - seen,
+            capped: replied - capped,
+        })
+    }
+}
+
+fn secs(secs: u64) -> humantime::FormattedDuration {
+    humantime::format_duration(Duration::from_secs(secs))
+}
+
+impl fmt::Display for Breakdown {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} after it was posted: seen after {}, then {} checking and queued, {} held by the reply cap",
+            secs(self.total),
+            secs(self.detection),
+            secs(self.queued),
+            secs(self.capped)
+        )
+    }
+}
+
+/// Seconds from posts to the bot's replies to them, over the most recent
+/// replies.
+#[derive(Clone, Debug, Default)]
+pub struct Latencies {
+    samples: VecDeque<u64>,
+}
+
+/// Percentiles of the reply latencies, in seconds, for the dashboard.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+pub struct LatencySummary {
+    pub replies: usize,
+    pub p50: Option<u64>,
+    pub p90: Option<u64>,
+    pub max: Option<u64>,
+}
+
+impl Latencies {
+    /// Count a reply made `secs` after its post, forgetting the oldest once
+    /// there are more than `SAMPLES`.
+    pub fn record(&mut self, secs: u64) {
+        if self.samples.len() == SAMPLES {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(secs);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// The latency within which `percent` of the replies were made, by
+    /// nearest rank.
+    pub fn percentile(&self, percent: usize) -> Option<u64> {
+        let mut sorted: Vec<u64> = self.samples.iter().copied().collect();
+        sorted.sort_unstable();
+        let rank = (percent * sorted.len()).div_ceil(100);
+        sorted.get(rank.max(1) - 1).copied()
+    }
+
+    pub fn summary(&self) -> LatencySummary {
+        LatencySummary {
+            replies: self.samples.len(),
+            p50: self.percentile(50),
+            p90: self.percentile(90),
+            max: self.samples.iter().max().copied(),
+        }
+    }
+}
+
+impl fmt::Display for Latencies {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let summary = self.summary();
+        match (summary.p50, summary.p90, summary.max) {
+            (Some(p50), Some(p90), Some(max)) => write!(
+                f,
+                "reply latency p50 {}, p90 {}, max {} over {} replies",
+                secs(p50),
+                secs(p90),
+                secs(max),
+                summary.replies
+            ),
+            _ => f.write_str("no reply latency yet"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Breakdown, Latencies, LatencySummary, Stages, SAMPLES};
+
+    #[test]
+    fn breaks_latency_down_by_stage() {
+        let mut stages = Stages::new(1000, 1060);
+        assert_eq!(
+            stages.breakdown(1090),
+            Some(Breakdown {
+                total: 90,
+                detection: 60,
+                queued: 30,
+                capped: 0
+            })
+        );
+        stages.cap(1200);
+        stages.cap(1300);
+        assert_eq!(stages.capped, Some(1200));
+        let breakdown = stages.breakdown(4800).unwrap();
+        assert_eq!(
+            breakdown,
+            Breakdown {
+                total: 3800,
+                detection: 60,
+                queued: 140,
+                capped: 3600
+            }
+        );
+        assert_eq!(
+            breakdown.to_string(),
+            "1h 3m 20s after it was posted: seen after 1m, then 2m 20s checking and queued, \
+             1h held by the reply cap"
+        );
+
+        // not known when it was posted
+        assert_eq!(Stages::new(0, 1060).breakdown(1090), None);
+        // Reddit's clock ahead of the bot's
+        assert_eq!(
+            Stages::new(1100, 1060).breakdown(1090),
+            Some(Breakdown {
+                total: 0,
+                detection: 0,
+                queued: 0,
+                capped: 0
+            })
+        );
+        // saved before stages were timestamped
+        assert_eq!(Stages::new(1000, 0).breakdown(1090).unwrap().queued, 90);
+    }
+
+    #[test]
+    fn works_out_percentiles() {
+        let mut latencies = Latencies::default();
+        assert_eq!(latencies.summary(), LatencySummary::default());
+        assert_eq!(latencies.to_string(), "no reply latency yet");
+
+        for secs in (1..=10).rev() {
+            latencies.record(secs * 60);
+        }
+        assert_eq!(
+            latencies.summary(),
+            LatencySummary {
+                replies: 10,
+                p50: Some(300),
+                p90: Some(540),
+                max: Some(600)
+            }
+        );
+        assert_eq!(latencies.percentile(0), Some(60));
+        assert_eq!(latencies.percentile(100), Some(600));
+        assert_eq!(
+            latencies.to_string(),
+            "reply latency p50 5m, p90 9m, max 10m over 10 replies"
+        );
+
+        let mut one = Latencies::default();
+        one.record(42);
+        assert_eq!(
+            (one.percentile(50), one.percentile(90)),
+            (Some(42), Some(42))
+        );
+    }
+
+    #[test]
+    fn keeps_only_the_most_recent_replies() {
+        let mut latencies = Latencies::default();
+        latencies.record(1_000_000);
+        for _ in 0..SAMPLES {
+            latencies.record(5);
+        }
+        let summary = latencies.summary();
+        assert_eq!(summary.replies, SAMPLES);
+        assert_eq!(summary.max, Some(5));
+    }
+}
@@ -0,0 +1,64 @@
+pub mod audit;
+pub mod backfill;
+pub mod bot;
+pub mod bundle;
+pub mod check;
+pub mod claims;
+pub mod clock;
+pub mod compaction;
+mod digest;
+pub mod doctor;
+mod duplicates;
+pub mod errors;
+#[cfg(unix)]
+pub mod event_socket;
+pub mod events;
+mod feed;
+pub mod findings;
+pub mod github;
+mod github_app;
+pub mod history;
+pub mod http;
+pub mod issues;
+pub mod latency;
+pub mod license;
+mod listing;
+pub mod logging;
+pub mod models;
+pub mod notify;
+pub mod paths;
+mod pending;
+mod pipeline;
+pub mod policy;
+mod praise;
+mod preview;
+mod processed;
+pub mod queue;
+pub mod quota;
+mod reddit;
+pub mod redis_store;
+pub mod reload;
+mod removal;
+mod reply;
+pub mod repo_history;
+pub mod report;
+mod resolved;
+pub mod restricted;
+pub mod retention;
+mod rotation;
+mod rules;
+mod sandbox;
+pub mod sanitize;
+pub mod schedule;
+pub mod scheduler;
+pub mod simulate;
+pub mod skip;
+pub mod state;
+pub mod stats;
+pub mod subreddit;
+pub mod supervisor;
+pub mod suppress;
+mod templates;
+pub mod transport;
+pub mod util;
+mod visibility;
@@ -0,0 +1,357 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt;
+
+use crate::check::Verdict;
+use crate::models::Config;
+use crate::sanitize::{self, NAME_LEN};
+
+/// SPDX id GitHub gives a license file it couldn't match to a license.
+pub(crate) const NO_ASSERTION: &str = "NOASSERTION";
+
+/// What GitHub says about a repository's license.
+#[derive(Clone, Debug, PartialEq)]
+pub enum License {
+    /// The repository has no license file.
+    Missing,
+    /// The repository has a license, with its SPDX id if GitHub recognized it.
+    Found(Option<String>),
+    /// GitHub's answers don't say either way, for the given reason.
+    Indeterminate(String),
+}
+
+/// Links to a repository's license file.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LicenseLinks {
+    /// The file in GitHub's API.
+    pub api: Option<String>,
+    /// The file's blob in the repository.
+    pub git: Option<String>,
+    /// The file on GitHub's website.
+    pub html: Option<String>,
+}
+
+/// The license file GitHub's `/license` endpoint found in a repository.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LicenseInfo {
+    /// SPDX id of the license, `NOASSERTION` if GitHub couldn't tell which it
+    /// is.
+    pub spdx_id: Option<String>,
+    pub links: LicenseLinks,
+    /// Whether the file only looks like a license, matching none that GitHub
+    /// knows exactly, which it reports as `other`.
+    pub other: bool,
+}
+
+impl LicenseInfo {
+    /// Parse the body of a `/license` response.
+    pub fn parse(body: &Value) -> Self {
+        let link = |name: &str| body["_links"][name].as_str().map(str::to_owned);
+        let license = &body["license"];
+        let spdx_id = license["spdx_id"].as_str().map(str::to_owned);
+        Self {
+            other: license.is_null()
+                || license["key"] == "other"
+                || spdx_id.as_deref() == Some(NO_ASSERTION),
+            spdx_id,
+            links: LicenseLinks {
+                api: link("self"),
+                git: link("git"),
+                html: link("html"),
+            },
+        }
+    }
+
+    /// A license known only by its SPDX id, such as a simulator fixture's.
+    pub fn from_spdx(spdx_id: Option<String>) -> Self {
+        Self {
+            other: spdx_id.as_deref().is_none_or(|id| id == NO_ASSERTION),
+            spdx_id,
+            links: LicenseLinks::default(),
+        }
+    }
+
+    /// Whether GitHub matched the file to a license it knows.
+    pub fn is_exact(&self) -> bool {
+        !self.other
+    }
+}
+
+/// Which license files count as licenses, which is up to the operator.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LicensePolicy {
+    /// Whether a file GitHub can't match to a license counts as none.
+    pub treat_other_as_unlicensed: bool,
+    /// SPDX ids of licenses that count as none.
+    pub non_licenses: Vec<String>,
+}
+
+impl LicensePolicy {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            treat_other_as_unlicensed: config.treat_other_as_unlicensed,
+            non_licenses: config.non_licenses.clone(),
+        }
+    }
+}
+
+/// Whether a repository with the license file GitHub found counts as
+/// licensed under `policy`.
+pub fn verdict(info: &LicenseInfo, policy: &LicensePolicy) -> License {
+    let not_a_license = info.spdx_id.as_deref().is_some_and(|id| {
+        policy
+            .non_licenses
+            .iter()
+            .any(|n| n.eq_ignore_ascii_case(id))
+    });
+    if not_a_license || (info.other && policy.treat_other_as_unlicensed) {
+        return License::Missing;
+    }
+    License::Found(info.spdx_id.clone())
+}
+
+/// One of the ways a repository is looked through for a license.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Probe {
+    /// GitHub's `/license` endpoint, which is always asked first.
+    LicenseApi,
+    /// The files at the top of the default branch.
+    ContentsScan,
+    /// The files at the top of the ref a repository was linked at.
+    LinkedRef,
+    /// The license declared by `Cargo.toml`.
+    CargoToml,
+    /// The license declared by `package.json`.
+    PackageJson,
+}
+
+impl Probe {
+    /// The probe as counted in the stats.
+    pub fn label(self) -> &'static str {
+        match self {
+            Probe::LicenseApi => "license API",
+            Probe::ContentsScan => "contents scan",
+            Probe::LinkedRef => "linked ref",
+            Probe::CargoToml => "Cargo.toml",
+            Probe::PackageJson => "package.json",
+        }
+    }
+}
+
+/// What one probe made of a repository.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct DetectionStep {
+    pub probe: Probe,
+    pub verdict: Verdict,
+    /// What the probe found or looked at, such as an SPDX id or a ref.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+impl DetectionStep {
+    pub fn new(probe: Probe, verdict: Verdict) -> Self {
+        Self {
+            probe,
+            verdict,
+            detail: None,
+        }
+    }
+
+    pub fn with_detail(self, detail: impl Into<String>) -> Self {
+        Self {
+            detail: Some(detail.into()),
+            ..self
+        }
+    }
+
+    /// The step with its detail made fit to keep and show.
+    pub fn sanitized(self) -> Self {
+        Self {
+            detail: self.detail.map(|d| sanitize::text(&d, NAME_LEN)),
+            ..self
+        }
+    }
+}
+
+impl fmt::Display for DetectionStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let verdict = match self.verdict {
+            Verdict::Licensed => "licensed",
+            Verdict::Unlicensed => "unlicensed",
+            Verdict::Indeterminate => "indeterminate",
+        };
+        write!(f, "{}: {}", self.probe.label(), verdict)?;
+        if let Some(detail) = &self.detail {
+            write!(f, " ({})", detail)?;
+        }
+        Ok(())
+    }
+}
+
+/// The fallback that overturned what GitHub's `/license` endpoint said, if
+/// one did: the last probe of a trail, when it came to a different verdict
+/// than the first.
+pub fn overturned_by(path: &[DetectionStep]) -> Option<Probe> {
+    let (first, last) = (path.first()?, path.last()?);
+    (first.probe == Probe::LicenseApi && last.probe != first.probe && last.verdict != first.verdict)
+        .then_some(last.probe)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        overturned_by, verdict, DetectionStep, License, LicenseInfo, LicenseLinks, LicensePolicy,
+        Probe,
+    };
+    use crate::check::Verdict;
+    use serde_json::json;
+
+    #[test]
+    fn parses_license_responses() {
+        let exact = LicenseInfo::parse(&json!({
+            "name": "LICENSE",
+            "path": "LICENSE",
+            "_links": {
+                "self": "https://api.github.com/repos/someone/project/contents/LICENSE?ref=main",
+                "git": "https://api.github.com/repos/someone/project/git/blobs/abc",
+                "html": "https://github.com/someone/project/blob/main/LICENSE"
+            },
+            "license": {"key": "mit", "name": "MIT License", "spdx_id": "MIT"}
+        }));
+        assert_eq!(exact.spdx_id.as_deref(), Some("MIT"));
+        assert_eq!(
+            exact.links.html.as_deref(),
+            Some("https://github.com/someone/project/blob/main/LICENSE")
+        );
+        assert!(exact.links.api.is_some() && exact.links.git.is_some());
+        assert!(exact.is_exact());
+
+        let other = LicenseInfo::parse(&json!({
+            "license": {"key": "other", "name": "Other", "spdx_id": "NOASSERTION"}
+        }));
+        assert_eq!(
+            other,
+            LicenseInfo {
+                spdx_id: Some("NOASSERTION".to_owned()),
+                links: LicenseLinks::default(),
+                other: true,
+            }
+        );
+        assert!(!LicenseInfo::parse(&json!({"license": null})).is_exact());
+        assert!(!LicenseInfo::parse(&json!({"license": {"key": "other"}})).is_exact());
+        assert!(LicenseInfo::from_spdx(Some("MIT".to_owned())).is_exact());
+        assert!(!LicenseInfo::from_spdx(None).is_exact());
+    }
+
+    #[test]
+    fn maps_licenses_to_verdicts() {
+        let mit = LicenseInfo::from_spdx(Some("MIT".to_owned()));
+        let wtfpl = LicenseInfo::from_spdx(Some("WTFPL".to_owned()));
+        let other = LicenseInfo::from_spdx(Some("NOASSERTION".to_owned()));
+        let unnamed = LicenseInfo::from_spdx(None);
+        let lenient = LicensePolicy::default();
+        let strict = LicensePolicy {
+            treat_other_as_unlicensed: true,
+            non_licenses: vec![],
+        };
+        let listed = LicensePolicy {
+            treat_other_as_unlicensed: false,
+            non_licenses: vec!["wtfpl".to_owned(), "Unlicense".to_owned()],
+        };
+        let found = |id: &str| License::Found(Some(id.to_owned()));
+        let cases = [
+            (&mit, &lenient, found("MIT")),
+            (&mit, &strict, found("MIT")),
+            (&mit, &listed, found("MIT")),
+            (&wtfpl, &lenient, found("WTFPL")),
+            (&wtfpl, &strict, found("WTFPL")),
+            (&wtfpl, &listed, License::Missing),
+            (&other, &lenient, found("NOASSERTION")),
+            (&other, &strict, License::Missing),
+            (&other, &listed, found("NOASSERTION")),
+            (&unnamed, &lenient, License::Found(None)),
+            (&unnamed, &strict, License::Missing),
+            (&unnamed, &listed, License::Found(None)),
+        ];
+        for (info, policy, expected) in &cases {
+            assert_eq!(
+                &verdict(info, policy),
+                expected,
+                "{:?} under {:?}",
+                info,
+                policy
+            );
+        }
+    }
+
+    #[test]
+    fn finds_the_fallback_that_overturned_the_verdict() {
+        let step = DetectionStep::new;
+        let api = |v| step(Probe::LicenseApi, v);
+        let cases = [
+            (vec![], None),
+            (vec![api(Verdict::Licensed)], None),
+            (vec![api(Verdict::Unlicensed)], None),
+            (
+                vec![
+                    api(Verdict::Unlicensed),
+                    step(Probe::ContentsScan, Verdict::Licensed),
+                ],
+                Some(Probe::ContentsScan),
+            ),
+            (
+                vec![
+                    api(Verdict::Unlicensed),
+                    step(Probe::ContentsScan, Verdict::Unlicensed),
+                    step(Probe::CargoToml, Verdict::Unlicensed),
+                    step(Probe::PackageJson, Verdict::Licensed),
+                ],
+                Some(Probe::PackageJson),
+            ),
+            (
+                vec![
+                    api(Verdict::Unlicensed),
+                    step(Probe::ContentsScan, Verdict::Unlicensed),
+                ],
+                None,
+            ),
+            (
+                vec![
+                    api(Verdict::Unlicensed),
+                    step(Probe::LinkedRef, Verdict::Licensed),
+                ],
+                Some(Probe::LinkedRef),
+            ),
+            (
+                vec![
+                    api(Verdict::Unlicensed),
+                    step(Probe::ContentsScan, Verdict::Indeterminate),
+                ],
+                Some(Probe::ContentsScan),
+            ),
+            // an empty repository is never asked about its license
+            (
+                vec![step(Probe::ContentsScan, Verdict::Indeterminate)],
+                None,
+            ),
+        ];
+        for (path, expected) in &cases {
+            assert_eq!(overturned_by(path), *expected, "{:?}", path);
+        }
+    }
+
+    #[test]
+    fn shows_steps() {
+        let step = DetectionStep::new(Probe::LinkedRef, Verdict::Licensed).with_detail("dev");
+        assert_eq!(step.to_string(), "linked ref: licensed (dev)");
+        assert_eq!(
+            DetectionStep::new(Probe::LicenseApi, Verdict::Unlicensed).to_string(),
+            "license API: unlicensed"
+        );
+        let hostile = DetectionStep::new(Probe::CargoToml, Verdict::Licensed)
+            .with_detail("MIT\u{202e}\n".repeat(100));
+        let detail = hostile.sanitized().detail.unwrap();
+        assert!(detail.starts_with("MIT MIT ") && detail.chars().count() == 150);
+    }
+}
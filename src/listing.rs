@@ -0,0 +1,462 @@
+use crate::errors::BotError;
+use crate::subreddit::{self, Subreddit, SubredditError};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{fmt, time::Duration};
+
+/// Delay between polls of a subreddit that has nothing new.
+const EMPTY_SUBREDDIT_DELAY: u64 = 15;
+/// Delay between polls of a listing read publicly, as Reddit allows far
+/// fewer requests without an account.
+pub const PUBLIC_POLL_INTERVAL: Duration = Duration::from_secs(60);
+/// Number of recently-seen fullnames kept to fall back on as anchors.
+const KNOWN_ANCHORS: usize = 25;
+/// Pages in a row with nothing but posts already seen that a cursor can
+/// fetch before it's taken to have gone stale.
+pub const STALE_AFTER: u32 = 3;
+
+/// A source of new posts that the bot can watch.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Listing {
+    /// The /new listing of a subreddit, or of several joined with '+'.
+    Subreddit(Vec<Subreddit>),
+    /// Posts submitted by a single user, newest first.
+    User(String),
+    /// Site-wide search results, newest first.
+    Search { query: String, interval: u64 },
+}
+
+impl Listing {
+    /// A single listing covering all of the given subreddits, joined with
+    /// '+' or separated by commas.
+    ///
+    /// Reddit serves `/r/a+b+c/new` as one combined listing, so related
+    /// subreddits share a cursor and a request per poll.
+    pub fn subreddits(names: &str) -> Result<Self, SubredditError> {
+        Ok(Listing::Subreddit(Subreddit::parse_list(names)?))
+    }
+
+    /// Path of the listing endpoint, relative to the OAuth API root.
+    pub fn path(&self) -> String {
+        match self {
+            Listing::Subreddit(names) => format!("/r/{}/new", subreddit::join(names)),
+            Listing::User(name) => format!("/user/{}/submitted", name),
+            Listing::Search { .. } => "/search".to_owned(),
+        }
+    }
+
+    /// Query parameters for a request to the listing endpoint.
+    pub fn query(&self, before: &Option<String>) -> Vec<(&'static str, String)> {
+        let mut query = vec![("raw_json", "1".to_owned()), ("limit", "100".to_owned())];
+        match self {
+            Listing::Subreddit(_) => {}
+            Listing::User(_) => query.push(("sort", "new".to_owned())),
+            Listing::Search { query: q, .. } => {
+                query.push(("q", q.to_owned()));
+                query.push(("sort", "new".to_owned()));
+                query.push(("restrict_sr", "false".to_owned()));
+                query.push(("type", "link".to_owned()));
+            }
+        }
+        if let Some(b) = before {
+            query.push(("before", b.to_owned()));
+        }
+        query
+    }
+
+    /// Name that the listing's persisted state is stored under.
+    pub fn store_key(&self) -> String {
+        match self {
+            Listing::Subreddit(names) => names
+                .iter()
+                .map(Subreddit::key)
+                .collect::<Vec<_>>()
+                .join("+"),
+            Listing::User(name) => format!("u_{}", name),
+            // '-' can't appear in a subreddit name, so this can't collide
+            Listing::Search { query, .. } => format!(
+                "search-{}",
+                query
+                    .chars()
+                    .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+                    .collect::<String>()
+            ),
+        }
+    }
+
+    /// Name that the listing's state was stored under before subreddits'
+    /// names were lowercased for it, if that's a different one.
+    pub fn legacy_store_key(&self) -> Option<String> {
+        match self {
+            Listing::Subreddit(names) => {
+                Some(subreddit::join(names)).filter(|key| *key != self.store_key())
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether only posts linking directly to github.com should be checked.
+    pub fn github_domain_only(&self) -> bool {
+        matches!(self, Listing::Search { .. })
+    }
+
+    /// How long to wait before polling again once caught up.
+    pub fn poll_interval(&self) -> Duration {
+        match self {
+            Listing::Subreddit(_) | Listing::User(_) => Duration::from_secs(EMPTY_SUBREDDIT_DELAY),
+            Listing::Search { interval, .. } => Duration::from_secs(*interval),
+        }
+    }
+}
+
+/// Position in a listing that the next poll continues from.
+///
+/// Polls ask for posts newer than the anchor, the newest post seen so far.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Cursor {
+    /// Fullnames of the newest posts seen, newest first.
+    known: Vec<String>,
+    /// Pages in a row fetched from the cursor with no posts not seen before.
+    #[serde(skip)]
+    stale_pages: u32,
+}
+
+impl Cursor {
+    /// The post that the next poll should fetch newer posts than.
+    pub fn anchor(&self) -> Option<&str> {
+        self.known.first().map(String::as_str)
+    }
+
+    /// Move the anchor forward past a page of posts, ordered newest first.
+    pub fn advance(&mut self, page: &[String]) {
+        let mut known: Vec<String> = page.to_vec();
+        known.extend(self.known.drain(..).filter(|f| !page.contains(f)));
+        known.truncate(KNOWN_ANCHORS);
+        self.known = known;
+    }
+
+    /// Count a page fetched from the cursor, returning whether it's gone
+    /// stale: Reddit has answered with nothing but posts already seen more
+    /// than `STALE_AFTER` times in a row, as it does once it stops honoring
+    /// an anchor and serves the top of the listing instead.
+    pub fn note_page(&mut self, all_seen: bool) -> bool {
+        if !all_seen {
+            self.stale_pages = 0;
+            return false;
+        }
+        self.stale_pages += 1;
+        self.stale_pages > STALE_AFTER
+    }
+
+    /// Forget every anchor, so the next poll starts from the newest posts.
+    pub fn reset(&mut self) {
+        self.known.clear();
+        self.stale_pages = 0;
+    }
+
+    /// Drop the current anchor, falling back to the next-newest known post.
+    ///
+    /// Used when the anchor post has been deleted, since Reddit answers
+    /// `before` requests for a deleted post with an empty page forever.
+    pub fn age(&mut self) -> Option<String> {
+        if self.known.is_empty() {
+            None
+        } else {
+            Some(self.known.remove(0))
+        }
+    }
+}
+
+/// A page of a listing, newest posts first.
+#[derive(Debug, Deserialize)]
+pub struct Page {
+    pub data: PageData,
+}
+
+/// Contents of a page of a listing.
+#[derive(Debug, Deserialize)]
+pub struct PageData {
+    pub children: Vec<Value>,
+    /// The first post on the page, to fetch newer posts than.
+    #[serde(default)]
+    pub before: Option<String>,
+    /// The last post on the page, to fetch older posts than.
+    #[serde(default)]
+    pub after: Option<String>,
+}
+
+impl Page {
+    /// Parse a response from a listing endpoint, telling a page apart from
+    /// Reddit saying that the listing can't be shown.
+    pub fn parse(listing: &Listing, status: u16, body: &str) -> Result<Self, BotError> {
+        if let Some(reason) = Unavailable::from_response(status, body) {
+            return Err(BotError::ListingUnavailable {
+                listing: listing.to_string(),
+                reason,
+            });
+        }
+        serde_json::from_str(body)
+            .map_err(|e| BotError::Parse(format!("listing page of {}: {}", listing, e)))
+    }
+}
+
+/// Why Reddit won't show a listing.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Unavailable {
+    Banned,
+    Private,
+    Quarantined,
+    NotFound,
+    /// Any other reason Reddit gives, such as `gold_only`.
+    Other(String),
+}
+
+impl Unavailable {
+    /// Reddit's reason for not showing a listing, if the response is an
+    /// error rather than a page.
+    ///
+    /// Banned and missing subreddits get a 404 and private and quarantined
+    /// ones a 403, each with an `{"error": ..., "reason": ...}` body, though
+    /// errors are sometimes sent with a 200 and quarantined subreddits may
+    /// get an HTML interstitial instead.
+    pub fn from_response(status: u16, body: &str) -> Option<Self> {
+        let json: Value = serde_json::from_str(body).unwrap_or_default();
+        let mentions_quarantine = || body.to_lowercase().contains("quarantine");
+        if json.get("error").is_none() && status != 403 && status != 404 {
+            return match json {
+                Value::Null if mentions_quarantine() => Some(Unavailable::Quarantined),
+                _ => None,
+            };
+        }
+        Some(match json["reason"].as_str() {
+            Some("banned") => Unavailable::Banned,
+            Some("private") => Unavailable::Private,
+            Some("quarantined") => Unavailable::Quarantined,
+            Some(reason) => Unavailable::Other(reason.to_owned()),
+            None if mentions_quarantine() => Unavailable::Quarantined,
+            None if status == 404 || json["error"] == 404 => Unavailable::NotFound,
+            None => Unavailable::Other(match json["message"].as_str() {
+                Some(message) => message.to_lowercase(),
+                None => format!("status {}", status),
+            }),
+        })
+    }
+}
+
+impl fmt::Display for Unavailable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Unavailable::Banned => write!(f, "banned"),
+            Unavailable::Private => write!(f, "private"),
+            Unavailable::Quarantined => write!(f, "quarantined"),
+            Unavailable::NotFound => write!(f, "not found"),
+            Unavailable::Other(reason) => write!(f, "unavailable ({})", reason),
+        }
+    }
+}
+
+impl std::fmt::Display for Listing {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Listing::Subreddit(names) => write!(f, "/r/{}", subreddit::join(names)),
+            Listing::User(name) => write!(f, "/u/{}", name),
+            Listing::Search { query, .. } => write!(f, "search '{}'", query),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Cursor, Listing, Page, Unavailable, STALE_AFTER};
+    use crate::errors::BotError;
+
+    #[test]
+    fn subreddit_listing() {
+        let listing = Listing::subreddits("rust").unwrap();
+        assert_eq!(listing.path(), "/r/rust/new");
+        assert_eq!(listing.store_key(), "rust");
+        assert_eq!(listing.legacy_store_key(), None);
+        assert_eq!(
+            listing.query(&Some("t3_a".to_owned())),
+            vec![
+                ("raw_json", "1".to_owned()),
+                ("limit", "100".to_owned()),
+                ("before", "t3_a".to_owned())
+            ]
+        );
+        assert!(!listing.github_domain_only());
+    }
+
+    #[test]
+    fn multireddit_listing() {
+        let listing = Listing::subreddits("rust, /r/Programming,,opensource ").unwrap();
+        assert_eq!(
+            listing,
+            Listing::subreddits("rust+programming+opensource").unwrap()
+        );
+        assert_eq!(listing.path(), "/r/rust+Programming+opensource/new");
+        assert_eq!(listing.to_string(), "/r/rust+Programming+opensource");
+        assert_eq!(listing.store_key(), "rust+programming+opensource");
+        assert_eq!(
+            listing.legacy_store_key().as_deref(),
+            Some("rust+Programming+opensource")
+        );
+        assert!(Listing::subreddits("rust+https://reddit.com/r/golang").is_err());
+    }
+
+    #[test]
+    fn user_listing() {
+        let listing = Listing::User("someone".to_owned());
+        assert_eq!(listing.path(), "/user/someone/submitted");
+        assert_eq!(listing.store_key(), "u_someone");
+        assert_eq!(
+            listing.query(&None),
+            vec![
+                ("raw_json", "1".to_owned()),
+                ("limit", "100".to_owned()),
+                ("sort", "new".to_owned())
+            ]
+        );
+        assert!(!listing.github_domain_only());
+    }
+
+    #[test]
+    fn search_listing() {
+        let listing = Listing::Search {
+            query: "site:github.com".to_owned(),
+            interval: 60,
+        };
+        assert_eq!(listing.path(), "/search");
+        assert_eq!(listing.store_key(), "search-site_github_com");
+        assert_eq!(
+            listing.query(&None),
+            vec![
+                ("raw_json", "1".to_owned()),
+                ("limit", "100".to_owned()),
+                ("q", "site:github.com".to_owned()),
+                ("sort", "new".to_owned()),
+                ("restrict_sr", "false".to_owned()),
+                ("type", "link".to_owned()),
+            ]
+        );
+        assert!(listing.github_domain_only());
+        assert_eq!(listing.poll_interval().as_secs(), 60);
+    }
+
+    #[test]
+    fn cursor_advances_and_ages() {
+        let mut cursor = Cursor::default();
+        assert_eq!(cursor.anchor(), None);
+
+        cursor.advance(&["t3_b".to_owned(), "t3_a".to_owned()]);
+        assert_eq!(cursor.anchor(), Some("t3_b"));
+        cursor.advance(&["t3_d".to_owned(), "t3_c".to_owned(), "t3_b".to_owned()]);
+        assert_eq!(cursor.anchor(), Some("t3_d"));
+        assert_eq!(cursor.known, vec!["t3_d", "t3_c", "t3_b", "t3_a"]);
+
+        assert_eq!(cursor.age(), Some("t3_d".to_owned()));
+        assert_eq!(cursor.anchor(), Some("t3_c"));
+    }
+
+    const BANNED: &str = r#"{"reason": "banned", "message": "Not Found", "error": 404}"#;
+    const PRIVATE: &str = r#"{"reason": "private", "message": "Forbidden", "error": 403}"#;
+    const QUARANTINED: &str = r#"{"quarantine_message_html": "<p>...</p>", "reason": "quarantined", "quarantine_message": "This community is quarantined.", "message": "Forbidden", "error": 403}"#;
+    const INTERSTITIAL: &str =
+        "<!doctype html><html><body><h1>Are you sure you want to view this community?</h1><p>This community has been quarantined.</p></body></html>";
+
+    #[test]
+    fn unavailable_listings() {
+        assert_eq!(
+            Unavailable::from_response(404, BANNED),
+            Some(Unavailable::Banned)
+        );
+        assert_eq!(
+            Unavailable::from_response(403, PRIVATE),
+            Some(Unavailable::Private)
+        );
+        assert_eq!(
+            Unavailable::from_response(403, QUARANTINED),
+            Some(Unavailable::Quarantined)
+        );
+        assert_eq!(
+            Unavailable::from_response(200, INTERSTITIAL),
+            Some(Unavailable::Quarantined)
+        );
+        assert_eq!(
+            Unavailable::from_response(200, r#"{"message": "Not Found", "error": 404}"#),
+            Some(Unavailable::NotFound)
+        );
+        assert_eq!(
+            Unavailable::from_response(403, r#"{"reason": "gold_only", "error": 403}"#),
+            Some(Unavailable::Other("gold_only".to_owned()))
+        );
+        assert_eq!(
+            Unavailable::from_response(403, ""),
+            Some(Unavailable::Other("status 403".to_owned()))
+        );
+        assert_eq!(
+            Unavailable::from_response(200, r#"{"data": {"children": []}}"#),
+            None
+        );
+    }
+
+    #[test]
+    fn parses_pages() {
+        let listing = Listing::subreddits("rust").unwrap();
+        let page = Page::parse(
+            &listing,
+            200,
+            r#"{"kind": "Listing", "data": {"before": "t3_b", "after": null, "children": [{"data": {"name": "t3_b"}}]}}"#,
+        )
+        .unwrap();
+        assert_eq!(page.data.children.len(), 1);
+        assert_eq!(page.data.before.as_deref(), Some("t3_b"));
+        assert_eq!(page.data.after, None);
+
+        let banned = Page::parse(&listing, 404, BANNED).unwrap_err();
+        assert_eq!(banned.to_string(), "/r/rust is banned");
+        assert!(matches!(
+            banned,
+            BotError::ListingUnavailable {
+                reason: Unavailable::Banned,
+                ..
+            }
+        ));
+        assert!(matches!(
+            Page::parse(&listing, 200, r#"{"kind": "t5", "data": {}}"#),
+            Err(BotError::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn cursor_is_bounded() {
+        let mut cursor = Cursor::default();
+        let page: Vec<String> = (0..100).map(|i| format!("t3_{}", i)).collect();
+        cursor.advance(&page);
+        assert_eq!(cursor.known.len(), super::KNOWN_ANCHORS);
+        assert_eq!(cursor.anchor(), Some("t3_0"));
+    }
+
+    #[test]
+    fn cursor_goes_stale_after_pages_of_seen_posts() {
+        let mut cursor = Cursor::default();
+        cursor.advance(&["t3_a".to_owned()]);
+        for _ in 0..STALE_AFTER {
+            assert!(!cursor.note_page(true));
+        }
+        // a page with anything new starts the count again
+        assert!(!cursor.note_page(false));
+        for _ in 0..STALE_AFTER {
+            assert!(!cursor.note_page(true));
+        }
+        assert!(cursor.note_page(true));
+
+        cursor.reset();
+        assert_eq!(cursor.anchor(), None);
+        assert!(!cursor.note_page(true));
+
+        // the count isn't saved
+        let saved = serde_json::to_string(&cursor).unwrap();
+        assert_eq!(saved, r#"{"known":[]}"#);
+    }
+}
@@ -0,0 +1,94 @@
+use anyhow::{anyhow, Result};
+use log::{Log, Metadata, Record};
+use std::{env, sync::Mutex, time::SystemTime};
+
+use crate::models::Config;
+use crate::rotation::RotatingFile;
+
+/// Logs to stderr and to a rotated log file.
+struct Tee {
+    stderr: Box<dyn Log>,
+    file: Mutex<RotatingFile>,
+}
+
+impl Log for Tee {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.stderr.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        self.stderr.log(record);
+        let line = file_line(SystemTime::now(), record);
+        let file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        if let Err(e) = file.write_line(&line) {
+            eprintln!("Could not write to log file: {}", e);
+        }
+    }
+
+    fn flush(&self) {
+        self.stderr.flush();
+    }
+}
+
+/// A record as it's written to the log file: plain text, without colors.
+fn file_line(at: SystemTime, record: &Record) -> String {
+    format!(
+        "{} {:<5} {} > {}",
+        humantime::format_rfc3339_seconds(at),
+        record.level(),
+        record.target(),
+        record.args()
+    )
+}
+
+/// Set up logging to stderr, filtered by `RUST_LOG`, and also to
+/// `CFL_LOG_FILE` if it's set.
+pub fn init(config: &Config) -> Result<()> {
+    let mut builder = pretty_env_logger::formatted_builder();
+    if let Ok(filters) = env::var("RUST_LOG") {
+        builder.parse_filters(&filters);
+    }
+    let stderr = builder.build();
+    let max_level = stderr.filter();
+    let logger: Box<dyn Log> = match &config.log_file {
+        Some(path) => Box::new(Tee {
+            stderr: Box::new(stderr),
+            file: Mutex::new(RotatingFile::new(
+                path.clone(),
+                config.log_max_size_mb * 1024 * 1024,
+                config.log_keep,
+            )),
+        }),
+        None => Box::new(stderr),
+    };
+    log::set_boxed_logger(logger).map_err(|e| anyhow!("Could not set up logging: {}", e))?;
+    log::set_max_level(max_level);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::file_line;
+    use log::{Level, Record};
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn file_lines_are_plain() {
+        let at = UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+        let line = file_line(
+            at,
+            &Record::builder()
+                .level(Level::Warn)
+                .target("check_for_license::bot")
+                .args(format_args!("Rate limited by {}", "GitHub"))
+                .build(),
+        );
+        assert_eq!(
+            line,
+            "2020-09-13T12:26:40Z WARN  check_for_license::bot > Rate limited by GitHub"
+        );
+    }
+}
@@ -1,24 +1,285 @@
-use anyhow::Result;
-use std::env;
+use anyhow::{anyhow, Result};
+use check_for_license::{
+    backfill,
+    bot::Bot,
+    bundle, check, doctor,
+    events::{EventLog, SharedEvents},
+    findings::FindingsLog,
+    history, http, logging,
+    models::{Config, Severity},
+    notify, paths, policy, queue, reload, repo_history,
+    report::{ErrorContext, Level, Reporter},
+    simulate, skip, stats,
+    supervisor::Supervisor,
+    suppress,
+};
+use std::{
+    env,
+    future::Future,
+    io::{self, IsTerminal, Write},
+    sync::Mutex,
+    time::Duration,
+};
+#[cfg(unix)]
+use tokio::signal::unix::{signal, SignalKind};
 
-mod models;
-use models::Config;
-mod bot;
-use bot::Bot;
-mod util;
+/// Commands run instead of the bot, by their first argument.
+const SUBCOMMANDS: &[&str] = &[
+    "doctor", "check", "queue", "history", "repo", "stats", "why", "state", "suppress", "policy",
+    "simulate", "backfill",
+];
+const USAGE: &str = "Usage: check_for_license [--adopt-state [--yes]]\n\
+                     \x20      check_for_license <command> ...\n\
+                     Commands: doctor, check, queue, history, repo, stats, why, state, suppress, \
+                     policy, simulate, backfill";
+
+/// Longest wait on shutdown for the posts already fetched to be processed.
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(30);
 
 #[tokio::main]
 async fn main() -> Result<()> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    // a mistyped command shouldn't start the bot
+    if let Some(first) = args.first() {
+        if !first.starts_with('-') && !SUBCOMMANDS.contains(&first.as_str()) {
+            return Err(anyhow!("Unknown command '{}'\n{}", first, USAGE));
+        }
+    }
+    if args.first().map(String::as_str) == Some("doctor") {
+        return doctor::run(&args[1..]).await;
+    }
+    if args.first().map(String::as_str) == Some("check") {
+        let code = check::run(&args[1..]).await?;
+        std::process::exit(code);
+    }
     kankyo::init().expect("Could not load .env file");
     if env::var("RUST_LOG").is_err() {
         env::set_var("RUST_LOG", "check_for_license");
     }
-    pretty_env_logger::init();
 
-    let mut bot = Bot::new(Config::from_env()?)?;
-    bot.login().await?;
+    let config = Config::from_env()?;
+    logging::init(&config)?;
+    paths::prepare_state_dir(&config.state_dir)?;
+    if args.first().map(String::as_str) == Some("queue") {
+        print!("{}", queue::run(&config.state_dir, &args[1..])?);
+        return Ok(());
+    }
+    if args.first().map(String::as_str) == Some("history") {
+        print!("{}", history::run(&config.state_dir, &args[1..])?);
+        return Ok(());
+    }
+    if args.first().map(String::as_str) == Some("repo") {
+        print!("{}", repo_history::run(&config.state_dir, &args[1..])?);
+        return Ok(());
+    }
+    if args.first().map(String::as_str) == Some("stats") {
+        print!("{}", stats::run(&config.state_dir, &args[1..])?);
+        return Ok(());
+    }
+    if args.first().map(String::as_str) == Some("why") {
+        print!("{}", skip::run(&config.state_dir, &args[1..])?);
+        return Ok(());
+    }
+    if args.first().map(String::as_str) == Some("state") {
+        print!("{}", bundle::run(&config.state_dir, &args[1..])?);
+        return Ok(());
+    }
+    if args.first().map(String::as_str) == Some("suppress") {
+        print!("{}", suppress::run(&config.state_dir, &args[1..])?);
+        return Ok(());
+    }
+    if args.first().map(String::as_str) == Some("policy") {
+        print!("{}", policy::run(&config, &args[1..])?);
+        return Ok(());
+    }
+    if args.first().map(String::as_str) == Some("simulate") {
+        print!("{}", simulate::run(config, &args[1..]).await?);
+        return Ok(());
+    }
+    log::info!("{}", config.summary());
+    let problems = config.validate();
+    let mut errors = vec![];
+    for problem in &problems {
+        match problem.severity {
+            Severity::Warning => log::warn!("{}", problem),
+            Severity::Error => errors.push(problem.to_string()),
+        }
+    }
+    if !errors.is_empty() {
+        return Err(anyhow!("Invalid configuration:\n{}", errors.join("\n")));
+    }
+    if args.first().map(String::as_str) == Some("backfill") {
+        return run_backfill(config, &args[1..]).await;
+    }
+    let adopt_state = args.iter().any(|a| a == "--adopt-state");
+    if adopt_state && !confirm_adoption(&config, &args)? {
+        return Err(anyhow!("Not taking over the state"));
+    }
+    let (updates, receiver) = tokio::sync::watch::channel(config.clone());
+    #[cfg(unix)]
+    tokio::spawn(async move {
+        if let Err(e) = reload::watch_sighup(updates).await {
+            log::error!("Could not listen for SIGHUP: {}", e);
+        }
+    });
+    #[cfg(not(unix))]
+    drop(updates);
+    let mut event_log = EventLog::open(&config.state_dir)?;
+    if let Some(path) = &config.findings_path {
+        event_log =
+            event_log.with_findings(FindingsLog::new(path.clone(), config.findings_max_bytes));
+    }
+    if let Some(path) = &config.feed_path {
+        event_log = event_log.with_feed(path.clone());
+    }
+    if let Some(path) = &config.event_socket {
+        #[cfg(unix)]
+        {
+            use check_for_license::event_socket::EventSocket;
+            event_log = event_log.with_socket(EventSocket::bind(path)?);
+        }
+        #[cfg(not(unix))]
+        log::warn!(
+            "Not opening the event socket at {}, as sockets aren't supported here",
+            path.display()
+        );
+    }
+    let events = SharedEvents::new(Mutex::new(event_log));
+    if let Some(addr) = config.http_listen {
+        let dashboard = http::Dashboard::new(events.clone(), receiver.clone());
+        tokio::spawn(async move {
+            if let Err(e) = http::serve(addr, dashboard).await {
+                log::error!("HTTP listener on {} failed: {}", addr, e);
+            }
+        });
+    }
+    let mut bot = Bot::new(config.clone())?
+        .with_config_updates(receiver)
+        .with_events(events.clone());
+    #[cfg(feature = "cassettes")]
+    {
+        if let Ok(path) = env::var("CFL_RECORD_CASSETTE") {
+            log::info!("Recording HTTP exchanges to {}", path);
+            bot = bot.with_transport(check_for_license::transport::Transport::record(path));
+        }
+    }
+    if adopt_state {
+        bot = bot.adopting_state();
+    }
+    let reporter = config.sentry_dsn.clone().map(Reporter::new);
+    if let Some(reporter) = &reporter {
+        bot = bot.with_reporter(reporter.clone());
+    }
+    bot.login_when_available().await?;
 
-    bot.watch_subreddit("celeo").await?;
+    let core = bot.core().clone();
+    let mut supervisor = Supervisor::new(config.max_restarts_per_hour, events);
+    let stop = shutdown_requested()?;
+    let mut run = Box::pin(supervisor.run(&mut bot));
+    let finished = tokio::select! {
+        result = &mut run => Some(result),
+        _ = stop => None,
+    };
+    let finished = match finished {
+        Some(result) => Some(result),
+        None => {
+            log::info!("Shutting down once the posts already fetched are processed");
+            notify::stopping();
+            core.stop();
+            tokio::time::timeout(SHUTDOWN_GRACE, &mut run).await.ok()
+        }
+    };
+    drop(run);
+    let result = match finished {
+        Some(result) => result,
+        None => shut_down(&mut bot),
+    };
+    if let (Err(e), Some(mut reporter)) = (&result, reporter) {
+        let sent = reporter
+            .capture(e, Level::Fatal, &ErrorContext::default())
+            .await;
+        if let Err(e) = sent {
+            log::warn!("Could not report error to Sentry: {:#}", e);
+        }
+    }
+    result
+}
 
+/// Check a subreddit's recent posts once, queueing replies for approval.
+/// Resolves once the process is asked to stop, by SIGTERM or Ctrl-C.
+#[cfg(unix)]
+fn shutdown_requested() -> Result<impl Future<Output = ()>> {
+    let mut terminate = signal(SignalKind::terminate())?;
+    Ok(async move {
+        tokio::select! {
+            _ = terminate.recv() => {}
+            _ = tokio::signal::ctrl_c() => {}
+        }
+    })
+}
+
+/// Resolves once the process is asked to stop by Ctrl-C, as there's no SIGTERM here.
+#[cfg(not(unix))]
+fn shutdown_requested() -> Result<impl Future<Output = ()>> {
+    Ok(async {
+        let _ = tokio::signal::ctrl_c().await;
+    })
+}
+
+async fn run_backfill(config: Config, args: &[String]) -> Result<()> {
+    let options = backfill::Options::parse(args)?;
+    let mut event_log = EventLog::default();
+    if !options.dry_run {
+        event_log = EventLog::open(&config.state_dir)?;
+        if let Some(path) = &config.findings_path {
+            event_log =
+                event_log.with_findings(FindingsLog::new(path.clone(), config.findings_max_bytes));
+        }
+    }
+    let mut bot = Bot::new(config)?.with_events(SharedEvents::new(Mutex::new(event_log)));
+    bot.login().await?;
+    let done = bot
+        .backfill(
+            &options.subreddit,
+            options.since.as_secs(),
+            options.dry_run,
+            |progress| println!("{}", progress),
+        )
+        .await?;
+    println!(
+        "Backfilled /r/{}: {}{}",
+        options.subreddit,
+        done,
+        if options.dry_run { " (dry run)" } else { "" }
+    );
     Ok(())
 }
+
+/// Ask whether to take over state stamped as another account's or listing's,
+/// unless `--yes` was given as well.
+fn confirm_adoption(config: &Config, args: &[String]) -> Result<bool> {
+    if args.iter().any(|a| a == "--yes") {
+        return Ok(true);
+    }
+    if !io::stdin().is_terminal() {
+        return Err(anyhow!(
+            "--adopt-state has to be confirmed on a terminal, or with --yes"
+        ));
+    }
+    print!(
+        "State stamped as another account's or listing's will be taken over as /u/{}'s, \
+         and replies may be repeated or held back because of what it holds. Continue? [y/N] ",
+        config.username
+    );
+    io::stdout().flush()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+/// Stop without waiting any longer, saving the state of the last pass that
+/// was finished.
+fn shut_down(bot: &mut Bot) -> Result<()> {
+    log::warn!("Gave up waiting for the posts already fetched to be processed");
+    bot.save_checkpoint()
+}
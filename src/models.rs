@@ -1,10 +1,29 @@
-use anyhow::Result;
-use serde::Deserialize;
-use std::env;
+use anyhow::{anyhow, Context, Result};
+use log::warn;
+use serde::{Deserialize, Deserializer};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    env, fmt,
+    net::SocketAddr,
+    path::PathBuf,
+    str::FromStr,
+    time::Duration,
+};
+
+use crate::duplicates::DuplicateMode;
+use crate::issues::IssueMode;
+use crate::queue::ApprovalMode;
+use crate::reply;
+use crate::report::Dsn;
+use crate::resolved::AckMode;
+use crate::retention::Retention;
+use crate::schedule::{ActiveHours, TimeZone, WeeklyTime};
+use crate::subreddit::{self, Subreddit};
+use crate::templates::{self, FALLBACK_LOCALE};
 
 /// Struct that contains the required information to
 /// access the Reddit API.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Config {
     pub username: String,
     pub password: String,
@@ -12,19 +31,1393 @@ pub struct Config {
     pub client_id: String,
     pub client_secret: String,
     pub github_username: String,
+    pub check_concurrency: usize,
+    pub max_pages: usize,
+    pub subreddit: String,
+    pub watch_user: Option<String>,
+    pub search_query: Option<String>,
+    pub search_interval: u64,
+    pub max_replies_per_hour: Option<usize>,
+    pub visibility_check_interval: u64,
+    pub pause_on_shadowban: bool,
+    pub invited_subreddits: Vec<String>,
+    pub state_dir: PathBuf,
+    pub http_listen: Option<SocketAddr>,
+    pub http_token: Option<String>,
+    pub findings_path: Option<PathBuf>,
+    pub findings_max_bytes: u64,
+    /// Days findings are kept in the findings log, 0 to keep them for good.
+    pub findings_retention_days: u64,
+    pub findings_retention: Retention,
+    pub feed_path: Option<PathBuf>,
+    pub event_socket: Option<PathBuf>,
+    pub active_hours: Option<ActiveHours>,
+    pub timezone: TimeZone,
+    /// What to reply to the bot to have its comment removed.
+    ///
+    /// Used both in the footer of replies and when reading the bot's inbox,
+    /// so the two can't disagree.
+    pub opt_out_keyword: String,
+    pub maintainer: Option<String>,
+    pub footer_template: Option<String>,
+    pub approval_mode: ApprovalMode,
+    /// How many times the watch loop may be restarted in an hour before giving up.
+    pub max_restarts_per_hour: usize,
+    /// File to write the log to as well as stderr.
+    pub log_file: Option<PathBuf>,
+    pub log_max_size_mb: u64,
+    /// Number of rotated log files to keep.
+    pub log_keep: usize,
+    /// Sentry project to report errors to.
+    pub sentry_dsn: Option<Dsn>,
+    /// Seconds during which the bot won't reply to the same author about the
+    /// same repository again; 0 turns this off.
+    pub reply_dedup_ttl: u64,
+    /// When to post the weekly digest of findings, in `timezone`.
+    pub digest_at: Option<WeeklyTime>,
+    /// Post to comment the digest in.
+    pub digest_thread: Option<String>,
+    /// Subreddit whose moderators are sent the digest.
+    pub digest_modmail: Option<String>,
+    /// Token sent when opening issues on GitHub, which needs the
+    /// `public_repo` scope.
+    pub github_token: Option<String>,
+    /// GitHub App installation to authenticate as, instead of with
+    /// `github_token`.
+    pub github_app: Option<GithubApp>,
+    pub github_issue_mode: IssueMode,
+    /// Repositories with more stars than this never get an issue.
+    pub github_issue_max_stars: u64,
+    /// Checks are deferred while fewer GitHub requests than this are left.
+    pub github_quota_floor: u64,
+    /// Seconds after replying to look at the reply again, to see whether it
+    /// was removed; 0 turns this off.
+    pub removal_check_delay: u64,
+    /// Only report findings in a subreddit once it has removed this many replies.
+    pub auto_disable_after_removals: Option<usize>,
+    /// Seconds during which a reply about a repository counts for other
+    /// submissions of the same link; 0 turns this off.
+    pub duplicate_window: u64,
+    pub duplicate_mode: DuplicateMode,
+    /// Minutes during which a repository replied about counts for its
+    /// author's posts of it in other subreddits; 0 turns this off.
+    pub cross_sub_window_minutes: u64,
+    pub cross_sub_mode: DuplicateMode,
+    /// Phrases that, in a post, say its code is unlicensed on purpose.
+    pub author_opt_out_phrases: Vec<String>,
+    /// Phrases that, in a post's title or flair, say its code is a work in
+    /// progress, which isn't replied about.
+    pub wip_phrases: Vec<String>,
+    /// Days after which the unlicensed repositories in a work in progress
+    /// are replied about, if the post is still up and they still have no
+    /// license, or 0 never to.
+    pub wip_followup_days: u64,
+    /// Accounts whose posts are never replied to, besides the bot's own.
+    pub exempt_authors: Vec<String>,
+    /// Lowercased owners of repositories that are never replied about, as
+    /// links to them are more likely news than someone's own project.
+    pub high_profile_owners: Vec<String>,
+    /// Whether a license file GitHub can't match to a license it knows
+    /// counts as no license.
+    pub treat_other_as_unlicensed: bool,
+    /// SPDX ids of licenses that count as no license.
+    pub non_licenses: Vec<String>,
+    /// Repositories with more stars than this are never replied about.
+    pub max_stars: u64,
+    /// Directory of reply templates, by locale, that take precedence over
+    /// the bundled ones.
+    pub template_dir: PathBuf,
+    /// Locale of replies in each subreddit, by lowercased name; others get
+    /// English.
+    pub subreddit_locales: BTreeMap<String, String>,
+    /// Seconds to wait before looking at a banned, private or quarantined
+    /// subreddit again.
+    pub unavailable_retry_interval: u64,
+    /// Opt in to quarantined subreddits, so their posts can be listed.
+    pub quarantine_optin: bool,
+    /// Seconds after which a held back reply's repositories are checked
+    /// again before replying; 0 turns this off.
+    pub recheck_after: u64,
+    /// Seconds to wait between tries while Reddit is down for maintenance.
+    pub maintenance_backoff: u64,
+    /// Seconds for which a processed post is remembered; 0 remembers them
+    /// forever.
+    pub processed_retention: u64,
+    /// Most processed posts remembered per listing, the oldest forgotten first.
+    pub processed_max: Option<usize>,
+    /// What to do when a repository replied about gains a license.
+    pub ack_resolved: AckMode,
+    /// Passes of the watch loop between compactions of what's kept in
+    /// memory; 0 turns them off.
+    pub compact_every: u64,
+    /// File to write a line to for every HTTP request sent.
+    pub http_audit_path: Option<PathBuf>,
+    /// Reply to posts whose authors' accounts have been deleted.
+    pub reply_to_deleted: bool,
+    /// Leave a note of praise on posts linking an exemplary repository.
+    pub praise_mode: bool,
+    /// Notes of praise left in any 24 hours at most.
+    pub max_praise_per_day: u32,
+    /// Checks waiting to be tried again kept at most, the oldest dropped
+    /// first; 0 keeps any number.
+    pub max_retries: usize,
+    /// Seconds after which a check waiting to be tried again is dropped;
+    /// 0 keeps it until it's made.
+    pub retry_max_age: u64,
+    /// Replies held back until the active hours kept at most, the oldest
+    /// dropped first; 0 keeps any number.
+    pub max_delayed_replies: usize,
+    /// Checks deferred while the GitHub quota was low kept at most, the
+    /// oldest dropped first; 0 keeps any number.
+    pub max_rechecks: usize,
+    /// Queued items worked on in each pass before the listing is polled; 0
+    /// works on all of them.
+    pub queue_budget: usize,
+    /// Redis to keep state in instead of the state directory, shared by
+    /// every bot given the same one.
+    pub redis_url: Option<String>,
+    /// Subreddit to post what would be replied to each post in, as a post
+    /// of its own, instead of replying.
+    pub sandbox_subreddit: Option<String>,
+    /// Longest URL read as a link to a repository, in bytes; 0 reads any.
+    pub max_url_len: usize,
+    /// Most repositories taken from one self post; 0 takes any number.
+    pub max_links_per_post: usize,
+    /// Seconds for which a post is young enough that a repository it links
+    /// that GitHub can't find may just not be public yet, and is looked for
+    /// once more; 0 gives up on those straight away.
+    pub young_post_age: u64,
+    /// Most pages fetched from the listing that may wait to be processed
+    /// before fetching waits too; 0 fetches and processes them in turn.
+    pub pipeline_depth: usize,
+    /// Which settings were given, rather than left at their defaults.
+    pub provenance: Provenance,
+}
+
+/// What authors say when they don't want their code licensed.
+const DEFAULT_AUTHOR_OPT_OUT_PHRASES: &[&str] = &[
+    "all rights reserved",
+    "intentionally unlicensed",
+    "not open source",
+];
+
+/// Phrases saying a post's code is a work in progress, unless others are
+/// set with `CFL_WIP_PHRASES`.
+const DEFAULT_WIP_PHRASES: &[&str] = &[
+    "wip",
+    "work in progress",
+    "prototype",
+    "toy project",
+    "learning project",
+];
+
+/// Owners of repositories that are never replied about, unless taken out
+/// with `CFL_HIGH_PROFILE_OWNERS`.
+const DEFAULT_HIGH_PROFILE_OWNERS: &[&str] = &[
+    "torvalds",
+    "microsoft",
+    "rust-lang",
+    "google",
+    "apple",
+    "facebook",
+    "aws",
+    "github",
+    "mozilla",
+    "apache",
+    "kubernetes",
+    "golang",
+    "python",
+    "nodejs",
+    "dotnet",
+];
+
+/// Read an optional environment variable, falling back to a default.
+fn env_or<T: FromStr>(key: &str, default: T) -> Result<T> {
+    match env::var(key) {
+        Ok(value) => value
+            .parse()
+            .map_err(|_| anyhow!("Could not parse value of {}: '{}'", key, value)),
+        Err(_) => Ok(default),
+    }
+}
+
+/// Read an optional comma-separated list from an environment variable.
+fn env_list(key: &str) -> Vec<String> {
+    match env::var(key) {
+        Ok(value) => value
+            .split(',')
+            .map(|s| s.trim().to_owned())
+            .filter(|s| !s.is_empty())
+            .collect(),
+        Err(_) => vec![],
+    }
+}
+
+/// A subreddit's name as given in a setting, without its `/r/` prefix, or
+/// as it was if it isn't a name, for `validate` to report.
+fn subreddit_name(name: String) -> String {
+    Subreddit::new(&name)
+        .map(|s| s.name().to_owned())
+        .unwrap_or(name)
+}
+
+/// Read a list of subreddits to watch, joined with `+` or separated by
+/// commas, without their prefixes.
+fn env_subreddits(key: &str, default: &str) -> Result<String> {
+    let names: String = env_or(key, default.to_owned())?;
+    Ok(Subreddit::parse_list(&names)
+        .map(|list| subreddit::join(&list))
+        .unwrap_or(names))
+}
+
+/// Read an optional comma-separated list of `subreddit:locale` pairs.
+fn env_locales(key: &str) -> Result<BTreeMap<String, String>> {
+    env_list(key)
+        .iter()
+        .map(|pair| match pair.split_once(':') {
+            Some((subreddit, locale)) => Ok((
+                Subreddit::new(subreddit)
+                    .map(|s| s.key())
+                    .unwrap_or_else(|_| subreddit.trim().to_lowercase()),
+                locale.trim().to_owned(),
+            )),
+            None => Err(anyhow!(
+                "Could not parse value of {}: '{}' isn't subreddit:locale",
+                key,
+                pair
+            )),
+        })
+        .collect()
+}
+
+/// The built-in high-profile owners, with those in `changes` added, or
+/// taken out if they start with `-`.
+fn high_profile_owners(changes: &[String]) -> Vec<String> {
+    let mut owners: Vec<String> = DEFAULT_HIGH_PROFILE_OWNERS
+        .iter()
+        .map(|o| o.to_string())
+        .collect();
+    for change in changes {
+        let change = change.to_lowercase();
+        match change.strip_prefix('-') {
+            Some(removed) => owners.retain(|o| o != removed.trim()),
+            None if !owners.contains(&change) => owners.push(change),
+            None => {}
+        }
+    }
+    owners
+}
+
+/// Read the GitHub App to authenticate as, whose settings are all given or
+/// none of them.
+fn env_github_app() -> Result<Option<GithubApp>> {
+    match (
+        env_opt("CFL_GITHUB_APP_ID")?,
+        env_opt("CFL_GITHUB_APP_INSTALLATION_ID")?,
+        env_opt("CFL_GITHUB_APP_KEY_PATH")?,
+    ) {
+        (Some(app_id), Some(installation_id), Some(key_path)) => Ok(Some(GithubApp {
+            app_id,
+            installation_id,
+            key_path,
+        })),
+        (None, None, None) => Ok(None),
+        _ => Err(anyhow!(
+            "CFL_GITHUB_APP_ID, CFL_GITHUB_APP_INSTALLATION_ID and \
+             CFL_GITHUB_APP_KEY_PATH must be set together"
+        )),
+    }
+}
+
+/// Read an optional environment variable that has no default.
+fn env_opt<T: FromStr>(key: &str) -> Result<Option<T>> {
+    match env::var(key) {
+        Ok(value) => value
+            .parse()
+            .map(Some)
+            .map_err(|_| anyhow!("Could not parse value of {}: '{}'", key, value)),
+        Err(_) => Ok(None),
+    }
 }
 
 impl Config {
     /// Pulls data from environment variables to populate the struct.
     pub fn from_env() -> Result<Self> {
-        Ok(Config {
-            username: env::var("CFL_USERNAME")?,
-            password: env::var("CFL_PASSWORD")?,
-            user_agent: env::var("CFL_USER_AGENT")?,
-            client_id: env::var("CFL_CLIENT_ID")?,
-            client_secret: env::var("CFL_CLIENT_SECRET")?,
+        // without them, the bot can only read Reddit's public listings
+        let username = env::var("CFL_USERNAME").unwrap_or_default();
+        let maintainer: Option<String> = env_opt("CFL_MAINTAINER")?;
+        let user_agent = reddit_user_agent(
+            env::var("CFL_USER_AGENT")
+                .ok()
+                .filter(|agent| !agent.trim().is_empty())
+                .as_deref(),
+            maintainer.as_deref().unwrap_or(&username),
+        );
+        let config = Config {
+            username,
+            password: env::var("CFL_PASSWORD").unwrap_or_default(),
+            user_agent,
+            client_id: env::var("CFL_CLIENT_ID").unwrap_or_default(),
+            client_secret: env::var("CFL_CLIENT_SECRET").unwrap_or_default(),
             github_username: env::var("CFL_GITHUB_USERNAME")?,
-        })
+            check_concurrency: env_or("CFL_CHECK_CONCURRENCY", 1)?,
+            max_pages: env_or("CFL_MAX_PAGES", 5)?,
+            subreddit: env_subreddits("CFL_SUBREDDIT", "celeo")?,
+            watch_user: env_opt("CFL_WATCH_USER")?,
+            search_query: env_opt("CFL_SEARCH_QUERY")?,
+            search_interval: env_or("CFL_SEARCH_INTERVAL", 60)?,
+            max_replies_per_hour: env_opt("CFL_MAX_REPLIES_PER_HOUR")?,
+            visibility_check_interval: env_or("CFL_VISIBILITY_CHECK_INTERVAL", 60 * 60)?,
+            pause_on_shadowban: env_or("CFL_PAUSE_ON_SHADOWBAN", false)?,
+            invited_subreddits: env_list("CFL_INVITED_SUBREDDITS")
+                .into_iter()
+                .map(subreddit_name)
+                .collect(),
+            state_dir: env_or("CFL_STATE_DIR", PathBuf::from("."))?,
+            http_listen: env_opt("CFL_HTTP_LISTEN")?,
+            http_token: env_opt("CFL_HTTP_TOKEN")?,
+            findings_path: env_opt("CFL_FINDINGS_PATH")?,
+            findings_max_bytes: env_or("CFL_FINDINGS_MAX_BYTES", 10 * 1024 * 1024)?,
+            findings_retention_days: env_or("CFL_FINDINGS_RETENTION_DAYS", 0)?,
+            findings_retention: env_or("CFL_FINDINGS_RETENTION", Retention::Archive)?,
+            feed_path: env_opt("CFL_FEED_PATH")?,
+            event_socket: env_opt("CFL_EVENT_SOCKET")?,
+            active_hours: env_opt("CFL_ACTIVE_HOURS")?,
+            timezone: match env::var("CFL_TIMEZONE") {
+                Ok(name) => TimeZone::load(&name)?,
+                Err(_) => TimeZone::utc(),
+            },
+            opt_out_keyword: env_or("CFL_OPT_OUT_KEYWORD", "!ignore".to_owned())?,
+            maintainer,
+            footer_template: env_opt("CFL_FOOTER_TEMPLATE")?,
+            approval_mode: env_or("CFL_APPROVAL_MODE", ApprovalMode::Auto)?,
+            max_restarts_per_hour: env_or("CFL_MAX_RESTARTS_PER_HOUR", 5)?,
+            log_file: env_opt("CFL_LOG_FILE")?,
+            log_max_size_mb: env_or("CFL_LOG_MAX_SIZE_MB", 10)?,
+            log_keep: env_or("CFL_LOG_KEEP", 5)?,
+            sentry_dsn: env_opt("CFL_SENTRY_DSN")?,
+            reply_dedup_ttl: env_or("CFL_REPLY_DEDUP_TTL", 30 * 24 * 60 * 60)?,
+            digest_at: env_opt("CFL_DIGEST_AT")?,
+            digest_thread: env_opt("CFL_DIGEST_THREAD")?,
+            digest_modmail: env_opt("CFL_DIGEST_MODMAIL")?.map(subreddit_name),
+            github_token: env_opt("CFL_GITHUB_TOKEN")?,
+            github_app: env_github_app()?,
+            github_issue_mode: env_or("CFL_GITHUB_ISSUE_MODE", IssueMode::Off)?,
+            github_issue_max_stars: env_or("CFL_GITHUB_ISSUE_MAX_STARS", 1000)?,
+            github_quota_floor: env_or("CFL_GITHUB_QUOTA_FLOOR", 5)?,
+            removal_check_delay: env_or("CFL_REMOVAL_CHECK_DELAY", 120)?,
+            auto_disable_after_removals: env_opt("CFL_AUTO_DISABLE_AFTER_REMOVALS")?,
+            duplicate_window: env_or("CFL_DUPLICATE_WINDOW", 7 * 24 * 60 * 60)?,
+            duplicate_mode: env_or("CFL_DUPLICATE_MODE", DuplicateMode::Skip)?,
+            cross_sub_window_minutes: env_or("CFL_CROSS_SUB_WINDOW_MINUTES", 60)?,
+            cross_sub_mode: env_or("CFL_CROSS_SUB_MODE", DuplicateMode::Skip)?,
+            author_opt_out_phrases: match env::var("CFL_AUTHOR_OPT_OUT_PHRASES") {
+                Ok(_) => env_list("CFL_AUTHOR_OPT_OUT_PHRASES"),
+                Err(_) => DEFAULT_AUTHOR_OPT_OUT_PHRASES
+                    .iter()
+                    .map(|p| p.to_string())
+                    .collect(),
+            },
+            wip_phrases: match env::var("CFL_WIP_PHRASES") {
+                Ok(_) => env_list("CFL_WIP_PHRASES"),
+                Err(_) => DEFAULT_WIP_PHRASES.iter().map(|p| p.to_string()).collect(),
+            },
+            wip_followup_days: env_or("CFL_WIP_FOLLOWUP_DAYS", 0)?,
+            exempt_authors: env_list("CFL_EXEMPT_AUTHORS"),
+            high_profile_owners: high_profile_owners(&env_list("CFL_HIGH_PROFILE_OWNERS")),
+            treat_other_as_unlicensed: env_or("CFL_TREAT_OTHER_AS_UNLICENSED", false)?,
+            non_licenses: env_list("CFL_NON_LICENSES"),
+            max_stars: env_or("CFL_MAX_STARS", 500)?,
+            template_dir: env_or("CFL_TEMPLATE_DIR", "templates".into())?,
+            subreddit_locales: env_locales("CFL_SUBREDDIT_LOCALES")?,
+            unavailable_retry_interval: env_or("CFL_UNAVAILABLE_RETRY_INTERVAL", 60 * 60)?,
+            quarantine_optin: env_or("CFL_QUARANTINE_OPTIN", false)?,
+            recheck_after: env_or("CFL_RECHECK_AFTER", 10 * 60)?,
+            maintenance_backoff: env_or("CFL_MAINTENANCE_BACKOFF", 5 * 60)?,
+            processed_retention: env_or("CFL_PROCESSED_RETENTION", 90 * 24 * 60 * 60)?,
+            processed_max: env_opt("CFL_PROCESSED_MAX")?,
+            ack_resolved: env_or("CFL_ACK_RESOLVED", AckMode::Off)?,
+            compact_every: env_or("CFL_COMPACT_EVERY", 100)?,
+            http_audit_path: env_opt("CFL_HTTP_AUDIT_PATH")?,
+            reply_to_deleted: env_or("CFL_REPLY_TO_DELETED", false)?,
+            praise_mode: env_or("CFL_PRAISE_MODE", false)?,
+            max_praise_per_day: env_or("CFL_MAX_PRAISE_PER_DAY", 2)?,
+            max_retries: env_or("CFL_MAX_RETRIES", 500)?,
+            retry_max_age: env_or("CFL_RETRY_MAX_AGE", 2 * 24 * 60 * 60)?,
+            max_delayed_replies: env_or("CFL_MAX_DELAYED_REPLIES", 500)?,
+            max_rechecks: env_or("CFL_MAX_RECHECKS", 1000)?,
+            queue_budget: env_or("CFL_QUEUE_BUDGET", 25)?,
+            redis_url: env_opt("CFL_REDIS_URL")?,
+            sandbox_subreddit: env_opt("CFL_SANDBOX_SUBREDDIT")?.map(subreddit_name),
+            max_url_len: env_or("CFL_MAX_URL_LEN", 2048)?,
+            max_links_per_post: env_or("CFL_MAX_LINKS_PER_POST", 20)?,
+            young_post_age: env_or("CFL_YOUNG_POST_AGE", 15 * 60)?,
+            pipeline_depth: env_or("CFL_PIPELINE_DEPTH", 4)?,
+            provenance: Provenance::from_env(),
+        };
+        reply::render_footer(&config).context("rendering CFL_FOOTER_TEMPLATE")?;
+        Ok(config)
+    }
+}
+
+/// A GitHub App installation, and the private key it signs with.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GithubApp {
+    pub app_id: u64,
+    pub installation_id: u64,
+    /// PEM file of the app's RSA private key.
+    pub key_path: PathBuf,
+}
+
+/// How the bot reads Reddit.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RedditAccess {
+    /// As its account, which it needs to reply.
+    Account,
+    /// Through the JSON endpoints of Reddit's website, as anyone not logged
+    /// in, only reporting what it finds.
+    Public,
+}
+
+/// Which layer of the config a setting's value comes from.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Source {
+    /// The built-in default, as nothing set it.
+    Default,
+    /// The environment, or the `.env` file read into it, which applies to
+    /// every subreddit.
+    Global,
+    /// A setting naming the subreddit, such as `CFL_SUBREDDIT_LOCALES`.
+    Subreddit,
+}
+
+impl Source {
+    pub fn label(self) -> &'static str {
+        match self {
+            Source::Default => "default",
+            Source::Global => "global config",
+            Source::Subreddit => "subreddit override",
+        }
+    }
+}
+
+/// The environment variable a setting is read from, by its name in `Config`.
+pub fn setting_name(field: &str) -> String {
+    format!("CFL_{}", field.to_uppercase())
+}
+
+/// Which settings were given when a config was read, so that a value can be
+/// told apart from a default it happens to equal.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Provenance {
+    /// Names of the environment variables that were set.
+    given: BTreeSet<String>,
+}
+
+impl Provenance {
+    /// The settings given in the environment.
+    pub fn from_env() -> Self {
+        Self::given(
+            env::vars_os()
+                .filter_map(|(key, _)| key.into_string().ok())
+                .filter(|key| key.starts_with("CFL_")),
+        )
+    }
+
+    /// Provenance of a config read from these environment variables.
+    pub fn given<S: Into<String>>(names: impl IntoIterator<Item = S>) -> Self {
+        Self {
+            given: names.into_iter().map(Into::into).collect(),
+        }
+    }
+
+    /// Where a setting's value comes from, by its name in `Config`.
+    pub fn source(&self, field: &str) -> Source {
+        if self.given.contains(&setting_name(field)) {
+            Source::Global
+        } else {
+            Source::Default
+        }
+    }
+
+    /// These settings' sources, except for `fields`, whose come from `kept`,
+    /// as the settings a reload leaves alone keep where they came from.
+    pub fn merged(&self, kept: &Provenance, fields: &[&str]) -> Self {
+        let mut given = self.given.clone();
+        for name in fields.iter().map(|f| setting_name(f)) {
+            given.remove(&name);
+            if kept.given.contains(&name) {
+                given.insert(name);
+            }
+        }
+        Self { given }
+    }
+}
+
+/// How serious a problem with the config is.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Severity {
+    /// The bot can run, but probably not as intended.
+    Warning,
+    /// The bot can't run properly.
+    Error,
+}
+
+/// A problem with a setting, with a suggestion for fixing it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Problem {
+    pub severity: Severity,
+    /// The environment variable the setting comes from.
+    pub setting: &'static str,
+    pub message: String,
+    pub suggestion: String,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}. {}", self.setting, self.message, self.suggestion)
+    }
+}
+
+/// Whether a user agent follows Reddit's `platform:app:version (by /u/username)`
+/// convention; Reddit throttles clients whose user agents don't.
+fn is_reddit_user_agent(agent: &str) -> bool {
+    let (app, by) = match agent.split_once(" (by /u/") {
+        Some(parts) => parts,
+        None => return false,
+    };
+    let parts: Vec<&str> = app.split(':').collect();
+    parts.len() == 3
+        && parts.iter().all(|p| !p.trim().is_empty())
+        && by.len() > 1
+        && by.ends_with(')')
+}
+
+/// The user agent to send to Reddit: `platform:check_for_license:version (by
+/// /u/owner)`, unless `configured` is one that follows the same convention.
+fn reddit_user_agent(configured: Option<&str>, owner: &str) -> String {
+    match configured {
+        Some(agent) if is_reddit_user_agent(agent) => return agent.to_owned(),
+        Some(agent) => warn!(
+            "Ignoring CFL_USER_AGENT '{}', which doesn't follow Reddit's \
+             `platform:app:version (by /u/username)` convention",
+            agent
+        ),
+        None => {}
+    }
+    format!(
+        "{}:{}:{} (by /u/{})",
+        env::consts::OS,
+        env!("CARGO_PKG_NAME"),
+        env!("CARGO_PKG_VERSION"),
+        owner.trim_start_matches("/u/").trim_start_matches("u/")
+    )
+}
+
+impl Config {
+    /// How the bot reads Reddit: publicly when none of the Reddit
+    /// credentials are set, but a subreddit is watched and findings are
+    /// reported somewhere other than Reddit.
+    pub fn reddit_access(&self) -> RedditAccess {
+        let credentials = [
+            &self.username,
+            &self.password,
+            &self.client_id,
+            &self.client_secret,
+        ];
+        let reported = self.findings_path.is_some()
+            || self.feed_path.is_some()
+            || self.event_socket.is_some()
+            || self.http_listen.is_some();
+        if credentials.iter().all(|c| c.is_empty())
+            && !self.subreddit.is_empty()
+            && self.watch_user.is_none()
+            && self.search_query.is_none()
+            && reported
+        {
+            RedditAccess::Public
+        } else {
+            RedditAccess::Account
+        }
+    }
+
+    /// The user agent to send to GitHub, naming the app, where to find it and
+    /// the GitHub account running it.
+    pub fn github_user_agent(&self) -> String {
+        format!(
+            "{}/{} (+{}; {})",
+            env!("CARGO_PKG_NAME"),
+            env!("CARGO_PKG_VERSION"),
+            reply::SOURCE_URL,
+            self.github_username
+        )
+    }
+
+    /// The locale to reply in in a subreddit.
+    pub fn locale(&self, subreddit: &str) -> &str {
+        match self.subreddit_locales.get(&subreddit.to_lowercase()) {
+            Some(locale) if templates::is_locale_name(locale) => locale,
+            _ => FALLBACK_LOCALE,
+        }
+    }
+
+    /// The effective settings, one per line, for logging at startup.
+    ///
+    /// Secrets are shown as `***`, and optional features as whether they're
+    /// enabled.
+    pub fn summary(&self) -> String {
+        fn or_disabled<T: fmt::Display>(value: &Option<T>) -> String {
+            value
+                .as_ref()
+                .map_or_else(|| "disabled".to_owned(), T::to_string)
+        }
+        fn secret<T>(value: &Option<T>) -> &'static str {
+            match value {
+                Some(_) => "***",
+                None => "not set",
+            }
+        }
+        fn seconds(secs: u64) -> String {
+            humantime::format_duration(Duration::from_secs(secs)).to_string()
+        }
+        let path = |path: &Option<PathBuf>| or_disabled(&path.as_ref().map(|p| p.display()));
+        let list = |items: &[String]| match items {
+            [] => "none".to_owned(),
+            items => items.join(", "),
+        };
+        let limit = |max: usize| match max {
+            0 => "any number of".to_owned(),
+            max => format!("up to {}", max),
+        };
+        let lines = [
+            (
+                "Reddit account",
+                match self.reddit_access() {
+                    RedditAccess::Account => format!(
+                        "/u/{} (password ***, client id ***, client secret ***)",
+                        self.username
+                    ),
+                    RedditAccess::Public => {
+                        "none, reading public listings and only reporting findings".to_owned()
+                    }
+                },
+            ),
+            ("Reddit user agent", self.user_agent.clone()),
+            (
+                "GitHub account",
+                match &self.github_app {
+                    Some(app) => format!(
+                        "{} (app {}, installation {}, key {})",
+                        self.github_username,
+                        app.app_id,
+                        app.installation_id,
+                        app.key_path.display()
+                    ),
+                    None => format!(
+                        "{} (token {})",
+                        self.github_username,
+                        secret(&self.github_token)
+                    ),
+                },
+            ),
+            ("GitHub user agent", self.github_user_agent()),
+            ("Subreddit", format!("/r/{}", self.subreddit)),
+            ("Invited subreddits", list(&self.invited_subreddits)),
+            (
+                "Watched user",
+                or_disabled(&self.watch_user.as_ref().map(|u| format!("/u/{}", u))),
+            ),
+            (
+                "Search",
+                or_disabled(
+                    &self
+                        .search_query
+                        .as_ref()
+                        .map(|q| format!("'{}' every {}", q, seconds(self.search_interval))),
+                ),
+            ),
+            (
+                "Pages per pass",
+                format!(
+                    "{}, checking {} at once",
+                    self.max_pages, self.check_concurrency
+                ),
+            ),
+            (
+                "Approval",
+                match self.approval_mode {
+                    ApprovalMode::Auto => "auto",
+                    ApprovalMode::Manual => "manual",
+                }
+                .to_owned(),
+            ),
+            (
+                "GitHub issues",
+                match self.github_issue_mode {
+                    IssueMode::Off => "disabled".to_owned(),
+                    IssueMode::Instead => format!(
+                        "instead of replying, up to {} stars",
+                        self.github_issue_max_stars
+                    ),
+                    IssueMode::Also => format!(
+                        "as well as replying, up to {} stars",
+                        self.github_issue_max_stars
+                    ),
+                },
+            ),
+            (
+                "Reply cap",
+                self.max_replies_per_hour
+                    .map_or_else(|| "none".to_owned(), |n| format!("{} per hour", n)),
+            ),
+            (
+                "Repeat replies",
+                match self.reply_dedup_ttl {
+                    0 => "allowed".to_owned(),
+                    ttl => format!("skipped for {}", seconds(ttl)),
+                },
+            ),
+            (
+                "Deleted authors",
+                if self.reply_to_deleted {
+                    "replied to"
+                } else {
+                    "not replied to"
+                }
+                .to_owned(),
+            ),
+            (
+                "Praise",
+                if self.praise_mode {
+                    format!("up to {} per day", self.max_praise_per_day)
+                } else {
+                    "off".to_owned()
+                },
+            ),
+            (
+                "Other submissions",
+                match (self.duplicate_window, self.duplicate_mode) {
+                    (0, _) => "replied to like any other post".to_owned(),
+                    (window, DuplicateMode::Skip) => {
+                        format!("skipped if replied to within {}", seconds(window))
+                    }
+                    (window, DuplicateMode::Reply) => {
+                        format!("pointed to the earlier reply if within {}", seconds(window))
+                    }
+                },
+            ),
+            (
+                "Posts in other subreddits",
+                match (self.cross_sub_window_minutes, self.cross_sub_mode) {
+                    (0, _) => "replied to like any other post".to_owned(),
+                    (minutes, DuplicateMode::Skip) => format!(
+                        "skipped if the author's repository was replied about within {}",
+                        seconds(minutes * 60)
+                    ),
+                    (minutes, DuplicateMode::Reply) => format!(
+                        "pointed to the reply about the author's repository if within {}",
+                        seconds(minutes * 60)
+                    ),
+                },
+            ),
+            ("Author opt-out phrases", list(&self.author_opt_out_phrases)),
+            (
+                "Work in progress phrases",
+                match self.wip_followup_days {
+                    0 => list(&self.wip_phrases),
+                    days => format!(
+                        "{}, replied about after {} days",
+                        list(&self.wip_phrases),
+                        days
+                    ),
+                },
+            ),
+            ("Exempt authors", list(&self.exempt_authors)),
+            (
+                "Not counted as licenses",
+                match (self.treat_other_as_unlicensed, &self.non_licenses[..]) {
+                    (false, ids) => list(ids),
+                    (true, []) => "unrecognized licenses".to_owned(),
+                    (true, ids) => format!("{}, unrecognized licenses", list(ids)),
+                },
+            ),
+            (
+                "High-profile repositories",
+                format!(
+                    "owned by {}, or with more than {} stars",
+                    list(&self.high_profile_owners),
+                    self.max_stars
+                ),
+            ),
+            (
+                "Reply locales",
+                self.subreddit_locales
+                    .iter()
+                    .map(|(subreddit, locale)| format!("{} in /r/{}", locale, subreddit))
+                    .chain(Some(format!(
+                        "{} elsewhere, templates in {}",
+                        FALLBACK_LOCALE,
+                        self.template_dir.display()
+                    )))
+                    .collect::<Vec<String>>()
+                    .join(", "),
+            ),
+            (
+                "Active hours",
+                self.active_hours.as_ref().map_or_else(
+                    || "always".to_owned(),
+                    |hours| format!("{} {}", hours, self.timezone),
+                ),
+            ),
+            (
+                "GitHub quota floor",
+                format!("{} requests", self.github_quota_floor),
+            ),
+            (
+                "Removal checks",
+                match self.removal_check_delay {
+                    0 => "disabled".to_owned(),
+                    delay => format!(
+                        "{} after replying, {}",
+                        seconds(delay),
+                        self.auto_disable_after_removals.map_or_else(
+                            || "never disabling replies".to_owned(),
+                            |n| format!("disabling replies in a subreddit after {} removals", n)
+                        )
+                    ),
+                },
+            ),
+            (
+                "Held back replies",
+                match self.recheck_after {
+                    0 => "made without checking again".to_owned(),
+                    after => format!("checked again if older than {}", seconds(after)),
+                },
+            ),
+            (
+                "Queues",
+                format!(
+                    "{} retries for {}, {} delayed replies, {} re-checks; {} per pass",
+                    limit(self.max_retries),
+                    match self.retry_max_age {
+                        0 => "ever".to_owned(),
+                        age => seconds(age),
+                    },
+                    limit(self.max_delayed_replies),
+                    limit(self.max_rechecks),
+                    match self.queue_budget {
+                        0 => "all worked on".to_owned(),
+                        budget => format!("{} worked on", budget),
+                    }
+                ),
+            ),
+            (
+                "Links",
+                format!(
+                    "{}, {} from each self post",
+                    match self.max_url_len {
+                        0 => "any length".to_owned(),
+                        len => format!("up to {} bytes long", len),
+                    },
+                    match self.max_links_per_post {
+                        0 => "all taken".to_owned(),
+                        max => format!("{} taken", max),
+                    }
+                ),
+            ),
+            (
+                "Missing repositories",
+                match self.young_post_age {
+                    0 => "given up on straight away".to_owned(),
+                    age => format!(
+                        "looked for once more if linked in posts under {} old",
+                        seconds(age)
+                    ),
+                },
+            ),
+            (
+                "Pages",
+                match self.pipeline_depth {
+                    0 => "fetched and processed in turn".to_owned(),
+                    depth => format!(
+                        "fetched while others are processed, up to {} waiting",
+                        depth
+                    ),
+                },
+            ),
+            (
+                "Unavailable subreddits",
+                format!(
+                    "retried every {}, {}opting in to quarantined ones",
+                    seconds(self.unavailable_retry_interval),
+                    if self.quarantine_optin { "" } else { "not " }
+                ),
+            ),
+            (
+                "Reddit outages",
+                format!("waited out {} at a time", seconds(self.maintenance_backoff)),
+            ),
+            (
+                "Processed posts",
+                match (self.processed_retention, self.processed_max) {
+                    (0, None) => "remembered forever".to_owned(),
+                    (0, Some(max)) => format!("the newest {} remembered", max),
+                    (retention, None) => format!("remembered for {}", seconds(retention)),
+                    (retention, Some(max)) => format!(
+                        "remembered for {}, at most {} of them",
+                        seconds(retention),
+                        max
+                    ),
+                },
+            ),
+            (
+                "New licenses",
+                match self.ack_resolved {
+                    AckMode::Off => "not acknowledged".to_owned(),
+                    AckMode::Comment => "acknowledged in a reply to the bot's comment".to_owned(),
+                    AckMode::Edit => "acknowledged in an edit of the bot's comment".to_owned(),
+                },
+            ),
+            (
+                "Memory compaction",
+                match self.compact_every {
+                    0 => "off".to_owned(),
+                    every => format!("every {} passes", every),
+                },
+            ),
+            (
+                "Visibility checks",
+                format!(
+                    "every {}, {}pausing on shadowban",
+                    seconds(self.visibility_check_interval),
+                    if self.pause_on_shadowban { "" } else { "not " }
+                ),
+            ),
+            (
+                "Footer",
+                match &self.footer_template {
+                    Some(_) => "custom template",
+                    None => "default",
+                }
+                .to_owned(),
+            ),
+            ("Opt-out keyword", format!("'{}'", self.opt_out_keyword)),
+            (
+                "Maintainer",
+                or_disabled(&self.maintainer.as_ref().map(|m| format!("/u/{}", m))),
+            ),
+            (
+                "Digest",
+                or_disabled(&self.digest_at.as_ref().map(|at| {
+                    format!(
+                        "{} {}, to thread {} and modmail {}",
+                        at,
+                        self.timezone,
+                        or_disabled(&self.digest_thread),
+                        or_disabled(&self.digest_modmail.as_ref().map(|s| format!("/r/{}", s)))
+                    )
+                })),
+            ),
+            (
+                "Sandbox",
+                or_disabled(
+                    &self
+                        .sandbox_subreddit
+                        .as_ref()
+                        .map(|s| format!("replies posted to /r/{} instead", s)),
+                ),
+            ),
+            ("State directory", self.state_dir.display().to_string()),
+            (
+                "State store",
+                // the URL may hold a password
+                if self.redis_url.is_some() {
+                    "Redis".to_owned()
+                } else {
+                    "state directory".to_owned()
+                },
+            ),
+            (
+                "HTTP listener",
+                or_disabled(
+                    &self
+                        .http_listen
+                        .map(|addr| format!("{} (token {})", addr, secret(&self.http_token))),
+                ),
+            ),
+            (
+                "Findings log",
+                or_disabled(&self.findings_path.as_ref().map(|p| {
+                    format!(
+                        "{} (rotated at {} bytes)",
+                        p.display(),
+                        self.findings_max_bytes
+                    )
+                })),
+            ),
+            (
+                "Findings retention",
+                match (self.findings_retention_days, self.findings_retention) {
+                    (0, _) => "kept for good".to_owned(),
+                    (days, Retention::Archive) => {
+                        format!("archived by month after {} days", days)
+                    }
+                    (days, Retention::Drop) => format!("dropped after {} days", days),
+                },
+            ),
+            ("Feed", path(&self.feed_path)),
+            ("Event socket", path(&self.event_socket)),
+            ("HTTP audit log", path(&self.http_audit_path)),
+            (
+                "Log file",
+                or_disabled(&self.log_file.as_ref().map(|p| {
+                    format!(
+                        "{} (rotated at {} MB, keeping {})",
+                        p.display(),
+                        self.log_max_size_mb,
+                        self.log_keep
+                    )
+                })),
+            ),
+            (
+                "Sentry",
+                match self.sentry_dsn {
+                    Some(_) => "enabled (DSN ***)",
+                    None => "disabled",
+                }
+                .to_owned(),
+            ),
+            (
+                "Restarts",
+                format!("up to {} per hour", self.max_restarts_per_hour),
+            ),
+        ];
+        let mut summary = "Configuration:".to_owned();
+        for (name, value) in &lines {
+            summary.push_str(&format!("\n  {}: {}", name, value));
+        }
+        summary
+    }
+
+    /// Check the format of every setting, returning all the problems found.
+    pub fn validate(&self) -> Vec<Problem> {
+        let mut problems = vec![];
+        let mut add = |severity, setting, message: &str, suggestion: String| {
+            problems.push(Problem {
+                severity,
+                setting,
+                message: message.to_owned(),
+                suggestion,
+            })
+        };
+        let public = self.reddit_access() == RedditAccess::Public;
+        for (setting, value, hint) in &[
+            (
+                "CFL_USERNAME",
+                &self.username,
+                "the bot account's Reddit username",
+            ),
+            (
+                "CFL_PASSWORD",
+                &self.password,
+                "the bot account's Reddit password",
+            ),
+            (
+                "CFL_GITHUB_USERNAME",
+                &self.github_username,
+                "the GitHub username to send in API requests",
+            ),
+        ] {
+            if value.is_empty() && (!public || *setting == "CFL_GITHUB_USERNAME") {
+                add(
+                    Severity::Error,
+                    setting,
+                    "is empty",
+                    format!("Set it to {}.", hint),
+                );
+            }
+        }
+        for (setting, value) in &[
+            ("CFL_CLIENT_ID", &self.client_id),
+            ("CFL_CLIENT_SECRET", &self.client_secret),
+        ] {
+            let suggestion =
+                "Copy it from the bot's app at https://www.reddit.com/prefs/apps.".to_owned();
+            if value.is_empty() {
+                if !public {
+                    add(Severity::Error, setting, "is empty", suggestion);
+                }
+            } else if value.chars().any(char::is_whitespace) {
+                add(Severity::Error, setting, "contains whitespace", suggestion);
+            }
+        }
+        // replying and sending messages need the bot's account
+        let needs_account = [
+            ("CFL_DIGEST_THREAD", self.digest_thread.is_some()),
+            ("CFL_DIGEST_MODMAIL", self.digest_modmail.is_some()),
+            ("CFL_PRAISE_MODE", self.praise_mode),
+            ("CFL_SANDBOX_SUBREDDIT", self.sandbox_subreddit.is_some()),
+        ];
+        for (setting, _) in needs_account.iter().filter(|(_, set)| public && *set) {
+            add(
+                Severity::Error,
+                setting,
+                "is set, but there are no Reddit credentials to post with",
+                "Set CFL_USERNAME, CFL_PASSWORD, CFL_CLIENT_ID and CFL_CLIENT_SECRET, \
+                 or unset it to only report findings."
+                    .to_owned(),
+            );
+        }
+        if !is_reddit_user_agent(&self.user_agent) {
+            add(
+                Severity::Warning,
+                "CFL_USER_AGENT",
+                "doesn't follow Reddit's `platform:app:version (by /u/username)` convention, \
+                 so Reddit may throttle the bot",
+                format!(
+                    "Try `{}:check_for_license:{} (by /u/{})`.",
+                    env::consts::OS,
+                    env!("CARGO_PKG_VERSION"),
+                    self.username
+                ),
+            );
+        }
+        let mut subreddits: Vec<(&str, &str)> = Subreddit::split(&self.subreddit)
+            .map(|name| ("CFL_SUBREDDIT", name))
+            .collect();
+        if subreddits.is_empty() {
+            subreddits.push(("CFL_SUBREDDIT", &self.subreddit));
+        }
+        subreddits.extend(
+            self.invited_subreddits
+                .iter()
+                .map(|name| ("CFL_INVITED_SUBREDDITS", name.as_str())),
+        );
+        subreddits.extend(
+            self.digest_modmail
+                .iter()
+                .map(|name| ("CFL_DIGEST_MODMAIL", name.as_str())),
+        );
+        subreddits.extend(
+            self.sandbox_subreddit
+                .iter()
+                .map(|name| ("CFL_SANDBOX_SUBREDDIT", name.as_str())),
+        );
+        for (setting, name) in subreddits {
+            if let Err(e) = Subreddit::new(name) {
+                add(
+                    Severity::Error,
+                    setting,
+                    &format!("has '{}', which isn't a subreddit name", name),
+                    e.suggestion(),
+                );
+            }
+        }
+        for (setting, value) in &[
+            ("CFL_CHECK_CONCURRENCY", self.check_concurrency as u64),
+            ("CFL_MAX_PAGES", self.max_pages as u64),
+            ("CFL_SEARCH_INTERVAL", self.search_interval),
+            (
+                "CFL_VISIBILITY_CHECK_INTERVAL",
+                self.visibility_check_interval,
+            ),
+            ("CFL_FINDINGS_MAX_BYTES", self.findings_max_bytes),
+            ("CFL_LOG_MAX_SIZE_MB", self.log_max_size_mb),
+        ] {
+            if *value == 0 {
+                add(
+                    Severity::Error,
+                    setting,
+                    "is 0",
+                    "Set it to a positive number, or unset it to use the default.".to_owned(),
+                );
+            }
+        }
+        if self.max_replies_per_hour == Some(0) {
+            add(
+                Severity::Warning,
+                "CFL_MAX_REPLIES_PER_HOUR",
+                "is 0, so the bot will never reply",
+                "Unset it to reply without a cap.".to_owned(),
+            );
+        }
+        let digest_target = self.digest_thread.is_some() || self.digest_modmail.is_some();
+        if self.digest_at.is_some() && !digest_target {
+            add(
+                Severity::Error,
+                "CFL_DIGEST_AT",
+                "is set, but there's nowhere to post the digest",
+                "Set CFL_DIGEST_THREAD to a post's id, or CFL_DIGEST_MODMAIL to a subreddit."
+                    .to_owned(),
+            );
+        } else if self.digest_at.is_none() && digest_target {
+            add(
+                Severity::Warning,
+                "CFL_DIGEST_AT",
+                "isn't set, so no digest will be posted",
+                "Set it to a weekday and time, like `Mon 09:00`.".to_owned(),
+            );
+        }
+        for (subreddit, locale) in &self.subreddit_locales {
+            if let Err(e) = Subreddit::new(subreddit) {
+                add(
+                    Severity::Error,
+                    "CFL_SUBREDDIT_LOCALES",
+                    &format!("has '{}', which isn't a subreddit name", subreddit),
+                    e.suggestion(),
+                );
+            }
+            if !templates::is_locale_name(locale) {
+                add(
+                    Severity::Error,
+                    "CFL_SUBREDDIT_LOCALES",
+                    &format!("has '{}', which isn't a locale name", locale),
+                    "Use letters, digits, dashes or underscores, like `de` or `pt_BR`.".to_owned(),
+                );
+            } else if !templates::has_locale(&self.template_dir, locale) {
+                add(
+                    Severity::Warning,
+                    "CFL_SUBREDDIT_LOCALES",
+                    &format!(
+                        "has '{}', which has no templates, so replies in /r/{} will be in English",
+                        locale, subreddit
+                    ),
+                    format!(
+                        "Add templates in {}.",
+                        self.template_dir.join(locale).display()
+                    ),
+                );
+            }
+        }
+        if self.github_issue_mode.files_issues()
+            && self.github_token.is_none()
+            && self.github_app.is_none()
+        {
+            add(
+                Severity::Error,
+                "CFL_GITHUB_ISSUE_MODE",
+                "opens issues, but there's no token to open them with",
+                "Set CFL_GITHUB_TOKEN to a token with the public_repo scope, \
+                 set up a GitHub App, or set CFL_GITHUB_ISSUE_MODE to off."
+                    .to_owned(),
+            );
+        }
+        if let Some(app) = &self.github_app {
+            if !app.key_path.is_file() {
+                add(
+                    Severity::Error,
+                    "CFL_GITHUB_APP_KEY_PATH",
+                    &format!("has '{}', which isn't a file", app.key_path.display()),
+                    "Set it to the private key downloaded from the GitHub App's settings."
+                        .to_owned(),
+                );
+            }
+        }
+        if let (Some(_), Some(_)) = (&self.github_token, &self.github_app) {
+            add(
+                Severity::Warning,
+                "CFL_GITHUB_TOKEN",
+                "is set, but isn't used as a GitHub App is",
+                "Unset CFL_GITHUB_TOKEN, or the CFL_GITHUB_APP_* settings to use the token."
+                    .to_owned(),
+            );
+        }
+        problems
+    }
+}
+
+#[cfg(test)]
+impl Config {
+    /// A valid config for tests to start from, watching /r/test with the
+    /// defaults for everything else.
+    pub(crate) fn for_tests() -> Self {
+        Config {
+            username: "bot".to_owned(),
+            password: "password".to_owned(),
+            user_agent: "linux:check_for_license:0.1.0 (by /u/bot)".to_owned(),
+            client_id: "id".to_owned(),
+            client_secret: "secret".to_owned(),
+            github_username: "someone".to_owned(),
+            check_concurrency: 1,
+            max_pages: 5,
+            subreddit: "test".to_owned(),
+            watch_user: None,
+            search_query: None,
+            search_interval: 60,
+            max_replies_per_hour: None,
+            visibility_check_interval: 60 * 60,
+            pause_on_shadowban: false,
+            invited_subreddits: vec![],
+            state_dir: ".".into(),
+            http_listen: None,
+            http_token: None,
+            findings_path: None,
+            findings_max_bytes: 1024 * 1024,
+            findings_retention_days: 0,
+            findings_retention: Retention::Archive,
+            feed_path: None,
+            event_socket: None,
+            active_hours: None,
+            timezone: TimeZone::utc(),
+            opt_out_keyword: "!ignore".to_owned(),
+            maintainer: None,
+            footer_template: None,
+            approval_mode: ApprovalMode::Auto,
+            max_restarts_per_hour: 5,
+            log_file: None,
+            log_max_size_mb: 10,
+            log_keep: 5,
+            sentry_dsn: None,
+            reply_dedup_ttl: 30 * 24 * 60 * 60,
+            digest_at: None,
+            digest_thread: None,
+            digest_modmail: None,
+            github_token: None,
+            github_issue_mode: IssueMode::Off,
+            github_issue_max_stars: 1000,
+            github_quota_floor: 5,
+            removal_check_delay: 120,
+            auto_disable_after_removals: None,
+            duplicate_window: 7 * 24 * 60 * 60,
+            duplicate_mode: DuplicateMode::Skip,
+            cross_sub_window_minutes: 60,
+            cross_sub_mode: DuplicateMode::Skip,
+            author_opt_out_phrases: vec![],
+            wip_phrases: vec![],
+            wip_followup_days: 0,
+            exempt_authors: vec![],
+            high_profile_owners: vec![],
+            treat_other_as_unlicensed: false,
+            non_licenses: vec![],
+            max_stars: 500,
+            template_dir: "templates".into(),
+            subreddit_locales: Default::default(),
+            unavailable_retry_interval: 60 * 60,
+            quarantine_optin: false,
+            recheck_after: 10 * 60,
+            maintenance_backoff: 5 * 60,
+            processed_retention: 90 * 24 * 60 * 60,
+            processed_max: None,
+            ack_resolved: AckMode::Off,
+            compact_every: 100,
+            http_audit_path: None,
+            reply_to_deleted: false,
+            praise_mode: false,
+            max_praise_per_day: 2,
+            max_retries: 500,
+            retry_max_age: 2 * 24 * 60 * 60,
+            max_delayed_replies: 500,
+            max_rechecks: 1000,
+            queue_budget: 25,
+            redis_url: None,
+            sandbox_subreddit: None,
+            max_url_len: 2048,
+            max_links_per_post: 20,
+            young_post_age: 15 * 60,
+            pipeline_depth: 4,
+            github_app: None,
+            provenance: Provenance::default(),
+        }
     }
 }
 
@@ -38,11 +1431,474 @@ pub struct AccessTokenResponse {
     pub scope: String,
 }
 
+/// A Reddit post, as returned in listings and from `/api/info`.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct Post {
+    pub name: String,
+    #[serde(default)]
+    pub domain: String,
+    #[serde(default)]
+    pub url: String,
+    #[serde(default)]
+    pub author: Author,
+    #[serde(default)]
+    pub subreddit: String,
+    #[serde(default)]
+    pub removed_by_category: Option<String>,
+    /// Path of the post on Reddit, starting with `/r/`.
+    #[serde(default)]
+    pub permalink: String,
+}
+
+impl Post {
+    /// Whether the post has been deleted by its author or removed by mods.
+    ///
+    /// A post whose author's account was deleted is still there.
+    pub fn is_removed(&self) -> bool {
+        self.removed_by_category.is_some()
+    }
+}
+
+/// Who made a post or comment.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Author {
+    /// An account, by name, which is empty if Reddit didn't say.
+    User(String),
+    /// An account that has been deleted, which Reddit gives as `[deleted]`
+    /// with no `author_fullname`.
+    Deleted,
+}
+
+impl Default for Author {
+    fn default() -> Self {
+        Author::User(String::new())
+    }
+}
+
+impl Author {
+    pub fn parse(name: &str) -> Self {
+        match name {
+            "[deleted]" => Author::Deleted,
+            name => Author::User(name.to_owned()),
+        }
+    }
+
+    pub fn is_deleted(&self) -> bool {
+        *self == Author::Deleted
+    }
+}
+
+impl fmt::Display for Author {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Author::User(name) => f.write_str(name),
+            Author::Deleted => f.write_str("[deleted]"),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Author {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name: Option<String> = Option::deserialize(deserializer)?;
+        Ok(Author::parse(name.as_deref().unwrap_or_default()))
+    }
+}
+
+/// A Reddit comment, as returned from a user's comment history.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct Comment {
+    pub name: String,
+    /// Fullname of the post that the comment was made in.
+    pub link_id: String,
+    #[serde(default)]
+    pub permalink: Option<String>,
+    #[serde(default)]
+    pub body: String,
+}
+
+/// Wrapper that Reddit puts around every object in a listing.
+#[derive(Debug, Deserialize)]
+pub struct Thing<T> {
+    pub data: T,
+}
+
+/// Typed response from one of Reddit's listing endpoints.
+#[derive(Debug, Deserialize)]
+pub struct ListingResponse<T> {
+    pub data: ListingData<T>,
+}
+
+/// Contents of a listing response.
+#[derive(Debug, Deserialize)]
+pub struct ListingData<T> {
+    pub children: Vec<Thing<T>>,
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{AccessTokenResponse, Config};
+    use super::{
+        high_profile_owners, is_reddit_user_agent, reddit_user_agent, AccessTokenResponse, Author,
+        Config, GithubApp, ListingResponse, Post, Provenance, RedditAccess, Severity, Source,
+        DEFAULT_HIGH_PROFILE_OWNERS,
+    };
+    use crate::issues::IssueMode;
+    use crate::retention::Retention;
     use std::env;
 
+    fn valid_config() -> Config {
+        Config {
+            subreddit: "rust+learn_rust".to_owned(),
+            invited_subreddits: vec!["programming".to_owned()],
+            ..Config::for_tests()
+        }
+    }
+
+    #[test]
+    fn valid_config_has_no_problems() {
+        assert_eq!(valid_config().validate(), vec![]);
+    }
+
+    #[test]
+    fn invalid_configs() {
+        type Change = fn(&mut Config);
+        let cases: Vec<(Change, &[(&str, Severity)])> = vec![
+            (|c| c.password.clear(), &[("CFL_PASSWORD", Severity::Error)]),
+            (|c| c.username.clear(), &[("CFL_USERNAME", Severity::Error)]),
+            (
+                |c| c.github_username.clear(),
+                &[("CFL_GITHUB_USERNAME", Severity::Error)],
+            ),
+            (
+                |c| c.client_id = "abc def".to_owned(),
+                &[("CFL_CLIENT_ID", Severity::Error)],
+            ),
+            (
+                |c| c.client_secret = "secret\n".to_owned(),
+                &[("CFL_CLIENT_SECRET", Severity::Error)],
+            ),
+            (
+                |c| c.user_agent = "my bot".to_owned(),
+                &[("CFL_USER_AGENT", Severity::Warning)],
+            ),
+            (
+                |c| c.subreddit = "https://www.reddit.com/r/rust/".to_owned(),
+                &[("CFL_SUBREDDIT", Severity::Error)],
+            ),
+            (
+                |c| c.subreddit = "rust+a+this_name_is_far_too_long".to_owned(),
+                &[
+                    ("CFL_SUBREDDIT", Severity::Error),
+                    ("CFL_SUBREDDIT", Severity::Error),
+                ],
+            ),
+            (
+                |c| c.subreddit = " + ".to_owned(),
+                &[("CFL_SUBREDDIT", Severity::Error)],
+            ),
+            (
+                |c| c.invited_subreddits = vec!["no spaces".to_owned()],
+                &[("CFL_INVITED_SUBREDDITS", Severity::Error)],
+            ),
+            (
+                |c| c.sandbox_subreddit = Some("x".to_owned()),
+                &[("CFL_SANDBOX_SUBREDDIT", Severity::Error)],
+            ),
+            (
+                |c| c.search_interval = 0,
+                &[("CFL_SEARCH_INTERVAL", Severity::Error)],
+            ),
+            (
+                |c| c.visibility_check_interval = 0,
+                &[("CFL_VISIBILITY_CHECK_INTERVAL", Severity::Error)],
+            ),
+            (
+                |c| c.check_concurrency = 0,
+                &[("CFL_CHECK_CONCURRENCY", Severity::Error)],
+            ),
+            (
+                |c| c.max_replies_per_hour = Some(0),
+                &[("CFL_MAX_REPLIES_PER_HOUR", Severity::Warning)],
+            ),
+            (
+                |c| c.digest_at = Some("Mon 09:00".parse().unwrap()),
+                &[("CFL_DIGEST_AT", Severity::Error)],
+            ),
+            (
+                |c| c.digest_thread = Some("t3_abc".to_owned()),
+                &[("CFL_DIGEST_AT", Severity::Warning)],
+            ),
+            (
+                |c| {
+                    c.digest_at = Some("Mon 09:00".parse().unwrap());
+                    c.digest_modmail = Some("mod mail".to_owned());
+                },
+                &[("CFL_DIGEST_MODMAIL", Severity::Error)],
+            ),
+            (
+                |c| c.github_issue_mode = IssueMode::Also,
+                &[("CFL_GITHUB_ISSUE_MODE", Severity::Error)],
+            ),
+            (
+                |c| {
+                    c.github_issue_mode = IssueMode::Also;
+                    c.github_token = Some("ghp_token".to_owned());
+                    c.github_app = Some(GithubApp {
+                        app_id: 1,
+                        installation_id: 2,
+                        key_path: "no-such-key.pem".into(),
+                    });
+                },
+                &[
+                    ("CFL_GITHUB_APP_KEY_PATH", Severity::Error),
+                    ("CFL_GITHUB_TOKEN", Severity::Warning),
+                ],
+            ),
+            (
+                |c| {
+                    c.subreddit_locales
+                        .insert("rust-lang".to_owned(), "../de".to_owned());
+                },
+                &[
+                    ("CFL_SUBREDDIT_LOCALES", Severity::Error),
+                    ("CFL_SUBREDDIT_LOCALES", Severity::Error),
+                ],
+            ),
+            (
+                |c| {
+                    c.subreddit_locales
+                        .insert("rust".to_owned(), "xx".to_owned());
+                },
+                &[("CFL_SUBREDDIT_LOCALES", Severity::Warning)],
+            ),
+            (
+                // every problem is reported, not just the first
+                |c| {
+                    c.password.clear();
+                    c.user_agent = "curl".to_owned();
+                    c.max_pages = 0;
+                },
+                &[
+                    ("CFL_PASSWORD", Severity::Error),
+                    ("CFL_USER_AGENT", Severity::Warning),
+                    ("CFL_MAX_PAGES", Severity::Error),
+                ],
+            ),
+        ];
+        for (i, (change, expected)) in cases.into_iter().enumerate() {
+            let mut config = valid_config();
+            change(&mut config);
+            let problems = config.validate();
+            let found: Vec<(&str, Severity)> =
+                problems.iter().map(|p| (p.setting, p.severity)).collect();
+            assert_eq!(found, expected, "case {}", i);
+            assert!(problems.iter().all(|p| !p.suggestion.is_empty()));
+        }
+    }
+
+    #[test]
+    fn selects_reddit_access() {
+        fn public() -> Config {
+            Config {
+                username: String::new(),
+                password: String::new(),
+                client_id: String::new(),
+                client_secret: String::new(),
+                findings_path: Some("findings.jsonl".into()),
+                ..valid_config()
+            }
+        }
+        type Change = fn(&mut Config);
+        let cases: Vec<(Change, RedditAccess)> = vec![
+            (|_| {}, RedditAccess::Public),
+            (|c| *c = valid_config(), RedditAccess::Account),
+            (|c| c.username = "bot".to_owned(), RedditAccess::Account),
+            (|c| c.findings_path = None, RedditAccess::Account),
+            (
+                |c| {
+                    c.findings_path = None;
+                    c.http_listen = Some("127.0.0.1:8080".parse().unwrap());
+                },
+                RedditAccess::Public,
+            ),
+            (
+                |c| {
+                    c.findings_path = None;
+                    c.feed_path = Some("feed.xml".into());
+                },
+                RedditAccess::Public,
+            ),
+            (
+                |c| {
+                    c.findings_path = None;
+                    c.event_socket = Some("events.sock".into());
+                },
+                RedditAccess::Public,
+            ),
+            (|c| c.subreddit.clear(), RedditAccess::Account),
+            (
+                |c| c.search_query = Some("github.com".to_owned()),
+                RedditAccess::Account,
+            ),
+            (
+                |c| c.watch_user = Some("someone".to_owned()),
+                RedditAccess::Account,
+            ),
+        ];
+        for (i, (change, expected)) in cases.into_iter().enumerate() {
+            let mut config = public();
+            change(&mut config);
+            assert_eq!(config.reddit_access(), expected, "case {}", i);
+        }
+        assert!(public().summary().contains("reading public listings"));
+    }
+
+    #[test]
+    fn public_access_cannot_post() {
+        let mut config = Config {
+            username: String::new(),
+            password: String::new(),
+            client_id: String::new(),
+            client_secret: String::new(),
+            http_listen: Some("127.0.0.1:8080".parse().unwrap()),
+            ..valid_config()
+        };
+        assert_eq!(config.validate(), vec![]);
+        config.digest_at = Some("Mon 09:00".parse().unwrap());
+        config.digest_thread = Some("t3_abc".to_owned());
+        config.digest_modmail = Some("rust".to_owned());
+        config.praise_mode = true;
+        config.sandbox_subreddit = Some("sandbox".to_owned());
+        let found: Vec<(&str, Severity)> = config
+            .validate()
+            .iter()
+            .map(|p| (p.setting, p.severity))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("CFL_DIGEST_THREAD", Severity::Error),
+                ("CFL_DIGEST_MODMAIL", Severity::Error),
+                ("CFL_PRAISE_MODE", Severity::Error),
+                ("CFL_SANDBOX_SUBREDDIT", Severity::Error),
+            ]
+        );
+        // without anywhere to report, the credentials are needed after all
+        config = Config {
+            http_listen: None,
+            ..config
+        };
+        assert!(config
+            .validate()
+            .iter()
+            .any(|p| p.setting == "CFL_USERNAME" && p.severity == Severity::Error));
+    }
+
+    #[test]
+    fn locales_by_subreddit() {
+        let mut config = valid_config();
+        config
+            .subreddit_locales
+            .insert("de_programmierung".to_owned(), "de".to_owned());
+        config
+            .subreddit_locales
+            .insert("bad".to_owned(), "../de".to_owned());
+        assert_eq!(config.locale("De_Programmierung"), "de");
+        assert_eq!(config.locale("rust"), "en");
+        assert_eq!(config.locale("bad"), "en");
+        assert_eq!(config.validate().len(), 1);
+    }
+
+    #[test]
+    fn problems_read_as_sentences() {
+        let mut config = valid_config();
+        config.subreddit = "rust-lang".to_owned();
+        assert_eq!(
+            config.validate()[0].to_string(),
+            "CFL_SUBREDDIT has 'rust-lang', which isn't a subreddit name. \
+             Use 2 to 21 letters, digits or underscores."
+        );
+        config.subreddit = "https://old.reddit.com/r/rust".to_owned();
+        assert_eq!(
+            config.validate()[0].to_string(),
+            "CFL_SUBREDDIT has 'https://old.reddit.com/r/rust', which isn't a subreddit \
+             name. Use just the subreddit's name, `rust`."
+        );
+    }
+
+    #[test]
+    fn reddit_user_agents() {
+        assert!(is_reddit_user_agent(
+            "linux:check_for_license:0.1.0 (by /u/Celeo)"
+        ));
+        for bad in &[
+            "check_for_license",
+            "linux:check_for_license (by /u/Celeo)",
+            "linux:check_for_license:0.1.0",
+            "linux:check_for_license:0.1.0 (by /u/)",
+            "linux::0.1.0 (by /u/Celeo)",
+        ] {
+            assert!(!is_reddit_user_agent(bad), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn summary_hides_secrets() {
+        let mut config = valid_config();
+        config.password = "hunter2-password".to_owned();
+        config.client_id = "client-id-value".to_owned();
+        config.client_secret = "client-secret-value".to_owned();
+        config.http_listen = Some("127.0.0.1:8080".parse().unwrap());
+        config.http_token = Some("http-token-value".to_owned());
+        config.github_token = Some("ghp_token_value".to_owned());
+        config.sentry_dsn = Some("https://dsnkey@o1.ingest.sentry.io/42".parse().unwrap());
+        config.footer_template = Some("custom footer text".to_owned());
+
+        let summary = config.summary();
+        for secret in &[
+            "hunter2-password",
+            "client-id-value",
+            "client-secret-value",
+            "http-token-value",
+            "ghp_token_value",
+            "dsnkey",
+            "sentry.io",
+        ] {
+            assert!(!summary.contains(secret), "{} in {}", secret, summary);
+        }
+        assert!(summary.contains("HTTP listener: 127.0.0.1:8080 (token ***)"));
+        assert!(summary.contains("GitHub account: someone (token ***)"));
+        assert!(summary.contains("Sentry: enabled (DSN ***)"));
+        assert!(summary.contains("Footer: custom template"));
+        assert!(valid_config().summary().contains("Sentry: disabled"));
+    }
+
+    #[test]
+    fn builds_user_agents() {
+        let built = reddit_user_agent(None, "Celeo");
+        assert_eq!(
+            built,
+            format!(
+                "{}:check_for_license:{} (by /u/Celeo)",
+                env::consts::OS,
+                env!("CARGO_PKG_VERSION")
+            )
+        );
+        assert!(is_reddit_user_agent(&built));
+        assert_eq!(reddit_user_agent(None, "/u/Celeo"), built);
+        // a configured user agent is only used if it follows the convention
+        let custom = "web:license-bot:2.0 (by /u/someone)";
+        assert_eq!(reddit_user_agent(Some(custom), "Celeo"), custom);
+        assert_eq!(reddit_user_agent(Some("curl"), "Celeo"), built);
+
+        assert_eq!(
+            valid_config().github_user_agent(),
+            format!(
+                "check_for_license/{} (+https://github.com/celeo/check_for_license; someone)",
+                env!("CARGO_PKG_VERSION")
+            )
+        );
+    }
+
     #[test]
     fn config_from_env() {
         env::set_var("CFL_USERNAME", "a");
@@ -51,15 +1907,52 @@ mod tests {
         env::set_var("CFL_CLIENT_ID", "d");
         env::set_var("CFL_CLIENT_SECRET", "e");
         env::set_var("CFL_GITHUB_USERNAME", "f");
+        env::set_var("CFL_SUBREDDIT", "/r/Rust, programming");
+        env::set_var("CFL_INVITED_SUBREDDITS", "r/RustJerk,no spaces");
+        env::set_var("CFL_SUBREDDIT_LOCALES", "/r/De_EDV:de");
+        env::set_var("CFL_FINDINGS_RETENTION", "drop");
 
         let c = Config::from_env().unwrap();
 
         assert_eq!(c.username, "a");
         assert_eq!(c.password, "b");
-        assert_eq!(c.user_agent, "c");
+        assert_eq!(c.user_agent, reddit_user_agent(None, "a"));
         assert_eq!(c.client_id, "d");
         assert_eq!(c.client_secret, "e");
         assert_eq!(c.github_username, "f");
+        assert_eq!(c.check_concurrency, 1);
+        assert_eq!(c.state_dir, std::path::PathBuf::from("."));
+        assert_eq!(c.subreddit, "Rust+programming");
+        // what isn't a name is left for validation to report
+        assert_eq!(c.invited_subreddits, vec!["RustJerk", "no spaces"]);
+        assert_eq!(c.subreddit_locales["de_edv"], "de");
+        assert_eq!(c.findings_retention_days, 0);
+        assert_eq!(c.findings_retention, Retention::Drop);
+        assert_eq!(c.provenance.source("subreddit"), Source::Global);
+        assert_eq!(c.provenance.source("check_concurrency"), Source::Default);
+        // a setting given as its default still comes from the config
+        env::set_var("CFL_MAX_PAGES", "5");
+        let c = Config::from_env().unwrap();
+        assert_eq!(c.max_pages, 5);
+        assert_eq!(c.provenance.source("max_pages"), Source::Global);
+        assert_eq!(Provenance::default().source("max_pages"), Source::Default);
+    }
+
+    #[test]
+    fn high_profile_owners_can_be_added_and_taken_out() {
+        assert_eq!(
+            high_profile_owners(&[]).len(),
+            DEFAULT_HIGH_PROFILE_OWNERS.len()
+        );
+        let owners = high_profile_owners(&[
+            "MyCorp".to_owned(),
+            "-Rust-Lang".to_owned(),
+            "torvalds".to_owned(),
+        ]);
+        assert!(owners.contains(&"mycorp".to_owned()));
+        assert!(!owners.contains(&"rust-lang".to_owned()));
+        assert_eq!(owners.iter().filter(|o| *o == "torvalds").count(), 1);
+        assert_eq!(owners.len(), DEFAULT_HIGH_PROFILE_OWNERS.len());
     }
 
     #[test]
@@ -72,4 +1965,28 @@ mod tests {
         assert_eq!(a.expires_in, 1);
         assert_eq!(a.scope, "c");
     }
+
+    #[test]
+    fn posts_from_info_json() {
+        let s = r#"{"kind":"Listing","data":{"children":[
+            {"kind":"t3","data":{"name":"t3_a","domain":"github.com","url":"u","author":"x"}},
+            {"kind":"t3","data":{"name":"t3_b","author":"[deleted]","removed_by_category":"deleted"}},
+            {"kind":"t3","data":{"name":"t3_c","author":"[deleted]"}},
+            {"kind":"t3","data":{"name":"t3_d"}}
+        ]}}"#;
+        let l: ListingResponse<Post> = serde_json::from_str(s).unwrap();
+        let posts: Vec<Post> = l.data.children.into_iter().map(|t| t.data).collect();
+
+        assert_eq!(posts.len(), 4);
+        assert_eq!(posts[0].name, "t3_a");
+        assert_eq!(posts[0].domain, "github.com");
+        assert_eq!(posts[0].author, Author::User("x".to_owned()));
+        assert!(!posts[0].is_removed());
+        assert_eq!(posts[1].removed_by_category, Some("deleted".to_owned()));
+        assert!(posts[1].is_removed());
+        // the account is gone, but the post isn't
+        assert!(posts[2].author.is_deleted() && !posts[2].is_removed());
+        assert_eq!(posts[2].author.to_string(), "[deleted]");
+        assert_eq!(posts[3].author, Author::User(String::new()));
+    }
 }
@@ -0,0 +1,189 @@
+use log::warn;
+use std::{env, ffi::OsStr, io, process, time::Duration};
+use tokio::time;
+
+/// A notification message of `KEY=VALUE` lines.
+fn message(fields: &[(&str, &str)]) -> String {
+    fields
+        .iter()
+        .map(|(key, value)| format!("{}={}\n", key, value))
+        .collect()
+}
+
+/// Send an `sd_notify` message to systemd, if it started the bot with
+/// `NOTIFY_SOCKET` set. Nothing is sent on platforms other than Unix.
+fn notify(fields: &[(&str, &str)]) {
+    let socket = env::var_os("NOTIFY_SOCKET");
+    if let Err(e) = send(socket.as_deref(), &message(fields)) {
+        warn!("Could not notify systemd: {}", e);
+    }
+}
+
+/// Send a message to the given socket, returning whether there was one.
+fn send(socket: Option<&OsStr>, message: &str) -> io::Result<bool> {
+    let socket = match socket {
+        Some(s) => s,
+        None => return Ok(false),
+    };
+    #[cfg(unix)]
+    {
+        unix::send(socket, message)?;
+        Ok(true)
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = (socket, message);
+        Ok(false)
+    }
+}
+
+/// Tell systemd that the bot has started up.
+pub fn ready() {
+    notify(&[("READY", "1")]);
+}
+
+/// Tell systemd that the bot is shutting down.
+pub fn stopping() {
+    notify(&[("STOPPING", "1")]);
+}
+
+/// Tell systemd what the bot is up to, for `systemctl status` to show.
+/// Only the first line is sent.
+pub fn status(text: &str) {
+    notify(&[("STATUS", text.lines().next().unwrap_or_default())]);
+}
+
+/// Tell systemd's watchdog that the bot is still running.
+pub fn watchdog() {
+    if watchdog_interval().is_some() {
+        notify(&[("WATCHDOG", "1")]);
+    }
+}
+
+/// How often the watchdog needs to hear from the bot, if it's enabled for it:
+/// half of `WATCHDOG_USEC`, to leave some slack.
+fn watchdog_interval() -> Option<Duration> {
+    parse_watchdog(
+        env::var("WATCHDOG_USEC").ok().as_deref(),
+        env::var("WATCHDOG_PID").ok().as_deref(),
+        process::id(),
+    )
+}
+
+fn parse_watchdog(usec: Option<&str>, pid: Option<&str>, own_pid: u32) -> Option<Duration> {
+    if let Some(pid) = pid {
+        if pid.parse::<u32>().ok()? != own_pid {
+            return None;
+        }
+    }
+    let usec: u64 = usec?.parse().ok().filter(|&u| u > 0)?;
+    Some(Duration::from_micros(usec / 2))
+}
+
+/// Wait for a while, keeping the watchdog fed in the meantime.
+pub async fn sleep(duration: Duration) {
+    let interval = match watchdog_interval() {
+        Some(i) => i,
+        None => return time::sleep(duration).await,
+    };
+    let mut left = duration;
+    while left > interval {
+        time::sleep(interval).await;
+        watchdog();
+        left -= interval;
+    }
+    time::sleep(left).await;
+}
+
+#[cfg(unix)]
+mod unix {
+    use std::{ffi::OsStr, io, os::unix::net::UnixDatagram};
+
+    /// Send a datagram to the socket systemd named in `NOTIFY_SOCKET`.
+    ///
+    /// Names starting with `@` are in Linux's abstract namespace.
+    pub fn send(socket: &OsStr, message: &str) -> io::Result<()> {
+        let conn = UnixDatagram::unbound()?;
+        let bytes = socket.to_string_lossy();
+        if let Some(name) = bytes.strip_prefix('@') {
+            return send_abstract(&conn, name, message);
+        }
+        conn.send_to(message.as_bytes(), socket)?;
+        Ok(())
+    }
+
+    #[cfg(target_os = "linux")]
+    fn send_abstract(conn: &UnixDatagram, name: &str, message: &str) -> io::Result<()> {
+        use std::os::{linux::net::SocketAddrExt, unix::net::SocketAddr};
+        let addr = SocketAddr::from_abstract_name(name.as_bytes())?;
+        conn.send_to_addr(message.as_bytes(), &addr)?;
+        Ok(())
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn send_abstract(_: &UnixDatagram, name: &str, _: &str) -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("abstract socket @{} is only supported on Linux", name),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{message, parse_watchdog, send};
+    use std::time::Duration;
+
+    #[test]
+    fn formats_messages() {
+        assert_eq!(message(&[("READY", "1")]), "READY=1\n");
+        assert_eq!(
+            message(&[("STOPPING", "1"), ("STATUS", "Shutting down")]),
+            "STOPPING=1\nSTATUS=Shutting down\n"
+        );
+    }
+
+    #[test]
+    fn watchdog_settings() {
+        assert_eq!(
+            parse_watchdog(Some("30000000"), None, 7),
+            Some(Duration::from_secs(15))
+        );
+        assert_eq!(
+            parse_watchdog(Some("30000000"), Some("7"), 7),
+            Some(Duration::from_secs(15))
+        );
+        // meant for another process
+        assert_eq!(parse_watchdog(Some("30000000"), Some("8"), 7), None);
+        assert_eq!(parse_watchdog(None, None, 7), None);
+        assert_eq!(parse_watchdog(Some("0"), None, 7), None);
+        assert_eq!(parse_watchdog(Some("soon"), None, 7), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn sends_datagrams() {
+        use std::os::unix::net::UnixDatagram;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("notify.sock");
+        let listener = UnixDatagram::bind(&path).unwrap();
+        assert!(send(Some(path.as_os_str()), "READY=1\n").unwrap());
+        let mut buf = [0; 64];
+        let n = listener.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..n], b"READY=1\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn missing_socket_is_an_error() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("gone.sock");
+        assert!(send(Some(path.as_os_str()), "READY=1\n").is_err());
+    }
+
+    #[test]
+    fn no_socket_is_a_no_op() {
+        assert!(!send(None, "READY=1\n").unwrap());
+    }
+}
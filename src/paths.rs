@@ -0,0 +1,138 @@
+use anyhow::{Context, Result};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// File created and removed to check that the state directory is writable.
+const WRITE_PROBE: &str = ".cfl-write-probe";
+
+/// Create the state directory if needed and make sure it can be written to.
+///
+/// Called at startup so that a bad directory fails immediately, rather than
+/// after the first pass over a listing.
+pub fn prepare_state_dir(dir: &Path) -> Result<()> {
+    fs::create_dir_all(dir)
+        .with_context(|| format!("Could not create state directory {}", dir.display()))?;
+    let probe = dir.join(WRITE_PROBE);
+    fs::write(&probe, b"")
+        .and_then(|_| fs::remove_file(&probe))
+        .with_context(|| {
+            format!(
+                "State directory {} is not writable; set CFL_STATE_DIR to a directory the bot can write to",
+                dir.display()
+            )
+        })
+}
+
+/// File holding everything persisted about a listing.
+pub fn state_file(dir: &Path, store_key: &str) -> PathBuf {
+    dir.join(format!("state-{}.json", store_key))
+}
+
+/// File holding replies waiting for approval.
+pub fn approval_file(dir: &Path) -> PathBuf {
+    dir.join("approvals.json")
+}
+
+/// File locked while the approval queue is being changed.
+pub fn approval_lock_file(dir: &Path) -> PathBuf {
+    dir.join("approvals.lock")
+}
+
+/// File listing repositories that the bot shouldn't reply about.
+pub fn suppression_file(dir: &Path) -> PathBuf {
+    dir.join("suppressed.json")
+}
+
+/// File recording every reply the bot has made, by author and repository.
+pub fn history_file(dir: &Path) -> PathBuf {
+    dir.join("replies.jsonl")
+}
+
+/// File recording the posts the bot is replying or has replied to.
+pub fn claim_file(dir: &Path) -> PathBuf {
+    dir.join("claims.json")
+}
+
+/// File recording the repositories claimed for their authors' posts in
+/// other subreddits.
+pub fn repo_claim_file(dir: &Path) -> PathBuf {
+    dir.join("repo_claims.json")
+}
+
+/// File keeping the latest checks of each repository.
+pub fn repo_history_file(dir: &Path) -> PathBuf {
+    dir.join("repo_history.json")
+}
+
+/// File recording the subreddits where only approved users may comment.
+pub fn restriction_file(dir: &Path) -> PathBuf {
+    dir.join("restricted.json")
+}
+
+/// File recording the issues the bot has opened on GitHub.
+pub fn issue_file(dir: &Path) -> PathBuf {
+    dir.join("issues.jsonl")
+}
+
+/// File remembering when the weekly digest was last posted.
+pub fn digest_file(dir: &Path) -> PathBuf {
+    dir.join("digest.json")
+}
+
+/// File listing the posts already processed in a listing, before state files.
+pub fn processed_file(dir: &Path, store_key: &str) -> PathBuf {
+    dir.join(format!("processed-{}.json", store_key))
+}
+
+/// File holding the position reached in a listing, before state files.
+pub fn cursor_file(dir: &Path, store_key: &str) -> PathBuf {
+    dir.join(format!("cursor-{}.json", store_key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{cursor_file, prepare_state_dir, processed_file, state_file};
+    use std::{fs, path::Path};
+
+    #[test]
+    fn creates_missing_state_dir() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("nested").join("state");
+        prepare_state_dir(&dir).unwrap();
+        assert!(dir.is_dir());
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn unusable_state_dir_fails_clearly() {
+        let tmp = tempfile::tempdir().unwrap();
+        let file = tmp.path().join("not-a-dir");
+        fs::write(&file, "").unwrap();
+
+        let err = prepare_state_dir(&file.join("state")).unwrap_err();
+        assert!(
+            format!("{}", err).contains("Could not create state directory"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn state_files_live_in_state_dir() {
+        let dir = Path::new("/var/lib/cfl");
+        assert_eq!(
+            processed_file(dir, "rust"),
+            Path::new("/var/lib/cfl/processed-rust.json")
+        );
+        assert_eq!(
+            state_file(dir, "rust"),
+            Path::new("/var/lib/cfl/state-rust.json")
+        );
+        assert_eq!(
+            cursor_file(dir, "u_someone"),
+            Path::new("/var/lib/cfl/cursor-u_someone.json")
+        );
+    }
+}
@@ -0,0 +1,431 @@
+use crate::latency::Stages;
+use crate::models::Author;
+use crate::preview::RepoSummary;
+use crate::util::GhRepo;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fmt};
+
+/// Details of a post that are kept for the event log.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct PostDetails {
+    pub title: String,
+    pub subreddit: String,
+    pub permalink: String,
+    pub author: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub flair: String,
+}
+
+impl PostDetails {
+    pub fn author(&self) -> Author {
+        Author::parse(&self.author)
+    }
+}
+
+/// A repository linked in a post, with the URL it was linked with.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct RepoLink {
+    #[serde(flatten)]
+    pub repo: GhRepo,
+    pub url: String,
+    /// SPDX id of the license the repository had when it was last checked,
+    /// if it's been removed since.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub removed_license: Option<String>,
+    /// What GitHub said about the repository when it was last checked.
+    #[serde(default, skip_serializing_if = "RepoSummary::is_empty")]
+    pub summary: RepoSummary,
+}
+
+/// A reply about unlicensed repositories that's been held back to be made later.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct PendingReply {
+    pub fullname: String,
+    /// Every repository the reply is about.
+    pub repos: Vec<RepoLink>,
+    #[serde(default)]
+    pub details: PostDetails,
+    /// Unix timestamp of when the reply was held back.
+    pub queued_at: u64,
+    /// Whether the repositories haven't been checked yet, because the checks
+    /// were deferred while the GitHub quota was low.
+    #[serde(default)]
+    pub deferred: bool,
+    /// Permalink of an earlier submission of the same link where the bot
+    /// replied about the repositories, to point to instead of replying in full.
+    #[serde(default)]
+    pub earlier: Option<String>,
+    /// Link to where the bot replied about the repositories on the author's
+    /// post of them in another subreddit, to point to in one line instead.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cross_post: Option<String>,
+    /// Unix timestamp before which deferred checks aren't made, when they're
+    /// waiting for an empty repository to get its first commit.
+    #[serde(default)]
+    pub check_after: Option<u64>,
+    /// Unix timestamp before which a held back reply isn't made, when it's
+    /// waiting to follow up on a work in progress.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub not_before: Option<u64>,
+    /// When the post was submitted, and when the reply went through each
+    /// stage since.
+    #[serde(default, skip_serializing_if = "Stages::is_empty")]
+    pub stages: Stages,
+}
+
+/// The queues of work waiting to be done, in the order they're worked on.
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QueueKind {
+    /// Replies approved in the approval queue, which are never dropped.
+    Approved,
+    /// Checks that couldn't tell whether a repository has a license.
+    Retry,
+    /// Replies held back until the active hours.
+    Delayed,
+    /// Checks deferred while the GitHub quota was low.
+    Recheck,
+}
+
+impl QueueKind {
+    pub fn label(self) -> &'static str {
+        match self {
+            QueueKind::Approved => "approved",
+            QueueKind::Retry => "retries",
+            QueueKind::Delayed => "delayed",
+            QueueKind::Recheck => "re-checks",
+        }
+    }
+}
+
+impl PendingReply {
+    /// The queue the reply is waiting in.
+    pub fn kind(&self) -> QueueKind {
+        match (self.deferred, self.check_after) {
+            (false, _) => QueueKind::Delayed,
+            (true, Some(_)) => QueueKind::Retry,
+            (true, None) => QueueKind::Recheck,
+        }
+    }
+}
+
+/// How much of each queue is kept.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Limits {
+    /// Most items kept in each queue, with 0 for any number.
+    pub retries: usize,
+    pub delayed: usize,
+    pub rechecks: usize,
+    /// Seconds after which a retry is dropped, with 0 for never.
+    pub retry_max_age: u64,
+}
+
+impl Limits {
+    fn max(&self, kind: QueueKind) -> usize {
+        match kind {
+            QueueKind::Approved => 0,
+            QueueKind::Retry => self.retries,
+            QueueKind::Delayed => self.delayed,
+            QueueKind::Recheck => self.rechecks,
+        }
+    }
+}
+
+/// Why an item was dropped from its queue.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Dropped {
+    /// The queue was full, and the item was the oldest in it.
+    Full,
+    /// The retry was too old to be worth making.
+    Expired,
+}
+
+/// Drop from the held back replies whatever doesn't fit in their queues at
+/// `now`, returning what was dropped and why.
+///
+/// Retries are dropped once they're older than their maximum age, and then
+/// the oldest items of any queue beyond its maximum size.
+pub fn enforce(
+    pending: &mut Vec<PendingReply>,
+    limits: &Limits,
+    now: u64,
+) -> Vec<(PendingReply, Dropped)> {
+    let mut dropped = vec![];
+    let mut keep = vec![];
+    for reply in pending.drain(..) {
+        let age = now.saturating_sub(reply.queued_at);
+        if reply.kind() == QueueKind::Retry
+            && limits.retry_max_age > 0
+            && age >= limits.retry_max_age
+        {
+            dropped.push((reply, Dropped::Expired));
+        } else {
+            keep.push(reply);
+        }
+    }
+    for kind in [QueueKind::Retry, QueueKind::Delayed, QueueKind::Recheck] {
+        let max = limits.max(kind);
+        let mut ages: Vec<u64> = keep
+            .iter()
+            .filter(|r| r.kind() == kind)
+            .map(|r| r.queued_at)
+            .collect();
+        if max == 0 || ages.len() <= max {
+            continue;
+        }
+        ages.sort_unstable();
+        // drop those older than the cutoff, then as many queued at the
+        // cutoff as are still too many, in their order
+        let excess = ages.len() - max;
+        let cutoff = ages[excess - 1];
+        let mut at_cutoff = excess - ages.iter().filter(|&&a| a < cutoff).count();
+        for reply in std::mem::take(&mut keep) {
+            let drop = reply.kind() == kind
+                && (reply.queued_at < cutoff || (reply.queued_at == cutoff && at_cutoff > 0));
+            if !drop {
+                keep.push(reply);
+                continue;
+            }
+            if reply.queued_at == cutoff {
+                at_cutoff -= 1;
+            }
+            dropped.push((reply, Dropped::Full));
+        }
+    }
+    *pending = keep;
+    dropped
+}
+
+/// How many queued items may still be worked on in this pass.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Budget(Option<usize>);
+
+impl Budget {
+    /// A budget of `per_pass` items, with 0 for no limit.
+    pub fn new(per_pass: usize) -> Self {
+        Budget(if per_pass == 0 { None } else { Some(per_pass) })
+    }
+
+    /// Take one item out of the budget, returning whether there was any left.
+    pub fn take(&mut self) -> bool {
+        match &mut self.0 {
+            None => true,
+            Some(0) => false,
+            Some(left) => {
+                *left -= 1;
+                true
+            }
+        }
+    }
+
+    pub fn is_spent(&self) -> bool {
+        self.0 == Some(0)
+    }
+}
+
+/// How many items wait in each queue, and how many were dropped from each
+/// since the bot started.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct QueueStats {
+    /// As of the last pass.
+    pub waiting: BTreeMap<QueueKind, usize>,
+    pub dropped: BTreeMap<QueueKind, u64>,
+}
+
+impl QueueStats {
+    /// Count what waits in the queues now.
+    pub fn count(&mut self, approved: usize, pending: &[PendingReply]) {
+        self.waiting.clear();
+        self.waiting.insert(QueueKind::Approved, approved);
+        for kind in [QueueKind::Retry, QueueKind::Delayed, QueueKind::Recheck] {
+            let waiting = pending.iter().filter(|r| r.kind() == kind).count();
+            self.waiting.insert(kind, waiting);
+        }
+    }
+
+    pub fn drop_one(&mut self, kind: QueueKind) {
+        *self.dropped.entry(kind).or_default() += 1;
+    }
+}
+
+impl fmt::Display for QueueStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let waiting: Vec<String> = self
+            .waiting
+            .iter()
+            .filter(|(_, n)| **n > 0)
+            .map(|(kind, n)| format!("{} {}", n, kind.label()))
+            .collect();
+        let dropped = self.dropped.values().sum::<u64>();
+        match &waiting[..] {
+            [] => write!(f, "nothing queued, {} dropped", dropped),
+            waiting => write!(f, "{} queued, {} dropped", waiting.join(", "), dropped),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{enforce, Budget, Dropped, Limits, PendingReply, QueueKind, QueueStats};
+    use crate::latency::Stages;
+
+    const HOUR: u64 = 60 * 60;
+
+    fn reply(fullname: &str, kind: QueueKind, queued_at: u64) -> PendingReply {
+        PendingReply {
+            fullname: fullname.to_owned(),
+            repos: vec![],
+            details: Default::default(),
+            queued_at,
+            deferred: kind != QueueKind::Delayed,
+            earlier: None,
+            cross_post: None,
+            check_after: if kind == QueueKind::Retry {
+                Some(queued_at + 3 * HOUR)
+            } else {
+                None
+            },
+            not_before: None,
+            stages: Stages::default(),
+        }
+    }
+
+    fn names(replies: &[PendingReply]) -> Vec<&str> {
+        replies.iter().map(|r| r.fullname.as_str()).collect()
+    }
+
+    const LIMITS: Limits = Limits {
+        retries: 0,
+        delayed: 0,
+        rechecks: 0,
+        retry_max_age: 0,
+    };
+
+    #[test]
+    fn tells_the_queues_apart() {
+        assert_eq!(reply("a", QueueKind::Retry, 0).kind(), QueueKind::Retry);
+        assert_eq!(reply("a", QueueKind::Delayed, 0).kind(), QueueKind::Delayed);
+        assert_eq!(reply("a", QueueKind::Recheck, 0).kind(), QueueKind::Recheck);
+        assert!(QueueKind::Approved < QueueKind::Retry);
+        assert!(QueueKind::Delayed < QueueKind::Recheck);
+    }
+
+    #[test]
+    fn drops_the_oldest_rechecks_and_delayed_replies_when_full() {
+        let now = 100 * HOUR;
+        let mut pending = vec![
+            reply("t3_r2", QueueKind::Recheck, now - 2 * HOUR),
+            reply("t3_r1", QueueKind::Recheck, now - 3 * HOUR),
+            reply("t3_d1", QueueKind::Delayed, now - 5 * HOUR),
+            reply("t3_r3", QueueKind::Recheck, now - HOUR),
+            reply("t3_d2", QueueKind::Delayed, now - 5 * HOUR),
+            reply("t3_d3", QueueKind::Delayed, now),
+        ];
+        let limits = Limits {
+            delayed: 2,
+            rechecks: 1,
+            ..LIMITS
+        };
+        let dropped = enforce(&mut pending, &limits, now);
+        assert_eq!(names(&pending), vec!["t3_r3", "t3_d2", "t3_d3"]);
+        let dropped: Vec<(&str, Dropped)> = dropped
+            .iter()
+            .map(|(r, why)| (r.fullname.as_str(), *why))
+            .collect();
+        assert_eq!(
+            dropped,
+            vec![
+                ("t3_d1", Dropped::Full),
+                ("t3_r2", Dropped::Full),
+                ("t3_r1", Dropped::Full),
+            ]
+        );
+
+        // of items queued at the same time, the first go, but never before older ones
+        let mut pending = vec![
+            reply("t3_a", QueueKind::Delayed, 5),
+            reply("t3_b", QueueKind::Delayed, 5),
+            reply("t3_c", QueueKind::Delayed, 4),
+        ];
+        let limits = Limits {
+            delayed: 1,
+            ..LIMITS
+        };
+        assert_eq!(enforce(&mut pending, &limits, now).len(), 2);
+        assert_eq!(names(&pending), vec!["t3_b"]);
+
+        // no limit keeps everything
+        let mut pending = vec![reply("t3_r1", QueueKind::Recheck, 0); 5];
+        assert!(enforce(&mut pending, &LIMITS, now).is_empty());
+        assert_eq!(pending.len(), 5);
+    }
+
+    #[test]
+    fn retries_age_out_before_the_queue_fills() {
+        let now = 100 * HOUR;
+        let mut pending = vec![
+            reply("t3_old", QueueKind::Retry, now - 48 * HOUR),
+            reply("t3_new", QueueKind::Retry, now - HOUR),
+            reply("t3_newer", QueueKind::Retry, now),
+            // only retries age out
+            reply("t3_held", QueueKind::Delayed, now - 72 * HOUR),
+        ];
+        let limits = Limits {
+            retries: 1,
+            retry_max_age: 48 * HOUR,
+            ..LIMITS
+        };
+        let dropped: Vec<(String, Dropped)> = enforce(&mut pending, &limits, now)
+            .into_iter()
+            .map(|(r, why)| (r.fullname, why))
+            .collect();
+        assert_eq!(
+            dropped,
+            vec![
+                ("t3_old".to_owned(), Dropped::Expired),
+                ("t3_new".to_owned(), Dropped::Full),
+            ]
+        );
+        assert_eq!(names(&pending), vec!["t3_newer", "t3_held"]);
+
+        // the same retry, a moment younger, is kept
+        let mut pending = vec![reply("t3_old", QueueKind::Retry, now - 48 * HOUR + 1)];
+        assert!(enforce(&mut pending, &limits, now).is_empty());
+    }
+
+    #[test]
+    fn budgets_run_out() {
+        let mut budget = Budget::new(2);
+        assert!(budget.take() && budget.take());
+        assert!(budget.is_spent());
+        assert!(!budget.take());
+        let mut unlimited = Budget::new(0);
+        assert!((0..1000).all(|_| unlimited.take()));
+        assert!(!unlimited.is_spent());
+    }
+
+    #[test]
+    fn counts_the_queues() {
+        let mut stats = QueueStats::default();
+        stats.count(
+            2,
+            &[
+                reply("a", QueueKind::Retry, 0),
+                reply("b", QueueKind::Delayed, 0),
+                reply("c", QueueKind::Delayed, 0),
+            ],
+        );
+        stats.drop_one(QueueKind::Recheck);
+        assert_eq!(
+            stats.to_string(),
+            "2 approved, 1 retries, 2 delayed queued, 1 dropped"
+        );
+        assert_eq!(
+            QueueStats::default().to_string(),
+            "nothing queued, 0 dropped"
+        );
+        let json = serde_json::to_value(&stats).unwrap();
+        assert_eq!(json["waiting"]["approved"], 2);
+        assert_eq!(json["dropped"]["recheck"], 1);
+    }
+}
@@ -0,0 +1,97 @@
+use serde_json::Value;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tokio::sync::Notify;
+
+use crate::listing::Cursor;
+
+/// A page of a listing fetched while watching it, waiting to be processed:
+/// its posts checked and replied to.
+#[derive(Debug)]
+pub struct Batch {
+    /// The page's posts, newest first.
+    pub postings: Vec<Value>,
+    /// Where the listing is up to once the page has been processed, and
+    /// so the cursor to save with it.
+    pub cursor: Cursor,
+}
+
+/// Fullnames of the posts on a page, in the order they're listed.
+pub fn fullnames(postings: &[Value]) -> Vec<String> {
+    postings
+        .iter()
+        .filter_map(|p| p["data"]["name"].as_str())
+        .map(str::to_owned)
+        .collect()
+}
+
+/// A request to stop watching, shared by the bot's tasks.
+///
+/// Once it's made, no more pages are fetched, while those already fetched
+/// are still processed.
+#[derive(Debug, Default)]
+pub struct Stop {
+    requested: AtomicBool,
+    notify: Notify,
+}
+
+impl Stop {
+    pub fn request(&self) {
+        self.requested.store(true, Ordering::SeqCst);
+        self.notify.notify_waiters();
+    }
+
+    pub fn is_requested(&self) -> bool {
+        self.requested.load(Ordering::SeqCst)
+    }
+
+    /// Wait until stopping is requested.
+    pub async fn requested(&self) {
+        loop {
+            // registered before looking, so a request in between isn't missed
+            let notified = self.notify.notified();
+            if self.is_requested() {
+                return;
+            }
+            notified.await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fullnames, Stop};
+    use serde_json::json;
+    use std::{sync::Arc, time::Duration};
+
+    #[test]
+    fn lists_fullnames_in_order() {
+        let postings = [
+            json!({"data": {"name": "t3_b"}}),
+            json!({"data": {}}),
+            json!({"data": {"name": "t3_a"}}),
+        ];
+        assert_eq!(fullnames(&postings), vec!["t3_b", "t3_a"]);
+    }
+
+    #[tokio::test]
+    async fn wakes_everything_waiting_to_stop() {
+        let stop = Arc::new(Stop::default());
+        let waiting: Vec<_> = (0..3)
+            .map(|_| {
+                let stop = stop.clone();
+                tokio::spawn(async move { stop.requested().await })
+            })
+            .collect();
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert!(!stop.is_requested());
+        stop.request();
+        for task in waiting {
+            tokio::time::timeout(Duration::from_secs(1), task)
+                .await
+                .unwrap()
+                .unwrap();
+        }
+        // and anything waiting after the request doesn't wait at all
+        stop.requested().await;
+    }
+}
@@ -0,0 +1,410 @@
+use anyhow::{anyhow, Result};
+use std::time::Duration;
+
+use crate::duplicates::DuplicateMode;
+use crate::issues::IssueMode;
+use crate::models::{setting_name, Config, Source};
+use crate::queue::ApprovalMode;
+use crate::rules::is_invited;
+use crate::subreddit::Subreddit;
+
+const USAGE: &str = "Usage: check_for_license policy [subreddit]";
+
+/// One of the settings that decide whether, and how, the bot replies to a
+/// post, as it's in effect in a subreddit.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Gate {
+    pub name: &'static str,
+    /// The environment variable the value is set with.
+    pub setting: String,
+    /// The value, as shown in the policy.
+    pub value: String,
+    pub source: Source,
+}
+
+fn seconds(secs: u64) -> String {
+    humantime::format_duration(Duration::from_secs(secs)).to_string()
+}
+
+fn list(items: &[String]) -> String {
+    match items {
+        [] => "none".to_owned(),
+        items => items.join(", "),
+    }
+}
+
+fn off_or(value: u64, on: impl FnOnce(u64) -> String) -> String {
+    match value {
+        0 => "off".to_owned(),
+        value => on(value),
+    }
+}
+
+/// The settings in effect in `subreddit`, in the order they're shown.
+pub fn gates(config: &Config, subreddit: &Subreddit) -> Vec<Gate> {
+    let global = |name, field: &str, value: String| Gate {
+        name,
+        setting: setting_name(field),
+        value,
+        source: config.provenance.source(field),
+    };
+    let overridden = |name, field: &str, value: String, set: bool| Gate {
+        name,
+        setting: setting_name(field),
+        value,
+        source: if set {
+            Source::Subreddit
+        } else {
+            Source::Default
+        },
+    };
+    let locale = config.subreddit_locales.get(&subreddit.key());
+    let invited = is_invited(subreddit.name(), &config.invited_subreddits);
+    let mode = |mode| match mode {
+        DuplicateMode::Skip => "skip".to_owned(),
+        DuplicateMode::Reply => "reply, pointing to the earlier reply".to_owned(),
+    };
+    vec![
+        overridden(
+            "Subreddit rules",
+            "invited_subreddits",
+            if invited {
+                "invited, so not checked for a ban on bots".to_owned()
+            } else {
+                "checked for a ban on bots".to_owned()
+            },
+            invited,
+        ),
+        overridden(
+            "Reply language",
+            "subreddit_locales",
+            config.locale(subreddit.name()).to_owned(),
+            locale.is_some(),
+        ),
+        global(
+            "Approval",
+            "approval_mode",
+            match config.approval_mode {
+                ApprovalMode::Auto => "replies are made straight away".to_owned(),
+                ApprovalMode::Manual => "replies wait for a moderator".to_owned(),
+            },
+        ),
+        global(
+            "Reply cap",
+            "max_replies_per_hour",
+            config
+                .max_replies_per_hour
+                .map_or_else(|| "none".to_owned(), |n| format!("{} per hour", n)),
+        ),
+        global(
+            "Active hours",
+            "active_hours",
+            config.active_hours.as_ref().map_or_else(
+                || "any time".to_owned(),
+                |hours| format!("{} ({})", hours, config.timezone),
+            ),
+        ),
+        global(
+            "Repeat replies to an author",
+            "reply_dedup_ttl",
+            off_or(config.reply_dedup_ttl, |ttl| {
+                format!("skipped for {}", seconds(ttl))
+            }),
+        ),
+        global(
+            "Other submissions of a link",
+            "duplicate_window",
+            off_or(config.duplicate_window, |window| {
+                format!("within {} of a reply", seconds(window))
+            }),
+        ),
+        global(
+            "Other submissions of a link, when found",
+            "duplicate_mode",
+            mode(config.duplicate_mode),
+        ),
+        global(
+            "Author's posts in other subreddits",
+            "cross_sub_window_minutes",
+            off_or(config.cross_sub_window_minutes, |minutes| {
+                format!("within {} of a reply", seconds(minutes * 60))
+            }),
+        ),
+        global(
+            "Author's posts in other subreddits, when found",
+            "cross_sub_mode",
+            mode(config.cross_sub_mode),
+        ),
+        global(
+            "Work in progress phrases",
+            "wip_phrases",
+            list(&config.wip_phrases),
+        ),
+        global(
+            "Work in progress follow-up",
+            "wip_followup_days",
+            off_or(config.wip_followup_days, |days| {
+                format!("after {} days", days)
+            }),
+        ),
+        global(
+            "Author opt-out phrases",
+            "author_opt_out_phrases",
+            list(&config.author_opt_out_phrases),
+        ),
+        global(
+            "Exempt authors",
+            "exempt_authors",
+            list(&config.exempt_authors),
+        ),
+        global(
+            "Deleted authors",
+            "reply_to_deleted",
+            if config.reply_to_deleted {
+                "replied to"
+            } else {
+                "not replied to"
+            }
+            .to_owned(),
+        ),
+        global(
+            "High-profile owners",
+            "high_profile_owners",
+            list(&config.high_profile_owners),
+        ),
+        global("Most stars", "max_stars", config.max_stars.to_string()),
+        global(
+            "Young posts",
+            "young_post_age",
+            off_or(config.young_post_age, |age| {
+                format!("missing repositories looked for again for {}", seconds(age))
+            }),
+        ),
+        global(
+            "Repositories read per post",
+            "max_links_per_post",
+            match config.max_links_per_post {
+                0 => "any number".to_owned(),
+                max => format!("up to {}", max),
+            },
+        ),
+        global(
+            "Longest URL",
+            "max_url_len",
+            match config.max_url_len {
+                0 => "any length".to_owned(),
+                max => format!("{} bytes", max),
+            },
+        ),
+        global(
+            "Unrecognized licenses",
+            "treat_other_as_unlicensed",
+            if config.treat_other_as_unlicensed {
+                "count as no license"
+            } else {
+                "count as a license"
+            }
+            .to_owned(),
+        ),
+        global(
+            "Not counted as licenses",
+            "non_licenses",
+            list(&config.non_licenses),
+        ),
+        global(
+            "Removed replies before only reporting",
+            "auto_disable_after_removals",
+            config
+                .auto_disable_after_removals
+                .map_or_else(|| "never".to_owned(), |n| n.to_string()),
+        ),
+        global(
+            "GitHub issues",
+            "github_issue_mode",
+            match config.github_issue_mode {
+                IssueMode::Off => "off",
+                IssueMode::Instead => "opened instead of replying",
+                IssueMode::Also => "opened as well as replying",
+            }
+            .to_owned(),
+        ),
+        global(
+            "Most stars for an issue",
+            "github_issue_max_stars",
+            config.github_issue_max_stars.to_string(),
+        ),
+        global(
+            "Praise",
+            "praise_mode",
+            if config.praise_mode { "on" } else { "off" }.to_owned(),
+        ),
+        global(
+            "Most praise per day",
+            "max_praise_per_day",
+            config.max_praise_per_day.to_string(),
+        ),
+        global(
+            "Opt-out keyword",
+            "opt_out_keyword",
+            config.opt_out_keyword.clone(),
+        ),
+    ]
+}
+
+/// A table cell's text, which can't break out of its cell or the table.
+fn cell(text: &str) -> String {
+    text.replace('|', "\\|").replace(['\r', '\n'], " ")
+}
+
+/// The policy in effect in `subreddit`, as markdown for its wiki.
+pub fn render(config: &Config, subreddit: &Subreddit) -> String {
+    let mut out = format!(
+        "# Policy in /r/{}\n\n\
+         What decides whether check_for_license replies to a post here, and \
+         where each setting comes from.\n\n\
+         | Gate | Value | Setting | Source |\n\
+         |---|---|---|---|\n",
+        subreddit
+    );
+    for gate in gates(config, subreddit) {
+        out.push_str(&format!(
+            "| {} | {} | `{}` | {} |\n",
+            gate.name,
+            cell(&gate.value),
+            gate.setting,
+            gate.source.label()
+        ));
+    }
+    out
+}
+
+/// The subreddit a policy is asked for, or the first one watched.
+pub fn subreddit(config: &Config, asked: Option<&str>) -> Result<Subreddit> {
+    let parsed = match asked {
+        Some(name) => Subreddit::new(name),
+        None => Subreddit::parse_list(&config.subreddit).map(|list| list[0].clone()),
+    };
+    parsed.map_err(|e| anyhow!("{}. {}", e, e.suggestion()))
+}
+
+/// Run the `policy` subcommand, returning the policy it prints.
+pub fn run(config: &Config, args: &[String]) -> Result<String> {
+    let asked = match args {
+        [] => None,
+        [name] => Some(name.as_str()),
+        _ => return Err(anyhow!(USAGE)),
+    };
+    Ok(render(config, &subreddit(config, asked)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{gates, render, run, subreddit, Gate};
+    use crate::models::{Config, Provenance, Source};
+    use crate::subreddit::Subreddit;
+
+    /// A config with settings given globally and for some subreddits.
+    fn layered() -> Config {
+        Config {
+            subreddit: "rust+RustJerk".to_owned(),
+            max_stars: 100,
+            invited_subreddits: vec!["RustJerk".to_owned()],
+            subreddit_locales: [("de_edv".to_owned(), "de".to_owned())].into(),
+            wip_phrases: vec!["wip".to_owned(), "a | b".to_owned()],
+            provenance: Provenance::given([
+                "CFL_SUBREDDIT",
+                "CFL_MAX_STARS",
+                "CFL_INVITED_SUBREDDITS",
+                "CFL_SUBREDDIT_LOCALES",
+                "CFL_WIP_PHRASES",
+            ]),
+            ..Config::for_tests()
+        }
+    }
+
+    fn gate(gates: &[Gate], name: &str) -> (String, Source) {
+        let gate = gates.iter().find(|g| g.name == name).unwrap();
+        (gate.value.clone(), gate.source)
+    }
+
+    #[test]
+    fn tells_where_each_setting_comes_from() {
+        let config = layered();
+        let in_sub = |name: &str| gates(&config, &Subreddit::new(name).unwrap());
+        let rust = in_sub("rust");
+        assert_eq!(
+            gate(&rust, "Most stars"),
+            ("100".to_owned(), Source::Global)
+        );
+        assert_eq!(
+            gate(&rust, "Reply cap"),
+            ("none".to_owned(), Source::Default)
+        );
+        assert_eq!(
+            gate(&rust, "Subreddit rules"),
+            ("checked for a ban on bots".to_owned(), Source::Default)
+        );
+        assert_eq!(
+            gate(&rust, "Reply language"),
+            ("en".to_owned(), Source::Default)
+        );
+
+        let jerk = in_sub("rustjerk");
+        assert_eq!(
+            gate(&jerk, "Subreddit rules"),
+            (
+                "invited, so not checked for a ban on bots".to_owned(),
+                Source::Subreddit
+            )
+        );
+        assert_eq!(
+            gate(&jerk, "Most stars"),
+            ("100".to_owned(), Source::Global)
+        );
+
+        let german = in_sub("/r/De_EDV");
+        assert_eq!(
+            gate(&german, "Reply language"),
+            ("de".to_owned(), Source::Subreddit)
+        );
+        assert_eq!(gate(&german, "Subreddit rules").1, Source::Default);
+    }
+
+    #[test]
+    fn renders_the_policy_as_markdown() {
+        let config = layered();
+        let policy = render(&config, &Subreddit::new("rust").unwrap());
+        assert!(policy.starts_with("# Policy in /r/rust\n"), "{}", policy);
+        let rows: Vec<&str> = policy.lines().filter(|l| l.starts_with("| ")).collect();
+        // the header, then a row per gate
+        assert_eq!(
+            rows.len(),
+            gates(&config, &Subreddit::new("rust").unwrap()).len() + 1
+        );
+        for row in [
+            "| Most stars | 100 | `CFL_MAX_STARS` | global config |",
+            "| Reply cap | none | `CFL_MAX_REPLIES_PER_HOUR` | default |",
+            "| Reply language | en | `CFL_SUBREDDIT_LOCALES` | default |",
+            // a phrase can't break the table
+            "| Work in progress phrases | wip, a \\| b | `CFL_WIP_PHRASES` | global config |",
+        ] {
+            assert!(rows.contains(&row), "{} not in {}", row, policy);
+        }
+    }
+
+    #[test]
+    fn runs_for_the_first_watched_subreddit_unless_given_one() {
+        let config = layered();
+        assert!(run(&config, &[])
+            .unwrap()
+            .starts_with("# Policy in /r/rust\n"));
+        assert!(run(&config, &["r/RustJerk".to_owned()])
+            .unwrap()
+            .starts_with("# Policy in /r/RustJerk\n"));
+        assert!(run(&config, &["a".to_owned(), "b".to_owned()]).is_err());
+        let error = subreddit(&config, Some("https://reddit.com/r/rust"))
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("`rust`"), "{}", error);
+    }
+}
@@ -0,0 +1,69 @@
+use std::time::{Duration, Instant};
+
+/// How long a note of praise counts against the daily cap.
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Whether a repository's top-level files include a README and contributing
+/// guidelines, which along with a license make it exemplary.
+pub fn has_community_files<'a>(names: impl IntoIterator<Item = &'a str>) -> bool {
+    let (mut readme, mut contributing) = (false, false);
+    for name in names {
+        let name = name.to_lowercase();
+        let stem = name.split('.').next().unwrap_or_default();
+        readme |= stem == "readme";
+        contributing |= stem == "contributing";
+    }
+    readme && contributing
+}
+
+/// Whether there's room for another note of praise under a cap of
+/// `per_day`, given when the earlier ones were left. Those from more than a
+/// day before `now` are forgotten.
+pub fn within_cap(given: &mut Vec<Instant>, now: Instant, per_day: u32) -> bool {
+    given.retain(|&at| now.saturating_duration_since(at) < DAY);
+    given.len() < per_day as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{has_community_files, within_cap, DAY};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn needs_a_readme_and_contributing_guidelines() {
+        assert!(has_community_files(vec![
+            "LICENSE",
+            "README.md",
+            "CONTRIBUTING.md",
+            "src"
+        ]));
+        assert!(has_community_files(vec!["readme", "Contributing.rst"]));
+        assert!(!has_community_files(vec!["README.md", "LICENSE"]));
+        assert!(!has_community_files(vec!["CONTRIBUTING.md"]));
+        assert!(!has_community_files(vec!["READMEs", "CONTRIBUTORS.md"]));
+        assert!(!has_community_files(vec![]));
+    }
+
+    #[test]
+    fn caps_praise_per_day() {
+        let start = Instant::now();
+        let mut given = vec![];
+        assert!(within_cap(&mut given, start, 2));
+        given.push(start);
+        assert!(within_cap(&mut given, start, 2));
+        given.push(start + Duration::from_secs(60));
+        assert!(!within_cap(&mut given, start + Duration::from_secs(120), 2));
+
+        // the first drops out a day after it was given
+        assert!(!within_cap(
+            &mut given,
+            start + DAY - Duration::from_secs(1),
+            2
+        ));
+        assert!(within_cap(&mut given, start + DAY, 2));
+        assert_eq!(given.len(), 1);
+
+        // and a cap of 0 allows none
+        assert!(!within_cap(&mut vec![], start, 0));
+    }
+}
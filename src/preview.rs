@@ -0,0 +1,162 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::reply::escape_markdown;
+use crate::util::GhRepo;
+
+/// What GitHub says about a repository besides its license, shown above a
+/// reply about it so readers don't have to click through.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct RepoSummary {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    /// When the repository was created, as GitHub gives it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_branch: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stars: Option<u64>,
+}
+
+impl RepoSummary {
+    /// The summary of a repository from GitHub's API response for it.
+    pub fn from_repo(data: &Value) -> Self {
+        let text = |key: &str| {
+            data[key]
+                .as_str()
+                .filter(|s| !s.is_empty())
+                .map(str::to_owned)
+        };
+        Self {
+            language: text("language"),
+            created_at: text("created_at"),
+            default_branch: text("default_branch"),
+            stars: data["stargazers_count"].as_u64(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// The date part of a timestamp such as `2024-01-02T03:04:05Z`.
+fn date(timestamp: &str) -> Option<String> {
+    let date = timestamp.get(..10)?;
+    let well_formed = date.chars().enumerate().all(|(i, c)| {
+        if i == 4 || i == 7 {
+            c == '-'
+        } else {
+            c.is_ascii_digit()
+        }
+    });
+    if well_formed {
+        Some(date.to_owned())
+    } else {
+        None
+    }
+}
+
+/// A count with commas between each three digits, like `12,345`.
+fn thousands(n: u64) -> String {
+    let digits = n.to_string();
+    let mut out = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            out.push(',');
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// Fill in the `{repo}`, `{language}`, `{created_at}`, `{default_branch}`
+/// and `{stars}` variables of a summary line.
+///
+/// Returns `None`, so the line is left out, if the line is empty or uses a
+/// variable GitHub gave no value for.
+pub fn line(template: &str, repo: &GhRepo, summary: &RepoSummary) -> Option<String> {
+    if template.trim().is_empty() {
+        return None;
+    }
+    let vars = [
+        ("repo", Some(escape_markdown(&repo.to_string()))),
+        ("language", summary.language.as_deref().map(escape_markdown)),
+        ("created_at", summary.created_at.as_deref().and_then(date)),
+        (
+            "default_branch",
+            summary.default_branch.as_deref().map(escape_markdown),
+        ),
+        ("stars", summary.stars.map(thousands)),
+    ];
+    let mut line = template.to_owned();
+    for (name, value) in &vars {
+        let var = format!("{{{}}}", name);
+        if line.contains(&var) {
+            line = line.replace(&var, value.as_deref()?);
+        }
+    }
+    Some(line)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{date, line, thousands, RepoSummary};
+    use crate::util::GhRepo;
+    use serde_json::json;
+
+    const TEMPLATE: &str =
+        "Repository: {repo} — {language}, created {created_at}, default branch {default_branch}, {stars} stars";
+
+    #[test]
+    fn formats_dates_and_counts() {
+        assert_eq!(date("2024-01-02T03:04:05Z").as_deref(), Some("2024-01-02"));
+        assert_eq!(date("2024-01-02").as_deref(), Some("2024-01-02"));
+        assert_eq!(date("yesterday"), None);
+        assert_eq!(date("2024/01/02 03:04"), None);
+
+        assert_eq!(thousands(0), "0");
+        assert_eq!(thousands(14), "14");
+        assert_eq!(thousands(999), "999");
+        assert_eq!(thousands(1000), "1,000");
+        assert_eq!(thousands(12_345), "12,345");
+        assert_eq!(thousands(1_234_567), "1,234,567");
+    }
+
+    #[test]
+    fn summarizes_repositories() {
+        let summary = RepoSummary::from_repo(&json!({
+            "language": "Rust",
+            "created_at": "2024-01-02T03:04:05Z",
+            "default_branch": "main",
+            "stargazers_count": 12345,
+        }));
+        assert_eq!(
+            line(TEMPLATE, &GhRepo::new("org", "some_repo"), &summary).as_deref(),
+            Some(
+                "Repository: org/some\\_repo — Rust, created 2024-01-02, default branch main, \
+                 12,345 stars"
+            )
+        );
+    }
+
+    #[test]
+    fn leaves_out_lines_missing_values() {
+        let repo = GhRepo::new("org", "repo");
+        let summary = RepoSummary::from_repo(&json!({
+            "language": null,
+            "created_at": "2024-01-02T03:04:05Z",
+            "stargazers_count": 0,
+        }));
+        assert_eq!(summary.language, None);
+        assert_eq!(line(TEMPLATE, &repo, &summary), None);
+        assert_eq!(
+            line("{repo}: {stars} stars since {created_at}", &repo, &summary).as_deref(),
+            Some("org/repo: 0 stars since 2024-01-02")
+        );
+        assert_eq!(line("", &repo, &summary), None);
+        assert_eq!(line(TEMPLATE, &repo, &RepoSummary::default()), None);
+        assert!(RepoSummary::default().is_empty());
+    }
+}
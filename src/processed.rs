@@ -0,0 +1,321 @@
+use serde::{Deserialize, Serialize};
+use std::{collections::HashSet, iter::FromIterator};
+
+use crate::compaction::{vec_size, worth_shrinking};
+use crate::skip::SkipReason;
+
+/// A post that's been looked at, and when it was first seen.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Seen {
+    pub fullname: String,
+    /// Unix timestamp of when the post was first seen, or 0 if it was saved
+    /// before that was recorded.
+    pub seen_at: u64,
+    /// Why the post was skipped without any of its repositories being
+    /// checked, if it was.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub skipped: Option<SkipReason>,
+}
+
+/// An entry as saved by any version of the state file.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Entry {
+    Seen(Seen),
+    /// Version 1 and earlier kept only the fullname.
+    Fullname(String),
+}
+
+/// The posts already looked at in a listing, in the order they were added.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(from = "Vec<Entry>")]
+pub struct Processed(Vec<Seen>);
+
+impl From<Vec<Entry>> for Processed {
+    fn from(entries: Vec<Entry>) -> Self {
+        Processed(
+            entries
+                .into_iter()
+                .map(|entry| match entry {
+                    Entry::Seen(seen) => seen,
+                    Entry::Fullname(fullname) => Seen {
+                        fullname,
+                        seen_at: 0,
+                        skipped: None,
+                    },
+                })
+                .collect(),
+        )
+    }
+}
+
+impl<'a> FromIterator<&'a str> for Processed {
+    /// Posts saved without when they were seen.
+    fn from_iter<I: IntoIterator<Item = &'a str>>(fullnames: I) -> Self {
+        Processed(
+            fullnames
+                .into_iter()
+                .map(|fullname| Seen {
+                    fullname: fullname.to_owned(),
+                    seen_at: 0,
+                    skipped: None,
+                })
+                .collect(),
+        )
+    }
+}
+
+impl FromIterator<Seen> for Processed {
+    /// Posts in the order given, which mustn't repeat any.
+    fn from_iter<I: IntoIterator<Item = Seen>>(entries: I) -> Self {
+        Processed(entries.into_iter().collect())
+    }
+}
+
+impl Processed {
+    pub fn contains(&self, fullname: &str) -> bool {
+        self.0.iter().any(|s| s.fullname == fullname)
+    }
+
+    /// Mark a post as seen at `at`, returning whether it's new.
+    pub fn insert(&mut self, fullname: &str, at: u64) -> bool {
+        if self.contains(fullname) {
+            return false;
+        }
+        self.0.push(Seen {
+            fullname: fullname.to_owned(),
+            seen_at: at,
+            skipped: None,
+        });
+        true
+    }
+
+    /// Forget a post was seen, so that it's looked at again.
+    pub fn remove(&mut self, fullname: &str) {
+        self.0.retain(|s| s.fullname != fullname);
+    }
+
+    /// When a post was first seen, if it has been.
+    pub fn seen_at(&self, fullname: &str) -> Option<u64> {
+        self.0
+            .iter()
+            .find(|s| s.fullname == fullname)
+            .map(|s| s.seen_at)
+    }
+
+    /// Record why a post was skipped without being checked.
+    pub fn skip(&mut self, fullname: &str, reason: SkipReason) {
+        for seen in self.0.iter_mut().filter(|s| s.fullname == fullname) {
+            seen.skipped = Some(reason);
+        }
+    }
+
+    /// Why a post was skipped without being checked, if it was.
+    pub fn skipped(&self, fullname: &str) -> Option<SkipReason> {
+        self.0
+            .iter()
+            .find(|s| s.fullname == fullname)
+            .and_then(|s| s.skipped)
+    }
+
+    /// Add the posts from another list that aren't in this one.
+    pub fn merge(&mut self, other: &Processed) {
+        let known: HashSet<String> = self.0.iter().map(|s| s.fullname.clone()).collect();
+        self.0.extend(
+            other
+                .0
+                .iter()
+                .filter(|s| !known.contains(&s.fullname))
+                .cloned(),
+        );
+    }
+
+    /// Give entries saved without a time the time `now`, so that they're
+    /// kept for the full retention from when they were migrated.
+    pub fn date_undated(&mut self, now: u64) {
+        for seen in self.0.iter_mut().filter(|s| s.seen_at == 0) {
+            seen.seen_at = now;
+        }
+    }
+
+    /// Drop entries seen more than `retention` seconds before `now`, unless
+    /// that's 0, and then the oldest entries beyond `max`, returning how many
+    /// were dropped.
+    pub fn prune(&mut self, now: u64, retention: u64, max: Option<usize>) -> usize {
+        let before = self.0.len();
+        if retention > 0 {
+            self.0
+                .retain(|s| s.seen_at == 0 || now.saturating_sub(s.seen_at) <= retention);
+        }
+        if let Some(max) = max {
+            if self.0.len() > max {
+                let mut times: Vec<u64> = self.0.iter().map(|s| s.seen_at).collect();
+                times.sort_unstable();
+                // keep entries newer than the cutoff, then as many seen at
+                // the cutoff as fit, in their original order
+                let cutoff = times[times.len() - max];
+                let mut at_cutoff = max - times.iter().filter(|&&t| t > cutoff).count();
+                self.0.retain(|s| {
+                    if s.seen_at > cutoff {
+                        return true;
+                    }
+                    if s.seen_at == cutoff && at_cutoff > 0 {
+                        at_cutoff -= 1;
+                        return true;
+                    }
+                    false
+                });
+            }
+        }
+        before - self.0.len()
+    }
+
+    /// Estimated bytes the entries take up on the heap.
+    pub fn heap_size(&self) -> usize {
+        vec_size(&self.0, self.0.capacity(), |s| s.fullname.capacity())
+    }
+
+    /// Give back the room left by dropped entries, if there's a lot of it.
+    pub fn shrink(&mut self) {
+        if worth_shrinking(self.0.len(), self.0.capacity()) {
+            self.0.shrink_to_fit();
+        }
+    }
+
+    /// Fullnames of the posts, in the order they were added.
+    pub fn fullnames(&self) -> Vec<&str> {
+        self.0.iter().map(|s| s.fullname.as_str()).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Processed, Seen};
+    use crate::skip::SkipReason;
+
+    const DAY: u64 = 24 * 60 * 60;
+
+    fn processed(entries: &[(&str, u64)]) -> Processed {
+        let mut processed = Processed::default();
+        for (fullname, at) in entries {
+            processed.insert(fullname, *at);
+        }
+        processed
+    }
+
+    #[test]
+    fn reads_bare_fullnames_and_timestamped_entries() {
+        let old: Processed = serde_json::from_str(r#"["t3_a", "t3_b"]"#).unwrap();
+        assert_eq!(old.fullnames(), vec!["t3_a", "t3_b"]);
+        assert_eq!(old.seen_at("t3_a"), Some(0));
+
+        let mixed: Processed =
+            serde_json::from_str(r#"[{"fullname": "t3_a", "seen_at": 5}, "t3_b"]"#).unwrap();
+        assert_eq!(mixed.seen_at("t3_a"), Some(5));
+        assert_eq!(mixed.seen_at("t3_b"), Some(0));
+
+        let saved = serde_json::to_string(&processed(&[("t3_c", 7)])).unwrap();
+        assert_eq!(saved, r#"[{"fullname":"t3_c","seen_at":7}]"#);
+
+        assert_eq!(
+            serde_json::from_str::<Processed>(&saved).unwrap(),
+            Processed(vec![Seen {
+                fullname: "t3_c".to_owned(),
+                seen_at: 7,
+                skipped: None
+            }])
+        );
+
+        let mut skipped = processed(&[("t3_d", 8)]);
+        skipped.skip("t3_d", SkipReason::NoLinks);
+        let saved = serde_json::to_string(&skipped).unwrap();
+        assert_eq!(
+            saved,
+            r#"[{"fullname":"t3_d","seen_at":8,"skipped":"no_links"}]"#
+        );
+        let read: Processed = serde_json::from_str(&saved).unwrap();
+        assert_eq!(read.skipped("t3_d"), Some(SkipReason::NoLinks));
+        assert_eq!(mixed.skipped("t3_a"), None);
+    }
+
+    #[test]
+    fn migrated_entries_are_kept_for_the_full_retention() {
+        let mut list: Processed = serde_json::from_str(r#"["t3_a"]"#).unwrap();
+        let now = 1000 * DAY;
+        list.date_undated(now);
+        assert_eq!(list.seen_at("t3_a"), Some(now));
+        assert_eq!(list.prune(now + 89 * DAY, 90 * DAY, None), 0);
+        assert_eq!(list.prune(now + 91 * DAY, 90 * DAY, None), 1);
+        assert!(list.is_empty());
+    }
+
+    #[test]
+    fn prunes_entries_older_than_the_retention() {
+        let now = 1000 * DAY;
+        let mut list = processed(&[
+            ("t3_old", now - 100 * DAY),
+            ("t3_new", now - DAY),
+            ("t3_edge", now - 90 * DAY),
+        ]);
+        assert_eq!(list.prune(now, 90 * DAY, None), 1);
+        assert_eq!(list.fullnames(), vec!["t3_new", "t3_edge"]);
+        assert!(list.contains("t3_new") && !list.contains("t3_old"));
+
+        // a retention of 0 keeps everything
+        let mut list = processed(&[("t3_old", 1)]);
+        assert_eq!(list.prune(now, 0, None), 0);
+    }
+
+    #[test]
+    fn whichever_limit_is_reached_first_wins() {
+        let now = 1000 * DAY;
+        let entries = [
+            ("t3_a", now - 100 * DAY),
+            ("t3_b", now - 3 * DAY),
+            ("t3_c", now - 2 * DAY),
+            ("t3_d", now - DAY),
+            ("t3_e", now - DAY),
+        ];
+
+        // the cap is hit first: the oldest go, though they're within the retention
+        let mut list = processed(&entries);
+        assert_eq!(list.prune(now, 200 * DAY, Some(2)), 3);
+        assert_eq!(list.fullnames(), vec!["t3_d", "t3_e"]);
+
+        // the retention is hit first: the cap isn't reached once it's applied
+        let mut list = processed(&entries);
+        assert_eq!(list.prune(now, 90 * DAY, Some(10)), 1);
+        assert_eq!(list.fullnames(), vec!["t3_b", "t3_c", "t3_d", "t3_e"]);
+
+        // both apply
+        let mut list = processed(&entries);
+        assert_eq!(list.prune(now, 90 * DAY, Some(3)), 2);
+        assert_eq!(list.fullnames(), vec!["t3_c", "t3_d", "t3_e"]);
+
+        // ties at the cutoff keep the ones added first
+        let mut list = processed(&entries);
+        assert_eq!(list.prune(now, 0, Some(1)), 4);
+        assert_eq!(list.fullnames(), vec!["t3_d"]);
+    }
+
+    #[test]
+    fn merges_without_duplicates() {
+        let mut list = processed(&[("t3_a", 1), ("t3_b", 2)]);
+        list.merge(&processed(&[("t3_b", 5), ("t3_c", 3)]));
+        assert_eq!(list.fullnames(), vec!["t3_a", "t3_b", "t3_c"]);
+        assert_eq!(list.seen_at("t3_b"), Some(2));
+        assert!(!list.insert("t3_a", 9));
+        assert!(list.insert("t3_d", 9));
+        assert_eq!(list.len(), 4);
+        list.remove("t3_b");
+        assert_eq!(list.fullnames(), vec!["t3_a", "t3_c", "t3_d"]);
+    }
+}
@@ -0,0 +1,422 @@
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::Write,
+    fs::{self, OpenOptions},
+    path::Path,
+    str::FromStr,
+    sync::Mutex,
+};
+use thiserror::Error;
+
+use crate::paths::{approval_file, approval_lock_file};
+use crate::pending::PendingReply;
+use crate::suppress::{self, Suppression};
+
+/// Whether replies are made straight away or wait for a human to approve them.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ApprovalMode {
+    Auto,
+    Manual,
+}
+
+impl FromStr for ApprovalMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "auto" => Ok(ApprovalMode::Auto),
+            "manual" => Ok(ApprovalMode::Manual),
+            _ => Err(anyhow!("Approval mode must be 'auto' or 'manual'")),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Pending,
+    Approved,
+}
+
+/// A proposed reply waiting in the approval queue.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct QueuedReply {
+    pub id: u64,
+    pub status: Status,
+    #[serde(flatten)]
+    pub reply: PendingReply,
+}
+
+/// Ways that acting on the approval queue can fail.
+#[derive(Debug, Error, PartialEq)]
+pub enum QueueError {
+    #[error("No reply with id {0} in the approval queue")]
+    UnknownId(u64),
+    #[error("Reply {0} has already been approved")]
+    AlreadyApproved(u64),
+}
+
+static UPDATE_LOCK: Mutex<()> = Mutex::new(());
+
+/// Replies proposed in manual approval mode, persisted in the state directory.
+///
+/// The bot adds replies and removes them once they're made; people approve
+/// or reject them in between, from the command line or the HTTP listener.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct ApprovalQueue {
+    next_id: u64,
+    items: Vec<QueuedReply>,
+}
+
+impl ApprovalQueue {
+    pub fn load(dir: &Path) -> Result<Self> {
+        let path = approval_file(dir);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let data =
+            fs::read_to_string(&path).with_context(|| format!("reading {}", path.display()))?;
+        serde_json::from_str(&data).with_context(|| format!("loading {}", path.display()))
+    }
+
+    /// Save the queue, replacing the file atomically.
+    pub fn save(&self, dir: &Path) -> Result<()> {
+        let path = approval_file(dir);
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_string_pretty(self)?)
+            .and_then(|_| fs::rename(&tmp, &path))
+            .with_context(|| format!("writing {}", path.display()))
+    }
+
+    /// Load the queue, change it and save it again.
+    ///
+    /// Changes are made one at a time so none is lost: those from within the
+    /// process, by the bot loop or the HTTP listener, and those from other
+    /// processes such as `check_for_license queue approve`, which wait on an
+    /// advisory lock of a file next to the queue.
+    pub fn update<T>(dir: &Path, change: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        let _guard = UPDATE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let lock_path = approval_lock_file(dir);
+        let lock = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&lock_path)
+            .and_then(|file| file.lock().map(|_| file))
+            .with_context(|| format!("locking {}", lock_path.display()))?;
+        let mut queue = Self::load(dir)?;
+        let result = change(&mut queue)?;
+        queue.save(dir)?;
+        drop(lock);
+        Ok(result)
+    }
+
+    /// Add a proposed reply, returning its id.
+    pub fn push(&mut self, reply: PendingReply) -> u64 {
+        self.next_id += 1;
+        self.items.push(QueuedReply {
+            id: self.next_id,
+            status: Status::Pending,
+            reply,
+        });
+        self.next_id
+    }
+
+    pub fn items(&self) -> &[QueuedReply] {
+        &self.items
+    }
+
+    /// Replies that have been approved, oldest first.
+    pub fn approved(&self) -> Vec<QueuedReply> {
+        self.items
+            .iter()
+            .filter(|i| i.status == Status::Approved)
+            .cloned()
+            .collect()
+    }
+
+    pub fn approve(&mut self, id: u64) -> Result<(), QueueError> {
+        let item = self
+            .items
+            .iter_mut()
+            .find(|i| i.id == id)
+            .ok_or(QueueError::UnknownId(id))?;
+        if item.status == Status::Approved {
+            return Err(QueueError::AlreadyApproved(id));
+        }
+        item.status = Status::Approved;
+        Ok(())
+    }
+
+    /// Take a reply out of the queue, whatever its status.
+    pub fn remove(&mut self, id: u64) -> Result<QueuedReply, QueueError> {
+        let index = self
+            .items
+            .iter()
+            .position(|i| i.id == id)
+            .ok_or(QueueError::UnknownId(id))?;
+        Ok(self.items.remove(index))
+    }
+
+    /// Discard a proposed reply, suppressing its repositories from now on.
+    pub fn reject(&mut self, dir: &Path, id: u64) -> Result<QueuedReply> {
+        let item = self.remove(id)?;
+        for link in &item.reply.repos {
+            suppress::add(
+                dir,
+                Suppression::repo(
+                    &link.repo,
+                    "approval queue",
+                    "rejected in the approval queue",
+                ),
+            )?;
+        }
+        Ok(item)
+    }
+
+    /// Add the replies from another queue, such as one from a state bundle.
+    ///
+    /// A reply to a post that's already queued replaces it only if it was
+    /// held back more recently; new ones get ids from this queue.
+    pub fn merge(&mut self, other: &ApprovalQueue) {
+        for item in &other.items {
+            match self
+                .items
+                .iter_mut()
+                .find(|i| i.reply.fullname == item.reply.fullname)
+            {
+                Some(existing) if item.reply.queued_at > existing.reply.queued_at => {
+                    existing.status = item.status;
+                    existing.reply = item.reply.clone();
+                }
+                Some(_) => {}
+                None => {
+                    let id = self.push(item.reply.clone());
+                    if let Some(added) = self.items.iter_mut().find(|i| i.id == id) {
+                        added.status = item.status;
+                    }
+                }
+            }
+        }
+    }
+}
+
+const USAGE: &str = "Usage: check_for_license queue list | approve <id> | reject <id>";
+
+/// Run a `queue` subcommand against the approval queue, returning its output.
+pub fn run(dir: &Path, args: &[String]) -> Result<String> {
+    let id = || -> Result<u64> {
+        args.get(1)
+            .and_then(|id| id.parse().ok())
+            .ok_or_else(|| anyhow!(USAGE))
+    };
+    match args.first().map(String::as_str) {
+        Some("list") => {
+            let queue = ApprovalQueue::load(dir)?;
+            if queue.items().is_empty() {
+                return Ok("The approval queue is empty\n".to_owned());
+            }
+            let mut out = String::new();
+            for item in queue.items() {
+                let repos: Vec<String> = item
+                    .reply
+                    .repos
+                    .iter()
+                    .map(|r| r.repo.to_string())
+                    .collect();
+                writeln!(
+                    out,
+                    "{:>4}  {:<8}  {}  {}  {}",
+                    item.id,
+                    format!("{:?}", item.status).to_lowercase(),
+                    item.reply.fullname,
+                    repos.join(", "),
+                    item.reply.details.permalink
+                )?;
+            }
+            Ok(out)
+        }
+        Some("approve") => {
+            let id = id()?;
+            ApprovalQueue::update(dir, |q| Ok(q.approve(id)?))?;
+            Ok(format!(
+                "Approved reply {}; it will be made on the bot's next pass\n",
+                id
+            ))
+        }
+        Some("reject") => {
+            let id = id()?;
+            let item = ApprovalQueue::update(dir, |q| q.reject(dir, id))?;
+            Ok(format!(
+                "Rejected reply {} to {}; its repositories won't be replied about again\n",
+                id, item.reply.fullname
+            ))
+        }
+        _ => Err(anyhow!(USAGE)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{run, ApprovalMode, ApprovalQueue, QueueError, Status};
+    use crate::latency::Stages;
+    use crate::paths::approval_lock_file;
+    use crate::pending::{PendingReply, PostDetails, RepoLink};
+    use crate::preview::RepoSummary;
+    use crate::suppress;
+    use crate::util::GhRepo;
+    use std::{fs, path::Path};
+
+    fn reply(fullname: &str, repo: &str) -> PendingReply {
+        PendingReply {
+            fullname: fullname.to_owned(),
+            repos: vec![RepoLink {
+                repo: GhRepo {
+                    owner: "someone".to_owned(),
+                    name: repo.to_owned(),
+                },
+                url: format!("https://github.com/someone/{}", repo),
+                removed_license: None,
+                summary: RepoSummary::default(),
+            }],
+            details: PostDetails {
+                permalink: format!("https://www.reddit.com/r/rust/comments/{}/", fullname),
+                ..PostDetails::default()
+            },
+            queued_at: 0,
+            deferred: false,
+            earlier: None,
+            cross_post: None,
+            check_after: None,
+            not_before: None,
+            stages: Stages::default(),
+        }
+    }
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn parses_modes() {
+        assert_eq!("auto".parse::<ApprovalMode>().unwrap(), ApprovalMode::Auto);
+        assert_eq!(
+            "manual".parse::<ApprovalMode>().unwrap(),
+            ApprovalMode::Manual
+        );
+        assert!("yes".parse::<ApprovalMode>().is_err());
+    }
+
+    #[test]
+    fn approving_and_removing() {
+        let mut queue = ApprovalQueue::default();
+        let a = queue.push(reply("t3_a", "one"));
+        let b = queue.push(reply("t3_b", "two"));
+        assert_eq!((a, b), (1, 2));
+        assert!(queue.approved().is_empty());
+
+        queue.approve(b).unwrap();
+        assert_eq!(queue.approve(b), Err(QueueError::AlreadyApproved(b)));
+        assert_eq!(queue.approve(9), Err(QueueError::UnknownId(9)));
+        let approved = queue.approved();
+        assert_eq!(approved.len(), 1);
+        assert_eq!(approved[0].reply.fullname, "t3_b");
+
+        assert_eq!(queue.remove(b).unwrap().status, Status::Approved);
+        assert_eq!(queue.remove(b), Err(QueueError::UnknownId(b)));
+        // ids aren't reused
+        assert_eq!(queue.push(reply("t3_c", "three")), 3);
+    }
+
+    #[test]
+    fn persists_in_state_dir() {
+        let tmp = tempfile::tempdir().unwrap();
+        assert_eq!(
+            ApprovalQueue::load(tmp.path()).unwrap(),
+            ApprovalQueue::default()
+        );
+        let id = ApprovalQueue::update(tmp.path(), |q| Ok(q.push(reply("t3_a", "one")))).unwrap();
+        let queue = ApprovalQueue::load(tmp.path()).unwrap();
+        assert_eq!(queue.items().len(), 1);
+        assert_eq!(queue.items()[0].id, id);
+        assert_eq!(queue.items()[0].reply, reply("t3_a", "one"));
+    }
+
+    #[test]
+    fn other_processes_wait_for_an_update() {
+        let tmp = tempfile::tempdir().unwrap();
+        ApprovalQueue::update(tmp.path(), |q| {
+            // as another process would, opening the lock file separately
+            let other = fs::File::open(approval_lock_file(tmp.path())).unwrap();
+            assert!(other.try_lock().is_err());
+            Ok(q.push(reply("t3_a", "one")))
+        })
+        .unwrap();
+        let other = fs::File::open(approval_lock_file(tmp.path())).unwrap();
+        other.try_lock().unwrap();
+    }
+
+    fn queue_of_two(dir: &Path) {
+        ApprovalQueue::update(dir, |q| {
+            q.push(reply("t3_a", "one"));
+            q.push(reply("t3_b", "two"));
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn cli_lists_and_approves() {
+        let tmp = tempfile::tempdir().unwrap();
+        assert_eq!(
+            run(tmp.path(), &args(&["list"])).unwrap(),
+            "The approval queue is empty\n"
+        );
+        queue_of_two(tmp.path());
+
+        let out = run(tmp.path(), &args(&["approve", "2"])).unwrap();
+        assert!(out.starts_with("Approved reply 2"), "{}", out);
+        assert!(run(tmp.path(), &args(&["approve", "2"])).is_err());
+        assert!(run(tmp.path(), &args(&["approve", "7"])).is_err());
+
+        let list = run(tmp.path(), &args(&["list"])).unwrap();
+        let lines: Vec<&str> = list.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].contains("pending") && lines[0].contains("t3_a"));
+        assert!(lines[0].contains("someone/one"));
+        assert!(lines[1].contains("approved") && lines[1].contains("t3_b"));
+    }
+
+    #[test]
+    fn cli_rejects_and_suppresses() {
+        let tmp = tempfile::tempdir().unwrap();
+        queue_of_two(tmp.path());
+
+        let out = run(tmp.path(), &args(&["reject", "1"])).unwrap();
+        assert!(out.starts_with("Rejected reply 1 to t3_a"), "{}", out);
+        let queue = ApprovalQueue::load(tmp.path()).unwrap();
+        assert_eq!(queue.items().len(), 1);
+        assert_eq!(queue.items()[0].id, 2);
+        let suppressed = suppress::load(tmp.path()).unwrap();
+        assert!(suppress::is_suppressed(
+            &suppressed,
+            &GhRepo::new("someone", "one"),
+            0
+        ));
+        assert!(!suppress::is_suppressed(
+            &suppressed,
+            &GhRepo::new("someone", "two"),
+            0
+        ));
+    }
+
+    #[test]
+    fn cli_usage_errors() {
+        let tmp = tempfile::tempdir().unwrap();
+        for bad in &[vec![], vec!["nope"], vec!["approve"], vec!["reject", "x"]] {
+            let err = run(tmp.path(), &args(bad)).unwrap_err();
+            assert!(err.to_string().starts_with("Usage:"), "{:?}", bad);
+        }
+    }
+}
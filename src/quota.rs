@@ -0,0 +1,128 @@
+use reqwest::header::HeaderMap;
+use serde::Serialize;
+use std::{
+    fmt,
+    time::{Duration, UNIX_EPOCH},
+};
+
+/// GitHub's rate limit, as of the latest response.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct Quota {
+    /// Requests left until the limit resets.
+    pub remaining: u64,
+    pub limit: u64,
+    /// Unix timestamp of when the limit resets.
+    pub reset: u64,
+}
+
+impl Quota {
+    /// Read the rate limit headers of a GitHub response, if it has all of them.
+    pub fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        let header =
+            |name: &str| -> Option<u64> { headers.get(name)?.to_str().ok()?.trim().parse().ok() };
+        Some(Self {
+            remaining: header("x-ratelimit-remaining")?,
+            limit: header("x-ratelimit-limit")?,
+            reset: header("x-ratelimit-reset")?,
+        })
+    }
+
+    /// Whether fewer than `floor` requests are left, and the limit hasn't
+    /// reset yet at Unix time `now`.
+    pub fn is_low(&self, floor: u64, now: u64) -> bool {
+        self.remaining < floor && now < self.reset
+    }
+}
+
+impl fmt::Display for Quota {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} of {} GitHub requests left until {}",
+            self.remaining,
+            self.limit,
+            humantime::format_rfc3339_seconds(UNIX_EPOCH + Duration::from_secs(self.reset))
+        )
+    }
+}
+
+/// Keeps the latest GitHub rate limit, and whether it's been warned about.
+#[derive(Debug, Default)]
+pub struct QuotaTracker {
+    latest: Option<Quota>,
+    /// Reset time of the window that the quota was last warned about in.
+    warned: Option<u64>,
+}
+
+impl QuotaTracker {
+    /// Record the rate limit from a response.
+    ///
+    /// Returns whether to warn that it's below `floor`, which is only the case
+    /// the first time it drops below in each rate limit window.
+    pub fn update(&mut self, quota: Quota, floor: u64) -> bool {
+        self.latest = Some(quota);
+        if quota.remaining >= floor || self.warned == Some(quota.reset) {
+            return false;
+        }
+        self.warned = Some(quota.reset);
+        true
+    }
+
+    pub fn latest(&self) -> Option<Quota> {
+        self.latest
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Quota, QuotaTracker};
+    use reqwest::header::{HeaderMap, HeaderValue};
+
+    fn quota(remaining: u64, reset: u64) -> Quota {
+        Quota {
+            remaining,
+            limit: 60,
+            reset,
+        }
+    }
+
+    #[test]
+    fn reads_rate_limit_headers() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-ratelimit-remaining", HeaderValue::from_static("42"));
+        headers.insert("x-ratelimit-limit", HeaderValue::from_static("60"));
+        assert_eq!(Quota::from_headers(&headers), None);
+        headers.insert("x-ratelimit-reset", HeaderValue::from_static(" 1000 "));
+        assert_eq!(Quota::from_headers(&headers), Some(quota(42, 1000)));
+        headers.insert("x-ratelimit-limit", HeaderValue::from_static("lots"));
+        assert_eq!(Quota::from_headers(&headers), None);
+    }
+
+    #[test]
+    fn low_until_reset() {
+        assert!(quota(4, 1000).is_low(5, 999));
+        assert!(!quota(4, 1000).is_low(5, 1000));
+        assert!(!quota(5, 1000).is_low(5, 999));
+        assert!(!quota(0, 1000).is_low(0, 999));
+    }
+
+    #[test]
+    fn warns_once_per_window() {
+        let mut tracker = QuotaTracker::default();
+        assert!(!tracker.update(quota(10, 1000), 5));
+        assert!(tracker.update(quota(4, 1000), 5));
+        assert!(!tracker.update(quota(3, 1000), 5));
+        assert_eq!(tracker.latest(), Some(quota(3, 1000)));
+        // the limit reset, and ran low again
+        assert!(!tracker.update(quota(60, 4600), 5));
+        assert!(tracker.update(quota(2, 4600), 5));
+    }
+
+    #[test]
+    fn displays_headroom() {
+        assert_eq!(
+            quota(42, 1_600_000_000).to_string(),
+            "42 of 60 GitHub requests left until 2020-09-13T12:26:40Z"
+        );
+    }
+}
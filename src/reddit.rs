@@ -0,0 +1,469 @@
+use anyhow::{anyhow, Result};
+use log::debug;
+use reqwest::StatusCode;
+use reqwest::{header::CONTENT_TYPE, Client, ClientBuilder, Method, RequestBuilder, Response};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::{collections::HashMap, sync::RwLock, time::Duration};
+
+use crate::duplicates::DuplicatesResponse;
+use crate::errors::BotError;
+use crate::listing::{Listing, Page};
+use crate::models::{AccessTokenResponse, Comment, Config, ListingResponse, Post};
+use crate::pending::PostDetails;
+use crate::restricted;
+use crate::sandbox;
+use crate::transport::Transport;
+
+pub const BASE_URL: &str = "https://www.reddit.com";
+pub const OAUTH_URL: &str = "https://oauth.reddit.com";
+/// Most fullnames that `/api/info` accepts in one request.
+const INFO_MAX_IDS: usize = 100;
+/// Longest `id` parameter sent to `/api/info`, to keep URLs a sane length.
+const INFO_MAX_QUERY_LEN: usize = 2000;
+
+/// Build a `reqwest::Client` for talking to Reddit.
+pub fn build_client(config: &Config) -> Result<Client> {
+    Ok(ClientBuilder::new()
+        .user_agent(&config.user_agent)
+        .timeout(Duration::from_secs(60))
+        .build()?)
+}
+
+/// The access token from the last login, with the OAuth scopes it was granted.
+#[derive(Debug, Default)]
+pub struct Token {
+    pub access: Option<String>,
+    pub scopes: Vec<String>,
+}
+
+/// Reddit's API, as the bot's account once it's logged in, and as anyone
+/// not logged in.
+#[derive(Debug)]
+pub struct Reddit {
+    client: Client,
+    /// Client for requests made without the access token, to see what
+    /// anyone would.
+    anonymous: Client,
+    base_url: String,
+    oauth_url: String,
+    /// Whether there's no account to log in as, so that everything is read
+    /// from the website's JSON endpoints instead of the OAuth API.
+    public_only: bool,
+    pub transport: Transport,
+    pub token: RwLock<Token>,
+}
+
+/// Turn a response from Reddit that isn't JSON into an error, going by its
+/// content type, such as the HTML page Reddit serves while it's down.
+fn expect_json(resp: &Response, action: &str) -> Result<(), BotError> {
+    let content_type = resp
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("");
+    match BotError::not_json(resp.status(), content_type, action) {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
+
+/// Read a successful response from Reddit as JSON.
+async fn reddit_json<T: DeserializeOwned>(resp: Response, action: &str) -> Result<T, BotError> {
+    expect_json(&resp, action)?;
+    Ok(resp.json().await?)
+}
+
+/// Classify Reddit's refusal of a reply posted in a subreddit, which says
+/// in its body when only approved users may comment there.
+async fn refused_reply(resp: Response, subreddit: &str) -> BotError {
+    let status = resp.status();
+    if status != StatusCode::FORBIDDEN {
+        return BotError::from_reddit(resp, "responding to post");
+    }
+    match resp.json::<Value>().await {
+        Ok(body) if restricted::is_not_allowed(&body) => {
+            BotError::CommentRestricted(subreddit.to_owned())
+        }
+        _ => BotError::Permanent(format!("Got status {} from responding to post", status)),
+    }
+}
+
+/// Split fullnames into comma-joined `id` parameters for `/api/info`.
+pub fn info_chunks(fullnames: &[String]) -> Vec<String> {
+    let mut chunks = vec![];
+    let mut current: Vec<&str> = vec![];
+    let mut len = 0;
+    for fullname in fullnames {
+        let added = fullname.len() + if current.is_empty() { 0 } else { 1 };
+        if !current.is_empty()
+            && (current.len() == INFO_MAX_IDS || len + added > INFO_MAX_QUERY_LEN)
+        {
+            chunks.push(current.join(","));
+            current.clear();
+            len = 0;
+        }
+        len += fullname.len() + if current.is_empty() { 0 } else { 1 };
+        current.push(fullname);
+    }
+    if !current.is_empty() {
+        chunks.push(current.join(","));
+    }
+    chunks
+}
+
+/// The fullname of the comment or submission Reddit says a reply was made
+/// as, if it says.
+pub fn reply_name(body: &Value) -> Option<String> {
+    // a submission is named in the data itself
+    let data = &body["json"]["data"];
+    data["things"][0]["data"]["name"]
+        .as_str()
+        .or_else(|| data["name"].as_str())
+        .map(str::to_owned)
+}
+
+impl Reddit {
+    pub fn new(client: Client, base_url: String, oauth_url: String, transport: Transport) -> Self {
+        Self {
+            anonymous: client.clone(),
+            client,
+            base_url,
+            oauth_url,
+            public_only: false,
+            transport,
+            token: RwLock::default(),
+        }
+    }
+
+    /// Read Reddit only as anyone not logged in would, through the JSON
+    /// endpoints of its website.
+    pub fn public_only(self) -> Self {
+        Self {
+            public_only: true,
+            ..self
+        }
+    }
+
+    /// Whether Reddit is only read as anyone not logged in would.
+    pub fn is_public_only(&self) -> bool {
+        self.public_only
+    }
+
+    /// Log in as the configured account, keeping the access token for the
+    /// requests after.
+    pub async fn login(&self, config: &Config) -> Result<(), BotError> {
+        if self.public_only {
+            debug!("No account to log in as, reading Reddit publicly");
+            return Ok(());
+        }
+        let form = {
+            let mut form = HashMap::new();
+            form.insert("grant_type", "password");
+            form.insert("username", &config.username);
+            form.insert("password", &config.password);
+            form
+        };
+        let request = self
+            .client
+            .post(format!("{}/api/v1/access_token", self.base_url))
+            .basic_auth(&config.client_id, Some(&config.client_secret))
+            .form(&form);
+        let resp = self.transport.send(&self.client, request).await?;
+        let status = resp.status();
+        if !BotError::login_body_expected(status) {
+            return Err(BotError::from_reddit(resp, "login"));
+        }
+        expect_json(&resp, "login")?;
+        let body = resp.text().await?;
+        if let Some(e) = BotError::from_login(status, &body) {
+            return Err(e);
+        }
+        let data: AccessTokenResponse = serde_json::from_str(&body)?;
+        debug!("ATR from API: {:?}", data);
+        let scopes = data
+            .scope
+            .split([' ', ','])
+            .filter(|s| !s.is_empty())
+            .map(str::to_owned)
+            .collect();
+        *self.token.write().unwrap() = Token {
+            access: Some(data.token),
+            scopes,
+        };
+        Ok(())
+    }
+
+    /// OAuth scopes granted at the last login; `*` means all of them.
+    pub fn scopes(&self) -> Vec<String> {
+        self.token.read().unwrap().scopes.clone()
+    }
+
+    /// A request to a path of Reddit's OAuth API, carrying the access token
+    /// once logged in, or to the same path's JSON on the website when
+    /// reading publicly.
+    fn oauth(&self, method: Method, path: &str) -> RequestBuilder {
+        if self.public_only {
+            return self
+                .anonymous
+                .request(method, format!("{}{}.json", self.base_url, path));
+        }
+        let request = self
+            .client
+            .request(method, format!("{}{}", self.oauth_url, path));
+        match &self.token.read().unwrap().access {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        }
+    }
+
+    async fn send(&self, request: RequestBuilder) -> reqwest::Result<Response> {
+        self.transport.send(&self.client, request).await
+    }
+
+    /// Post a form to a path of the OAuth API, failing unless Reddit
+    /// accepts it.
+    async fn post_form(
+        &self,
+        path: &str,
+        form: &[(&str, &str)],
+        action: &str,
+    ) -> Result<(), BotError> {
+        let request = self.oauth(Method::POST, path).form(form);
+        let resp = self.send(request).await?;
+        if !resp.status().is_success() {
+            return Err(BotError::from_reddit(resp, action));
+        }
+        Ok(())
+    }
+
+    /// A reply to a post, or with a sandbox subreddit, a submission there
+    /// standing in for it, to be sent with `send_reply`.
+    pub fn reply_request(
+        &self,
+        sandbox: Option<&str>,
+        fullname: &str,
+        post: &PostDetails,
+        text: &str,
+    ) -> RequestBuilder {
+        match sandbox {
+            Some(sandbox) => self
+                .oauth(Method::POST, "/api/submit")
+                .form(&sandbox::submission(sandbox, fullname, post, text)),
+            None => self.oauth(Method::POST, "/api/comment").form(&[
+                ("api_type", "json"),
+                ("thing_id", fullname),
+                ("text", text),
+            ]),
+        }
+    }
+
+    /// Send a reply made with `reply_request` in a subreddit, returning
+    /// Reddit's response if it accepted it.
+    pub async fn send_reply(
+        &self,
+        request: RequestBuilder,
+        subreddit: &str,
+    ) -> Result<Response, BotError> {
+        match self.send(request).await {
+            Ok(resp) if resp.status().is_success() => Ok(resp),
+            Ok(resp) => Err(refused_reply(resp, subreddit).await),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Comment on a post or comment.
+    pub async fn comment(&self, thing_id: &str, text: &str, action: &str) -> Result<(), BotError> {
+        let form = [("api_type", "json"), ("thing_id", thing_id), ("text", text)];
+        self.post_form("/api/comment", &form, action).await
+    }
+
+    /// Replace the text of one of the bot's comments.
+    pub async fn edit(&self, thing_id: &str, text: &str, action: &str) -> Result<(), BotError> {
+        let form = [("api_type", "json"), ("thing_id", thing_id), ("text", text)];
+        self.post_form("/api/editusertext", &form, action).await
+    }
+
+    /// Send a private message, or modmail to `/r/<subreddit>`.
+    pub async fn compose(
+        &self,
+        to: &str,
+        subject: &str,
+        text: &str,
+        action: &str,
+    ) -> Result<(), BotError> {
+        let form = [
+            ("api_type", "json"),
+            ("to", to),
+            ("subject", subject),
+            ("text", text),
+        ];
+        self.post_form("/api/compose", &form, action).await
+    }
+
+    /// Opt the account in to viewing a quarantined subreddit.
+    pub async fn opt_in_to_quarantine(&self, subreddit: &str) -> Result<(), BotError> {
+        let form = [("sr_name", subreddit)];
+        self.post_form("/api/quarantine_optin", &form, "opting in to quarantine")
+            .await
+    }
+
+    /// A post and its top-level comments, as two listings.
+    pub async fn post_comments(&self, fullname: &str) -> Result<Vec<ListingResponse<Value>>> {
+        let path = format!("/comments/{}", fullname.trim_start_matches("t3_"));
+        let request = self.oauth(Method::GET, &path).query(&[
+            ("raw_json", "1"),
+            ("depth", "1"),
+            ("limit", "100"),
+        ]);
+        let resp = self.send(request).await?;
+        if !resp.status().is_success() {
+            return Err(BotError::from_reddit(resp, "post comments").into());
+        }
+        Ok(reddit_json(resp, "post comments").await?)
+    }
+
+    /// The current state of things of any kind by their fullnames, leaving
+    /// out those Reddit doesn't return.
+    pub async fn info<T: DeserializeOwned>(&self, fullnames: &[String]) -> Result<Vec<T>> {
+        let mut things = vec![];
+        for ids in info_chunks(fullnames) {
+            debug!("Fetching info for {}", ids);
+            let request = self
+                .oauth(Method::GET, "/api/info")
+                .query(&[("raw_json", "1"), ("id", &ids)]);
+            let resp = self.send(request).await?;
+            if !resp.status().is_success() {
+                return Err(BotError::from_reddit(resp, "info endpoint").into());
+            }
+            let data: ListingResponse<T> = reddit_json(resp, "info endpoint").await?;
+            things.extend(data.data.children.into_iter().map(|t| t.data));
+        }
+        Ok(things)
+    }
+
+    /// The other submissions of a post's link.
+    pub async fn duplicates(&self, fullname: &str) -> Result<Vec<Post>> {
+        let path = format!("/duplicates/{}", fullname.trim_start_matches("t3_"));
+        let request = self.oauth(Method::GET, &path).query(&[("raw_json", "1")]);
+        let resp = self.send(request).await?;
+        if !resp.status().is_success() {
+            return Err(BotError::from_reddit(resp, "duplicates endpoint").into());
+        }
+        let data: DuplicatesResponse = reddit_json(resp, "duplicates endpoint").await?;
+        Ok(data.others())
+    }
+
+    /// A user's most recent comments, newest first.
+    pub async fn user_comments(&self, username: &str, limit: usize) -> Result<Vec<Comment>> {
+        let request = self
+            .oauth(Method::GET, &format!("/user/{}/comments", username))
+            .query(&[("raw_json", "1"), ("limit", &limit.to_string())]);
+        let resp = self.send(request).await?;
+        if !resp.status().is_success() {
+            return Err(anyhow!("Got status {} from comment history", resp.status()));
+        }
+        let data: ListingResponse<Comment> = reddit_json(resp, "comment history").await?;
+        Ok(data.data.children.into_iter().map(|t| t.data).collect())
+    }
+
+    /// A subreddit's `about` or `about/rules`.
+    pub async fn subreddit(&self, subreddit: &str, path: &str) -> Result<Value> {
+        let request = self
+            .oauth(Method::GET, &format!("/r/{}/{}", subreddit, path))
+            .query(&[("raw_json", "1")]);
+        let resp = self.send(request).await?;
+        if !resp.status().is_success() {
+            return Err(anyhow!(
+                "Got status {} from subreddit {}",
+                resp.status(),
+                path
+            ));
+        }
+        Ok(resp.json::<Value>().await?)
+    }
+
+    /// A page of a listing, with the given query.
+    ///
+    /// A banned, private or quarantined listing is a
+    /// [`BotError::ListingUnavailable`] rather than a page.
+    pub async fn page(&self, listing: &Listing, query: &[(&'static str, String)]) -> Result<Page> {
+        let request = self.oauth(Method::GET, &listing.path()).query(query);
+        let resp = self.send(request).await?;
+        let status = resp.status();
+        if !status.is_success()
+            && status != StatusCode::FORBIDDEN
+            && status != StatusCode::NOT_FOUND
+        {
+            return Err(BotError::from_reddit(resp, "listing endpoint").into());
+        }
+        expect_json(&resp, "listing endpoint")?;
+        let body = resp.text().await.map_err(BotError::from)?;
+        Ok(Page::parse(listing, status.as_u16(), &body)?)
+    }
+
+    /// A page of the website as anyone not logged in would see it, or
+    /// `None` if it's not found.
+    pub async fn public(
+        &self,
+        path: &str,
+        query: &[(&str, &str)],
+        what: &str,
+    ) -> Result<Option<Response>> {
+        let request = self
+            .anonymous
+            .get(format!("{}{}", self.base_url, path))
+            .query(query);
+        let resp = self.transport.send(&self.anonymous, request).await?;
+        if resp.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !resp.status().is_success() {
+            return Err(anyhow!("Got status {} from {}", resp.status(), what));
+        }
+        Ok(Some(resp))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{info_chunks, reply_name, INFO_MAX_QUERY_LEN};
+    use serde_json::json;
+
+    #[test]
+    fn info_chunks_by_count() {
+        let fullnames: Vec<String> = (0..250).map(|i| format!("t3_{}", i)).collect();
+        let chunks = info_chunks(&fullnames);
+
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(chunks[0].split(',').count(), 100);
+        assert_eq!(chunks[1].split(',').count(), 100);
+        assert_eq!(chunks[2].split(',').count(), 50);
+        assert!(chunks[0].starts_with("t3_0,t3_1,"));
+        assert!(chunks[2].ends_with(",t3_249"));
+        assert!(info_chunks(&[]).is_empty());
+    }
+
+    #[test]
+    fn info_chunks_by_length() {
+        let fullnames: Vec<String> = (0..60).map(|i| format!("t3_{:0>40}", i)).collect();
+        let chunks = info_chunks(&fullnames);
+
+        assert_eq!(chunks.len(), 2);
+        for chunk in &chunks {
+            assert!(chunk.len() <= INFO_MAX_QUERY_LEN);
+        }
+        assert_eq!(chunks.join(",").split(',').count(), 60);
+    }
+
+    #[test]
+    fn names_replies() {
+        let comment = json!({"json": {"errors": [], "data": {"things": [
+            {"kind": "t1", "data": {"name": "t1_reply"}}
+        ]}}});
+        assert_eq!(reply_name(&comment).as_deref(), Some("t1_reply"));
+        let submission = json!({"json": {"errors": [], "data": {"name": "t3_sandbox"}}});
+        assert_eq!(reply_name(&submission).as_deref(), Some("t3_sandbox"));
+        assert_eq!(reply_name(&json!({"json": {"errors": []}})), None);
+    }
+}
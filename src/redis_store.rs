@@ -0,0 +1,952 @@
+use anyhow::{anyhow, Context, Result};
+use log::{info, warn};
+use redis::{Client, Cmd, Connection, Pipeline, RedisError, RedisResult};
+use serde_json::{Map, Value};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt,
+    sync::Mutex,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use crate::claims::{ReplyClaim, RepoClaim, ARCHIVED_AFTER};
+use crate::history::{self, ReplyRecord};
+use crate::issues::IssueRecord;
+use crate::pending::PendingReply;
+use crate::processed::{Processed, Seen};
+use crate::restricted::Restriction;
+use crate::skip::SkipReason;
+use crate::state::{StateFile, Store};
+use crate::stats::Stats;
+
+/// How long connecting, and each command, may take before Redis is taken
+/// to be unreachable.
+const TIMEOUT: Duration = Duration::from_secs(5);
+/// Seconds to wait before the first attempt to reconnect, doubled after
+/// each attempt that fails.
+const FIRST_BACKOFF: u64 = 1;
+const MAX_BACKOFF: u64 = 5 * 60;
+
+/// The connection to Redis, if there is one, and when to next try to
+/// make one if there isn't.
+struct Link {
+    conn: Option<Connection>,
+    failures: u32,
+    retry_at: Option<Instant>,
+}
+
+/// What's known of each listing's state, to keep scanning from while Redis
+/// is unreachable and to tell what a save changes.
+#[derive(Default)]
+struct Local {
+    /// The state as this bot last loaded or saved it.
+    states: HashMap<String, StateFile>,
+    /// The state as Redis last had it, as far as this bot knows.
+    synced: HashMap<String, StateFile>,
+    /// Listings saved while Redis was unreachable.
+    unsaved: HashSet<String>,
+}
+
+/// Keeps state in Redis, so that bots on several machines can share it.
+///
+/// Each listing's processed posts are a sorted set by when they were seen,
+/// its held back replies a list and its stats a hash of counters. A save
+/// only sends what changed since the last load, so two bots saving the
+/// same listing add to each other's posts and counts rather than
+/// overwriting them. A post is claimed by setting a key only if it isn't
+/// set, which Redis does as one step for every bot.
+///
+/// If Redis can't be reached, listings are loaded from and saved to memory,
+/// starting empty if they weren't loaded before, and written back once it
+/// can be, while claims fail, so replies wait.
+pub struct RedisStore {
+    client: Client,
+    prefix: String,
+    /// Seconds processed posts are kept for, or 0 to keep them until pruned.
+    retention: u64,
+    link: Mutex<Link>,
+    local: Mutex<Local>,
+}
+
+impl fmt::Debug for RedisStore {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RedisStore")
+            .field("prefix", &self.prefix)
+            .field("retention", &self.retention)
+            .finish()
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// Whether an error means the connection can't be used any more.
+fn is_disconnect(e: &RedisError) -> bool {
+    e.is_io_error() || e.is_connection_dropped() || e.is_connection_refusal() || e.is_timeout()
+}
+
+/// Stats as counters, by subreddit and count joined by `/`.
+fn counters(stats: &Stats) -> BTreeMap<String, i64> {
+    fn walk(prefix: &str, value: &Value, out: &mut BTreeMap<String, i64>) {
+        match value {
+            Value::Object(map) => {
+                for (key, value) in map {
+                    let path = if prefix.is_empty() {
+                        key.clone()
+                    } else {
+                        format!("{}/{}", prefix, key)
+                    };
+                    walk(&path, value, out);
+                }
+            }
+            Value::Number(n) => {
+                out.insert(prefix.to_owned(), n.as_i64().unwrap_or_default());
+            }
+            _ => {}
+        }
+    }
+    let mut out = BTreeMap::new();
+    walk(
+        "",
+        &serde_json::to_value(stats).unwrap_or_default(),
+        &mut out,
+    );
+    out
+}
+
+/// Stats from counters, as made by `counters`.
+fn from_counters(counters: HashMap<String, i64>) -> Result<Stats> {
+    let mut root = Map::new();
+    for (path, count) in counters {
+        let mut parts: Vec<&str> = path.split('/').collect();
+        let last = parts.pop().unwrap_or_default();
+        let mut map = &mut root;
+        for part in parts {
+            map = map
+                .entry(part)
+                .or_insert_with(|| Value::Object(Map::new()))
+                .as_object_mut()
+                .ok_or_else(|| anyhow!("Stats counter {} is nested in a count", path))?;
+        }
+        map.insert(last.to_owned(), Value::from(count.max(0)));
+    }
+    serde_json::from_value(Value::Object(root)).context("reading stats counters")
+}
+
+fn to_json<T: serde::Serialize>(value: &T) -> Result<String> {
+    serde_json::to_string(value).context("serializing for Redis")
+}
+
+impl RedisStore {
+    /// A store for the Redis at `url`, keeping processed posts for
+    /// `retention` seconds.
+    ///
+    /// Redis being unreachable isn't an error here; it's connected to once
+    /// it's needed.
+    pub fn open(url: &str, retention: u64) -> Result<Self> {
+        let client = Client::open(url).context("reading the Redis URL")?;
+        Ok(Self {
+            client,
+            prefix: "cfl".to_owned(),
+            retention,
+            link: Mutex::new(Link {
+                conn: None,
+                failures: 0,
+                retry_at: None,
+            }),
+            local: Mutex::new(Local::default()),
+        })
+    }
+
+    /// Prefix keys with `prefix` instead of `cfl`, so that bots that mustn't
+    /// share state can share a Redis.
+    pub fn prefix(mut self, prefix: &str) -> Self {
+        self.prefix = prefix.to_owned();
+        self
+    }
+
+    fn key(&self, parts: &[&str]) -> String {
+        let mut key = self.prefix.clone();
+        for part in parts {
+            key.push(':');
+            key.push_str(part);
+        }
+        key
+    }
+
+    fn claim_key(&self, fullname: &str) -> String {
+        self.key(&["claim", fullname])
+    }
+
+    /// Connect to Redis if there's no connection and it's time to try again.
+    fn connect(&self, link: &mut Link) -> Result<()> {
+        if link.conn.is_some() {
+            return Ok(());
+        }
+        if let Some(at) = link.retry_at {
+            if Instant::now() < at {
+                return Err(anyhow!("Redis is unreachable, not trying again yet"));
+            }
+        }
+        let connected = self
+            .client
+            .get_connection_with_timeout(TIMEOUT)
+            .and_then(|c| {
+                c.set_read_timeout(Some(TIMEOUT))?;
+                c.set_write_timeout(Some(TIMEOUT))?;
+                Ok(c)
+            });
+        match connected {
+            Ok(conn) => {
+                if link.failures > 0 {
+                    info!("Reconnected to Redis");
+                }
+                link.conn = Some(conn);
+                link.failures = 0;
+                link.retry_at = None;
+                Ok(())
+            }
+            Err(e) => {
+                let backoff = (FIRST_BACKOFF << link.failures.min(16)).min(MAX_BACKOFF);
+                link.failures += 1;
+                link.retry_at = Some(Instant::now() + Duration::from_secs(backoff));
+                warn!(
+                    "Could not connect to Redis, trying again in {}s: {}",
+                    backoff, e
+                );
+                Err(anyhow!("connecting to Redis: {}", e))
+            }
+        }
+    }
+
+    /// Run commands against Redis, reconnecting first if need be and
+    /// writing back listings saved while it couldn't be reached.
+    fn with_conn<T>(&self, run: impl FnOnce(&mut Connection) -> Result<T>) -> Result<T> {
+        let mut link = self.link.lock().unwrap_or_else(|e| e.into_inner());
+        let reconnected = link.conn.is_none();
+        self.connect(&mut link)?;
+        let conn = link.conn.as_mut().expect("connected above");
+        let mut result = Ok(());
+        if reconnected {
+            result = self.write_back(conn);
+        }
+        let result = result.and_then(|_| run(conn));
+        if let Err(e) = &result {
+            if e.downcast_ref::<RedisError>().is_some_and(is_disconnect) {
+                warn!("Lost the connection to Redis: {:#}", e);
+                link.conn = None;
+                link.failures = 1;
+                link.retry_at = Some(Instant::now() + Duration::from_secs(FIRST_BACKOFF));
+            }
+        }
+        result
+    }
+
+    /// Save the listings that were saved while Redis was unreachable.
+    fn write_back(&self, conn: &mut Connection) -> Result<()> {
+        let mut local = self.local.lock().unwrap_or_else(|e| e.into_inner());
+        let keys: Vec<String> = local.unsaved.iter().cloned().collect();
+        for key in keys {
+            let state = local.states[&key].clone();
+            let before = local.synced.get(&key).cloned().unwrap_or_default();
+            self.send_state(conn, &key, &before, &state)?;
+            local.synced.insert(key.clone(), state);
+            local.unsaved.remove(&key);
+            info!(
+                "Saved the state of {} kept while Redis was unreachable",
+                key
+            );
+        }
+        Ok(())
+    }
+
+    fn fetch_state(&self, conn: &mut Connection, key: &str) -> Result<StateFile> {
+        let mut pipe = redis::pipe();
+        pipe.cmd("GET")
+            .arg(self.key(&[key, "cursor"]))
+            .cmd("ZRANGE")
+            .arg(self.key(&[key, "processed"]))
+            .arg(0)
+            .arg(-1)
+            .arg("WITHSCORES")
+            .cmd("HGETALL")
+            .arg(self.key(&[key, "skipped"]))
+            .cmd("LRANGE")
+            .arg(self.key(&[key, "pending"]))
+            .arg(0)
+            .arg(-1)
+            .cmd("HGETALL")
+            .arg(self.key(&[key, "stats"]))
+            .cmd("GET")
+            .arg(self.key(&[key, "owner"]));
+        #[allow(clippy::type_complexity)]
+        let (cursor, seen, skipped, pending, stats, owner): (
+            Option<String>,
+            Vec<(String, f64)>,
+            HashMap<String, String>,
+            Vec<String>,
+            HashMap<String, i64>,
+            Option<String>,
+        ) = pipe.query(conn)?;
+        let processed: Processed = seen
+            .into_iter()
+            .map(|(fullname, at)| Seen {
+                skipped: skipped
+                    .get(&fullname)
+                    .and_then(|reason| serde_json::from_str::<SkipReason>(reason).ok()),
+                fullname,
+                seen_at: at as u64,
+            })
+            .collect();
+        Ok(StateFile {
+            processed,
+            cursor: match cursor {
+                Some(cursor) => serde_json::from_str(&cursor).context("reading the cursor")?,
+                None => Default::default(),
+            },
+            pending: pending
+                .iter()
+                .map(|p| serde_json::from_str::<PendingReply>(p))
+                .collect::<serde_json::Result<_>>()
+                .context("reading held back replies")?,
+            stats: from_counters(stats)?,
+            owner: owner
+                .map(|owner| serde_json::from_str(&owner))
+                .transpose()
+                .context("reading the state's owner")?,
+            ..StateFile::default()
+        })
+    }
+
+    /// Send Redis what changed in a listing's state since `before`.
+    fn send_state(
+        &self,
+        conn: &mut Connection,
+        key: &str,
+        before: &StateFile,
+        state: &StateFile,
+    ) -> Result<()> {
+        let processed = self.key(&[key, "processed"]);
+        let skipped = self.key(&[key, "skipped"]);
+        let pending = self.key(&[key, "pending"]);
+        let stats = self.key(&[key, "stats"]);
+        let mut pipe = redis::pipe();
+        pipe.atomic();
+        pipe.cmd("SET")
+            .arg(self.key(&[key, "cursor"]))
+            .arg(to_json(&state.cursor)?)
+            .ignore();
+        if let Some(owner) = &state.owner {
+            if before.owner.as_ref() != Some(owner) {
+                pipe.cmd("SET")
+                    .arg(self.key(&[key, "owner"]))
+                    .arg(to_json(owner)?)
+                    .ignore();
+            }
+        }
+
+        let old: HashSet<&str> = before.processed.fullnames().into_iter().collect();
+        let new: HashSet<&str> = state.processed.fullnames().into_iter().collect();
+        for fullname in old.difference(&new) {
+            pipe.cmd("ZREM").arg(&processed).arg(*fullname).ignore();
+            pipe.cmd("HDEL").arg(&skipped).arg(*fullname).ignore();
+        }
+        for fullname in &new {
+            if !old.contains(fullname) {
+                let at = state.processed.seen_at(fullname).unwrap_or_default();
+                // the first bot to see a post says when it was seen
+                pipe.cmd("ZADD")
+                    .arg(&processed)
+                    .arg("NX")
+                    .arg(at)
+                    .arg(*fullname)
+                    .ignore();
+            }
+            if let Some(reason) = state.processed.skipped(fullname) {
+                if before.processed.skipped(fullname) != Some(reason) {
+                    pipe.cmd("HSET")
+                        .arg(&skipped)
+                        .arg(*fullname)
+                        .arg(to_json(&reason)?)
+                        .ignore();
+                }
+            }
+        }
+        if self.retention > 0 {
+            // entries saved without a time have a score of 0 and are kept
+            let cutoff = now().saturating_sub(self.retention);
+            expire_before(&mut pipe, &processed, cutoff);
+        }
+
+        if before.pending != state.pending {
+            pipe.cmd("DEL").arg(&pending).ignore();
+            for reply in &state.pending {
+                pipe.cmd("RPUSH")
+                    .arg(&pending)
+                    .arg(to_json(reply)?)
+                    .ignore();
+            }
+        }
+
+        let old = counters(&before.stats);
+        for (counter, count) in counters(&state.stats) {
+            let delta = count - old.get(&counter).copied().unwrap_or_default();
+            if delta != 0 {
+                pipe.cmd("HINCRBY")
+                    .arg(&stats)
+                    .arg(counter)
+                    .arg(delta)
+                    .ignore();
+            }
+        }
+        pipe.query::<()>(conn)?;
+        Ok(())
+    }
+}
+
+/// Drop processed posts seen before `cutoff`, but after 0.
+fn expire_before(pipe: &mut Pipeline, processed: &str, cutoff: u64) {
+    pipe.cmd("ZREMRANGEBYSCORE")
+        .arg(processed)
+        .arg(1)
+        .arg(format!("({}", cutoff))
+        .ignore();
+}
+
+/// Run a single command, for `with_conn`.
+fn query<T: redis::FromRedisValue>(conn: &mut Connection, cmd: &mut Cmd) -> Result<T> {
+    let result: RedisResult<T> = cmd.query(conn);
+    Ok(result?)
+}
+
+impl Store for RedisStore {
+    fn load(&self, key: &str) -> Result<StateFile> {
+        match self.with_conn(|conn| self.fetch_state(conn, key)) {
+            Ok(state) => {
+                let mut local = self.local.lock().unwrap_or_else(|e| e.into_inner());
+                local.states.insert(key.to_owned(), state.clone());
+                local.synced.insert(key.to_owned(), state.clone());
+                Ok(state)
+            }
+            Err(e) => {
+                let local = self.local.lock().unwrap_or_else(|e| e.into_inner());
+                match local.states.get(key) {
+                    Some(state) => {
+                        warn!("Using the last known state of {}: {:#}", key, e);
+                        Ok(state.clone())
+                    }
+                    None => {
+                        // nothing is replied to until Redis is back, and what's
+                        // seen until then is added to what it has
+                        warn!("Starting {} afresh until Redis is back: {:#}", key, e);
+                        Ok(StateFile::default())
+                    }
+                }
+            }
+        }
+    }
+
+    fn save(&mut self, key: &str, state: &StateFile) -> Result<()> {
+        let before = {
+            let mut local = self.local.lock().unwrap_or_else(|e| e.into_inner());
+            local.states.insert(key.to_owned(), state.clone());
+            if !local.unsaved.is_empty() {
+                // written back along with the others on reconnecting
+                local.unsaved.insert(key.to_owned());
+                return Ok(());
+            }
+            local.synced.get(key).cloned().unwrap_or_default()
+        };
+        match self.with_conn(|conn| self.send_state(conn, key, &before, state)) {
+            Ok(()) => {
+                let mut local = self.local.lock().unwrap_or_else(|e| e.into_inner());
+                local.synced.insert(key.to_owned(), state.clone());
+            }
+            Err(e) => {
+                warn!(
+                    "Keeping the state of {} in memory until Redis is back: {:#}",
+                    key, e
+                );
+                let mut local = self.local.lock().unwrap_or_else(|e| e.into_inner());
+                local.unsaved.insert(key.to_owned());
+            }
+        }
+        Ok(())
+    }
+
+    fn replies(&self) -> Result<Vec<ReplyRecord>> {
+        let records: Vec<String> = self.with_conn(|conn| {
+            query(
+                conn,
+                redis::cmd("LRANGE")
+                    .arg(self.key(&["replies"]))
+                    .arg(0)
+                    .arg(-1),
+            )
+        })?;
+        records
+            .iter()
+            .map(|r| serde_json::from_str(r).context("reading replies from Redis"))
+            .collect()
+    }
+
+    fn add_replies(&mut self, records: &[ReplyRecord]) -> Result<()> {
+        if records.is_empty() {
+            return Ok(());
+        }
+        let records = records.iter().map(to_json).collect::<Result<Vec<_>>>()?;
+        self.with_conn(|conn| {
+            query::<()>(
+                conn,
+                redis::cmd("RPUSH").arg(self.key(&["replies"])).arg(records),
+            )
+        })
+    }
+
+    fn resolve_reply(&mut self, comment: &str, repo: &str, at: u64) -> Result<()> {
+        let before = self.replies()?;
+        let mut after = before.clone();
+        history::mark_resolved(&mut after, comment, repo, at);
+        for (index, record) in after.iter().enumerate() {
+            if before[index] != *record {
+                let record = to_json(record)?;
+                self.with_conn(|conn| {
+                    query::<()>(
+                        conn,
+                        redis::cmd("LSET")
+                            .arg(self.key(&["replies"]))
+                            .arg(index)
+                            .arg(record),
+                    )
+                })?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Only the oldest replies are trimmed, so those other bots push
+    /// meanwhile are kept.
+    fn prune_replies(&mut self, now: u64, horizon: u64) -> Result<usize> {
+        let expired = history::expired(&self.replies()?, now, horizon);
+        if expired > 0 {
+            self.with_conn(|conn| {
+                query::<()>(
+                    conn,
+                    redis::cmd("LTRIM")
+                        .arg(self.key(&["replies"]))
+                        .arg(expired)
+                        .arg(-1),
+                )
+            })?;
+        }
+        Ok(expired)
+    }
+
+    fn issues(&self) -> Result<Vec<IssueRecord>> {
+        let records: Vec<String> = self.with_conn(|conn| {
+            query(
+                conn,
+                redis::cmd("LRANGE")
+                    .arg(self.key(&["issues"]))
+                    .arg(0)
+                    .arg(-1),
+            )
+        })?;
+        records
+            .iter()
+            .map(|r| serde_json::from_str(r).context("reading issues from Redis"))
+            .collect()
+    }
+
+    fn add_issue(&mut self, record: &IssueRecord) -> Result<()> {
+        let record = to_json(record)?;
+        self.with_conn(|conn| {
+            query::<()>(
+                conn,
+                redis::cmd("RPUSH").arg(self.key(&["issues"])).arg(record),
+            )
+        })
+    }
+
+    fn try_claim_reply(&mut self, fullname: &str, at: u64) -> Result<bool> {
+        let claim = to_json(&ReplyClaim {
+            fullname: fullname.to_owned(),
+            at,
+            replied: false,
+            comment: None,
+        })?;
+        self.with_conn(|conn| {
+            // claims are forgotten once the post must have been archived
+            let set: Option<String> = query(
+                conn,
+                redis::cmd("SET")
+                    .arg(self.claim_key(fullname))
+                    .arg(claim)
+                    .arg("NX")
+                    .arg("PX")
+                    .arg(ARCHIVED_AFTER * 1000),
+            )?;
+            if set.is_some() {
+                query::<()>(
+                    conn,
+                    redis::cmd("ZADD")
+                        .arg(self.key(&["claims"]))
+                        .arg(at)
+                        .arg(fullname),
+                )?;
+            }
+            Ok(set.is_some())
+        })
+    }
+
+    fn confirm_reply(&mut self, fullname: &str, comment: Option<&str>) -> Result<()> {
+        self.with_conn(|conn| {
+            let key = self.claim_key(fullname);
+            let claim: Option<String> = query(conn, redis::cmd("GET").arg(&key))?;
+            let mut claim: ReplyClaim = match claim {
+                Some(claim) => serde_json::from_str(&claim).context("reading a claim")?,
+                None => return Ok(()),
+            };
+            claim.replied = true;
+            claim.comment = comment.map(str::to_owned).or(claim.comment);
+            query::<()>(
+                conn,
+                redis::cmd("SET")
+                    .arg(&key)
+                    .arg(to_json(&claim)?)
+                    .arg("XX")
+                    .arg("KEEPTTL"),
+            )
+        })
+    }
+
+    fn release_reply(&mut self, fullname: &str) -> Result<()> {
+        self.with_conn(|conn| {
+            let key = self.claim_key(fullname);
+            let claim: Option<String> = query(conn, redis::cmd("GET").arg(&key))?;
+            let replied = match claim {
+                Some(claim) => {
+                    serde_json::from_str::<ReplyClaim>(&claim)
+                        .context("reading a claim")?
+                        .replied
+                }
+                None => false,
+            };
+            if replied {
+                return Ok(());
+            }
+            redis::pipe()
+                .atomic()
+                .cmd("DEL")
+                .arg(&key)
+                .ignore()
+                .cmd("ZREM")
+                .arg(self.key(&["claims"]))
+                .arg(fullname)
+                .ignore()
+                .query::<()>(conn)?;
+            Ok(())
+        })
+    }
+
+    fn reply_claims(&self) -> Result<Vec<ReplyClaim>> {
+        self.with_conn(|conn| {
+            let index = self.key(&["claims"]);
+            let fullnames: Vec<String> =
+                query(conn, redis::cmd("ZRANGE").arg(&index).arg(0).arg(-1))?;
+            if fullnames.is_empty() {
+                return Ok(vec![]);
+            }
+            let keys: Vec<String> = fullnames.iter().map(|f| self.claim_key(f)).collect();
+            let found: Vec<Option<String>> = query(conn, redis::cmd("MGET").arg(keys))?;
+            let mut claims = vec![];
+            for (fullname, claim) in fullnames.iter().zip(found) {
+                match claim {
+                    Some(claim) => {
+                        claims.push(serde_json::from_str(&claim).context("reading a claim")?)
+                    }
+                    // the claim expired
+                    None => query::<()>(conn, redis::cmd("ZREM").arg(&index).arg(fullname))?,
+                }
+            }
+            Ok(claims)
+        })
+    }
+
+    fn try_claim_repo(&mut self, claim: &RepoClaim, window: u64) -> Result<Option<RepoClaim>> {
+        if window == 0 {
+            return Ok(None);
+        }
+        let key = self.key(&["repoclaim", &claim.key()]);
+        let value = to_json(claim)?;
+        self.with_conn(|conn| {
+            // the claim is forgotten once the window is over
+            let set: Option<String> = query(
+                conn,
+                redis::cmd("SET")
+                    .arg(&key)
+                    .arg(value)
+                    .arg("NX")
+                    .arg("PX")
+                    .arg(window * 1000),
+            )?;
+            if set.is_some() {
+                return Ok(None);
+            }
+            let held: Option<String> = query(conn, redis::cmd("GET").arg(&key))?;
+            let held: RepoClaim = match held {
+                Some(held) => serde_json::from_str(&held).context("reading a claim")?,
+                None => return Ok(None),
+            };
+            Ok((held.fullname != claim.fullname).then_some(held))
+        })
+    }
+
+    fn restrictions(&self) -> Result<Vec<Restriction>> {
+        let saved: Option<String> =
+            self.with_conn(|conn| query(conn, redis::cmd("GET").arg(self.key(&["restricted"]))))?;
+        match saved {
+            Some(saved) => serde_json::from_str(&saved).context("reading restricted subreddits"),
+            None => Ok(vec![]),
+        }
+    }
+
+    fn save_restrictions(&mut self, restrictions: &[Restriction]) -> Result<()> {
+        let saved = to_json(&restrictions)?;
+        self.with_conn(|conn| {
+            query::<()>(
+                conn,
+                redis::cmd("SET").arg(self.key(&["restricted"])).arg(saved),
+            )
+        })
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        let local = self.local.lock().unwrap_or_else(|e| e.into_inner());
+        if local.unsaved.is_empty() {
+            return Ok(());
+        }
+        drop(local);
+        self.with_conn(|conn| self.write_back(conn))
+    }
+
+    fn is_degraded(&self) -> bool {
+        self.with_conn(|_| Ok(())).is_err()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{counters, from_counters, RedisStore};
+    use crate::claims::RepoClaim;
+    use crate::history::ReplyRecord;
+    use crate::skip::SkipReason;
+    use crate::state::{Owner, StateFile, Store};
+    use crate::stats::Stats;
+    use std::{
+        collections::HashSet,
+        thread,
+        time::{SystemTime, UNIX_EPOCH},
+    };
+
+    /// A store on the Redis named by `CFL_TEST_REDIS_URL`, with keys of its
+    /// own, or `None` to skip the test if there isn't one.
+    fn test_store(name: &str) -> Option<(String, String)> {
+        let url = std::env::var("CFL_TEST_REDIS_URL").ok()?;
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        Some((url, format!("cfl-test-{}-{}", name, nanos)))
+    }
+
+    fn open(url: &str, prefix: &str) -> RedisStore {
+        RedisStore::open(url, 0).unwrap().prefix(prefix)
+    }
+
+    fn clean_up(url: &str, prefix: &str) {
+        let mut conn = redis::Client::open(url).unwrap().get_connection().unwrap();
+        let keys: Vec<String> = redis::cmd("KEYS")
+            .arg(format!("{}:*", prefix))
+            .query(&mut conn)
+            .unwrap();
+        if !keys.is_empty() {
+            redis::cmd("DEL").arg(keys).query::<()>(&mut conn).unwrap();
+        }
+    }
+
+    #[test]
+    fn keeps_stats_as_counters() {
+        let mut stats = Stats::default();
+        stats.subreddit("rust").scanned = 3;
+        stats.skip("rust", SkipReason::OwnPost);
+        stats.subreddit("programming").replies = 1;
+        let counters = counters(&stats);
+        assert_eq!(counters["rust/scanned"], 3);
+        assert_eq!(counters["rust/skips/own post"], 1);
+        assert_eq!(counters["programming/replies"], 1);
+        assert_eq!(
+            from_counters(counters.into_iter().collect()).unwrap(),
+            stats
+        );
+    }
+
+    #[test]
+    fn keeps_listings_in_memory_while_redis_is_unreachable() {
+        // nothing listens on port 1
+        let mut store = RedisStore::open("redis://127.0.0.1:1/", 0).unwrap();
+        assert!(store.is_degraded());
+        assert_eq!(store.load("r-rust").unwrap(), StateFile::default());
+        // nothing to save yet
+        store.flush().unwrap();
+
+        let mut state = StateFile::default();
+        state.processed.insert("t3_a", 1);
+        store.save("r-rust", &state).unwrap();
+        assert_eq!(store.load("r-rust").unwrap(), state);
+        assert!(store.flush().is_err());
+        assert!(store.try_claim_reply("t3_a", 1).is_err());
+        assert!(store.replies().is_err());
+        assert!(RedisStore::open("not a url", 0).is_err());
+    }
+
+    #[test]
+    fn only_one_client_claims_each_post() {
+        let (url, prefix) = match test_store("claims") {
+            Some(store) => store,
+            None => return,
+        };
+        let posts: Vec<String> = (0..200).map(|i| format!("t3_{}", i)).collect();
+        let clients: Vec<_> = (0..2)
+            .map(|client| {
+                let (url, prefix, mut posts) = (url.clone(), prefix.clone(), posts.clone());
+                if client == 1 {
+                    posts.reverse();
+                }
+                thread::spawn(move || {
+                    let mut store = open(&url, &prefix);
+                    posts
+                        .into_iter()
+                        .filter(|post| store.try_claim_reply(post, 1).unwrap())
+                        .collect::<HashSet<String>>()
+                })
+            })
+            .collect();
+        let claimed: Vec<HashSet<String>> =
+            clients.into_iter().map(|c| c.join().unwrap()).collect();
+        assert!(claimed[0].is_disjoint(&claimed[1]));
+        assert_eq!(claimed[0].len() + claimed[1].len(), posts.len());
+
+        let mut store = open(&url, &prefix);
+        store.confirm_reply("t3_0", Some("t1_a")).unwrap();
+        store.release_reply("t3_0").unwrap();
+        store.release_reply("t3_1").unwrap();
+        assert!(!store.try_claim_reply("t3_0", 2).unwrap());
+        assert!(store.try_claim_reply("t3_1", 2).unwrap());
+        let claims = store.reply_claims().unwrap();
+        assert_eq!(claims.len(), posts.len());
+        let first = claims.iter().find(|c| c.fullname == "t3_0").unwrap();
+        assert!(first.replied);
+        assert_eq!(first.comment.as_deref(), Some("t1_a"));
+        clean_up(&url, &prefix);
+    }
+
+    #[test]
+    fn repo_claims_are_shared_across_bots() {
+        let (url, prefix) = match test_store("repo-claims") {
+            Some(store) => store,
+            None => return,
+        };
+        let claim = |fullname: &str, subreddit: &str| RepoClaim {
+            author: "op".to_owned(),
+            repo: "someone/project".to_owned(),
+            fullname: fullname.to_owned(),
+            subreddit: subreddit.to_owned(),
+            permalink: String::new(),
+            at: 1,
+        };
+        let mut first = open(&url, &prefix);
+        let mut second = open(&url, &prefix);
+        assert_eq!(
+            first.try_claim_repo(&claim("t3_a", "rust"), 60).unwrap(),
+            None
+        );
+        assert_eq!(
+            first.try_claim_repo(&claim("t3_a", "rust"), 60).unwrap(),
+            None
+        );
+        let held = second
+            .try_claim_repo(&claim("t3_b", "programming"), 60)
+            .unwrap();
+        assert_eq!(held, Some(claim("t3_a", "rust")));
+        clean_up(&url, &prefix);
+    }
+
+    #[test]
+    fn bots_add_to_each_others_listings() {
+        let (url, prefix) = match test_store("listings") {
+            Some(store) => store,
+            None => return,
+        };
+        let mut first = open(&url, &prefix);
+        let mut second = open(&url, &prefix);
+        first
+            .update("r-rust", &mut |s| {
+                s.processed.insert("t3_a", 1);
+                s.processed.skip("t3_a", SkipReason::NoLinks);
+                s.stats.subreddit("rust").scanned += 1;
+                s.owner = Some(Owner::new("bot", "r-rust"));
+            })
+            .unwrap();
+
+        // both load the listing, then each saves what it saw
+        let mut seen_first = first.load("r-rust").unwrap();
+        let mut seen_second = second.load("r-rust").unwrap();
+        assert_eq!(
+            seen_second.processed.skipped("t3_a"),
+            Some(SkipReason::NoLinks)
+        );
+        assert_eq!(seen_second.owner, Some(Owner::new("bot", "r-rust")));
+        seen_first.processed.insert("t3_b", 2);
+        seen_first.stats.subreddit("rust").scanned += 1;
+        seen_second.processed.insert("t3_c", 3);
+        seen_second.stats.subreddit("rust").scanned += 1;
+        first.save("r-rust", &seen_first).unwrap();
+        second.save("r-rust", &seen_second).unwrap();
+
+        let mut state = first.load("r-rust").unwrap();
+        assert_eq!(state.processed.fullnames(), vec!["t3_a", "t3_b", "t3_c"]);
+        assert_eq!(state.stats.subreddit("rust").scanned, 3);
+
+        // pruning drops posts for both
+        state.processed.prune(10, 0, Some(1));
+        first.save("r-rust", &state).unwrap();
+        assert_eq!(
+            second.load("r-rust").unwrap().processed.fullnames(),
+            vec!["t3_c"]
+        );
+
+        let record = ReplyRecord {
+            author: "op".to_owned(),
+            repo: "some/repo".to_owned(),
+            post: "t3_a".to_owned(),
+            comment: Some("t1_a".to_owned()),
+            at: 1,
+            resolved: None,
+        };
+        first.add_replies(std::slice::from_ref(&record)).unwrap();
+        second.resolve_reply("t1_a", "some/repo", 5).unwrap();
+        assert_eq!(first.replies().unwrap()[0].resolved, Some(5));
+
+        // pruning trims the oldest replies for both
+        let newer = ReplyRecord { at: 100, ..record };
+        second.add_replies(std::slice::from_ref(&newer)).unwrap();
+        assert_eq!(first.prune_replies(100, 50).unwrap(), 1);
+        assert_eq!(second.replies().unwrap(), vec![newer]);
+        clean_up(&url, &prefix);
+    }
+}
@@ -0,0 +1,294 @@
+use anyhow::Result;
+#[cfg(unix)]
+use log::{error, info, warn};
+use std::{env, fs, path::Path};
+#[cfg(unix)]
+use tokio::{
+    signal::unix::{signal, SignalKind},
+    sync::watch,
+};
+
+use crate::models::Config;
+
+/// Settings that only take effect on restart, by name.
+///
+/// Everything else in `Config` is read fresh each time it's used.
+const RESTART_REQUIRED: &[&str] = &[
+    "username",
+    "password",
+    "user_agent",
+    "client_id",
+    "client_secret",
+    "github_username",
+    "subreddit",
+    "watch_user",
+    "search_query",
+    "state_dir",
+    "redis_url",
+    "http_listen",
+    "findings_path",
+    "findings_max_bytes",
+    "feed_path",
+    "event_socket",
+    "http_audit_path",
+    "github_app",
+    "max_restarts_per_hour",
+    "log_file",
+    "log_max_size_mb",
+    "log_keep",
+    "sentry_dsn",
+    "pipeline_depth",
+];
+
+/// Names of the settings that differ between two configs.
+fn changed_fields(old: &Config, new: &Config) -> Vec<&'static str> {
+    let mut changed = vec![];
+    macro_rules! compare {
+        ($($field:ident),*) => {
+            $(if old.$field != new.$field {
+                changed.push(stringify!($field));
+            })*
+        };
+    }
+    compare!(
+        username,
+        password,
+        user_agent,
+        client_id,
+        client_secret,
+        github_username,
+        check_concurrency,
+        max_pages,
+        subreddit,
+        watch_user,
+        search_query,
+        search_interval,
+        max_replies_per_hour,
+        visibility_check_interval,
+        pause_on_shadowban,
+        invited_subreddits,
+        state_dir,
+        http_listen,
+        http_token,
+        findings_path,
+        findings_max_bytes,
+        findings_retention_days,
+        findings_retention,
+        feed_path,
+        event_socket,
+        active_hours,
+        timezone,
+        opt_out_keyword,
+        maintainer,
+        footer_template,
+        approval_mode,
+        max_restarts_per_hour,
+        log_file,
+        log_max_size_mb,
+        log_keep,
+        sentry_dsn,
+        reply_dedup_ttl,
+        digest_at,
+        digest_thread,
+        digest_modmail,
+        github_token,
+        github_issue_mode,
+        github_issue_max_stars,
+        github_quota_floor,
+        removal_check_delay,
+        auto_disable_after_removals,
+        duplicate_window,
+        duplicate_mode,
+        cross_sub_window_minutes,
+        cross_sub_mode,
+        author_opt_out_phrases,
+        wip_phrases,
+        wip_followup_days,
+        exempt_authors,
+        high_profile_owners,
+        max_stars,
+        template_dir,
+        subreddit_locales,
+        unavailable_retry_interval,
+        quarantine_optin,
+        recheck_after,
+        maintenance_backoff,
+        processed_retention,
+        processed_max,
+        ack_resolved,
+        compact_every,
+        http_audit_path,
+        reply_to_deleted,
+        github_app,
+        praise_mode,
+        max_praise_per_day,
+        max_retries,
+        retry_max_age,
+        max_delayed_replies,
+        max_rechecks,
+        queue_budget,
+        redis_url,
+        sandbox_subreddit,
+        max_url_len,
+        max_links_per_post,
+        young_post_age,
+        pipeline_depth
+    );
+    changed
+}
+
+/// Apply the settings from a reloaded config that are safe to change live.
+///
+/// Returns the merged config, with the names of the settings that were
+/// applied and of those that were left alone until the next restart.
+pub fn apply(old: &Config, new: &Config) -> (Config, Vec<&'static str>, Vec<&'static str>) {
+    let (restart, live): (Vec<_>, Vec<_>) = changed_fields(old, new)
+        .into_iter()
+        .partition(|f| RESTART_REQUIRED.contains(f));
+    let merged = Config {
+        username: old.username.clone(),
+        password: old.password.clone(),
+        user_agent: old.user_agent.clone(),
+        client_id: old.client_id.clone(),
+        client_secret: old.client_secret.clone(),
+        github_username: old.github_username.clone(),
+        subreddit: old.subreddit.clone(),
+        watch_user: old.watch_user.clone(),
+        search_query: old.search_query.clone(),
+        state_dir: old.state_dir.clone(),
+        http_listen: old.http_listen,
+        findings_path: old.findings_path.clone(),
+        findings_max_bytes: old.findings_max_bytes,
+        feed_path: old.feed_path.clone(),
+        event_socket: old.event_socket.clone(),
+        http_audit_path: old.http_audit_path.clone(),
+        github_app: old.github_app.clone(),
+        max_restarts_per_hour: old.max_restarts_per_hour,
+        log_file: old.log_file.clone(),
+        log_max_size_mb: old.log_max_size_mb,
+        log_keep: old.log_keep,
+        sentry_dsn: old.sentry_dsn.clone(),
+        pipeline_depth: old.pipeline_depth,
+        provenance: new.provenance.merged(&old.provenance, RESTART_REQUIRED),
+        ..new.clone()
+    };
+    (merged, live, restart)
+}
+
+/// Load `KEY=VALUE` lines from an env file, replacing any current values.
+///
+/// Used instead of the startup loader, which keeps existing variables.
+#[cfg(any(unix, test))]
+fn load_env_file(path: &Path) -> Result<()> {
+    let data = fs::read_to_string(path)?;
+    for line in data.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some((key, value)) = line.split_once('=') {
+            let value = value.trim().trim_matches(|c| c == '"' || c == '\'');
+            env::set_var(key.trim(), value);
+        }
+    }
+    Ok(())
+}
+
+/// Re-read the config whenever the process gets SIGHUP, sending it to the bot.
+///
+/// The bot picks the new config up at the top of its next pass. There's no
+/// SIGHUP off unix, so there the config is only read at startup.
+#[cfg(unix)]
+pub async fn watch_sighup(updates: watch::Sender<Config>) -> Result<()> {
+    let mut hangups = signal(SignalKind::hangup())?;
+    while hangups.recv().await.is_some() {
+        info!("Got SIGHUP, reloading configuration");
+        let env_file = Path::new(".env");
+        if env_file.exists() {
+            if let Err(e) = load_env_file(env_file) {
+                warn!("Could not read {}: {}", env_file.display(), e);
+            }
+        }
+        match Config::from_env() {
+            Ok(config) => {
+                if updates.send(config).is_err() {
+                    break;
+                }
+            }
+            Err(e) => error!("Not reloading, configuration is invalid: {}", e),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{apply, load_env_file};
+    use crate::models::{Config, Provenance, Source};
+    use std::{env, fs};
+
+    #[test]
+    fn applies_live_settings_only() {
+        let old = Config::for_tests();
+        let mut new = Config::for_tests();
+        new.max_replies_per_hour = Some(3);
+        new.invited_subreddits = vec!["rust".to_owned()];
+        new.subreddit = "other".to_owned();
+        new.password = "changed".to_owned();
+
+        let (merged, live, restart) = apply(&old, &new);
+        assert_eq!(live, vec!["max_replies_per_hour", "invited_subreddits"]);
+        assert_eq!(restart, vec!["password", "subreddit"]);
+        assert_eq!(merged.max_replies_per_hour, Some(3));
+        assert_eq!(merged.invited_subreddits, vec!["rust"]);
+        assert_eq!(merged.subreddit, "test");
+        assert_eq!(merged.password, "password");
+    }
+
+    #[test]
+    fn merged_settings_keep_their_sources() {
+        let old = Config {
+            provenance: Provenance::given(["CFL_SUBREDDIT", "CFL_MAX_STARS"]),
+            ..Config::for_tests()
+        };
+        let new = Config {
+            subreddit: "other".to_owned(),
+            max_stars: 100,
+            max_pages: 10,
+            provenance: Provenance::given(["CFL_MAX_PAGES", "CFL_PASSWORD"]),
+            ..Config::for_tests()
+        };
+        let (merged, _, _) = apply(&old, &new);
+        let source = |field| merged.provenance.source(field);
+        // the subreddit and password only change on restart
+        assert_eq!(source("subreddit"), Source::Global);
+        assert_eq!(source("password"), Source::Default);
+        // the rest are as reloaded, whether set or taken out
+        assert_eq!(source("max_pages"), Source::Global);
+        assert_eq!(source("max_stars"), Source::Default);
+    }
+
+    #[test]
+    fn unchanged_config_applies_nothing() {
+        let (merged, live, restart) = apply(&Config::for_tests(), &Config::for_tests());
+        assert!(live.is_empty());
+        assert!(restart.is_empty());
+        assert_eq!(merged, Config::for_tests());
+    }
+
+    #[test]
+    fn env_file_overrides_current_values() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join(".env");
+        fs::write(
+            &path,
+            "# comment\n\nCFL_RELOAD_TEST_A=1\nCFL_RELOAD_TEST_B = \"two words\"\n",
+        )
+        .unwrap();
+        env::set_var("CFL_RELOAD_TEST_A", "0");
+
+        load_env_file(&path).unwrap();
+        assert_eq!(env::var("CFL_RELOAD_TEST_A").unwrap(), "1");
+        assert_eq!(env::var("CFL_RELOAD_TEST_B").unwrap(), "two words");
+    }
+}
@@ -0,0 +1,71 @@
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// A reply to look at again later, to see whether it was removed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RemovalCheck {
+    /// Fullname of the bot's comment.
+    pub comment: String,
+    /// Fullname of the post it replied to.
+    pub post: String,
+    pub subreddit: String,
+    /// Unix timestamp of when to look at the comment.
+    pub due: u64,
+}
+
+/// Whether a comment's data, as returned by `/api/info`, shows that it was
+/// removed by a moderator or AutoModerator.
+pub fn is_removed(comment: &Value) -> bool {
+    comment["removed"].as_bool() == Some(true) || comment["body"].as_str() == Some("[removed]")
+}
+
+/// Counts the bot's removed replies in each subreddit.
+#[derive(Debug, Default)]
+pub struct Removals {
+    counts: HashMap<String, usize>,
+}
+
+impl Removals {
+    /// Count a removed reply in a subreddit, returning how many there have been.
+    pub fn record(&mut self, subreddit: &str) -> usize {
+        let count = self.counts.entry(subreddit.to_lowercase()).or_default();
+        *count += 1;
+        *count
+    }
+
+    /// Whether a subreddit has removed at least `limit` replies, if there's a limit.
+    pub fn reached(&self, subreddit: &str, limit: Option<usize>) -> bool {
+        let count = self
+            .counts
+            .get(&subreddit.to_lowercase())
+            .copied()
+            .unwrap_or_default();
+        limit.is_some_and(|limit| count >= limit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_removed, Removals};
+    use serde_json::json;
+
+    #[test]
+    fn recognizes_removed_comments() {
+        assert!(is_removed(&json!({"removed": true, "body": "hi"})));
+        assert!(is_removed(&json!({"body": "[removed]"})));
+        assert!(!is_removed(&json!({"removed": false, "body": "hi"})));
+        assert!(!is_removed(&json!({"body": "[deleted]"})));
+    }
+
+    #[test]
+    fn counts_removals_per_subreddit() {
+        let mut removals = Removals::default();
+        assert!(!removals.reached("rust", Some(2)));
+        assert_eq!(removals.record("rust"), 1);
+        assert!(!removals.reached("rust", Some(2)));
+        assert_eq!(removals.record("Rust"), 2);
+        assert!(removals.reached("RUST", Some(2)));
+        assert!(!removals.reached("rust", None));
+        assert!(!removals.reached("other", Some(2)));
+    }
+}
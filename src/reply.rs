@@ -0,0 +1,809 @@
+use anyhow::{anyhow, Context, Result};
+use std::fmt::Write;
+
+use crate::models::Config;
+use crate::pending::{PendingReply, RepoLink};
+use crate::preview;
+use crate::templates;
+
+/// Longest comment that Reddit accepts, in characters.
+pub const MAX_COMMENT_LEN: usize = 10_000;
+/// Where the bot's source lives, linked from the footer.
+pub const SOURCE_URL: &str = env!("CARGO_PKG_REPOSITORY");
+/// Characters that mean something in Reddit's markdown.
+const MARKDOWN_SPECIAL: &[char] = &[
+    '\\', '`', '*', '_', '~', '^', '[', ']', '(', ')', '#', '>', '<', '|', '&',
+];
+
+/// Fill in the `{name}` variables of a template.
+///
+/// Unknown variables, and variables without a value, are errors.
+pub fn render(template: &str, vars: &[(&str, Option<&str>)]) -> Result<String> {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        out.push_str(&rest[..open]);
+        let close = rest[open..]
+            .find('}')
+            .ok_or_else(|| anyhow!("Unclosed '{{' in template '{}'", template))?;
+        let name = &rest[open + 1..open + close];
+        match vars.iter().find(|(n, _)| *n == name) {
+            Some((_, Some(value))) => out.push_str(value),
+            Some((_, None)) => {
+                return Err(anyhow!(
+                    "Template uses {{{}}}, which isn't configured",
+                    name
+                ))
+            }
+            None => return Err(anyhow!("Unknown variable {{{}}} in template", name)),
+        }
+        rest = &rest[open + close + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// Escape text so that it shows up as-is in Reddit's markdown.
+///
+/// Line breaks become spaces, so that the text can't start a new block.
+pub fn escape_markdown(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\r' | '\n' => out.push(' '),
+            c if MARKDOWN_SPECIAL.contains(&c) => {
+                out.push('\\');
+                out.push(c);
+            }
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Percent-encode text for use as one segment of a link's path, leaving
+/// nothing that could end the link early.
+pub fn escape_link(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for byte in text.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                out.push(byte as char)
+            }
+            _ => write!(out, "%{:02X}", byte).unwrap(),
+        }
+    }
+    out
+}
+
+/// Percent-encode the characters of a URL that could end a markdown link early.
+fn escape_url(url: &str) -> String {
+    let mut out = String::with_capacity(url.len());
+    for c in url.chars() {
+        match c {
+            '(' | ')' | '[' | ']' | '<' | '>' => write!(out, "%{:02X}", c as u8).unwrap(),
+            c if c.is_whitespace() => out.push_str("%20"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// The parts of a reply in a subreddit's locale, with their variables filled in.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Texts {
+    pub intro: String,
+    pub plural_intro: String,
+    /// Starts a reply about a repository whose license was removed, with
+    /// `{license}` left to fill in.
+    pub license_removed: String,
+    /// Ends a shortened list of repositories, with `{count}` left to fill in.
+    pub more_repos: String,
+    pub more_info: String,
+    /// The short reply pointing to an earlier thread, for one repository and
+    /// for several, if there is an earlier thread.
+    pub earlier: Option<(String, String)>,
+    /// The one line pointing to the reply on the author's post in another
+    /// subreddit, if there is a link to point to.
+    pub cross_post: Option<String>,
+    /// The note left on a post linking an exemplary repository.
+    pub praise: String,
+    /// The line about a repository above a reply about it alone, with its
+    /// `{repo}`, `{language}`, `{created_at}`, `{default_branch}` and
+    /// `{stars}` left to fill in.
+    pub repo_summary: String,
+    pub footer: String,
+}
+
+/// Render the parts of a reply in a subreddit's locale, from the templates
+/// there are for it, or English ones.
+///
+/// Every template can use the same variables: `{keyword}`, `{maintainer}`,
+/// `{source}`, `{link}` to an earlier thread or another subreddit's reply and
+/// the `{license}` a repository
+/// used to have, along with what GitHub says about the repository. Values
+/// are escaped, so they show up as configured whatever the template.
+pub fn texts(config: &Config, subreddit: &str, link: Option<&str>) -> Result<Texts> {
+    let locale = config.locale(subreddit);
+    let keyword = escape_markdown(&config.opt_out_keyword);
+    let maintainer = config.maintainer.as_deref().map(escape_markdown);
+    let link = link.map(escape_url);
+    let vars = [
+        ("keyword", Some(keyword.as_str())),
+        ("maintainer", maintainer.as_deref()),
+        ("source", Some(SOURCE_URL)),
+        ("link", link.as_deref()),
+        ("count", Some("{count}")),
+        ("license", Some("{license}")),
+        ("repo", Some("{repo}")),
+        ("language", Some("{language}")),
+        ("created_at", Some("{created_at}")),
+        ("default_branch", Some("{default_branch}")),
+        ("stars", Some("{stars}")),
+    ];
+    let template = |name: &str| -> Result<String> {
+        let template = templates::resolve(&config.template_dir, locale, name)?;
+        render(&template, &vars)
+            .with_context(|| format!("rendering the {} template for locale {}", name, locale))
+    };
+    let footer = match (&config.footer_template, &config.maintainer) {
+        (Some(t), _) => render(t, &vars)?,
+        (None, Some(_)) => template("footer_maintainer")?,
+        (None, None) => template("footer")?,
+    };
+    Ok(Texts {
+        intro: template("missing_license")?,
+        plural_intro: template("missing_license_plural")?,
+        license_removed: template("license_removed")?,
+        more_repos: template("more_repos")?,
+        more_info: template("more_info")?,
+        earlier: match link {
+            Some(_) => Some((
+                template("earlier_thread")?,
+                template("earlier_thread_plural")?,
+            )),
+            None => None,
+        },
+        cross_post: match link {
+            Some(_) => Some(template("cross_post")?),
+            None => None,
+        },
+        praise: template("praise")?,
+        repo_summary: template("repo_summary")?,
+        footer,
+    })
+}
+
+/// Lay out a reply about one or more unlicensed repositories, listing only
+/// the first `listed` of them and leaving out the optional parts that aren't
+/// wanted.
+fn layout(
+    texts: &Texts,
+    repos: &[RepoLink],
+    listed: usize,
+    summary: bool,
+    more_info: bool,
+    footer: bool,
+) -> String {
+    let mut sections = vec![];
+    if let ([repo], true) = (repos, summary) {
+        // left out if GitHub didn't say all that the line needs
+        sections.extend(preview::line(
+            &texts.repo_summary,
+            &repo.repo,
+            &repo.summary,
+        ));
+    }
+    if let [RepoLink {
+        removed_license: Some(license),
+        ..
+    }] = repos
+    {
+        sections.push(
+            texts
+                .license_removed
+                .replace("{license}", &escape_markdown(license)),
+        );
+    } else if repos.len() < 2 {
+        sections.push(texts.intro.clone());
+    } else {
+        sections.push(texts.plural_intro.clone());
+        let mut list: Vec<String> = repos[..listed]
+            .iter()
+            .map(|r| {
+                format!(
+                    "- [{}/{}](https://github.com/{}/{})",
+                    escape_markdown(&r.repo.owner),
+                    escape_markdown(&r.repo.name),
+                    escape_link(&r.repo.owner),
+                    escape_link(&r.repo.name)
+                )
+            })
+            .collect();
+        if listed < repos.len() {
+            let more = (repos.len() - listed).to_string();
+            list.push(format!("- {}", texts.more_repos.replace("{count}", &more)));
+        }
+        sections.push(list.join("\n"));
+    }
+    if more_info {
+        sections.push(texts.more_info.clone());
+    }
+    let mut text = sections.join("\n\n");
+    if footer {
+        write!(text, "\n\n---\n\n{}", texts.footer).unwrap();
+    }
+    text
+}
+
+/// Render the footer appended to every reply in English.
+///
+/// The opt-out keyword is the same one the bot honors in replies to it.
+pub fn render_footer(config: &Config) -> Result<String> {
+    texts(config, "", None).map(|t| t.footer)
+}
+
+/// The full text of a reply, with the footer, in its subreddit's locale.
+pub fn text(reply: &PendingReply, config: &Config) -> Result<String> {
+    let link = reply.cross_post.as_deref().or(reply.earlier.as_deref());
+    let texts = texts(config, &reply.details.subreddit, link)?;
+    Ok(match (&reply.cross_post, &texts.earlier) {
+        (Some(_), _) => compose_cross_post(&reply.repos, &texts),
+        (None, Some(_)) => compose_earlier(&reply.repos, &texts),
+        (None, None) => compose(&reply.repos, &texts),
+    })
+}
+
+/// The full text of a note praising an exemplary repository, with the
+/// footer, in a subreddit's locale.
+pub fn praise(subreddit: &str, config: &Config) -> Result<String> {
+    let texts = texts(config, subreddit, None)?;
+    Ok(format!("{}\n\n---\n\n{}", texts.praise, texts.footer))
+}
+
+/// Put together a short reply about repositories that were already replied
+/// about in an earlier submission of the same link, pointing there.
+///
+/// The footer is left out if it would make the reply too long.
+pub fn compose_earlier(repos: &[RepoLink], texts: &Texts) -> String {
+    let body = match &texts.earlier {
+        Some((one, _)) if repos.len() < 2 => one.clone(),
+        Some((_, several)) => several.clone(),
+        None => return compose(repos, texts),
+    };
+    let text = format!("{}\n\n---\n\n{}", body, texts.footer);
+    if text.chars().count() > MAX_COMMENT_LEN {
+        return body;
+    }
+    text
+}
+
+/// Put together the one line pointing to the reply about the repositories
+/// on the author's post of them in another subreddit.
+///
+/// The footer is left out if it would make the reply too long.
+pub fn compose_cross_post(repos: &[RepoLink], texts: &Texts) -> String {
+    let body = match &texts.cross_post {
+        Some(line) => line,
+        None => return compose(repos, texts),
+    };
+    let text = format!("{}\n\n---\n\n{}", body, texts.footer);
+    if text.chars().count() > MAX_COMMENT_LEN {
+        return body.clone();
+    }
+    text
+}
+
+/// Put a reply about some unlicensed repositories together, with the footer
+/// below a rule.
+///
+/// If that's too long for Reddit to accept, parts are left out until it fits:
+/// first repositories from the end of the list, then the line about the
+/// repository, then the link to more information, and last of all the footer.
+pub fn compose(repos: &[RepoLink], texts: &Texts) -> String {
+    let mut listed = repos.len();
+    let mut summary = true;
+    let mut more_info = true;
+    let mut footer = true;
+    loop {
+        let text = layout(texts, repos, listed, summary, more_info, footer);
+        if text.chars().count() <= MAX_COMMENT_LEN {
+            return text;
+        }
+        if listed > 1 {
+            listed -= 1;
+        } else if summary {
+            summary = false;
+        } else if more_info {
+            more_info = false;
+        } else if footer {
+            footer = false;
+        } else {
+            return text.chars().take(MAX_COMMENT_LEN).collect();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        compose, compose_cross_post, compose_earlier, escape_link, escape_markdown, render,
+        render_footer, text, texts, Texts, MAX_COMMENT_LEN, SOURCE_URL,
+    };
+    use crate::latency::Stages;
+    use crate::models::Config;
+    use crate::pending::{PendingReply, PostDetails, RepoLink};
+    use crate::preview::RepoSummary;
+    use crate::util::GhRepo;
+
+    #[test]
+    fn default_footers() {
+        let mut config = Config::for_tests();
+        assert_eq!(
+            render_footer(&config).unwrap(),
+            format!(
+                r#"^(I'm a bot. Reply "!ignore" to have this comment removed. | [source]({}))"#,
+                SOURCE_URL
+            )
+        );
+        config.maintainer = Some("Celeo".to_owned());
+        config.opt_out_keyword = "!optout".to_owned();
+        let footer = render_footer(&config).unwrap();
+        assert!(footer.contains(r#"Reply "!optout""#), "{}", footer);
+        assert!(footer.contains("Message /u/Celeo"), "{}", footer);
+    }
+
+    #[test]
+    fn custom_footer_template() {
+        let mut config = Config::for_tests();
+        config.footer_template = Some("{keyword} / {source}".to_owned());
+        assert_eq!(
+            render_footer(&config).unwrap(),
+            format!("!ignore / {}", SOURCE_URL)
+        );
+
+        config.footer_template = Some("ask /u/{maintainer}".to_owned());
+        assert!(render_footer(&config).is_err());
+        config.footer_template = Some("{nope}".to_owned());
+        assert!(render_footer(&config).is_err());
+    }
+
+    #[test]
+    fn template_variables() {
+        let vars = [("a", Some("1")), ("b", None)];
+        assert_eq!(render("x{a}y{a}", &vars).unwrap(), "x1y1");
+        assert_eq!(render("no variables", &vars).unwrap(), "no variables");
+        assert!(render("{b}", &vars).is_err());
+        assert!(render("{c}", &vars).is_err());
+        assert!(render("{a", &vars).is_err());
+    }
+
+    #[test]
+    fn footer_values_are_escaped() {
+        let mut config = Config::for_tests();
+        config.maintainer = Some("some_one".to_owned());
+        config.opt_out_keyword = "[stop](x)".to_owned();
+        let footer = render_footer(&config).unwrap();
+        assert!(footer.contains(r#"Reply "\[stop\]\(x\)""#), "{}", footer);
+        assert!(footer.contains(r"Message /u/some\_one |"), "{}", footer);
+    }
+
+    #[test]
+    fn escapes_markdown() {
+        for (text, escaped) in &[
+            ("plain", "plain"),
+            ("some-repo.rs", "some-repo.rs"),
+            ("snake_case", r"snake\_case"),
+            ("**bold**", r"\*\*bold\*\*"),
+            ("](http://evil.example)", r"\]\(http://evil.example\)"),
+            ("[link](url)", r"\[link\]\(url\)"),
+            (r"back\slash", r"back\\slash"),
+            (r"\*", r"\\\*"),
+            ("`code`", r"\`code\`"),
+            ("~~gone~~", r"\~\~gone\~\~"),
+            ("^(up)", r"\^\(up\)"),
+            ("# heading", r"\# heading"),
+            ("> quote", r"\> quote"),
+            ("a | b", r"a \| b"),
+            ("<b>&amp;", r"\<b\>\&amp;"),
+            ("two\nlines\r\n", "two lines  "),
+            ("", ""),
+            ("日本語", "日本語"),
+            ("ünïcödé_名前", r"ünïcödé\_名前"),
+            ("🦀*crab*", r"🦀\*crab\*"),
+        ] {
+            assert_eq!(escape_markdown(text), *escaped, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn escapes_links() {
+        for (text, escaped) in &[
+            ("some-repo.rs", "some-repo.rs"),
+            ("snake_case~1", "snake_case~1"),
+            ("a b", "a%20b"),
+            (
+                "](http://evil.example)",
+                "%5D%28http%3A%2F%2Fevil.example%29",
+            ),
+            ("ü", "%C3%BC"),
+        ] {
+            assert_eq!(escape_link(text), *escaped, "{:?}", text);
+        }
+    }
+
+    fn link(org: &str, repo: &str) -> RepoLink {
+        RepoLink {
+            repo: GhRepo {
+                owner: org.to_owned(),
+                name: repo.to_owned(),
+            },
+            url: format!("https://github.com/{}/{}/tree/main", org, repo),
+            removed_license: None,
+            summary: RepoSummary::default(),
+        }
+    }
+
+    /// The English texts, with another footer.
+    fn english(footer: &str) -> Texts {
+        Texts {
+            footer: footer.to_owned(),
+            ..texts(&Config::for_tests(), "", None).unwrap()
+        }
+    }
+
+    #[test]
+    fn single_repo_reply() {
+        assert_eq!(
+            compose(&[link("someone", "one")], &english("footer")),
+            format!(
+                "{}\n\n{}\n\n---\n\nfooter",
+                english("").intro,
+                english("").more_info
+            )
+        );
+    }
+
+    #[test]
+    fn single_repo_reply_starts_with_a_summary() {
+        let summarized = RepoLink {
+            summary: RepoSummary {
+                language: Some("C++".to_owned()),
+                created_at: Some("2024-01-02T03:04:05Z".to_owned()),
+                default_branch: Some("main".to_owned()),
+                stars: Some(14),
+            },
+            ..link("org", "repo")
+        };
+        let text = compose(std::slice::from_ref(&summarized), &english("footer"));
+        assert!(
+            text.starts_with(
+                "Repository: org/repo — C++, created 2024-01-02, default branch main, 14 stars\n\n\
+                 The linked GitHub repository does not contain a license."
+            ),
+            "{}",
+            text
+        );
+
+        // a line GitHub didn't give every value for is left out
+        let unknown = RepoLink {
+            summary: RepoSummary {
+                language: None,
+                ..summarized.summary.clone()
+            },
+            ..summarized.clone()
+        };
+        assert_eq!(
+            compose(&[unknown], &english("footer")),
+            compose(&[link("org", "repo")], &english("footer"))
+        );
+        // as is one for a list of repositories
+        let text = compose(&[summarized, link("other", "two")], &english("footer"));
+        assert!(text.starts_with("The linked GitHub repositories"));
+    }
+
+    #[test]
+    fn reply_about_a_removed_license() {
+        let removed = RepoLink {
+            removed_license: Some("MIT".to_owned()),
+            ..link("someone", "one")
+        };
+        let text = compose(std::slice::from_ref(&removed), &english("footer"));
+        assert!(
+            text.starts_with(
+                "The linked GitHub repository had a license (MIT) when it was last checked, \
+                 but no longer does."
+            ),
+            "{}",
+            text
+        );
+        // a list of repositories is the same as ever
+        let text = compose(&[removed, link("other", "two")], &english("footer"));
+        assert!(text.starts_with("The linked GitHub repositories do not contain a license:"));
+
+        let tmp = tempfile::tempdir().unwrap();
+        let removed = RepoLink {
+            removed_license: Some("GPL-3.0".to_owned()),
+            ..link("someone", "one")
+        };
+        let reply = pending("de_programmierung", vec![removed], None);
+        let text = super::text(&reply, &german_config(tmp.path())).unwrap();
+        assert!(text.contains("eine Lizenz (GPL-3.0)"), "{}", text);
+    }
+
+    #[test]
+    fn plural_reply_lists_every_repo() {
+        let text = compose(
+            &[link("someone", "one"), link("other", "two")],
+            &english("footer"),
+        );
+        assert_eq!(
+            text,
+            "The linked GitHub repositories do not contain a license:\n\n\
+             - [someone/one](https://github.com/someone/one)\n\
+             - [other/two](https://github.com/other/two)\n\n\
+             Please read over this article for more information: \
+             https://help.github.com/en/github/creating-cloning-and-archiving-repositories/licensing-a-repository\
+             \n\n---\n\nfooter"
+        );
+    }
+
+    #[test]
+    fn repo_names_cant_inject_markdown() {
+        let text = compose(
+            &[
+                link("someone", "](http://evil.example)"),
+                link("other", "two"),
+            ],
+            &english("footer"),
+        );
+        assert!(
+            text.contains(
+                r"- [someone/\]\(http://evil.example\)](https://github.com/someone/%5D%28http%3A%2F%2Fevil.example%29)"
+            ),
+            "{}",
+            text
+        );
+    }
+
+    #[test]
+    fn non_ascii_repo_names_render_intact() {
+        let text = compose(
+            &[link("Вася", "проект-🎉"), link("other", "two")],
+            &english("footer"),
+        );
+        assert!(
+            text.contains(
+                "- [Вася/проект-🎉](https://github.com/%D0%92%D0%B0%D1%81%D1%8F/\
+                 %D0%BF%D1%80%D0%BE%D0%B5%D0%BA%D1%82-%F0%9F%8E%89)"
+            ),
+            "{}",
+            text
+        );
+    }
+
+    #[test]
+    fn long_replies_leave_out_optional_parts() {
+        let repos: Vec<RepoLink> = (0..200)
+            .map(|i| link("someone", &format!("{}{}", "r".repeat(90), i)))
+            .collect();
+        let footer = "footer";
+        // repositories are left out first
+        let text = compose(&repos, &english(footer));
+        assert!(text.chars().count() <= MAX_COMMENT_LEN);
+        assert!(text.contains("- and "), "{}", text);
+        assert!(text.contains(&english("").more_info));
+        assert!(text.ends_with("\n\n---\n\nfooter"));
+
+        // then the link to more information, and the footer last
+        let long_footer = "f".repeat(MAX_COMMENT_LEN - 400);
+        let text = compose(&repos, &english(&long_footer));
+        assert!(text.chars().count() <= MAX_COMMENT_LEN);
+        assert!(text.contains("- and 199 more"), "{}", text);
+        assert!(!text.contains(&english("").more_info));
+        assert!(text.ends_with(&long_footer));
+
+        let too_long = "f".repeat(MAX_COMMENT_LEN);
+        let text = compose(&repos, &english(&too_long));
+        assert!(!text.contains(&too_long));
+        assert!(!text.contains(&english("").more_info));
+        assert!(text.ends_with("- and 199 more"), "{}", text);
+    }
+
+    #[test]
+    fn footer_counts_toward_the_length_limit() {
+        let separator = "\n\n---\n\n".len();
+        let body = compose(&[link("someone", "one")], &english("")).len() - separator;
+        let fits = "f".repeat(MAX_COMMENT_LEN - separator - body);
+        assert!(compose(&[link("someone", "one")], &english(&fits)).ends_with(&fits));
+        // one more character, and the link to more information is left out
+        let too_long = format!("{}f", fits);
+        let text = compose(&[link("someone", "one")], &english(&too_long));
+        assert_eq!(
+            text,
+            format!("{}\n\n---\n\n{}", english("").intro, too_long)
+        );
+        // characters, not bytes
+        let wide = "é".repeat(MAX_COMMENT_LEN - separator - body);
+        assert!(compose(&[link("someone", "one")], &english(&wide)).ends_with(&wide));
+    }
+
+    #[test]
+    fn points_to_earlier_thread() {
+        let permalink = "https://www.reddit.com/r/rust/comments/old/my_(project)/";
+        let mut texts = texts(&Config::for_tests(), "rust", Some(permalink)).unwrap();
+        texts.footer = "footer".to_owned();
+        assert_eq!(
+            compose_earlier(&[link("someone", "one")], &texts),
+            "This was posted before, and the linked GitHub repository does not contain a \
+             license. See [the earlier thread](https://www.reddit.com/r/rust/comments/old/my_%28project%29/).\
+             \n\n---\n\nfooter"
+        );
+        texts.footer = "f".repeat(MAX_COMMENT_LEN);
+        let text = compose_earlier(&[link("someone", "one"), link("someone", "two")], &texts);
+        assert!(text.contains("repositories do not"));
+        assert!(text.ends_with("%29/)."), "{}", text);
+    }
+
+    #[test]
+    fn points_to_the_reply_in_another_subreddit() {
+        let permalink = "https://www.reddit.com/r/rust/comments/a/my_(project)/t1_b/";
+        let mut texts = texts(&Config::for_tests(), "programming", Some(permalink)).unwrap();
+        texts.footer = "footer".to_owned();
+        let expected = "This was also shared in another subreddit, where the missing license \
+             is already pointed out. See [the reply there](https://www.reddit.com/r/rust/comments/a/my_%28project%29/t1_b/).";
+        for repos in &[
+            vec![link("someone", "one")],
+            vec![link("someone", "one"), link("someone", "two")],
+        ] {
+            assert_eq!(
+                compose_cross_post(repos, &texts),
+                format!("{}\n\n---\n\nfooter", expected)
+            );
+        }
+        texts.footer = "f".repeat(MAX_COMMENT_LEN);
+        assert_eq!(
+            compose_cross_post(&[link("someone", "one")], &texts),
+            expected
+        );
+        // nothing to point to
+        let plain = english("footer");
+        assert_eq!(
+            compose_cross_post(&[link("someone", "one")], &plain),
+            compose(&[link("someone", "one")], &plain)
+        );
+
+        let tmp = tempfile::tempdir().unwrap();
+        let config = german_config(tmp.path());
+        let mut reply = pending(
+            "de_programmierung",
+            vec![link("someone", "one")],
+            Some("https://www.reddit.com/r/x/comments/earlier/"),
+        );
+        reply.cross_post = Some("https://www.reddit.com/r/y/comments/z/_/t1_c/".to_owned());
+        let text = super::text(&reply, &config).unwrap();
+        assert!(
+            text.starts_with("Das wurde auch in einem anderen Subreddit geteilt")
+                && text.contains("(https://www.reddit.com/r/y/comments/z/_/t1_c/)"),
+            "{}",
+            text
+        );
+    }
+
+    fn german_config(template_dir: &std::path::Path) -> Config {
+        let mut config = Config::for_tests();
+        config.template_dir = template_dir.to_owned();
+        config.subreddit_locales = vec![
+            ("de_programmierung".to_owned(), "de".to_owned()),
+            ("fr_dev".to_owned(), "fr".to_owned()),
+        ]
+        .into_iter()
+        .collect();
+        config
+    }
+
+    fn pending(subreddit: &str, repos: Vec<RepoLink>, earlier: Option<&str>) -> PendingReply {
+        PendingReply {
+            fullname: "t3_post".to_owned(),
+            repos,
+            details: PostDetails {
+                subreddit: subreddit.to_owned(),
+                ..PostDetails::default()
+            },
+            queued_at: 0,
+            deferred: false,
+            earlier: earlier.map(str::to_owned),
+            cross_post: None,
+            check_after: None,
+            not_before: None,
+            stages: Stages::default(),
+        }
+    }
+
+    #[test]
+    fn replies_in_the_subreddit_locale() {
+        let tmp = tempfile::tempdir().unwrap();
+        let config = german_config(tmp.path());
+        let text = text(
+            &pending("De_Programmierung", vec![link("someone", "one")], None),
+            &config,
+        )
+        .unwrap();
+        assert!(
+            text.starts_with("Das verlinkte GitHub-Repository enthält keine Lizenz.\n\n"),
+            "{}",
+            text
+        );
+        assert!(text.contains(r#"Antworte "!ignore""#), "{}", text);
+        assert!(text.contains(SOURCE_URL), "{}", text);
+
+        let repos: Vec<RepoLink> = (0..200)
+            .map(|i| link("someone", &format!("{}{}", "r".repeat(90), i)))
+            .collect();
+        let text = super::text(&pending("de_programmierung", repos, None), &config).unwrap();
+        assert!(text.contains("\n- und "), "{}", text);
+
+        let earlier = pending(
+            "de_programmierung",
+            vec![link("someone", "one")],
+            Some("https://www.reddit.com/r/x/comments/y/"),
+        );
+        assert!(super::text(&earlier, &config)
+            .unwrap()
+            .contains("Siehe [den früheren Thread](https://www.reddit.com/r/x/comments/y/)."));
+
+        // other subreddits are in English
+        let english = super::text(
+            &pending("rust", vec![link("someone", "one")], None),
+            &config,
+        )
+        .unwrap();
+        assert!(
+            english.starts_with("The linked GitHub repository"),
+            "{}",
+            english
+        );
+    }
+
+    #[test]
+    fn partial_locales_fall_back_to_english() {
+        let tmp = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(tmp.path().join("fr")).unwrap();
+        std::fs::write(
+            tmp.path().join("fr").join("missing_license.md"),
+            "Le dépôt GitHub lié ne contient pas de licence. Voir {source}\n",
+        )
+        .unwrap();
+        let config = german_config(tmp.path());
+        let texts = texts(&config, "fr_dev", None).unwrap();
+        assert_eq!(
+            texts.intro,
+            format!(
+                "Le dépôt GitHub lié ne contient pas de licence. Voir {}",
+                SOURCE_URL
+            )
+        );
+        let english = english("");
+        assert_eq!(texts.plural_intro, english.plural_intro);
+        assert_eq!(texts.more_info, english.more_info);
+        assert_eq!(texts.footer, render_footer(&config).unwrap());
+        assert_eq!(texts.earlier, None);
+
+        // a template with an unknown variable is passed over for the English one
+        std::fs::write(tmp.path().join("fr").join("footer.md"), "{nope}").unwrap();
+        assert_eq!(
+            super::texts(&config, "fr_dev", None).unwrap().footer,
+            render_footer(&config).unwrap()
+        );
+    }
+
+    #[test]
+    fn custom_footer_applies_in_every_locale() {
+        let tmp = tempfile::tempdir().unwrap();
+        let mut config = german_config(tmp.path());
+        config.footer_template = Some("{keyword}!".to_owned());
+        for subreddit in &["de_programmierung", "fr_dev", "rust"] {
+            assert_eq!(texts(&config, subreddit, None).unwrap().footer, "!ignore!");
+        }
+    }
+}
@@ -0,0 +1,370 @@
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::Path,
+    time::{Duration, UNIX_EPOCH},
+};
+
+use crate::check::Verdict;
+use crate::events::Event;
+use crate::license::{DetectionStep, NO_ASSERTION};
+use crate::paths::repo_history_file;
+use crate::util::{fold_case, format_table, GhRepo};
+
+const USAGE: &str = "Usage: check_for_license repo <org/name>";
+
+/// Most checks kept of each repository, the oldest dropped first.
+pub const HISTORY_LEN: usize = 20;
+
+/// What one check of a repository came to.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct CheckRecord {
+    /// Unix timestamp, in seconds, of the check.
+    pub at: u64,
+    pub verdict: Verdict,
+    /// SPDX id of the license, if GitHub recognized it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub license: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub detection_path: Vec<DetectionStep>,
+}
+
+impl CheckRecord {
+    /// The check an event records, unless it isn't about one.
+    pub fn from_event(event: &Event) -> Option<Self> {
+        let verdict = match event.verdict.as_str() {
+            "licensed" => Verdict::Licensed,
+            "unlicensed" => Verdict::Unlicensed,
+            "indeterminate" => Verdict::Indeterminate,
+            _ => return None,
+        };
+        Some(Self {
+            at: event.at,
+            verdict,
+            license: event.license.clone(),
+            detection_path: event.detection_path.clone(),
+        })
+    }
+
+    /// Whether the check told either way.
+    fn is_conclusive(&self) -> bool {
+        self.verdict != Verdict::Indeterminate
+    }
+
+    /// Whether this and `other` came to the same, whatever was looked at.
+    fn same_outcome(&self, other: &CheckRecord) -> bool {
+        self.verdict == other.verdict && self.license == other.license
+    }
+}
+
+/// The latest checks of each repository, oldest first, by lowercased
+/// `org/repo`.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(transparent)]
+pub struct RepoHistory {
+    repos: BTreeMap<String, Vec<CheckRecord>>,
+}
+
+impl RepoHistory {
+    /// Load the history in the state directory.
+    pub fn load(dir: &Path) -> Result<Self> {
+        let path = repo_history_file(dir);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let data =
+            fs::read_to_string(&path).with_context(|| format!("reading {}", path.display()))?;
+        serde_json::from_str(&data).with_context(|| format!("parsing {}", path.display()))
+    }
+
+    /// Whether the state directory has a history yet.
+    pub fn exists(dir: &Path) -> bool {
+        repo_history_file(dir).exists()
+    }
+
+    /// Save the history to the state directory, replacing the file
+    /// atomically.
+    pub fn save(&self, dir: &Path) -> Result<()> {
+        let path = repo_history_file(dir);
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_string(self)?)
+            .and_then(|_| fs::rename(&tmp, &path))
+            .with_context(|| format!("writing {}", path.display()))
+    }
+
+    /// The history the checks in an event log, oldest first, make up, for a
+    /// state directory from before histories were kept.
+    pub fn from_events(events: &[Event]) -> Self {
+        let mut history = Self::default();
+        for event in events {
+            if let Some(check) = CheckRecord::from_event(event) {
+                history.append(&event.repo, check);
+            }
+        }
+        history
+    }
+
+    /// Add a check of a repository, as `org/repo`, to its history, dropping
+    /// the oldest beyond `HISTORY_LEN`.
+    ///
+    /// A check made in the same second as the last one and coming to the
+    /// same, as when several posts link the repository, is kept once.
+    /// Returns whether the check was added.
+    pub fn append(&mut self, repo: &str, check: CheckRecord) -> bool {
+        let checks = self.repos.entry(fold_case(repo)).or_default();
+        if checks
+            .last()
+            .is_some_and(|last| last.at == check.at && last.same_outcome(&check))
+        {
+            return false;
+        }
+        checks.push(check);
+        if checks.len() > HISTORY_LEN {
+            checks.drain(..checks.len() - HISTORY_LEN);
+        }
+        true
+    }
+
+    /// The checks kept of a repository, oldest first.
+    pub fn checks(&self, repo: &str) -> &[CheckRecord] {
+        self.repos.get(&fold_case(repo)).map_or(&[], Vec::as_slice)
+    }
+
+    /// The last check of a repository that told either way.
+    fn last_conclusive(&self, repo: &str) -> Option<&CheckRecord> {
+        self.checks(repo).iter().rev().find(|c| c.is_conclusive())
+    }
+
+    /// When a repository was last found licensed or unlicensed.
+    pub fn last_checked(&self, repo: &str) -> Option<u64> {
+        self.last_conclusive(repo).map(|c| c.at)
+    }
+
+    /// The SPDX id of the license a repository had when it was last found
+    /// licensed or unlicensed, if it had one GitHub recognized.
+    pub fn last_license(&self, repo: &str) -> Option<String> {
+        let last = self.last_conclusive(repo)?;
+        match (&last.verdict, &last.license) {
+            (Verdict::Licensed, Some(id)) if id != NO_ASSERTION => Some(id.clone()),
+            _ => None,
+        }
+    }
+}
+
+/// What changed since the check before, if anything told either way then.
+fn change(before: Option<&CheckRecord>, check: &CheckRecord) -> &'static str {
+    let before = match before {
+        Some(before) => before,
+        None if check.verdict == Verdict::Unlicensed => return "flagged",
+        None => return "",
+    };
+    match (before.verdict, check.verdict) {
+        (_, Verdict::Indeterminate) => "",
+        (Verdict::Licensed, Verdict::Unlicensed) => "license removed",
+        (Verdict::Unlicensed, Verdict::Licensed) => "license added",
+        (Verdict::Licensed, Verdict::Licensed) if before.license != check.license => {
+            "license changed"
+        }
+        _ => "",
+    }
+}
+
+/// A repository's checks as a table, oldest first, with what changed at
+/// each.
+pub fn timeline(checks: &[CheckRecord]) -> String {
+    let mut before: Option<&CheckRecord> = None;
+    let rows: Vec<Vec<String>> = checks
+        .iter()
+        .map(|check| {
+            let verdict = match check.verdict {
+                Verdict::Licensed => "licensed",
+                Verdict::Unlicensed => "unlicensed",
+                Verdict::Indeterminate => "indeterminate",
+            };
+            let row = vec![
+                humantime::format_rfc3339_seconds(UNIX_EPOCH + Duration::from_secs(check.at))
+                    .to_string(),
+                verdict.to_owned(),
+                check.license.clone().unwrap_or_else(|| "-".to_owned()),
+                change(before, check).to_owned(),
+                check
+                    .detection_path
+                    .iter()
+                    .map(DetectionStep::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            ];
+            if check.is_conclusive() {
+                before = Some(check);
+            }
+            row
+        })
+        .collect();
+    format_table(&["WHEN", "VERDICT", "LICENSE", "CHANGE", "CHECKED"], &rows)
+}
+
+/// Run the `repo` subcommand against the state directory, returning the
+/// repository's timeline.
+pub fn run(dir: &Path, args: &[String]) -> Result<String> {
+    let repo: GhRepo = match args {
+        [repo] => repo.parse().map_err(|_| anyhow!(USAGE))?,
+        _ => return Err(anyhow!(USAGE)),
+    };
+    let history = RepoHistory::load(dir)?;
+    match history.checks(&repo.to_string()) {
+        [] => Ok(format!("{} hasn't been checked\n", repo)),
+        checks => Ok(timeline(checks)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{run, timeline, CheckRecord, RepoHistory, HISTORY_LEN};
+    use crate::check::Verdict;
+    use crate::events::Event;
+    use crate::license::{DetectionStep, Probe};
+
+    const DAY: u64 = 24 * 60 * 60;
+
+    fn check(at: u64, verdict: Verdict, license: Option<&str>) -> CheckRecord {
+        CheckRecord {
+            at,
+            verdict,
+            license: license.map(str::to_owned),
+            detection_path: vec![],
+        }
+    }
+
+    #[test]
+    fn keeps_the_latest_checks_of_each_repository() {
+        let mut history = RepoHistory::default();
+        assert!(history.append("Someone/Project", check(1, Verdict::Unlicensed, None)));
+        // the same check, for another post linking the repository
+        assert!(!history.append("someone/project", check(1, Verdict::Unlicensed, None)));
+        assert!(history.append("someone/project", check(1, Verdict::Licensed, Some("MIT"))));
+        assert_eq!(history.checks("SOMEONE/project").len(), 2);
+
+        for at in 2..HISTORY_LEN as u64 + 10 {
+            history.append("someone/project", check(at, Verdict::Unlicensed, None));
+        }
+        let checks = history.checks("someone/project");
+        assert_eq!(checks.len(), HISTORY_LEN);
+        assert_eq!(checks[0].at, 10);
+        assert_eq!(checks[HISTORY_LEN - 1].at, HISTORY_LEN as u64 + 9);
+        assert!(history.checks("someone/else").is_empty());
+    }
+
+    #[test]
+    fn remembers_the_last_license() {
+        let mut history = RepoHistory::default();
+        let repo = "someone/project";
+        assert_eq!(history.last_license(repo), None);
+        history.append(repo, check(1, Verdict::Licensed, Some("MIT")));
+        assert_eq!(history.last_license(repo), Some("MIT".to_owned()));
+        // a check that couldn't tell changes nothing
+        history.append(repo, check(2, Verdict::Indeterminate, None));
+        assert_eq!(history.last_license(repo), Some("MIT".to_owned()));
+        assert_eq!(history.last_checked(repo), Some(1));
+        history.append(repo, check(3, Verdict::Licensed, Some("NOASSERTION")));
+        assert_eq!(history.last_license(repo), None);
+        history.append(repo, check(4, Verdict::Licensed, Some("GPL-3.0")));
+        history.append(repo, check(5, Verdict::Unlicensed, None));
+        assert_eq!(history.last_license(repo), None);
+        assert_eq!(history.last_checked(repo), Some(5));
+    }
+
+    #[test]
+    fn is_saved_and_rebuilt_from_events() {
+        let tmp = tempfile::tempdir().unwrap();
+        assert!(!RepoHistory::exists(tmp.path()));
+        assert_eq!(
+            RepoHistory::load(tmp.path()).unwrap(),
+            RepoHistory::default()
+        );
+        let event = |at, verdict: &str, action: &str| Event {
+            at,
+            fullname: "t3_a".to_owned(),
+            title: String::new(),
+            subreddit: "rust".to_owned(),
+            permalink: String::new(),
+            author: "op".to_owned(),
+            repo: "someone/project".to_owned(),
+            verdict: verdict.to_owned(),
+            license: None,
+            action: action.to_owned(),
+            removed_license: None,
+            detection_path: vec![DetectionStep::new(Probe::LicenseApi, Verdict::Unlicensed)],
+        };
+        let history = RepoHistory::from_events(&[
+            event(1, "unlicensed", "replied"),
+            event(1, "unlicensed", "already replied to author"),
+            event(2, "licensed", "licensed"),
+        ]);
+        let checks = history.checks("someone/project");
+        assert_eq!(checks.len(), 2);
+        assert_eq!(checks[0].detection_path.len(), 1);
+        history.save(tmp.path()).unwrap();
+        assert!(RepoHistory::exists(tmp.path()));
+        assert_eq!(RepoHistory::load(tmp.path()).unwrap(), history);
+    }
+
+    #[test]
+    fn shows_a_timeline() {
+        let mut removed = check(3 * DAY, Verdict::Unlicensed, None);
+        removed.detection_path = vec![
+            DetectionStep::new(Probe::LicenseApi, Verdict::Unlicensed),
+            DetectionStep::new(Probe::ContentsScan, Verdict::Unlicensed),
+        ];
+        let checks = [
+            check(0, Verdict::Unlicensed, None),
+            check(DAY, Verdict::Licensed, Some("MIT")),
+            check(2 * DAY, Verdict::Indeterminate, None),
+            removed,
+            check(4 * DAY, Verdict::Licensed, Some("MIT")),
+            check(5 * DAY, Verdict::Licensed, Some("Apache-2.0")),
+        ];
+        assert_eq!(
+            timeline(&checks),
+            "WHEN                  VERDICT        LICENSE     CHANGE           CHECKED\n\
+             1970-01-01T00:00:00Z  unlicensed     -           flagged\n\
+             1970-01-02T00:00:00Z  licensed       MIT         license added\n\
+             1970-01-03T00:00:00Z  indeterminate  -\n\
+             1970-01-04T00:00:00Z  unlicensed     -           license removed  \
+             license API: unlicensed, contents scan: unlicensed\n\
+             1970-01-05T00:00:00Z  licensed       MIT         license added\n\
+             1970-01-06T00:00:00Z  licensed       Apache-2.0  license changed\n"
+        );
+    }
+
+    #[test]
+    fn repo_command() {
+        let tmp = tempfile::tempdir().unwrap();
+        let args = |args: &[&str]| -> Vec<String> { args.iter().map(|a| a.to_string()).collect() };
+        assert_eq!(
+            run(tmp.path(), &args(&["someone/project"])).unwrap(),
+            "someone/project hasn't been checked\n"
+        );
+        let mut history = RepoHistory::default();
+        history.append("Someone/Project", check(0, Verdict::Unlicensed, None));
+        history.save(tmp.path()).unwrap();
+        let expected = "WHEN                  VERDICT     LICENSE  CHANGE   CHECKED\n\
+                        1970-01-01T00:00:00Z  unlicensed  -        flagged\n";
+        assert_eq!(
+            run(tmp.path(), &args(&["someone/project"])).unwrap(),
+            expected
+        );
+        assert_eq!(
+            run(
+                tmp.path(),
+                &args(&["https://github.com/Someone/Project/tree/main"])
+            )
+            .unwrap(),
+            expected
+        );
+        assert!(run(tmp.path(), &args(&[])).is_err());
+        assert!(run(tmp.path(), &args(&["project"])).is_err());
+        assert!(run(tmp.path(), &args(&["a/b", "c/d"])).is_err());
+    }
+}
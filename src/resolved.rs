@@ -0,0 +1,171 @@
+use anyhow::{anyhow, Result};
+use std::{collections::BTreeMap, str::FromStr};
+
+use crate::history::ReplyRecord;
+use crate::reply::{escape_link, escape_markdown};
+use crate::util::GhRepo;
+
+/// Seconds between checks of the repositories replied about.
+pub const CHECK_INTERVAL: u64 = 24 * 60 * 60;
+/// Seconds after a reply during which its repositories are checked.
+pub const CHECK_WINDOW: u64 = 30 * 24 * 60 * 60;
+
+/// What the bot does when a repository it replied about gains a license.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AckMode {
+    /// Nothing.
+    Off,
+    /// Reply to its own comment saying so.
+    Comment,
+    /// Add a note saying so to its own comment.
+    Edit,
+}
+
+impl FromStr for AckMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "off" => Ok(AckMode::Off),
+            "comment" => Ok(AckMode::Comment),
+            "edit" => Ok(AckMode::Edit),
+            _ => Err(anyhow!(
+                "Acknowledgment mode must be 'comment', 'edit' or 'off'"
+            )),
+        }
+    }
+}
+
+/// The repositories of unresolved replies made less than `CHECK_WINDOW`
+/// before `now`, each once, by the fullname of the comment they were made in.
+pub fn due(records: &[ReplyRecord], now: u64) -> BTreeMap<String, Vec<GhRepo>> {
+    let mut due: BTreeMap<String, Vec<GhRepo>> = BTreeMap::new();
+    for record in records {
+        let comment = match &record.comment {
+            Some(comment) if record.resolved.is_none() => comment,
+            _ => continue,
+        };
+        if now.saturating_sub(record.at) >= CHECK_WINDOW {
+            continue;
+        }
+        let repo = match record.repo.parse::<GhRepo>() {
+            Ok(repo) => repo,
+            Err(_) => continue,
+        };
+        let repos = due.entry(comment.clone()).or_default();
+        if !repos
+            .iter()
+            .any(|r| r.canonical_key() == repo.canonical_key())
+        {
+            repos.push(repo);
+        }
+    }
+    due
+}
+
+/// Text saying that repositories replied about now have licenses, each with
+/// its SPDX id if GitHub recognized it.
+pub fn text(licensed: &[(GhRepo, Option<String>)]) -> String {
+    let link = |repo: &GhRepo| {
+        format!(
+            "[{}/{}](https://github.com/{}/{})",
+            escape_markdown(&repo.owner),
+            escape_markdown(&repo.name),
+            escape_link(&repo.owner),
+            escape_link(&repo.name)
+        )
+    };
+    // GitHub says NOASSERTION for a license it doesn't recognize
+    let known = |spdx_id: &Option<String>| {
+        spdx_id
+            .as_deref()
+            .filter(|id| *id != "NOASSERTION")
+            .map(escape_markdown)
+    };
+    match licensed {
+        [(repo, spdx_id)] => format!(
+            "Update: {} now has {}. Thanks for adding one!",
+            link(repo),
+            match known(spdx_id) {
+                Some(id) => format!("the {} license", id),
+                None => "a license".to_owned(),
+            }
+        ),
+        many => {
+            let list: Vec<String> = many
+                .iter()
+                .map(|(repo, spdx_id)| match known(spdx_id) {
+                    Some(id) => format!("- {}: {}", link(repo), id),
+                    None => format!("- {}", link(repo)),
+                })
+                .collect();
+            format!(
+                "Update: these repositories now have licenses:\n\n{}\n\nThanks for adding them!",
+                list.join("\n")
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{due, text, AckMode, CHECK_WINDOW};
+    use crate::history::ReplyRecord;
+    use crate::util::GhRepo;
+
+    fn record(repo: &str, comment: Option<&str>, at: u64) -> ReplyRecord {
+        ReplyRecord {
+            author: "op".to_owned(),
+            repo: repo.to_owned(),
+            post: "t3_a".to_owned(),
+            comment: comment.map(str::to_owned),
+            at,
+            resolved: None,
+        }
+    }
+
+    #[test]
+    fn parses_modes() {
+        assert_eq!("off".parse::<AckMode>().unwrap(), AckMode::Off);
+        assert_eq!("comment".parse::<AckMode>().unwrap(), AckMode::Comment);
+        assert_eq!("edit".parse::<AckMode>().unwrap(), AckMode::Edit);
+        assert!("reply".parse::<AckMode>().is_err());
+    }
+
+    #[test]
+    fn only_recent_unresolved_replies_are_due() {
+        let now = 1_600_000_000;
+        let mut resolved = record("done/repo", Some("t1_b"), now);
+        resolved.resolved = Some(now);
+        let records = vec![
+            record("some/repo", Some("t1_a"), now - 1),
+            record("Some/Repo", Some("t1_a"), now - 1),
+            record("other/repo", Some("t1_a"), now - 1),
+            resolved,
+            record("lost/repo", None, now),
+            record("old/repo", Some("t1_c"), now - CHECK_WINDOW),
+        ];
+        let due = due(&records, now);
+        assert_eq!(due.len(), 1);
+        assert_eq!(
+            due["t1_a"],
+            vec![GhRepo::new("some", "repo"), GhRepo::new("other", "repo")]
+        );
+    }
+
+    #[test]
+    fn names_the_licenses() {
+        let one = text(&[(GhRepo::new("some", "repo"), Some("MIT".to_owned()))]);
+        assert_eq!(
+            one,
+            "Update: [some/repo](https://github.com/some/repo) now has the MIT license. \
+             Thanks for adding one!"
+        );
+        let many = text(&[
+            (GhRepo::new("some", "repo"), Some("NOASSERTION".to_owned())),
+            (GhRepo::new("other", "repo"), Some("Apache-2.0".to_owned())),
+        ]);
+        assert!(many.contains("- [some/repo](https://github.com/some/repo)\n"));
+        assert!(many.contains("- [other/repo](https://github.com/other/repo): Apache-2.0\n"));
+    }
+}
@@ -0,0 +1,150 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{fs, path::Path};
+
+use crate::paths::restriction_file;
+
+/// Reddit's error code for a comment in a subreddit that only lets approved
+/// users comment.
+pub const NOT_ALLOWED: &str = "SUBREDDIT_NOTALLOWED";
+
+/// Seconds after a reply was refused before one is tried again in the
+/// subreddit, to find out whether the bot has been approved since.
+pub const PROBE_INTERVAL: u64 = 24 * 60 * 60;
+
+/// A subreddit that refused the bot's replies, as only approved users may
+/// comment there.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Restriction {
+    /// The subreddit's name, in lowercase.
+    pub subreddit: String,
+    /// Unix timestamp of the first refusal.
+    pub since: u64,
+    /// Unix timestamp of when a reply was last refused, or tried again.
+    pub tried_at: u64,
+}
+
+impl Restriction {
+    /// Whether it's been long enough since the last try to try again.
+    pub fn probe_due(&self, now: u64) -> bool {
+        now.saturating_sub(self.tried_at) >= PROBE_INTERVAL
+    }
+}
+
+/// Whether the body of Reddit's answer to a comment says that only approved
+/// users may comment in the subreddit, either as a JSON API error or as the
+/// reason for a refusal.
+pub fn is_not_allowed(body: &Value) -> bool {
+    let errors = body["json"]["errors"].as_array();
+    errors
+        .into_iter()
+        .flatten()
+        .any(|e| e[0].as_str() == Some(NOT_ALLOWED))
+        || body["reason"].as_str() == Some(NOT_ALLOWED)
+}
+
+/// The restriction on a subreddit, if there is one.
+pub fn find<'a>(restrictions: &'a [Restriction], subreddit: &str) -> Option<&'a Restriction> {
+    restrictions
+        .iter()
+        .find(|r| r.subreddit.eq_ignore_ascii_case(subreddit))
+}
+
+/// Record that a subreddit refused a reply at `now`, returning whether it
+/// hadn't before.
+pub fn restrict(restrictions: &mut Vec<Restriction>, subreddit: &str, now: u64) -> bool {
+    let subreddit = subreddit.to_lowercase();
+    match restrictions.iter_mut().find(|r| r.subreddit == subreddit) {
+        Some(restriction) => {
+            restriction.tried_at = now;
+            false
+        }
+        None => {
+            restrictions.push(Restriction {
+                subreddit,
+                since: now,
+                tried_at: now,
+            });
+            true
+        }
+    }
+}
+
+/// Forget the restriction on a subreddit, returning whether there was one.
+pub fn lift(restrictions: &mut Vec<Restriction>, subreddit: &str) -> bool {
+    let before = restrictions.len();
+    restrictions.retain(|r| !r.subreddit.eq_ignore_ascii_case(subreddit));
+    restrictions.len() != before
+}
+
+/// Load the restricted subreddits from the state directory.
+pub fn load(dir: &Path) -> Result<Vec<Restriction>> {
+    let path = restriction_file(dir);
+    if !path.exists() {
+        return Ok(vec![]);
+    }
+    let data = fs::read_to_string(&path).with_context(|| format!("reading {}", path.display()))?;
+    serde_json::from_str(&data).with_context(|| format!("parsing {}", path.display()))
+}
+
+/// Save the restricted subreddits to the state directory, replacing the
+/// file atomically.
+pub fn save(dir: &Path, restrictions: &[Restriction]) -> Result<()> {
+    let path = restriction_file(dir);
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, serde_json::to_string(restrictions)?)
+        .and_then(|_| fs::rename(&tmp, &path))
+        .with_context(|| format!("writing {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{find, is_not_allowed, lift, load, restrict, save, PROBE_INTERVAL};
+    use serde_json::json;
+
+    #[test]
+    fn recognizes_refusals_for_unapproved_users() {
+        assert!(is_not_allowed(&json!({"json": {"errors": [
+            ["SUBREDDIT_NOTALLOWED", "you aren't allowed to post there.", "parent"]
+        ]}})));
+        assert!(is_not_allowed(
+            &json!({"reason": "SUBREDDIT_NOTALLOWED", "message": "Forbidden"})
+        ));
+        assert!(!is_not_allowed(&json!({"json": {"errors": [
+            ["RATELIMIT", "you are doing that too much", "ratelimit"]
+        ]}})));
+        assert!(!is_not_allowed(
+            &json!({"json": {"errors": [], "data": {}}})
+        ));
+        assert!(!is_not_allowed(
+            &json!({"message": "Forbidden", "error": 403})
+        ));
+    }
+
+    #[test]
+    fn restricts_subreddits_until_lifted() {
+        let mut restrictions = vec![];
+        assert!(restrict(&mut restrictions, "Approved", 100));
+        assert!(!restrict(&mut restrictions, "approved", 200));
+        let restriction = find(&restrictions, "APPROVED").unwrap();
+        assert_eq!((restriction.since, restriction.tried_at), (100, 200));
+        assert!(!restriction.probe_due(200 + PROBE_INTERVAL - 1));
+        assert!(restriction.probe_due(200 + PROBE_INTERVAL));
+        assert!(find(&restrictions, "other").is_none());
+
+        assert!(lift(&mut restrictions, "Approved"));
+        assert!(!lift(&mut restrictions, "approved"));
+        assert!(find(&restrictions, "approved").is_none());
+    }
+
+    #[test]
+    fn saves_and_loads() {
+        let tmp = tempfile::tempdir().unwrap();
+        assert!(load(tmp.path()).unwrap().is_empty());
+        let mut restrictions = vec![];
+        restrict(&mut restrictions, "approved", 100);
+        save(tmp.path(), &restrictions).unwrap();
+        assert_eq!(load(tmp.path()).unwrap(), restrictions);
+    }
+}
@@ -0,0 +1,458 @@
+use anyhow::{anyhow, Context, Result};
+use flate2::{read::MultiGzDecoder, write::GzEncoder, Compression};
+use log::{debug, info, warn};
+use serde::Deserialize;
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::{Duration, UNIX_EPOCH},
+};
+
+/// Lines of a findings file read between progress messages.
+const PROGRESS_EVERY: usize = 100_000;
+
+/// What becomes of findings older than the retention period.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Retention {
+    /// They're moved to a gzipped archive for the month they were made in.
+    Archive,
+    /// They're deleted.
+    Drop,
+}
+
+impl FromStr for Retention {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "archive" => Ok(Retention::Archive),
+            "drop" => Ok(Retention::Drop),
+            _ => Err(anyhow!("Findings retention must be 'archive' or 'drop'")),
+        }
+    }
+}
+
+/// The only part of a finding that compacting looks at.
+#[derive(Deserialize)]
+struct Stamp {
+    at: u64,
+}
+
+/// The month of a Unix timestamp, as `YYYY-MM`.
+fn month(at: u64) -> String {
+    let stamp = humantime::format_rfc3339_seconds(UNIX_EPOCH + Duration::from_secs(at));
+    stamp.to_string()[..7].to_owned()
+}
+
+/// The archive of the findings a findings log at `log` had from `month`,
+/// kept next to it.
+pub fn archive_path(log: &Path, month: &str) -> PathBuf {
+    let stem = log.file_stem().unwrap_or_default().to_string_lossy();
+    log.with_file_name(format!("{}-{}.jsonl.gz", stem, month))
+}
+
+/// Where what's kept of a file is written before it replaces the file.
+fn tmp_path(path: &Path) -> PathBuf {
+    let mut name = path.to_owned().into_os_string();
+    name.push(".compacting");
+    name.into()
+}
+
+fn remove_if_present(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// Add findings to an archive, through a temporary file so that a
+/// compaction stopped halfway never leaves half an archive.
+///
+/// Findings already in the archive, from a compaction stopped before the
+/// file they came from was replaced, aren't added again.
+fn add_to_archive(archive: &Path, lines: &[String]) -> Result<()> {
+    let mut existing = String::new();
+    if archive.exists() {
+        MultiGzDecoder::new(File::open(archive)?)
+            .read_to_string(&mut existing)
+            .with_context(|| format!("reading {}", archive.display()))?;
+    }
+    let present: HashSet<&str> = existing.lines().collect();
+    let tmp = tmp_path(archive);
+    let mut encoder = GzEncoder::new(File::create(&tmp)?, Compression::default());
+    encoder.write_all(existing.as_bytes())?;
+    for line in lines
+        .iter()
+        .filter(|l| !present.contains(l.trim_end_matches('\n')))
+    {
+        encoder.write_all(line.as_bytes())?;
+    }
+    encoder.finish()?.sync_all()?;
+    fs::rename(&tmp, archive).with_context(|| format!("writing {}", archive.display()))
+}
+
+/// A compaction of a findings file that has archived or dropped its old
+/// findings, and written the rest to a temporary file that `finish` puts in
+/// its place.
+#[derive(Debug)]
+pub struct Compaction {
+    path: PathBuf,
+    tmp: PathBuf,
+    file: File,
+    /// Bytes of the file that were read.
+    read: u64,
+    /// Findings kept.
+    pub kept: usize,
+    /// Findings archived or dropped.
+    pub aged_out: usize,
+    /// The archives findings were added to.
+    pub archives: Vec<PathBuf>,
+}
+
+impl Compaction {
+    /// Read the findings file at `path`, archiving those made before
+    /// `cutoff` next to the findings log at `log`, or dropping them, and
+    /// writing the rest to a temporary file.
+    ///
+    /// Whatever a compaction stopped halfway left behind is cleared first.
+    /// There's nothing to compact if the file doesn't exist.
+    pub fn start(
+        path: &Path,
+        log: &Path,
+        cutoff: u64,
+        retention: Retention,
+    ) -> Result<Option<Self>> {
+        let tmp = tmp_path(path);
+        remove_if_present(&tmp)?;
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e).with_context(|| format!("reading {}", path.display())),
+        };
+        let size = file.metadata()?.len();
+        info!("Compacting {} ({} bytes)", path.display(), size);
+        let mut reader = BufReader::new(&file);
+        let mut out = BufWriter::new(File::create(&tmp)?);
+        let mut old: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        let (mut read, mut kept, mut lines) = (0, 0, 0);
+        let mut line = String::new();
+        loop {
+            line.clear();
+            let n = reader
+                .read_line(&mut line)
+                .with_context(|| format!("reading {}", path.display()))?;
+            // a line still being written is left to `finish`
+            if n == 0 || !line.ends_with('\n') {
+                break;
+            }
+            read += n as u64;
+            lines += 1;
+            if lines % PROGRESS_EVERY == 0 {
+                info!(
+                    "Compacting {}: read {} of {} bytes",
+                    path.display(),
+                    read,
+                    size
+                );
+            }
+            match serde_json::from_str::<Stamp>(&line) {
+                Ok(stamp) if stamp.at < cutoff => {
+                    old.entry(month(stamp.at)).or_default().push(line.clone())
+                }
+                _ => {
+                    out.write_all(line.as_bytes())?;
+                    kept += 1;
+                }
+            }
+        }
+        out.into_inner()?.sync_all()?;
+        let mut archives = vec![];
+        if retention == Retention::Archive {
+            for (month, lines) in &old {
+                let archive = archive_path(log, month);
+                debug!(
+                    "Archiving {} findings from {} to {}",
+                    lines.len(),
+                    month,
+                    archive.display()
+                );
+                add_to_archive(&archive, lines)?;
+                archives.push(archive);
+            }
+        }
+        Ok(Some(Self {
+            path: path.to_owned(),
+            tmp,
+            file,
+            read,
+            kept,
+            aged_out: old.values().map(Vec::len).sum(),
+            archives,
+        }))
+    }
+
+    /// Replace the file with what was kept of it, along with whatever was
+    /// appended to it since it was read, returning whether it was replaced.
+    ///
+    /// Nothing may be appended to the file while this runs. A file rotated
+    /// since it was read is left for the next compaction.
+    pub fn finish(mut self) -> Result<bool> {
+        if self.aged_out == 0 {
+            fs::remove_file(&self.tmp)?;
+            return Ok(true);
+        }
+        let mut tail = vec![];
+        self.file.seek(SeekFrom::Start(self.read))?;
+        self.file.read_to_end(&mut tail)?;
+        let size = fs::metadata(&self.path).map(|m| m.len()).ok();
+        if size != Some(self.read + tail.len() as u64) {
+            warn!(
+                "{} was rotated while it was compacted, leaving it for the next time",
+                self.path.display()
+            );
+            fs::remove_file(&self.tmp)?;
+            return Ok(false);
+        }
+        let mut out = OpenOptions::new().append(true).open(&self.tmp)?;
+        if !tail.is_empty() {
+            debug!(
+                "Keeping {} bytes added to {} while it was compacted",
+                tail.len(),
+                self.path.display()
+            );
+            out.write_all(&tail)?;
+            self.kept += tail.iter().filter(|&&b| b == b'\n').count();
+        }
+        out.sync_all()?;
+        fs::rename(&self.tmp, &self.path)
+            .with_context(|| format!("writing {}", self.path.display()))?;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{archive_path, month, Compaction, Retention};
+    use crate::events::Event;
+    use crate::findings::FindingsLog;
+    use flate2::read::MultiGzDecoder;
+    use std::{
+        fs::{self, File},
+        io::Read,
+        path::Path,
+    };
+
+    const DAY: u64 = 24 * 60 * 60;
+    /// 2020-09-13
+    const START: u64 = 1_600_000_000;
+
+    fn finding(fullname: &str, at: u64) -> Event {
+        Event {
+            at,
+            fullname: fullname.to_owned(),
+            title: "A project".to_owned(),
+            subreddit: "rust".to_owned(),
+            permalink: "https://www.reddit.com/r/rust/comments/abc/a_project/".to_owned(),
+            author: "someone".to_owned(),
+            repo: "someone/project".to_owned(),
+            verdict: "unlicensed".to_owned(),
+            license: None,
+            action: "replied".to_owned(),
+            removed_license: None,
+            detection_path: vec![],
+        }
+    }
+
+    /// A findings log with a finding every hour for `days` days from `START`.
+    fn store(path: &Path, days: u64) -> usize {
+        let mut data = String::new();
+        let count = (days * 24) as usize;
+        for i in 0..count {
+            let event = finding(&format!("t3_{}", i), START + i as u64 * 60 * 60);
+            data.push_str(&serde_json::to_string(&event).unwrap());
+            data.push('\n');
+        }
+        fs::write(path, data).unwrap();
+        count
+    }
+
+    fn fullnames(data: &str) -> Vec<String> {
+        data.lines()
+            .map(|l| serde_json::from_str::<Event>(l).unwrap().fullname)
+            .collect()
+    }
+
+    fn unzip(path: &Path) -> String {
+        let mut data = String::new();
+        MultiGzDecoder::new(File::open(path).unwrap())
+            .read_to_string(&mut data)
+            .unwrap();
+        data
+    }
+
+    #[test]
+    fn names_archives_by_month() {
+        assert_eq!(month(START), "2020-09");
+        assert_eq!(month(START + 30 * DAY), "2020-10");
+        assert_eq!(
+            archive_path(Path::new("/state/findings.jsonl"), "2020-09"),
+            Path::new("/state/findings-2020-09.jsonl.gz")
+        );
+        assert_eq!(
+            archive_path(Path::new("findings"), "2021-01"),
+            Path::new("findings-2021-01.jsonl.gz")
+        );
+        assert_eq!("archive".parse::<Retention>().unwrap(), Retention::Archive);
+        assert_eq!("drop".parse::<Retention>().unwrap(), Retention::Drop);
+        assert!("keep".parse::<Retention>().is_err());
+    }
+
+    #[test]
+    fn archives_old_findings_by_month() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("findings.jsonl");
+        let count = store(&path, 120);
+        let cutoff = START + 90 * DAY;
+
+        let compaction = Compaction::start(&path, &path, cutoff, Retention::Archive)
+            .unwrap()
+            .unwrap();
+        assert_eq!(compaction.aged_out, 90 * 24);
+        assert_eq!(compaction.kept, 30 * 24);
+        assert!(compaction.finish().unwrap());
+
+        let kept = fullnames(&fs::read_to_string(&path).unwrap());
+        assert_eq!(kept.len(), 30 * 24);
+        assert_eq!(kept[0], format!("t3_{}", 90 * 24));
+        let mut archived = 0;
+        for month in &["2020-09", "2020-10", "2020-11", "2020-12"] {
+            let data = unzip(&tmp.path().join(format!("findings-{}.jsonl.gz", month)));
+            for line in data.lines() {
+                let event: Event = serde_json::from_str(line).unwrap();
+                assert!(event.at < cutoff);
+                assert_eq!(super::month(event.at), *month);
+            }
+            archived += data.lines().count();
+        }
+        assert_eq!(archived + kept.len(), count);
+        assert!(!tmp.path().join("findings.jsonl.compacting").exists());
+    }
+
+    #[test]
+    fn keeps_findings_written_during_compaction() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("findings.jsonl");
+        store(&path, 60);
+        let now = START + 60 * DAY;
+        let log = FindingsLog::new(path.clone(), u64::MAX);
+
+        let compaction = Compaction::start(&path, &path, now - 30 * DAY, Retention::Archive)
+            .unwrap()
+            .unwrap();
+        log.append(&finding("t3_late_a", now)).unwrap();
+        log.append(&finding("t3_late_b", now)).unwrap();
+        assert!(compaction.finish().unwrap());
+        log.append(&finding("t3_later", now)).unwrap();
+
+        let kept = fullnames(&fs::read_to_string(&path).unwrap());
+        assert_eq!(kept.len(), 30 * 24 + 3);
+        assert_eq!(
+            &kept[kept.len() - 3..],
+            ["t3_late_a", "t3_late_b", "t3_later"]
+        );
+    }
+
+    #[test]
+    fn leaves_a_file_rotated_during_compaction() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("findings.jsonl");
+        store(&path, 60);
+        let before = fs::read_to_string(&path).unwrap();
+
+        let compaction = Compaction::start(&path, &path, START + 30 * DAY, Retention::Drop)
+            .unwrap()
+            .unwrap();
+        fs::rename(&path, tmp.path().join("findings.jsonl.1")).unwrap();
+        FindingsLog::new(path.clone(), u64::MAX)
+            .append(&finding("t3_new", START + 60 * DAY))
+            .unwrap();
+        assert!(!compaction.finish().unwrap());
+
+        assert_eq!(fullnames(&fs::read_to_string(&path).unwrap()), ["t3_new"]);
+        assert_eq!(
+            fs::read_to_string(tmp.path().join("findings.jsonl.1")).unwrap(),
+            before
+        );
+        assert!(!tmp.path().join("findings.jsonl.compacting").exists());
+    }
+
+    #[test]
+    fn drops_old_findings_when_told_to() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("findings.jsonl");
+        store(&path, 45);
+
+        let compaction = Compaction::start(&path, &path, START + 40 * DAY, Retention::Drop)
+            .unwrap()
+            .unwrap();
+        assert!(compaction.archives.is_empty());
+        assert!(compaction.finish().unwrap());
+
+        assert_eq!(fullnames(&fs::read_to_string(&path).unwrap()).len(), 5 * 24);
+        let names: Vec<_> = fs::read_dir(tmp.path())
+            .unwrap()
+            .map(|e| e.unwrap().file_name())
+            .collect();
+        assert_eq!(names, ["findings.jsonl"]);
+    }
+
+    #[test]
+    fn picks_up_after_a_stopped_compaction() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("findings.jsonl");
+        fs::write(tmp.path().join("findings.jsonl.compacting"), "half a li").unwrap();
+        store(&path, 20);
+        let cutoff = START + 10 * DAY;
+
+        // stopped after archiving, before the findings log was replaced
+        let stopped = Compaction::start(&path, &path, cutoff, Retention::Archive)
+            .unwrap()
+            .unwrap();
+        drop(stopped);
+        let compaction = Compaction::start(&path, &path, cutoff, Retention::Archive)
+            .unwrap()
+            .unwrap();
+        assert!(compaction.finish().unwrap());
+
+        let archived = fullnames(&unzip(&tmp.path().join("findings-2020-09.jsonl.gz")));
+        assert_eq!(archived.len(), 10 * 24);
+        assert_eq!(archived[0], "t3_0");
+        assert_eq!(
+            fullnames(&fs::read_to_string(&path).unwrap()).len(),
+            10 * 24
+        );
+        assert!(!tmp.path().join("findings.jsonl.compacting").exists());
+    }
+
+    #[test]
+    fn nothing_to_compact() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("findings.jsonl");
+        assert!(Compaction::start(&path, &path, START, Retention::Archive)
+            .unwrap()
+            .is_none());
+
+        store(&path, 2);
+        let before = fs::read_to_string(&path).unwrap();
+        let compaction = Compaction::start(&path, &path, START, Retention::Archive)
+            .unwrap()
+            .unwrap();
+        assert_eq!(compaction.aged_out, 0);
+        assert!(compaction.finish().unwrap());
+        assert_eq!(fs::read_to_string(&path).unwrap(), before);
+        assert_eq!(fs::read_dir(tmp.path()).unwrap().count(), 1);
+    }
+}
@@ -0,0 +1,139 @@
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::PathBuf,
+};
+
+/// A file written a line at a time, rotated by size.
+///
+/// Once the file would grow past `max_bytes` it's moved to `{path}.1`, older
+/// files move up by one, and those past `{path}.{keep}` are deleted. The
+/// file is opened for each write, so if it's deleted from under the process
+/// it's simply started again.
+#[derive(Debug)]
+pub struct RotatingFile {
+    path: PathBuf,
+    max_bytes: u64,
+    keep: usize,
+}
+
+impl RotatingFile {
+    pub fn new(path: PathBuf, max_bytes: u64, keep: usize) -> Self {
+        Self {
+            path,
+            max_bytes,
+            keep,
+        }
+    }
+
+    /// The path of the `n`th newest rotated file.
+    fn numbered(&self, n: usize) -> PathBuf {
+        let mut name = self.path.clone().into_os_string();
+        name.push(format!(".{}", n));
+        name.into()
+    }
+
+    fn rotate(&self) -> io::Result<()> {
+        if self.keep == 0 {
+            return remove_if_present(self.path.clone());
+        }
+        remove_if_present(self.numbered(self.keep))?;
+        for n in (1..self.keep).rev() {
+            let from = self.numbered(n);
+            if from.exists() {
+                fs::rename(from, self.numbered(n + 1))?;
+            }
+        }
+        fs::rename(&self.path, self.numbered(1))
+    }
+
+    /// Append a line to the file, rotating it first if it's full.
+    pub fn write_line(&self, line: &str) -> io::Result<()> {
+        if let Ok(meta) = fs::metadata(&self.path) {
+            if meta.len() > 0 && meta.len() + line.len() as u64 + 1 > self.max_bytes {
+                self.rotate()?;
+            }
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        file.write_all(format!("{}\n", line).as_bytes())
+    }
+}
+
+fn remove_if_present(path: PathBuf) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RotatingFile;
+    use std::{fs, path::Path};
+
+    fn read(path: &Path) -> String {
+        fs::read_to_string(path).unwrap_or_default()
+    }
+
+    #[test]
+    fn rotates_and_keeps_the_newest_files() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("bot.log");
+        // room for two 4-byte lines per file
+        let file = RotatingFile::new(path.clone(), 10, 2);
+        for line in &["one", "two", "thr", "fou", "fiv", "six", "sev"] {
+            file.write_line(line).unwrap();
+        }
+        assert_eq!(read(&path), "sev\n");
+        assert_eq!(read(&tmp.path().join("bot.log.1")), "fiv\nsix\n");
+        assert_eq!(read(&tmp.path().join("bot.log.2")), "thr\nfou\n");
+        assert!(!tmp.path().join("bot.log.3").exists());
+    }
+
+    #[test]
+    fn keeping_none_truncates() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("bot.log");
+        let file = RotatingFile::new(path.clone(), 10, 0);
+        for line in &["one", "two", "thr"] {
+            file.write_line(line).unwrap();
+        }
+        assert_eq!(read(&path), "thr\n");
+        assert_eq!(fs::read_dir(tmp.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn recreates_a_deleted_file() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("bot.log");
+        let file = RotatingFile::new(path.clone(), 1024, 2);
+        file.write_line("before").unwrap();
+        fs::remove_file(&path).unwrap();
+        file.write_line("after").unwrap();
+        assert_eq!(read(&path), "after\n");
+    }
+
+    #[test]
+    fn long_lines_get_a_file_each() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("bot.log");
+        let file = RotatingFile::new(path.clone(), 4, 1);
+        file.write_line("longer than the limit").unwrap();
+        file.write_line("and another").unwrap();
+        assert_eq!(read(&path), "and another\n");
+        assert_eq!(
+            read(&tmp.path().join("bot.log.1")),
+            "longer than the limit\n"
+        );
+    }
+
+    #[test]
+    fn unwritable_path_is_an_error() {
+        let tmp = tempfile::tempdir().unwrap();
+        let file = RotatingFile::new(tmp.path().join("missing/bot.log"), 1024, 2);
+        assert!(file.write_line("line").is_err());
+    }
+}
@@ -0,0 +1,137 @@
+use serde_json::Value;
+
+/// Phrases in a subreddit's rules that mean bots aren't welcome.
+const BOT_BAN_PHRASES: &[&str] = &[
+    "no bots",
+    "no bot accounts",
+    "bot accounts",
+    "bots are not allowed",
+    "bots are banned",
+    "bots will be banned",
+    "no automated accounts",
+    "automated accounts are not allowed",
+];
+
+/// Whether the bot may comment in a subreddit.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Verdict {
+    Allowed,
+    /// The bot should only report what it finds, with the reason why.
+    ReportOnly(String),
+}
+
+/// Find a bot-prohibition phrase in some rule text, if there is one.
+pub fn find_bot_ban(text: &str) -> Option<&'static str> {
+    let normalized = text
+        .to_lowercase()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+    BOT_BAN_PHRASES
+        .iter()
+        .find(|phrase| normalized.contains(*phrase))
+        .copied()
+}
+
+/// Decide whether to comment in a subreddit from its `about` and
+/// `about/rules` responses.
+pub fn assess(about: &Value, rules: &Value) -> Verdict {
+    let about = &about["data"];
+    if about["quarantine"].as_bool() == Some(true) {
+        return Verdict::ReportOnly("subreddit is quarantined".to_owned());
+    }
+    if let Some(kind) = about["subreddit_type"].as_str() {
+        if kind != "public" {
+            return Verdict::ReportOnly(format!("subreddit is {}", kind));
+        }
+    }
+    let rules = rules["rules"].as_array().map(Vec::as_slice).unwrap_or(&[]);
+    for rule in rules {
+        for field in &["short_name", "description", "violation_reason"] {
+            if let Some(phrase) = rule[field].as_str().and_then(find_bot_ban) {
+                return Verdict::ReportOnly(format!("rules mention '{}'", phrase));
+            }
+        }
+    }
+    if let Some(phrase) = about["description"].as_str().and_then(find_bot_ban) {
+        return Verdict::ReportOnly(format!("sidebar mentions '{}'", phrase));
+    }
+    Verdict::Allowed
+}
+
+/// Whether the mods of a subreddit have explicitly invited the bot.
+pub fn is_invited(subreddit: &str, invited: &[String]) -> bool {
+    invited.iter().any(|s| s.eq_ignore_ascii_case(subreddit))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{assess, find_bot_ban, is_invited, Verdict};
+    use serde_json::json;
+
+    #[test]
+    fn bot_ban_phrases() {
+        let cases = vec![
+            ("No bots.", Some("no bots")),
+            ("No   Bots\nallowed", Some("no bots")),
+            ("Bot accounts will be banned", Some("bot accounts")),
+            (
+                "Automated accounts are not allowed here",
+                Some("automated accounts are not allowed"),
+            ),
+            ("Be nice to each other", None),
+            ("Robots and robotics discussion only", None),
+            ("", None),
+        ];
+        for (text, expected) in cases {
+            assert_eq!(find_bot_ban(text), expected, "{}", text);
+        }
+    }
+
+    fn about(kind: &str, quarantine: bool) -> serde_json::Value {
+        json!({"data": {"subreddit_type": kind, "quarantine": quarantine, "description": ""}})
+    }
+
+    #[test]
+    fn public_subreddit_with_friendly_rules_is_allowed() {
+        let rules =
+            json!({"rules": [{"short_name": "Be civil", "description": "No personal attacks"}]});
+        assert_eq!(assess(&about("public", false), &rules), Verdict::Allowed);
+        assert_eq!(
+            assess(&about("public", false), &json!({})),
+            Verdict::Allowed
+        );
+    }
+
+    #[test]
+    fn rule_banning_bots_is_report_only() {
+        let rules = json!({"rules": [
+            {"short_name": "Be civil", "description": ""},
+            {"short_name": "Spam", "description": "No bots or self-promotion"}
+        ]});
+        assert_eq!(
+            assess(&about("public", false), &rules),
+            Verdict::ReportOnly("rules mention 'no bots'".to_owned())
+        );
+    }
+
+    #[test]
+    fn quarantined_and_restricted_are_report_only() {
+        let rules = json!({"rules": []});
+        assert_eq!(
+            assess(&about("public", true), &rules),
+            Verdict::ReportOnly("subreddit is quarantined".to_owned())
+        );
+        assert_eq!(
+            assess(&about("restricted", false), &rules),
+            Verdict::ReportOnly("subreddit is restricted".to_owned())
+        );
+    }
+
+    #[test]
+    fn invited_subreddits_ignore_case() {
+        let invited = vec!["RustJerk".to_owned()];
+        assert!(is_invited("rustjerk", &invited));
+        assert!(!is_invited("rust", &invited));
+    }
+}
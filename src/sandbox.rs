@@ -0,0 +1,107 @@
+use crate::pending::PostDetails;
+
+/// Longest title Reddit accepts for a post, in characters.
+const MAX_TITLE_LEN: usize = 300;
+
+/// Link to a post, from its permalink if it's known or else its fullname.
+fn post_url(fullname: &str, post: &PostDetails) -> String {
+    if post.permalink.is_empty() {
+        format!(
+            "https://www.reddit.com/comments/{}/",
+            fullname.trim_start_matches("t3_")
+        )
+    } else {
+        format!("https://www.reddit.com{}", post.permalink)
+    }
+}
+
+/// Text quoted as a Markdown block quote, line by line.
+fn quote(text: &str) -> String {
+    text.lines()
+        .map(|line| {
+            if line.is_empty() {
+                ">".to_owned()
+            } else {
+                format!("> {}", line)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// The form for `/api/submit` that posts, to the sandbox subreddit, what
+/// would have been commented on a post, in place of commenting there.
+pub fn submission(
+    sandbox: &str,
+    fullname: &str,
+    post: &PostDetails,
+    text: &str,
+) -> Vec<(&'static str, String)> {
+    let title = format!(
+        "Would reply to {} in /r/{}: {}",
+        fullname, post.subreddit, post.title
+    );
+    let body = format!(
+        "Would have commented on {}:\n\n{}",
+        post_url(fullname, post),
+        quote(text)
+    );
+    vec![
+        ("api_type", "json".to_owned()),
+        ("sr", sandbox.to_owned()),
+        ("kind", "self".to_owned()),
+        ("title", title.chars().take(MAX_TITLE_LEN).collect()),
+        ("text", body),
+        ("sendreplies", "false".to_owned()),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{submission, MAX_TITLE_LEN};
+    use crate::pending::PostDetails;
+
+    fn field<'a>(form: &'a [(&str, String)], name: &str) -> &'a str {
+        &form.iter().find(|(n, _)| *n == name).unwrap().1
+    }
+
+    #[test]
+    fn quotes_the_reply_with_where_it_would_go() {
+        let post = PostDetails {
+            title: "My new project".to_owned(),
+            subreddit: "rust".to_owned(),
+            permalink: "/r/rust/comments/abc/my_new_project/".to_owned(),
+            author: "op".to_owned(),
+            flair: String::new(),
+        };
+        let form = submission(
+            "cfl_sandbox",
+            "t3_abc",
+            &post,
+            "No license.\n\n---\n\nfooter",
+        );
+        assert_eq!(field(&form, "sr"), "cfl_sandbox");
+        assert_eq!(field(&form, "kind"), "self");
+        assert_eq!(
+            field(&form, "title"),
+            "Would reply to t3_abc in /r/rust: My new project"
+        );
+        assert_eq!(
+            field(&form, "text"),
+            "Would have commented on https://www.reddit.com/r/rust/comments/abc/my_new_project/:\n\n\
+             > No license.\n>\n> ---\n>\n> footer"
+        );
+    }
+
+    #[test]
+    fn links_posts_without_permalinks_and_cuts_long_titles() {
+        let post = PostDetails {
+            title: "x".repeat(400),
+            ..PostDetails::default()
+        };
+        let form = submission("cfl_sandbox", "t3_abc", &post, "text");
+        assert!(field(&form, "text")
+            .starts_with("Would have commented on https://www.reddit.com/comments/abc/:"));
+        assert_eq!(field(&form, "title").chars().count(), MAX_TITLE_LEN);
+    }
+}
@@ -0,0 +1,127 @@
+use reqwest::Url;
+
+/// Most characters kept of a post's title, which is as long as Reddit
+/// allows them to be.
+pub const TITLE_LEN: usize = 300;
+/// Most characters kept of a name, such as an author's, a subreddit's or a
+/// license's, or a repository's as `owner/name`.
+pub const NAME_LEN: usize = 150;
+/// Most characters kept of other text, such as what was done about a post.
+pub const TEXT_LEN: usize = 500;
+/// Longest URL kept, in bytes.
+pub const URL_LEN: usize = 2000;
+
+/// Characters that change the order text is shown in, which can make what's
+/// shown differ from what's kept.
+fn reorders_text(c: char) -> bool {
+    matches!(c, '\u{200e}' | '\u{200f}' | '\u{202a}'..='\u{202e}' | '\u{2066}'..='\u{2069}')
+}
+
+/// A character as it's kept: line breaks and tabs become spaces, and other
+/// control characters and those that reorder text are dropped.
+fn clean(c: char) -> Option<char> {
+    match c {
+        '\n' | '\r' | '\t' => Some(' '),
+        c if c.is_control() || reorders_text(c) => None,
+        c => Some(c),
+    }
+}
+
+/// Text from Reddit or GitHub made fit to keep and show: cleaned of control
+/// characters and cut to at most `max_chars` characters, the last of them an
+/// ellipsis if it was cut.
+///
+/// Only as much of the text as is kept is looked at, however long it is.
+pub fn text(raw: &str, max_chars: usize) -> String {
+    let kept: Vec<char> = raw.chars().filter_map(clean).take(max_chars + 1).collect();
+    if kept.len() <= max_chars {
+        return kept.into_iter().collect();
+    }
+    let mut cut: String = kept[..max_chars.saturating_sub(1)].iter().collect();
+    if max_chars > 0 {
+        cut.push('…');
+    }
+    cut
+}
+
+/// A URL from Reddit or GitHub if it's fit to keep: an `http` or `https` URL
+/// of at most `URL_LEN` bytes, with no whitespace or control characters.
+pub fn url(raw: &str) -> Option<String> {
+    if raw.len() > URL_LEN
+        || raw
+            .chars()
+            .any(|c| c.is_whitespace() || clean(c) != Some(c))
+    {
+        return None;
+    }
+    let parsed = Url::parse(raw).ok()?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return None;
+    }
+    Some(raw.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{text, url, URL_LEN};
+
+    #[test]
+    fn drops_control_characters() {
+        assert_eq!(
+            text("A\u{0}project\u{7}\u{1b}[31m that\r\nworks\tfine", 100),
+            "Aproject[31m that  works fine"
+        );
+        // reordering text could hide what's really there
+        assert_eq!(text("evil\u{202e}txt.exe\u{2066}", 100), "eviltxt.exe");
+        assert_eq!(text("\u{85}\u{9f}", 100), "");
+    }
+
+    #[test]
+    fn cuts_oversized_text() {
+        let huge = "a".repeat(5_000_000);
+        let cut = text(&huge, 300);
+        assert_eq!(cut.chars().count(), 300);
+        assert!(cut.ends_with("a…"));
+        assert_eq!(text("exactly", 7), "exactly");
+        assert_eq!(text("too long", 7), "too lo…");
+        assert_eq!(text("anything", 1), "…");
+        assert_eq!(text("anything", 0), "");
+        // dropped characters don't count
+        assert_eq!(text("\u{0}\u{0}\u{0}short", 5), "short");
+    }
+
+    #[test]
+    fn keeps_mixed_scripts_whole() {
+        let title = "Rust 🦀 — ржавчина, 錆, صدأ, ζάγκρα";
+        assert_eq!(text(title, 100), title);
+        // cut by characters, never within one
+        assert_eq!(text(title, 9), "Rust 🦀 —…");
+        assert_eq!(text("錆錆錆錆", 3), "錆錆…");
+        assert_eq!(text("e\u{301}te\u{301}", 100), "e\u{301}te\u{301}");
+    }
+
+    #[test]
+    fn validates_urls() {
+        for ok in &[
+            "https://github.com/someone/project",
+            "http://example.com/a?b=c#d",
+            "https://www.reddit.com/r/rust/comments/abc/ünïcode/",
+        ] {
+            assert_eq!(url(ok).as_deref(), Some(*ok));
+        }
+        for bad in &[
+            "",
+            "not a url",
+            "javascript:alert(1)",
+            "data:text/html,<script>alert(1)</script>",
+            "https://github.com/some one",
+            "https://github.com/\u{0}someone",
+            "https://github.com/\u{202e}someone",
+            "ftp://example.com/file",
+        ] {
+            assert_eq!(url(bad), None, "{:?}", bad);
+        }
+        let long = format!("https://github.com/{}", "a".repeat(URL_LEN));
+        assert_eq!(url(&long), None);
+    }
+}
@@ -0,0 +1,648 @@
+use anyhow::{anyhow, Context, Result};
+use std::{env, fmt, fs, path::PathBuf, str::FromStr};
+
+const DAY: i64 = 24 * 60 * 60;
+
+/// Daily window, in local time, during which the bot may reply.
+///
+/// Parsed from `HH:MM-HH:MM`. The start is inclusive and the end exclusive;
+/// a window whose end is before its start wraps past midnight.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ActiveHours {
+    /// Minutes after local midnight.
+    start: u32,
+    end: u32,
+}
+
+fn parse_clock(s: &str) -> Option<u32> {
+    let (h, m) = s.trim().split_once(':')?;
+    let (h, m): (u32, u32) = (h.parse().ok()?, m.parse().ok()?);
+    if h > 24 || m > 59 || (h == 24 && m != 0) {
+        return None;
+    }
+    Some(h * 60 + m)
+}
+
+impl FromStr for ActiveHours {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (start, end) = s
+            .split_once('-')
+            .and_then(|(a, b)| Some((parse_clock(a)?, parse_clock(b)?)))
+            .ok_or_else(|| anyhow!("Expected a window like 08:00-22:00, got '{}'", s))?;
+        if start == end {
+            return Err(anyhow!("Window '{}' starts when it ends", s));
+        }
+        Ok(Self { start, end })
+    }
+}
+
+impl fmt::Display for ActiveHours {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:02}:{:02}-{:02}:{:02}",
+            self.start / 60,
+            self.start % 60,
+            self.end / 60,
+            self.end % 60
+        )
+    }
+}
+
+impl ActiveHours {
+    /// Whether the window is open at the given Unix timestamp in a time zone.
+    pub fn contains(&self, zone: &TimeZone, at: i64) -> bool {
+        let minute = ((at + i64::from(zone.offset_at(at))).rem_euclid(DAY) / 60) as u32;
+        if self.start < self.end {
+            self.start <= minute && minute < self.end
+        } else {
+            minute >= self.start || minute < self.end
+        }
+    }
+}
+
+const WEEKDAYS: [&str; 7] = [
+    "sunday",
+    "monday",
+    "tuesday",
+    "wednesday",
+    "thursday",
+    "friday",
+    "saturday",
+];
+
+/// A time once a week, in local time, such as when to post a digest.
+///
+/// Parsed from a weekday and a time, like `Mon 09:00`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WeeklyTime {
+    /// Day of the week, 0 being Sunday.
+    weekday: u32,
+    /// Minutes after local midnight.
+    minute: u32,
+}
+
+impl FromStr for WeeklyTime {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let parsed = s.trim().split_once(' ').and_then(|(day, time)| {
+            let day = day.to_lowercase();
+            let weekday = WEEKDAYS
+                .iter()
+                .position(|d| day.len() >= 3 && d.starts_with(&day))?;
+            let minute = parse_clock(time).filter(|&m| m < 24 * 60)?;
+            Some(Self {
+                weekday: weekday as u32,
+                minute,
+            })
+        });
+        parsed.ok_or_else(|| anyhow!("Expected a weekday and time like Mon 09:00, got '{}'", s))
+    }
+}
+
+impl fmt::Display for WeeklyTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let day = WEEKDAYS[self.weekday as usize];
+        write!(
+            f,
+            "{}{} {:02}:{:02}",
+            day[..1].to_uppercase(),
+            &day[1..3],
+            self.minute / 60,
+            self.minute % 60
+        )
+    }
+}
+
+impl WeeklyTime {
+    /// The Unix timestamp of the latest occurrence at or before `at`, in a time zone.
+    pub fn last_before(&self, zone: &TimeZone, at: i64) -> i64 {
+        let offset = i64::from(zone.offset_at(at));
+        let local = at + offset;
+        let today = local.div_euclid(DAY);
+        let weekday = (today + 4).rem_euclid(7);
+        let days_back = (weekday - i64::from(self.weekday)).rem_euclid(7);
+        let mut occurrence = (today - days_back) * DAY + i64::from(self.minute) * 60;
+        if occurrence > local {
+            occurrence -= 7 * DAY;
+        }
+        // the offset may differ at the occurrence, across a daylight saving change
+        occurrence - i64::from(zone.offset_at(occurrence - offset))
+    }
+}
+
+/// Days since the Unix epoch of a date in the proleptic Gregorian calendar.
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let month = i64::from(month);
+    let doy = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + i64::from(day) - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// Year of a day counted from the Unix epoch.
+fn year_of(days: i64) -> i64 {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    yoe + era * 400 + i64::from(mp >= 10)
+}
+
+/// A `Mm.w.d/time` transition date from a POSIX TZ string.
+#[derive(Clone, Debug, PartialEq)]
+struct RuleDate {
+    month: u32,
+    /// Week of the month, 1 to 5, where 5 means the last.
+    week: u32,
+    /// Day of the week, 0 being Sunday.
+    weekday: u32,
+    /// Seconds after local midnight.
+    time: i64,
+}
+
+impl RuleDate {
+    /// Local seconds since the epoch at which the transition happens in a year.
+    fn local_time(&self, year: i64) -> i64 {
+        let first = days_from_civil(year, self.month, 1);
+        let next = if self.month == 12 {
+            days_from_civil(year + 1, 1, 1)
+        } else {
+            days_from_civil(year, self.month + 1, 1)
+        };
+        let first_weekday = (first + 4).rem_euclid(7);
+        let mut day = first
+            + (i64::from(self.weekday) - first_weekday).rem_euclid(7)
+            + 7 * i64::from(self.week - 1);
+        while day >= next {
+            day -= 7;
+        }
+        day * DAY + self.time
+    }
+}
+
+/// Daylight saving rule from the footer of a TZif file.
+#[derive(Clone, Debug, PartialEq)]
+struct Rule {
+    /// Standard and daylight offsets, in seconds east of UTC.
+    std: i32,
+    dst: i32,
+    start: RuleDate,
+    end: RuleDate,
+}
+
+impl Rule {
+    fn offset_at(&self, at: i64) -> i32 {
+        let year = year_of((at + i64::from(self.std)).div_euclid(DAY));
+        let start = self.start.local_time(year) - i64::from(self.std);
+        let end = self.end.local_time(year) - i64::from(self.dst);
+        let dst = if start < end {
+            start <= at && at < end
+        } else {
+            !(end <= at && at < start)
+        };
+        if dst {
+            self.dst
+        } else {
+            self.std
+        }
+    }
+}
+
+/// Parse a leading `[+-]hh[:mm[:ss]]`, returning seconds and the rest.
+fn parse_hms(s: &str) -> Option<(i64, &str)> {
+    let (sign, s) = match s.as_bytes().first()? {
+        b'-' => (-1, &s[1..]),
+        b'+' => (1, &s[1..]),
+        _ => (1, s),
+    };
+    let end = s
+        .find(|c: char| !c.is_ascii_digit() && c != ':')
+        .unwrap_or(s.len());
+    let mut secs = 0;
+    for (i, part) in s[..end].split(':').enumerate() {
+        if i > 2 {
+            return None;
+        }
+        secs += part.parse::<i64>().ok()? * [3600, 60, 1][i];
+    }
+    Some((sign * secs, &s[end..]))
+}
+
+/// Parse a leading zone abbreviation, such as `EST` or `<+03>`.
+fn parse_abbreviation(s: &str) -> Option<&str> {
+    if let Some(rest) = s.strip_prefix('<') {
+        return Some(&rest[rest.find('>')? + 1..]);
+    }
+    let end = s
+        .find(|c: char| !c.is_ascii_alphabetic())
+        .unwrap_or(s.len());
+    if end < 3 {
+        return None;
+    }
+    Some(&s[end..])
+}
+
+fn parse_rule_date(s: &str) -> Option<RuleDate> {
+    let (date, time) = match s.split_once('/') {
+        Some((date, time)) => (date, parse_hms(time)?.0),
+        None => (s, 2 * 60 * 60),
+    };
+    let mut parts = date.strip_prefix('M')?.split('.');
+    let mut next = || parts.next()?.parse::<u32>().ok();
+    let (month, week, weekday) = (next()?, next()?, next()?);
+    if !(1..=12).contains(&month) || !(1..=5).contains(&week) || weekday > 6 {
+        return None;
+    }
+    Some(RuleDate {
+        month,
+        week,
+        weekday,
+        time,
+    })
+}
+
+/// The rule of a POSIX TZ string, such as `EST5EDT,M3.2.0,M11.1.0`.
+///
+/// Returns the standard offset alone for zones without daylight saving, and
+/// `None` for anything that can't be understood.
+fn parse_posix(s: &str) -> Option<(i32, Option<Rule>)> {
+    let rest = parse_abbreviation(s)?;
+    let (offset, rest) = parse_hms(rest)?;
+    // POSIX offsets are west of UTC
+    let std = -offset as i32;
+    if rest.is_empty() {
+        return Some((std, None));
+    }
+    let rest = parse_abbreviation(rest)?;
+    let (dst, rest) = match rest.as_bytes().first() {
+        Some(b',') | None => (std + 60 * 60, rest),
+        _ => {
+            let (offset, rest) = parse_hms(rest)?;
+            (-offset as i32, rest)
+        }
+    };
+    let mut dates = rest.strip_prefix(',')?.split(',');
+    let start = parse_rule_date(dates.next()?)?;
+    let end = parse_rule_date(dates.next()?)?;
+    Some((
+        std,
+        Some(Rule {
+            std,
+            dst,
+            start,
+            end,
+        }),
+    ))
+}
+
+/// A time zone from the IANA database, enough to find its UTC offset.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TimeZone {
+    name: String,
+    /// Offset before the first transition.
+    initial: i32,
+    /// Times at which the offset changed, with the offset from then on.
+    transitions: Vec<(i64, i32)>,
+    /// Offset after the last transition, with its daylight saving rule.
+    footer: Option<(i32, Option<Rule>)>,
+}
+
+impl fmt::Display for TimeZone {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+fn be_u32(data: &[u8], at: usize) -> Option<u32> {
+    let bytes = data.get(at..at + 4)?;
+    Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn be_i64(data: &[u8], at: usize) -> Option<i64> {
+    let bytes = data.get(at..at + 8)?;
+    let mut buf = [0; 8];
+    buf.copy_from_slice(bytes);
+    Some(i64::from_be_bytes(buf))
+}
+
+impl TimeZone {
+    pub fn utc() -> Self {
+        Self {
+            name: "UTC".to_owned(),
+            initial: 0,
+            transitions: vec![],
+            footer: None,
+        }
+    }
+
+    /// Load a zone such as `Europe/Berlin` from the system's zoneinfo files.
+    ///
+    /// The files are looked for in `TZDIR`, defaulting to `/usr/share/zoneinfo`.
+    pub fn load(name: &str) -> Result<Self> {
+        if name == "UTC" {
+            return Ok(Self::utc());
+        }
+        if name.is_empty() || name.starts_with('/') || name.split('/').any(|p| p == "..") {
+            return Err(anyhow!("'{}' is not a time zone name", name));
+        }
+        let dir = env::var("TZDIR").unwrap_or_else(|_| "/usr/share/zoneinfo".to_owned());
+        let path = PathBuf::from(dir).join(name);
+        let data = fs::read(&path)
+            .with_context(|| format!("loading time zone {} from {}", name, path.display()))?;
+        Self::parse(name, &data).ok_or_else(|| anyhow!("{} is not a TZif file", path.display()))
+    }
+
+    /// Parse the contents of a TZif file.
+    fn parse(name: &str, data: &[u8]) -> Option<Self> {
+        if data.get(..4)? != b"TZif" {
+            return None;
+        }
+        let header = |at: usize| -> Option<[usize; 6]> {
+            let mut counts = [0; 6];
+            for (i, count) in counts.iter_mut().enumerate() {
+                *count = be_u32(data, at + 20 + 4 * i)? as usize;
+            }
+            Some(counts)
+        };
+        let [isut, isstd, leap, time, typ, chars] = header(0)?;
+        let v1_len = time * 5 + typ * 6 + chars + leap * 8 + isstd + isut;
+        // version 2 and up repeat the data with 64-bit times, then a footer
+        let (at, time_len, counts) = if *data.get(4)? >= b'2' {
+            (44 + v1_len, 8, header(44 + v1_len)?)
+        } else {
+            (0, 4, [isut, isstd, leap, time, typ, chars])
+        };
+        let [isut, isstd, leap, time, typ, chars] = counts;
+        let times_at = at + 44;
+        let indices_at = times_at + time * time_len;
+        let types_at = indices_at + time;
+        let offset_of =
+            |index: usize| -> Option<i32> { Some(be_u32(data, types_at + index * 6)? as i32) };
+        let mut transitions = Vec::with_capacity(time);
+        for i in 0..time {
+            let when = if time_len == 8 {
+                be_i64(data, times_at + i * 8)?
+            } else {
+                i64::from(be_u32(data, times_at + i * 4)? as i32)
+            };
+            transitions.push((when, offset_of(*data.get(indices_at + i)? as usize)?));
+        }
+        let footer = if time_len == 8 {
+            let footer_at = types_at + typ * 6 + chars + leap * 12 + isstd + isut;
+            let footer = std::str::from_utf8(data.get(footer_at..)?).ok()?;
+            parse_posix(footer.trim_matches('\n'))
+        } else {
+            None
+        };
+        Some(Self {
+            name: name.to_owned(),
+            initial: offset_of(0)?,
+            transitions,
+            footer,
+        })
+    }
+
+    /// Offset from UTC, in seconds east, at a Unix timestamp.
+    pub fn offset_at(&self, at: i64) -> i32 {
+        let last = match self.transitions.iter().rposition(|(when, _)| *when <= at) {
+            Some(i) => i,
+            None => return self.initial,
+        };
+        if last == self.transitions.len() - 1 {
+            match &self.footer {
+                Some((_, Some(rule))) => return rule.offset_at(at),
+                Some((std, None)) => return *std,
+                None => {}
+            }
+        }
+        self.transitions[last].1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{days_from_civil, parse_posix, year_of, ActiveHours, TimeZone, WeeklyTime, DAY};
+
+    /// Unix timestamp of a UTC date and time.
+    fn utc(year: i64, month: u32, day: u32, hour: i64, minute: i64) -> i64 {
+        days_from_civil(year, month, day) * DAY + hour * 3600 + minute * 60
+    }
+
+    fn new_york() -> TimeZone {
+        TimeZone {
+            name: "America/New_York".to_owned(),
+            initial: -5 * 3600,
+            transitions: vec![(0, -5 * 3600)],
+            footer: parse_posix("EST5EDT,M3.2.0,M11.1.0"),
+        }
+    }
+
+    fn hours(s: &str) -> ActiveHours {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn civil_dates() {
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        assert_eq!(utc(2020, 9, 13, 12, 26), 1_599_999_960);
+        assert_eq!(
+            days_from_civil(2000, 3, 1) - days_from_civil(2000, 2, 28),
+            2
+        );
+        assert_eq!(year_of(days_from_civil(2021, 12, 31)), 2021);
+        assert_eq!(year_of(days_from_civil(2022, 1, 1)), 2022);
+        assert_eq!(year_of(-1), 1969);
+    }
+
+    #[test]
+    fn parses_windows() {
+        assert_eq!(hours("08:00-22:00").to_string(), "08:00-22:00");
+        assert_eq!(hours(" 22:30 - 6:05 ").to_string(), "22:30-06:05");
+        assert_eq!(hours("00:00-24:00").to_string(), "00:00-24:00");
+        for bad in &[
+            "",
+            "08:00",
+            "8-22",
+            "08:00-25:00",
+            "08:61-09:00",
+            "10:00-10:00",
+        ] {
+            assert!(bad.parse::<ActiveHours>().is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn parses_weekly_times() {
+        let weekly = |s: &str| s.parse::<WeeklyTime>().unwrap().to_string();
+        assert_eq!(weekly("Mon 09:00"), "Mon 09:00");
+        assert_eq!(weekly("sunday 18:30"), "Sun 18:30");
+        assert_eq!(weekly(" FRI 7:05 "), "Fri 07:05");
+        for bad in &[
+            "",
+            "Mon",
+            "09:00",
+            "Moon 09:00",
+            "Mo 09:00",
+            "Mon 24:00",
+            "Mon 9",
+        ] {
+            assert!(bad.parse::<WeeklyTime>().is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn last_weekly_occurrence() {
+        let monday_nine: WeeklyTime = "Mon 09:00".parse().unwrap();
+        let zone = TimeZone::utc();
+        // 2021-06-07 was a Monday
+        let due = utc(2021, 6, 7, 9, 0);
+        assert_eq!(monday_nine.last_before(&zone, due), due);
+        assert_eq!(monday_nine.last_before(&zone, due + 1), due);
+        assert_eq!(monday_nine.last_before(&zone, due - 1), due - 7 * DAY);
+        assert_eq!(monday_nine.last_before(&zone, utc(2021, 6, 12, 23, 0)), due);
+        // 09:00 in New York is 13:00 UTC in the summer
+        let new_york = new_york();
+        assert_eq!(
+            monday_nine.last_before(&new_york, utc(2021, 6, 7, 14, 0)),
+            utc(2021, 6, 7, 13, 0)
+        );
+        assert_eq!(
+            monday_nine.last_before(&new_york, utc(2021, 6, 7, 12, 0)),
+            utc(2021, 5, 31, 13, 0)
+        );
+    }
+
+    #[test]
+    fn window_edges_in_utc() {
+        let zone = TimeZone::utc();
+        let window = hours("08:00-22:00");
+        assert!(!window.contains(&zone, utc(2021, 6, 1, 7, 59)));
+        assert!(window.contains(&zone, utc(2021, 6, 1, 8, 0)));
+        assert!(window.contains(&zone, utc(2021, 6, 1, 21, 59)));
+        assert!(!window.contains(&zone, utc(2021, 6, 1, 22, 0)));
+    }
+
+    #[test]
+    fn window_wrapping_midnight() {
+        let zone = TimeZone::utc();
+        let window = hours("22:00-06:00");
+        assert!(window.contains(&zone, utc(2021, 6, 1, 23, 0)));
+        assert!(window.contains(&zone, utc(2021, 6, 2, 0, 0)));
+        assert!(window.contains(&zone, utc(2021, 6, 2, 5, 59)));
+        assert!(!window.contains(&zone, utc(2021, 6, 2, 6, 0)));
+        assert!(!window.contains(&zone, utc(2021, 6, 2, 12, 0)));
+        assert!(!window.contains(&zone, utc(2021, 6, 2, 21, 59)));
+    }
+
+    #[test]
+    fn offsets_follow_daylight_saving() {
+        let zone = new_york();
+        // 2021: EDT from 2021-03-14 07:00 UTC until 2021-11-07 06:00 UTC
+        assert_eq!(zone.offset_at(utc(2021, 1, 15, 12, 0)), -5 * 3600);
+        assert_eq!(zone.offset_at(utc(2021, 3, 14, 6, 59)), -5 * 3600);
+        assert_eq!(zone.offset_at(utc(2021, 3, 14, 7, 0)), -4 * 3600);
+        assert_eq!(zone.offset_at(utc(2021, 11, 7, 5, 59)), -4 * 3600);
+        assert_eq!(zone.offset_at(utc(2021, 11, 7, 6, 0)), -5 * 3600);
+        assert_eq!(zone.offset_at(utc(2021, 12, 31, 23, 0)), -5 * 3600);
+    }
+
+    #[test]
+    fn window_across_daylight_saving_changes() {
+        let zone = new_york();
+        let window = hours("08:00-22:00");
+        // 12:30 UTC is 07:30 EST the day before the change, 08:30 EDT after it
+        assert!(!window.contains(&zone, utc(2021, 3, 13, 12, 30)));
+        assert!(window.contains(&zone, utc(2021, 3, 14, 12, 30)));
+        // and the other way around in the autumn
+        assert!(window.contains(&zone, utc(2021, 11, 6, 12, 30)));
+        assert!(!window.contains(&zone, utc(2021, 11, 7, 12, 30)));
+        // 02:30 local doesn't exist on the spring change; 01:59 EST is followed by 03:00 EDT
+        let night = hours("02:00-03:00");
+        assert!(!night.contains(&zone, utc(2021, 3, 14, 6, 59)));
+        assert!(!night.contains(&zone, utc(2021, 3, 14, 7, 0)));
+        // and 01:30 happens twice in the autumn
+        let early = hours("01:00-02:00");
+        assert!(early.contains(&zone, utc(2021, 11, 7, 5, 30)));
+        assert!(early.contains(&zone, utc(2021, 11, 7, 6, 30)));
+    }
+
+    #[test]
+    fn southern_hemisphere_rule() {
+        // Australia/Sydney: AEDT from the first Sunday of October to the first of April
+        let (_, rule) = parse_posix("AEST-10AEDT,M10.1.0,M4.1.0/3").unwrap();
+        let rule = rule.unwrap();
+        assert_eq!(rule.offset_at(utc(2021, 1, 15, 0, 0)), 11 * 3600);
+        assert_eq!(rule.offset_at(utc(2021, 4, 3, 15, 59)), 11 * 3600);
+        assert_eq!(rule.offset_at(utc(2021, 4, 3, 16, 0)), 10 * 3600);
+        assert_eq!(rule.offset_at(utc(2021, 7, 1, 0, 0)), 10 * 3600);
+        assert_eq!(rule.offset_at(utc(2021, 10, 2, 16, 0)), 11 * 3600);
+    }
+
+    #[test]
+    fn posix_strings() {
+        assert_eq!(parse_posix("UTC0"), Some((0, None)));
+        assert_eq!(parse_posix("<+0530>-5:30"), Some((19800, None)));
+        let (std, rule) = parse_posix("CET-1CEST,M3.5.0,M10.5.0/3").unwrap();
+        assert_eq!(std, 3600);
+        assert_eq!(rule.unwrap().dst, 7200);
+        assert_eq!(parse_posix("EST5EDT,J60,J300"), None);
+        assert_eq!(parse_posix(""), None);
+    }
+
+    /// A version 2 TZif file with one transition and a footer.
+    fn tzif() -> Vec<u8> {
+        fn header(version: u8, types: u32, times: u32, chars: u32) -> Vec<u8> {
+            let mut data = b"TZif".to_vec();
+            data.push(version);
+            data.extend_from_slice(&[0; 15]);
+            for count in &[0, 0, 0, times, types, chars] {
+                data.extend_from_slice(&count.to_be_bytes());
+            }
+            data
+        }
+        let types = [(-5 * 3600i32, 0u8, 0u8), (-4 * 3600, 1, 4)];
+        let mut data = header(b'2', 2, 0, 8);
+        for (offset, dst, abbr) in &types {
+            data.extend_from_slice(&offset.to_be_bytes());
+            data.extend_from_slice(&[*dst, *abbr]);
+        }
+        data.extend_from_slice(b"EST\0EDT\0");
+        data.extend(header(b'2', 2, 1, 8));
+        data.extend_from_slice(&1_000_000_000i64.to_be_bytes());
+        data.push(1);
+        for (offset, dst, abbr) in &types {
+            data.extend_from_slice(&offset.to_be_bytes());
+            data.extend_from_slice(&[*dst, *abbr]);
+        }
+        data.extend_from_slice(b"EST\0EDT\0");
+        data.extend_from_slice(b"\nEST5EDT,M3.2.0,M11.1.0\n");
+        data
+    }
+
+    #[test]
+    fn parses_tzif() {
+        let zone = TimeZone::parse("Test/Zone", &tzif()).unwrap();
+        assert_eq!(zone.to_string(), "Test/Zone");
+        assert_eq!(zone.offset_at(0), -5 * 3600);
+        assert_eq!(zone.offset_at(1_000_000_000), -4 * 3600);
+        assert_eq!(zone.offset_at(utc(2021, 1, 15, 12, 0)), -5 * 3600);
+        assert_eq!(zone.offset_at(utc(2021, 7, 15, 12, 0)), -4 * 3600);
+        assert!(TimeZone::parse("Test/Zone", b"nope").is_none());
+    }
+
+    #[test]
+    fn rejects_bad_zone_names() {
+        assert_eq!(TimeZone::load("UTC").unwrap(), TimeZone::utc());
+        assert!(TimeZone::load("../etc/passwd").is_err());
+        assert!(TimeZone::load("/etc/passwd").is_err());
+        assert!(TimeZone::load("Not/A_Zone").is_err());
+    }
+}
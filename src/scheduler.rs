@@ -0,0 +1,373 @@
+use anyhow::Result;
+use futures::{
+    future::{BoxFuture, FutureExt},
+    stream::{FuturesUnordered, StreamExt},
+};
+use log::{debug, warn};
+use serde::Serialize;
+use std::{
+    collections::{hash_map::RandomState, BTreeMap},
+    fmt,
+    future::Future,
+    hash::{BuildHasher, Hasher},
+    panic::AssertUnwindSafe,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio::time::{sleep_until, Instant};
+
+use crate::bot::panic_message;
+
+/// What's known about how a scheduled task has been running.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct TaskStatus {
+    pub interval_secs: u64,
+    pub runs: u64,
+    pub failures: u64,
+    /// Times the task was due while its last run hadn't finished, and so
+    /// wasn't run.
+    pub skipped: u64,
+    pub running: bool,
+    /// Unix timestamp of when the last run finished.
+    pub last_run: Option<u64>,
+    pub last_duration_ms: Option<u64>,
+    /// Why the last run failed, if it did.
+    pub last_error: Option<String>,
+}
+
+impl fmt::Display for TaskStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} runs, {} failed, {} skipped",
+            self.runs, self.failures, self.skipped
+        )?;
+        if let Some(e) = &self.last_error {
+            write!(f, ", last failed with: {}", e)?;
+        }
+        Ok(())
+    }
+}
+
+/// Statuses of scheduled tasks by name, shared with whatever reports them.
+pub type SharedStatuses = Arc<Mutex<BTreeMap<String, TaskStatus>>>;
+
+type TaskFn<C> = Arc<dyn Fn(Arc<C>) -> BoxFuture<'static, Result<()>> + Send + Sync>;
+
+struct Task<C> {
+    name: String,
+    interval: Duration,
+    jitter: Duration,
+    run: TaskFn<C>,
+}
+
+/// Runs named tasks every so often, each given the same context.
+///
+/// A task that fails or panics is logged and run again when it's next due,
+/// without holding up the others. A task still running when it's due again
+/// isn't run a second time alongside.
+pub struct Scheduler<C> {
+    context: Arc<C>,
+    tasks: Vec<Task<C>>,
+    statuses: SharedStatuses,
+}
+
+/// A random wait of up to `max`, to keep tasks from running in lockstep.
+fn jitter(max: Duration) -> Duration {
+    if max.is_zero() {
+        return max;
+    }
+    // a fresh `RandomState` is keyed differently each time
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(0);
+    let nanos = max.as_nanos().min(u64::MAX as u128) as u64;
+    Duration::from_nanos(hasher.finish() % (nanos + 1))
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+impl<C: Send + Sync + 'static> Scheduler<C> {
+    /// A scheduler with no tasks, keeping their statuses in `statuses`.
+    pub fn new(context: Arc<C>, statuses: SharedStatuses) -> Self {
+        Self {
+            context,
+            tasks: vec![],
+            statuses,
+        }
+    }
+
+    /// Run a task as soon as the scheduler starts, and then every
+    /// `interval` after that, plus a random wait of up to `jitter` each time.
+    pub fn every<F>(mut self, name: &str, interval: Duration, jitter: Duration, run: F) -> Self
+    where
+        F: Fn(Arc<C>) -> BoxFuture<'static, Result<()>> + Send + Sync + 'static,
+    {
+        self.statuses.lock().unwrap().insert(
+            name.to_owned(),
+            TaskStatus {
+                interval_secs: interval.as_secs(),
+                ..TaskStatus::default()
+            },
+        );
+        self.tasks.push(Task {
+            name: name.to_owned(),
+            interval,
+            jitter,
+            run: Arc::new(run),
+        });
+        self
+    }
+
+    fn status(&self, i: usize, change: impl FnOnce(&mut TaskStatus)) {
+        let mut statuses = self.statuses.lock().unwrap();
+        change(statuses.entry(self.tasks[i].name.clone()).or_default());
+    }
+
+    /// Run the tasks until `stop` finishes, then wait for the runs under way.
+    pub async fn run(self, stop: impl Future<Output = ()>) {
+        let start = Instant::now();
+        let mut due: Vec<Instant> = self
+            .tasks
+            .iter()
+            .map(|t| start + jitter(t.jitter))
+            .collect();
+        let mut running = FuturesUnordered::new();
+        tokio::pin!(stop);
+        while let Some(next) = due.iter().min().copied() {
+            tokio::select! {
+                _ = &mut stop => break,
+                Some((i, result, took)) = running.next() => self.finish(i, result, took),
+                _ = sleep_until(next) => {
+                    let now = Instant::now();
+                    for (i, task) in self.tasks.iter().enumerate() {
+                        if due[i] > now {
+                            continue;
+                        }
+                        due[i] = now + task.interval + jitter(task.jitter);
+                        let mut busy = false;
+                        self.status(i, |s| {
+                            busy = s.running;
+                            if busy {
+                                s.skipped += 1;
+                            } else {
+                                s.running = true;
+                            }
+                        });
+                        if busy {
+                            debug!("{} is still running, not running it again yet", task.name);
+                            continue;
+                        }
+                        let run = (task.run)(self.context.clone());
+                        running.push(async move {
+                            let started = Instant::now();
+                            let result = AssertUnwindSafe(run).catch_unwind().await;
+                            (i, result, started.elapsed())
+                        });
+                    }
+                }
+            }
+        }
+        while let Some((i, result, took)) = running.next().await {
+            self.finish(i, result, took);
+        }
+    }
+
+    fn finish(&self, i: usize, result: std::thread::Result<Result<()>>, took: Duration) {
+        let error = match result {
+            Ok(Ok(())) => None,
+            Ok(Err(e)) => Some(format!("{:#}", e)),
+            Err(payload) => Some(format!("panicked: {}", panic_message(&*payload))),
+        };
+        if let Some(e) = &error {
+            warn!("Scheduled task {} failed: {}", self.tasks[i].name, e);
+        }
+        self.status(i, |s| {
+            s.running = false;
+            s.runs += 1;
+            s.failures += u64::from(error.is_some());
+            s.last_run = Some(unix_now());
+            s.last_duration_ms = Some(took.as_millis() as u64);
+            s.last_error = error;
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{jitter, Scheduler, SharedStatuses};
+    use anyhow::anyhow;
+    use futures::FutureExt;
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc, Mutex,
+        },
+        time::Duration,
+    };
+    use tokio::time::{sleep, Instant};
+
+    const SECOND: Duration = Duration::from_secs(1);
+
+    /// Times at which each run of a task started, counting from `start`.
+    #[derive(Default)]
+    struct Runs {
+        started: Mutex<Vec<Duration>>,
+        in_flight: AtomicUsize,
+        most_in_flight: AtomicUsize,
+    }
+
+    fn scheduler(runs: &Arc<Runs>) -> (Scheduler<Runs>, SharedStatuses) {
+        let statuses = SharedStatuses::default();
+        (Scheduler::new(runs.clone(), statuses.clone()), statuses)
+    }
+
+    /// A task that records when it starts, then takes `takes` to finish.
+    fn timed(
+        start: Instant,
+        takes: Duration,
+    ) -> impl Fn(Arc<Runs>) -> futures::future::BoxFuture<'static, anyhow::Result<()>> {
+        move |runs: Arc<Runs>| {
+            async move {
+                runs.started.lock().unwrap().push(start.elapsed());
+                let now = runs.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                runs.most_in_flight.fetch_max(now, Ordering::SeqCst);
+                sleep(takes).await;
+                runs.in_flight.fetch_sub(1, Ordering::SeqCst);
+                Ok(())
+            }
+            .boxed()
+        }
+    }
+
+    fn started(runs: &Runs) -> Vec<u64> {
+        runs.started
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|d| d.as_secs())
+            .collect()
+    }
+
+    #[test]
+    fn jitter_stays_within_bounds() {
+        assert_eq!(jitter(Duration::ZERO), Duration::ZERO);
+        let max = Duration::from_millis(50);
+        let waits: Vec<Duration> = (0..1000).map(|_| jitter(max)).collect();
+        assert!(waits.iter().all(|w| *w <= max));
+        // and they aren't all the same
+        assert!(waits.iter().any(|w| *w != waits[0]));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn runs_tasks_on_their_intervals() {
+        let runs = Arc::new(Runs::default());
+        let start = Instant::now();
+        let (scheduler, statuses) = scheduler(&runs);
+        scheduler
+            .every("quick", 10 * SECOND, Duration::ZERO, timed(start, SECOND))
+            .run(sleep(35 * SECOND))
+            .await;
+        assert_eq!(started(&runs), [0, 10, 20, 30]);
+        let status = statuses.lock().unwrap()["quick"].clone();
+        assert_eq!((status.runs, status.skipped), (4, 0));
+        assert_eq!(status.interval_secs, 10);
+        assert_eq!(status.last_duration_ms, Some(1000));
+        assert!(!status.running);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn jitter_delays_runs_by_at_most_its_bound() {
+        let runs = Arc::new(Runs::default());
+        let start = Instant::now();
+        let (scheduler, _) = scheduler(&runs);
+        scheduler
+            .every("jittery", 10 * SECOND, 5 * SECOND, timed(start, SECOND))
+            .run(sleep(200 * SECOND))
+            .await;
+        let started = runs.started.lock().unwrap().clone();
+        assert!(started[0] <= 5 * SECOND, "{:?}", started);
+        for gap in started.windows(2).map(|w| w[1] - w[0]) {
+            assert!(gap >= 10 * SECOND && gap <= 15 * SECOND, "{:?}", started);
+        }
+        assert!(started.len() >= 13, "{:?}", started);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn does_not_overlap_runs_of_a_task() {
+        let runs = Arc::new(Runs::default());
+        let start = Instant::now();
+        let (scheduler, statuses) = scheduler(&runs);
+        scheduler
+            .every(
+                "slow",
+                10 * SECOND,
+                Duration::ZERO,
+                timed(start, 25 * SECOND),
+            )
+            .run(sleep(65 * SECOND))
+            .await;
+        // due at 10 and 20 while the first run is going, at 40 and 50 while
+        // the second is, and at 60 while the third is
+        assert_eq!(started(&runs), [0, 30, 60]);
+        assert_eq!(runs.most_in_flight.load(Ordering::SeqCst), 1);
+        let status = statuses.lock().unwrap()["slow"].clone();
+        assert_eq!((status.runs, status.skipped), (3, 4));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn failing_tasks_do_not_hold_up_the_others() {
+        let runs = Arc::new(Runs::default());
+        let start = Instant::now();
+        let (scheduler, statuses) = scheduler(&runs);
+        scheduler
+            .every("failing", 10 * SECOND, Duration::ZERO, |_| {
+                async { Err(anyhow!("could not do it")) }.boxed()
+            })
+            .every("panicking", 10 * SECOND, Duration::ZERO, |_| {
+                async { panic!("bug") }.boxed()
+            })
+            .every("fine", 10 * SECOND, Duration::ZERO, timed(start, SECOND))
+            .run(sleep(25 * SECOND))
+            .await;
+        assert_eq!(started(&runs), [0, 10, 20]);
+        let statuses = statuses.lock().unwrap();
+        let failing = &statuses["failing"];
+        assert_eq!((failing.runs, failing.failures), (3, 3));
+        assert_eq!(failing.last_error.as_deref(), Some("could not do it"));
+        assert_eq!(
+            failing.to_string(),
+            "3 runs, 3 failed, 0 skipped, last failed with: could not do it"
+        );
+        let panicking = &statuses["panicking"];
+        assert_eq!(panicking.failures, 3);
+        assert_eq!(panicking.last_error.as_deref(), Some("panicked: bug"));
+        assert_eq!(statuses["fine"].failures, 0);
+        assert_eq!(statuses["fine"].last_error, None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn stops_starting_runs_and_waits_for_those_under_way() {
+        let runs = Arc::new(Runs::default());
+        let start = Instant::now();
+        let (scheduler, statuses) = scheduler(&runs);
+        scheduler
+            .every(
+                "slow",
+                10 * SECOND,
+                Duration::ZERO,
+                timed(start, 8 * SECOND),
+            )
+            .run(sleep(12 * SECOND))
+            .await;
+        // the run started at 10 was finished, and none started after stopping
+        assert_eq!(start.elapsed(), 18 * SECOND);
+        assert_eq!(started(&runs), [0, 10]);
+        assert_eq!(runs.in_flight.load(Ordering::SeqCst), 0);
+        assert_eq!(statuses.lock().unwrap()["slow"].runs, 2);
+    }
+}
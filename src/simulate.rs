@@ -0,0 +1,420 @@
+use anyhow::{anyhow, Context, Result};
+use reqwest::{Request, Response, Url};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{collections::HashMap, fs, sync::Mutex};
+
+use crate::bot::Bot;
+use crate::events::Event;
+use crate::models::Config;
+use crate::preview::RepoSummary;
+use crate::skip::SkipReason;
+use crate::util::{fold_case, GhRepo};
+
+const USAGE: &str = "Usage: check_for_license simulate <listing file> [--fixtures <file>] [--json]";
+/// Posts simulated together, as many as are on a page of a listing.
+pub const PAGE_LEN: usize = 100;
+
+/// How a repository's check is answered in a simulation, given in a
+/// fixtures file by `org/repo`.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+pub struct Fixture {
+    pub licensed: bool,
+    /// SPDX id of the license, if GitHub would recognize it.
+    #[serde(default)]
+    pub license: Option<String>,
+    /// What GitHub says about the repository otherwise, like its language.
+    #[serde(flatten)]
+    pub summary: RepoSummary,
+}
+
+/// Parse a fixtures file's JSON, an object of fixtures by `org/repo`, into
+/// fixtures by lowercased `org/repo`.
+pub fn parse_fixtures(json: &str) -> Result<HashMap<String, Fixture>> {
+    let fixtures: HashMap<String, Fixture> =
+        serde_json::from_str(json).context("Could not parse fixtures")?;
+    Ok(fixtures
+        .into_iter()
+        .map(|(repo, fixture)| (fold_case(&repo), fixture))
+        .collect())
+}
+
+/// What to simulate a listing with.
+#[derive(Clone, Debug)]
+pub struct Options {
+    pub config: Config,
+    /// Answers to checks, by lowercased `org/repo`; repositories without one
+    /// can't be told to have a license or not.
+    pub fixtures: HashMap<String, Fixture>,
+}
+
+/// What was found and done about a repository a post links.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct RepoDecision {
+    pub repo: String,
+    /// `licensed`, `unlicensed` or `indeterminate`.
+    pub verdict: String,
+    pub license: Option<String>,
+    pub action: String,
+    pub skip_reason: Option<SkipReason>,
+}
+
+/// What the bot would do about a post.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Decision {
+    pub fullname: String,
+    pub title: String,
+    pub subreddit: String,
+    pub author: String,
+    /// Why the post was passed over without checking what it links.
+    pub skipped: Option<SkipReason>,
+    pub repos: Vec<RepoDecision>,
+    /// The reply that would be made.
+    pub reply: Option<String>,
+}
+
+impl Decision {
+    /// The decision about a post, from its place in a listing, why it was
+    /// skipped if it was, the events recorded about it and the replies made.
+    pub(crate) fn new(
+        posting: &Value,
+        skipped: Option<SkipReason>,
+        events: &[Event],
+        replies: &[(String, String)],
+    ) -> Self {
+        let post = &posting["data"];
+        let field = |name: &str| post[name].as_str().unwrap_or_default().to_owned();
+        let fullname = field("name");
+        Self {
+            repos: events
+                .iter()
+                .filter(|e| e.fullname == fullname)
+                .map(|e| RepoDecision {
+                    repo: e.repo.clone(),
+                    verdict: e.verdict.clone(),
+                    license: e.license.clone(),
+                    action: e.action.clone(),
+                    skip_reason: e.skip_reason(),
+                })
+                .collect(),
+            reply: replies
+                .iter()
+                .find(|(thing, _)| *thing == fullname)
+                .map(|(_, text)| text.clone()),
+            title: field("title"),
+            subreddit: field("subreddit"),
+            author: field("author"),
+            skipped,
+            fullname,
+        }
+    }
+}
+
+/// Answers the bot's requests without the network: checks from fixtures,
+/// posts from the listing being simulated, and comments by keeping them.
+///
+/// Subreddits are taken to be public and without rules against bots, and
+/// posts to have no other submissions.
+#[derive(Debug, Default)]
+pub struct Simulator {
+    posts: HashMap<String, Value>,
+    fixtures: HashMap<String, Fixture>,
+    /// Fullnames of the posts commented on, with what was commented.
+    replies: Mutex<Vec<(String, String)>>,
+}
+
+impl Simulator {
+    pub fn new(postings: &[Value], fixtures: HashMap<String, Fixture>) -> Self {
+        let posts = postings
+            .iter()
+            .filter_map(|p| Some((p["data"]["name"].as_str()?.to_owned(), p["data"].clone())))
+            .collect();
+        Self {
+            posts,
+            fixtures,
+            replies: Mutex::default(),
+        }
+    }
+
+    /// The answer to a check of a repository, if there's a fixture for it.
+    pub fn fixture(&self, repo: &GhRepo) -> Option<&Fixture> {
+        self.fixtures.get(&repo.canonical_key())
+    }
+
+    /// The comments made so far, by the fullname of the post commented on.
+    pub fn replies(&self) -> Vec<(String, String)> {
+        self.replies.lock().unwrap().clone()
+    }
+
+    /// Answer a request as Reddit would, going by its path.
+    pub fn answer(&self, request: &Request) -> Response {
+        let path = request.url().path();
+        let (status, body) = if path.ends_with("/api/info") {
+            let ids = query(request.url(), "id").unwrap_or_default();
+            let children: Vec<Value> = ids
+                .split(',')
+                .filter_map(|id| self.posts.get(id))
+                .map(|post| json!({"kind": "t3", "data": post}))
+                .collect();
+            (
+                200,
+                json!({"kind": "Listing", "data": {"children": children}}),
+            )
+        } else if path.contains("/duplicates/") {
+            let empty = json!({"kind": "Listing", "data": {"children": []}});
+            (200, json!([empty, empty]))
+        } else if path.ends_with("/about/rules") {
+            (200, json!({"rules": []}))
+        } else if path.ends_with("/about") {
+            (200, json!({"data": {"subreddit_type": "public"}}))
+        } else if path.ends_with("/api/comment") {
+            let form = request
+                .body()
+                .and_then(|b| b.as_bytes())
+                .map(|b| String::from_utf8_lossy(b).into_owned())
+                .unwrap_or_default();
+            let mut url = Url::parse("http://form.invalid/").expect("Invalid form URL");
+            url.set_query(Some(&form));
+            let thing = query(&url, "thing_id").unwrap_or_default();
+            let text = query(&url, "text").unwrap_or_default();
+            let mut replies = self.replies.lock().unwrap();
+            replies.push((thing, text));
+            let name = format!("t1_simulated{}", replies.len());
+            (
+                200,
+                json!({"json": {"errors": [], "data": {"things": [{"data": {"name": name}}]}}}),
+            )
+        } else {
+            (404, json!({"message": "Not Found"}))
+        };
+        http::Response::builder()
+            .status(status)
+            .header("content-type", "application/json")
+            .body(body.to_string())
+            .expect("Invalid simulated response")
+            .into()
+    }
+}
+
+fn query(url: &Url, name: &str) -> Option<String> {
+    url.query_pairs()
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value.into_owned())
+}
+
+/// The posts in a saved listing, either a page of a listing as Reddit gives
+/// it or a findings log.
+///
+/// A post in a findings log links the repositories it has findings for: a
+/// link post if there's one, and otherwise a self post linking them all.
+pub fn postings(input: &str) -> Result<Vec<Value>> {
+    if let Ok(page) = serde_json::from_str::<Value>(input) {
+        if let Some(children) = page["data"]["children"].as_array() {
+            return Ok(children.clone());
+        }
+    }
+    let mut posts: Vec<(Event, Vec<String>)> = vec![];
+    for (number, line) in input.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let event: Event = serde_json::from_str(line).map_err(|e| {
+            anyhow!(
+                "Line {} is neither a listing page nor a finding: {}",
+                number + 1,
+                e
+            )
+        })?;
+        match posts.iter_mut().find(|(p, _)| p.fullname == event.fullname) {
+            Some((_, repos)) if repos.contains(&event.repo) => {}
+            Some((_, repos)) => repos.push(event.repo),
+            None => {
+                let repo = event.repo.clone();
+                posts.push((event, vec![repo]));
+            }
+        }
+    }
+    Ok(posts
+        .into_iter()
+        .map(|(event, repos)| {
+            let urls: Vec<String> = repos
+                .iter()
+                .map(|r| format!("https://github.com/{}", r))
+                .collect();
+            let mut post = json!({
+                "name": event.fullname,
+                "title": event.title,
+                "subreddit": event.subreddit,
+                "author": event.author,
+                "permalink": event.permalink.trim_start_matches("https://www.reddit.com"),
+            });
+            if let [url] = &urls[..] {
+                post["domain"] = json!("github.com");
+                post["url"] = json!(url);
+            } else {
+                post["domain"] = json!(format!("self.{}", event.subreddit));
+                post["selftext"] = json!(urls.join("\n"));
+            }
+            json!({"kind": "t3", "data": post})
+        })
+        .collect())
+}
+
+/// The decisions, post by post, as a report to read through.
+pub fn report(decisions: &[Decision]) -> String {
+    let mut out = String::new();
+    for decision in decisions {
+        out.push_str(&format!(
+            "{} in /r/{} by /u/{}: {}\n",
+            decision.fullname, decision.subreddit, decision.author, decision.title
+        ));
+        if let Some(reason) = decision.skipped {
+            out.push_str(&format!("  skipped: {}\n", reason));
+        }
+        for repo in &decision.repos {
+            out.push_str(&format!("  {}: {}", repo.repo, repo.verdict));
+            if let Some(license) = &repo.license {
+                out.push_str(&format!(" ({})", license));
+            }
+            out.push_str(&format!(", {}\n", repo.action));
+        }
+        if let Some(reply) = &decision.reply {
+            out.push_str("  reply:\n");
+            for line in reply.lines() {
+                if line.is_empty() {
+                    out.push_str("    |\n");
+                } else {
+                    out.push_str(&format!("    | {}\n", line));
+                }
+            }
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// Run the `simulate` subcommand, returning its output.
+pub async fn run(config: Config, args: &[String]) -> Result<String> {
+    let mut listing = None;
+    let mut fixtures = None;
+    let mut as_json = false;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--fixtures" => fixtures = Some(args.next().ok_or_else(|| anyhow!(USAGE))?),
+            "--json" => as_json = true,
+            path if listing.is_none() && !path.starts_with("--") => listing = Some(path),
+            _ => return Err(anyhow!(USAGE)),
+        }
+    }
+    let listing = listing.ok_or_else(|| anyhow!(USAGE))?;
+    let input = fs::read_to_string(listing).with_context(|| format!("reading {}", listing))?;
+    let fixtures = match fixtures {
+        Some(path) => {
+            parse_fixtures(&fs::read_to_string(path).with_context(|| format!("reading {}", path))?)?
+        }
+        None => HashMap::new(),
+    };
+    let options = Options { config, fixtures };
+    let decisions = Bot::simulate(&input, &options).await?;
+    if as_json {
+        return Ok(format!("{}\n", serde_json::to_string_pretty(&decisions)?));
+    }
+    Ok(report(&decisions))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_fixtures, postings, report, Decision, RepoDecision};
+    use crate::skip::SkipReason;
+
+    #[test]
+    fn reads_listing_pages_and_findings_logs() {
+        let page = r#"{"kind":"Listing","data":{"children":[{"data":{"name":"t3_a"}}]}}"#;
+        let posts = postings(page).unwrap();
+        assert_eq!(posts.len(), 1);
+        assert_eq!(posts[0]["data"]["name"], "t3_a");
+
+        let finding = |fullname: &str, repo: &str| {
+            format!(
+                r#"{{"at":1,"fullname":"{}","title":"T","subreddit":"rust","author":"op",
+                "permalink":"https://www.reddit.com/r/rust/comments/a/t/","repo":"{}",
+                "verdict":"unlicensed","action":"replied"}}"#,
+                fullname, repo
+            )
+            .replace('\n', "")
+        };
+        let log = [
+            finding("t3_one", "org/repo"),
+            finding("t3_two", "org/a"),
+            finding("t3_two", "org/b"),
+            finding("t3_one", "org/repo"),
+        ]
+        .join("\n");
+        let posts = postings(&log).unwrap();
+        assert_eq!(posts.len(), 2);
+        let one = &posts[0]["data"];
+        assert_eq!(one["domain"], "github.com");
+        assert_eq!(one["url"], "https://github.com/org/repo");
+        assert_eq!(one["permalink"], "/r/rust/comments/a/t/");
+        let two = &posts[1]["data"];
+        assert_eq!(two["domain"], "self.rust");
+        assert_eq!(
+            two["selftext"],
+            "https://github.com/org/a\nhttps://github.com/org/b"
+        );
+
+        assert!(postings("not json").is_err());
+    }
+
+    #[test]
+    fn fixtures_are_keyed_regardless_of_case() {
+        let fixtures = parse_fixtures(
+            r#"{"Org/Repo": {"licensed": true, "license": "MIT", "language": "Rust"},
+                "org/other": {"licensed": false}}"#,
+        )
+        .unwrap();
+        let fixture = &fixtures["org/repo"];
+        assert!(fixture.licensed);
+        assert_eq!(fixture.license.as_deref(), Some("MIT"));
+        assert_eq!(fixture.summary.language.as_deref(), Some("Rust"));
+        assert!(!fixtures["org/other"].licensed);
+        assert!(parse_fixtures("[]").is_err());
+    }
+
+    #[test]
+    fn reports_decisions() {
+        let decisions = [
+            Decision {
+                fullname: "t3_a".to_owned(),
+                title: "No code".to_owned(),
+                subreddit: "rust".to_owned(),
+                author: "op".to_owned(),
+                skipped: Some(SkipReason::NoLinks),
+                repos: vec![],
+                reply: None,
+            },
+            Decision {
+                fullname: "t3_b".to_owned(),
+                title: "A tool".to_owned(),
+                subreddit: "rust".to_owned(),
+                author: "op".to_owned(),
+                skipped: None,
+                repos: vec![RepoDecision {
+                    repo: "org/repo".to_owned(),
+                    verdict: "unlicensed".to_owned(),
+                    license: None,
+                    action: "replied".to_owned(),
+                    skip_reason: None,
+                }],
+                reply: Some("No license.\n\nFooter".to_owned()),
+            },
+        ];
+        assert_eq!(
+            report(&decisions),
+            "t3_a in /r/rust by /u/op: No code\n  skipped: no links\n\n\
+             t3_b in /r/rust by /u/op: A tool\n  org/repo: unlicensed, replied\n  reply:\n\
+             \x20   | No license.\n    |\n    | Footer\n\n"
+        );
+    }
+}
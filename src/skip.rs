@@ -0,0 +1,354 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt,
+    path::Path,
+    time::{Duration, UNIX_EPOCH},
+};
+
+use crate::bundle::listing_keys;
+use crate::events::{self, Event};
+use crate::state;
+
+const USAGE: &str = "Usage: check_for_license why <post fullname or URL>";
+
+/// Why the bot said nothing about a post, or about a repository it links.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SkipReason {
+    /// A self post that links no GitHub repository.
+    NoLinks,
+    /// A link to somewhere other than GitHub.
+    NotGithub,
+    /// A link post whose URL is too long to be read.
+    UrlTooLong,
+    /// A post missing what its links are read from, its domain or URL.
+    Unreadable,
+    /// A link to GitHub that isn't to a repository, such as a profile, or to
+    /// one GitHub couldn't find, even when looked for again.
+    NotARepository,
+    OwnPost,
+    ExemptAuthor,
+    /// The post's title or flair says its code is a work in progress.
+    WorkInProgress,
+    /// The repository is owned by a high-profile account, or has more stars
+    /// than any the bot replies about.
+    HighProfile,
+    /// A post by an account that's since been deleted.
+    DeletedAuthor,
+    Licensed,
+    /// The repository got a license while the reply was held back.
+    ResolvedBeforeReply,
+    /// The post's author was replied to about the repository recently.
+    AlreadyRepliedToAuthor,
+    Suppressed,
+    /// The post says its code is unlicensed on purpose.
+    AuthorOptedOut,
+    RepliedInEarlierPost,
+    RepliesPaused,
+    /// The subreddit is one the bot only reports findings in.
+    ReportOnly,
+    /// Only a dry run, such as of a backfill.
+    DryRun,
+    ReplyCapReached,
+    PostRemoved,
+    /// The post was claimed by another reply first.
+    AlreadyReplied,
+    IssueAlreadyOpened,
+    IssuesDisabled,
+    TooManyStars,
+    IssueFailed,
+    /// Dropped from a queue that was full.
+    QueueFull,
+    /// A check to be tried again that was dropped as too old.
+    RetryExpired,
+}
+
+impl SkipReason {
+    /// The reason as counted in the stats.
+    pub fn label(self) -> &'static str {
+        match self {
+            SkipReason::NoLinks => "no links",
+            SkipReason::NotGithub => "not GitHub",
+            SkipReason::UrlTooLong => "URL too long",
+            SkipReason::Unreadable => "unreadable post",
+            SkipReason::NotARepository => "not a repository",
+            SkipReason::OwnPost => "own post",
+            SkipReason::ExemptAuthor => "exempt author",
+            SkipReason::WorkInProgress => "skipped_wip",
+            SkipReason::HighProfile => "skipped_high_profile",
+            SkipReason::DeletedAuthor => "deleted author",
+            SkipReason::Licensed => "licensed",
+            // kept as it was first counted, so old and new counts add up
+            SkipReason::ResolvedBeforeReply => "resolved_before_reply",
+            SkipReason::AlreadyRepliedToAuthor => "already replied to author",
+            SkipReason::Suppressed => "suppressed",
+            SkipReason::AuthorOptedOut => "author opted out",
+            SkipReason::RepliedInEarlierPost => "replied in earlier post",
+            SkipReason::RepliesPaused => "replies paused",
+            SkipReason::ReportOnly => "report only",
+            SkipReason::DryRun => "dry run",
+            SkipReason::ReplyCapReached => "reply cap reached",
+            SkipReason::PostRemoved => "post removed",
+            SkipReason::AlreadyReplied => "already replied",
+            SkipReason::IssueAlreadyOpened => "issue already opened",
+            SkipReason::IssuesDisabled => "issues disabled",
+            SkipReason::TooManyStars => "too many stars for an issue",
+            SkipReason::IssueFailed => "issue failed",
+            SkipReason::QueueFull => "queue full",
+            SkipReason::RetryExpired => "retry expired",
+        }
+    }
+
+    /// Why nothing was said about a repository, from its verdict and what
+    /// its event says was done, if nothing was.
+    ///
+    /// Actions with details, like `report only: subreddit is private`, are
+    /// read without them.
+    pub fn from_action(verdict: &str, action: &str) -> Option<SkipReason> {
+        let action = action.split(':').next().unwrap_or_default().trim();
+        match (verdict, action) {
+            (_, "resolved_before_reply") => Some(SkipReason::ResolvedBeforeReply),
+            (_, "queue full") => Some(SkipReason::QueueFull),
+            (_, "retry expired") => Some(SkipReason::RetryExpired),
+            (_, "not a repository") => Some(SkipReason::NotARepository),
+            (_, "post removed") => Some(SkipReason::PostRemoved),
+            (_, "skipped_high_profile") => Some(SkipReason::HighProfile),
+            (_, "skipped_wip") => Some(SkipReason::WorkInProgress),
+            ("licensed", "praised") => None,
+            ("licensed", _) => Some(SkipReason::Licensed),
+            ("unlicensed", action) => match action {
+                "already replied to author" => Some(SkipReason::AlreadyRepliedToAuthor),
+                "suppressed" => Some(SkipReason::Suppressed),
+                "author opted out" => Some(SkipReason::AuthorOptedOut),
+                "replied in earlier post" => Some(SkipReason::RepliedInEarlierPost),
+                "replies paused" => Some(SkipReason::RepliesPaused),
+                "report only" => Some(SkipReason::ReportOnly),
+                "would queue for approval" => Some(SkipReason::DryRun),
+                "reply cap reached" => Some(SkipReason::ReplyCapReached),
+                "already replied" => Some(SkipReason::AlreadyReplied),
+                "issue already opened" => Some(SkipReason::IssueAlreadyOpened),
+                "issues disabled" => Some(SkipReason::IssuesDisabled),
+                "too many stars for an issue" => Some(SkipReason::TooManyStars),
+                "issue failed" => Some(SkipReason::IssueFailed),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.label())
+    }
+}
+
+/// The fullname of a post, given as one or as a link to it.
+fn post_fullname(arg: &str) -> Option<String> {
+    if arg.starts_with("t3_") {
+        return Some(arg.to_owned());
+    }
+    let path = arg.split(['?', '#']).next().unwrap_or_default();
+    let mut segments = path.split('/').filter(|s| !s.is_empty());
+    let id = if path.contains("redd.it/") {
+        segments.next_back()
+    } else {
+        segments.skip_while(|s| *s != "comments").nth(1)
+    }?;
+    if id.chars().all(|c| c.is_ascii_alphanumeric()) {
+        Some(format!("t3_{}", id))
+    } else {
+        None
+    }
+}
+
+fn timestamp(at: u64) -> String {
+    humantime::format_rfc3339_seconds(UNIX_EPOCH + Duration::from_secs(at)).to_string()
+}
+
+/// What was decided about a post, line by line: when it was seen, whether
+/// it was skipped outright, and then what was done about each repository
+/// it links, with how its license was looked for.
+fn explain(
+    fullname: &str,
+    seen: &[(String, Option<u64>, Option<SkipReason>)],
+    events: &[Event],
+) -> String {
+    let mut out = String::new();
+    if seen.is_empty() {
+        // the listing's state was pruned, or moved elsewhere
+        out.push_str(&format!("{}: not in any listing's state\n", fullname));
+    }
+    for (key, at, skipped) in seen {
+        let at = match at {
+            Some(at) if *at > 0 => timestamp(*at),
+            _ => "an unknown time".to_owned(),
+        };
+        out.push_str(&format!("{}: seen in {} at {}\n", fullname, key, at));
+        if let Some(reason) = skipped {
+            out.push_str(&format!("  skipped: {}\n", reason));
+        }
+    }
+    for event in events {
+        out.push_str(&format!(
+            "  {} at {}: {}, {}",
+            event.repo,
+            timestamp(event.at),
+            event.verdict,
+            event.action
+        ));
+        match event.skip_reason() {
+            Some(reason) => out.push_str(&format!(" (skipped: {})\n", reason)),
+            None => out.push('\n'),
+        }
+        for step in &event.detection_path {
+            out.push_str(&format!("    {}\n", step));
+        }
+    }
+    out
+}
+
+/// Run the `why` subcommand against the state directory, returning its output.
+pub fn run(dir: &Path, args: &[String]) -> Result<String> {
+    let fullname = match args {
+        [arg] => post_fullname(arg).ok_or_else(|| anyhow!(USAGE))?,
+        _ => return Err(anyhow!(USAGE)),
+    };
+    let mut seen = vec![];
+    for key in listing_keys(dir)? {
+        let processed = state::load(dir, &key)?.processed;
+        if processed.contains(&fullname) {
+            seen.push((
+                key,
+                processed.seen_at(&fullname),
+                processed.skipped(&fullname),
+            ));
+        }
+    }
+    let events: Vec<Event> = events::load(dir)?
+        .into_iter()
+        .filter(|e| e.fullname == fullname)
+        .collect();
+    if seen.is_empty() && events.is_empty() {
+        return Ok(format!("Nothing is known about {}\n", fullname));
+    }
+    Ok(explain(&fullname, &seen, &events))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{post_fullname, run, SkipReason};
+    use crate::check::Verdict;
+    use crate::events::{Event, EventLog};
+    use crate::license::{DetectionStep, Probe};
+    use crate::processed::Processed;
+    use crate::state::{self, StateFile};
+
+    #[test]
+    fn reasons_from_actions() {
+        let reason = SkipReason::from_action;
+        assert_eq!(reason("unlicensed", "replied"), None);
+        assert_eq!(reason("unlicensed", "held for active hours"), None);
+        assert_eq!(
+            reason("unlicensed", "suppressed"),
+            Some(SkipReason::Suppressed)
+        );
+        assert_eq!(
+            reason("unlicensed", "report only: subreddit is private"),
+            Some(SkipReason::ReportOnly)
+        );
+        assert_eq!(
+            reason("unlicensed", "issue failed: Got status 500"),
+            Some(SkipReason::IssueFailed)
+        );
+        assert_eq!(reason("licensed", "none"), Some(SkipReason::Licensed));
+        assert_eq!(reason("licensed", "praised"), None);
+        assert_eq!(
+            reason("licensed", "resolved_before_reply"),
+            Some(SkipReason::ResolvedBeforeReply)
+        );
+        assert_eq!(reason("indeterminate", "deferred"), None);
+        assert_eq!(
+            reason("unlicensed", "skipped_high_profile"),
+            Some(SkipReason::HighProfile)
+        );
+
+        assert_eq!(SkipReason::OwnPost.to_string(), "own post");
+        assert_eq!(
+            serde_json::to_string(&SkipReason::NotGithub).unwrap(),
+            r#""not_github""#
+        );
+    }
+
+    #[test]
+    fn finds_posts_by_fullname_or_link() {
+        for arg in &[
+            "t3_abc123",
+            "https://www.reddit.com/r/rust/comments/abc123/some_title/",
+            "https://old.reddit.com/r/rust/comments/abc123/?context=3",
+            "https://redd.it/abc123",
+        ] {
+            assert_eq!(post_fullname(arg).as_deref(), Some("t3_abc123"), "{}", arg);
+        }
+        assert_eq!(post_fullname("https://github.com/some/repo"), None);
+        assert_eq!(post_fullname("abc123"), None);
+    }
+
+    #[test]
+    fn explains_what_was_decided() {
+        let tmp = tempfile::tempdir().unwrap();
+        let mut processed = Processed::default();
+        processed.insert("t3_mine", 1_600_000_000);
+        processed.skip("t3_mine", SkipReason::OwnPost);
+        processed.insert("t3_checked", 1_600_000_000);
+        let listing = StateFile {
+            processed,
+            ..StateFile::default()
+        };
+        state::save(tmp.path(), "r-rust", &listing).unwrap();
+        let mut log = EventLog::open(tmp.path()).unwrap();
+        let overturned = vec![
+            DetectionStep::new(Probe::LicenseApi, Verdict::Unlicensed),
+            DetectionStep::new(Probe::LinkedRef, Verdict::Licensed).with_detail("dev"),
+        ];
+        for (repo, verdict, action, detection_path) in [
+            ("some/repo", "unlicensed", "suppressed", vec![]),
+            ("other/repo", "licensed", "none", overturned),
+            ("third/repo", "unlicensed", "replied", vec![]),
+        ] {
+            log.record(Event {
+                at: 1_600_000_060,
+                fullname: "t3_checked".to_owned(),
+                title: String::new(),
+                subreddit: "rust".to_owned(),
+                permalink: String::new(),
+                author: "op".to_owned(),
+                repo: repo.to_string(),
+                verdict: verdict.to_string(),
+                license: None,
+                action: action.to_string(),
+                removed_license: None,
+                detection_path,
+            });
+        }
+
+        let why = |arg: &str| run(tmp.path(), &[arg.to_owned()]).unwrap();
+        assert_eq!(
+            why("t3_mine"),
+            "t3_mine: seen in r-rust at 2020-09-13T12:26:40Z\n  skipped: own post\n"
+        );
+        assert_eq!(
+            why("https://www.reddit.com/r/rust/comments/checked/title/"),
+            "t3_checked: seen in r-rust at 2020-09-13T12:26:40Z\n\
+             \x20 some/repo at 2020-09-13T12:27:40Z: unlicensed, suppressed (skipped: suppressed)\n\
+             \x20 other/repo at 2020-09-13T12:27:40Z: licensed, none (skipped: licensed)\n\
+             \x20   license API: unlicensed\n\
+             \x20   linked ref: licensed (dev)\n\
+             \x20 third/repo at 2020-09-13T12:27:40Z: unlicensed, replied\n"
+        );
+        assert_eq!(why("t3_unknown"), "Nothing is known about t3_unknown\n");
+        assert!(run(tmp.path(), &[]).is_err());
+        assert!(run(tmp.path(), &["nonsense".to_owned()]).is_err());
+    }
+}
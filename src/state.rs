@@ -0,0 +1,624 @@
+use anyhow::{anyhow, Context, Result};
+use log::{debug, info};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    fmt::{self, Debug},
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use thiserror::Error;
+
+use crate::claims::{self, ReplyClaim, RepoClaim};
+use crate::history::{self, ReplyRecord};
+use crate::issues::{self, IssueRecord};
+use crate::listing::Cursor;
+use crate::paths::{cursor_file, processed_file, state_file};
+use crate::pending::PendingReply;
+use crate::processed::Processed;
+use crate::restricted::{self, Restriction};
+use crate::stats::Stats;
+
+/// Version of the state file format written by this build.
+pub const STATE_VERSION: u64 = 3;
+
+/// Everything persisted about one watched listing.
+///
+/// Version 0 was a bare array of processed fullnames in
+/// `processed-{key}.json`, with the cursor kept in `cursor-{key}.json`.
+/// Version 1 kept processed posts as bare fullnames, without when they were
+/// first seen. Version 2 wasn't stamped with who it belongs to.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct StateFile {
+    pub version: u64,
+    /// Who the state belongs to, once a bot watching the listing saved it.
+    #[serde(default)]
+    pub owner: Option<Owner>,
+    /// Posts that have already been looked at.
+    #[serde(default)]
+    pub processed: Processed,
+    #[serde(default)]
+    pub cursor: Cursor,
+    /// Replies that have been held back to be made later.
+    #[serde(default)]
+    pub pending: Vec<PendingReply>,
+    /// What the bot has done in each subreddit.
+    #[serde(default)]
+    pub stats: Stats,
+}
+
+impl Default for StateFile {
+    fn default() -> Self {
+        Self {
+            version: STATE_VERSION,
+            owner: None,
+            processed: Processed::default(),
+            cursor: Cursor::default(),
+            pending: vec![],
+            stats: Stats::default(),
+        }
+    }
+}
+
+/// The account a bot replies as and the store key of the listing it
+/// watches, which its state files are stamped with.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Owner {
+    pub account: String,
+    pub listing: String,
+}
+
+/// How the owner a state file is stamped with compares with a bot loading it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Ownership {
+    Same,
+    /// Saved before state files were stamped, or never saved.
+    Unstamped,
+    /// The same account and listing, but written in another case, such as a
+    /// subreddit whose name was configured with capitals.
+    Recased,
+    /// Another account's, or another listing's.
+    Other,
+}
+
+impl Owner {
+    pub fn new(account: &str, listing: &str) -> Self {
+        Self {
+            account: account.to_owned(),
+            listing: listing.to_owned(),
+        }
+    }
+
+    /// How the owner a state file is stamped with compares with this one.
+    pub fn ownership(&self, stamp: Option<&Owner>) -> Ownership {
+        match stamp {
+            None => Ownership::Unstamped,
+            Some(stamp) if stamp == self => Ownership::Same,
+            Some(stamp)
+                if stamp.account.eq_ignore_ascii_case(&self.account)
+                    && stamp.listing.eq_ignore_ascii_case(&self.listing) =>
+            {
+                Ownership::Recased
+            }
+            Some(_) => Ownership::Other,
+        }
+    }
+}
+
+impl fmt::Display for Owner {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "/u/{} watching {}", self.account, self.listing)
+    }
+}
+
+/// State loaded for a listing that's stamped as another bot's, which isn't
+/// used as it is: merging it would repeat replies the other bot made, or
+/// hold back replies because of posts only it looked at.
+#[derive(Debug, Error)]
+#[error(
+    "The state of {key} belongs to {stamp}, not {owner}. Give this bot a state directory \
+     (CFL_STATE_DIR) or Redis prefix of its own, or run it once with --adopt-state to take \
+     the state over"
+)]
+pub struct ForeignState {
+    pub key: String,
+    pub stamp: Owner,
+    pub owner: Owner,
+}
+
+/// Parse a state file of any known version, migrating it to the current one.
+fn parse(data: &str) -> Result<StateFile> {
+    let value: Value = serde_json::from_str(data)?;
+    if value.is_array() {
+        return Ok(StateFile {
+            processed: serde_json::from_value(value)?,
+            ..StateFile::default()
+        });
+    }
+    let version = value["version"]
+        .as_u64()
+        .ok_or_else(|| anyhow!("State file has no version"))?;
+    if version > STATE_VERSION {
+        return Err(anyhow!(
+            "State file is version {}, but this build only understands up to version {}; \
+             upgrade check_for_license, or move the file aside to start from scratch",
+            version,
+            STATE_VERSION
+        ));
+    }
+    Ok(StateFile {
+        version: STATE_VERSION,
+        ..serde_json::from_value(value)?
+    })
+}
+
+/// Load the state of a listing from the state directory.
+///
+/// Falls back to the version 0 files if there's no state file yet, and to an
+/// empty state if there's nothing at all. A state file that can't be read is
+/// an error, rather than silently starting over.
+pub fn load(dir: &Path, store_key: &str) -> Result<StateFile> {
+    let path = state_file(dir, store_key);
+    if path.exists() {
+        let data = fs::read_to_string(&path)
+            .with_context(|| format!("reading state file {}", path.display()))?;
+        let state =
+            parse(&data).with_context(|| format!("loading state file {}", path.display()))?;
+        debug!(
+            "Loaded state with {} processed posts from {}",
+            state.processed.len(),
+            path.display()
+        );
+        return Ok(state);
+    }
+    let legacy = processed_file(dir, store_key);
+    if !legacy.exists() {
+        return Ok(StateFile::default());
+    }
+    let data = fs::read_to_string(&legacy)
+        .with_context(|| format!("reading state file {}", legacy.display()))?;
+    let mut state =
+        parse(&data).with_context(|| format!("loading state file {}", legacy.display()))?;
+    if let Ok(data) = fs::read_to_string(cursor_file(dir, store_key)) {
+        state.cursor = serde_json::from_str(&data).unwrap_or_default();
+    }
+    info!("Migrating {} to {}", legacy.display(), path.display());
+    Ok(state)
+}
+
+/// Save the state of a listing to the state directory.
+///
+/// The file is replaced atomically so a crash mid-write can't corrupt it.
+pub fn save(dir: &Path, store_key: &str, state: &StateFile) -> Result<()> {
+    let path = state_file(dir, store_key);
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, serde_json::to_string(state)?)
+        .and_then(|_| fs::rename(&tmp, &path))
+        .with_context(|| format!("writing {}", path.display()))
+}
+
+/// Rename the state file of a listing saved under `from` to be under `to`,
+/// returning whether there was one and nothing was under `to` yet.
+pub fn move_file(dir: &Path, from: &str, to: &str) -> Result<bool> {
+    let (old, new) = (state_file(dir, from), state_file(dir, to));
+    // on a filesystem that ignores case, these can be the same file
+    if from == to || new.exists() || !old.exists() {
+        return Ok(false);
+    }
+    fs::rename(&old, &new)
+        .with_context(|| format!("moving {} to {}", old.display(), new.display()))?;
+    Ok(true)
+}
+
+/// Where the state of watched listings is kept, by store key, along with
+/// the history of replies and of issues opened.
+pub trait Store: Debug + Send {
+    fn load(&self, key: &str) -> Result<StateFile>;
+    fn save(&mut self, key: &str, state: &StateFile) -> Result<()>;
+    /// Every reply the bot has made, oldest first.
+    fn replies(&self) -> Result<Vec<ReplyRecord>>;
+    fn add_replies(&mut self, records: &[ReplyRecord]) -> Result<()>;
+    /// Mark the replies made in a comment about a repository as resolved.
+    fn resolve_reply(&mut self, comment: &str, repo: &str, at: u64) -> Result<()>;
+    /// Forget the oldest replies that `history::expired` says can go,
+    /// returning how many.
+    fn prune_replies(&mut self, now: u64, horizon: u64) -> Result<usize>;
+    /// Every issue the bot has opened, oldest first.
+    fn issues(&self) -> Result<Vec<IssueRecord>>;
+    fn add_issue(&mut self, record: &IssueRecord) -> Result<()>;
+    /// Claim a post to reply to, returning whether nothing else has.
+    ///
+    /// Checking and claiming happen as one step, and the claim is saved
+    /// before this returns, so a post is replied to once at most.
+    fn try_claim_reply(&mut self, fullname: &str, at: u64) -> Result<bool>;
+    /// Mark the reply to a claimed post as made.
+    fn confirm_reply(&mut self, fullname: &str, comment: Option<&str>) -> Result<()>;
+    /// Give up the claim on a post whose reply failed.
+    fn release_reply(&mut self, fullname: &str) -> Result<()>;
+    /// Every post claimed, oldest first.
+    fn reply_claims(&self) -> Result<Vec<ReplyClaim>>;
+    /// Claim a repository for its author's post, unless another of their
+    /// posts claimed it less than `window` seconds before, returning that
+    /// post's claim.
+    ///
+    /// Claims are shared by every subreddit watched, and checking and
+    /// claiming happen as one step.
+    fn try_claim_repo(&mut self, claim: &RepoClaim, window: u64) -> Result<Option<RepoClaim>>;
+    /// Subreddits where only approved users may comment.
+    fn restrictions(&self) -> Result<Vec<Restriction>>;
+    fn save_restrictions(&mut self, restrictions: &[Restriction]) -> Result<()>;
+
+    /// Save whatever is only kept in memory for now, such as listings saved
+    /// while what the store keeps state in couldn't be reached.
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Whether the store has lost what it keeps state in, and is only
+    /// keeping it in memory until that's back, so nothing can be claimed.
+    fn is_degraded(&self) -> bool {
+        false
+    }
+
+    /// Move the state saved under `from`, a key a listing had before, to
+    /// `to`, returning whether there was any to move.
+    ///
+    /// Nothing is moved if there's already state under `to`.
+    fn move_state(&mut self, from: &str, to: &str) -> Result<bool> {
+        if from == to || self.load(to)? != StateFile::default() {
+            return Ok(false);
+        }
+        let state = self.load(from)?;
+        if state == StateFile::default() {
+            return Ok(false);
+        }
+        self.save(to, &state)?;
+        Ok(true)
+    }
+
+    /// Load the state of a listing, change it and save it again.
+    fn update(&mut self, key: &str, change: &mut dyn FnMut(&mut StateFile)) -> Result<StateFile> {
+        let mut state = self.load(key)?;
+        change(&mut state);
+        self.save(key, &state)?;
+        Ok(state)
+    }
+}
+
+/// Keeps state in files in the state directory.
+#[derive(Debug)]
+pub struct DirStore {
+    dir: PathBuf,
+}
+
+impl DirStore {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+}
+
+impl Store for DirStore {
+    fn load(&self, key: &str) -> Result<StateFile> {
+        load(&self.dir, key)
+    }
+
+    fn save(&mut self, key: &str, state: &StateFile) -> Result<()> {
+        save(&self.dir, key, state)
+    }
+
+    fn move_state(&mut self, from: &str, to: &str) -> Result<bool> {
+        move_file(&self.dir, from, to)
+    }
+
+    fn replies(&self) -> Result<Vec<ReplyRecord>> {
+        history::load(&self.dir)
+    }
+
+    fn add_replies(&mut self, records: &[ReplyRecord]) -> Result<()> {
+        history::append(&self.dir, records)
+    }
+
+    fn resolve_reply(&mut self, comment: &str, repo: &str, at: u64) -> Result<()> {
+        history::resolve(&self.dir, comment, repo, at)
+    }
+
+    fn prune_replies(&mut self, now: u64, horizon: u64) -> Result<usize> {
+        history::prune(&self.dir, now, horizon)
+    }
+
+    fn issues(&self) -> Result<Vec<IssueRecord>> {
+        issues::load(&self.dir)
+    }
+
+    fn add_issue(&mut self, record: &IssueRecord) -> Result<()> {
+        issues::append(&self.dir, record)
+    }
+
+    fn try_claim_reply(&mut self, fullname: &str, at: u64) -> Result<bool> {
+        claims::update(&self.dir, |c| claims::claim(c, fullname, at))
+    }
+
+    fn confirm_reply(&mut self, fullname: &str, comment: Option<&str>) -> Result<()> {
+        claims::update(&self.dir, |c| claims::confirm(c, fullname, comment))
+    }
+
+    fn release_reply(&mut self, fullname: &str) -> Result<()> {
+        claims::update(&self.dir, |c| claims::release(c, fullname))
+    }
+
+    fn reply_claims(&self) -> Result<Vec<ReplyClaim>> {
+        claims::load(&self.dir)
+    }
+
+    fn try_claim_repo(&mut self, claim: &RepoClaim, window: u64) -> Result<Option<RepoClaim>> {
+        claims::update_repos(&self.dir, |c| claims::claim_repo(c, claim, window))
+    }
+
+    fn restrictions(&self) -> Result<Vec<Restriction>> {
+        restricted::load(&self.dir)
+    }
+
+    fn save_restrictions(&mut self, restrictions: &[Restriction]) -> Result<()> {
+        restricted::save(&self.dir, restrictions)
+    }
+}
+
+/// Keeps state in memory, for tests and for embedding the bot.
+///
+/// Clones share the same states.
+#[derive(Clone, Debug, Default)]
+pub struct MemoryStore {
+    states: Arc<Mutex<HashMap<String, StateFile>>>,
+    replies: Arc<Mutex<Vec<ReplyRecord>>>,
+    issues: Arc<Mutex<Vec<IssueRecord>>>,
+    claims: Arc<Mutex<Vec<ReplyClaim>>>,
+    repo_claims: Arc<Mutex<Vec<RepoClaim>>>,
+    restrictions: Arc<Mutex<Vec<Restriction>>>,
+}
+
+impl Store for MemoryStore {
+    fn load(&self, key: &str) -> Result<StateFile> {
+        let states = self.states.lock().unwrap();
+        Ok(states.get(key).cloned().unwrap_or_default())
+    }
+
+    fn save(&mut self, key: &str, state: &StateFile) -> Result<()> {
+        let mut states = self.states.lock().unwrap();
+        states.insert(key.to_owned(), state.clone());
+        Ok(())
+    }
+
+    fn replies(&self) -> Result<Vec<ReplyRecord>> {
+        Ok(self.replies.lock().unwrap().clone())
+    }
+
+    fn add_replies(&mut self, records: &[ReplyRecord]) -> Result<()> {
+        self.replies.lock().unwrap().extend_from_slice(records);
+        Ok(())
+    }
+
+    fn resolve_reply(&mut self, comment: &str, repo: &str, at: u64) -> Result<()> {
+        history::mark_resolved(&mut self.replies.lock().unwrap(), comment, repo, at);
+        Ok(())
+    }
+
+    fn prune_replies(&mut self, now: u64, horizon: u64) -> Result<usize> {
+        let mut replies = self.replies.lock().unwrap();
+        let expired = history::expired(&replies, now, horizon);
+        replies.drain(..expired);
+        Ok(expired)
+    }
+
+    fn issues(&self) -> Result<Vec<IssueRecord>> {
+        Ok(self.issues.lock().unwrap().clone())
+    }
+
+    fn add_issue(&mut self, record: &IssueRecord) -> Result<()> {
+        self.issues.lock().unwrap().push(record.clone());
+        Ok(())
+    }
+
+    fn try_claim_reply(&mut self, fullname: &str, at: u64) -> Result<bool> {
+        Ok(claims::claim(
+            &mut self.claims.lock().unwrap(),
+            fullname,
+            at,
+        ))
+    }
+
+    fn confirm_reply(&mut self, fullname: &str, comment: Option<&str>) -> Result<()> {
+        claims::confirm(&mut self.claims.lock().unwrap(), fullname, comment);
+        Ok(())
+    }
+
+    fn release_reply(&mut self, fullname: &str) -> Result<()> {
+        claims::release(&mut self.claims.lock().unwrap(), fullname);
+        Ok(())
+    }
+
+    fn reply_claims(&self) -> Result<Vec<ReplyClaim>> {
+        Ok(self.claims.lock().unwrap().clone())
+    }
+
+    fn try_claim_repo(&mut self, claim: &RepoClaim, window: u64) -> Result<Option<RepoClaim>> {
+        Ok(claims::claim_repo(
+            &mut self.repo_claims.lock().unwrap(),
+            claim,
+            window,
+        ))
+    }
+
+    fn restrictions(&self) -> Result<Vec<Restriction>> {
+        Ok(self.restrictions.lock().unwrap().clone())
+    }
+
+    fn save_restrictions(&mut self, restrictions: &[Restriction]) -> Result<()> {
+        *self.restrictions.lock().unwrap() = restrictions.to_vec();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        load, parse, save, DirStore, MemoryStore, Owner, Ownership, StateFile, Store, STATE_VERSION,
+    };
+    use crate::listing::{Cursor, Listing};
+    use crate::paths::state_file;
+    use crate::util::GhRepo;
+    use std::fs;
+
+    #[test]
+    fn migrates_v0_files() {
+        let tmp = tempfile::tempdir().unwrap();
+        fs::write(
+            tmp.path().join("processed-rust.json"),
+            r#"["t3_hcr8l6","t3_hcqz3v","t3_hcqsbn"]"#,
+        )
+        .unwrap();
+        fs::write(
+            tmp.path().join("cursor-rust.json"),
+            r#"{"known":["t3_hcr8l6"]}"#,
+        )
+        .unwrap();
+
+        let state = load(tmp.path(), "rust").unwrap();
+        assert_eq!(state.version, STATE_VERSION);
+        assert_eq!(
+            state.processed.fullnames(),
+            vec!["t3_hcr8l6", "t3_hcqz3v", "t3_hcqsbn"]
+        );
+        assert_eq!(state.cursor.anchor(), Some("t3_hcr8l6"));
+
+        save(tmp.path(), "rust", &state).unwrap();
+        assert_eq!(load(tmp.path(), "rust").unwrap(), state);
+        let saved = fs::read_to_string(state_file(tmp.path(), "rust")).unwrap();
+        assert!(saved.contains(r#""version":3"#), "{}", saved);
+    }
+
+    #[test]
+    fn migrates_v1_files() {
+        let state = parse(r#"{"version":1,"processed":["t3_a","t3_b"]}"#).unwrap();
+        assert_eq!(state.version, STATE_VERSION);
+        assert_eq!(state.processed.fullnames(), vec!["t3_a", "t3_b"]);
+        assert_eq!(state.processed.seen_at("t3_a"), Some(0));
+        let saved = serde_json::to_string(&state).unwrap();
+        assert!(
+            saved.contains(r#"{"fullname":"t3_a","seen_at":0}"#),
+            "{}",
+            saved
+        );
+    }
+
+    #[test]
+    fn reads_pending_replies_as_saved_before() {
+        let state = parse(
+            r#"{"version":1,"processed":[],"pending":[{"fullname":"t3_a","queued_at":1,
+                "repos":[{"org":"Someone","repo":"Project","url":"https://github.com/Someone/Project"}]}]}"#,
+        )
+        .unwrap();
+        let link = &state.pending[0].repos[0];
+        assert_eq!(link.repo, GhRepo::new("Someone", "Project"));
+        assert_eq!(link.url, "https://github.com/Someone/Project");
+        let saved = serde_json::to_value(link).unwrap();
+        assert_eq!(
+            saved,
+            serde_json::json!({"org": "Someone", "repo": "Project", "url": "https://github.com/Someone/Project"})
+        );
+    }
+
+    #[test]
+    fn stamps_state_with_its_owner() {
+        let state = parse(r#"{"version":2,"processed":[]}"#).unwrap();
+        assert_eq!(state.owner, None);
+        let owner = Owner::new("bot", "rust");
+        assert_eq!(owner.ownership(None), Ownership::Unstamped);
+        assert_eq!(owner.ownership(Some(&owner)), Ownership::Same);
+        assert_eq!(
+            owner.ownership(Some(&Owner::new("Bot", "Rust"))),
+            Ownership::Recased
+        );
+        assert_eq!(
+            owner.ownership(Some(&Owner::new("other_bot", "rust"))),
+            Ownership::Other
+        );
+        assert_eq!(
+            owner.ownership(Some(&Owner::new("bot", "rust+programming"))),
+            Ownership::Other
+        );
+
+        let stamped = StateFile {
+            owner: Some(owner.clone()),
+            ..StateFile::default()
+        };
+        let saved = serde_json::to_string(&stamped).unwrap();
+        assert!(
+            saved.contains(r#""owner":{"account":"bot","listing":"rust"}"#),
+            "{}",
+            saved
+        );
+        assert_eq!(parse(&saved).unwrap(), stamped);
+        assert_eq!(owner.to_string(), "/u/bot watching rust");
+    }
+
+    #[test]
+    fn rejects_newer_versions() {
+        let err = parse(r#"{"version":99,"processed":[]}"#).unwrap_err();
+        let message = err.to_string();
+        assert!(message.contains("version 99"), "{}", message);
+        assert!(message.contains("upgrade"), "{}", message);
+    }
+
+    #[test]
+    fn unreadable_state_is_an_error() {
+        let tmp = tempfile::tempdir().unwrap();
+        fs::write(state_file(tmp.path(), "broken"), "{\"vers").unwrap();
+        assert!(load(tmp.path(), "broken").is_err());
+        assert!(parse(r#"{"processed":[]}"#).is_err());
+    }
+
+    #[test]
+    fn missing_state_is_empty() {
+        let tmp = tempfile::tempdir().unwrap();
+        assert_eq!(load(tmp.path(), "new").unwrap(), StateFile::default());
+        assert_eq!(StateFile::default().cursor, Cursor::default());
+    }
+
+    #[test]
+    fn moves_state_saved_under_names_as_they_were_given() {
+        let listing = Listing::subreddits("Rust+r/Programming").unwrap();
+        let old = listing.legacy_store_key().unwrap();
+        let new = listing.store_key();
+        assert_eq!(
+            (old.as_str(), new.as_str()),
+            ("Rust+Programming", "rust+programming")
+        );
+        let mut state = StateFile::default();
+        state.processed.insert("t3_old", 1_600_000_000);
+
+        let tmp = tempfile::tempdir().unwrap();
+        save(tmp.path(), &old, &state).unwrap();
+        let mut dir = DirStore::new(tmp.path().to_owned());
+        assert!(dir.move_state(&old, &new).unwrap());
+        assert_eq!(dir.load(&new).unwrap(), state);
+        assert!(!state_file(tmp.path(), &old).exists());
+        // and only once
+        assert!(!dir.move_state(&old, &new).unwrap());
+
+        // state already under the new key is kept
+        let mut other = StateFile::default();
+        other.processed.insert("t3_other", 1_600_000_000);
+        save(tmp.path(), &old, &other).unwrap();
+        assert!(!dir.move_state(&old, &new).unwrap());
+        assert_eq!(dir.load(&new).unwrap(), state);
+
+        let mut memory = MemoryStore::default();
+        memory.save(&old, &state).unwrap();
+        assert!(memory.move_state(&old, &new).unwrap());
+        assert_eq!(memory.load(&new).unwrap(), state);
+        assert!(!memory.move_state(&old, &new).unwrap());
+    }
+}
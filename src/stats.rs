@@ -0,0 +1,316 @@
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fs, path::Path};
+
+use crate::skip::SkipReason;
+use crate::state;
+use crate::util::format_table;
+
+const USAGE: &str = "Usage: check_for_license stats [--json]";
+
+/// Counts of what the bot has done in one subreddit.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub struct SubredditStats {
+    /// Posts looked at.
+    pub scanned: u64,
+    /// Posts linking at least one GitHub repository.
+    pub candidates: u64,
+    /// Repositories found without a license.
+    pub unlicensed: u64,
+    pub replies: u64,
+    /// Posts and repositories that weren't replied about, by why not.
+    pub skips: BTreeMap<String, u64>,
+    /// Repositories whose license couldn't be checked.
+    pub errors: u64,
+    /// Repositories a fallback check came to another verdict about than
+    /// GitHub's `/license` endpoint, by the fallback.
+    pub overturns: BTreeMap<String, u64>,
+}
+
+impl SubredditStats {
+    fn add(&mut self, other: &SubredditStats) {
+        self.scanned += other.scanned;
+        self.candidates += other.candidates;
+        self.unlicensed += other.unlicensed;
+        self.replies += other.replies;
+        for (reason, count) in &other.skips {
+            *self.skips.entry(reason.clone()).or_default() += count;
+        }
+        self.errors += other.errors;
+        for (probe, count) in &other.overturns {
+            *self.overturns.entry(probe.clone()).or_default() += count;
+        }
+    }
+
+    fn skipped(&self) -> u64 {
+        self.skips.values().sum()
+    }
+}
+
+/// Counts of what the bot has done, by lowercased subreddit name.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(transparent)]
+pub struct Stats(BTreeMap<String, SubredditStats>);
+
+fn display_name(key: &str) -> String {
+    if key.is_empty() {
+        "(unknown)".to_owned()
+    } else {
+        format!("/r/{}", key)
+    }
+}
+
+impl Stats {
+    /// The counts for a subreddit, starting from zero if there are none yet.
+    pub fn subreddit(&mut self, name: &str) -> &mut SubredditStats {
+        self.0.entry(name.to_lowercase()).or_default()
+    }
+
+    /// Count a skipped post or repository in a subreddit.
+    pub fn skip(&mut self, subreddit: &str, reason: SkipReason) {
+        *self
+            .subreddit(subreddit)
+            .skips
+            .entry(reason.label().to_owned())
+            .or_default() += 1;
+    }
+
+    /// Add another set of counts to these.
+    pub fn merge(&mut self, other: &Stats) {
+        for (name, stats) in &other.0 {
+            self.subreddit(name).add(stats);
+        }
+    }
+
+    /// Take the saved counts of any subreddit that isn't being counted yet,
+    /// such as when another listing is picked up.
+    pub fn restore(&mut self, saved: &Stats) {
+        for (name, stats) in &saved.0 {
+            self.0.entry(name.clone()).or_insert_with(|| stats.clone());
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// One line per subreddit, for the periodic summary.
+    pub fn lines(&self) -> Vec<String> {
+        self.0
+            .iter()
+            .map(|(name, s)| {
+                format!(
+                    "{}: {} scanned, {} candidates, {} unlicensed, {} replies, {} skipped, {} errors",
+                    display_name(name),
+                    s.scanned,
+                    s.candidates,
+                    s.unlicensed,
+                    s.replies,
+                    s.skipped(),
+                    s.errors
+                )
+            })
+            .collect()
+    }
+
+    /// A table of the counts for each subreddit, followed by one of why
+    /// repositories were skipped and one of which fallback checks overturned
+    /// GitHub's license API.
+    pub fn table(&self) -> String {
+        let row = |name: String, s: &SubredditStats| {
+            vec![
+                name,
+                s.scanned.to_string(),
+                s.candidates.to_string(),
+                s.unlicensed.to_string(),
+                s.replies.to_string(),
+                s.skipped().to_string(),
+                s.errors.to_string(),
+            ]
+        };
+        let mut rows: Vec<Vec<String>> = self
+            .0
+            .iter()
+            .map(|(name, s)| row(display_name(name), s))
+            .collect();
+        if self.0.len() > 1 {
+            let mut total = SubredditStats::default();
+            for s in self.0.values() {
+                total.add(s);
+            }
+            rows.push(row("TOTAL".to_owned(), &total));
+        }
+        let mut out = format_table(
+            &[
+                "SUBREDDIT",
+                "SCANNED",
+                "CANDIDATES",
+                "UNLICENSED",
+                "REPLIES",
+                "SKIPPED",
+                "ERRORS",
+            ],
+            &rows,
+        );
+        let counts = |header: &str, by: fn(&SubredditStats) -> &BTreeMap<String, u64>| {
+            let rows: Vec<Vec<String>> = self
+                .0
+                .iter()
+                .flat_map(|(name, s)| {
+                    by(s).iter().map(move |(key, count)| {
+                        vec![display_name(name), key.clone(), count.to_string()]
+                    })
+                })
+                .collect();
+            if rows.is_empty() {
+                return String::new();
+            }
+            format!("\n{}", format_table(&["SUBREDDIT", header, "COUNT"], &rows))
+        };
+        out.push_str(&counts("SKIPPED BECAUSE", |s| &s.skips));
+        out.push_str(&counts("OVERTURNED BY", |s| &s.overturns));
+        out
+    }
+}
+
+/// The counts kept in every listing's state file in the state directory,
+/// added together.
+pub fn load(dir: &Path) -> Result<Stats> {
+    let mut stats = Stats::default();
+    let entries = fs::read_dir(dir).with_context(|| format!("reading {}", dir.display()))?;
+    for entry in entries {
+        let name = entry?.file_name();
+        let name = name.to_string_lossy();
+        if let Some(key) = name
+            .strip_prefix("state-")
+            .and_then(|n| n.strip_suffix(".json"))
+        {
+            stats.merge(&state::load(dir, key)?.stats);
+        }
+    }
+    Ok(stats)
+}
+
+/// Run a `stats` subcommand against the state directory, returning its output.
+pub fn run(dir: &Path, args: &[String]) -> Result<String> {
+    let mut json = false;
+    for arg in args {
+        match arg.as_str() {
+            "--json" => json = true,
+            _ => return Err(anyhow!(USAGE)),
+        }
+    }
+    let stats = load(dir)?;
+    if json {
+        return Ok(format!("{}\n", serde_json::to_string_pretty(&stats)?));
+    }
+    if stats.is_empty() {
+        return Ok("No statistics yet\n".to_owned());
+    }
+    Ok(stats.table())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{load, run, Stats};
+    use crate::skip::SkipReason;
+    use std::fs;
+
+    fn stats(subreddit: &str, scanned: u64, skips: &[SkipReason]) -> Stats {
+        let mut stats = Stats::default();
+        let s = stats.subreddit(subreddit);
+        s.scanned = scanned;
+        s.candidates = 2;
+        s.unlicensed = 1;
+        s.replies = 1;
+        for reason in skips {
+            stats.skip(subreddit, *reason);
+        }
+        stats
+    }
+
+    #[test]
+    fn merges_by_subreddit() {
+        let mut total = stats("rust", 10, &[SkipReason::Suppressed]);
+        total.merge(&stats(
+            "Rust",
+            5,
+            &[SkipReason::Suppressed, SkipReason::ReplyCapReached],
+        ));
+        total.merge(&stats("golang", 1, &[]));
+        let rust = total.subreddit("rust").clone();
+        assert_eq!(rust.scanned, 15);
+        assert_eq!(rust.candidates, 4);
+        assert_eq!(rust.replies, 2);
+        assert_eq!(rust.skips["suppressed"], 2);
+        assert_eq!(rust.skips["reply cap reached"], 1);
+        assert_eq!(
+            total.lines(),
+            vec![
+                "/r/golang: 1 scanned, 2 candidates, 1 unlicensed, 1 replies, 0 skipped, 0 errors",
+                "/r/rust: 15 scanned, 4 candidates, 2 unlicensed, 2 replies, 3 skipped, 0 errors",
+            ]
+        );
+    }
+
+    /// Write a state file as the bot would, with stats in it.
+    fn write_state(dir: &std::path::Path, key: &str, json: &str) {
+        fs::write(
+            dir.join(format!("state-{}.json", key)),
+            format!(r#"{{"version":1,"processed":[],"stats":{}}}"#, json),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn reads_every_state_file() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_state(
+            tmp.path(),
+            "r-rust",
+            r#"{"rust":{"scanned":10,"candidates":4,"unlicensed":2,"replies":1,"skips":{"suppressed":1},"errors":1,"overturns":{"linked ref":2}}}"#,
+        );
+        write_state(
+            tmp.path(),
+            "search-abc",
+            r#"{"rust":{"scanned":5,"replies":1,"overturns":{"linked ref":1}},"golang":{"scanned":3,"skips":{"report only":2}}}"#,
+        );
+        // a state file from before there were stats
+        fs::write(
+            tmp.path().join("state-u-someone.json"),
+            r#"{"version":1,"processed":["t3_a"]}"#,
+        )
+        .unwrap();
+        fs::write(tmp.path().join("replies.jsonl"), "").unwrap();
+
+        let mut stats = load(tmp.path()).unwrap();
+        assert_eq!(stats.subreddit("rust").scanned, 15);
+        assert_eq!(stats.subreddit("golang").skips["report only"], 2);
+
+        assert_eq!(
+            run(tmp.path(), &[]).unwrap(),
+            "SUBREDDIT  SCANNED  CANDIDATES  UNLICENSED  REPLIES  SKIPPED  ERRORS\n\
+             /r/golang  3        0           0           0        2        0\n\
+             /r/rust    15       4           2           2        1        1\n\
+             TOTAL      18       4           2           2        3        1\n\
+             \n\
+             SUBREDDIT  SKIPPED BECAUSE  COUNT\n\
+             /r/golang  report only      2\n\
+             /r/rust    suppressed       1\n\
+             \n\
+             SUBREDDIT  OVERTURNED BY  COUNT\n\
+             /r/rust    linked ref     3\n"
+        );
+        let json: serde_json::Value =
+            serde_json::from_str(&run(tmp.path(), &["--json".to_owned()]).unwrap()).unwrap();
+        assert_eq!(json["rust"]["scanned"], 15);
+        assert!(run(tmp.path(), &["--nope".to_owned()]).is_err());
+    }
+
+    #[test]
+    fn empty_state_dir() {
+        let tmp = tempfile::tempdir().unwrap();
+        assert_eq!(run(tmp.path(), &[]).unwrap(), "No statistics yet\n");
+    }
+}
@@ -0,0 +1,278 @@
+use std::{
+    fmt,
+    hash::{Hash, Hasher},
+    str::FromStr,
+};
+use thiserror::Error;
+
+/// Why a name given for a subreddit can't be one.
+#[derive(Clone, Debug, Error, PartialEq)]
+pub enum SubredditError {
+    #[error("no subreddit name was given")]
+    Empty,
+    /// A link to a subreddit, with the name it links to if it could be read.
+    #[error("'{link}' is a link rather than a subreddit name")]
+    Link { link: String, name: Option<String> },
+    #[error("'{0}' isn't 2 to 21 characters long")]
+    Length(String),
+    #[error("'{0}' has characters other than letters, digits and underscores")]
+    Characters(String),
+}
+
+impl SubredditError {
+    /// How to give the name instead.
+    pub fn suggestion(&self) -> String {
+        match self {
+            SubredditError::Link {
+                name: Some(name), ..
+            } => {
+                format!("Use just the subreddit's name, `{}`.", name)
+            }
+            SubredditError::Link { name: None, .. } => {
+                "Use just the subreddit's name, not a link to it.".to_owned()
+            }
+            _ => "Use 2 to 21 letters, digits or underscores.".to_owned(),
+        }
+    }
+}
+
+/// The name of a subreddit, as given by whoever configured the bot.
+///
+/// Names are compared without regard to case, as Reddit does, but shown as
+/// they were given.
+#[derive(Clone, Debug, Eq)]
+pub struct Subreddit {
+    name: String,
+}
+
+/// Strip a leading `/r/` or `r/`, in either case.
+fn strip_prefix(name: &str) -> &str {
+    let name = name.strip_prefix('/').unwrap_or(name);
+    match name.get(..2) {
+        Some(prefix) if prefix.eq_ignore_ascii_case("r/") => &name[2..],
+        _ => name,
+    }
+}
+
+/// Whether a name looks like a link, rather than a name.
+fn is_link(name: &str) -> bool {
+    let lower = name.to_ascii_lowercase();
+    lower.contains("://")
+        || lower.starts_with("www.")
+        || lower.starts_with("reddit.com")
+        || lower.starts_with("old.reddit.com")
+}
+
+/// The name of the subreddit a link points to, if it can be read from it.
+fn linked_name(link: &str) -> Option<String> {
+    let lower = link.to_ascii_lowercase();
+    let start = lower.find("/r/")? + 3;
+    let name = link[start..].split(['/', '?', '#']).next()?;
+    Subreddit::new(name).ok().map(|s| s.name)
+}
+
+impl Subreddit {
+    /// Read a subreddit's name, with or without its `/r/` prefix.
+    pub fn new(raw: &str) -> Result<Self, SubredditError> {
+        let raw = raw.trim();
+        if is_link(raw) {
+            return Err(SubredditError::Link {
+                link: raw.to_owned(),
+                name: linked_name(raw),
+            });
+        }
+        let name = strip_prefix(raw);
+        let name = name.strip_suffix('/').unwrap_or(name);
+        if name.is_empty() {
+            return Err(SubredditError::Empty);
+        }
+        if !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err(SubredditError::Characters(raw.to_owned()));
+        }
+        if !(2..=21).contains(&name.len()) {
+            return Err(SubredditError::Length(raw.to_owned()));
+        }
+        Ok(Self {
+            name: name.to_owned(),
+        })
+    }
+
+    /// The names in a list of subreddits, joined with `+` as in a
+    /// multireddit or separated by commas, skipping any left empty.
+    pub fn split(raw: &str) -> impl Iterator<Item = &str> {
+        raw.split(['+', ','])
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+    }
+
+    /// Read a list of subreddits, as taken by [`Subreddit::split`].
+    pub fn parse_list(raw: &str) -> Result<Vec<Self>, SubredditError> {
+        let names = Self::split(raw)
+            .map(Self::new)
+            .collect::<Result<Vec<_>, _>>()?;
+        if names.is_empty() {
+            return Err(SubredditError::Empty);
+        }
+        Ok(names)
+    }
+
+    /// The name as it was given, without its prefix.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The name in lowercase, to key what's stored about the subreddit.
+    pub fn key(&self) -> String {
+        self.name.to_ascii_lowercase()
+    }
+}
+
+impl PartialEq for Subreddit {
+    fn eq(&self, other: &Self) -> bool {
+        self.name.eq_ignore_ascii_case(&other.name)
+    }
+}
+
+impl Hash for Subreddit {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key().hash(state);
+    }
+}
+
+impl FromStr for Subreddit {
+    type Err = SubredditError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::new(s)
+    }
+}
+
+impl fmt::Display for Subreddit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.name)
+    }
+}
+
+/// Subreddits' names joined with '+', as in a multireddit.
+pub fn join(subreddits: &[Subreddit]) -> String {
+    subreddits
+        .iter()
+        .map(Subreddit::name)
+        .collect::<Vec<_>>()
+        .join("+")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Subreddit, SubredditError};
+
+    #[test]
+    fn reads_names_however_they_are_given() {
+        for (raw, name) in [
+            ("rust", "rust"),
+            ("  Rust ", "Rust"),
+            ("r/rust", "rust"),
+            ("/r/rust", "rust"),
+            ("/r/rust/", "rust"),
+            ("R/learn_rust", "learn_rust"),
+            ("/R/de", "de"),
+            ("AskReddit", "AskReddit"),
+            ("r_", "r_"),
+            ("abcdefghijklmnopqrstu", "abcdefghijklmnopqrstu"),
+        ] {
+            let subreddit = Subreddit::new(raw).unwrap_or_else(|e| panic!("{}: {}", raw, e));
+            assert_eq!(subreddit.name(), name, "{}", raw);
+            assert_eq!(subreddit.key(), name.to_lowercase(), "{}", raw);
+        }
+    }
+
+    #[test]
+    fn rejects_what_isnt_a_name() {
+        let link = |link: &str, name: Option<&str>| SubredditError::Link {
+            link: link.to_owned(),
+            name: name.map(str::to_owned),
+        };
+        for (raw, error) in [
+            ("", SubredditError::Empty),
+            ("   ", SubredditError::Empty),
+            ("/r/", SubredditError::Empty),
+            ("a", SubredditError::Length("a".to_owned())),
+            ("r/a", SubredditError::Length("r/a".to_owned())),
+            (
+                "abcdefghijklmnopqrstuv",
+                SubredditError::Length("abcdefghijklmnopqrstuv".to_owned()),
+            ),
+            (
+                "no spaces",
+                SubredditError::Characters("no spaces".to_owned()),
+            ),
+            (
+                "rust-lang",
+                SubredditError::Characters("rust-lang".to_owned()),
+            ),
+            (
+                "r/rust/new",
+                SubredditError::Characters("r/rust/new".to_owned()),
+            ),
+            (
+                "/u/someone",
+                SubredditError::Characters("/u/someone".to_owned()),
+            ),
+            ("ünïcode", SubredditError::Characters("ünïcode".to_owned())),
+            (
+                "https://www.reddit.com/r/rust/",
+                link("https://www.reddit.com/r/rust/", Some("rust")),
+            ),
+            (
+                "old.reddit.com/R/Rust?sort=new",
+                link("old.reddit.com/R/Rust?sort=new", Some("Rust")),
+            ),
+            (
+                "https://example.com/rust",
+                link("https://example.com/rust", None),
+            ),
+        ] {
+            assert_eq!(Subreddit::new(raw), Err(error), "{}", raw);
+        }
+        assert_eq!(
+            Subreddit::new("https://reddit.com/r/rust")
+                .unwrap_err()
+                .suggestion(),
+            "Use just the subreddit's name, `rust`."
+        );
+    }
+
+    #[test]
+    fn reads_lists_of_names() {
+        let names = |raw: &str| -> Vec<String> {
+            Subreddit::parse_list(raw)
+                .unwrap()
+                .iter()
+                .map(|s| s.name().to_owned())
+                .collect()
+        };
+        assert_eq!(names("rust"), ["rust"]);
+        assert_eq!(
+            names("rust+/r/Programming, opensource,"),
+            ["rust", "Programming", "opensource"]
+        );
+        assert_eq!(
+            Subreddit::parse_list("rust+a"),
+            Err(SubredditError::Length("a".to_owned()))
+        );
+        assert_eq!(Subreddit::parse_list(" + ,"), Err(SubredditError::Empty));
+    }
+
+    #[test]
+    fn compares_without_case() {
+        assert_eq!(
+            Subreddit::new("Rust").unwrap(),
+            Subreddit::new("r/rust").unwrap()
+        );
+        assert_ne!(
+            Subreddit::new("rust").unwrap(),
+            Subreddit::new("rustjerk").unwrap()
+        );
+        assert_eq!(Subreddit::new("Rust").unwrap().to_string(), "Rust");
+    }
+}
@@ -0,0 +1,249 @@
+use anyhow::Result;
+use futures::future::LocalBoxFuture;
+use log::{error, info, warn};
+use std::{
+    convert::TryFrom,
+    time::{Duration, Instant},
+};
+
+use crate::errors::BotError;
+use crate::events::SharedEvents;
+use crate::notify;
+use crate::state::ForeignState;
+
+const HOUR: Duration = Duration::from_secs(60 * 60);
+/// Wait before the first restart in an hour; it doubles with each one after.
+const BASE_BACKOFF: Duration = Duration::from_secs(5);
+const MAX_BACKOFF: Duration = Duration::from_secs(5 * 60);
+
+/// A long-running loop that the supervisor keeps going.
+pub trait Supervised {
+    /// Run until there's an error.
+    fn run(&mut self) -> LocalBoxFuture<'_, Result<()>>;
+    /// Get ready to run again after an error, e.g. by logging in again.
+    fn restart(&mut self) -> LocalBoxFuture<'_, Result<()>>;
+}
+
+/// Restarts the watch loop when it stops with an error, backing off between
+/// attempts and giving up once too many restarts have been made in an hour.
+#[derive(Debug)]
+pub struct Supervisor {
+    max_restarts_per_hour: usize,
+    base_backoff: Duration,
+    max_backoff: Duration,
+    restarts: Vec<Instant>,
+    events: SharedEvents,
+}
+
+impl Supervisor {
+    pub fn new(max_restarts_per_hour: usize, events: SharedEvents) -> Self {
+        Self {
+            max_restarts_per_hour,
+            base_backoff: BASE_BACKOFF,
+            max_backoff: MAX_BACKOFF,
+            restarts: vec![],
+            events,
+        }
+    }
+
+    /// Use a different backoff between restarts.
+    #[cfg(test)]
+    fn with_backoff(mut self, base: Duration, max: Duration) -> Self {
+        self.base_backoff = base;
+        self.max_backoff = max;
+        self
+    }
+
+    /// Wait before the next restart, doubling with each restart in the last hour.
+    fn backoff(&self) -> Duration {
+        let doublings = u32::try_from(self.restarts.len())
+            .unwrap_or(u32::MAX)
+            .min(16);
+        self.base_backoff
+            .checked_mul(1 << doublings)
+            .map_or(self.max_backoff, |wait| wait.min(self.max_backoff))
+    }
+
+    /// Run `target` until it finishes, restarting it whenever it fails.
+    ///
+    /// Returns an error when the restart budget is used up, or when getting
+    /// ready to run again fails permanently, such as on bad credentials.
+    pub async fn run(&mut self, target: &mut impl Supervised) -> Result<()> {
+        loop {
+            let mut error = match target.run().await {
+                Ok(()) => return Ok(()),
+                // restarting won't make another bot's state this one's
+                Err(e) if e.chain().any(|cause| cause.is::<ForeignState>()) => return Err(e),
+                Err(e) => e,
+            };
+            error!("Watch loop stopped: {:#}", error);
+            loop {
+                let now = Instant::now();
+                self.restarts.retain(|&at| now.duration_since(at) < HOUR);
+                if self.restarts.len() >= self.max_restarts_per_hour {
+                    return Err(error.context(format!(
+                        "Watch loop restarted {} times in the last hour, giving up",
+                        self.restarts.len()
+                    )));
+                }
+                let wait = self.backoff();
+                warn!("Restarting the watch loop in {:?}", wait);
+                self.restarts.push(now);
+                self.events.lock().unwrap().record_restart();
+                notify::sleep(wait).await;
+                match target.restart().await {
+                    Ok(()) => break,
+                    Err(e) if is_permanent(&e) => return Err(e),
+                    Err(e) => {
+                        error!("Could not restart the watch loop: {:#}", e);
+                        error = e;
+                    }
+                }
+            }
+            info!("Restarted the watch loop");
+        }
+    }
+}
+
+/// Whether an error won't go away by trying again.
+fn is_permanent(error: &anyhow::Error) -> bool {
+    error.chain().any(|cause| {
+        matches!(
+            cause.downcast_ref::<BotError>().map(BotError::kind),
+            Some(
+                BotError::Permanent(_)
+                    | BotError::NotFound(_)
+                    | BotError::BadCredentials(_)
+                    | BotError::BadClient(_)
+                    | BotError::UnauthorizedClient(_)
+                    | BotError::UnsupportedGrant(_)
+                    | BotError::BadLoginRequest(_)
+            )
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Supervised, Supervisor};
+    use crate::errors::BotError;
+    use crate::events::SharedEvents;
+    use crate::state::{ForeignState, Owner};
+    use anyhow::{anyhow, Result};
+    use futures::future::{FutureExt, LocalBoxFuture};
+    use std::{collections::VecDeque, time::Duration, time::Instant};
+
+    /// A loop that fails or finishes as scripted.
+    #[derive(Default)]
+    struct Scripted {
+        runs: VecDeque<Result<()>>,
+        restarts: VecDeque<Result<()>>,
+        ran: usize,
+        restarted: usize,
+    }
+
+    impl Supervised for Scripted {
+        fn run(&mut self) -> LocalBoxFuture<'_, Result<()>> {
+            self.ran += 1;
+            let result = self
+                .runs
+                .pop_front()
+                .unwrap_or_else(|| Err(anyhow!("loop failed")));
+            async { result }.boxed_local()
+        }
+
+        fn restart(&mut self) -> LocalBoxFuture<'_, Result<()>> {
+            self.restarted += 1;
+            let result = self.restarts.pop_front().unwrap_or(Ok(()));
+            async { result }.boxed_local()
+        }
+    }
+
+    fn supervisor(max_restarts_per_hour: usize) -> (Supervisor, SharedEvents) {
+        let events = SharedEvents::default();
+        let supervisor = Supervisor::new(max_restarts_per_hour, events.clone())
+            .with_backoff(Duration::from_millis(1), Duration::from_millis(4));
+        (supervisor, events)
+    }
+
+    #[tokio::test]
+    async fn restarts_a_failed_loop() {
+        let (mut supervisor, events) = supervisor(5);
+        let mut target = Scripted {
+            runs: vec![Err(anyhow!("state file is corrupt")), Ok(())].into(),
+            ..Scripted::default()
+        };
+        supervisor.run(&mut target).await.unwrap();
+        assert_eq!((target.ran, target.restarted), (2, 1));
+        assert_eq!(events.lock().unwrap().restarts(), 1);
+    }
+
+    #[tokio::test]
+    async fn gives_up_when_budget_is_spent() {
+        let (mut supervisor, events) = supervisor(3);
+        let mut target = Scripted::default();
+        let err = supervisor.run(&mut target).await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Watch loop restarted 3 times in the last hour, giving up"
+        );
+        assert_eq!((target.ran, target.restarted), (4, 3));
+        assert_eq!(events.lock().unwrap().restarts(), 3);
+    }
+
+    #[tokio::test]
+    async fn bad_credentials_are_fatal() {
+        let (mut supervisor, _) = supervisor(5);
+        let mut target = Scripted {
+            restarts: vec![Err(
+                BotError::BadCredentials("invalid_grant".to_owned()).into()
+            )]
+            .into(),
+            ..Scripted::default()
+        };
+        let err = supervisor.run(&mut target).await.unwrap_err();
+        assert!(err.to_string().contains("invalid_grant"), "{}", err);
+        assert_eq!((target.ran, target.restarted), (1, 1));
+    }
+
+    #[tokio::test]
+    async fn foreign_state_is_fatal() {
+        let (mut supervisor, _) = supervisor(5);
+        let foreign = ForeignState {
+            key: "rust".to_owned(),
+            stamp: Owner::new("other_bot", "rust"),
+            owner: Owner::new("bot", "rust"),
+        };
+        let mut target = Scripted {
+            runs: vec![Err(anyhow::Error::new(foreign).context("loading rust"))].into(),
+            ..Scripted::default()
+        };
+        let err = supervisor.run(&mut target).await.unwrap_err();
+        assert!(format!("{:#}", err).contains("--adopt-state"), "{:#}", err);
+        assert_eq!((target.ran, target.restarted), (1, 0));
+    }
+
+    #[tokio::test]
+    async fn failed_restarts_are_retried() {
+        let (mut supervisor, events) = supervisor(5);
+        let mut target = Scripted {
+            runs: vec![Err(anyhow!("loop failed")), Ok(())].into(),
+            restarts: vec![Err(anyhow!("Reddit is down")), Ok(())].into(),
+            ..Scripted::default()
+        };
+        supervisor.run(&mut target).await.unwrap();
+        assert_eq!((target.ran, target.restarted), (2, 2));
+        assert_eq!(events.lock().unwrap().restarts(), 2);
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap() {
+        let (mut supervisor, _) = supervisor(100);
+        let mut waits = vec![];
+        for _ in 0..5 {
+            waits.push(supervisor.backoff().as_millis());
+            supervisor.restarts.push(Instant::now());
+        }
+        assert_eq!(waits, vec![1, 2, 4, 4, 4]);
+    }
+}
@@ -0,0 +1,415 @@
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::Path,
+    time::{Duration, UNIX_EPOCH},
+};
+
+use crate::events;
+use crate::models::Author;
+use crate::paths::suppression_file;
+use crate::util::{author_key, format_table, GhRepo};
+
+const USAGE: &str = "Usage: check_for_license suppress list [--json]\n\
+                     \x20      check_for_license suppress add <org/repo|user:name> [--reason X] [--ttl DAYS]\n\
+                     \x20      check_for_license suppress remove <org/repo|user:name>";
+const DAY: u64 = 24 * 60 * 60;
+/// Prefix of entries suppressing everything posted by a user.
+const USER_PREFIX: &str = "user:";
+
+/// A repository, or a user, that the bot shouldn't reply about.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Suppression {
+    /// A canonical `org/repo` key, or `user:name` keyed by `author_key`.
+    #[serde(alias = "repo")]
+    pub entry: String,
+    /// What added the entry.
+    #[serde(default = "unknown")]
+    pub source: String,
+    pub reason: String,
+    /// Unix timestamp of when it was added.
+    pub at: u64,
+    /// Unix timestamp after which it no longer applies.
+    #[serde(default)]
+    pub expires: Option<u64>,
+}
+
+fn unknown() -> String {
+    "unknown".to_owned()
+}
+
+impl Suppression {
+    /// A suppression of a repository that doesn't expire.
+    pub fn repo(repo: &GhRepo, source: &str, reason: &str) -> Self {
+        Suppression {
+            entry: repo.canonical_key(),
+            source: source.to_owned(),
+            reason: reason.to_owned(),
+            at: events::now(),
+            expires: None,
+        }
+    }
+
+    fn active(&self, now: u64) -> bool {
+        self.expires.is_none_or(|expires| now < expires)
+    }
+}
+
+/// The canonical form of an `org/repo` or `user:name` entry.
+pub fn parse_entry(text: &str) -> Result<String> {
+    let text = text.trim();
+    if let Some(name) = text.strip_prefix(USER_PREFIX) {
+        let name = author_key(name);
+        if name.is_empty() || name.contains('/') {
+            return Err(anyhow!("'{}' isn't a username", text));
+        }
+        return Ok(format!("{}{}", USER_PREFIX, name));
+    }
+    match text.parse::<GhRepo>() {
+        Ok(repo) => Ok(repo.canonical_key()),
+        Err(_) => Err(anyhow!(
+            "'{}' isn't a repository as org/repo, or a user as user:name",
+            text
+        )),
+    }
+}
+
+/// Load the suppression list from the state directory, expired entries and all.
+pub fn load(dir: &Path) -> Result<Vec<Suppression>> {
+    let path = suppression_file(dir);
+    if !path.exists() {
+        return Ok(vec![]);
+    }
+    let data = fs::read_to_string(&path).with_context(|| format!("reading {}", path.display()))?;
+    serde_json::from_str(&data).with_context(|| format!("loading {}", path.display()))
+}
+
+pub(crate) fn save(dir: &Path, list: &[Suppression]) -> Result<()> {
+    let path = suppression_file(dir);
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, serde_json::to_string_pretty(list)?)
+        .and_then(|_| fs::rename(&tmp, &path))
+        .with_context(|| format!("writing {}", path.display()))
+}
+
+/// Add an entry to the suppression list, returning whether it's new.
+///
+/// An entry that's already there is replaced if it has expired, and left
+/// alone otherwise.
+pub fn add(dir: &Path, suppression: Suppression) -> Result<bool> {
+    let mut list = load(dir)?;
+    let now = events::now();
+    match list.iter().position(|s| s.entry == suppression.entry) {
+        Some(i) if list[i].active(now) => return Ok(false),
+        Some(i) => list[i] = suppression,
+        None => list.push(suppression),
+    }
+    save(dir, &list)?;
+    Ok(true)
+}
+
+/// Remove an entry from the suppression list, returning whether it was there.
+pub fn remove(dir: &Path, entry: &str) -> Result<bool> {
+    let mut list = load(dir)?;
+    let before = list.len();
+    list.retain(|s| s.entry != entry);
+    if list.len() == before {
+        return Ok(false);
+    }
+    save(dir, &list)?;
+    Ok(true)
+}
+
+/// Whether a repository is on the suppression list at `now`.
+pub fn is_suppressed(list: &[Suppression], repo: &GhRepo, now: u64) -> bool {
+    let key = repo.canonical_key();
+    list.iter().any(|s| s.entry == key && s.active(now))
+}
+
+/// Whether everything a user posts is on the suppression list at `now`.
+pub fn is_user_suppressed(list: &[Suppression], author: &str, now: u64) -> bool {
+    // every deleted account goes by the same name
+    if Author::parse(author).is_deleted() {
+        return false;
+    }
+    let key = format!("{}{}", USER_PREFIX, author_key(author));
+    list.iter().any(|s| s.entry == key && s.active(now))
+}
+
+fn timestamp(at: u64) -> String {
+    humantime::format_rfc3339_seconds(UNIX_EPOCH + Duration::from_secs(at)).to_string()
+}
+
+fn table(list: &[Suppression], now: u64) -> String {
+    let rows: Vec<Vec<String>> = list
+        .iter()
+        .map(|s| {
+            vec![
+                s.entry.clone(),
+                s.source.clone(),
+                s.reason.clone(),
+                timestamp(s.at),
+                match s.expires {
+                    None => "never".to_owned(),
+                    Some(at) if at <= now => format!("{} (expired)", timestamp(at)),
+                    Some(at) => timestamp(at),
+                },
+            ]
+        })
+        .collect();
+    format_table(&["ENTRY", "SOURCE", "REASON", "ADDED", "EXPIRES"], &rows)
+}
+
+/// Run a `suppress` subcommand against the suppression list, returning its output.
+pub fn run(dir: &Path, args: &[String]) -> Result<String> {
+    let (command, args) = args.split_first().ok_or_else(|| anyhow!(USAGE))?;
+    match command.as_str() {
+        "list" => {
+            let json = match args {
+                [] => false,
+                [flag] if flag == "--json" => true,
+                _ => return Err(anyhow!(USAGE)),
+            };
+            let list = load(dir)?;
+            if json {
+                return Ok(format!("{}\n", serde_json::to_string_pretty(&list)?));
+            }
+            if list.is_empty() {
+                return Ok("Nothing is suppressed\n".to_owned());
+            }
+            Ok(table(&list, events::now()))
+        }
+        "add" => {
+            let (entry, flags) = args.split_first().ok_or_else(|| anyhow!(USAGE))?;
+            let entry = parse_entry(entry)?;
+            let mut reason = "added by hand".to_owned();
+            let mut ttl = None;
+            let mut flags = flags.iter();
+            while let Some(flag) = flags.next() {
+                let value = flags.next().ok_or_else(|| anyhow!(USAGE))?;
+                match flag.as_str() {
+                    "--reason" => reason = value.clone(),
+                    "--ttl" => {
+                        let days: u64 = value.parse().map_err(|_| {
+                            anyhow!("--ttl takes a number of days, not '{}'", value)
+                        })?;
+                        ttl = Some(days * DAY);
+                    }
+                    _ => return Err(anyhow!(USAGE)),
+                }
+            }
+            let at = events::now();
+            let added = add(
+                dir,
+                Suppression {
+                    entry: entry.clone(),
+                    source: "cli".to_owned(),
+                    reason,
+                    at,
+                    expires: ttl.map(|ttl| at + ttl),
+                },
+            )?;
+            Ok(if added {
+                format!("Suppressed {}\n", entry)
+            } else {
+                format!("{} is already suppressed\n", entry)
+            })
+        }
+        "remove" => {
+            let entry = match args {
+                [entry] => parse_entry(entry)?,
+                _ => return Err(anyhow!(USAGE)),
+            };
+            if !remove(dir, &entry)? {
+                return Err(anyhow!("{} isn't suppressed", entry));
+            }
+            Ok(format!("No longer suppressing {}\n", entry))
+        }
+        _ => Err(anyhow!(USAGE)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        add, is_suppressed, is_user_suppressed, load, parse_entry, remove, run, Suppression,
+    };
+    use crate::events;
+    use crate::util::GhRepo;
+    use std::fs;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn adds_each_repo_once() {
+        let tmp = tempfile::tempdir().unwrap();
+        assert!(load(tmp.path()).unwrap().is_empty());
+
+        let rejected = Suppression::repo(
+            &GhRepo::new("Someone", "Project"),
+            "approval queue",
+            "rejected",
+        );
+        assert!(add(tmp.path(), rejected).unwrap());
+        let again = Suppression::repo(
+            &GhRepo::new("someone", "project"),
+            "approval queue",
+            "rejected again",
+        );
+        assert!(!add(tmp.path(), again).unwrap());
+        let list = load(tmp.path()).unwrap();
+        assert_eq!(list.len(), 1);
+        assert_eq!(list[0].entry, "someone/project");
+        assert_eq!(list[0].reason, "rejected");
+
+        let now = events::now();
+        assert!(is_suppressed(
+            &list,
+            &GhRepo::new("SOMEONE", "project"),
+            now
+        ));
+        assert!(!is_suppressed(&list, &GhRepo::new("someone", "other"), now));
+
+        assert!(remove(tmp.path(), "someone/project").unwrap());
+        assert!(!remove(tmp.path(), "someone/project").unwrap());
+        assert!(load(tmp.path()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn reads_lists_from_before_users_and_expiry() {
+        let tmp = tempfile::tempdir().unwrap();
+        fs::write(
+            tmp.path().join("suppressed.json"),
+            r#"[{"repo":"someone/project","reason":"rejected in the approval queue","at":1600000000}]"#,
+        )
+        .unwrap();
+        let list = load(tmp.path()).unwrap();
+        assert_eq!(list[0].entry, "someone/project");
+        assert_eq!(list[0].source, "unknown");
+        assert_eq!(list[0].expires, None);
+        assert!(is_suppressed(
+            &list,
+            &GhRepo::new("someone", "project"),
+            u64::MAX
+        ));
+    }
+
+    #[test]
+    fn entries_expire() {
+        let list = vec![
+            Suppression {
+                entry: "someone/project".to_owned(),
+                source: "cli".to_owned(),
+                reason: "for a while".to_owned(),
+                at: 100,
+                expires: Some(200),
+            },
+            Suppression {
+                entry: "user:spammer".to_owned(),
+                source: "cli".to_owned(),
+                reason: "for a while".to_owned(),
+                at: 100,
+                expires: Some(200),
+            },
+        ];
+        assert!(is_suppressed(
+            &list,
+            &GhRepo::new("someone", "project"),
+            199
+        ));
+        assert!(!is_suppressed(
+            &list,
+            &GhRepo::new("someone", "project"),
+            200
+        ));
+        assert!(is_user_suppressed(&list, "/u/Spammer", 199));
+        assert!(!is_user_suppressed(&list, "spammer", 200));
+        assert!(!is_user_suppressed(&list, "someone_else", 100));
+    }
+
+    #[test]
+    fn deleted_authors_are_never_suppressed_as_users() {
+        let list = vec![Suppression {
+            entry: "user:[deleted]".to_owned(),
+            source: "cli".to_owned(),
+            reason: "hand edited".to_owned(),
+            at: 100,
+            expires: None,
+        }];
+        assert!(!is_user_suppressed(&list, "[deleted]", 100));
+    }
+
+    #[test]
+    fn parses_entries() {
+        assert_eq!(parse_entry("Someone/Project").unwrap(), "someone/project");
+        assert_eq!(parse_entry("user:/u/Someone").unwrap(), "user:someone");
+        assert_eq!(parse_entry(" user:someone ").unwrap(), "user:someone");
+        for bad in &["someone", "someone/", "a/b/c", "user:", "user:a/b", ""] {
+            assert!(parse_entry(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn cli() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        assert_eq!(
+            run(dir, &args(&["list"])).unwrap(),
+            "Nothing is suppressed\n"
+        );
+
+        assert_eq!(
+            run(dir, &args(&["add", "Someone/Project"])).unwrap(),
+            "Suppressed someone/project\n"
+        );
+        assert_eq!(
+            run(dir, &args(&["add", "someone/project"])).unwrap(),
+            "someone/project is already suppressed\n"
+        );
+        run(
+            dir,
+            &args(&["add", "user:Spammer", "--reason", "spam", "--ttl", "7"]),
+        )
+        .unwrap();
+        let list = load(dir).unwrap();
+        assert_eq!(list[0].reason, "added by hand");
+        assert_eq!(list[0].source, "cli");
+        assert_eq!(list[1].entry, "user:spammer");
+        assert_eq!(list[1].reason, "spam");
+        assert_eq!(list[1].expires, Some(list[1].at + 7 * 24 * 60 * 60));
+
+        let table = run(dir, &args(&["list"])).unwrap();
+        let lines: Vec<&str> = table.lines().collect();
+        assert!(lines[0].starts_with("ENTRY"), "{}", table);
+        assert!(lines[1].starts_with("someone/project  cli"), "{}", table);
+        assert!(lines[1].ends_with("never"), "{}", table);
+        assert!(lines[2].contains("spam"), "{}", table);
+        let json: serde_json::Value =
+            serde_json::from_str(&run(dir, &args(&["list", "--json"])).unwrap()).unwrap();
+        assert_eq!(json[1]["entry"], "user:spammer");
+
+        assert_eq!(
+            run(dir, &args(&["remove", "user:Spammer"])).unwrap(),
+            "No longer suppressing user:spammer\n"
+        );
+        assert!(run(dir, &args(&["remove", "user:spammer"])).is_err());
+        assert_eq!(load(dir).unwrap().len(), 1);
+
+        for bad in &[
+            &["add"][..],
+            &["add", "nope"],
+            &["add", "a/b", "--ttl"],
+            &["add", "a/b", "--ttl", "soon"],
+            &["add", "a/b", "--nope", "x"],
+            &["remove"],
+            &["list", "--nope"],
+            &["nope"],
+            &[],
+        ] {
+            assert!(run(dir, &args(bad)).is_err(), "{:?}", bad);
+        }
+    }
+}
@@ -0,0 +1,523 @@
+use anyhow::{anyhow, Context, Result};
+use log::{error, info};
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{Mutex, MutexGuard},
+    time::SystemTime,
+};
+
+use crate::reply::{self, MAX_COMMENT_LEN};
+
+/// Locale that every template is bundled in, used for whatever another
+/// locale doesn't have.
+pub const FALLBACK_LOCALE: &str = "en";
+
+/// Templates built into the bot, as `(locale, name, template)`.
+const BUNDLED: &[(&str, &str, &str)] = &[
+    (
+        "en",
+        "missing_license",
+        include_str!("../templates/en/missing_license.md"),
+    ),
+    (
+        "en",
+        "missing_license_plural",
+        include_str!("../templates/en/missing_license_plural.md"),
+    ),
+    (
+        "en",
+        "license_removed",
+        include_str!("../templates/en/license_removed.md"),
+    ),
+    (
+        "en",
+        "more_repos",
+        include_str!("../templates/en/more_repos.md"),
+    ),
+    (
+        "en",
+        "more_info",
+        include_str!("../templates/en/more_info.md"),
+    ),
+    (
+        "en",
+        "earlier_thread",
+        include_str!("../templates/en/earlier_thread.md"),
+    ),
+    (
+        "en",
+        "earlier_thread_plural",
+        include_str!("../templates/en/earlier_thread_plural.md"),
+    ),
+    (
+        "en",
+        "cross_post",
+        include_str!("../templates/en/cross_post.md"),
+    ),
+    ("en", "praise", include_str!("../templates/en/praise.md")),
+    (
+        "en",
+        "repo_summary",
+        include_str!("../templates/en/repo_summary.md"),
+    ),
+    ("en", "footer", include_str!("../templates/en/footer.md")),
+    (
+        "en",
+        "footer_maintainer",
+        include_str!("../templates/en/footer_maintainer.md"),
+    ),
+    (
+        "de",
+        "missing_license",
+        include_str!("../templates/de/missing_license.md"),
+    ),
+    (
+        "de",
+        "missing_license_plural",
+        include_str!("../templates/de/missing_license_plural.md"),
+    ),
+    (
+        "de",
+        "license_removed",
+        include_str!("../templates/de/license_removed.md"),
+    ),
+    (
+        "de",
+        "more_repos",
+        include_str!("../templates/de/more_repos.md"),
+    ),
+    (
+        "de",
+        "more_info",
+        include_str!("../templates/de/more_info.md"),
+    ),
+    (
+        "de",
+        "earlier_thread",
+        include_str!("../templates/de/earlier_thread.md"),
+    ),
+    (
+        "de",
+        "earlier_thread_plural",
+        include_str!("../templates/de/earlier_thread_plural.md"),
+    ),
+    (
+        "de",
+        "cross_post",
+        include_str!("../templates/de/cross_post.md"),
+    ),
+    ("de", "praise", include_str!("../templates/de/praise.md")),
+    (
+        "de",
+        "repo_summary",
+        include_str!("../templates/de/repo_summary.md"),
+    ),
+    ("de", "footer", include_str!("../templates/de/footer.md")),
+    (
+        "de",
+        "footer_maintainer",
+        include_str!("../templates/de/footer_maintainer.md"),
+    ),
+];
+
+/// Variables that a template can use.
+pub const VARIABLES: &[&str] = &[
+    "keyword",
+    "maintainer",
+    "source",
+    "link",
+    "count",
+    "license",
+    "repo",
+    "language",
+    "created_at",
+    "default_branch",
+    "stars",
+];
+
+/// A template file as it was when last read.
+struct Loaded {
+    modified: Option<SystemTime>,
+    /// The last valid version, or `None` if there hasn't been one, when the
+    /// bundled template is used instead.
+    template: Option<String>,
+    /// Why the file as it is now was rejected, if it was.
+    rejected: Option<String>,
+}
+
+/// Template files read from template directories, by path, kept until
+/// they're changed on disk.
+static LOADED: Mutex<BTreeMap<PathBuf, Loaded>> = Mutex::new(BTreeMap::new());
+
+fn loaded() -> MutexGuard<'static, BTreeMap<PathBuf, Loaded>> {
+    LOADED.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Check that a template only uses variables that exist, and would fit in a
+/// reply.
+pub fn validate(template: &str) -> Result<()> {
+    let len = template.chars().count();
+    if len > MAX_COMMENT_LEN {
+        return Err(anyhow!("{} characters long, more than fit in a reply", len));
+    }
+    let vars: Vec<(&str, Option<&str>)> = VARIABLES.iter().map(|v| (*v, Some(""))).collect();
+    reply::render(template, &vars).map(|_| ())
+}
+
+/// Read a template file, or `None` if there isn't one.
+fn read(path: &Path) -> Result<Option<(Option<SystemTime>, String)>> {
+    let modified = match fs::metadata(path) {
+        Ok(metadata) => metadata.modified().ok(),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e).with_context(|| format!("reading {}", path.display())),
+    };
+    match fs::read_to_string(path) {
+        Ok(template) => Ok(Some((modified, template.trim_end().to_owned()))),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e).with_context(|| format!("reading {}", path.display())),
+    }
+}
+
+/// Read a template file for the first time, remembering it.
+fn load(path: &Path) -> Result<Option<String>> {
+    let (modified, template) = match read(path)? {
+        Some(read) => read,
+        None => return Ok(None),
+    };
+    let entry = match validate(&template) {
+        Ok(()) => Loaded {
+            modified,
+            template: Some(template),
+            rejected: None,
+        },
+        Err(e) => {
+            error!(
+                "{} is invalid, using the bundled template instead: {:#}",
+                path.display(),
+                e
+            );
+            Loaded {
+                modified,
+                template: None,
+                rejected: Some(format!("{:#}", e)),
+            }
+        }
+    };
+    let template = entry.template.clone();
+    loaded().insert(path.to_owned(), entry);
+    Ok(template)
+}
+
+/// Re-read the template files in a directory that have changed on disk
+/// since they were read.
+///
+/// A file that's no longer valid is logged and its last valid version kept,
+/// so that a typo never stops replies or makes them garbled.
+pub fn refresh(dir: &Path) {
+    let mut loaded = loaded();
+    let paths: Vec<PathBuf> = loaded
+        .keys()
+        .filter(|p| p.starts_with(dir))
+        .cloned()
+        .collect();
+    for path in paths {
+        let (modified, template) = match read(&path) {
+            Ok(Some(read)) => read,
+            Ok(None) => {
+                info!("{} was removed, using the bundled template", path.display());
+                loaded.remove(&path);
+                continue;
+            }
+            Err(e) => {
+                error!("Could not reload {}: {:#}", path.display(), e);
+                continue;
+            }
+        };
+        let entry = loaded.get_mut(&path).unwrap();
+        if modified == entry.modified {
+            continue;
+        }
+        entry.modified = modified;
+        match validate(&template) {
+            Ok(()) => {
+                info!("Reloaded {}", path.display());
+                entry.template = Some(template);
+                entry.rejected = None;
+            }
+            Err(e) => {
+                error!(
+                    "{} is invalid, keeping the previous version: {:#}",
+                    path.display(),
+                    e
+                );
+                entry.rejected = Some(format!("{:#}", e));
+            }
+        }
+    }
+}
+
+/// The template files in a directory that were rejected as they are now,
+/// with why.
+pub fn rejected(dir: &Path) -> Vec<(PathBuf, String)> {
+    loaded()
+        .iter()
+        .filter(|(p, _)| p.starts_with(dir))
+        .filter_map(|(p, l)| l.rejected.clone().map(|e| (p.clone(), e)))
+        .collect()
+}
+
+/// Whether a locale name is safe to use as a directory name.
+pub fn is_locale_name(locale: &str) -> bool {
+    !locale.is_empty()
+        && locale
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+fn path(dir: &Path, locale: &str, name: &str) -> PathBuf {
+    dir.join(locale).join(format!("{}.md", name))
+}
+
+/// A template in exactly one locale: from the template directory if it's
+/// there and valid, or else bundled with the bot.
+///
+/// Files are only read once, until `refresh` finds they've changed.
+fn find(dir: &Path, locale: &str, name: &str) -> Result<Option<String>> {
+    let path = path(dir, locale, name);
+    let known = loaded().get(&path).map(|l| l.template.clone());
+    let template = match known {
+        Some(template) => template,
+        None => load(&path)?,
+    };
+    if template.is_some() {
+        return Ok(template);
+    }
+    Ok(BUNDLED
+        .iter()
+        .find(|(l, n, _)| *l == locale && *n == name)
+        .map(|(_, _, template)| template.trim_end().to_owned()))
+}
+
+/// The locales to look for a template in, in order.
+pub fn resolution_order(locale: &str) -> Vec<&str> {
+    if locale == FALLBACK_LOCALE {
+        vec![FALLBACK_LOCALE]
+    } else {
+        vec![locale, FALLBACK_LOCALE]
+    }
+}
+
+/// Find a template for a locale, falling back to English if the locale, or
+/// just that template, is missing.
+pub fn resolve(dir: &Path, locale: &str, name: &str) -> Result<String> {
+    for locale in resolution_order(locale) {
+        if let Some(template) = find(dir, locale, name)? {
+            return Ok(template);
+        }
+    }
+    Err(anyhow!("There's no {} template", name))
+}
+
+/// Whether there are any templates for a locale, bundled or in the template
+/// directory.
+pub fn has_locale(dir: &Path, locale: &str) -> bool {
+    BUNDLED.iter().any(|(l, _, _)| *l == locale) || dir.join(locale).is_dir()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        has_locale, is_locale_name, refresh, rejected, resolution_order, resolve, validate, BUNDLED,
+    };
+    use std::{
+        fs::{self, File},
+        path::Path,
+        time::{Duration, SystemTime},
+    };
+
+    fn touch(path: &Path, at: SystemTime) {
+        File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(at)
+            .unwrap();
+    }
+
+    #[test]
+    fn every_locale_is_complete() {
+        let names: Vec<&str> = BUNDLED
+            .iter()
+            .filter(|(l, _, _)| *l == "en")
+            .map(|(_, n, _)| *n)
+            .collect();
+        for (locale, _, _) in BUNDLED {
+            for name in &names {
+                assert!(
+                    BUNDLED.iter().any(|(l, n, _)| l == locale && n == name),
+                    "{} is missing {}",
+                    locale,
+                    name
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn resolves_bundled_templates() {
+        let tmp = tempfile::tempdir().unwrap();
+        assert_eq!(resolution_order("de"), vec!["de", "en"]);
+        assert_eq!(resolution_order("en"), vec!["en"]);
+        assert_eq!(
+            resolve(tmp.path(), "en", "missing_license").unwrap(),
+            "The linked GitHub repository does not contain a license."
+        );
+        assert_eq!(
+            resolve(tmp.path(), "de", "missing_license").unwrap(),
+            "Das verlinkte GitHub-Repository enthält keine Lizenz."
+        );
+        // an unknown locale is English
+        assert_eq!(
+            resolve(tmp.path(), "xx", "missing_license").unwrap(),
+            resolve(tmp.path(), "en", "missing_license").unwrap()
+        );
+        assert!(resolve(tmp.path(), "en", "nope").is_err());
+    }
+
+    #[test]
+    fn template_directory_comes_first() {
+        let tmp = tempfile::tempdir().unwrap();
+        // a partial locale, with only one of the templates
+        fs::create_dir_all(tmp.path().join("fr")).unwrap();
+        fs::write(
+            tmp.path().join("fr").join("missing_license.md"),
+            "Le dépôt GitHub lié ne contient pas de licence.\n",
+        )
+        .unwrap();
+        // and an override of a bundled one
+        fs::create_dir_all(tmp.path().join("de")).unwrap();
+        fs::write(tmp.path().join("de").join("more_info.md"), "Siehe {link}\n").unwrap();
+
+        assert!(has_locale(tmp.path(), "fr"));
+        assert!(has_locale(tmp.path(), "de"));
+        assert!(!has_locale(tmp.path(), "xx"));
+        assert_eq!(
+            resolve(tmp.path(), "fr", "missing_license").unwrap(),
+            "Le dépôt GitHub lié ne contient pas de licence."
+        );
+        assert_eq!(
+            resolve(tmp.path(), "fr", "footer").unwrap(),
+            resolve(tmp.path(), "en", "footer").unwrap()
+        );
+        assert_eq!(
+            resolve(tmp.path(), "de", "more_info").unwrap(),
+            "Siehe {link}"
+        );
+        assert!(resolve(tmp.path(), "de", "footer")
+            .unwrap()
+            .starts_with("^(Ich bin ein Bot."));
+    }
+
+    #[test]
+    fn locale_names() {
+        assert!(is_locale_name("de"));
+        assert!(is_locale_name("pt_BR"));
+        assert!(is_locale_name("zh-Hant"));
+        assert!(!is_locale_name(""));
+        assert!(!is_locale_name("../en"));
+        assert!(!is_locale_name("de/x"));
+    }
+
+    #[test]
+    fn bundled_templates_are_valid() {
+        for (locale, name, template) in BUNDLED {
+            assert!(validate(template).is_ok(), "{} {}", locale, name);
+        }
+        assert!(validate("{nope}").is_err());
+        assert!(validate("{keyword").is_err());
+        assert!(validate(&"x".repeat(10_001)).is_err());
+    }
+
+    #[test]
+    fn changed_templates_are_reloaded() {
+        let tmp = tempfile::tempdir().unwrap();
+        fs::create_dir_all(tmp.path().join("en")).unwrap();
+        let path = tmp.path().join("en").join("more_info.md");
+        let at = SystemTime::now() - Duration::from_secs(60);
+        fs::write(&path, "See {source}").unwrap();
+        touch(&path, at);
+        assert_eq!(
+            resolve(tmp.path(), "en", "more_info").unwrap(),
+            "See {source}"
+        );
+
+        // nothing is read again until the file is changed
+        fs::write(&path, "Read {source}").unwrap();
+        touch(&path, at);
+        refresh(tmp.path());
+        assert_eq!(
+            resolve(tmp.path(), "en", "more_info").unwrap(),
+            "See {source}"
+        );
+
+        touch(&path, at + Duration::from_secs(1));
+        refresh(tmp.path());
+        assert_eq!(
+            resolve(tmp.path(), "en", "more_info").unwrap(),
+            "Read {source}"
+        );
+
+        // and once it's gone, the bundled one is used
+        fs::remove_file(&path).unwrap();
+        refresh(tmp.path());
+        assert!(resolve(tmp.path(), "en", "more_info")
+            .unwrap()
+            .starts_with("Please read over this article"));
+    }
+
+    #[test]
+    fn invalid_templates_keep_the_previous_version() {
+        let tmp = tempfile::tempdir().unwrap();
+        fs::create_dir_all(tmp.path().join("en")).unwrap();
+        let footer = tmp.path().join("en").join("footer.md");
+        let at = SystemTime::now() - Duration::from_secs(60);
+        fs::write(&footer, "Reply {keyword} to opt out").unwrap();
+        touch(&footer, at);
+        assert_eq!(
+            resolve(tmp.path(), "en", "footer").unwrap(),
+            "Reply {keyword} to opt out"
+        );
+
+        fs::write(&footer, "Reply {keywrod} to opt out").unwrap();
+        touch(&footer, at + Duration::from_secs(1));
+        refresh(tmp.path());
+        assert_eq!(
+            resolve(tmp.path(), "en", "footer").unwrap(),
+            "Reply {keyword} to opt out"
+        );
+        let rejected = rejected(tmp.path());
+        assert_eq!(rejected.len(), 1);
+        assert_eq!(rejected[0].0, footer);
+        assert!(rejected[0].1.contains("{keywrod}"), "{}", rejected[0].1);
+
+        // fixing it clears the rejection
+        fs::write(&footer, "Reply {keyword} to stop").unwrap();
+        touch(&footer, at + Duration::from_secs(2));
+        refresh(tmp.path());
+        assert_eq!(
+            resolve(tmp.path(), "en", "footer").unwrap(),
+            "Reply {keyword} to stop"
+        );
+        assert!(super::rejected(tmp.path()).is_empty());
+
+        // one that was never valid falls back to the bundled template
+        let intro = tmp.path().join("en").join("missing_license.md");
+        fs::write(&intro, "x".repeat(10_001)).unwrap();
+        assert_eq!(
+            resolve(tmp.path(), "en", "missing_license").unwrap(),
+            "The linked GitHub repository does not contain a license."
+        );
+    }
+}
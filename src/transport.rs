@@ -0,0 +1,492 @@
+use reqwest::{Client, RequestBuilder, Response};
+use std::{
+    sync::Arc,
+    time::{Instant, SystemTime},
+};
+
+use crate::audit::{self, AuditLog};
+use crate::simulate::Simulator;
+#[cfg(any(test, feature = "cassettes"))]
+use {
+    log::warn,
+    serde::{Deserialize, Serialize},
+    serde_json::Value,
+    std::{collections::BTreeMap, fs, path::PathBuf, sync::Mutex},
+};
+
+/// Replaces secrets in recorded form bodies and JSON responses.
+#[cfg(any(test, feature = "cassettes"))]
+const SCRUBBED: &str = "[scrubbed]";
+/// Form fields and JSON keys whose values are never written to a cassette.
+#[cfg(any(test, feature = "cassettes"))]
+const SECRET_KEYS: &[&str] = &["password", "client_secret", "access_token", "refresh_token"];
+/// Response headers kept in a cassette; the rest are noise or cookies.
+#[cfg(any(test, feature = "cassettes"))]
+const KEPT_HEADERS: &[&str] = &[
+    "content-type",
+    "retry-after",
+    "x-ratelimit-remaining",
+    "x-ratelimit-reset",
+    "x-ratelimit-used",
+];
+
+/// How the bot's HTTP requests are carried out.
+#[derive(Clone, Debug, Default)]
+pub enum Transport {
+    /// Send requests over the network.
+    #[default]
+    Live,
+    /// Send requests over the network and save every exchange to a cassette.
+    #[cfg(any(test, feature = "cassettes"))]
+    Record(Arc<Mutex<Recorder>>),
+    /// Answer requests from a cassette, in order, without the network.
+    #[cfg(test)]
+    Replay(Arc<Mutex<Replayer>>),
+    /// Send requests with another transport, writing each to an audit log.
+    Audited(Arc<AuditLog>, Box<Transport>),
+    /// Answer requests from a simulation, without the network.
+    Simulated(Arc<Simulator>),
+}
+
+impl Transport {
+    /// Record every exchange to a cassette file at the given path.
+    #[cfg(any(test, feature = "cassettes"))]
+    pub fn record<P: Into<PathBuf>>(path: P) -> Self {
+        Transport::Record(Arc::new(Mutex::new(Recorder {
+            path: path.into(),
+            interactions: vec![],
+        })))
+    }
+
+    /// Replay the exchanges in a cassette's JSON.
+    #[cfg(test)]
+    pub fn replay(cassette: &str) -> Self {
+        let interactions: Vec<Interaction> =
+            serde_json::from_str(cassette).expect("Could not parse cassette");
+        Transport::Replay(Arc::new(Mutex::new(Replayer {
+            interactions: interactions.into_iter().collect(),
+        })))
+    }
+
+    /// Write every request sent with this transport to an audit log.
+    pub fn audited(self, audit: Arc<AuditLog>) -> Self {
+        match self {
+            // already audited, perhaps to another log
+            Transport::Audited(_, inner) => Transport::Audited(audit, inner),
+            transport => Transport::Audited(audit, Box::new(transport)),
+        }
+    }
+
+    /// The audit log requests are written to, if any.
+    pub fn audit(&self) -> Option<&Arc<AuditLog>> {
+        match self {
+            Transport::Audited(audit, _) => Some(audit),
+            _ => None,
+        }
+    }
+
+    /// Send a request built from `client`.
+    #[cfg_attr(not(any(test, feature = "cassettes")), allow(unused_variables))]
+    pub async fn send(
+        &self,
+        client: &Client,
+        request: RequestBuilder,
+    ) -> reqwest::Result<Response> {
+        match self {
+            Transport::Live => request.send().await,
+            #[cfg(any(test, feature = "cassettes"))]
+            Transport::Record(recorder) => {
+                let request = request.build()?;
+                let recorded = RecordedRequest::from_request(&request);
+                let resp = client.execute(request).await?;
+                let response = RecordedResponse::from_response(resp).await?;
+                recorder.lock().unwrap().push(Interaction {
+                    request: recorded,
+                    response: response.clone(),
+                });
+                Ok(response.into_response())
+            }
+            #[cfg(test)]
+            Transport::Replay(replayer) => {
+                let request = RecordedRequest::from_request(&request.build()?);
+                let response = replayer.lock().unwrap().next(&request);
+                Ok(response.into_response())
+            }
+            Transport::Audited(audit, inner) => {
+                let request = request.build()?;
+                let method = request.method().clone();
+                let url = request.url().clone();
+                let credentials = audit::credentials(request.headers());
+                let sent_at = SystemTime::now();
+                let started = Instant::now();
+                let request = RequestBuilder::from_parts(client.clone(), request);
+                let result = Box::pin(inner.send(client, request)).await;
+                let (status, headers, body) = match result {
+                    Ok(resp) => (resp.status(), resp.headers().clone(), resp.bytes().await),
+                    Err(e) => {
+                        let mut entry = audit.entry(
+                            sent_at,
+                            &method,
+                            &url,
+                            &credentials,
+                            None,
+                            &Default::default(),
+                            started.elapsed(),
+                            &[],
+                        );
+                        entry.error = Some(failure(&e).to_owned());
+                        audit.append(&entry);
+                        return Err(e);
+                    }
+                };
+                let mut entry = audit.entry(
+                    sent_at,
+                    &method,
+                    &url,
+                    &credentials,
+                    Some(status),
+                    &headers,
+                    started.elapsed(),
+                    body.as_deref().unwrap_or_default(),
+                );
+                let body = match body {
+                    Ok(body) => body,
+                    Err(e) => {
+                        entry.error = Some(failure(&e).to_owned());
+                        audit.append(&entry);
+                        return Err(e);
+                    }
+                };
+                audit.append(&entry);
+                // the body was read to hash it, so hand on a copy
+                let mut builder = http::Response::builder().status(status);
+                if let Some(all) = builder.headers_mut() {
+                    *all = headers;
+                }
+                Ok(builder.body(body).expect("Invalid audited response").into())
+            }
+            Transport::Simulated(simulator) => Ok(simulator.answer(&request.build()?)),
+        }
+    }
+
+    /// Number of cassette exchanges that haven't been replayed yet.
+    #[cfg(test)]
+    pub fn remaining(&self) -> usize {
+        match self {
+            Transport::Replay(replayer) => replayer.lock().unwrap().interactions.len(),
+            _ => 0,
+        }
+    }
+}
+
+/// What went wrong with a request, without the URL that reqwest's errors
+/// include, as it may hold credentials.
+fn failure(e: &reqwest::Error) -> &'static str {
+    if e.is_timeout() {
+        "timed out"
+    } else if e.is_connect() {
+        "could not connect"
+    } else if e.is_redirect() {
+        "too many redirects"
+    } else if e.is_body() || e.is_decode() {
+        "could not read the response"
+    } else {
+        "request failed"
+    }
+}
+
+/// One request and the response it got.
+#[cfg(any(test, feature = "cassettes"))]
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Interaction {
+    request: RecordedRequest,
+    response: RecordedResponse,
+}
+
+/// The parts of a request that a replayed request has to match.
+#[cfg(any(test, feature = "cassettes"))]
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+struct RecordedRequest {
+    method: String,
+    /// Path and query, without the host, so cassettes work against any endpoint.
+    path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    body: Option<String>,
+}
+
+#[cfg(any(test, feature = "cassettes"))]
+impl RecordedRequest {
+    fn from_request(request: &reqwest::Request) -> Self {
+        let url = request.url();
+        let path = match url.query() {
+            Some(q) => format!("{}?{}", url.path(), q),
+            None => url.path().to_owned(),
+        };
+        let body = request
+            .body()
+            .and_then(|b| b.as_bytes())
+            .map(|b| scrub_form(&String::from_utf8_lossy(b)));
+        Self {
+            method: request.method().to_string(),
+            path,
+            body,
+        }
+    }
+}
+
+#[cfg(any(test, feature = "cassettes"))]
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+struct RecordedResponse {
+    status: u16,
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+    body: String,
+}
+
+#[cfg(any(test, feature = "cassettes"))]
+impl RecordedResponse {
+    async fn from_response(resp: Response) -> reqwest::Result<Self> {
+        let status = resp.status().as_u16();
+        let headers = resp
+            .headers()
+            .iter()
+            .filter(|(name, _)| KEPT_HEADERS.contains(&name.as_str()))
+            .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_owned())))
+            .collect();
+        let body = scrub_json(&resp.text().await?);
+        Ok(Self {
+            status,
+            headers,
+            body,
+        })
+    }
+
+    fn into_response(self) -> Response {
+        let mut builder = http::Response::builder().status(self.status);
+        for (name, value) in &self.headers {
+            builder = builder.header(name.as_str(), value.as_str());
+        }
+        builder
+            .body(self.body)
+            .expect("Invalid recorded response")
+            .into()
+    }
+}
+
+/// Writes exchanges to a cassette file as they happen.
+#[cfg(any(test, feature = "cassettes"))]
+#[derive(Debug)]
+pub struct Recorder {
+    path: PathBuf,
+    interactions: Vec<Interaction>,
+}
+
+#[cfg(any(test, feature = "cassettes"))]
+impl Recorder {
+    fn push(&mut self, interaction: Interaction) {
+        self.interactions.push(interaction);
+        // the bot runs until stopped, so the file is rewritten after every exchange
+        let written = serde_json::to_string_pretty(&self.interactions)
+            .map_err(anyhow::Error::from)
+            .and_then(|data| fs::write(&self.path, data).map_err(anyhow::Error::from));
+        if let Err(e) = written {
+            warn!("Could not write cassette {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+/// Hands out a cassette's responses, checking each request against it.
+#[cfg(test)]
+#[derive(Debug)]
+pub struct Replayer {
+    interactions: std::collections::VecDeque<Interaction>,
+}
+
+#[cfg(test)]
+impl Replayer {
+    fn next(&mut self, request: &RecordedRequest) -> RecordedResponse {
+        let interaction = self
+            .interactions
+            .pop_front()
+            .unwrap_or_else(|| panic!("Cassette has no response left for {:?}", request));
+        assert_eq!(
+            &interaction.request, request,
+            "Request doesn't match the next one in the cassette"
+        );
+        interaction.response
+    }
+}
+
+/// Sort a form body's fields and blank out secrets.
+///
+/// Forms are built from maps, so field order isn't stable between runs.
+#[cfg(any(test, feature = "cassettes"))]
+fn scrub_form(body: &str) -> String {
+    let mut fields: Vec<String> = body
+        .split('&')
+        .map(|field| match field.split_once('=') {
+            Some((key, _)) if SECRET_KEYS.contains(&key) => format!("{}={}", key, SCRUBBED),
+            _ => field.to_owned(),
+        })
+        .collect();
+    fields.sort();
+    fields.join("&")
+}
+
+/// Blank out secrets in a JSON body, leaving anything else untouched.
+#[cfg(any(test, feature = "cassettes"))]
+fn scrub_json(body: &str) -> String {
+    let mut value: Value = match serde_json::from_str(body) {
+        Ok(v) => v,
+        Err(_) => return body.to_owned(),
+    };
+    let mut scrubbed = false;
+    if let Some(object) = value.as_object_mut() {
+        for key in SECRET_KEYS {
+            if let Some(v) = object.get_mut(*key) {
+                *v = Value::String(SCRUBBED.to_owned());
+                scrubbed = true;
+            }
+        }
+    }
+    if scrubbed {
+        value.to_string()
+    } else {
+        body.to_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{scrub_form, scrub_json, Transport};
+    use crate::audit::AuditLog;
+    use mockito::mock;
+    use reqwest::Client;
+    use std::sync::Arc;
+
+    #[test]
+    fn scrubs_form_secrets_and_sorts_fields() {
+        assert_eq!(
+            scrub_form("username=bot&password=hunter2&grant_type=password"),
+            "grant_type=password&password=[scrubbed]&username=bot"
+        );
+    }
+
+    #[test]
+    fn scrubs_json_secrets_only() {
+        let scrubbed = scrub_json(r#"{"access_token":"abc","expires_in":3600}"#);
+        let value: serde_json::Value = serde_json::from_str(&scrubbed).unwrap();
+        assert_eq!(value["access_token"], "[scrubbed]");
+        assert_eq!(value["expires_in"], 3600);
+
+        let untouched = r#"{"kind": "Listing"}"#;
+        assert_eq!(scrub_json(untouched), untouched);
+        assert_eq!(scrub_json("not json"), "not json");
+    }
+
+    #[tokio::test]
+    async fn recorded_cassette_replays() {
+        let _token = mock("POST", "/transport/token")
+            .with_header("content-type", "application/json")
+            .with_header("set-cookie", "session=secret")
+            .with_body(r#"{"access_token":"live-token"}"#)
+            .create();
+        let path = std::env::temp_dir().join(format!("cfl-cassette-{}.json", std::process::id()));
+        let client = Client::new();
+        let url = format!("{}/transport/token", mockito::server_url());
+
+        let recording = Transport::record(&path);
+        let resp = recording
+            .send(&client, client.post(&url).form(&[("password", "hunter2")]))
+            .await
+            .unwrap();
+        assert_eq!(
+            resp.text().await.unwrap(),
+            r#"{"access_token":"[scrubbed]"}"#
+        );
+
+        let cassette = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(!cassette.contains("hunter2"), "{}", cassette);
+        assert!(!cassette.contains("live-token"), "{}", cassette);
+        assert!(!cassette.contains("session=secret"), "{}", cassette);
+
+        let replay = Transport::replay(&cassette);
+        let resp = replay
+            .send(
+                &client,
+                client
+                    .post("http://replayed.invalid/transport/token")
+                    .form(&[("password", "other")]),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            resp.headers()["content-type"],
+            "application/json".to_owned()
+        );
+        assert_eq!(replay.remaining(), 0);
+    }
+
+    #[tokio::test]
+    async fn audited_requests_are_written_down() {
+        let _repo = mock("GET", "/audited/repo?access_token=ghp_leaked&page=2")
+            .with_header("x-ratelimit-remaining", "4999")
+            .with_header("set-cookie", "session=secret")
+            .with_body(r#"{"name":"repo"}"#)
+            .create();
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("audit.jsonl");
+        let transport = Transport::Live.audited(Arc::new(AuditLog::new(path.clone(), vec![])));
+        let client = Client::new();
+        let url = format!(
+            "{}/audited/repo?access_token=ghp_leaked&page=2",
+            mockito::server_url()
+        );
+        let resp = transport
+            .send(&client, client.get(&url).bearer_auth("reddit-token"))
+            .await
+            .unwrap();
+        assert_eq!(resp.headers()["x-ratelimit-remaining"], "4999");
+        assert_eq!(resp.text().await.unwrap(), r#"{"name":"repo"}"#);
+
+        let unreachable = transport
+            .send(
+                &client,
+                client.get("http://127.0.0.1:1/audited?password=hunter2"),
+            )
+            .await;
+        assert!(unreachable.is_err());
+
+        let written = std::fs::read_to_string(&path).unwrap();
+        for secret in &["ghp_leaked", "reddit-token", "session=secret", "hunter2"] {
+            assert!(!written.contains(secret), "{}", written);
+        }
+        let lines: Vec<serde_json::Value> = written
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["method"], "GET");
+        assert!(lines[0]["url"]
+            .as_str()
+            .unwrap()
+            .ends_with("/audited/repo?access_token=%5Bredacted%5D&page=2"));
+        assert_eq!(lines[0]["status"], 200);
+        assert_eq!(lines[0]["headers"]["x-ratelimit-remaining"], "4999");
+        assert_eq!(lines[0]["body_bytes"], 15);
+        assert!(lines[0]["body_hash"].is_string());
+        assert_eq!(lines[1]["status"], serde_json::Value::Null);
+        assert_eq!(lines[1]["error"], "could not connect");
+    }
+
+    #[tokio::test]
+    #[should_panic(expected = "doesn't match")]
+    async fn replay_rejects_unexpected_request() {
+        let replay = Transport::replay(
+            r#"[{"request":{"method":"GET","path":"/a"},"response":{"status":200,"body":""}}]"#,
+        );
+        let client = Client::new();
+        let _ = replay.send(&client, client.get("http://x.invalid/b")).await;
+    }
+}
@@ -1,33 +1,366 @@
+use anyhow::{anyhow, Result};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr};
+
+/// A GitHub repository, named as it was linked.
+///
+/// Serialized as `org` and `repo`, as in the state files.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct GhRepo {
+    #[serde(rename = "org")]
+    pub owner: String,
+    #[serde(rename = "repo")]
+    pub name: String,
+}
+
+impl GhRepo {
+    pub fn new(owner: &str, name: &str) -> Self {
+        GhRepo {
+            owner: owner.to_owned(),
+            name: name.to_owned(),
+        }
+    }
+
+    /// Key identifying the repository regardless of the casing it was
+    /// linked with.
+    pub fn canonical_key(&self) -> String {
+        canonical_repo_key(&self.owner, &self.name)
+    }
+
+    /// The repository's endpoint in the GitHub API at `base`.
+    pub fn api_url(&self, base: &str) -> String {
+        format!("{}/repos/{}/{}", base, self.owner, self.name)
+    }
+
+    /// The repository's page on GitHub.
+    pub fn html_url(&self) -> String {
+        format!("https://github.com/{}/{}", self.owner, self.name)
+    }
+}
+
+impl fmt::Display for GhRepo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.owner, self.name)
+    }
+}
+
+impl FromStr for GhRepo {
+    type Err = anyhow::Error;
+
+    /// Parse a repository given as `owner/name` or as a GitHub URL.
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        if s.contains("github.com/") {
+            return extract_gh_info(s).ok_or_else(|| anyhow!("'{}' isn't a GitHub repository", s));
+        }
+        match s.split('/').collect::<Vec<&str>>()[..] {
+            [owner, name] if !owner.is_empty() && !name.is_empty() => Ok(GhRepo::new(owner, name)),
+            _ => Err(anyhow!("'{}' isn't a repository as owner/name", s)),
+        }
+    }
+}
+
 /// Attempt to pull a org name and repo name from a GitHub URL.
-pub fn extract_gh_info(url: &str) -> Option<(String, String)> {
-    let index = match url.find("github.com/") {
-        Some(i) => i + 11,
-        None => return None,
-    };
-    let rest: String = url.chars().skip(index).collect();
+pub fn extract_gh_info(url: &str) -> Option<GhRepo> {
+    // a byte index, so the text before it may be anything
+    let index = url.find("github.com/")? + "github.com/".len();
+    let rest = &url[index..];
 
-    let mut parts = rest.split('/');
-    let org = match parts.next() {
-        Some(s) => s,
-        None => return None,
-    };
-    let repo = match parts.next() {
-        Some(s) => s,
-        None => return None,
+    // the path ends at a query string or fragment
+    let path = rest.split(['?', '#']).next()?;
+    let mut parts = path.split('/');
+    let org = parts.next()?;
+    let repo = parts.next()?;
+    if org.is_empty() || repo.is_empty() {
+        return None;
+    }
+    Some(GhRepo::new(org, repo))
+}
+
+/// Hosts of Reddit's website, which some clients link through as
+/// `/media?url=<link>`.
+const REDDIT_HOSTS: &[&str] = &[
+    "reddit.com",
+    "www.reddit.com",
+    "old.reddit.com",
+    "new.reddit.com",
+    "np.reddit.com",
+];
+/// Most redirects a link is unwrapped from, as one may wrap another.
+const MAX_REDIRECT_DEPTH: usize = 3;
+
+/// Where a Reddit redirect goes: the percent-decoded `url` parameter of a
+/// `reddit.com/media` link or an `out.reddit.com` click-tracking one.
+fn redirect_target(url: &str) -> Option<String> {
+    let parsed = Url::parse(url).ok()?;
+    let host = parsed.host_str()?;
+    let wrapped = host == "out.reddit.com"
+        || (REDDIT_HOSTS.contains(&host) && parsed.path().trim_end_matches('/') == "/media");
+    if !wrapped {
+        return None;
+    }
+    parsed
+        .query_pairs()
+        .find(|(key, _)| key == "url")
+        .map(|(_, value)| value.into_owned())
+}
+
+/// A post's URL with the Reddit redirects it may be wrapped in taken off,
+/// up to `MAX_REDIRECT_DEPTH` of them. A redirect that doesn't say where it
+/// goes is left as it is.
+pub fn unwrap_redirects(url: &str) -> String {
+    let mut url = url.to_owned();
+    for _ in 0..MAX_REDIRECT_DEPTH {
+        match redirect_target(&url) {
+            Some(target) => url = target,
+            None => break,
+        }
+    }
+    url
+}
+
+/// Most refs a link is checked at, since each takes a request.
+const MAX_REF_CANDIDATES: usize = 3;
+
+/// A repository linked at a branch, tag or commit, as in a `/tree/<ref>` or
+/// `/blob/<ref>/<path>` link.
+///
+/// Refs may contain slashes, so where the ref ends and the path in the
+/// repository starts is only known to GitHub.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GhRepoRef {
+    pub repo: GhRepo,
+    /// The segments of the link after `tree/` or `blob/`, leaving out the
+    /// file a blob link ends in.
+    segments: Vec<String>,
+}
+
+impl GhRepoRef {
+    /// The refs the link may be at, longest first, so that a link to
+    /// `tree/feature/foo` is tried at `feature/foo` before `feature`.
+    pub fn candidates(&self) -> Vec<String> {
+        (1..=self.segments.len())
+            .rev()
+            .take(MAX_REF_CANDIDATES)
+            .map(|n| self.segments[..n].join("/"))
+            .collect()
+    }
+}
+
+/// Pull a repository and the ref it's linked at from a GitHub URL, if it
+/// links a tree or a blob.
+pub fn extract_gh_ref(url: &str) -> Option<GhRepoRef> {
+    let repo = extract_gh_info(url)?;
+    let rest = &url[url.find("github.com/")? + 11..];
+    let path = rest.split(['?', '#']).next()?;
+    let mut parts = path.split('/').skip(2);
+    let blob = match parts.next() {
+        Some("tree") => false,
+        Some("blob") => true,
+        _ => return None,
     };
-    Some((org.to_owned(), repo.to_owned()))
+    let mut segments: Vec<String> = parts.filter(|s| !s.is_empty()).map(str::to_owned).collect();
+    if blob {
+        segments.pop();
+    }
+    if segments.is_empty() {
+        return None;
+    }
+    Some(GhRepoRef { repo, segments })
+}
+
+/// Bounds on the links read from a post, so that pathological ones, like
+/// URLs with kilobytes of tracking parameters, cost little.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LinkLimits {
+    /// Longest URL read as a link, in bytes; 0 reads any.
+    pub max_url_len: usize,
+    /// Most repositories taken from one self post; 0 takes any number.
+    pub max_links: usize,
+}
+
+impl Default for LinkLimits {
+    fn default() -> Self {
+        Self {
+            max_url_len: 2048,
+            max_links: 20,
+        }
+    }
+}
+
+impl LinkLimits {
+    /// Whether a URL is too long to be read as a link.
+    pub fn too_long(&self, url: &str) -> bool {
+        self.max_url_len > 0 && url.len() > self.max_url_len
+    }
+}
+
+/// A URL cut to at most `max_len` bytes, marked with an ellipsis if it was,
+/// for recording URLs that are too long to keep whole.
+pub fn truncate_url(url: &str, max_len: usize) -> String {
+    if url.len() <= max_len {
+        return url.to_owned();
+    }
+    let mut end = max_len;
+    while !url.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}…", &url[..end])
+}
+
+/// Find every GitHub repository linked in a block of text, such as a self post.
+///
+/// Repositories are returned once each, in the order they're first linked,
+/// leaving out links longer than the limits allow and any past the most
+/// that are taken. The text is scanned once, however it's made up.
+pub fn extract_gh_links(text: &str, limits: &LinkLimits) -> Vec<GhRepo> {
+    let mut found: Vec<GhRepo> = vec![];
+    // where the last link ended, so that text in it isn't scanned again
+    let mut scanned = 0;
+    for (start, _) in text.match_indices("github.com/") {
+        if start < scanned {
+            continue;
+        }
+        if limits.max_links > 0 && found.len() >= limits.max_links {
+            break;
+        }
+        let link = &text[start..];
+        let end = link
+            .find(|c: char| c.is_whitespace() || "()[]<>\"'|*`".contains(c))
+            .unwrap_or(link.len());
+        scanned = start + end;
+        // punctuation ending a sentence isn't part of the link
+        let link = link[..end].trim_end_matches(|c| ".,;:!?".contains(c));
+        if limits.too_long(link) {
+            continue;
+        }
+        if let Some(repo) = extract_gh_info(link) {
+            let key = repo.canonical_key();
+            if !found.iter().any(|r| r.canonical_key() == key) {
+                found.push(repo);
+            }
+        }
+    }
+    found
+}
+
+/// Key identifying a repository regardless of the URL casing it was linked with.
+pub fn canonical_repo_key(org: &str, repo: &str) -> String {
+    format!("{}/{}", fold_case(org), fold_case(repo))
+}
+
+/// Text in a single case, for comparing without case.
+///
+/// Beyond lowercasing, letters with more than one lowercase form fold to
+/// one of them, like `ß` to `ss` and a final `ς` to `σ`, each character
+/// on its own whatever the text around it.
+pub fn fold_case(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars().flat_map(char::to_lowercase) {
+        match c {
+            'ß' => out.push_str("ss"),
+            'ς' => out.push('σ'),
+            'ſ' => out.push('s'),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Key identifying a Reddit account however its name was written: without
+/// case, and with or without a `u/`, `/u/` or `/user/` prefix.
+pub fn author_key(name: &str) -> String {
+    let name = name.trim().trim_start_matches('/');
+    let unprefixed = ["u/", "user/"].iter().find_map(|prefix| {
+        name.get(..prefix.len())
+            .filter(|p| p.eq_ignore_ascii_case(prefix))
+            .map(|_| &name[prefix.len()..])
+    });
+    fold_case(unprefixed.unwrap_or(name))
+}
+
+/// Phrases that say a post's code is under a license after all, making
+/// something like "all rights reserved" just a copyright notice.
+const LICENSE_GRANTS: &[&str] = &["licensed under", "released under", "available under"];
+
+/// Lowercased words of some text, ignoring punctuation and spacing.
+fn words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric() && c != '\'')
+        .filter(|w| !w.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+fn contains_phrase(words: &[String], phrase: &str) -> bool {
+    let phrase = self::words(phrase);
+    !phrase.is_empty() && words.windows(phrase.len()).any(|w| w == phrase.as_slice())
+}
+
+/// Find the first of the phrases in some text, matching whole words and
+/// ignoring case and spacing.
+pub fn find_phrase<'a>(text: &str, phrases: &'a [String]) -> Option<&'a str> {
+    let words = words(text);
+    phrases
+        .iter()
+        .find(|p| contains_phrase(&words, p))
+        .map(String::as_str)
+}
+
+/// Find the first of the phrases saying that a post's code is unlicensed on
+/// purpose in the text of the post.
+///
+/// Phrases only match whole words, ignoring case and spacing, and not at all
+/// if the text also says what license the code is under.
+pub fn opt_out_phrase<'a>(text: &str, phrases: &'a [String]) -> Option<&'a str> {
+    let words = words(text);
+    if LICENSE_GRANTS.iter().any(|g| contains_phrase(&words, g)) {
+        return None;
+    }
+    phrases
+        .iter()
+        .find(|p| contains_phrase(&words, p))
+        .map(String::as_str)
+}
+
+/// Lay rows out in columns under a header, for printing to a terminal.
+pub fn format_table(header: &[&str], rows: &[Vec<String>]) -> String {
+    let header: Vec<String> = header.iter().map(|h| h.to_string()).collect();
+    let mut widths = vec![0; header.len()];
+    for row in rows.iter().chain(Some(&header)) {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let mut out = String::new();
+    for row in Some(&header).into_iter().chain(rows) {
+        let cells: Vec<String> = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, &width)| format!("{:<width$}", cell, width = width))
+            .collect();
+        out.push_str(cells.join("  ").trim_end());
+        out.push('\n');
+    }
+    out
 }
 
 #[cfg(test)]
 mod tests {
-    use super::extract_gh_info;
+    use super::{
+        author_key, canonical_repo_key, extract_gh_info, extract_gh_links, extract_gh_ref,
+        fold_case, opt_out_phrase, truncate_url, unwrap_redirects, GhRepo, LinkLimits,
+    };
+    use proptest::prelude::*;
+    use reqwest::Url;
+    use std::time::{Duration, Instant};
 
     #[test]
     fn test_extract_gh_info_valid() {
         let url = "https://github.com/Celeo/check_for_license/actions";
-        let (org, repo) = extract_gh_info(url).unwrap();
-        assert_eq!(org, "Celeo");
-        assert_eq!(repo, "check_for_license");
+        let repo = extract_gh_info(url).unwrap();
+        assert_eq!(repo.owner, "Celeo");
+        assert_eq!(repo.name, "check_for_license");
     }
 
     #[test]
@@ -36,4 +369,337 @@ mod tests {
         let data = extract_gh_info(url);
         assert_eq!(data, None);
     }
+
+    #[test]
+    fn test_extract_gh_info_query_and_fragment() {
+        for url in &[
+            "https://github.com/Celeo/check_for_license?tab=readme",
+            "https://github.com/Celeo/check_for_license#readme",
+            "https://github.com/Celeo/check_for_license/?x=1",
+        ] {
+            assert_eq!(
+                extract_gh_info(url),
+                Some(GhRepo::new("Celeo", "check_for_license")),
+                "{}",
+                url
+            );
+        }
+        assert_eq!(extract_gh_info("https://github.com/Celeo?tab=repos"), None);
+        assert_eq!(extract_gh_info("https://github.com//repo"), None);
+    }
+
+    #[test]
+    fn test_extract_gh_links() {
+        let text = "I made [two](https://github.com/Celeo/one) tools: \
+                    https://github.com/Celeo/two, and (github.com/celeo/ONE/tree/main).\n\
+                    Not a repo: https://github.com/Celeo ünïcode <https://github.com/a/b>";
+        let links: Vec<String> = extract_gh_links(text, &LinkLimits::default())
+            .into_iter()
+            .map(|repo| repo.to_string())
+            .collect();
+        assert_eq!(links, vec!["Celeo/one", "Celeo/two", "a/b"]);
+        assert!(extract_gh_links("no links here", &LinkLimits::default()).is_empty());
+    }
+
+    #[test]
+    fn limits_links_taken_from_text() {
+        let limits = LinkLimits {
+            max_url_len: 64,
+            max_links: 2,
+        };
+        let long = format!("https://github.com/a/long?utm={}", "x".repeat(64));
+        let text = format!(
+            "{} https://github.com/a/one https://github.com/a/two https://github.com/a/three",
+            long
+        );
+        assert_eq!(
+            extract_gh_links(&text, &limits),
+            vec![GhRepo::new("a", "one"), GhRepo::new("a", "two")]
+        );
+        let unlimited = LinkLimits {
+            max_url_len: 0,
+            max_links: 0,
+        };
+        assert_eq!(extract_gh_links(&text, &unlimited).len(), 4);
+        assert!(limits.too_long(&long));
+        assert!(!unlimited.too_long(&long));
+    }
+
+    #[test]
+    fn truncates_urls_on_char_boundaries() {
+        assert_eq!(
+            truncate_url("https://github.com/a/b", 64),
+            "https://github.com/a/b"
+        );
+        assert_eq!(truncate_url("https://github.com/a/b", 8), "https://…");
+        // 'é' is two bytes, so it can't be cut in half
+        assert_eq!(truncate_url("https://é", 9), "https://…");
+        assert_eq!(
+            truncate_url(&"x".repeat(5000), 2048).len(),
+            2048 + '…'.len_utf8()
+        );
+    }
+
+    #[test]
+    fn pathological_text_is_scanned_quickly() {
+        let limits = LinkLimits::default();
+        let inputs = [
+            // one link that never ends, made of nothing but link starts
+            "github.com/".repeat(200_000),
+            // a data URI pasted by mistake, after a link
+            format!(
+                "https://github.com/a/b?x=data:image/png;base64,{}",
+                "QUJD".repeat(500_000)
+            ),
+            // thousands of distinct links
+            (0..100_000)
+                .map(|i| format!("https://github.com/org/repo{} ", i))
+                .collect(),
+            // unbalanced brackets and punctuation with no links in them
+            "([<\"'.,;:!?".repeat(200_000),
+        ];
+        let started = Instant::now();
+        let found: Vec<usize> = inputs
+            .iter()
+            .map(|text| extract_gh_links(text, &limits).len())
+            .collect();
+        assert!(
+            started.elapsed() < Duration::from_secs(2),
+            "took {:?}",
+            started.elapsed()
+        );
+        assert_eq!(found, vec![0, 0, 20, 0]);
+    }
+
+    #[test]
+    fn extracts_refs() {
+        let candidates = |url: &str| extract_gh_ref(url).map(|r| r.candidates());
+        let refs = |refs: &[&str]| Some(refs.iter().map(|r| r.to_string()).collect::<Vec<_>>());
+        assert_eq!(
+            candidates("https://github.com/org/repo/tree/dev-branch"),
+            refs(&["dev-branch"])
+        );
+        assert_eq!(
+            candidates("https://github.com/org/repo/tree/feature/foo?tab=readme"),
+            refs(&["feature/foo", "feature"])
+        );
+        // the file a blob link ends in is never part of the ref
+        assert_eq!(
+            candidates("github.com/org/repo/blob/feature/foo/README.md#usage"),
+            refs(&["feature/foo", "feature"])
+        );
+        assert_eq!(
+            candidates("https://github.com/org/repo/blob/v1.0/LICENSE"),
+            refs(&["v1.0"])
+        );
+        // a tree link may go into a directory, and only so many refs are tried
+        assert_eq!(
+            candidates("https://github.com/org/repo/tree/main/src/bin/tool/"),
+            refs(&["main/src/bin/tool", "main/src/bin", "main/src"])
+        );
+        let link = extract_gh_ref("https://github.com/Org/Repo/tree/0a1b2c3").unwrap();
+        assert_eq!(link.repo, GhRepo::new("Org", "Repo"));
+
+        for url in &[
+            "https://github.com/org/repo",
+            "https://github.com/org/repo/tree",
+            "https://github.com/org/repo/tree/",
+            "https://github.com/org/repo/blob/README.md",
+            "https://github.com/org/repo/issues/1",
+            "https://github.com/org/repo?path=tree/main",
+            "https://github.com/org",
+        ] {
+            assert_eq!(extract_gh_ref(url), None, "{}", url);
+        }
+    }
+
+    #[test]
+    fn test_canonical_repo_key() {
+        assert_eq!(
+            canonical_repo_key("Celeo", "Check_For_License"),
+            "celeo/check_for_license"
+        );
+    }
+
+    #[test]
+    fn reads_links_after_non_ascii_text() {
+        let url = "Schau → ünïcode 🎉 https://github.com/Org/Проект";
+        assert_eq!(extract_gh_info(url), Some(GhRepo::new("Org", "Проект")));
+        let text = format!("{} and more", url);
+        assert_eq!(
+            extract_gh_links(&text, &LinkLimits::default()),
+            vec![GhRepo::new("Org", "Проект")]
+        );
+        assert_eq!(
+            extract_gh_info("🎉https://github.com/🎉/🎉"),
+            Some(GhRepo::new("🎉", "🎉"))
+        );
+    }
+
+    #[test]
+    fn unwraps_reddit_redirects() {
+        let repo = "https://github.com/someone/project";
+        let media = "https://www.reddit.com/media?url=https%3A%2F%2Fgithub.com%2Fsomeone%2Fproject";
+        let out = "https://out.reddit.com/t3_abc?url=https%3A%2F%2Fgithub.com%2Fsomeone%2Fproject&token=x&app_name=web";
+        assert_eq!(unwrap_redirects(media), repo);
+        assert_eq!(unwrap_redirects(out), repo);
+        assert_eq!(unwrap_redirects(repo), repo);
+        let wrap = |url: &str| {
+            Url::parse_with_params("https://out.reddit.com/t3_abc", &[("url", url)])
+                .unwrap()
+                .to_string()
+        };
+        // one wrapped in another
+        assert_eq!(unwrap_redirects(&wrap(media)), repo);
+        assert_eq!(unwrap_redirects(&wrap(&wrap(repo))), repo);
+        // unwrapping stops after a few
+        let deep = wrap(&wrap(&wrap(&wrap(repo))));
+        assert_eq!(unwrap_redirects(&deep), wrap(repo));
+        // a redirect that doesn't say where it goes, or other Reddit links
+        for url in [
+            "https://www.reddit.com/media?link=https%3A%2F%2Fgithub.com%2Fa%2Fb",
+            "https://out.reddit.com/t3_abc",
+            "https://www.reddit.com/r/rust/comments/abc/?url=https%3A%2F%2Fgithub.com%2Fa%2Fb",
+            "https://evil.example/media?url=https%3A%2F%2Fgithub.com%2Fa%2Fb",
+            "not a url",
+        ] {
+            assert_eq!(unwrap_redirects(url), url);
+        }
+    }
+
+    #[test]
+    fn folds_case_beyond_ascii() {
+        assert_eq!(fold_case("Straße"), fold_case("STRASSE"));
+        assert_eq!(fold_case("ΣΟΦΟΣ"), fold_case("σοφος"));
+        assert_eq!(fold_case("Проект"), "проект");
+        assert_eq!(fold_case("🎉 Rust"), "🎉 rust");
+        assert_eq!(
+            canonical_repo_key("Вася", "Straße"),
+            canonical_repo_key("ВАСЯ", "strasse")
+        );
+    }
+
+    #[test]
+    fn author_keys_ignore_prefixes_and_case() {
+        for name in &["Вася", "/u/Вася", "U/вася", "user/ВАСЯ", " /USER/васЯ "]
+        {
+            assert_eq!(author_key(name), "вася", "{:?}", name);
+        }
+        assert_eq!(author_key("u"), "u");
+        assert_eq!(author_key("ü/name"), "ü/name");
+    }
+
+    #[test]
+    fn gh_repo_helpers() {
+        let repo = GhRepo::new("Celeo", "Check_For_License");
+        assert_eq!(repo.to_string(), "Celeo/Check_For_License");
+        assert_eq!(repo.canonical_key(), "celeo/check_for_license");
+        assert_eq!(
+            repo.api_url("https://api.github.com"),
+            "https://api.github.com/repos/Celeo/Check_For_License"
+        );
+        assert_eq!(
+            repo.html_url(),
+            "https://github.com/Celeo/Check_For_License"
+        );
+        // serialized like the state files always have been
+        let json = serde_json::to_value(&repo).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({"org": "Celeo", "repo": "Check_For_License"})
+        );
+        assert_eq!(serde_json::from_value::<GhRepo>(json).unwrap(), repo);
+    }
+
+    #[test]
+    fn parses_gh_repos() {
+        let expected = GhRepo::new("Celeo", "check_for_license");
+        for text in &[
+            "Celeo/check_for_license",
+            " Celeo/check_for_license\n",
+            "https://github.com/Celeo/check_for_license",
+            "github.com/Celeo/check_for_license/tree/master?x=1",
+        ] {
+            assert_eq!(text.parse::<GhRepo>().unwrap(), expected, "{:?}", text);
+        }
+        for text in &[
+            "",
+            "Celeo",
+            "/check_for_license",
+            "Celeo/",
+            "Celeo/check_for_license/tree",
+            "https://github.com/Celeo",
+        ] {
+            assert!(text.parse::<GhRepo>().is_err(), "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn test_opt_out_phrase() {
+        let phrases: Vec<String> = [
+            "all rights reserved",
+            "intentionally unlicensed",
+            "not open source",
+        ]
+        .iter()
+        .map(|p| p.to_string())
+        .collect();
+        let cases = [
+            (
+                "Source available, ALL RIGHTS\n  RESERVED.",
+                Some("all rights reserved"),
+            ),
+            (
+                "It's intentionally  unlicensed for now",
+                Some("intentionally unlicensed"),
+            ),
+            ("My game (not open-source, sorry)", Some("not open source")),
+            // near misses
+            (
+                "Licensed under MIT, all rights reserved by contributors",
+                None,
+            ),
+            ("Released under the GPL. All rights reserved.", None),
+            ("all rights reservedly yours", None),
+            ("not opened source", None),
+            ("An open source tool", None),
+            ("", None),
+        ];
+        for (text, expected) in &cases {
+            assert_eq!(opt_out_phrase(text, &phrases), *expected, "{}", text);
+        }
+        assert_eq!(opt_out_phrase("all rights reserved", &[]), None);
+        assert_eq!(opt_out_phrase("anything", &["  ".to_owned()]), None);
+    }
+
+    proptest! {
+        #[test]
+        fn extraction_round_trips(
+            scheme in prop::sample::select(vec!["https://", "http://", "https://www.", ""]),
+            org in "[A-Za-z0-9][A-Za-z0-9-]{0,38}",
+            repo in "[A-Za-z0-9._-]{1,100}",
+            suffix in prop::sample::select(vec![
+                "", "/", "/tree/master", "/blob/master/README.md", "?tab=readme", "#readme", "/?a=b",
+            ]),
+        ) {
+            let url = format!("{}github.com/{}/{}{}", scheme, org, repo, suffix);
+            prop_assert_eq!(extract_gh_info(&url), Some(GhRepo { owner: org, name: repo }));
+        }
+
+        #[test]
+        fn extraction_never_panics(url in any::<String>()) {
+            let _ = extract_gh_info(&url);
+        }
+
+        #[test]
+        fn extracted_segments_are_clean(rest in "\\PC*") {
+            let url = format!("https://github.com/{}", rest);
+            if let Some(repo) = extract_gh_info(&url) {
+                for segment in &[repo.owner, repo.name] {
+                    prop_assert!(!segment.is_empty());
+                    prop_assert!(!segment.contains(['/', '?', '#']));
+                }
+            }
+        }
+    }
 }
@@ -0,0 +1,88 @@
+use serde_json::Value;
+
+/// Whether the bot's activity can be seen by other users.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Visibility {
+    /// The account and its recent comment are publicly visible.
+    Visible,
+    /// The account's profile can't be seen anonymously, usually a shadowban.
+    AccountHidden,
+    /// The given comment is missing or removed when viewed anonymously.
+    CommentHidden(String),
+}
+
+/// Search a comment thread's JSON for a visible comment with the given fullname.
+///
+/// The thread is the two-listing array Reddit returns for `{permalink}.json`:
+/// the post, then its comment tree.
+pub fn thread_shows_comment(thread: &Value, fullname: &str) -> bool {
+    match thread.as_array() {
+        Some(listings) => listings.iter().any(|l| listing_shows_comment(l, fullname)),
+        None => listing_shows_comment(thread, fullname),
+    }
+}
+
+fn listing_shows_comment(listing: &Value, fullname: &str) -> bool {
+    let children = match listing["data"]["children"].as_array() {
+        Some(c) => c,
+        None => return false,
+    };
+    children.iter().any(|child| {
+        let data = &child["data"];
+        if data["name"].as_str() == Some(fullname) {
+            return data["body"].as_str() != Some("[removed]");
+        }
+        // replies is "" rather than a listing when there are none
+        listing_shows_comment(&data["replies"], fullname)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::thread_shows_comment;
+    use serde_json::json;
+
+    fn thread(comments: serde_json::Value) -> serde_json::Value {
+        json!([
+            {"kind": "Listing", "data": {"children": [{"kind": "t3", "data": {"name": "t3_p"}}]}},
+            {"kind": "Listing", "data": {"children": comments}}
+        ])
+    }
+
+    #[test]
+    fn finds_top_level_comment() {
+        let t = thread(json!([
+            {"kind": "t1", "data": {"name": "t1_other", "body": "hi", "replies": ""}},
+            {"kind": "t1", "data": {"name": "t1_ours", "body": "hello", "replies": ""}}
+        ]));
+        assert!(thread_shows_comment(&t, "t1_ours"));
+    }
+
+    #[test]
+    fn finds_nested_comment() {
+        let t = thread(json!([
+            {"kind": "t1", "data": {"name": "t1_parent", "body": "hi", "replies": {
+                "kind": "Listing", "data": {"children": [
+                    {"kind": "t1", "data": {"name": "t1_ours", "body": "hello", "replies": ""}}
+                ]}
+            }}}
+        ]));
+        assert!(thread_shows_comment(&t, "t1_ours"));
+    }
+
+    #[test]
+    fn missing_comment_is_hidden() {
+        let t = thread(json!([
+            {"kind": "t1", "data": {"name": "t1_other", "body": "hi", "replies": ""}}
+        ]));
+        assert!(!thread_shows_comment(&t, "t1_ours"));
+    }
+
+    #[test]
+    fn removed_comment_is_hidden() {
+        let t = thread(json!([
+            {"kind": "t1", "data": {"name": "t1_ours", "body": "[removed]", "replies": ""}}
+        ]));
+        assert!(!thread_shows_comment(&t, "t1_ours"));
+    }
+}